@@ -0,0 +1,7 @@
+// defmt's linker script, for the log frames' interned strings. Only the
+// firmware binary links against it, the host tests don't.
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_LOG_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+    }
+}
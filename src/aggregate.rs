@@ -0,0 +1,302 @@
+use core::fmt;
+
+use heapless::HistoryBuffer;
+
+use crate::{fixed::Fixed, math, sensor::STANDARD_GRAVITY, Reading, Thresholds};
+
+// Buckets start on the minute of the monotonic uptime, whatever the sample
+// rate, and an hour of them is kept for the `trend` command
+pub const MINUTE_MS: u64 = 60_000;
+pub const TREND_MINUTES: usize = 60;
+
+// The acceleration magnitude in m/s^2, each gyroscope axis in rad/s and the
+// temperature in ºC
+pub const TREND_CHANNELS: [&str; 5] = ["acc", "gx", "gy", "gz", "temp"];
+const CHANNELS: usize = TREND_CHANNELS.len();
+
+const DECIMALS: u8 = 2;
+const WIDTH: usize = 7;
+
+// What the crossings are counted against, from the warning levels: the
+// acceleration past gravity by the mechanical one, a gyroscope axis past
+// the rotational one either way, the temperature past its ceiling
+pub fn trend_limits(thresholds: &Thresholds) -> [f32; CHANNELS] {
+    let gyro = thresholds.rotational.warning;
+    [
+        STANDARD_GRAVITY + thresholds.mechanical.warning,
+        gyro,
+        gyro,
+        gyro,
+        thresholds.temperature_ceiling.warning,
+    ]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelStats {
+    pub min: f32,
+    pub mean: f32,
+    pub max: f32,
+    // Times the channel went over its limit, from under it
+    pub crossings: u16,
+}
+
+impl ChannelStats {
+    const EMPTY: ChannelStats = ChannelStats {
+        min: f32::INFINITY,
+        mean: 0.0,
+        max: f32::NEG_INFINITY,
+        crossings: 0,
+    };
+
+    // The mean moves by each sample's share, no sum to grow out of an
+    // f32's precision over a minute of fast samples
+    fn push(&mut self, value: f32, count: u32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.mean += (value - self.mean) / count as f32;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bucket {
+    // Minutes of uptime
+    pub minute: u32,
+    pub samples: u32,
+    // Indexed like TREND_CHANNELS
+    pub channels: [ChannelStats; CHANNELS],
+}
+
+impl Bucket {
+    fn new(minute: u32) -> Self {
+        Self {
+            minute,
+            samples: 0,
+            channels: [ChannelStats::EMPTY; CHANNELS],
+        }
+    }
+}
+
+// Folds every status sample into the bucket of its minute
+pub struct Aggregator<const N: usize = TREND_MINUTES> {
+    current: Option<Bucket>,
+    history: HistoryBuffer<Bucket, N>,
+    // Each channel over its limit at the last sample, carried across
+    // buckets so a crossing is only counted once
+    over: [bool; CHANNELS],
+}
+
+impl<const N: usize> Aggregator<N> {
+    pub const fn new() -> Self {
+        Self {
+            current: None,
+            history: HistoryBuffer::new(),
+            over: [false; CHANNELS],
+        }
+    }
+
+    // Returns the last minute's bucket when the sample starts a new one.
+    // A minute without samples gets no bucket.
+    pub fn push(&mut self, reading: &Reading, limits: &[f32; CHANNELS]) -> Option<Bucket> {
+        let minute = (reading.t_ms / MINUTE_MS) as u32;
+        let closed = match self.current {
+            Some(bucket) if bucket.minute != minute => {
+                self.history.write(bucket);
+                self.current = None;
+                Some(bucket)
+            }
+            _ => None,
+        };
+
+        let [ax, ay, az] = reading.acc;
+        let magnitude = math::sqrt(ax * ax + ay * ay + az * az);
+        let [gx, gy, gz] = reading.gyro;
+        let values = [magnitude, gx, gy, gz, reading.temp];
+
+        let bucket = self.current.get_or_insert(Bucket::new(minute));
+        bucket.samples += 1;
+        for (i, value) in values.into_iter().enumerate() {
+            let channel = &mut bucket.channels[i];
+            channel.push(value, bucket.samples);
+            let over = math::abs(value) > limits[i];
+            if over && !self.over[i] {
+                channel.crossings = channel.crossings.saturating_add(1);
+            }
+            self.over[i] = over;
+        }
+        closed
+    }
+
+    // The minute still being filled
+    pub fn current(&self) -> Option<&Bucket> {
+        self.current.as_ref()
+    }
+
+    // The closed buckets, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &Bucket> {
+        self.history.oldest_ordered()
+    }
+}
+
+impl<const N: usize> Default for Aggregator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The minute's uptime as hh:mm
+struct Minute(u32);
+
+impl fmt::Display for Minute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+// n/a for NaN, padded to the format's width
+struct Value(f32);
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Fixed::new(self.0, DECIMALS) {
+            Some(value) => value.fmt(f),
+            None => f.pad("n/a"),
+        }
+    }
+}
+
+// One line a minute:
+// `[00:12] 120 samples, acc 9.78/9.81/9.85 x0, gx .. , temp 25.10/25.12/25.20 x0`
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} samples", Minute(self.minute), self.samples)?;
+        for (name, channel) in TREND_CHANNELS.iter().zip(&self.channels) {
+            write!(
+                f,
+                ", {} {}/{}/{} x{}",
+                name,
+                Value(channel.min),
+                Value(channel.mean),
+                Value(channel.max),
+                channel.crossings
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// The `trend` table's column names, over TrendRow
+pub struct TrendHeader;
+
+impl fmt::Display for TrendHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5} {:>7}", "time", "samples")?;
+        for name in TREND_CHANNELS {
+            write!(
+                f,
+                " {:>width$} {:>width$} {:>width$} {:>4}",
+                "min",
+                "mean",
+                name,
+                "x",
+                width = WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// A bucket as a fixed-width row: min, mean, max and crossings of each
+// channel, the channel's name over its max
+pub struct TrendRow<'a>(pub &'a Bucket);
+
+impl fmt::Display for TrendRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bucket = self.0;
+        write!(f, "{:>5} {:>7}", Minute(bucket.minute), bucket.samples)?;
+        for channel in &bucket.channels {
+            write!(
+                f,
+                " {:>width$} {:>width$} {:>width$} {:>4}",
+                Value(channel.min),
+                Value(channel.mean),
+                Value(channel.max),
+                channel.crossings,
+                width = WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: [f32; CHANNELS] = [12.0, 1.0, 1.0, 1.0, 60.0];
+
+    fn reading(t_ms: u64, az: f32, gx: f32) -> Reading {
+        Reading::new([0.0, 0.0, az], [gx, 0.0, 0.0], 25.0, t_ms)
+    }
+
+    #[test]
+    fn buckets_roll_over_on_the_minute() {
+        let mut aggregator: Aggregator<3> = Aggregator::new();
+        // A fast rate, then a slow one: the buckets follow the clock
+        for t_ms in (0..MINUTE_MS).step_by(100) {
+            assert_eq!(aggregator.push(&reading(t_ms, 9.8, 0.0), &LIMITS), None);
+        }
+        let closed = aggregator.push(&reading(MINUTE_MS, 9.8, 0.0), &LIMITS);
+        assert_eq!(
+            closed.map(|bucket| (bucket.minute, bucket.samples)),
+            Some((0, 600))
+        );
+        for t_ms in (MINUTE_MS + 5_000..2 * MINUTE_MS).step_by(5_000) {
+            assert_eq!(aggregator.push(&reading(t_ms, 9.8, 0.0), &LIMITS), None);
+        }
+
+        // Nothing in minute 2, so no bucket for it
+        let closed = aggregator.push(&reading(3 * MINUTE_MS, 9.8, 0.0), &LIMITS);
+        assert_eq!(
+            closed.map(|bucket| (bucket.minute, bucket.samples)),
+            Some((1, 12))
+        );
+        assert_eq!(aggregator.current().map(|bucket| bucket.minute), Some(3));
+        for minute in 4..=6 {
+            aggregator.push(&reading(minute * MINUTE_MS, 9.8, 0.0), &LIMITS);
+        }
+        let minutes: Vec<u32> = aggregator.history().map(|bucket| bucket.minute).collect();
+        assert_eq!(minutes, [3, 4, 5]);
+    }
+
+    #[test]
+    fn channels_keep_min_mean_max_and_crossings() {
+        let mut aggregator: Aggregator = Aggregator::new();
+        // The gyroscope over its limit twice, once each way, and held
+        // there across the minute
+        let samples = [
+            (9.0, 0.0),
+            (13.0, 1.5),
+            (9.0, 1.2),
+            (11.0, 0.5),
+            (10.0, -2.0),
+        ];
+        for (i, (az, gx)) in samples.into_iter().enumerate() {
+            aggregator.push(&reading(i as u64 * 1_000, az, gx), &LIMITS);
+        }
+        let bucket = *aggregator.current().unwrap();
+        let acc = bucket.channels[0];
+        assert_eq!((acc.min, acc.max, acc.crossings), (9.0, 13.0, 1));
+        assert!((acc.mean - 10.4).abs() < 1e-5);
+        let gx = bucket.channels[1];
+        assert_eq!((gx.min, gx.max, gx.crossings), (-2.0, 1.5, 2));
+        assert_eq!(bucket.channels[4].mean, 25.0);
+
+        let closed = aggregator.push(&reading(MINUTE_MS + 1_000, 9.0, -1.5), &LIMITS);
+        assert_eq!(closed, Some(bucket));
+        assert_eq!(aggregator.current().unwrap().channels[1].crossings, 0);
+        assert_eq!(
+            bucket.to_string().split(", ").nth(1),
+            Some("acc 9.00/10.40/13.00 x1")
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::fault::FAULT_BLINK_MS;
+use crate::{Alert, Fault, Limit, Severity};
+
+// Tones played by a passive buzzer, in Hz.
+// The LEDC only has four high-speed timers, so related limits share a tone
+// and tell themselves apart by their pattern.
+pub const MECHANICAL_TONE_HZ: u32 = 2_000;
+pub const ROTATIONAL_TONE_HZ: u32 = 3_000;
+pub const TEMPERATURE_TONE_HZ: u32 = 4_000;
+pub const VIBRATION_TONE_HZ: u32 = 2_500;
+pub const TONES_HZ: [u32; 4] = [
+    MECHANICAL_TONE_HZ,
+    ROTATIONAL_TONE_HZ,
+    TEMPERATURE_TONE_HZ,
+    VIBRATION_TONE_HZ,
+];
+
+// Anything that can make noise for an alert.
+// The limit is passed along so tone-capable buzzers can pick the pitch.
+pub trait Buzzer {
+    type Error;
+
+    fn start(&mut self, limit: &Limit) -> Result<(), Self::Error>;
+    fn stop(&mut self) -> Result<(), Self::Error>;
+}
+
+// Active buzzer on a plain push-pull output, it only knows on and off
+pub struct PinBuzzer<P> {
+    pin: P,
+}
+
+impl<P: OutputPin> PinBuzzer<P> {
+    pub fn new(pin: P) -> Self {
+        Self { pin }
+    }
+
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+impl<P: OutputPin> Buzzer for PinBuzzer<P> {
+    type Error = P::Error;
+
+    fn start(&mut self, _limit: &Limit) -> Result<(), Self::Error> {
+        self.pin.set_high()
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low()
+    }
+}
+
+#[derive(Debug)]
+pub enum AlarmError<BE, LE> {
+    Buzzer(BE),
+    Led(LE),
+}
+
+// Buzzer and LED pair driven whenever a limit is exceeded.
+// Any embedded-hal output pin works for the LED, so it can be moved to another GPIO.
+//
+// Patterns don't block: `tick()` is called every loop iteration and toggles
+// the outputs based on the elapsed time, so sampling carries on while the
+// buzzer plays.
+pub struct Alarm<B, L> {
+    buzzer: B,
+    led: L,
+    pattern: Option<Pattern>,
+    indicator: bool,
+    heartbeat: bool,
+    // An output failed to go off at the end of a pattern, retried on every
+    // tick until it does
+    unsettled: bool,
+}
+
+// Length of the reminder chirp, in ms
+pub const CHIRP_MS: u32 = 30;
+
+#[derive(Clone, Copy, Debug)]
+struct Pattern {
+    // None for reminder chirps and faults, which any alert interrupts
+    alert: Option<Alert>,
+    limit: Limit,
+    use_buzzer: bool,
+    half_ms: u32,
+    // Each buzz is an "on" and an "off" half
+    halves_left: u8,
+    on: bool,
+    since_ms: u32,
+}
+
+type AlarmResult<B, L> = Result<(), AlarmError<<B as Buzzer>::Error, <L as OutputPin>::Error>>;
+
+impl<B, L> Alarm<B, L>
+where
+    B: Buzzer,
+    L: OutputPin,
+{
+    pub fn new(buzzer: B, led: L) -> Self {
+        Self {
+            buzzer,
+            led,
+            pattern: None,
+            indicator: false,
+            heartbeat: false,
+            unsettled: false,
+        }
+    }
+
+    // Starts the pattern for an alert.
+    // If another pattern is in progress, the new one only takes over when it
+    // has a higher priority.
+    pub fn start(&mut self, alert: Alert, now_ms: u32) -> AlarmResult<B, L> {
+        if let Some(Pattern {
+            alert: Some(current),
+            ..
+        }) = &self.pattern
+        {
+            if !alert.outranks(current) {
+                return Ok(());
+            }
+        }
+
+        // Warnings only blink the LED, critical alerts sound the buzzer along with it
+        self.play(Pattern {
+            alert: Some(alert),
+            limit: alert.limit,
+            use_buzzer: alert.severity == Severity::Critical,
+            half_ms: alarm_time(&alert.limit),
+            halves_left: buzzes(&alert.limit) * 2,
+            on: false,
+            since_ms: now_ms,
+        })
+    }
+
+    // Short single buzz, used as a reminder while an alarm is latched.
+    // Doesn't interrupt a running pattern.
+    pub fn chirp(&mut self, limit: Limit, now_ms: u32) -> AlarmResult<B, L> {
+        self.chirps(limit, 1, now_ms)
+    }
+
+    // `count` short buzzes in a row, same rules as `chirp()`
+    pub fn chirps(&mut self, limit: Limit, count: u8, now_ms: u32) -> AlarmResult<B, L> {
+        if self.pattern.is_some() {
+            return Ok(());
+        }
+
+        self.play(Pattern {
+            alert: None,
+            limit,
+            use_buzzer: true,
+            half_ms: CHIRP_MS,
+            halves_left: count * 2,
+            on: false,
+            since_ms: now_ms,
+        })
+    }
+
+    // Blink pattern of a fault, the caller repeats it while the fault lasts.
+    // Replaces any running pattern.
+    pub fn fault(&mut self, fault: Fault, now_ms: u32) -> AlarmResult<B, L> {
+        self.silence()?;
+
+        self.play(Pattern {
+            alert: None,
+            limit: Limit::Mechanical,
+            use_buzzer: fault.beeps(),
+            half_ms: FAULT_BLINK_MS,
+            halves_left: fault.blinks() * 2,
+            on: false,
+            since_ms: now_ms,
+        })
+    }
+
+    fn play(&mut self, pattern: Pattern) -> AlarmResult<B, L> {
+        self.pattern = Some(pattern);
+        self.switch(true)
+    }
+
+    // Advances the running pattern, if any
+    pub fn tick(&mut self, now_ms: u32) -> AlarmResult<B, L> {
+        let Some(mut pattern) = self.pattern else {
+            return if self.unsettled { self.rest() } else { Ok(()) };
+        };
+
+        if now_ms.wrapping_sub(pattern.since_ms) < pattern.half_ms {
+            return Ok(());
+        }
+
+        pattern.halves_left -= 1;
+        pattern.since_ms = now_ms;
+
+        if pattern.halves_left == 0 {
+            self.pattern = None;
+            return self.rest();
+        }
+
+        self.pattern = Some(pattern);
+        self.switch(!pattern.on)
+    }
+
+    // Stops the running pattern, the LED goes back to the indicator state
+    pub fn silence(&mut self) -> AlarmResult<B, L> {
+        if self.pattern.take().is_some() || self.unsettled {
+            self.rest()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.pattern.is_some()
+    }
+
+    // Steady LED while a condition is latched.
+    // A running pattern owns the LED until it finishes.
+    pub fn set_indicator(&mut self, on: bool) -> AlarmResult<B, L> {
+        self.indicator = on;
+
+        if self.pattern.is_none() {
+            self.show_indicator()
+        } else {
+            Ok(())
+        }
+    }
+
+    // Heartbeat blink, shown only while there's no pattern and no indicator
+    pub fn set_heartbeat(&mut self, on: bool) -> AlarmResult<B, L> {
+        self.heartbeat = on;
+
+        if self.pattern.is_none() {
+            self.show_indicator()
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn release(self) -> (B, L) {
+        (self.buzzer, self.led)
+    }
+
+    fn switch(&mut self, on: bool) -> AlarmResult<B, L> {
+        let Some(pattern) = &mut self.pattern else {
+            return Ok(());
+        };
+        pattern.on = on;
+
+        if on {
+            if pattern.use_buzzer {
+                self.buzzer
+                    .start(&pattern.limit)
+                    .map_err(AlarmError::Buzzer)?;
+            }
+            self.led.set_high().map_err(AlarmError::Led)
+        } else {
+            // Both go off even if the first one fails
+            let buzzer = self.buzzer.stop().map_err(AlarmError::Buzzer);
+            buzzer.and(self.led.set_low().map_err(AlarmError::Led))
+        }
+    }
+
+    // Buzzer off and the LED back to the indicator, each tried whether or
+    // not the other failed
+    fn rest(&mut self) -> AlarmResult<B, L> {
+        let buzzer = self.buzzer.stop().map_err(AlarmError::Buzzer);
+        let led = self.show_indicator();
+        self.unsettled = buzzer.is_err() || led.is_err();
+        buzzer.and(led)
+    }
+
+    fn show_indicator(&mut self) -> AlarmResult<B, L> {
+        if self.indicator || self.heartbeat {
+            self.led.set_high().map_err(AlarmError::Led)
+        } else {
+            self.led.set_low().map_err(AlarmError::Led)
+        }
+    }
+}
+
+// Number of buzzes in each limit's pattern
+pub fn buzzes(limit: &Limit) -> u8 {
+    match limit {
+        Limit::SensorDetached => 10,
+        Limit::Mechanical => 3,
+        Limit::Rotational => 6,
+        Limit::Jerk => 2,
+        Limit::Temperature => 9,
+        Limit::Vibration => 4,
+        Limit::Velocity => 7,
+        Limit::Bearing => 8,
+        Limit::Orientation => 5,
+        Limit::Anomaly => 1,
+        Limit::Probe => 11,
+        Limit::Knock => 12,
+        Limit::Electrical => 13,
+    }
+}
+
+// Index of a limit's tone in TONES_HZ
+pub fn tone(limit: &Limit) -> usize {
+    match limit {
+        Limit::SensorDetached | Limit::Mechanical | Limit::Jerk | Limit::Knock => 0,
+        Limit::Rotational | Limit::Orientation | Limit::Electrical => 1,
+        Limit::Temperature | Limit::Probe => 2,
+        Limit::Vibration | Limit::Velocity | Limit::Bearing | Limit::Anomaly => 3,
+    }
+}
+
+pub fn tone_hz(limit: &Limit) -> u32 {
+    TONES_HZ[tone(limit)]
+}
+
+// Duration of each half of a buzz, in ms
+pub fn alarm_time(limit: &Limit) -> u32 {
+    match limit {
+        Limit::SensorDetached => 40,
+        Limit::Mechanical => 100,
+        Limit::Rotational => 200,
+        Limit::Jerk => 60,
+        Limit::Temperature => 50,
+        Limit::Vibration => 150,
+        Limit::Velocity => 120,
+        Limit::Bearing => 90,
+        Limit::Orientation => 300,
+        Limit::Anomaly => 400,
+        Limit::Probe => 50,
+        Limit::Knock => 60,
+        Limit::Electrical => 250,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    const TICK_MS: u32 = 10;
+
+    #[derive(Debug, PartialEq)]
+    struct Stuck;
+
+    // An output pin the test keeps a handle on
+    #[derive(Clone, Default)]
+    struct Pin {
+        high: Rc<Cell<bool>>,
+        // Writes still to fail, leaving the level as it was
+        failures: Rc<Cell<u8>>,
+    }
+
+    impl Pin {
+        fn write(&mut self, high: bool) -> Result<(), Stuck> {
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(Stuck);
+            }
+            self.high.set(high);
+            Ok(())
+        }
+    }
+
+    impl OutputPin for Pin {
+        type Error = Stuck;
+
+        fn set_low(&mut self) -> Result<(), Stuck> {
+            self.write(false)
+        }
+
+        fn set_high(&mut self) -> Result<(), Stuck> {
+            self.write(true)
+        }
+    }
+
+    type TestAlarm = Alarm<PinBuzzer<Pin>, Pin>;
+
+    fn outputs() -> (TestAlarm, Pin, Pin) {
+        let (buzzer, led) = (Pin::default(), Pin::default());
+        let alarm = Alarm::new(PinBuzzer::new(buzzer.clone()), led.clone());
+        (alarm, buzzer, led)
+    }
+
+    // When each output was on, in ms from `start_ms`, ticking every TICK_MS
+    // for `duration_ms`
+    #[derive(Debug, Default, PartialEq)]
+    struct Trace {
+        buzzer: Vec<(u32, u32)>,
+        led: Vec<(u32, u32)>,
+    }
+
+    fn trace(alarm: &mut TestAlarm, pins: (&Pin, &Pin), start_ms: u32, duration_ms: u32) -> Trace {
+        let mut trace = Trace::default();
+        let mut since = [None, None];
+        for elapsed in (0..=duration_ms).step_by(TICK_MS as usize) {
+            if elapsed > 0 {
+                alarm.tick(start_ms.wrapping_add(elapsed)).unwrap();
+            }
+            let outputs = [(pins.0, &mut trace.buzzer), (pins.1, &mut trace.led)];
+            for ((pin, periods), since) in outputs.into_iter().zip(&mut since) {
+                match (pin.high.get(), *since) {
+                    (true, None) => *since = Some(elapsed),
+                    (false, Some(on)) => {
+                        periods.push((on, elapsed));
+                        *since = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        trace
+    }
+
+    fn alert(limit: Limit, severity: Severity) -> Alert {
+        Alert { limit, severity }
+    }
+
+    #[test]
+    fn critical_patterns_buzz_each_limit_its_count_and_length() {
+        let mechanical = [(0, 100), (200, 300), (400, 500)];
+        let temperature: Vec<(u32, u32)> = (0..9).map(|i| (i * 100, i * 100 + 50)).collect();
+
+        // Across the wrap of the millisecond clock, too
+        for start_ms in [0, u32::MAX - 250] {
+            let (mut alarm, buzzer, led) = outputs();
+            alarm
+                .start(alert(Limit::Mechanical, Severity::Critical), start_ms)
+                .unwrap();
+            let played = trace(&mut alarm, (&buzzer, &led), start_ms, 1_000);
+            assert_eq!(played.buzzer, mechanical);
+            assert_eq!(played.led, mechanical);
+            assert!(!alarm.is_playing());
+        }
+
+        let (mut alarm, buzzer, led) = outputs();
+        alarm
+            .start(alert(Limit::Temperature, Severity::Critical), 0)
+            .unwrap();
+        let played = trace(&mut alarm, (&buzzer, &led), 0, 1_000);
+        assert_eq!(played.buzzer, temperature);
+
+        for limit in Limit::ALL {
+            let (mut alarm, buzzer, led) = outputs();
+            alarm.start(alert(limit, Severity::Critical), 0).unwrap();
+            let played = trace(&mut alarm, (&buzzer, &led), 0, 10_000);
+            let half = alarm_time(&limit);
+            let expected: Vec<(u32, u32)> = (0..buzzes(&limit) as u32)
+                .map(|i| (2 * i * half, (2 * i + 1) * half))
+                .collect();
+            assert_eq!(played.buzzer, expected, "{:?}", limit);
+            assert_eq!(played.led, expected, "{:?}", limit);
+        }
+    }
+
+    #[test]
+    fn warnings_only_blink_and_hand_the_led_back() {
+        let (mut alarm, buzzer, led) = outputs();
+        alarm
+            .start(alert(Limit::Mechanical, Severity::Warning), 0)
+            .unwrap();
+        let played = trace(&mut alarm, (&buzzer, &led), 0, 1_000);
+        assert_eq!(played.buzzer, []);
+        assert_eq!(played.led, [(0, 100), (200, 300), (400, 500)]);
+
+        // The indicator comes back on after the last off half
+        alarm.set_indicator(true).unwrap();
+        alarm
+            .start(alert(Limit::Mechanical, Severity::Warning), 2_000)
+            .unwrap();
+        let played = trace(&mut alarm, (&buzzer, &led), 2_000, 1_000);
+        assert_eq!(played.led, [(0, 100), (200, 300), (400, 500)]);
+        assert!(led.high.get());
+
+        alarm.set_indicator(false).unwrap();
+        assert!(!led.high.get());
+    }
+
+    #[test]
+    fn critical_takes_over_and_chirps_wait() {
+        let (mut alarm, buzzer, led) = outputs();
+        alarm
+            .start(alert(Limit::Orientation, Severity::Warning), 0)
+            .unwrap();
+        assert_eq!(trace(&mut alarm, (&buzzer, &led), 0, 100).buzzer, []);
+
+        alarm.chirp(Limit::Mechanical, 100).unwrap();
+        alarm
+            .start(alert(Limit::Jerk, Severity::Critical), 100)
+            .unwrap();
+        let played = trace(&mut alarm, (&buzzer, &led), 100, 1_000);
+        assert_eq!(played.buzzer, [(0, 60), (120, 180)]);
+
+        // Nothing playing, so the chirp goes
+        alarm.chirp(Limit::Mechanical, 2_000).unwrap();
+        let played = trace(&mut alarm, (&buzzer, &led), 2_000, 100);
+        assert_eq!(played.buzzer, [(0, CHIRP_MS)]);
+    }
+
+    #[test]
+    fn outputs_end_low_past_a_failed_write() {
+        let (mut alarm, buzzer, led) = outputs();
+        alarm
+            .start(alert(Limit::Mechanical, Severity::Critical), 0)
+            .unwrap();
+        trace(&mut alarm, (&buzzer, &led), 0, 490);
+
+        // The buzzer fails to stop on the last buzz and again at the end:
+        // the LED goes off regardless, the buzzer on the next tick
+        buzzer.failures.set(2);
+        assert!(matches!(alarm.tick(500), Err(AlarmError::Buzzer(Stuck))));
+        assert!(buzzer.high.get());
+        assert!(!led.high.get());
+        assert!(matches!(alarm.tick(600), Err(AlarmError::Buzzer(Stuck))));
+        assert!(!alarm.is_playing());
+        alarm.tick(610).unwrap();
+        assert!(!buzzer.high.get() && !led.high.get());
+
+        // Same for the LED halfway through, then silenced
+        alarm
+            .start(alert(Limit::Temperature, Severity::Critical), 1_000)
+            .unwrap();
+        led.failures.set(1);
+        assert!(matches!(alarm.tick(1_050), Err(AlarmError::Led(Stuck))));
+        assert!(!buzzer.high.get());
+        alarm.tick(1_100).unwrap();
+        led.failures.set(1);
+        assert!(alarm.silence().is_err());
+        assert!(!buzzer.high.get());
+        alarm.tick(1_110).unwrap();
+        assert!(!buzzer.high.get() && !led.high.get());
+    }
+}
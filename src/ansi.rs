@@ -0,0 +1,109 @@
+use core::fmt;
+
+use crate::Levels;
+
+// Share of the warning level where a value turns from green to yellow
+pub const NEAR_WARNING: f32 = 0.8;
+
+const RESET: &str = "\x1b[0m";
+
+// How a value goes out on an ANSI terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    // Well below the warning level
+    Green,
+    // Getting close to the warning level, or past it
+    Yellow,
+    // At the critical level
+    Red,
+    // Bold red, for the alarm banners
+    Alarm,
+}
+
+impl Style {
+    // Traffic light of a value against its levels. NaN stays plain, it's
+    // neither fine nor alarming.
+    pub fn shade(value: f32, levels: &Levels) -> Self {
+        if value.is_nan() {
+            Style::Plain
+        } else if value >= levels.critical {
+            Style::Red
+        } else if value >= levels.warning * NEAR_WARNING {
+            Style::Yellow
+        } else {
+            Style::Green
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Green => "\x1b[32m",
+            Style::Yellow => "\x1b[33m",
+            Style::Red => "\x1b[31m",
+            Style::Alarm => "\x1b[1;31m",
+        }
+    }
+}
+
+// A value between the escapes of its style, or as it is without `color`.
+// The width, precision and alignment of the format go to the value, inside
+// the escapes, so colored columns line up like plain ones.
+pub struct Paint<T> {
+    pub value: T,
+    pub style: Style,
+    pub color: bool,
+}
+
+impl<T> Paint<T> {
+    pub fn new(value: T, style: Style, color: bool) -> Self {
+        Self {
+            value,
+            style,
+            color,
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Paint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let painted = self.color && self.style != Style::Plain;
+        if painted {
+            f.write_str(self.style.code())?;
+        }
+        self.value.fmt(f)?;
+        if painted {
+            f.write_str(RESET)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shades_by_the_levels() {
+        let levels = Levels::new(1.0, 2.0);
+        assert_eq!(Style::shade(0.5, &levels), Style::Green);
+        assert_eq!(Style::shade(0.8, &levels), Style::Yellow);
+        assert_eq!(Style::shade(1.5, &levels), Style::Yellow);
+        assert_eq!(Style::shade(2.0, &levels), Style::Red);
+        assert_eq!(Style::shade(f32::NAN, &levels), Style::Plain);
+    }
+
+    #[test]
+    fn padding_goes_inside_the_escapes() {
+        let paint = Paint::new(-1.23456, Style::Red, true);
+        assert_eq!(format!("{:>8.2}", paint), "\x1b[31m   -1.23\x1b[0m");
+
+        let paint = Paint::new(-1.23456, Style::Red, false);
+        assert_eq!(format!("{:>8.2}", paint), "   -1.23");
+        assert_eq!(
+            format!("{:<6}|", Paint::new("ok", Style::Plain, true)),
+            "ok    |"
+        );
+    }
+}
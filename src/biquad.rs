@@ -0,0 +1,118 @@
+use core::f32::consts::PI;
+
+use crate::math;
+
+// Normalized second-order filter coefficients, a0 = 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl Coefficients {
+    // From the Audio EQ Cookbook, unity gain at the centre frequency
+    pub fn band_pass(center_hz: f32, q: f32, rate_hz: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(center_hz, q, rate_hz);
+
+        Self::normalized([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    // Unity gain at DC
+    pub fn low_pass(cutoff_hz: f32, q: f32, rate_hz: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, q, rate_hz);
+        let b = (1.0 - cos) / 2.0;
+
+        Self::normalized([b, 2.0 * b, b], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    fn prewarp(frequency_hz: f32, q: f32, rate_hz: f32) -> (f32, f32) {
+        let w0 = 2.0 * PI * frequency_hz / rate_hz;
+        (math::cos(w0), math::sin(w0) / (2.0 * q))
+    }
+
+    fn normalized(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+        }
+    }
+}
+
+// Biquad in transposed direct form II
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Biquad {
+    coefficients: Coefficients,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub const fn new(coefficients: Coefficients) -> Self {
+        Self {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, x: f32) -> f32 {
+        let Coefficients { b0, b1, b2, a1, a2 } = self.coefficients;
+
+        let y = b0 * x + self.z1;
+        self.z1 = b1 * x - a1 * y + self.z2;
+        self.z2 = b2 * x - a2 * y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE_HZ: f32 = 1_000.0;
+
+    // Output over input RMS once the filter settled on a sine
+    fn gain(coefficients: Coefficients, frequency_hz: f32) -> f32 {
+        let mut filter = Biquad::new(coefficients);
+
+        let squares = (0..2_000).fold(0.0, |sum, n| {
+            let x = math::sin(2.0 * PI * frequency_hz * n as f32 / RATE_HZ);
+            let y = filter.push(x);
+            if n < 1_000 {
+                sum
+            } else {
+                sum + y * y
+            }
+        });
+        math::sqrt(squares / 1_000.0) * core::f32::consts::SQRT_2
+    }
+
+    #[test]
+    fn band_pass_keeps_the_centre_only() {
+        let band = Coefficients::band_pass(300.0, 2.0, RATE_HZ);
+
+        assert!((gain(band, 300.0) - 1.0).abs() < 0.01);
+        assert!(gain(band, 30.0) < 0.05);
+        assert!(gain(band, 480.0) < 0.2);
+    }
+
+    #[test]
+    fn low_pass_keeps_dc() {
+        let mut filter = Biquad::new(Coefficients::low_pass(50.0, 0.707, RATE_HZ));
+
+        let settled = (0..500).fold(0.0, |_, _| filter.push(2.0));
+        assert!((settled - 2.0).abs() < 1e-3);
+        assert!(gain(Coefficients::low_pass(50.0, 0.707, RATE_HZ), 400.0) < 0.02);
+    }
+}
@@ -0,0 +1,38 @@
+use embedded_hal::adc::OneShot;
+use hal::adc::{ADC, ADC1};
+
+use super::time;
+
+// ADC1 and the analog inputs on it, GPIO32 to GPIO39. ADC2 is off limits,
+// the WiFi radio takes it when it's on.
+pub type Adc1<'d> = ADC<'d, ADC1>;
+
+// Up to `samples` one-shot conversions of `pin`, started `interval_us`
+// apart, or back to back at 0, for no longer than `budget_us`
+pub fn convert<A, P>(
+    adc: &mut A,
+    pin: &mut P,
+    samples: u32,
+    interval_us: u32,
+    budget_us: u32,
+    mut push: impl FnMut(u16),
+) where
+    A: OneShot<ADC1, u16, P>,
+{
+    let start_us = time::awake_us();
+    let elapsed_us = || time::awake_us().saturating_sub(start_us);
+    for sample in 0..samples {
+        let due_us = sample as u64 * interval_us as u64;
+        while elapsed_us() < due_us {}
+        // WouldBlock until the conversion is done
+        let counts = loop {
+            if elapsed_us() >= budget_us as u64 {
+                return;
+            }
+            if let Ok(counts) = adc.read(pin) {
+                break counts;
+            }
+        };
+        push(counts);
+    }
+}
@@ -0,0 +1,19 @@
+use core::panic::PanicInfo;
+
+// The `backtrace-panic` handler, esp-backtrace's report on the UART and,
+// with `log-defmt`, over RTT too, where the probe-rs host is. The return
+// addresses are for `xtensa-esp32-elf-addr2line`. Then it halts, for
+// whoever is attached to read.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    esp_println::println!("\n\n!! {}\n\nBacktrace:\n", info);
+    #[cfg(feature = "log-defmt")]
+    defmt::error!("!! {}", defmt::Display2Format(info));
+
+    for address in esp_backtrace::arch::backtrace().into_iter().flatten() {
+        esp_println::println!("0x{:x}", address);
+        #[cfg(feature = "log-defmt")]
+        defmt::error!("backtrace: {=usize:#x}", address);
+    }
+    loop {}
+}
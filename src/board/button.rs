@@ -0,0 +1,47 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use critical_section::Mutex;
+use hal::{
+    gpio::{Event, Gpio0, Input, PullUp},
+    interrupt,
+    peripherals::Interrupt,
+    prelude::*,
+};
+
+// Acknowledge/mute button on GPIO0 (the BOOT button on most dev boards).
+// The falling edge is caught by an interrupt, so a press is never missed
+// while the main loop is busy sampling or printing. Debouncing is done by
+// the main loop when it takes the press.
+static BUTTON: Mutex<RefCell<Option<Gpio0<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+static PRESSED: AtomicBool = AtomicBool::new(false);
+
+pub fn init(mut button: Gpio0<Input<PullUp>>) {
+    button.listen(Event::FallingEdge);
+    critical_section::with(|cs| BUTTON.borrow_ref_mut(cs).replace(button));
+
+    interrupt::enable(Interrupt::GPIO, interrupt::Priority::Priority2)
+        .expect("Error while enabling the button interrupt");
+}
+
+// Returns true once for every edge seen since the last call
+pub fn take_press() -> bool {
+    PRESSED.swap(false, Ordering::Relaxed)
+}
+
+// Called from the shared GPIO interrupt
+pub(super) fn on_interrupt() {
+    let pressed = critical_section::with(|cs| match BUTTON.borrow_ref_mut(cs).as_mut() {
+        Some(button) if button.is_interrupt_set() => {
+            button.clear_interrupt();
+            true
+        }
+        _ => false,
+    });
+
+    if pressed {
+        PRESSED.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,87 @@
+// CAN for the `can` feature: the frames of `src/can.rs` on the TWAI
+// controller, through an external 3.3 V transceiver (an SN65HVD230 or the
+// like) on the pins `main()` picks. Transmit only, whatever the other
+// nodes send is left in the receive FIFO. One frame a tick at most; the
+// controller retries an unacked one on its own, so a frame only waits here
+// while the one before it is still going out.
+
+use embedded_hal::can::{nb::Can, Frame, StandardId};
+use hal::{
+    clock::Clocks,
+    gpio::{InputPin, OutputPin},
+    peripherals::TWAI0,
+    system::PeripheralClockControl,
+    twai::{BaudRate, EspTwaiFrame, Twai, TwaiConfiguration},
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    can::{BusEvent, CanCounters, CanLink, CanMessage},
+    config::CAN,
+};
+
+pub struct Bus<'d> {
+    // Out of it while it's being restarted
+    twai: Option<Twai<'d, TWAI0>>,
+    link: CanLink,
+}
+
+impl<'d> Bus<'d> {
+    pub fn new<TX: OutputPin, RX: InputPin>(
+        twai: TWAI0,
+        tx: TX,
+        rx: RX,
+        clocks: &Clocks,
+        peripheral_clock_control: &mut PeripheralClockControl,
+    ) -> Self {
+        let baud_rate = match CAN.kbps {
+            125 => BaudRate::B125K,
+            500 => BaudRate::B500K,
+            1000 => BaudRate::B1000K,
+            _ => BaudRate::B250K,
+        };
+        let config =
+            TwaiConfiguration::new(twai, tx, rx, peripheral_clock_control, clocks, baud_rate);
+        Self {
+            twai: Some(config.start()),
+            link: CanLink::new(),
+        }
+    }
+
+    pub fn push(&mut self, message: CanMessage) {
+        self.link.push(message);
+    }
+
+    // The bus-off check and its recovery, then the next frame
+    pub fn poll(&mut self, now_ms: u32) {
+        let bus_off = match &self.twai {
+            Some(twai) => twai.is_bus_off(),
+            None => true,
+        };
+        match self.link.update(bus_off, now_ms) {
+            Some(BusEvent::Off) => error!("ERROR: CAN bus off, frames dropped until it's back"),
+            // Out of reset mode the controller waits for 128 times 11
+            // recessive bits, the bus's recovery, before it's active again
+            Some(BusEvent::Restart) => {
+                self.twai = self.twai.take().map(|twai| twai.stop().start());
+            }
+            Some(BusEvent::Recovered) => info!("CAN: bus recovered"),
+            None => {}
+        }
+
+        let Some((alarm, data)) = self.link.outgoing() else {
+            return;
+        };
+        let id = if alarm { CAN.alarm_id } else { CAN.readings_id };
+        let frame = StandardId::new(id).and_then(|id| EspTwaiFrame::new(id, data));
+        let taken = match (&mut self.twai, frame) {
+            (Some(twai), Some(frame)) => twai.transmit(&frame).is_ok(),
+            _ => false,
+        };
+        self.link.sent(taken);
+    }
+
+    // Ours, and the controller's transmit error counter
+    pub fn counters(&self) -> (CanCounters, u8) {
+        let tec = self.twai.as_ref().map_or(0, Twai::transmit_error_count);
+        (self.link.counters(), tec)
+    }
+}
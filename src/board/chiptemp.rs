@@ -0,0 +1,51 @@
+use embedded_hal::blocking::delay::DelayUs;
+use hal::{peripherals::SENS, Delay};
+use rs_esp32_simple_preventive_maintenance_example::chiptemp::tsens_celsius;
+
+// The HAL has no driver for it, this is the sequence of ESP-IDF's
+// `temprature_sens_read()` on the SENS registers. The sensor is powered up
+// for each read, and needs 100 µs before its output is good.
+const POWER_UP_US: u32 = 100;
+const DUMP_US: u32 = 5;
+
+// Reads averaged into one, the output moves a count or two between reads
+const READS: u32 = 4;
+
+// In ºC, before the offset
+pub fn read(delay: &mut Delay) -> f32 {
+    let sens = unsafe { &*SENS::PTR };
+    // The SAR ADC powered up by software, the sensor clocked at a tenth of it
+    sens.sar_meas_wait2
+        .modify(|_, w| unsafe { w.force_xpd_sar().bits(3) });
+    sens.sar_tsens_ctrl
+        .modify(|_, w| unsafe { w.tsens_clk_div().bits(10) });
+
+    let mut sum = 0.0;
+    for _ in 0..READS {
+        sens.sar_tsens_ctrl.modify(|_, w| {
+            w.tsens_power_up()
+                .clear_bit()
+                .tsens_dump_out()
+                .clear_bit()
+                .tsens_power_up_force()
+                .set_bit()
+        });
+        sens.sar_tsens_ctrl
+            .modify(|_, w| w.tsens_power_up().set_bit());
+        delay.delay_us(POWER_UP_US);
+        sens.sar_tsens_ctrl
+            .modify(|_, w| w.tsens_dump_out().set_bit());
+        delay.delay_us(DUMP_US);
+        sum += tsens_celsius(sens.sar_slave_addr3.read().tsens_out().bits());
+    }
+    sum / READS as f32
+}
+
+// Averaged over CHIP_OFFSET_READS, for the offset at boot
+pub fn mean(delay: &mut Delay, reads: u32) -> f32 {
+    let mut sum = 0.0;
+    for _ in 0..reads {
+        sum += read(delay);
+    }
+    sum / reads as f32
+}
@@ -0,0 +1,57 @@
+use hal::{
+    adc::{AdcPin, ADC1},
+    gpio::{Analog, Gpio35},
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    current::{CURRENT_SAMPLES, CURRENT_SAMPLE_US, ZERO_WINDOWS},
+    CurrentScale, CurrentWindow,
+};
+
+use super::adc::{self, Adc1};
+
+// A window that runs this much past its mains cycle is cut off
+const WINDOW_SLACK_US: u32 = 5_000;
+
+// An ACS712 current sensor's output on GPIO35 (input only) through the
+// divider, at 11 dB
+pub struct CurrentSensor {
+    pin: AdcPin<Gpio35<Analog>, ADC1>,
+    scale: CurrentScale,
+}
+
+impl CurrentSensor {
+    pub fn new(pin: AdcPin<Gpio35<Analog>, ADC1>, scale: CurrentScale) -> Self {
+        Self { pin, scale }
+    }
+
+    // Measures the zero offset, averaged over a few mains cycles. None,
+    // keeping the one it had, if the windows came out short.
+    pub fn zero(&mut self, adc: &mut Adc1<'_>) -> Option<f32> {
+        let (mut sum, mut windows) = (0.0, 0);
+        for _ in 0..ZERO_WINDOWS {
+            if let Some(mean) = self.window(adc).mean_counts() {
+                sum += mean;
+                windows += 1;
+            }
+        }
+        if windows < ZERO_WINDOWS {
+            return None;
+        }
+        self.scale = self.scale.with_zero(sum / windows as f32);
+        Some(self.scale.zero_counts)
+    }
+
+    // One mains cycle of conversions, blocking for its ~20 ms
+    pub fn window(&mut self, adc: &mut Adc1<'_>) -> CurrentWindow {
+        let mut window = CurrentWindow::new(self.scale);
+        adc::convert(
+            adc,
+            &mut self.pin,
+            CURRENT_SAMPLES,
+            CURRENT_SAMPLE_US,
+            CURRENT_SAMPLES * CURRENT_SAMPLE_US + WINDOW_SLACK_US,
+            |counts| window.push(counts),
+        );
+        window
+    }
+}
@@ -0,0 +1,63 @@
+// The chart recorder output for the `dac` feature: DAC channel 1 on GPIO25,
+// the vibration RMS mapped by `DacMapping`, rewritten every tick. Channel 2
+// is GPIO26, the relay's pin, so the temperature isn't put out. The DAC has
+// no drive to speak of, a recorder input under ~10 kΩ wants a buffer.
+
+use hal::{analog, dac::DAC1, gpio::Analog, gpio::Gpio25};
+use rs_esp32_simple_preventive_maintenance_example::dac::{percent_code, DacMapping, Staircase};
+
+pub struct Output {
+    dac: DAC1,
+    staircase: Staircase,
+    // Until the next update starts the test pattern
+    test: bool,
+    // The test pattern's step, to print it as it changes
+    step: Option<u8>,
+    code: u8,
+}
+
+impl Output {
+    // Starts with the test pattern, a recorder is set up at power-on
+    pub fn new(dac: analog::DAC1, pin: Gpio25<Analog>) -> Self {
+        Self {
+            dac: DAC1::dac(dac, pin).unwrap(),
+            staircase: Staircase::new(),
+            test: true,
+            step: None,
+            code: 0,
+        }
+    }
+
+    // From the bottom step, if it was already running
+    pub fn test(&mut self) {
+        self.test = true;
+    }
+
+    pub fn is_testing(&self) -> bool {
+        self.test || self.staircase.is_running()
+    }
+
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    pub fn update(&mut self, now_ms: u32, mapping: &DacMapping, vibration_rms: Option<f32>) {
+        if core::mem::take(&mut self.test) {
+            self.staircase.start(now_ms);
+            self.step = None;
+        }
+        let step = self.staircase.percent(now_ms);
+        if step != self.step {
+            match step {
+                Some(percent) => info!("DAC: test pattern at {}%", percent),
+                None => info!("DAC: test pattern done, back to the vibration RMS"),
+            }
+            self.step = step;
+        }
+        self.code = match step {
+            Some(percent) => percent_code(percent),
+            None => mapping.code(vibration_rms),
+        };
+        self.dac.write(self.code);
+    }
+}
@@ -0,0 +1,66 @@
+use core::ptr::{addr_of, addr_of_mut};
+
+use hal::{
+    macros::ram,
+    reset::{get_reset_reason, get_wakeup_cause, SleepSource},
+    Cpu,
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    diagnostics::{PanicMessage, PANIC_MESSAGE_LEN},
+    ResetCause,
+};
+
+// Set by the panic handler right before it resets the chip, read and
+// cleared at the next boot. The ROM only sees a software or watchdog reset.
+#[ram(rtc_slow, uninitialized)]
+static mut PANIC_MARK: u32 = 0;
+#[ram(rtc_slow, uninitialized)]
+static mut PANIC_LEN: u32 = 0;
+#[ram(rtc_slow, uninitialized)]
+static mut PANIC_BYTES: [u8; PANIC_MESSAGE_LEN] = [0; PANIC_MESSAGE_LEN];
+
+const PANIC_MAGIC: u32 = 0x5041_4e43;
+
+pub struct Boot {
+    // ROM reset code of the PRO CPU, None if it's not one the HAL knows
+    pub code: Option<u32>,
+    pub cause: ResetCause,
+    // What woke the chip, only meaningful after deep sleep
+    pub wakeup: SleepSource,
+    // Start of the message of the panic that caused the reset
+    pub panic: Option<PanicMessage>,
+}
+
+// Why the chip started, call once at boot: it clears the panic mark
+pub fn boot() -> Boot {
+    let code = get_reset_reason(Cpu::ProCpu).map(|reason| reason as u32);
+    let panic = unsafe {
+        let panicked = addr_of!(PANIC_MARK).read_volatile() == PANIC_MAGIC;
+        addr_of_mut!(PANIC_MARK).write_volatile(0);
+        panicked.then(|| {
+            PanicMessage::from_raw(
+                addr_of!(PANIC_BYTES).read_volatile(),
+                addr_of!(PANIC_LEN).read_volatile() as usize,
+            )
+        })
+    };
+
+    Boot {
+        code,
+        cause: ResetCause::classify(code, panic.is_some()),
+        wakeup: get_wakeup_cause(),
+        panic,
+    }
+}
+
+// Only plain memory writes, safe to call from the panic handler
+#[cfg(feature = "panic-pattern")]
+pub fn record_panic(message: &PanicMessage) {
+    let (bytes, len) = message.raw();
+    unsafe {
+        addr_of_mut!(PANIC_BYTES).write_volatile(bytes);
+        addr_of_mut!(PANIC_LEN).write_volatile(len as u32);
+        // Last, so a reset halfway leaves no mark
+        addr_of_mut!(PANIC_MARK).write_volatile(PANIC_MAGIC);
+    }
+}
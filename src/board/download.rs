@@ -0,0 +1,225 @@
+// A `download` on the console's UART, the protocol's side in `src/xmodem.rs`.
+// The file is read from flash a block at a time, as the receiver asks:
+// - events.txt: the flash event log as `events` prints it, a line a record.
+//   Writing events out is held off until it's over, so the file keeps the
+//   length it was announced with.
+// - raw.bin: the raw log's sectors as they are, oldest first, for a host
+//   tool to decode. The log keeps filling, a block written over since the
+//   start is left out and the end padded with blank sectors in its place.
+// Nothing else goes to the UART meanwhile, see `log::pause()`.
+
+use core::fmt::Write;
+
+use hal::{peripherals::UART0, Uart};
+use heapless::String;
+#[cfg(feature = "raw-log")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    rawlog::{BlockHeader, RAW_BLOCK_LEN, RAW_HEADER_LEN, RAW_LOG_SECTORS},
+    RawRing,
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    xmodem::{Outcome, Protocol, Sender, XMODEM_CHUNK},
+    FlashLog, FlashRecord, Slot,
+};
+
+use super::flash;
+
+// Longer than any record's line
+const LINE_LEN: usize = 128;
+
+struct Events {
+    log: FlashLog,
+    // Into `log.slots()`
+    slot: usize,
+    line: String<LINE_LEN>,
+    at: usize,
+}
+
+impl Events {
+    fn new(log: FlashLog) -> Self {
+        Self {
+            log,
+            slot: 0,
+            line: String::new(),
+            at: 0,
+        }
+    }
+
+    // Every line formatted once, ~12 KB of reads
+    fn len(&self) -> u32 {
+        self.log
+            .slots()
+            .map(|slot| record_line(slot).len() as u32)
+            .sum()
+    }
+
+    // Asked for in order, it carries on from where it got to
+    fn fill(&mut self, _offset: u32, bytes: &mut [u8]) {
+        for byte in bytes.iter_mut() {
+            while self.at >= self.line.len() {
+                let Some(slot) = self.log.slots().nth(self.slot) else {
+                    break;
+                };
+                self.slot += 1;
+                self.line = record_line(slot);
+                self.at = 0;
+            }
+            // Only short if a record changed since the length was taken
+            *byte = self.line.as_bytes().get(self.at).copied().unwrap_or(b' ');
+            self.at += 1;
+        }
+    }
+}
+
+// Empty for a blank or corrupt slot
+fn record_line(slot: usize) -> String<LINE_LEN> {
+    let mut line = String::new();
+    if let Slot::Record(record) = FlashRecord::decode(&flash::read_record(slot)) {
+        writeln!(line, "#{} {}", record.seq, record.event).ok();
+    }
+    line
+}
+
+#[cfg(feature = "raw-log")]
+struct Raw {
+    // The oldest block as it started, and how far past it
+    start: usize,
+    position: usize,
+    // The blocks written since are left out
+    until_seq: u32,
+    block: [u8; RAW_BLOCK_LEN],
+}
+
+#[cfg(feature = "raw-log")]
+impl Raw {
+    fn new(ring: &RawRing) -> Self {
+        Self {
+            start: ring.blocks().next().unwrap_or(0),
+            position: 0,
+            until_seq: ring.next_seq(),
+            block: [0xff; RAW_BLOCK_LEN],
+        }
+    }
+
+    fn block(&self, position: usize) -> usize {
+        (self.start + position) % RAW_LOG_SECTORS
+    }
+
+    fn is_wanted(&self, block: usize) -> bool {
+        let mut header = [0xff; RAW_HEADER_LEN];
+        if flash::read_raw(block, &mut header).is_err() {
+            return false;
+        }
+        matches!(BlockHeader::decode(&header), Slot::Record(header) if header.seq < self.until_seq)
+    }
+
+    // A header from each sector
+    fn len(&self) -> u32 {
+        let blocks = (0..RAW_LOG_SECTORS)
+            .filter(|position| self.is_wanted(self.block(*position)))
+            .count();
+        (blocks * RAW_BLOCK_LEN) as u32
+    }
+
+    // The blocks are a whole number of either protocol's
+    fn fill(&mut self, offset: u32, bytes: &mut [u8]) {
+        let at = offset as usize % RAW_BLOCK_LEN;
+        if at == 0 {
+            self.load();
+        }
+        let end = (at + bytes.len()).min(RAW_BLOCK_LEN);
+        bytes[..end - at].copy_from_slice(&self.block[at..end]);
+    }
+
+    fn load(&mut self) {
+        while self.position < RAW_LOG_SECTORS {
+            let block = self.block(self.position);
+            self.position += 1;
+            if self.is_wanted(block) && flash::read_raw(block, &mut self.block).is_ok() {
+                return;
+            }
+        }
+        self.block.fill(0xff);
+    }
+}
+
+enum File {
+    Events(Events),
+    #[cfg(feature = "raw-log")]
+    Raw(Raw),
+}
+
+pub struct Download {
+    sender: Sender,
+    file: File,
+    name: &'static str,
+}
+
+impl Download {
+    pub fn events(log: FlashLog, protocol: Protocol, now_ms: u32) -> Self {
+        let events = Events::new(log);
+        Self::new(
+            protocol,
+            "events.txt",
+            events.len(),
+            File::Events(events),
+            now_ms,
+        )
+    }
+
+    #[cfg(feature = "raw-log")]
+    pub fn raw(ring: &RawRing, protocol: Protocol, now_ms: u32) -> Self {
+        let raw = Raw::new(ring);
+        Self::new(protocol, "raw.bin", raw.len(), File::Raw(raw), now_ms)
+    }
+
+    fn new(protocol: Protocol, name: &'static str, len: u32, file: File, now_ms: u32) -> Self {
+        Self {
+            sender: Sender::new(protocol, name, len, now_ms),
+            file,
+            name,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn size(&self) -> u32 {
+        self.sender.progress().1
+    }
+
+    // Bytes of the file sent so far
+    pub fn sent(&self) -> u32 {
+        self.sender.progress().0
+    }
+
+    // Whether events are to stay in RAM until it's over
+    pub fn holds_event_log(&self) -> bool {
+        matches!(self.file, File::Events(_))
+    }
+
+    // A byte from the console
+    pub fn receive(&mut self, byte: u8, now_ms: u32) {
+        let file = &mut self.file;
+        self.sender
+            .receive(byte, now_ms, |offset, bytes| match file {
+                File::Events(events) => events.fill(offset, bytes),
+                #[cfg(feature = "raw-log")]
+                File::Raw(raw) => raw.fill(offset, bytes),
+            });
+    }
+
+    // The `download` task: the timeouts, then what's next of the frame.
+    // How it ended, once it has.
+    pub fn send(&mut self, uart: &mut Uart<'_, UART0>, now_ms: u32) -> Option<Outcome> {
+        self.sender.poll(now_ms);
+        let pending = self.sender.pending();
+        let len = pending.len().min(XMODEM_CHUNK);
+        if len > 0 {
+            uart.write_bytes(&pending[..len]).ok();
+            self.sender.sent(len, now_ms);
+        }
+        self.sender.finished()
+    }
+}
@@ -0,0 +1,147 @@
+use embedded_storage::{nor_flash::NorFlash, ReadStorage, Storage};
+use esp_storage::{FlashStorage, FlashStorageError};
+use rs_esp32_simple_preventive_maintenance_example::{
+    flashlog::{self, FLASH_RECORD_LEN, FLASH_SECTOR_LEN},
+    learning::BASELINE_LEN,
+    settings::SETTINGS_LEN,
+    Commissioning, Event, FlashLog, FlashRecord, FlashWrite, HoursRecord, HoursRing, RunHours,
+    Settings, SettingsError,
+};
+#[cfg(feature = "raw-log")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    rawlog::{self, RawOp, RAW_BLOCK_LEN, RAW_HEADER_LEN},
+    RawRing,
+};
+
+// The settings block lives at the start of the default partition table's
+// NVS partition, the firmware doesn't use NVS otherwise.
+// Only written on an explicit `save`, `learn accept` or `factory-reset`,
+// flash sectors wear out after ~100k erases.
+const SETTINGS_OFFSET: u32 = 0x9000;
+// The commissioning baseline shares its sector, `Storage` writes keep the
+// rest of the sector as it was
+const BASELINE_OFFSET: u32 = SETTINGS_OFFSET + 0x100;
+// The rest of it, up to the PHY data at 0xf000: the event log's 3 sectors,
+// then the running hours' 2
+const EVENT_LOG_OFFSET: u32 = 0xa000;
+const HOURS_OFFSET: u32 = 0xd000;
+// The raw acceleration log's 2 MiB, past the default partition table's
+// 1 MiB app on the 4 MiB flash of the usual modules. Nothing in that table
+// claims it; one with OTA slots or a bigger app would, and needs this moved.
+#[cfg(feature = "raw-log")]
+const RAW_LOG_OFFSET: u32 = 0x11_0000;
+
+pub fn load() -> Result<Settings, SettingsError> {
+    let mut bytes = [0; SETTINGS_LEN];
+    // An unreadable block is as good as an erased one
+    if FlashStorage::new()
+        .read(SETTINGS_OFFSET, &mut bytes)
+        .is_err()
+    {
+        return Err(SettingsError::Blank);
+    }
+    Settings::decode(&bytes)
+}
+
+pub fn save(settings: &Settings) -> Result<(), FlashStorageError> {
+    FlashStorage::new().write(SETTINGS_OFFSET, &settings.encode())
+}
+
+// Leaves the block erased, the next boot uses the compiled-in defaults.
+// The commissioning baseline goes with it, the machine is learned again.
+pub fn erase() -> Result<(), FlashStorageError> {
+    let mut flash = FlashStorage::new();
+    flash.write(SETTINGS_OFFSET, &[0xff; SETTINGS_LEN])?;
+    flash.write(BASELINE_OFFSET, &[0xff; BASELINE_LEN])
+}
+
+// None when nothing was learned, or it can't be read
+pub fn load_baseline() -> Option<Commissioning> {
+    let mut bytes = [0; BASELINE_LEN];
+    FlashStorage::new().read(BASELINE_OFFSET, &mut bytes).ok()?;
+    Commissioning::decode(&bytes)
+}
+
+pub fn save_baseline(baseline: &Commissioning) -> Result<(), FlashStorageError> {
+    FlashStorage::new().write(BASELINE_OFFSET, &baseline.encode())
+}
+
+// A slot of the event log. One that can't be read counts as corrupt, and
+// is never written to.
+pub fn read_record(slot: usize) -> [u8; FLASH_RECORD_LEN] {
+    read_slot(EVENT_LOG_OFFSET, slot)
+}
+
+// Where the event log left off, ~12 KB of reads
+pub fn scan_log() -> FlashLog {
+    FlashLog::scan(read_record).0
+}
+
+pub fn append(log: &mut FlashLog, event: &Event) -> Result<(), FlashStorageError> {
+    apply(
+        EVENT_LOG_OFFSET,
+        log.append(|seq| FlashRecord { seq, event: *event }),
+    )
+}
+
+// The newest running hours save, if any
+pub fn scan_hours() -> (HoursRing, Option<HoursRecord>) {
+    HoursRing::scan(|slot| read_slot(HOURS_OFFSET, slot))
+}
+
+pub fn save_hours(ring: &mut HoursRing, hours: &RunHours) -> Result<(), FlashStorageError> {
+    apply(HOURS_OFFSET, ring.append(|seq| hours.record(seq)))
+}
+
+// Where the raw log left off, a header from each sector
+#[cfg(feature = "raw-log")]
+pub fn scan_raw() -> RawRing {
+    RawRing::scan(|block| {
+        let mut header = [0xff; RAW_HEADER_LEN];
+        let offset = RAW_LOG_OFFSET + rawlog::block_offset(block);
+        if FlashStorage::new().read(offset, &mut header).is_err() {
+            return [0; RAW_HEADER_LEN];
+        }
+        header
+    })
+}
+
+// From the start of a block, its header or all of it
+#[cfg(feature = "raw-log")]
+pub fn read_raw(block: usize, bytes: &mut [u8]) -> Result<(), FlashStorageError> {
+    FlashStorage::new().read(RAW_LOG_OFFSET + rawlog::block_offset(block), bytes)
+}
+
+#[cfg(feature = "raw-log")]
+pub fn apply_raw(op: RawOp<'_>) -> Result<(), FlashStorageError> {
+    let mut flash = FlashStorage::new();
+    match op {
+        RawOp::Erase(from) => {
+            let from = RAW_LOG_OFFSET + from;
+            NorFlash::erase(&mut flash, from, from + RAW_BLOCK_LEN as u32)
+        }
+        RawOp::Program(offset, bytes) => {
+            NorFlash::write(&mut flash, RAW_LOG_OFFSET + offset, bytes)
+        }
+    }
+}
+
+fn read_slot(region: u32, slot: usize) -> [u8; FLASH_RECORD_LEN] {
+    let mut bytes = [0xff; FLASH_RECORD_LEN];
+    let offset = region + flashlog::slot_offset(slot);
+    if FlashStorage::new().read(offset, &mut bytes).is_err() {
+        return [0; FLASH_RECORD_LEN];
+    }
+    bytes
+}
+
+// Erasing a sector first when a ring reaches it. Plain NOR writes, the
+// `Storage` ones would erase and rewrite the whole sector for every record.
+fn apply(region: u32, write: FlashWrite) -> Result<(), FlashStorageError> {
+    let mut flash = FlashStorage::new();
+    if let Some(sector) = write.erase {
+        let from = region + sector;
+        NorFlash::erase(&mut flash, from, from + FLASH_SECTOR_LEN as u32)?;
+    }
+    NorFlash::write(&mut flash, region + write.offset, &write.bytes)
+}
@@ -0,0 +1,52 @@
+use hal::{
+    adc::{AdcPin, ADC1},
+    gpio::{Analog, Gpio34},
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    knock::{KNOCK_BIAS_BURSTS, KNOCK_BURST_BUDGET_US, KNOCK_BURST_SAMPLES},
+    KnockBias, KnockBurst,
+};
+
+use super::adc::{self, Adc1};
+
+// A piezo knock sensor on GPIO34 (input only), at 11 dB for the 0-3.1 V
+// range, biased at mid-supply by its divider
+pub struct KnockSensor {
+    pin: AdcPin<Gpio34<Analog>, ADC1>,
+    bias: f32,
+}
+
+impl KnockSensor {
+    pub fn new(pin: AdcPin<Gpio34<Analog>, ADC1>) -> Self {
+        Self { pin, bias: 0.0 }
+    }
+
+    // The DC bias, averaged over a few bursts. The swing averages out, so
+    // the machine may be running. None if the input sits at a rail.
+    pub fn calibrate(&mut self, adc: &mut Adc1<'_>) -> Option<f32> {
+        let mut bias = KnockBias::new();
+        for _ in 0..KNOCK_BIAS_BURSTS {
+            self.convert(adc, |counts| bias.push(counts));
+        }
+        self.bias = bias.mean()?;
+        Some(self.bias)
+    }
+
+    pub fn burst(&mut self, adc: &mut Adc1<'_>) -> KnockBurst {
+        let mut burst = KnockBurst::new(self.bias);
+        self.convert(adc, |counts| burst.push(counts));
+        burst
+    }
+
+    // Back to back, for no longer than KNOCK_BURST_BUDGET_US
+    fn convert(&mut self, adc: &mut Adc1<'_>, push: impl FnMut(u16)) {
+        adc::convert(
+            adc,
+            &mut self.pin,
+            KNOCK_BURST_SAMPLES,
+            0,
+            KNOCK_BURST_BUDGET_US,
+            push,
+        );
+    }
+}
@@ -0,0 +1,157 @@
+// Where the log lines go, picked by feature so the call sites stay the same:
+// - log-text: esp-println on UART0, every line starting with the uptime
+// - log-defmt: defmt over RTT, the host adds the uptime from the timestamp
+// The JSON, CSV and binary telemetry stays on UART0 either way.
+// `println!` always prints, `error!` to `debug!` only up to the level set
+// with `set_level()`. The ANSI colors are on while `color()` is.
+// Nothing is printed while it's `paused()`, for a `download`.
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use rs_esp32_simple_preventive_maintenance_example::config::{Level, COLOR, LOG_LEVEL};
+
+#[cfg(all(feature = "log-text", feature = "log-defmt"))]
+compile_error!("pick one of the log-text and log-defmt features");
+#[cfg(not(any(feature = "log-text", feature = "log-defmt")))]
+compile_error!("no log backend, enable `log-text` (default) or `log-defmt`");
+
+#[cfg(feature = "log-defmt")]
+use defmt_rtt as _;
+
+#[cfg(feature = "log-text")]
+macro_rules! println {
+    ($($arg:tt)*) => {
+        if !$crate::board::log::paused() {
+            esp_println::println!(
+                "{} {}",
+                rs_esp32_simple_preventive_maintenance_example::Timestamp(
+                    $crate::board::time::uptime_ms()
+                ),
+                format_args!($($arg)*)
+            )
+        }
+    };
+}
+
+// The lines are still formatted on the chip, defmt only carries them
+#[cfg(feature = "log-defmt")]
+macro_rules! println {
+    ($($arg:tt)*) => {
+        if !$crate::board::log::paused() {
+            defmt::println!("{}", defmt::Display2Format(&format_args!($($arg)*)))
+        }
+    };
+}
+
+#[cfg(feature = "log-defmt")]
+defmt::timestamp!("{=u64:ms}", super::time::uptime_ms());
+
+static LEVEL: AtomicU8 = AtomicU8::new(LOG_LEVEL as u8);
+
+pub fn level() -> Level {
+    Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+#[cfg(not(feature = "embassy"))]
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "embassy"))]
+pub fn pause(paused: bool) {
+    PAUSED.store(paused, Ordering::Relaxed);
+}
+
+static COLOR_ON: AtomicBool = AtomicBool::new(COLOR);
+
+// Never over defmt, the host colors the lines by level itself
+pub fn color() -> bool {
+    cfg!(feature = "log-text") && COLOR_ON.load(Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "embassy"))]
+pub fn set_color(on: bool) {
+    COLOR_ON.store(on, Ordering::Relaxed);
+}
+
+// defmt gets the level too, for its host-side coloring and filtering
+#[cfg(feature = "log-text")]
+macro_rules! log_at {
+    ($level:ident, $defmt:ident, $($arg:tt)*) => {
+        if $crate::board::log::enabled(
+            rs_esp32_simple_preventive_maintenance_example::config::Level::$level,
+        ) {
+            println!($($arg)*)
+        }
+    };
+}
+
+#[cfg(feature = "log-defmt")]
+macro_rules! log_at {
+    ($level:ident, $defmt:ident, $($arg:tt)*) => {
+        if $crate::board::log::enabled(
+            rs_esp32_simple_preventive_maintenance_example::config::Level::$level,
+        ) && !$crate::board::log::paused()
+        {
+            defmt::$defmt!("{}", defmt::Display2Format(&format_args!($($arg)*)))
+        }
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { log_at!(Error, error, $($arg)*) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { log_at!(Warn, warn, $($arg)*) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { log_at!(Info, info, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { log_at!(Debug, debug, $($arg)*) };
+}
+
+// An alarm banner, in bold red on a color terminal
+macro_rules! banner {
+    ($($arg:tt)*) => {
+        println!(
+            "{}",
+            rs_esp32_simple_preventive_maintenance_example::Paint::new(
+                format_args!($($arg)*),
+                rs_esp32_simple_preventive_maintenance_example::Style::Alarm,
+                $crate::board::log::color(),
+            )
+        )
+    };
+}
+
+// A telemetry line, without the uptime: each format carries its own
+macro_rules! data_println {
+    ($($arg:tt)*) => {
+        if !$crate::board::log::paused() {
+            esp_println::println!($($arg)*)
+        }
+    };
+}
+
+// For the panic handler, which can't trust the HAL timer behind the text
+// timestamps. The UART line goes first, it doesn't need the timer at all.
+#[cfg(feature = "panic-pattern")]
+pub fn panic_message(message: &str) {
+    esp_println::println!("PANIC: {}", message);
+    #[cfg(feature = "log-defmt")]
+    defmt::error!("PANIC: {=str}", message);
+}
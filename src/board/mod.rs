@@ -0,0 +1,66 @@
+// ESP32-specific drivers used by the firmware.
+// Everything hardware-agnostic lives in the library crate instead.
+
+use hal::prelude::*;
+
+#[cfg(not(any(feature = "backtrace-panic", feature = "panic-pattern")))]
+compile_error!("no panic handler, enable `backtrace-panic` (default) or `panic-pattern`");
+
+// First, the other modules use its macros
+#[macro_use]
+pub mod log;
+
+#[cfg(any(feature = "knock", feature = "current"))]
+pub mod adc;
+#[cfg(feature = "backtrace-panic")]
+mod backtrace;
+pub mod button;
+#[cfg(feature = "can")]
+pub mod can;
+#[cfg(not(feature = "embassy"))]
+pub mod chiptemp;
+#[cfg(feature = "current")]
+pub mod current;
+#[cfg(feature = "dac")]
+pub mod dac;
+pub mod diagnostics;
+#[cfg(not(feature = "embassy"))]
+pub mod download;
+#[cfg(not(feature = "embassy"))]
+pub mod flash;
+#[cfg(feature = "knock")]
+pub mod knock;
+#[cfg(feature = "modbus")]
+pub mod modbus;
+pub mod motion;
+#[cfg(feature = "ds18b20")]
+pub mod onewire;
+#[cfg(feature = "panic-pattern")]
+mod panic;
+#[cfg(feature = "raw-log")]
+pub mod rawlog;
+#[cfg(not(feature = "embassy"))]
+pub mod record;
+#[cfg(feature = "sd-card")]
+pub mod sdcard;
+#[cfg(not(feature = "embassy"))]
+pub mod sleep;
+#[cfg(feature = "tachometer")]
+pub mod tachometer;
+#[cfg(not(feature = "embassy"))]
+pub mod ticker;
+pub mod time;
+#[cfg(feature = "ledc-buzzer")]
+pub mod tone;
+#[cfg(feature = "daq-trigger")]
+pub mod trigger;
+
+// All GPIOs share one interrupt, each pin checks its own status bit.
+// Only flags and timestamps are set here, the main loop does the work.
+#[interrupt]
+fn GPIO() {
+    button::on_interrupt();
+    motion::on_interrupt();
+    #[cfg(feature = "tachometer")]
+    tachometer::on_interrupt();
+}
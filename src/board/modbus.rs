@@ -0,0 +1,149 @@
+// Modbus RTU on UART1 for the `modbus` feature, the register map of
+// `src/modbus.rs`. TX on GPIO17, RX on GPIO16, and GPIO18 driving an
+// RS-485 transceiver's DE and /RE tied together, high while sending; with
+// a plain TTL or RS-232 adapter it's left unconnected. Other pins are
+// changed here and in `main()`.
+//
+// Every byte is timestamped in the UART's interrupt as it comes in, so the
+// 1.5 and 3.5 character silences are measured on the line and not on the
+// 10 ms tick. The `modbus` task takes a whole frame at a time.
+// A reply goes into the TX FIFO whole and the interrupt drops DE once
+// the last bit is out, the task doesn't wait for it.
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use embedded_hal::{digital::v2::OutputPin, serial::Read};
+use hal::{
+    clock::Clocks,
+    gpio::{Gpio16, Gpio17, Gpio18, Output, PushPull, Unknown},
+    interrupt,
+    peripherals::{Interrupt, UART1},
+    prelude::*,
+    system::PeripheralClockControl,
+    uart::{
+        config::{Config, DataBits, Parity, StopBits},
+        TxRxPins,
+    },
+    Uart,
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    config::MODBUS,
+    modbus::{Adu, Receiver},
+};
+
+use super::time;
+
+static UART: Mutex<RefCell<Option<Uart<'static, UART1>>>> = Mutex::new(RefCell::new(None));
+static RECEIVER: Mutex<RefCell<Receiver>> = Mutex::new(RefCell::new(Receiver::new(MODBUS.baud)));
+// A frame the next one's first byte ended, until the task takes it
+static FRAME: Mutex<RefCell<Option<Adu>>> = Mutex::new(RefCell::new(None));
+static DE: Mutex<RefCell<Option<Gpio18<Output<PushPull>>>>> = Mutex::new(RefCell::new(None));
+// From the reply's first byte until TX done, what comes in is its echo
+static SENDING: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+// The UART and DE live in the statics above, the interrupt needs them
+pub struct Port;
+
+impl Port {
+    pub fn new(
+        uart: UART1,
+        tx: Gpio17<Unknown>,
+        rx: Gpio16<Unknown>,
+        de: Gpio18<Unknown>,
+        clocks: &Clocks,
+        peripheral_clock_control: &mut PeripheralClockControl,
+    ) -> Self {
+        let config = Config {
+            baudrate: MODBUS.baud,
+            data_bits: DataBits::DataBits8,
+            parity: Parity::ParityEven,
+            stop_bits: StopBits::STOP1,
+        };
+        let pins = TxRxPins::new_tx_rx(tx.into_push_pull_output(), rx.into_floating_input());
+        let mut uart = Uart::new_with_config(
+            uart,
+            Some(config),
+            Some(pins),
+            clocks,
+            peripheral_clock_control,
+        );
+        // An interrupt a byte, for its timestamp
+        uart.set_rx_fifo_full_threshold(1);
+        uart.listen_rx_fifo_full();
+        let mut de = de.into_push_pull_output();
+        de.set_low().ok();
+        critical_section::with(|cs| {
+            UART.borrow_ref_mut(cs).replace(uart);
+            DE.borrow_ref_mut(cs).replace(de);
+        });
+
+        interrupt::enable(Interrupt::UART1, interrupt::Priority::Priority2)
+            .expect("Error while enabling the Modbus interrupt");
+
+        Self
+    }
+
+    // The next frame in, whole, once the line went quiet after it
+    pub fn take(&mut self) -> Option<Adu> {
+        let now_us = time::awake_us();
+        critical_section::with(|cs| {
+            FRAME
+                .borrow_ref_mut(cs)
+                .take()
+                .or_else(|| RECEIVER.borrow_ref_mut(cs).idle(now_us))
+        })
+    }
+
+    // Returns right away, the map's longest reply is 29 bytes and the FIFO
+    // holds 128. The transceiver keeps talking until the interrupt sees the
+    // shift register empty, 17 ms later at 19200 baud.
+    pub fn send(&mut self, adu: &[u8]) {
+        critical_section::with(|cs| {
+            let mut uart = UART.borrow_ref_mut(cs);
+            let Some(uart) = uart.as_mut() else {
+                return;
+            };
+            if let Some(de) = DE.borrow_ref_mut(cs).as_mut() {
+                de.set_high().ok();
+            }
+            SENDING.borrow(cs).set(true);
+            // A TX done from the last reply would end this one at once
+            uart.reset_tx_done_interrupt();
+            uart.write_bytes(adu).ok();
+            uart.listen_tx_done();
+        });
+    }
+}
+
+#[interrupt]
+fn UART1() {
+    critical_section::with(|cs| {
+        let mut uart = UART.borrow_ref_mut(cs);
+        let Some(uart) = uart.as_mut() else {
+            return;
+        };
+        let mut receiver = RECEIVER.borrow_ref_mut(cs);
+        let sending = SENDING.borrow(cs);
+        while let Ok(byte) = uart.read() {
+            if sending.get() {
+                continue;
+            }
+            if let Some(adu) = receiver.push(byte, time::awake_us()) {
+                FRAME.borrow_ref_mut(cs).replace(adu);
+            }
+        }
+        uart.reset_rx_fifo_full_interrupt();
+
+        if uart.tx_done_interrupt_set() {
+            uart.unlisten_tx_done();
+            uart.reset_tx_done_interrupt();
+            if let Some(de) = DE.borrow_ref_mut(cs).as_mut() {
+                de.set_low().ok();
+            }
+            sending.set(false);
+            // The master's next request starts from silence
+            receiver.clear();
+        }
+    });
+}
@@ -0,0 +1,61 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use critical_section::Mutex;
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+use hal::{gpio::Event, interrupt, peripherals::Interrupt};
+use hal::{
+    gpio::{Gpio4, Input, PullDown},
+    prelude::*,
+};
+
+// MPU6050 INT on GPIO4, raised by its motion detector.
+// The pull-down keeps an unconnected pin quiet, the monitor then only
+// samples at its normal cadence.
+static INT_PIN: Mutex<RefCell<Option<Gpio4<Input<PullDown>>>>> = Mutex::new(RefCell::new(None));
+static MOTION: AtomicBool = AtomicBool::new(false);
+
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+pub fn init(mut pin: Gpio4<Input<PullDown>>) {
+    pin.listen(Event::RisingEdge);
+    critical_section::with(|cs| INT_PIN.borrow_ref_mut(cs).replace(pin));
+
+    interrupt::enable(Interrupt::GPIO, interrupt::Priority::Priority2)
+        .expect("Error while enabling the motion interrupt");
+}
+
+// Takes the pin back for the deep sleep wake-up source, with its interrupt
+// off: a latched INT would otherwise keep the handler running. None if it
+// was never set up.
+#[cfg(feature = "deep-sleep")]
+pub fn take_pin() -> Option<Gpio4<Input<PullDown>>> {
+    critical_section::with(|cs| {
+        let mut pin = INT_PIN.borrow_ref_mut(cs).take()?;
+        pin.unlisten();
+        pin.clear_interrupt();
+        Some(pin)
+    })
+}
+
+// Returns true once for every edge seen since the last call
+#[cfg(not(feature = "embassy"))]
+pub fn take_motion() -> bool {
+    MOTION.swap(false, Ordering::Relaxed)
+}
+
+// Called from the shared GPIO interrupt
+pub(super) fn on_interrupt() {
+    let moved = critical_section::with(|cs| match INT_PIN.borrow_ref_mut(cs).as_mut() {
+        Some(pin) if pin.is_interrupt_set() => {
+            pin.clear_interrupt();
+            true
+        }
+        _ => false,
+    });
+
+    if moved {
+        MOTION.store(true, Ordering::Relaxed);
+    }
+}
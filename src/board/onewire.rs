@@ -0,0 +1,69 @@
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::{InputPin, OutputPin},
+};
+use hal::{
+    gpio::{Gpio27, OpenDrain, Output},
+    Delay,
+};
+use rs_esp32_simple_preventive_maintenance_example::OneWire;
+
+// Slot timings of Maxim AN126, standard speed, in µs
+const RESET_LOW_US: u32 = 480;
+const PRESENCE_SAMPLE_US: u32 = 70;
+const RESET_RECOVERY_US: u32 = 410;
+const WRITE_1_LOW_US: u32 = 6;
+const WRITE_1_RECOVERY_US: u32 = 64;
+const WRITE_0_LOW_US: u32 = 60;
+const WRITE_0_RECOVERY_US: u32 = 10;
+const READ_LOW_US: u32 = 6;
+const READ_SAMPLE_US: u32 = 9;
+const READ_RECOVERY_US: u32 = 55;
+
+// The DS18B20's DQ on GPIO27, open drain with the 4.7 kΩ pull-up on the
+// board. Each slot runs in a critical section: an interrupt landing inside
+// one would stretch it past what the devices accept.
+pub struct OneWirePin {
+    pin: Gpio27<Output<OpenDrain>>,
+    delay: Delay,
+}
+
+impl OneWirePin {
+    pub fn new(mut pin: Gpio27<Output<OpenDrain>>, delay: Delay) -> Self {
+        // Released, the bus idles high
+        pin.set_high().unwrap();
+        Self { pin, delay }
+    }
+
+    // Pulls the bus low for `low_us`, releases it and returns what it reads
+    // `sample_us` later, then waits `recovery_us`
+    fn slot(&mut self, low_us: u32, sample_us: u32, recovery_us: u32) -> bool {
+        critical_section::with(|_| {
+            self.pin.set_low().unwrap();
+            self.delay.delay_us(low_us);
+            self.pin.set_high().unwrap();
+            self.delay.delay_us(sample_us);
+            let high = self.pin.is_high().unwrap();
+            self.delay.delay_us(recovery_us);
+            high
+        })
+    }
+}
+
+impl OneWire for OneWirePin {
+    fn reset(&mut self) -> bool {
+        // A device pulls the bus low for its presence pulse
+        !self.slot(RESET_LOW_US, PRESENCE_SAMPLE_US, RESET_RECOVERY_US)
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        match bit {
+            true => self.slot(WRITE_1_LOW_US, 0, WRITE_1_RECOVERY_US),
+            false => self.slot(WRITE_0_LOW_US, 0, WRITE_0_RECOVERY_US),
+        };
+    }
+
+    fn read_bit(&mut self) -> bool {
+        self.slot(READ_LOW_US, READ_SAMPLE_US, READ_RECOVERY_US)
+    }
+}
@@ -0,0 +1,117 @@
+use core::{fmt::Write, panic::PanicInfo};
+
+use rs_esp32_simple_preventive_maintenance_example::diagnostics::{PanicMessage, SOS, SOS_UNIT_MS};
+
+#[cfg(feature = "backtrace-panic")]
+compile_error!(
+    "`panic-pattern` replaces the backtrace panic handler, build it with `--no-default-features`"
+);
+
+// Panic handler for installed units, `--features panic-pattern`: nobody
+// reads the serial port there, so the LED and buzzer play SOS in Morse for
+// a minute, then the chip resets and monitoring resumes. The next boot
+// banner shows the start of the message, and chirps.
+// The HAL's drivers may be what panicked, everything here goes straight to
+// the registers (ESP32 TRM addresses) and nothing is borrowed from them.
+
+// Long enough for someone walking by to notice
+const PANIC_SOS_MS: u32 = 60_000;
+
+const LED: u32 = 2;
+const BUZZER: u32 = 33;
+
+const GPIO_OUT_W1TS: *mut u32 = 0x3ff4_4008 as _;
+const GPIO_OUT_W1TC: *mut u32 = 0x3ff4_400c as _;
+const GPIO_OUT1_W1TS: *mut u32 = 0x3ff4_4014 as _;
+const GPIO_OUT1_W1TC: *mut u32 = 0x3ff4_4018 as _;
+const GPIO_ENABLE_W1TS: *mut u32 = 0x3ff4_4024 as _;
+const GPIO_ENABLE1_W1TS: *mut u32 = 0x3ff4_4030 as _;
+const GPIO_FUNC_OUT_SEL_CFG: usize = 0x3ff4_4530;
+// Plain GPIO output, enabled by GPIO_ENABLE: takes the buzzer pin back
+// from LEDC with `ledc-buzzer`
+const SIMPLE_OUTPUT: u32 = 0x100 | 1 << 10;
+
+// TIMG1 timer 1, the uptime timer, at 1 MHz from the 80 MHz APB clock
+const TIMG1_T1_CONFIG: *mut u32 = 0x3ff6_0024 as _;
+const TIMG1_T1_LO: *mut u32 = 0x3ff6_0028 as _;
+const TIMG1_T1_UPDATE: *mut u32 = 0x3ff6_0030 as _;
+const TIMER_RUN: u32 = 1 << 31 | 1 << 30 | 80 << 13;
+
+const TIMG0_WDT_FEED: *mut u32 = 0x3ff5_f060 as _;
+const TIMG0_WDT_PROTECT: *mut u32 = 0x3ff5_f064 as _;
+const WDT_UNLOCK: u32 = 0x50d8_3aa1;
+
+const RTC_CNTL_OPTIONS0: *mut u32 = 0x3ff4_8000 as _;
+const SW_SYS_RST: u32 = 1 << 31;
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // Nothing else runs from here on, the section is never released
+    let _ = unsafe { critical_section::acquire() };
+
+    let mut message = PanicMessage::new();
+    let _ = write!(message, "{}", info);
+    super::diagnostics::record_panic(&message);
+    super::log::panic_message(message.as_str());
+
+    unsafe {
+        out_sel(LED).write_volatile(SIMPLE_OUTPUT);
+        out_sel(BUZZER).write_volatile(SIMPLE_OUTPUT);
+        GPIO_ENABLE_W1TS.write_volatile(1 << LED);
+        GPIO_ENABLE1_W1TS.write_volatile(1 << (BUZZER - 32));
+        // Harmless if it's already running, needed if the panic came first
+        TIMG1_T1_CONFIG.write_volatile(TIMER_RUN);
+    }
+
+    let start = now_us();
+    while now_us().wrapping_sub(start) < PANIC_SOS_MS * 1_000 {
+        for (on, off) in SOS {
+            outputs(true);
+            wait_ms(on * SOS_UNIT_MS);
+            outputs(false);
+            wait_ms(off * SOS_UNIT_MS);
+        }
+    }
+
+    unsafe {
+        let options = RTC_CNTL_OPTIONS0.read_volatile();
+        RTC_CNTL_OPTIONS0.write_volatile(options | SW_SYS_RST);
+    }
+    loop {}
+}
+
+fn out_sel(pin: u32) -> *mut u32 {
+    (GPIO_FUNC_OUT_SEL_CFG + 4 * pin as usize) as *mut u32
+}
+
+fn outputs(on: bool) {
+    let (low, high) = if on {
+        (GPIO_OUT_W1TS, GPIO_OUT1_W1TS)
+    } else {
+        (GPIO_OUT_W1TC, GPIO_OUT1_W1TC)
+    };
+    unsafe {
+        low.write_volatile(1 << LED);
+        high.write_volatile(1 << (BUZZER - 32));
+    }
+}
+
+// Low 32 bits of the uptime counter, in µs, wrapping
+fn now_us() -> u32 {
+    unsafe {
+        TIMG1_T1_UPDATE.write_volatile(1);
+        TIMG1_T1_LO.read_volatile()
+    }
+}
+
+// Feeds the TIMG0 watchdog meanwhile, it would cut the pattern short
+fn wait_ms(duration_ms: u32) {
+    let start = now_us();
+    while now_us().wrapping_sub(start) < duration_ms * 1_000 {
+        unsafe {
+            TIMG0_WDT_PROTECT.write_volatile(WDT_UNLOCK);
+            TIMG0_WDT_FEED.write_volatile(1);
+            TIMG0_WDT_PROTECT.write_volatile(0);
+        }
+    }
+}
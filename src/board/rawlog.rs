@@ -0,0 +1,181 @@
+// The raw acceleration log for the `raw-log` feature, the blocks of
+// `src/rawlog.rs` in the flash region `board::flash` puts them in.
+// One flash operation a tick at most, right after the tick's sample: a
+// page, or the erase ahead of the next block. An erase blocks the CPU for
+// ~45 ms, up to 400 ms on a worn sector, and the slots whose deadline went
+// by meanwhile are logged as a gap rather than read late.
+// A dump prints a line every RAW_DUMP_PERIOD_MS, the log keeps filling.
+
+use rs_esp32_simple_preventive_maintenance_example::{
+    rawlog::{
+        self, BlockHeader, RawOp, RAW_BLOCK_LEN, RAW_DUMP_LINE, RAW_HEADER_LEN, RAW_LOG_SECTORS,
+        RAW_PERIOD_MS,
+    },
+    RawEncoding, RawLog, RawRing, Slot,
+};
+
+use super::{flash, time};
+
+// Blank sectors skipped in one dump line's time, at 32 bytes each
+const HEADERS_PER_LINE: usize = 16;
+
+struct Dump {
+    encoding: RawEncoding,
+    // The oldest block as it started, and how far past it
+    start: usize,
+    position: usize,
+    // The blocks written since are left out
+    until_seq: u32,
+    // The block loaded: its length, and how much of it is out
+    loaded: Option<(usize, usize)>,
+    blocks: u32,
+    corrupt: u32,
+}
+
+pub struct Recorder {
+    log: RawLog,
+    // Slots an erase went over
+    skip: u32,
+    failed: u32,
+    dump: Option<Dump>,
+    block: [u8; RAW_BLOCK_LEN],
+}
+
+impl Recorder {
+    pub fn new(ring: RawRing, acc_lsb_per_g: u16) -> Self {
+        Self {
+            log: RawLog::new(ring, acc_lsb_per_g),
+            skip: 0,
+            failed: 0,
+            dump: None,
+            block: [0; RAW_BLOCK_LEN],
+        }
+    }
+
+    // Whether this slot's sample is wanted, rather than one read late
+    pub fn wants_sample(&self) -> bool {
+        self.skip == 0
+    }
+
+    // The `raw-log` task: the slot's sample, then the flash operation due
+    pub fn record(&mut self, t_ms: u64, acc: Option<[i16; 3]>) {
+        let acc = match self.skip {
+            0 => acc,
+            _ => {
+                self.skip -= 1;
+                None
+            }
+        };
+        self.log.push(t_ms, acc);
+
+        let Some(op) = self.log.next_op() else {
+            return;
+        };
+        let erase = matches!(op, RawOp::Erase(_));
+        let started_us = time::awake_us();
+        if let Err(error) = flash::apply_raw(op) {
+            self.failed += 1;
+            warn!(
+                "WARNING: raw log flash {} failed: {:?}",
+                if erase { "erase" } else { "write" },
+                error
+            );
+        }
+        let took_ms = (time::awake_us().wrapping_sub(started_us) / 1_000) as u32;
+        self.skip = took_ms / RAW_PERIOD_MS;
+        self.log.done();
+    }
+
+    pub fn log(&self) -> &RawLog {
+        &self.log
+    }
+
+    pub fn failed(&self) -> u32 {
+        self.failed
+    }
+
+    pub fn is_dumping(&self) -> bool {
+        self.dump.is_some()
+    }
+
+    // The newest `blocks`, or all of them
+    pub fn dump(&mut self, encoding: RawEncoding, blocks: Option<u16>) {
+        let ring = self.log.ring();
+        let position = match blocks {
+            Some(blocks) => RAW_LOG_SECTORS.saturating_sub(blocks as usize),
+            None => 0,
+        };
+        println!(
+            "RAW begin, {} lines of {} bytes, a `RAW block` line before each block",
+            encoding.name(),
+            RAW_DUMP_LINE
+        );
+        self.dump = Some(Dump {
+            encoding,
+            start: ring.blocks().next().unwrap_or(0),
+            position,
+            until_seq: ring.next_seq(),
+            loaded: None,
+            blocks: 0,
+            corrupt: 0,
+        });
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(dump) = self.dump.take() {
+            println!("RAW end, stopped after {} blocks", dump.blocks);
+        }
+    }
+
+    // The `raw-dump` task, a line a run
+    pub fn dump_line(&mut self) {
+        let Some(dump) = &mut self.dump else {
+            return;
+        };
+        if let Some((len, printed)) = dump.loaded {
+            if printed < len {
+                let end = (printed + RAW_DUMP_LINE).min(len);
+                println!(
+                    "{}",
+                    rawlog::encode_line(dump.encoding, &self.block[printed..end])
+                );
+                dump.loaded = Some((len, end));
+                return;
+            }
+            dump.loaded = None;
+        }
+
+        for _ in 0..HEADERS_PER_LINE {
+            if dump.position >= RAW_LOG_SECTORS {
+                println!("RAW end, {} blocks, {} corrupt", dump.blocks, dump.corrupt);
+                self.dump = None;
+                return;
+            }
+            let block = (dump.start + dump.position) % RAW_LOG_SECTORS;
+            dump.position += 1;
+
+            let mut header = [0xff; RAW_HEADER_LEN];
+            if flash::read_raw(block, &mut header).is_err() {
+                dump.corrupt += 1;
+                continue;
+            }
+            match BlockHeader::decode(&header) {
+                Slot::Blank => {}
+                // Written over since the dump started
+                Slot::Record(header) if header.seq >= dump.until_seq => {}
+                Slot::Record(header) => {
+                    let read = flash::read_raw(block, &mut self.block);
+                    if read.is_err() || !header.verify(&self.block) {
+                        dump.corrupt += 1;
+                        continue;
+                    }
+                    println!("RAW block {}", header.seq);
+                    dump.blocks += 1;
+                    dump.loaded = Some((RAW_HEADER_LEN + header.len as usize, 0));
+                    return;
+                }
+                Slot::Corrupt => dump.corrupt += 1,
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use core::ptr::{addr_of, addr_of_mut};
+
+use hal::macros::ram;
+use rs_esp32_simple_preventive_maintenance_example::{record::RECORD_WORDS, EventRecord};
+
+// Left alone by the startup code, so it keeps its contents across resets
+// and deep sleep. Only the main loop touches it.
+#[ram(rtc_slow, uninitialized)]
+static mut RECORD: [u32; RECORD_WORDS] = [0; RECORD_WORDS];
+
+// The record from before the reset, or a zeroed one and false if there's
+// none (first power-on) or it didn't survive intact
+pub fn load() -> (EventRecord, bool) {
+    let words = unsafe { addr_of!(RECORD).read_volatile() };
+    match EventRecord::decode(&words) {
+        Some(record) => (record, true),
+        None => (EventRecord::new(), false),
+    }
+}
+
+pub fn store(record: &EventRecord) {
+    unsafe { addr_of_mut!(RECORD).write_volatile(record.encode()) };
+}
@@ -0,0 +1,311 @@
+// The microSD card for the `sd-card` feature, FAT16 or FAT32, on SPI2:
+// SCK GPIO14, MOSI GPIO13, MISO GPIO32 and CS GPIO15, set in `main()`.
+// The card is mounted at 400 kHz, as it has to be, then run at 10 MHz.
+//
+// Every write opens the file, appends the rows `SdLog` kept and closes it
+// again, which is what updates its directory entry; a card pulled between
+// two writes loses nothing that was written. A missing card or a failed
+// write is a warning, and the next write after `SD_RETRY_MS` mounts it again.
+
+use core::fmt::Write;
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_sdmmc::{
+    Directory, Error, Mode, SdCard, SdCardError, TimeSource, Timestamp, Volume, VolumeIdx,
+    VolumeManager,
+};
+use hal::{
+    clock::Clocks,
+    gpio::{Gpio13, Gpio14, Gpio15, Gpio32, Output, PushPull, Unknown},
+    peripherals::SPI2,
+    prelude::*,
+    spi::{Spi, SpiMode},
+    system::PeripheralClockControl,
+    Delay,
+};
+use heapless::String;
+use rs_esp32_simple_preventive_maintenance_example::{
+    sdlog::{self, MAX_LOG_NUMBER, ROTATE_BYTES, SD_COLUMNS, SD_RETRY_MS},
+    SdLog,
+};
+
+const INIT_KHZ: u32 = 400;
+const RUN_KHZ: u32 = 10_000;
+// What `tail` reads back from the end of the file
+const TAIL_BYTES: usize = 1024;
+
+type Device = SdCard<Spi<'static, SPI2>, Gpio15<Output<PushPull>>, Delay>;
+type SdError = Error<SdCardError>;
+
+// The rows have their own time, the files are all dated with the firmware's
+// year rather than 1980
+struct FixedTime;
+
+impl TimeSource for FixedTime {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp::from_calendar(2026, 1, 1, 0, 0, 0).unwrap()
+    }
+}
+
+struct Mounted {
+    volume: Volume,
+    root: Directory,
+    // The file being appended to, and its length
+    number: u16,
+    length: u32,
+}
+
+pub struct Card<'d> {
+    volumes: VolumeManager<Device, FixedTime>,
+    clocks: &'d Clocks<'d>,
+    mounted: Option<Mounted>,
+    // When mounting last failed, or a write
+    failed_ms: Option<u32>,
+}
+
+impl<'d> Card<'d> {
+    pub fn new(
+        spi: SPI2,
+        sck: Gpio14<Unknown>,
+        mosi: Gpio13<Unknown>,
+        miso: Gpio32<Unknown>,
+        cs: Gpio15<Unknown>,
+        clocks: &'d Clocks<'d>,
+        peripheral_clock_control: &mut PeripheralClockControl,
+    ) -> Self {
+        let spi = Spi::new_no_cs(
+            spi,
+            sck,
+            mosi,
+            miso,
+            INIT_KHZ.kHz(),
+            SpiMode::Mode0,
+            peripheral_clock_control,
+            clocks,
+        );
+        let mut cs = cs.into_push_pull_output();
+        cs.set_high().ok();
+        let device = SdCard::new(spi, cs, Delay::new(clocks));
+        Self {
+            volumes: VolumeManager::new(device, FixedTime),
+            clocks,
+            mounted: None,
+            failed_ms: None,
+        }
+    }
+
+    // The `sd-card` task: the rows kept since the last time, mounting the
+    // card first if it isn't. The rows stay in `log` until they're written.
+    pub fn write(&mut self, now_ms: u32, log: &mut SdLog) {
+        if self.mounted.is_none() {
+            let due = match self.failed_ms {
+                Some(failed_ms) => now_ms.wrapping_sub(failed_ms) >= SD_RETRY_MS,
+                None => true,
+            };
+            if !due {
+                return;
+            }
+            match self.mount() {
+                Ok(mounted) => {
+                    info!(
+                        "SD: appending to {}, {} bytes",
+                        sdlog::file_name(mounted.number),
+                        mounted.length
+                    );
+                    self.mounted = Some(mounted);
+                    self.failed_ms = None;
+                }
+                Err(error) => {
+                    warn!(
+                        "WARNING: SD card not mounted ({:?}), trying again in {} s",
+                        error,
+                        SD_RETRY_MS / 1_000
+                    );
+                    self.failed_ms = Some(now_ms);
+                    return;
+                }
+            }
+        }
+        if log.pending().is_empty() {
+            return;
+        }
+        match self.append(log.pending()) {
+            Ok(()) => log.written(),
+            Err(error) => {
+                warn!(
+                    "WARNING: SD write failed ({:?}), {} bytes kept, mounting again in {} s",
+                    error,
+                    log.pending().len(),
+                    SD_RETRY_MS / 1_000
+                );
+                self.unmount();
+                self.failed_ms = Some(now_ms);
+            }
+        }
+    }
+
+    // The log being appended to and its length, None without a card
+    pub fn current(&self) -> Option<(u16, u32)> {
+        self.mounted
+            .as_ref()
+            .map(|mounted| (mounted.number, mounted.length))
+    }
+
+    // The card's root directory, for `ls`
+    pub fn list(&mut self) {
+        let Some(mounted) = &self.mounted else {
+            println!("ERROR: no SD card mounted");
+            return;
+        };
+        let listed = self
+            .volumes
+            .iterate_dir(&mounted.volume, &mounted.root, |entry| {
+                if !entry.attributes.is_directory() && !entry.attributes.is_volume() {
+                    println!("{} {}", entry.name, entry.size);
+                }
+            });
+        if let Err(error) = listed {
+            println!("ERROR: SD card read failed ({:?})", error);
+        }
+    }
+
+    // The last whole rows of the file being appended to, for `tail`
+    pub fn tail(&mut self) {
+        let Some(mounted) = &mut self.mounted else {
+            println!("ERROR: no SD card mounted");
+            return;
+        };
+        let mut bytes = [0; TAIL_BYTES];
+        let name = sdlog::file_name(mounted.number);
+        let read = match self.volumes.open_file_in_dir(
+            &mut mounted.volume,
+            &mounted.root,
+            name.as_str(),
+            Mode::ReadOnly,
+        ) {
+            Ok(mut file) => {
+                // Within the file, it can't fail
+                file.seek_from_end(file.length().min(TAIL_BYTES as u32))
+                    .ok();
+                let read = self.volumes.read(&mounted.volume, &mut file, &mut bytes);
+                self.volumes.close_file(&mounted.volume, file).and(read)
+            }
+            Err(error) => Err(error),
+        };
+        match read {
+            Ok(len) => {
+                let bytes = &bytes[..len];
+                // From the first row that's there whole, unless it's the whole file
+                let start = if len < TAIL_BYTES {
+                    0
+                } else {
+                    bytes
+                        .iter()
+                        .position(|byte| *byte == b'\n')
+                        .map_or(0, |i| i + 1)
+                };
+                println!("{}:", name);
+                for row in bytes[start..].split(|byte| *byte == b'\n') {
+                    if let Ok(row) = core::str::from_utf8(row) {
+                        if !row.is_empty() {
+                            println!("{}", row);
+                        }
+                    }
+                }
+            }
+            Err(error) => println!("ERROR: SD card read failed ({:?})", error),
+        }
+    }
+
+    fn mount(&mut self) -> Result<Mounted, SdError> {
+        // At the init speed again after a failure, and made to go through
+        // the card's init
+        let clocks = self.clocks;
+        let device = self.volumes.device();
+        device.spi(|spi| spi.change_bus_frequency(INIT_KHZ.kHz(), clocks));
+        device.mark_card_uninit();
+        let volume = self.volumes.get_volume(VolumeIdx(0))?;
+        self.volumes
+            .device()
+            .spi(|spi| spi.change_bus_frequency(RUN_KHZ.kHz(), clocks));
+        let root = self.volumes.open_root_dir(&volume)?;
+
+        // The newest log, carried on with if it has room left
+        let mut newest: Option<(u16, u32)> = None;
+        self.volumes.iterate_dir(&volume, &root, |entry| {
+            let mut name = String::<12>::new();
+            write!(name, "{}", entry.name).ok();
+            if let Some(number) = sdlog::file_number(&name) {
+                if !matches!(newest, Some((newest, _)) if newest > number) {
+                    newest = Some((number, entry.size));
+                }
+            }
+        })?;
+        let (number, length) = match newest {
+            None => (0, 0),
+            Some((number, length)) if length < ROTATE_BYTES || number == MAX_LOG_NUMBER => {
+                (number, length)
+            }
+            Some((number, _)) => (number + 1, 0),
+        };
+        let mut mounted = Mounted {
+            volume,
+            root,
+            number,
+            length,
+        };
+        if length == 0 {
+            self.start(&mut mounted)?;
+        }
+        Ok(mounted)
+    }
+
+    // A new file's header
+    fn start(&mut self, mounted: &mut Mounted) -> Result<(), SdError> {
+        let mut header = String::<{ SD_COLUMNS.len() + 1 }>::new();
+        header.push_str(SD_COLUMNS).ok();
+        header.push('\n').ok();
+        mounted.length = 0;
+        self.write_file(mounted, header.as_bytes())
+    }
+
+    fn append(&mut self, rows: &[u8]) -> Result<(), SdError> {
+        let Some(mut mounted) = self.mounted.take() else {
+            return Ok(());
+        };
+        let mut result = Ok(());
+        // The last file is appended to past 10 MB rather than overwritten
+        if mounted.length + rows.len() as u32 > ROTATE_BYTES && mounted.number < MAX_LOG_NUMBER {
+            mounted.number += 1;
+            info!("SD: {} started", sdlog::file_name(mounted.number));
+            result = self.start(&mut mounted);
+        }
+        let result = result.and_then(|()| self.write_file(&mut mounted, rows));
+        self.mounted = Some(mounted);
+        result
+    }
+
+    fn write_file(&mut self, mounted: &mut Mounted, bytes: &[u8]) -> Result<(), SdError> {
+        let name = sdlog::file_name(mounted.number);
+        let mut file = self.volumes.open_file_in_dir(
+            &mut mounted.volume,
+            &mounted.root,
+            name.as_str(),
+            Mode::ReadWriteCreateOrAppend,
+        )?;
+        let written = self.volumes.write(&mut mounted.volume, &mut file, bytes);
+        // Closing is what updates the directory entry, even after a failure
+        let length = file.length();
+        let closed = self.volumes.close_file(&mounted.volume, file);
+        written?;
+        closed?;
+        mounted.length = length;
+        Ok(())
+    }
+
+    fn unmount(&mut self) {
+        if let Some(mounted) = self.mounted.take() {
+            self.volumes.close_dir(&mounted.volume, mounted.root);
+        }
+    }
+}
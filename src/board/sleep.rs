@@ -0,0 +1,112 @@
+#[cfg(feature = "deep-sleep")]
+use core::time::Duration;
+use core::{
+    mem::MaybeUninit,
+    ptr::{addr_of, addr_of_mut},
+};
+
+use hal::macros::ram;
+use hal::reset::SleepSource;
+#[cfg(feature = "deep-sleep")]
+use hal::{
+    rtc_cntl::sleep::{Ext0WakeupSource, TimerWakeupSource, WakeupLevel},
+    Delay, Rtc,
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    sensor::{Model, SensorConfig},
+    Commissioning, DacMapping, DutyCycle, FlashLog, GyroBias, HoursRing, MaintenanceMonitor,
+    Profile, ResetCause, RunHours, Wake, WakeGuard,
+};
+
+use super::diagnostics::Boot;
+
+// What a wake-up from deep sleep carries on with instead of the boot checks
+// and calibration
+#[derive(Clone, Copy)]
+pub struct Resume {
+    pub sample_period_ms: u32,
+    pub gyro_bias: GyroBias,
+    pub dac: DacMapping,
+    pub address: u8,
+    pub model: Model,
+    pub sensor_config: SensorConfig,
+    pub motion_threshold: u8,
+    pub duty: DutyCycle,
+    pub wake_guard: WakeGuard,
+    // Where the flash event log goes on, without scanning it again
+    pub flash_log: FlashLog,
+    // and the running hours, with the time since their last save
+    pub hours: RunHours,
+    pub hours_ring: HoursRing,
+    // The commissioning baseline, without reading it again, and the samples
+    // its drift is measured with
+    pub baseline: Option<Commissioning>,
+    pub recent: Profile,
+    // Uptime the wake-up starts from, see `time::resume()`
+    pub uptime_ms: u64,
+}
+
+// The whole monitor, references, filters and trend windows alike, ~4.5 KB
+// of the 8 KB of RTC slow memory. Only trusted after a deep sleep wake-up
+// and with the mark, which is written last: a reset in between, or a
+// firmware built with another layout, starts over with a cold boot instead.
+#[ram(rtc_slow, uninitialized)]
+static mut MONITOR: MaybeUninit<MaintenanceMonitor> = MaybeUninit::uninit();
+#[ram(rtc_slow, uninitialized)]
+static mut RESUME: MaybeUninit<Resume> = MaybeUninit::uninit();
+#[ram(rtc_slow, uninitialized)]
+static mut MARK: u32 = 0;
+
+const SLEEP_MAGIC: u32 = 0x534c_5031;
+
+// What was kept before the deep sleep, None on any other boot (and always
+// without `deep-sleep`). Clears the mark, the state is only picked up once.
+pub fn restore(boot: &Boot) -> Option<(MaintenanceMonitor, Resume)> {
+    unsafe {
+        let marked = addr_of!(MARK).read_volatile() == SLEEP_MAGIC;
+        addr_of_mut!(MARK).write_volatile(0);
+        if boot.cause != ResetCause::DeepSleep || !marked {
+            return None;
+        }
+        Some((
+            addr_of!(MONITOR).read_volatile().assume_init(),
+            addr_of!(RESUME).read_volatile().assume_init(),
+        ))
+    }
+}
+
+// What ended the deep sleep: the RTC timer, or the MPU6050 INT on GPIO4
+pub fn wake_source(boot: &Boot) -> Wake {
+    match boot.wakeup {
+        SleepSource::Ext0 => Wake::Motion,
+        _ => Wake::Timer,
+    }
+}
+
+// Keeps the state in RTC memory and sleeps for `sleep_ms`, or until INT
+// goes high with `motion`. The chip then boots again from the start.
+#[cfg(feature = "deep-sleep")]
+pub fn sleep(
+    rtc: &mut Rtc,
+    delay: &mut Delay,
+    monitor: &MaintenanceMonitor,
+    resume: Resume,
+    sleep_ms: u32,
+    motion: bool,
+) -> ! {
+    unsafe {
+        // The monitor isn't Copy, this is the last use of it before the reset
+        core::ptr::copy_nonoverlapping(monitor, addr_of_mut!(MONITOR).cast(), 1);
+        addr_of_mut!(RESUME).write_volatile(MaybeUninit::new(resume));
+        addr_of_mut!(MARK).write_volatile(SLEEP_MAGIC);
+    }
+
+    let timer = TimerWakeupSource::new(Duration::from_millis(sleep_ms as u64));
+    match super::motion::take_pin() {
+        Some(mut pin) if motion => {
+            let ext0 = Ext0WakeupSource::new(&mut pin, WakeupLevel::High);
+            rtc.sleep_deep(&[&timer, &ext0], delay)
+        }
+        _ => rtc.sleep_deep(&[&timer], delay),
+    }
+}
@@ -0,0 +1,48 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use hal::{
+    gpio::{Event, Gpio25, Input, PullUp},
+    interrupt,
+    peripherals::Interrupt,
+    prelude::*,
+};
+use rs_esp32_simple_preventive_maintenance_example::{tachometer::PULSES_PER_REV, Tachometer};
+
+use super::time;
+
+// Hall-effect or optical sensor on GPIO25, open collector: it pulls the
+// line low while the magnet or mark passes. Each falling edge is
+// timestamped in the interrupt, the main loop reads the speed from them.
+static PIN: Mutex<RefCell<Option<Gpio25<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+static TACHOMETER: Mutex<RefCell<Tachometer>> =
+    Mutex::new(RefCell::new(Tachometer::new(PULSES_PER_REV)));
+
+pub fn init(mut pin: Gpio25<Input<PullUp>>) {
+    pin.listen(Event::FallingEdge);
+    critical_section::with(|cs| PIN.borrow_ref_mut(cs).replace(pin));
+
+    interrupt::enable(Interrupt::GPIO, interrupt::Priority::Priority2)
+        .expect("Error while enabling the tachometer interrupt");
+}
+
+// The speed in RPM since the last call, and the pulses dropped as bounce
+pub fn take() -> (f32, u32) {
+    let now_us = time::awake_us();
+    critical_section::with(|cs| {
+        let mut tachometer = TACHOMETER.borrow_ref_mut(cs);
+        (tachometer.take_rpm(now_us), tachometer.take_bounces())
+    })
+}
+
+// Called from the shared GPIO interrupt
+pub(super) fn on_interrupt() {
+    critical_section::with(|cs| {
+        let mut pin = PIN.borrow_ref_mut(cs);
+        let Some(pin) = pin.as_mut().filter(|pin| pin.is_interrupt_set()) else {
+            return;
+        };
+        pin.clear_interrupt();
+        TACHOMETER.borrow_ref_mut(cs).pulse(time::awake_us());
+    });
+}
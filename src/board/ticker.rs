@@ -0,0 +1,56 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use critical_section::Mutex;
+use hal::{
+    interrupt,
+    peripherals::{Interrupt, TIMG1},
+    prelude::*,
+    timer::{Timer, Timer0},
+};
+
+// Loop tick from TIMG1 timer 0. The interrupt only counts, the main loop
+// runs one iteration per count, so the tick rate doesn't depend on how long
+// an iteration takes as long as it catches up.
+static TIMER: Mutex<RefCell<Option<Timer<Timer0<TIMG1>>>>> = Mutex::new(RefCell::new(None));
+static TICKS: AtomicU32 = AtomicU32::new(0);
+static PERIOD_MS: AtomicU32 = AtomicU32::new(0);
+
+pub fn init(mut timer: Timer<Timer0<TIMG1>>, period_ms: u32) {
+    PERIOD_MS.store(period_ms, Ordering::Relaxed);
+    timer.start((period_ms as u64).millis());
+    timer.listen();
+    critical_section::with(|cs| TIMER.borrow_ref_mut(cs).replace(timer));
+
+    interrupt::enable(Interrupt::TG1_T0_LEVEL, interrupt::Priority::Priority1)
+        .expect("Error while enabling the tick interrupt");
+}
+
+// Timer ticks since `init()`
+pub fn ticks() -> u32 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// Blocks until the timer has ticked past `tick`
+pub fn wait(tick: u32) {
+    while ticks().wrapping_sub(tick) as i32 <= 0 {}
+}
+
+#[interrupt]
+fn TG1_T0_LEVEL() {
+    let ticked = critical_section::with(|cs| match TIMER.borrow_ref_mut(cs).as_mut() {
+        Some(timer) if timer.is_interrupt_set() => {
+            timer.clear_interrupt();
+            // The alarm has to be re-armed after every interrupt
+            timer.start((PERIOD_MS.load(Ordering::Relaxed) as u64).millis());
+            true
+        }
+        _ => false,
+    });
+
+    if ticked {
+        TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,55 @@
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use hal::{
+    peripherals::TIMG1,
+    timer::{Instance, Timer, Timer1},
+};
+
+// Uptime from TIMG1 timer 1, free-running from boot at 1 MHz. Its counter
+// is 64 bits wide, it doesn't wrap in the lifetime of the board.
+// Every log line and reading timestamp comes from here, so they line up.
+static TIMER: Mutex<RefCell<Option<Timer<Timer1<TIMG1>>>>> = Mutex::new(RefCell::new(None));
+// Uptime before the last deep sleep, the timer restarts with the chip
+static RESUMED_MS: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+
+const TICK_HZ: u32 = 1_000_000;
+
+// `apb_hz` is the timer's clock
+pub fn init(mut timer: Timer<Timer1<TIMG1>>, apb_hz: u32) {
+    timer.set_counter_active(false);
+    timer.set_divider((apb_hz / TICK_HZ) as u16);
+    timer.set_counter_decrementing(false);
+    timer.set_auto_reload(false);
+    timer.reset_counter();
+    timer.set_counter_active(true);
+    critical_section::with(|cs| TIMER.borrow_ref_mut(cs).replace(timer));
+}
+
+// Milliseconds since the cold boot, 0 before `init()`
+pub fn uptime_ms() -> u64 {
+    critical_section::with(|cs| RESUMED_MS.borrow(cs).get()) + awake_ms()
+}
+
+// The same in whole seconds, for the event log
+#[cfg(not(feature = "embassy"))]
+pub fn uptime_s() -> u32 {
+    (uptime_ms() / 1_000) as u32
+}
+
+// Milliseconds since `init()`, this boot or wake-up only
+pub fn awake_ms() -> u64 {
+    awake_us() / (TICK_HZ / 1_000) as u64
+}
+
+// The same in µs, for timing I2C transfers
+pub fn awake_us() -> u64 {
+    critical_section::with(|cs| TIMER.borrow_ref(cs).as_ref().map_or(0, |timer| timer.now()))
+}
+
+// After a deep sleep, uptime carries on from `uptime_ms`, the time the
+// board went to sleep plus how long it slept
+#[cfg(not(feature = "embassy"))]
+pub fn resume(uptime_ms: u64) {
+    critical_section::with(|cs| RESUMED_MS.borrow(cs).set(uptime_ms));
+}
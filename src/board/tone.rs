@@ -0,0 +1,87 @@
+use hal::{
+    gpio::OutputPin,
+    ledc::{
+        channel::{self, ChannelHW, ChannelIFace},
+        timer::{self, TimerIFace},
+        HighSpeed, LEDC,
+    },
+    prelude::*,
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    alarm::{tone, TONES_HZ},
+    Buzzer, Limit,
+};
+
+// Duty cycle of the square wave, 50% is the loudest a piezo gets
+pub const TONE_DUTY_PCT: u8 = 50;
+
+// One LEDC timer per tone
+pub type ToneTimers<'a> = [timer::Timer<'a, HighSpeed>; 4];
+
+pub fn timers(ledc: &LEDC) -> ToneTimers<'_> {
+    let mut timers = [
+        timer::Number::Timer0,
+        timer::Number::Timer1,
+        timer::Number::Timer2,
+        timer::Number::Timer3,
+    ]
+    .map(|number| ledc.get_timer::<HighSpeed>(number));
+
+    for (timer, tone_hz) in timers.iter_mut().zip(TONES_HZ) {
+        timer
+            .configure(timer::config::Config {
+                duty: timer::config::Duty::Duty5Bit,
+                clock_source: timer::HSClockSource::APBClk,
+                frequency: tone_hz.Hz(),
+            })
+            .expect("Error while configuring the buzzer tone timer");
+    }
+
+    timers
+}
+
+// Passive piezo driven by an LEDC channel.
+// Starting a tone points the channel at the timer for that limit's tone.
+pub struct ToneBuzzer<'a, O: OutputPin> {
+    channel: channel::Channel<'a, HighSpeed, O>,
+    timers: &'a ToneTimers<'a>,
+}
+
+impl<'a, O> ToneBuzzer<'a, O>
+where
+    O: OutputPin,
+    channel::Channel<'a, HighSpeed, O>: ChannelHW<O>,
+{
+    pub fn new(ledc: &'a LEDC, timers: &'a ToneTimers<'a>, pin: O) -> Self {
+        let mut channel = ledc.get_channel(channel::Number::Channel0, pin);
+        channel
+            .configure(channel::config::Config {
+                timer: &timers[0],
+                duty_pct: 0,
+                pin_config: channel::config::PinConfig::PushPull,
+            })
+            .expect("Error while configuring the buzzer channel");
+
+        Self { channel, timers }
+    }
+}
+
+impl<'a, O> Buzzer for ToneBuzzer<'a, O>
+where
+    O: OutputPin,
+    channel::Channel<'a, HighSpeed, O>: ChannelHW<O>,
+{
+    type Error = channel::Error;
+
+    fn start(&mut self, limit: &Limit) -> Result<(), Self::Error> {
+        self.channel.configure(channel::config::Config {
+            timer: &self.timers[tone(limit)],
+            duty_pct: TONE_DUTY_PCT,
+            pin_config: channel::config::PinConfig::PushPull,
+        })
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.channel.set_duty(0)
+    }
+}
@@ -0,0 +1,75 @@
+// The DAQ trigger for the `daq-trigger` feature: a pulse on GPIO23 the
+// moment a limit trips, for an external recorder to capture the waveform
+// on. The rising edge is set right after the sample's checks, before
+// anything is printed or the buzzer starts; the falling one in TIMG0
+// timer 1's interrupt, so the width doesn't depend on the 10 ms tick. The
+// pin floats until `init()`, a 10 kΩ pull-down keeps it low through a reset.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_hal::digital::v2::{OutputPin, StatefulOutputPin};
+use hal::{
+    gpio::{Gpio23, Output, PushPull, Unknown},
+    interrupt,
+    peripherals::{Interrupt, TIMG0},
+    prelude::*,
+    timer::{Timer, Timer1},
+};
+use rs_esp32_simple_preventive_maintenance_example::config::TRIGGER_WIDTH_MS;
+
+use super::time;
+
+static PIN: Mutex<RefCell<Option<Gpio23<Output<PushPull>>>>> = Mutex::new(RefCell::new(None));
+static TIMER: Mutex<RefCell<Option<Timer<Timer1<TIMG0>>>>> = Mutex::new(RefCell::new(None));
+
+// Low from here on
+pub fn init(pin: Gpio23<Unknown>, timer: Timer<Timer1<TIMG0>>) {
+    let mut pin = pin.into_push_pull_output();
+    pin.set_low().ok();
+    critical_section::with(|cs| {
+        PIN.borrow_ref_mut(cs).replace(pin);
+        TIMER.borrow_ref_mut(cs).replace(timer);
+    });
+
+    interrupt::enable(Interrupt::TG0_T1_LEVEL, interrupt::Priority::Priority2)
+        .expect("Error while enabling the trigger interrupt");
+}
+
+// Starts the pulse, and returns when its edge went out in awake time.
+// None if the last one is still going, it isn't stretched.
+pub fn fire() -> Option<u64> {
+    critical_section::with(|cs| {
+        let mut timer = TIMER.borrow_ref_mut(cs);
+        let timer = timer.as_mut()?;
+        let mut pin = PIN.borrow_ref_mut(cs);
+        let pin = pin.as_mut()?;
+        if pin.is_set_high().unwrap_or(false) {
+            return None;
+        }
+        pin.set_high().ok();
+        let edge_us = time::awake_us();
+        timer.start((TRIGGER_WIDTH_MS as u64).millis());
+        timer.listen();
+        Some(edge_us)
+    })
+}
+
+#[interrupt]
+fn TG0_T1_LEVEL() {
+    critical_section::with(|cs| {
+        let mut timer = TIMER.borrow_ref_mut(cs);
+        let Some(timer) = timer.as_mut() else {
+            return;
+        };
+        if !timer.is_interrupt_set() {
+            return;
+        }
+        timer.clear_interrupt();
+        // One shot, not re-armed like the tick's
+        timer.unlisten();
+        if let Some(pin) = PIN.borrow_ref_mut(cs).as_mut() {
+            pin.set_low().ok();
+        }
+    });
+}
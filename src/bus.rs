@@ -0,0 +1,344 @@
+use core::cell::RefCell;
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use heapless::{HistoryBuffer, Vec};
+
+// Range of 7-bit addresses probed, the rest are reserved
+pub const SCAN_FIRST: u8 = 0x08;
+pub const SCAN_LAST: u8 = 0x77;
+const SCAN_SIZE: usize = (SCAN_LAST - SCAN_FIRST + 1) as usize;
+
+// The MPU6050 answers on 0x68, or 0x69 with AD0 pulled up
+pub const MPU_ADDRESSES: [u8; 2] = [0x68, 0x69];
+
+// Addresses that ACK a zero-length write
+pub fn scan<I: Write>(i2c: &mut I) -> Vec<u8, SCAN_SIZE> {
+    (SCAN_FIRST..=SCAN_LAST)
+        .filter(|address| i2c.write(*address, &[]).is_ok())
+        .collect()
+}
+
+// Devices the firmware knows by their address, for the boot scan
+pub fn device_name(address: u8) -> Option<&'static str> {
+    match address {
+        0x20..=0x27 => Some("I/O expander (PCF8574/MCP23017)"),
+        0x3c => Some("SSD1306 display"),
+        0x48..=0x4b => Some("temperature sensor (TMP102/LM75)"),
+        0x68 | 0x69 => Some("MPU6050"),
+        0x6a | 0x6b => Some("LSM6DS3"),
+        _ => None,
+    }
+}
+
+// First MPU6050 address among the ones found
+pub fn mpu_address(found: &[u8]) -> Option<u8> {
+    MPU_ADDRESSES
+        .into_iter()
+        .find(|address| found.contains(address))
+}
+
+// Attempts per sensor read, and the pause between them
+pub const READ_ATTEMPTS: u8 = 3;
+pub const RETRY_DELAY_MS: u8 = 5;
+
+// Failed reads within ERROR_WINDOW_MS before the bus is recovered
+pub const ERROR_THRESHOLD: usize = 10;
+pub const ERROR_WINDOW_MS: u32 = 30_000;
+
+// What to do about a sample that couldn't be read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusAction {
+    // Skip the sample and carry on
+    Skip,
+    // Too many failures, re-initialize the sensor
+    Recover,
+    // Still failing right after a recovery, sound the error alarm
+    Escalate,
+}
+
+// Counts I2C failures so one bad transaction (long wires, motor EMI) only
+// costs a sample, while a bus that keeps failing gets recovered
+#[derive(Default)]
+pub struct BusHealth {
+    // When the last ERROR_THRESHOLD failures happened
+    failures: HistoryBuffer<u32, ERROR_THRESHOLD>,
+    last_recovery_ms: Option<u32>,
+    escalated: bool,
+    pub retries: u32,
+    pub skipped: u32,
+    pub recoveries: u32,
+}
+
+impl BusHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A read that only worked after retrying
+    pub fn retried(&mut self, attempts: u8) {
+        self.retries = self.retries.saturating_add(attempts as u32);
+    }
+
+    // Returns true if the bus is back after the error alarm
+    pub fn success(&mut self) -> bool {
+        core::mem::take(&mut self.escalated)
+    }
+
+    pub fn failure(&mut self, now_ms: u32) -> BusAction {
+        self.skipped = self.skipped.saturating_add(1);
+        self.failures.write(now_ms);
+
+        let oldest = self.failures.oldest_ordered().next().copied();
+        let burst = self.failures.len() == ERROR_THRESHOLD
+            && oldest.is_some_and(|oldest| now_ms.wrapping_sub(oldest) <= ERROR_WINDOW_MS);
+        let recovered_lately = self
+            .last_recovery_ms
+            .is_some_and(|last| now_ms.wrapping_sub(last) <= ERROR_WINDOW_MS);
+
+        if self.escalated || (burst && recovered_lately) {
+            self.escalated = true;
+            return BusAction::Escalate;
+        }
+        if !burst {
+            return BusAction::Skip;
+        }
+
+        self.failures.clear();
+        self.last_recovery_ms = Some(now_ms);
+        self.recoveries = self.recoveries.saturating_add(1);
+        BusAction::Recover
+    }
+
+    pub fn is_escalated(&self) -> bool {
+        self.escalated
+    }
+}
+
+// One I2C bus for several drivers, each of which wants to own one. They get
+// a BusProxy each, which borrows the bus for one transaction at a time.
+// Single core and never touched from an interrupt, so a RefCell will do:
+// no transaction can start while another one is running.
+pub struct SharedBus<I> {
+    i2c: RefCell<I>,
+}
+
+impl<I> SharedBus<I> {
+    pub fn new(i2c: I) -> Self {
+        Self {
+            i2c: RefCell::new(i2c),
+        }
+    }
+
+    pub fn proxy(&self) -> BusProxy<'_, I> {
+        BusProxy { bus: &self.i2c }
+    }
+}
+
+// What a transaction on a BusProxy can fail with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusError<E> {
+    // Another proxy's transaction still holds the bus. Can't happen from
+    // the main loop, it would take a driver calling another one mid-transfer.
+    Busy,
+    // From the I2C driver: a NACK, arbitration lost, a timeout
+    I2c(E),
+}
+
+pub struct BusProxy<'a, I> {
+    bus: &'a RefCell<I>,
+}
+
+impl<I> BusProxy<'_, I> {
+    fn transaction<T, E>(
+        &self,
+        run: impl FnOnce(&mut I) -> Result<T, E>,
+    ) -> Result<T, BusError<E>> {
+        let mut i2c = self.bus.try_borrow_mut().map_err(|_| BusError::Busy)?;
+        run(&mut i2c).map_err(BusError::I2c)
+    }
+}
+
+impl<I: Write> Write for BusProxy<'_, I> {
+    type Error = BusError<I::Error>;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(|i2c| i2c.write(address, bytes))
+    }
+}
+
+impl<I: Read> Read for BusProxy<'_, I> {
+    type Error = BusError<I::Error>;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(|i2c| i2c.read(address, buffer))
+    }
+}
+
+impl<I: WriteRead> WriteRead for BusProxy<'_, I> {
+    type Error = BusError<I::Error>;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.transaction(|i2c| i2c.write_read(address, bytes, buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        display::{DISPLAY_ADDRESS, PAGES},
+        sensor, MaintenanceMonitor, Oled, Reading, Screen, Thresholds, SAMPLE_PERIOD_MS,
+    };
+
+    struct Bus {
+        devices: &'static [u8],
+    }
+
+    impl Write for Bus {
+        type Error = ();
+
+        fn write(&mut self, address: u8, _bytes: &[u8]) -> Result<(), ()> {
+            if self.devices.contains(&address) {
+                Ok(())
+            } else {
+                Err(())
+            }
+        }
+    }
+
+    #[test]
+    fn scan_finds_the_mpu() {
+        let mut bus = Bus {
+            devices: &[0x3c, 0x69, 0x7f],
+        };
+
+        let found = scan(&mut bus);
+        assert_eq!(found, [0x3c, 0x69]);
+        assert_eq!(mpu_address(&found), Some(0x69));
+
+        let mut bus = Bus { devices: &[] };
+        assert_eq!(mpu_address(&scan(&mut bus)), None);
+    }
+
+    #[test]
+    fn proxies_share_the_bus() {
+        let bus = SharedBus::new(Bus {
+            devices: &[0x3c, 0x68],
+        });
+        let (mut mpu, mut display) = (bus.proxy(), bus.proxy());
+
+        assert_eq!(mpu.write(0x68, &[0x75]), Ok(()));
+        assert_eq!(display.write(0x3c, &[0x00, 0xaf]), Ok(()));
+        assert_eq!(display.write(0x3d, &[]), Err(BusError::I2c(())));
+        assert_eq!(scan(&mut mpu), [0x3c, 0x68]);
+
+        let held = bus.i2c.borrow_mut();
+        assert_eq!(display.write(0x3c, &[]), Err(BusError::Busy));
+        drop(held);
+        assert_eq!(display.write(0x3c, &[]), Ok(()));
+
+        assert_eq!(device_name(0x3c), Some("SSD1306 display"));
+        assert_eq!(device_name(0x6b), Some("LSM6DS3"));
+        assert_eq!(device_name(0x50), None);
+    }
+
+    // An MPU6050 register file at 0x68 and a display at 0x3c
+    struct Devices {
+        registers: [u8; 128],
+        display_writes: usize,
+    }
+
+    impl Write for Devices {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            match (address, bytes) {
+                (0x68, [register, data @ ..]) => {
+                    let start = *register as usize;
+                    self.registers[start..start + data.len()].copy_from_slice(data);
+                }
+                (0x3c, _) => self.display_writes += 1,
+                _ => return Err(()),
+            }
+            Ok(())
+        }
+    }
+
+    impl WriteRead for Devices {
+        type Error = ();
+
+        fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), ()> {
+            if address != 0x68 {
+                return Err(());
+            }
+            let start = bytes[0] as usize;
+            buffer.copy_from_slice(&self.registers[start..start + buffer.len()]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn two_drivers_on_one_bus() {
+        let mut registers = [0; 128];
+        registers[0x75] = 0x68;
+        let bus = SharedBus::new(Devices {
+            registers,
+            display_writes: 0,
+        });
+
+        let mut mpu = mpu6050::Mpu6050::new(bus.proxy());
+        let mut display = Oled::new(bus.proxy(), DISPLAY_ADDRESS);
+        display.init().unwrap();
+
+        let monitor = MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        for _ in 0..3 {
+            assert_eq!(sensor::who_am_i(&mut mpu).unwrap(), 0x68);
+            let reading = Reading::new([0.0, 0.0, 9.81], [0.0; 3], 25.0, 0);
+            display.show(&Screen::new(&reading, &monitor));
+            while display.is_flushing() {
+                display.flush_page().unwrap();
+                mpu.get_acc().unwrap();
+            }
+        }
+        // Init, then a command and a data write per page
+        assert_eq!(bus.i2c.borrow().display_writes, 1 + 3 * 2 * PAGES);
+    }
+
+    #[test]
+    fn sparse_failures_are_only_skipped() {
+        let mut health = BusHealth::new();
+
+        for i in 0..3 * ERROR_THRESHOLD as u32 {
+            assert_eq!(health.failure(i * 5_000), BusAction::Skip);
+        }
+        assert_eq!(health.skipped, 3 * ERROR_THRESHOLD as u32);
+    }
+
+    #[test]
+    fn burst_recovers_then_escalates() {
+        let mut health = BusHealth::new();
+        let mut now_ms = 0;
+        let mut burst = |health: &mut BusHealth| {
+            for _ in 1..ERROR_THRESHOLD {
+                assert_eq!(health.failure(now_ms), BusAction::Skip);
+                now_ms += 500;
+            }
+            let action = health.failure(now_ms);
+            now_ms += 500;
+            action
+        };
+
+        assert_eq!(burst(&mut health), BusAction::Recover);
+        assert_eq!(health.recoveries, 1);
+
+        assert_eq!(burst(&mut health), BusAction::Escalate);
+        assert!(health.is_escalated());
+        assert!(health.success());
+        assert!(!health.is_escalated());
+    }
+}
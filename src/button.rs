@@ -0,0 +1,61 @@
+// Software debounce for a push-button.
+// Contact bounce produces a burst of edges on every press, so after an
+// accepted press any further edge within the lockout window is ignored.
+
+pub const BUTTON_LOCKOUT_MS: u32 = 250;
+
+pub struct Debouncer {
+    lockout_ms: u32,
+    last_press_ms: Option<u32>,
+}
+
+impl Debouncer {
+    pub const fn new(lockout_ms: u32) -> Self {
+        Self {
+            lockout_ms,
+            last_press_ms: None,
+        }
+    }
+
+    // Registers an edge, returns true if it counts as a new press
+    pub fn press(&mut self, now_ms: u32) -> bool {
+        if let Some(last) = self.last_press_ms {
+            if now_ms.wrapping_sub(last) < self.lockout_ms {
+                return false;
+            }
+        }
+
+        self.last_press_ms = Some(now_ms);
+        true
+    }
+}
+
+impl Default for Debouncer {
+    fn default() -> Self {
+        Self::new(BUTTON_LOCKOUT_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounces_within_lockout_are_ignored() {
+        let mut button = Debouncer::new(250);
+
+        assert!(button.press(1_000));
+        assert!(!button.press(1_010));
+        assert!(!button.press(1_249));
+        assert!(button.press(1_250));
+    }
+
+    #[test]
+    fn survives_clock_wrap_around() {
+        let mut button = Debouncer::new(250);
+
+        assert!(button.press(u32::MAX - 100));
+        assert!(!button.press(50));
+        assert!(button.press(200));
+    }
+}
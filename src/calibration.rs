@@ -0,0 +1,251 @@
+use heapless::Vec;
+
+use crate::math;
+
+// Samples averaged into the boot references, taken every
+// CALIBRATION_PERIOD_MS (50 × 40 ms = 2 s)
+pub const CALIBRATION_SAMPLES: usize = 50;
+pub const CALIBRATION_PERIOD_MS: u32 = 40;
+
+// Samples further than this many standard deviations from the mean are
+// dropped before averaging
+pub const OUTLIER_SIGMAS: f32 = 3.0;
+
+// Above this accelerometer standard deviation (m/s^2) the machine was most
+// likely running or being handled during calibration
+pub const CALIBRATION_MAX_STD_DEV: f32 = 0.15;
+
+// Above this gyroscope standard deviation (rad/s) the device was moving and
+// the average is not a usable bias
+pub const GYRO_BIAS_MAX_STD_DEV: f32 = 0.02;
+
+// Extra windows taken when the first one is too noisy
+pub const CALIBRATION_RETRIES: u8 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub mean: f32,
+    pub std_dev: f32,
+    // Samples dropped as outliers
+    pub rejected: usize,
+}
+
+impl Stats {
+    // Mean and standard deviation of the samples within OUTLIER_SIGMAS
+    // of the raw mean
+    fn of(values: impl Iterator<Item = f32> + Clone) -> Self {
+        let (mean, std_dev) = mean_std_dev(values.clone());
+        let limit = OUTLIER_SIGMAS * std_dev;
+        let inliers = values
+            .clone()
+            .filter(|value| math::abs(value - mean) <= limit);
+
+        let (mean, std_dev) = mean_std_dev(inliers.clone());
+        Self {
+            mean,
+            std_dev,
+            rejected: values.count() - inliers.count(),
+        }
+    }
+}
+
+fn mean_std_dev(values: impl Iterator<Item = f32> + Clone) -> (f32, f32) {
+    let n = values.clone().count();
+    if n == 0 {
+        return (0.0, 0.0);
+    }
+
+    let mean = values.clone().sum::<f32>() / n as f32;
+    let variance = values
+        .map(|value| (value - mean) * (value - mean))
+        .sum::<f32>()
+        / n as f32;
+    (mean, math::sqrt(variance))
+}
+
+// Averaged readings of a calibration window
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Baseline {
+    pub acc: [Stats; 3],
+    pub gyro: [Stats; 3],
+    pub temp: Stats,
+}
+
+impl Baseline {
+    pub fn acc_mean(&self) -> [f32; 3] {
+        self.acc.map(|stats| stats.mean)
+    }
+
+    pub fn gyro_mean(&self) -> [f32; 3] {
+        self.gyro.map(|stats| stats.mean)
+    }
+
+    // False if the accelerometer was too noisy for a usable reference
+    pub fn is_steady(&self) -> bool {
+        self.acc
+            .iter()
+            .all(|stats| stats.std_dev <= CALIBRATION_MAX_STD_DEV)
+    }
+
+    // Per-axis gyroscope average, None if the device was moving
+    pub fn gyro_bias(&self) -> Option<[f32; 3]> {
+        self.gyro
+            .iter()
+            .all(|stats| stats.std_dev <= GYRO_BIAS_MAX_STD_DEV)
+            .then(|| self.gyro_mean())
+    }
+}
+
+// Constant offset of each gyroscope axis.
+// The MPU6050 reads a few hundredths of rad/s at rest, which would otherwise
+// show up in every rotational delta.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GyroBias {
+    offsets: [f32; 3],
+}
+
+impl GyroBias {
+    pub const fn new(offsets: [f32; 3]) -> Self {
+        Self { offsets }
+    }
+
+    // Takes the bias from a calibration window.
+    // Returns false and keeps the previous offsets if the device was moving.
+    pub fn update(&mut self, baseline: &Baseline) -> bool {
+        match baseline.gyro_bias() {
+            Some(offsets) => {
+                self.offsets = offsets;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn offsets(&self) -> [f32; 3] {
+        self.offsets
+    }
+
+    // Raw reading minus the bias
+    pub fn apply(&self, gyro: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = self.offsets;
+        [gyro[0] - x, gyro[1] - y, gyro[2] - z]
+    }
+}
+
+// Collects the boot samples the references are computed from.
+// A single reading at boot can be an outlier and would skew every
+// comparison after it.
+pub struct Calibration<const N: usize = CALIBRATION_SAMPLES> {
+    acc: Vec<[f32; 3], N>,
+    gyro: Vec<[f32; 3], N>,
+    temp: Vec<f32, N>,
+}
+
+impl<const N: usize> Calibration<N> {
+    pub const fn new() -> Self {
+        Self {
+            acc: Vec::new(),
+            gyro: Vec::new(),
+            temp: Vec::new(),
+        }
+    }
+
+    // Adds one sample, returns true once the window is full.
+    // Samples past a full window are ignored.
+    pub fn push(&mut self, acc: [f32; 3], gyro: [f32; 3], temp: f32) -> bool {
+        if !self.is_full() {
+            // Can't fail, all three have the same capacity
+            let _ = self.acc.push(acc);
+            let _ = self.gyro.push(gyro);
+            let _ = self.temp.push(temp);
+        }
+
+        self.is_full()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.temp.is_full()
+    }
+
+    pub fn baseline(&self) -> Baseline {
+        let axis = |samples: &Vec<[f32; 3], N>, i: usize| {
+            Stats::of(samples.iter().map(move |sample| sample[i]))
+        };
+
+        Baseline {
+            acc: [0, 1, 2].map(|i| axis(&self.acc, i)),
+            gyro: [0, 1, 2].map(|i| axis(&self.gyro, i)),
+            temp: Stats::of(self.temp.iter().copied()),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.acc.clear();
+        self.gyro.clear();
+        self.temp.clear();
+    }
+}
+
+impl<const N: usize> Default for Calibration<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STILL: [f32; 3] = [0.0; 3];
+
+    #[test]
+    fn outlier_is_dropped_from_the_mean() {
+        let mut calibration: Calibration = Calibration::new();
+        for i in 0..CALIBRATION_SAMPLES {
+            let z = if i == 10 {
+                15.0
+            } else {
+                9.8 + (i % 2) as f32 * 0.02
+            };
+            calibration.push([0.0, 0.0, z], STILL, 25.0);
+        }
+        assert!(calibration.is_full());
+
+        let baseline = calibration.baseline();
+        assert_eq!(baseline.acc[2].rejected, 1);
+        assert!(math::abs(baseline.acc[2].mean - 9.81) < 0.001);
+        assert!(baseline.acc[2].std_dev < 0.02);
+        assert_eq!(baseline.temp.mean, 25.0);
+        assert!(baseline.is_steady());
+    }
+
+    #[test]
+    fn vibration_is_not_steady() {
+        let mut calibration: Calibration = Calibration::new();
+        let mut i = 0;
+        while !calibration.push([0.0, 0.0, 9.8 + (i % 2) as f32], STILL, 25.0) {
+            i += 1;
+        }
+
+        assert!(!calibration.baseline().is_steady());
+    }
+
+    #[test]
+    fn gyro_bias_is_kept_while_moving() {
+        let mut calibration: Calibration = Calibration::new();
+        while !calibration.push([0.0, 0.0, 9.8], [0.0, 0.0, 0.125], 25.0) {}
+
+        let mut bias = GyroBias::default();
+        assert!(bias.update(&calibration.baseline()));
+        assert_eq!(bias.offsets(), [0.0, 0.0, 0.125]);
+        assert_eq!(bias.apply([0.25, 0.0, 0.125]), [0.25, 0.0, 0.0]);
+
+        calibration.clear();
+        let mut i = 0;
+        while !calibration.push([0.0, 0.0, 9.8], [0.0, 0.0, (i % 2) as f32], 25.0) {
+            i += 1;
+        }
+        assert!(!bias.update(&calibration.baseline()));
+        assert_eq!(bias.offsets(), [0.0, 0.0, 0.125]);
+    }
+}
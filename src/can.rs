@@ -0,0 +1,428 @@
+use heapless::Deque;
+
+use crate::{math, Alert, Limit, Severity};
+
+// Classic CAN frames for a machine's backbone, 8 data bytes on standard
+// 11-bit IDs, see `config::CAN`. Little-endian. The readings, every
+// `CanConfig::period_ms`:
+//
+//   0  vibration RMS, i16, 0.01 m/s^2
+//   2  acceleration peak, i16, 0.01 m/s^2, the largest delta since the
+//      last sample
+//   4  temperature, i16, 0.01 ºC
+//   6  health score, u8, NO_HEALTH before the first minute
+//   7  counter, u8, one a readings frame, wraps
+//
+// and an alarm, as it fires, on its own ID:
+//
+//   0  limit, its index in `Limit::ALL`
+//   1  severity, 1 warning, 2 critical
+//   2  value, f32, in the limit's unit
+//   6  alarms latched, u8
+//   7  counter, u8, one an alarm frame, wraps
+//
+// A value there's none of, the RMS before its window is full or a NaN,
+// is NO_VALUE.
+pub const FRAME_LEN: usize = 8;
+pub const MAX_ID: u16 = 0x7ff;
+pub const OFFSET_VIBRATION: usize = 0;
+pub const OFFSET_PEAK: usize = 2;
+pub const OFFSET_TEMPERATURE: usize = 4;
+pub const OFFSET_HEALTH: usize = 6;
+pub const OFFSET_LIMIT: usize = 0;
+pub const OFFSET_SEVERITY: usize = 1;
+pub const OFFSET_VALUE: usize = 2;
+pub const OFFSET_LATCHED: usize = 6;
+pub const OFFSET_COUNTER: usize = 7;
+
+pub const ACCELERATION_SCALE: f32 = 100.0;
+pub const TEMPERATURE_SCALE: f32 = 100.0;
+pub const NO_VALUE: i16 = i16::MIN;
+pub const NO_HEALTH: u8 = 0xff;
+
+// Tries per frame while the controller's transmit buffer is still busy,
+// one per tick
+pub const SEND_TRIES: u8 = 3;
+// Alarms waiting to go, past this the oldest is dropped
+pub const QUEUE_FRAMES: usize = 4;
+// After a bus-off, the first restart and the longest wait between two
+pub const RESTART_MS: u32 = 1_000;
+pub const MAX_RESTART_MS: u32 = 60_000;
+
+pub type Frame = [u8; FRAME_LEN];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CanMessage {
+    Readings {
+        vibration_rms: Option<f32>,
+        acc_peak: Option<f32>,
+        temp: Option<f32>,
+        health: Option<u8>,
+    },
+    Alarm {
+        limit: Limit,
+        severity: Severity,
+        value: f32,
+        latched: u8,
+    },
+}
+
+impl CanMessage {
+    pub fn alarm(alert: &Alert, value: f32, latched: u8) -> Self {
+        CanMessage::Alarm {
+            limit: alert.limit,
+            severity: alert.severity,
+            value,
+            latched,
+        }
+    }
+
+    pub fn is_alarm(&self) -> bool {
+        matches!(self, CanMessage::Alarm { .. })
+    }
+
+    pub fn encode(&self, counter: u8) -> Frame {
+        let mut frame = [0; FRAME_LEN];
+        match *self {
+            CanMessage::Readings {
+                vibration_rms,
+                acc_peak,
+                temp,
+                health,
+            } => {
+                let vibration = scaled(vibration_rms, ACCELERATION_SCALE);
+                frame[OFFSET_VIBRATION..OFFSET_PEAK].copy_from_slice(&vibration.to_le_bytes());
+                let peak = scaled(acc_peak, ACCELERATION_SCALE);
+                frame[OFFSET_PEAK..OFFSET_TEMPERATURE].copy_from_slice(&peak.to_le_bytes());
+                let temp = scaled(temp, TEMPERATURE_SCALE);
+                frame[OFFSET_TEMPERATURE..OFFSET_HEALTH].copy_from_slice(&temp.to_le_bytes());
+                frame[OFFSET_HEALTH] = health.unwrap_or(NO_HEALTH);
+            }
+            CanMessage::Alarm {
+                limit,
+                severity,
+                value,
+                latched,
+            } => {
+                frame[OFFSET_LIMIT] = limit as u8;
+                frame[OFFSET_SEVERITY] = match severity {
+                    Severity::Warning => 1,
+                    Severity::Critical => 2,
+                };
+                frame[OFFSET_VALUE..OFFSET_LATCHED].copy_from_slice(&value.to_le_bytes());
+                frame[OFFSET_LATCHED] = latched;
+            }
+        }
+        frame[OFFSET_COUNTER] = counter;
+        frame
+    }
+
+    // For a receiver, the counter and the readings, back in m/s^2 and ºC
+    pub fn decode_readings(frame: &[u8]) -> Option<(u8, Self)> {
+        let frame: &Frame = frame.try_into().ok()?;
+        let value = |offset: usize, scale: f32| {
+            let raw = i16::from_le_bytes([frame[offset], frame[offset + 1]]);
+            (raw != NO_VALUE).then_some(raw as f32 / scale)
+        };
+        let readings = CanMessage::Readings {
+            vibration_rms: value(OFFSET_VIBRATION, ACCELERATION_SCALE),
+            acc_peak: value(OFFSET_PEAK, ACCELERATION_SCALE),
+            temp: value(OFFSET_TEMPERATURE, TEMPERATURE_SCALE),
+            health: Some(frame[OFFSET_HEALTH]).filter(|health| *health != NO_HEALTH),
+        };
+        Some((frame[OFFSET_COUNTER], readings))
+    }
+
+    pub fn decode_alarm(frame: &[u8]) -> Option<(u8, Self)> {
+        let frame: &Frame = frame.try_into().ok()?;
+        let alarm = CanMessage::Alarm {
+            limit: *Limit::ALL.get(frame[OFFSET_LIMIT] as usize)?,
+            severity: match frame[OFFSET_SEVERITY] {
+                1 => Severity::Warning,
+                2 => Severity::Critical,
+                _ => return None,
+            },
+            value: f32::from_le_bytes(frame[OFFSET_VALUE..OFFSET_LATCHED].try_into().ok()?),
+            latched: frame[OFFSET_LATCHED],
+        };
+        Some((frame[OFFSET_COUNTER], alarm))
+    }
+}
+
+// Rounded to the frame's unit, saturated
+fn scaled(value: Option<f32>, scale: f32) -> i16 {
+    match value.filter(|value| !value.is_nan()) {
+        Some(value) => math::round(value * scale).clamp(-(i16::MAX as f32), i16::MAX as f32) as i16,
+        None => NO_VALUE,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CanCounters {
+    pub sent: u32,
+    // Frames that never went out: out of tries, pushed out of the queue,
+    // or due while the bus was off
+    pub tx_errors: u32,
+    pub bus_offs: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusEvent {
+    Off,
+    // The controller is to be reset, and goes through the bus's recovery
+    Restart,
+    Recovered,
+}
+
+// The frames waiting for the controller, alarms in the order they fired
+// and then the latest readings, and the bus-off recovery. Restarts back off
+// from RESTART_MS to MAX_RESTART_MS until a frame goes out again, so a dead
+// bus isn't hammered.
+pub struct CanLink<const N: usize = QUEUE_FRAMES> {
+    alarms: Deque<Frame, N>,
+    readings: Option<Frame>,
+    readings_counter: u8,
+    alarm_counter: u8,
+    tries: u8,
+    // Set while the bus is off
+    restart_at_ms: Option<u32>,
+    restarts: u8,
+    counters: CanCounters,
+}
+
+impl<const N: usize> CanLink<N> {
+    pub const fn new() -> Self {
+        Self {
+            alarms: Deque::new(),
+            readings: None,
+            readings_counter: 0,
+            alarm_counter: 0,
+            tries: 0,
+            restart_at_ms: None,
+            restarts: 0,
+            counters: CanCounters {
+                sent: 0,
+                tx_errors: 0,
+                bus_offs: 0,
+            },
+        }
+    }
+
+    pub fn push(&mut self, message: CanMessage) {
+        if self.is_off() {
+            self.counters.tx_errors += 1;
+            return;
+        }
+        if message.is_alarm() {
+            let frame = message.encode(self.alarm_counter);
+            self.alarm_counter = self.alarm_counter.wrapping_add(1);
+            // The readings' tries aren't this one's
+            if self.alarms.is_empty() {
+                self.tries = 0;
+            }
+            if self.alarms.is_full() {
+                self.alarms.pop_front();
+                self.counters.tx_errors += 1;
+                self.tries = 0;
+            }
+            self.alarms.push_back(frame).ok();
+        } else {
+            let frame = message.encode(self.readings_counter);
+            self.readings_counter = self.readings_counter.wrapping_add(1);
+            // The newer ones replace them
+            if self.readings.replace(frame).is_some() {
+                self.counters.tx_errors += 1;
+            }
+            if self.alarms.is_empty() {
+                self.tries = 0;
+            }
+        }
+    }
+
+    // The frame to try now, whether it's an alarm's, the same one until
+    // `sent()` says it went
+    pub fn outgoing(&self) -> Option<(bool, &Frame)> {
+        if self.is_off() {
+            return None;
+        }
+        match self.alarms.front() {
+            Some(frame) => Some((true, frame)),
+            None => self.readings.as_ref().map(|frame| (false, frame)),
+        }
+    }
+
+    // Whether the controller took the frame `outgoing()` gave
+    pub fn sent(&mut self, taken: bool) {
+        self.tries += 1;
+        if !taken && self.tries < SEND_TRIES {
+            return;
+        }
+        self.tries = 0;
+        if taken {
+            self.counters.sent += 1;
+            self.restarts = 0;
+        } else {
+            self.counters.tx_errors += 1;
+        }
+        if self.alarms.pop_front().is_none() {
+            self.readings = None;
+        }
+    }
+
+    // With the controller's bus-off flag, every tick
+    pub fn update(&mut self, bus_off: bool, now_ms: u32) -> Option<BusEvent> {
+        match (self.restart_at_ms, bus_off) {
+            (None, false) => None,
+            (None, true) => {
+                self.counters.bus_offs += 1;
+                let dropped = self.alarms.len() + self.readings.iter().count();
+                self.counters.tx_errors += dropped as u32;
+                self.alarms.clear();
+                self.readings = None;
+                self.tries = 0;
+                self.restart_at_ms = Some(now_ms.wrapping_add(self.restart_wait_ms()));
+                Some(BusEvent::Off)
+            }
+            (Some(restart_at_ms), true) if now_ms.wrapping_sub(restart_at_ms) as i32 >= 0 => {
+                self.restarts = self.restarts.saturating_add(1);
+                self.restart_at_ms = Some(now_ms.wrapping_add(self.restart_wait_ms()));
+                Some(BusEvent::Restart)
+            }
+            (Some(_), true) => None,
+            (Some(_), false) => {
+                self.restart_at_ms = None;
+                Some(BusEvent::Recovered)
+            }
+        }
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.restart_at_ms.is_some()
+    }
+
+    pub fn counters(&self) -> CanCounters {
+        self.counters
+    }
+
+    fn restart_wait_ms(&self) -> u32 {
+        let doubled = RESTART_MS
+            .checked_shl(self.restarts as u32)
+            .unwrap_or(u32::MAX);
+        doubled.min(MAX_RESTART_MS)
+    }
+}
+
+impl<const N: usize> Default for CanLink<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readings() -> CanMessage {
+        CanMessage::Readings {
+            vibration_rms: Some(1.25),
+            acc_peak: Some(-0.5),
+            temp: Some(31.07),
+            health: Some(87),
+        }
+    }
+
+    fn alarm(value: f32) -> CanMessage {
+        CanMessage::Alarm {
+            limit: Limit::Vibration,
+            severity: Severity::Critical,
+            value,
+            latched: 2,
+        }
+    }
+
+    #[test]
+    fn frames_follow_the_layout() {
+        let frame = readings().encode(0x41);
+        assert_eq!(frame, [0x7d, 0x00, 0xce, 0xff, 0x23, 0x0c, 87, 0x41]);
+        assert_eq!(
+            CanMessage::decode_readings(&frame),
+            Some((0x41, readings()))
+        );
+
+        let empty = CanMessage::Readings {
+            vibration_rms: None,
+            acc_peak: Some(1_000.0),
+            temp: Some(f32::NAN),
+            health: None,
+        };
+        assert_eq!(
+            empty.encode(0),
+            [0x00, 0x80, 0xff, 0x7f, 0x00, 0x80, 0xff, 0x00]
+        );
+
+        let frame = alarm(12.5).encode(0xff);
+        assert_eq!(frame, [5, 2, 0x00, 0x00, 0x48, 0x41, 2, 0xff]);
+        assert_eq!(CanMessage::decode_alarm(&frame), Some((0xff, alarm(12.5))));
+        assert_eq!(CanMessage::decode_alarm(&[13, 2, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(CanMessage::decode_alarm(&[0, 3, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(CanMessage::decode_readings(&frame[..7]), None);
+    }
+
+    #[test]
+    fn alarms_go_first_and_busy_frames_are_tried_again() {
+        let mut link: CanLink<2> = CanLink::new();
+        link.push(readings());
+        link.push(alarm(1.0));
+        link.push(alarm(2.0));
+        assert_eq!(link.outgoing(), Some((true, &alarm(1.0).encode(0))));
+        link.sent(true);
+        // The transmit buffer stays busy for the second one
+        for _ in 0..SEND_TRIES {
+            assert_eq!(link.outgoing(), Some((true, &alarm(2.0).encode(1))));
+            link.sent(false);
+        }
+        assert_eq!(link.outgoing(), Some((false, &readings().encode(0))));
+        link.sent(true);
+        assert_eq!(link.outgoing(), None);
+
+        // A third alarm pushes the oldest out, newer readings replace theirs
+        link.push(readings());
+        link.push(readings());
+        for value in [3.0, 4.0, 5.0] {
+            link.push(alarm(value));
+        }
+        assert_eq!(link.outgoing(), Some((true, &alarm(4.0).encode(3))));
+        let counters = link.counters();
+        assert_eq!((counters.sent, counters.tx_errors), (2, 3));
+    }
+
+    #[test]
+    fn recovers_from_bus_off_with_a_backoff() {
+        let mut link: CanLink = CanLink::new();
+        link.push(alarm(1.0));
+        assert_eq!(link.update(false, 0), None);
+        assert_eq!(link.update(true, 100), Some(BusEvent::Off));
+        assert!(link.is_off() && link.outgoing().is_none());
+        link.push(readings());
+        assert_eq!(link.counters().tx_errors, 2);
+
+        assert_eq!(link.update(true, 1_099), None);
+        assert_eq!(link.update(true, 1_100), Some(BusEvent::Restart));
+        // Still off, twice as long
+        assert_eq!(link.update(true, 3_099), None);
+        assert_eq!(link.update(true, 3_100), Some(BusEvent::Restart));
+        assert_eq!(link.update(false, 3_110), Some(BusEvent::Recovered));
+        assert!(!link.is_off());
+
+        // Off again before anything went out, the wait keeps growing
+        assert_eq!(link.update(true, 4_000), Some(BusEvent::Off));
+        assert_eq!(link.update(true, 7_999), None);
+        assert_eq!(link.update(true, 8_000), Some(BusEvent::Restart));
+        assert_eq!(link.update(false, 8_010), Some(BusEvent::Recovered));
+        link.push(readings());
+        link.sent(true);
+        assert_eq!(link.update(true, 9_000), Some(BusEvent::Off));
+        assert_eq!(link.update(true, 10_000), Some(BusEvent::Restart));
+        assert_eq!(link.counters().bus_offs, 3);
+    }
+}
@@ -0,0 +1,202 @@
+use core::fmt;
+
+use heapless::HistoryBuffer;
+
+use crate::Reading;
+
+// Readings kept from before an alarm, at SAMPLE_PERIOD_MS that's 32 s
+pub const PRE_TRIGGER_SAMPLES: usize = 64;
+
+// Fast capture after an alarm: 2 s at 50 Hz
+pub const POST_TRIGGER_MS: u32 = 2_000;
+pub const POST_TRIGGER_PERIOD_MS: u32 = 20;
+// One per period, plus the trigger sample itself
+pub const POST_TRIGGER_SAMPLES: usize = (POST_TRIGGER_MS / POST_TRIGGER_PERIOD_MS) as usize + 1;
+
+// Column names of `CsvLine`
+pub const CSV_HEADER: &str = "t_ms,ax,ay,az,gx,gy,gz,temp";
+
+// Last N readings, so an alarm can show how the event developed.
+// The oldest reading is overwritten, nothing is allocated.
+pub struct Capture<const N: usize = PRE_TRIGGER_SAMPLES> {
+    readings: HistoryBuffer<Reading, N>,
+}
+
+impl<const N: usize> Capture<N> {
+    pub fn new() -> Self {
+        Self {
+            readings: HistoryBuffer::new(),
+        }
+    }
+
+    pub fn push(&mut self, reading: Reading) {
+        self.readings.write(reading);
+    }
+
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.len() == 0
+    }
+
+    // Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Reading> {
+        self.readings.oldest_ordered()
+    }
+
+    pub fn clear(&mut self) {
+        self.readings.clear();
+    }
+}
+
+impl<const N: usize> Default for Capture<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Window of fast sampling opened when an alarm fires, it shows whether the
+// event was a one-off knock or the start of sustained chatter.
+// Only recorded, these readings don't go through detection, so they can't
+// drag the baseline along.
+pub struct PostTrigger {
+    readings: Capture<POST_TRIGGER_SAMPLES>,
+    origin_ms: Option<u64>,
+}
+
+impl PostTrigger {
+    pub fn new() -> Self {
+        Self {
+            readings: Capture::new(),
+            origin_ms: None,
+        }
+    }
+
+    // Starts a window at the trigger time.
+    // Returns false if one is already open, that one carries on.
+    pub fn open(&mut self, origin_ms: u64) -> bool {
+        if self.is_open() {
+            return false;
+        }
+
+        self.readings.clear();
+        self.origin_ms = Some(origin_ms);
+        true
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.origin_ms.is_some()
+    }
+
+    // Records a reading, returns true once the window is complete
+    pub fn push(&mut self, reading: Reading) -> bool {
+        let Some(origin_ms) = self.origin_ms else {
+            return false;
+        };
+
+        self.readings.push(reading);
+        reading.t_ms.saturating_sub(origin_ms) >= POST_TRIGGER_MS as u64
+            || self.readings.len() == POST_TRIGGER_SAMPLES
+    }
+
+    pub fn origin_ms(&self) -> Option<u64> {
+        self.origin_ms
+    }
+
+    pub fn readings(&self) -> &Capture<POST_TRIGGER_SAMPLES> {
+        &self.readings
+    }
+
+    // Ends the window, the readings stay until the next one opens
+    pub fn close(&mut self) {
+        self.origin_ms = None;
+    }
+}
+
+impl Default for PostTrigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A reading as one spreadsheet-friendly line, see CSV_HEADER.
+// The time is relative to origin_ms, so samples before a trigger are negative.
+pub struct CsvLine<'a> {
+    pub reading: &'a Reading,
+    pub origin_ms: u64,
+}
+
+impl fmt::Display for CsvLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Reading {
+            acc,
+            gyro,
+            temp,
+            t_ms,
+        } = self.reading;
+        let relative_ms = *t_ms as i64 - self.origin_ms as i64;
+
+        write!(
+            f,
+            "{},{:.3},{:.3},{:.3},{:.4},{:.4},{:.4},{:.2}",
+            relative_ms, acc[0], acc[1], acc[2], gyro[0], gyro[1], gyro[2], temp
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(t_ms: u64) -> Reading {
+        Reading::new([0.0, 0.0, 9.81], [0.0; 3], 25.0, t_ms)
+    }
+
+    #[test]
+    fn keeps_the_last_readings_in_order() {
+        let mut capture: Capture<4> = Capture::new();
+        for t_ms in 0..6 {
+            capture.push(reading(t_ms * 500));
+        }
+
+        assert_eq!(capture.len(), 4);
+        let times: Vec<u64> = capture.iter().map(|reading| reading.t_ms).collect();
+        assert_eq!(times, [1_000, 1_500, 2_000, 2_500]);
+    }
+
+    #[test]
+    fn post_trigger_window_closes_after_its_length() {
+        let mut post = PostTrigger::new();
+        assert!(!post.push(reading(0)));
+
+        assert!(post.open(1_000));
+        assert!(!post.open(1_200));
+
+        let mut t_ms = 1_000;
+        while !post.push(reading(t_ms)) {
+            t_ms += POST_TRIGGER_PERIOD_MS as u64;
+        }
+        assert_eq!(t_ms, 1_000 + POST_TRIGGER_MS as u64);
+        assert_eq!(post.readings().len(), POST_TRIGGER_SAMPLES);
+
+        post.close();
+        assert!(!post.is_open());
+        assert!(post.open(5_000));
+        assert!(post.readings().is_empty());
+    }
+
+    #[test]
+    fn csv_line_is_relative_to_the_trigger() {
+        let line = CsvLine {
+            reading: &reading(1_500),
+            origin_ms: 2_000,
+        };
+
+        assert_eq!(
+            line.to_string(),
+            "-500,0.000,0.000,9.810,0.0000,0.0000,0.0000,25.00"
+        );
+    }
+}
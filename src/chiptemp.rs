@@ -0,0 +1,149 @@
+use crate::math;
+
+// The ESP32's internal temperature sensor reads out in counts that are
+// roughly ºF, and off by up to a few tens of degrees from one chip to the
+// next: it's only good for changes, against an offset taken at boot
+pub fn tsens_celsius(counts: u8) -> f32 {
+    (counts as f32 - 32.0) / 1.8
+}
+
+// Reads averaged at boot for the offset, with both chips still cold
+pub const CHIP_OFFSET_READS: u32 = 16;
+
+// How far the MPU may drift from the chip, in ºC past the boot offset,
+// for how many samples in a row before its temperature is suspect. The
+// margin leaves room for the ESP32 warming up more than the MPU does once
+// it's running, the wait for a machine that heats one side of the board
+// before the other.
+pub const CROSS_CHECK_MARGIN: f32 = 10.0;
+pub const CROSS_CHECK_SAMPLES: u16 = 120;
+
+// Back within this much of the margin, the two agree again
+pub const CROSS_CHECK_RELEASE: f32 = 0.8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Plausibility {
+    // The MPU's temperature has been off the chip's for too long
+    Diverged,
+    // and is back in line
+    Agreed,
+}
+
+// Keeps the MPU's temperature honest against the chip's. It never raises
+// an alarm: a drifting or lying MPU reading is a sensor fault, not an
+// overheating machine.
+#[derive(Clone, Copy, Debug)]
+pub struct CrossCheck {
+    // MPU minus chip, at boot
+    offset: Option<f32>,
+    divergence: Option<f32>,
+    far_samples: u16,
+    diverged: bool,
+}
+
+impl CrossCheck {
+    pub const fn new() -> Self {
+        Self {
+            offset: None,
+            divergence: None,
+            far_samples: 0,
+            diverged: false,
+        }
+    }
+
+    pub fn set_offset(&mut self, mpu_temp: f32, chip_temp: f32) {
+        *self = Self {
+            offset: Some(mpu_temp - chip_temp),
+            ..Self::new()
+        };
+    }
+
+    pub fn offset(&self) -> Option<f32> {
+        self.offset
+    }
+
+    // Does nothing before the offset. Returns the change, if any.
+    pub fn update(&mut self, mpu_temp: f32, chip_temp: f32) -> Option<Plausibility> {
+        let offset = self.offset?;
+        let divergence = mpu_temp - chip_temp - offset;
+        self.divergence = Some(divergence);
+
+        let far = math::abs(divergence) > CROSS_CHECK_MARGIN;
+        self.far_samples = if far {
+            self.far_samples.saturating_add(1)
+        } else {
+            0
+        };
+        if !self.diverged && self.far_samples >= CROSS_CHECK_SAMPLES {
+            self.diverged = true;
+            return Some(Plausibility::Diverged);
+        }
+        if self.diverged && math::abs(divergence) < CROSS_CHECK_MARGIN * CROSS_CHECK_RELEASE {
+            self.diverged = false;
+            return Some(Plausibility::Agreed);
+        }
+        None
+    }
+
+    // In ºC past the boot offset as of the last update, positive with the
+    // MPU reading hotter
+    pub fn divergence(&self) -> Option<f32> {
+        self.divergence
+    }
+
+    pub fn is_diverged(&self) -> bool {
+        self.diverged
+    }
+}
+
+impl Default for CrossCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_read_as_fahrenheit() {
+        assert_eq!(tsens_celsius(32), 0.0);
+        assert!((tsens_celsius(122) - 50.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn a_sustained_divergence_is_implausible() {
+        let mut check = CrossCheck::new();
+        assert_eq!(check.update(25.0, 50.0), None);
+        assert_eq!(check.divergence(), None);
+
+        // The chip reads 22 ºC over the MPU, cold
+        check.set_offset(21.0, 43.0);
+        assert_eq!(check.offset(), Some(-22.0));
+
+        // Both warming up the same way is fine, and so is anything shorter than
+        // the wait
+        for _ in 0..CROSS_CHECK_SAMPLES * 2 {
+            assert_eq!(check.update(40.0, 61.0), None);
+        }
+        for _ in 0..CROSS_CHECK_SAMPLES - 1 {
+            assert_eq!(check.update(55.0, 61.0), None);
+        }
+        assert_eq!(check.update(45.0, 61.0), None);
+
+        // Stuck at a cold reading while the board heats up
+        for _ in 0..CROSS_CHECK_SAMPLES - 1 {
+            assert_eq!(check.update(21.0, 60.0), None);
+        }
+        assert_eq!(check.update(21.0, 60.0), Some(Plausibility::Diverged));
+        assert!(check.is_diverged());
+        assert_eq!(check.divergence(), Some(-17.0));
+        assert_eq!(check.update(21.0, 60.0), None);
+
+        // Within the margin isn't enough, it has to come back further
+        assert_eq!(check.update(30.0, 60.0), None);
+        assert_eq!(check.update(32.0, 60.0), Some(Plausibility::Agreed));
+        assert!(!check.is_diverged());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Severity;
+
+// Latched state of one limit, with debounce and hysteresis.
+// A condition is only raised after the limit is exceeded on a number of
+// consecutive samples, so a single bogus reading doesn't sound the alarm.
+// Once tripped, it only clears after the reading stays below the release
+// level for a number of consecutive samples, so a value hovering around the
+// trip point doesn't make the alarm chatter.
+// A muted condition stays latched but doesn't raise alerts again until it
+// clears, unless it escalates to a higher severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    confirmations: u8,
+    release_samples: u8,
+    latched: Option<Severity>,
+    violations: u8,
+    released_samples: u8,
+    muted: bool,
+}
+
+impl Condition {
+    pub const fn new(confirmations: u8, release_samples: u8) -> Self {
+        Self {
+            confirmations,
+            release_samples,
+            latched: None,
+            violations: 0,
+            released_samples: 0,
+            muted: false,
+        }
+    }
+
+    // Feeds one sample's outcome.
+    // Returns the severity only when the condition is newly raised or
+    // escalates, re-triggering while latched at the same level returns None.
+    pub fn update(&mut self, tripped: Option<Severity>, released: bool) -> Option<Severity> {
+        let Some(severity) = tripped else {
+            self.violations = 0;
+
+            if self.latched.is_some() {
+                if released {
+                    self.released_samples = self.released_samples.saturating_add(1);
+                    if self.released_samples >= self.release_samples {
+                        self.clear();
+                    }
+                } else {
+                    // Between the release and trip levels
+                    self.released_samples = 0;
+                }
+            }
+
+            return None;
+        };
+
+        self.released_samples = 0;
+        self.violations = self.violations.saturating_add(1);
+        if self.violations < self.confirmations {
+            return None;
+        }
+
+        let raised = match self.latched {
+            Some(latched) => severity > latched,
+            None => true,
+        };
+        if raised {
+            let escalated = self.latched.is_some();
+            self.latched = Some(severity);
+
+            if self.muted && !escalated {
+                return None;
+            }
+            self.muted = false;
+            return Some(severity);
+        }
+
+        None
+    }
+
+    pub fn latched(&self) -> Option<Severity> {
+        self.latched
+    }
+
+    // Silences a latched condition until it clears
+    pub fn mute(&mut self) {
+        if self.latched.is_some() {
+            self.muted = true;
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    // Consecutive samples over the limit that haven't raised the condition yet,
+    // as (count, required)
+    pub fn pending(&self) -> Option<(u8, u8)> {
+        if self.latched.is_none() && self.violations > 0 && self.violations < self.confirmations {
+            Some((self.violations, self.confirmations))
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.confirmations, self.release_samples);
+    }
+}
@@ -0,0 +1,233 @@
+// Build-time choices for what goes out on the serial port. The mode is only
+// the one at boot, the `output` command switches it at runtime.
+
+#[cfg(any(
+    all(feature = "json-telemetry", feature = "csv-telemetry"),
+    all(feature = "json-telemetry", feature = "binary-telemetry"),
+    all(feature = "csv-telemetry", feature = "binary-telemetry"),
+))]
+compile_error!("pick one of the json-telemetry, csv-telemetry and binary-telemetry features");
+
+// How every status sample goes out on the serial port
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    // The labelled lines, one value per line
+    Human,
+    // One JSON object per line, see `telemetry::JsonLine`
+    Json,
+    // One comma-separated row per line under a header, see `telemetry::CsvRow`
+    Csv,
+    // COBS frames, see `frame`
+    Binary,
+}
+
+impl OutputMode {
+    pub const ALL: [OutputMode; 4] = [
+        OutputMode::Human,
+        OutputMode::Json,
+        OutputMode::Csv,
+        OutputMode::Binary,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputMode::Human => "human",
+            OutputMode::Json => "json",
+            OutputMode::Csv => "csv",
+            OutputMode::Binary => "binary",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// Log lines up to this level print, see `board::log` in the firmware.
+// Console replies and the boot banner always print.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    // Faults and critical alarms
+    Error,
+    // Warning alarms and anything that needs a look
+    Warn,
+    // Events and the summary lines
+    Info,
+    // Every sample, in full
+    Debug,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Error, Level::Warn, Level::Info, Level::Debug];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn from_u8(value: u8) -> Self {
+        Self::ALL[(value as usize).min(Self::ALL.len() - 1)]
+    }
+}
+
+// Until a `level` command changes it
+pub const LOG_LEVEL: Level = Level::Info;
+
+// ANSI colors in the log lines, until a `color` command changes it. The
+// no-color feature turns them off from boot, for dumb terminals and log files.
+pub const COLOR: bool = cfg!(not(feature = "no-color"));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputConfig {
+    pub mode: OutputMode,
+    // Digits after the point in the CSV rows, up to `fixed::MAX_DECIMALS`.
+    // Every row of a mode has the
+    // same width give or take the signs, so the serial throughput at a
+    // given sample rate is known upfront.
+    pub decimals: u8,
+}
+
+pub const OUTPUT: OutputConfig = OutputConfig {
+    mode: DEFAULT_MODE,
+    decimals: 3,
+};
+
+#[cfg(not(any(
+    feature = "json-telemetry",
+    feature = "csv-telemetry",
+    feature = "binary-telemetry"
+)))]
+const DEFAULT_MODE: OutputMode = OutputMode::Human;
+#[cfg(feature = "json-telemetry")]
+const DEFAULT_MODE: OutputMode = OutputMode::Json;
+#[cfg(feature = "csv-telemetry")]
+const DEFAULT_MODE: OutputMode = OutputMode::Csv;
+#[cfg(feature = "binary-telemetry")]
+const DEFAULT_MODE: OutputMode = OutputMode::Binary;
+
+// The `modbus` feature's slave address and line speed, from the environment
+// at build time: MODBUS_ADDRESS=1 MODBUS_BAUD=19200. Always 8 data bits,
+// even parity and a stop bit, the spec's default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModbusConfig {
+    // 1 to 247, anything else is 1
+    pub address: u8,
+    pub baud: u32,
+}
+
+pub const MODBUS: ModbusConfig = ModbusConfig {
+    address: match number(option_env!("MODBUS_ADDRESS"), 1) {
+        address @ 1..=247 => address as u8,
+        _ => 1,
+    },
+    baud: match number(option_env!("MODBUS_BAUD"), 19_200) {
+        0 => 19_200,
+        baud => baud,
+    },
+};
+
+// The `can` feature's IDs, bit rate and readings period, from the
+// environment at build time too: CAN_READINGS_ID=0x301 CAN_ALARM_ID=0x101
+// CAN_KBPS=250 CAN_PERIOD_MS=1000. The IDs are standard 11-bit ones, in hex
+// with `0x` or decimal; the alarm's is the lower one by default, so it wins
+// the arbitration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanConfig {
+    pub readings_id: u16,
+    pub alarm_id: u16,
+    // 125, 250, 500 or 1000, anything else is 250
+    pub kbps: u32,
+    // A multiple of the 10 ms tick, anything else is 1000
+    pub period_ms: u32,
+}
+
+pub const CAN: CanConfig = CanConfig {
+    readings_id: can_id(option_env!("CAN_READINGS_ID"), 0x301),
+    alarm_id: can_id(option_env!("CAN_ALARM_ID"), 0x101),
+    kbps: match number(option_env!("CAN_KBPS"), 250) {
+        kbps @ (125 | 250 | 500 | 1000) => kbps,
+        _ => 250,
+    },
+    period_ms: match number(option_env!("CAN_PERIOD_MS"), 1_000) {
+        period_ms if period_ms > 0 && period_ms % 10 == 0 => period_ms,
+        _ => 1_000,
+    },
+};
+
+// The `daq-trigger` feature's pulse width, TRIGGER_WIDTH_MS=10 at build
+// time, 1 to 1000 ms, anything else is 10
+pub const TRIGGER_WIDTH_MS: u32 = match number(option_env!("TRIGGER_WIDTH_MS"), 10) {
+    width_ms @ 1..=1_000 => width_ms,
+    _ => 10,
+};
+
+// `0x` and hex digits, or decimal, up to 0x7ff. `default` otherwise.
+const fn can_id(value: Option<&str>, default: u16) -> u16 {
+    let Some(value) = value else {
+        return default;
+    };
+    let digits = value.as_bytes();
+    let id = if digits.len() > 2 && digits[0] == b'0' && (digits[1] | 0x20) == b'x' {
+        let mut id: u32 = 0;
+        let mut i = 2;
+        while i < digits.len() {
+            let digit = match digits[i] {
+                b'0'..=b'9' => digits[i] - b'0',
+                b'a'..=b'f' => digits[i] - b'a' + 10,
+                b'A'..=b'F' => digits[i] - b'A' + 10,
+                _ => return default,
+            };
+            id = id * 16 + digit as u32;
+            if id > 0x7ff {
+                return default;
+            }
+            i += 1;
+        }
+        id
+    } else {
+        number(Some(value), u32::MAX)
+    };
+    if id > 0x7ff {
+        return default;
+    }
+    id as u16
+}
+
+// A whole decimal number, `default` when unset or anything else
+const fn number(value: Option<&str>, default: u32) -> u32 {
+    let Some(value) = value else {
+        return default;
+    };
+    let digits = value.as_bytes();
+    if digits.is_empty() {
+        return default;
+    }
+    let mut n: u32 = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        if !digits[i].is_ascii_digit() {
+            return default;
+        }
+        n = match n.checked_mul(10) {
+            Some(n) => match n.checked_add((digits[i] - b'0') as u32) {
+                Some(n) => n,
+                None => return default,
+            },
+            None => return default,
+        };
+        i += 1;
+    }
+    n
+}
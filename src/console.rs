@@ -0,0 +1,661 @@
+use core::fmt;
+
+use heapless::Vec;
+
+use crate::dac::DAC_RANGE;
+use crate::hours::SERVICE_INTERVAL_RANGE_H;
+use crate::rawlog::RawEncoding;
+use crate::sim;
+use crate::xmodem::Protocol;
+use crate::{config::Level, Levels, Limit, OutputMode, Thresholds};
+
+// Longest command line, anything longer is dropped whole. Long enough for
+// a replayed `csv` row.
+pub const LINE_LEN: usize = 96;
+
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+// Limits that can be tuned at runtime, by their warning level
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    // Acceleration delta, m/s^2
+    Mechanical,
+    // Temperature rise rate, ºC/min
+    Temperature,
+    // Rotation rate delta, rad/s
+    Rotational,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 3] = [
+        Setting::Mechanical,
+        Setting::Temperature,
+        Setting::Rotational,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::Mechanical => "mech",
+            Setting::Temperature => "temp",
+            Setting::Rotational => "gyro",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Setting::Mechanical => "m/s^2",
+            Setting::Temperature => "ºC/min",
+            Setting::Rotational => "rad/s",
+        }
+    }
+
+    pub fn limit(&self) -> Limit {
+        match self {
+            Setting::Mechanical => Limit::Mechanical,
+            Setting::Temperature => Limit::Temperature,
+            Setting::Rotational => Limit::Rotational,
+        }
+    }
+
+    // Accepted warning levels. Below the low end the noise alone trips the
+    // limit, above the high end it can't trip at all (±8 g and ±500 º/s
+    // ranges), or would only once the machine is already on fire.
+    pub fn range(&self) -> (f32, f32) {
+        match self {
+            Setting::Mechanical => (0.05, 40.0),
+            Setting::Temperature => (0.1, 20.0),
+            Setting::Rotational => (0.05, 8.0),
+        }
+    }
+
+    pub fn levels(&self, thresholds: &Thresholds) -> Levels {
+        match self {
+            Setting::Mechanical => thresholds.mechanical,
+            Setting::Temperature => thresholds.temperature_rate,
+            Setting::Rotational => thresholds.rotational,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|setting| setting.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Set(Setting, f32),
+    Get,
+    Calibrate,
+    ZeroCurrent,
+    Mute,
+    Status,
+    Save,
+    FactoryReset,
+    Counters,
+    ClearCounters,
+    Log,
+    ClearLog,
+    DumpFlash,
+    Trend,
+    Replay,
+    // The SD card's files, and the last rows of the one being written
+    Ls,
+    Tail,
+    Service(Service),
+    Learn(Learn),
+    Sim(Sim),
+    Dac(Dac),
+    Raw(Raw),
+    Download(LogFile, Protocol),
+    Output(OutputMode),
+    Level(Level),
+    Color(bool),
+    Help,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Service {
+    // Running hours and when the next service is due
+    Status,
+    Done,
+    // Every so many running hours
+    Interval(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Learn {
+    // How far the run is, or the drift from the commissioning baseline
+    Status,
+    Start,
+    // Take the proposed levels and save them, with the baseline
+    Accept,
+    Discard,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sim {
+    // The scenario playing and how far into it
+    Status,
+    // An index in `sim::SCENARIOS`, started over
+    Play(usize),
+    // The same scenario started over with another noise
+    Seed(u32),
+}
+
+// The analog output's mapping, in m/s^2 RMS, or its test pattern
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dac {
+    Status,
+    Test,
+    Zero(f32),
+    FullScale(f32),
+}
+
+// The raw acceleration log in flash: how much it holds, a dump of it,
+// oldest first, or of its newest so many blocks, or the end of the dump
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Raw {
+    Status,
+    Dump(RawEncoding, Option<u16>),
+    Stop,
+}
+
+// What `download` sends: the flash event log as text, or the raw log's
+// blocks as they are in flash
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFile {
+    Events,
+    Raw,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommandError {
+    Unknown,
+    // Right command, wrong arguments, holds the expected usage
+    Usage(&'static str),
+    NotANumber,
+    OutOfRange(Setting),
+    TooLong,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Unknown => write!(f, "unknown command, try `help`"),
+            CommandError::Usage(usage) => write!(f, "usage: {}", usage),
+            CommandError::NotANumber => write!(f, "not a number"),
+            CommandError::OutOfRange(setting) => {
+                let (min, max) = setting.range();
+                write!(
+                    f,
+                    "{} must be between {} and {} {}",
+                    setting.name(),
+                    min,
+                    max,
+                    setting.unit()
+                )
+            }
+            CommandError::TooLong => write!(f, "line longer than {} characters", LINE_LEN),
+        }
+    }
+}
+
+pub const HELP: [&str; 29] = [
+    "set mech <m/s^2>: mechanical warning level",
+    "set temp <ºC/min>: temperature rise warning level",
+    "set gyro <rad/s>: rotational warning level",
+    "get: current levels",
+    "calibrate: take new references, keep the machine still",
+    "zero-current: take the current sensor's zero, with the motor off",
+    "mute: silence the latched alarms",
+    "status: monitor status",
+    "save: keep the levels and gyroscope bias across resets",
+    "factory-reset: back to the compiled-in levels",
+    "counters: boots and alarms since the counters were cleared",
+    "clear-counters: start them over",
+    "log: alarms, faults, settings and resets since boot, oldest first",
+    "clear: empty the event log",
+    "dumpflash: every event in the flash log, oldest first",
+    "trend: min, mean, max and limit crossings of every minute in the last hour",
+    "replay: t,ax,ay,az,gx,gy,gz,temp rows in place of the sensor, until `end`",
+    "ls: the SD card's files, with `sd-card`",
+    "tail: the last rows of the SD card's log",
+    "service [done|<hours>]: running hours, `done` once serviced, or a new service interval",
+    "learn [start|accept|discard]: commissioning run, its proposed levels, or the drift since",
+    "sim [idle|imbalance|impacts|overheat|script|seed <n>]: the simulated machine, with `sim`",
+    "dac [test|zero <m/s^2>|full <m/s^2>]: the analog output's mapping, or its staircase, with `dac`",
+    "raw [hex|base64 [<blocks>]|stop]: the raw acceleration log in flash, dumped, with `raw-log`",
+    "download events|raw [xmodem|ymodem]: the flash event log or the raw log as a file, YMODEM by default",
+    "output human|json|csv|binary: how the samples are printed",
+    "level error|warn|info|debug: which log lines print, debug for every sample",
+    "color on|off: ANSI colors in the log lines",
+    "help: this list",
+];
+
+const SET_USAGE: &str = "set mech|temp|gyro <value>";
+const OUTPUT_USAGE: &str = "output human|json|csv|binary";
+const LEVEL_USAGE: &str = "level error|warn|info|debug";
+const COLOR_USAGE: &str = "color on|off";
+const SERVICE_USAGE: &str = "service [done|<hours, 1 to 100000>]";
+const LEARN_USAGE: &str = "learn [start|accept|discard]";
+const SIM_USAGE: &str = "sim [idle|imbalance|impacts|overheat|script|seed <n>]";
+const DAC_USAGE: &str = "dac [test|zero <m/s^2, 0 to 20>|full <m/s^2, 0 to 20>]";
+const RAW_USAGE: &str = "raw [hex|base64 [<blocks>]|stop]";
+const DOWNLOAD_USAGE: &str = "download events|raw [xmodem|ymodem]";
+
+// Commands and setting names are matched regardless of case
+pub fn parse(line: &str) -> Result<Command, CommandError> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Err(CommandError::Unknown);
+    };
+
+    if command.eq_ignore_ascii_case("set") {
+        let (Some(name), Some(value), None) = (words.next(), words.next(), words.next()) else {
+            return Err(CommandError::Usage(SET_USAGE));
+        };
+        let setting = Setting::parse(name).ok_or(CommandError::Usage(SET_USAGE))?;
+        let value: f32 = value.parse().map_err(|_| CommandError::NotANumber)?;
+        // NaN fails both comparisons
+        let (min, max) = setting.range();
+        if !(value >= min && value <= max) {
+            return Err(CommandError::OutOfRange(setting));
+        }
+        return Ok(Command::Set(setting, value));
+    }
+
+    if command.eq_ignore_ascii_case("output") {
+        let (Some(name), None) = (words.next(), words.next()) else {
+            return Err(CommandError::Usage(OUTPUT_USAGE));
+        };
+        let mode = OutputMode::parse(name).ok_or(CommandError::Usage(OUTPUT_USAGE))?;
+        return Ok(Command::Output(mode));
+    }
+
+    if command.eq_ignore_ascii_case("level") {
+        let (Some(name), None) = (words.next(), words.next()) else {
+            return Err(CommandError::Usage(LEVEL_USAGE));
+        };
+        let level = Level::parse(name).ok_or(CommandError::Usage(LEVEL_USAGE))?;
+        return Ok(Command::Level(level));
+    }
+
+    if command.eq_ignore_ascii_case("color") {
+        let (Some(state), None) = (words.next(), words.next()) else {
+            return Err(CommandError::Usage(COLOR_USAGE));
+        };
+        return match state {
+            _ if state.eq_ignore_ascii_case("on") => Ok(Command::Color(true)),
+            _ if state.eq_ignore_ascii_case("off") => Ok(Command::Color(false)),
+            _ => Err(CommandError::Usage(COLOR_USAGE)),
+        };
+    }
+
+    if command.eq_ignore_ascii_case("service") {
+        let service = match (words.next(), words.next()) {
+            (None, _) => Service::Status,
+            (Some(done), None) if done.eq_ignore_ascii_case("done") => Service::Done,
+            (Some(hours), None) => {
+                let (min, max) = SERVICE_INTERVAL_RANGE_H;
+                match hours.parse() {
+                    Ok(hours) if (min..=max).contains(&hours) => Service::Interval(hours),
+                    _ => return Err(CommandError::Usage(SERVICE_USAGE)),
+                }
+            }
+            _ => return Err(CommandError::Usage(SERVICE_USAGE)),
+        };
+        return Ok(Command::Service(service));
+    }
+
+    if command.eq_ignore_ascii_case("learn") {
+        let learn = match (words.next(), words.next()) {
+            (None, _) => Learn::Status,
+            (Some(action), None) => [
+                ("start", Learn::Start),
+                ("accept", Learn::Accept),
+                ("discard", Learn::Discard),
+            ]
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(action))
+            .map(|(_, learn)| learn)
+            .ok_or(CommandError::Usage(LEARN_USAGE))?,
+            _ => return Err(CommandError::Usage(LEARN_USAGE)),
+        };
+        return Ok(Command::Learn(learn));
+    }
+
+    if command.eq_ignore_ascii_case("sim") {
+        let sim = match (words.next(), words.next(), words.next()) {
+            (None, _, _) => Sim::Status,
+            (Some(seed), Some(value), None) if seed.eq_ignore_ascii_case("seed") => {
+                Sim::Seed(value.parse().map_err(|_| CommandError::NotANumber)?)
+            }
+            (Some(name), None, _) => {
+                Sim::Play(sim::scenario(name).ok_or(CommandError::Usage(SIM_USAGE))?)
+            }
+            _ => return Err(CommandError::Usage(SIM_USAGE)),
+        };
+        return Ok(Command::Sim(sim));
+    }
+
+    if command.eq_ignore_ascii_case("dac") {
+        let dac = match (words.next(), words.next(), words.next()) {
+            (None, _, _) => Dac::Status,
+            (Some(test), None, _) if test.eq_ignore_ascii_case("test") => Dac::Test,
+            (Some(end), Some(value), None) => {
+                let value: f32 = value.parse().map_err(|_| CommandError::NotANumber)?;
+                let (min, max) = DAC_RANGE;
+                if !(value >= min && value <= max) {
+                    return Err(CommandError::Usage(DAC_USAGE));
+                }
+                match end {
+                    _ if end.eq_ignore_ascii_case("zero") => Dac::Zero(value),
+                    _ if end.eq_ignore_ascii_case("full") => Dac::FullScale(value),
+                    _ => return Err(CommandError::Usage(DAC_USAGE)),
+                }
+            }
+            _ => return Err(CommandError::Usage(DAC_USAGE)),
+        };
+        return Ok(Command::Dac(dac));
+    }
+
+    if command.eq_ignore_ascii_case("raw") {
+        let raw = match (words.next(), words.next(), words.next()) {
+            (None, _, _) => Raw::Status,
+            (Some(stop), None, _) if stop.eq_ignore_ascii_case("stop") => Raw::Stop,
+            (Some(name), blocks, None) => {
+                let encoding = RawEncoding::parse(name).ok_or(CommandError::Usage(RAW_USAGE))?;
+                let blocks = match blocks.map(str::parse) {
+                    None => None,
+                    Some(Ok(blocks)) if blocks > 0 => Some(blocks),
+                    Some(_) => return Err(CommandError::Usage(RAW_USAGE)),
+                };
+                Raw::Dump(encoding, blocks)
+            }
+            _ => return Err(CommandError::Usage(RAW_USAGE)),
+        };
+        return Ok(Command::Raw(raw));
+    }
+
+    if command.eq_ignore_ascii_case("download") {
+        let (Some(name), protocol, None) = (words.next(), words.next(), words.next()) else {
+            return Err(CommandError::Usage(DOWNLOAD_USAGE));
+        };
+        let file = match name {
+            _ if name.eq_ignore_ascii_case("events") => LogFile::Events,
+            _ if name.eq_ignore_ascii_case("raw") => LogFile::Raw,
+            _ => return Err(CommandError::Usage(DOWNLOAD_USAGE)),
+        };
+        let protocol = match protocol {
+            Some(protocol) => {
+                Protocol::parse(protocol).ok_or(CommandError::Usage(DOWNLOAD_USAGE))?
+            }
+            None => Protocol::Ymodem,
+        };
+        return Ok(Command::Download(file, protocol));
+    }
+
+    let (_, parsed) = SIMPLE_COMMANDS
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(command))
+        .ok_or(CommandError::Unknown)?;
+    // None of them takes arguments
+    match words.next() {
+        Some(_) => Err(CommandError::Unknown),
+        None => Ok(parsed),
+    }
+}
+
+const SIMPLE_COMMANDS: [(&str, Command); 17] = [
+    ("get", Command::Get),
+    ("calibrate", Command::Calibrate),
+    ("zero-current", Command::ZeroCurrent),
+    ("mute", Command::Mute),
+    ("status", Command::Status),
+    ("save", Command::Save),
+    ("factory-reset", Command::FactoryReset),
+    ("counters", Command::Counters),
+    ("clear-counters", Command::ClearCounters),
+    ("log", Command::Log),
+    ("clear", Command::ClearLog),
+    ("dumpflash", Command::DumpFlash),
+    ("trend", Command::Trend),
+    ("replay", Command::Replay),
+    ("ls", Command::Ls),
+    ("tail", Command::Tail),
+    ("help", Command::Help),
+];
+
+// Collects the bytes coming in on the UART into lines.
+// Only printable ASCII makes it into a line, other bytes (line noise, a
+// terminal's escape sequences) are dropped. Backspace works.
+pub struct LineBuffer {
+    line: Vec<u8, LINE_LEN>,
+    overflowed: bool,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    // Returns the parsed command once a line ends, with CR, LF or both.
+    // Blank lines are ignored.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Command, CommandError>> {
+        self.push_with(byte, parse)
+            .map(|parsed| parsed.and_then(|command| command))
+    }
+
+    // The same, with `f` given the line instead of parsing it
+    pub fn push_with<R>(
+        &mut self,
+        byte: u8,
+        f: impl FnOnce(&str) -> R,
+    ) -> Option<Result<R, CommandError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let overflowed = core::mem::take(&mut self.overflowed);
+                let result = if overflowed {
+                    Some(Err(CommandError::TooLong))
+                } else if self.line.is_empty() {
+                    None
+                } else {
+                    // Printable ASCII only, it's always valid UTF-8
+                    core::str::from_utf8(&self.line).ok().map(f).map(Ok)
+                };
+                self.line.clear();
+                result
+            }
+            BACKSPACE | DELETE => {
+                self.line.pop();
+                None
+            }
+            b' '..=b'~' => {
+                if self.line.push(byte).is_err() {
+                    self.overflowed = true;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(buffer: &mut LineBuffer, bytes: &[u8]) -> Option<Result<Command, CommandError>> {
+        let mut result = None;
+        for byte in bytes {
+            if let Some(line) = buffer.push(*byte) {
+                assert!(result.is_none(), "more than one line");
+                result = Some(line);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn parses_the_commands() {
+        assert_eq!(
+            parse("set mech 1.2"),
+            Ok(Command::Set(Setting::Mechanical, 1.2))
+        );
+        assert_eq!(
+            parse("  SET temp  3.0 "),
+            Ok(Command::Set(Setting::Temperature, 3.0))
+        );
+        assert_eq!(parse("get"), Ok(Command::Get));
+        assert_eq!(parse("Calibrate"), Ok(Command::Calibrate));
+        assert_eq!(parse("zero-current"), Ok(Command::ZeroCurrent));
+        assert_eq!(parse("mute"), Ok(Command::Mute));
+        assert_eq!(parse("status"), Ok(Command::Status));
+        assert_eq!(
+            parse("set gyro 0.9"),
+            Ok(Command::Set(Setting::Rotational, 0.9))
+        );
+        assert_eq!(parse("save"), Ok(Command::Save));
+        assert_eq!(parse("factory-reset"), Ok(Command::FactoryReset));
+        assert_eq!(parse("counters"), Ok(Command::Counters));
+        assert_eq!(parse("clear-counters"), Ok(Command::ClearCounters));
+        assert_eq!(parse("log"), Ok(Command::Log));
+        assert_eq!(parse("Clear"), Ok(Command::ClearLog));
+        assert_eq!(parse("dumpflash"), Ok(Command::DumpFlash));
+        assert_eq!(parse("trend"), Ok(Command::Trend));
+        assert_eq!(parse("service"), Ok(Command::Service(Service::Status)));
+        assert_eq!(parse("service Done"), Ok(Command::Service(Service::Done)));
+        assert_eq!(
+            parse("service 500"),
+            Ok(Command::Service(Service::Interval(500)))
+        );
+        assert_eq!(parse("service 0"), Err(CommandError::Usage(SERVICE_USAGE)));
+        assert_eq!(
+            parse("service done now"),
+            Err(CommandError::Usage(SERVICE_USAGE))
+        );
+        assert_eq!(parse("learn"), Ok(Command::Learn(Learn::Status)));
+        assert_eq!(parse("learn START"), Ok(Command::Learn(Learn::Start)));
+        assert_eq!(parse("learn accept"), Ok(Command::Learn(Learn::Accept)));
+        assert_eq!(parse("learn discard"), Ok(Command::Learn(Learn::Discard)));
+        assert_eq!(parse("learn forget"), Err(CommandError::Usage(LEARN_USAGE)));
+        assert_eq!(parse("sim"), Ok(Command::Sim(Sim::Status)));
+        assert_eq!(parse("sim Script"), Ok(Command::Sim(Sim::Play(4))));
+        assert_eq!(parse("sim seed 42"), Ok(Command::Sim(Sim::Seed(42))));
+        assert_eq!(parse("sim seed x"), Err(CommandError::NotANumber));
+        assert_eq!(parse("sim spin"), Err(CommandError::Usage(SIM_USAGE)));
+        assert_eq!(parse("dac"), Ok(Command::Dac(Dac::Status)));
+        assert_eq!(parse("dac TEST"), Ok(Command::Dac(Dac::Test)));
+        assert_eq!(parse("dac zero 0.2"), Ok(Command::Dac(Dac::Zero(0.2))));
+        assert_eq!(parse("dac full 4"), Ok(Command::Dac(Dac::FullScale(4.0))));
+        assert_eq!(parse("dac full 30"), Err(CommandError::Usage(DAC_USAGE)));
+        assert_eq!(parse("dac span 1"), Err(CommandError::Usage(DAC_USAGE)));
+        assert_eq!(parse("dac zero x"), Err(CommandError::NotANumber));
+        assert_eq!(parse("raw"), Ok(Command::Raw(Raw::Status)));
+        assert_eq!(
+            parse("raw hex"),
+            Ok(Command::Raw(Raw::Dump(RawEncoding::Hex, None)))
+        );
+        assert_eq!(
+            parse("RAW base64 12"),
+            Ok(Command::Raw(Raw::Dump(RawEncoding::Base64, Some(12))))
+        );
+        assert_eq!(parse("raw stop"), Ok(Command::Raw(Raw::Stop)));
+        assert_eq!(parse("raw hex 0"), Err(CommandError::Usage(RAW_USAGE)));
+        assert_eq!(parse("raw octal"), Err(CommandError::Usage(RAW_USAGE)));
+        assert_eq!(
+            parse("download events"),
+            Ok(Command::Download(LogFile::Events, Protocol::Ymodem))
+        );
+        assert_eq!(
+            parse("DOWNLOAD raw XMODEM"),
+            Ok(Command::Download(LogFile::Raw, Protocol::Xmodem))
+        );
+        assert_eq!(
+            parse("download raw zmodem"),
+            Err(CommandError::Usage(DOWNLOAD_USAGE))
+        );
+        assert_eq!(parse("download"), Err(CommandError::Usage(DOWNLOAD_USAGE)));
+        assert_eq!(parse("output JSON"), Ok(Command::Output(OutputMode::Json)));
+        assert_eq!(parse("output csv"), Ok(Command::Output(OutputMode::Csv)));
+        assert_eq!(parse("level Debug"), Ok(Command::Level(Level::Debug)));
+        assert_eq!(parse("color OFF"), Ok(Command::Color(false)));
+        assert_eq!(parse("replay"), Ok(Command::Replay));
+        assert_eq!(parse("LS"), Ok(Command::Ls));
+        assert_eq!(parse("tail"), Ok(Command::Tail));
+        assert_eq!(parse("help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn rejects_bad_commands_and_values() {
+        assert_eq!(parse("reboot"), Err(CommandError::Unknown));
+        assert_eq!(parse("get all"), Err(CommandError::Unknown));
+        assert_eq!(parse("set mech"), Err(CommandError::Usage(SET_USAGE)));
+        assert_eq!(parse("set jerk 1.0"), Err(CommandError::Usage(SET_USAGE)));
+        assert_eq!(parse("set mech 1 2"), Err(CommandError::Usage(SET_USAGE)));
+        assert_eq!(parse("set mech fast"), Err(CommandError::NotANumber));
+        assert_eq!(parse("output"), Err(CommandError::Usage(OUTPUT_USAGE)));
+        assert_eq!(parse("output xml"), Err(CommandError::Usage(OUTPUT_USAGE)));
+        assert_eq!(parse("level trace"), Err(CommandError::Usage(LEVEL_USAGE)));
+        assert_eq!(parse("color"), Err(CommandError::Usage(COLOR_USAGE)));
+        assert_eq!(parse("color blue"), Err(CommandError::Usage(COLOR_USAGE)));
+        assert_eq!(
+            parse("set mech -1"),
+            Err(CommandError::OutOfRange(Setting::Mechanical))
+        );
+        assert_eq!(
+            parse("set temp 500"),
+            Err(CommandError::OutOfRange(Setting::Temperature))
+        );
+        assert_eq!(
+            parse("set mech NaN"),
+            Err(CommandError::OutOfRange(Setting::Mechanical))
+        );
+    }
+
+    #[test]
+    fn lines_survive_junk_and_line_endings() {
+        let mut buffer = LineBuffer::new();
+
+        assert_eq!(feed(&mut buffer, b"\r\n\n"), None);
+        assert_eq!(
+            feed(&mut buffer, b"\x00\xffset m\x1b\x08\x08 mech 0.9\r\n"),
+            Some(Ok(Command::Set(Setting::Mechanical, 0.9)))
+        );
+        assert_eq!(feed(&mut buffer, b"get\n"), Some(Ok(Command::Get)));
+
+        // A replayed row, handed over as it is
+        let mut rows = Vec::<usize, 2>::new();
+        for byte in b"1000,0,0,9.8,0,0,0,25\r\nend\n" {
+            if let Some(Ok(len)) = buffer.push_with(*byte, str::len) {
+                rows.push(len).unwrap();
+            }
+        }
+        assert_eq!(rows, [21, 3]);
+    }
+
+    #[test]
+    fn long_lines_are_dropped_whole() {
+        let mut buffer = LineBuffer::new();
+
+        assert_eq!(
+            feed(&mut buffer, &[b'x'; LINE_LEN + 10]),
+            None,
+            "no line end yet"
+        );
+        assert_eq!(feed(&mut buffer, b"\n"), Some(Err(CommandError::TooLong)));
+        assert_eq!(feed(&mut buffer, b"mute\n"), Some(Ok(Command::Mute)));
+    }
+}
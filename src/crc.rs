@@ -0,0 +1,96 @@
+// CRC-32 (IEEE 802.3, the zlib one), bit by bit: the blocks it guards are
+// a few dozen bytes, or a 4 KiB raw log block now and then, not worth a
+// 1 KiB table
+const POLYNOMIAL: u32 = 0xedb8_8320;
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_of(&[bytes])
+}
+
+// Over several pieces, as if they were one
+pub fn crc32_of(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLYNOMIAL & mask);
+        }
+    }
+    !crc
+}
+
+// CRC-16/CCITT-FALSE, for the telemetry frames: short enough not to bloat
+// them, and what most serial tools call "CRC-16 CCITT"
+const POLYNOMIAL_16: u16 = 0x1021;
+
+pub fn crc16(bytes: &[u8]) -> u16 {
+    crc16_from(0xffff, bytes)
+}
+
+// CRC-16/XMODEM, the same polynomial started from zero
+pub fn crc16_xmodem(bytes: &[u8]) -> u16 {
+    crc16_from(0, bytes)
+}
+
+fn crc16_from(mut crc: u16, bytes: &[u8]) -> u16 {
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            let mask = (crc >> 15).wrapping_neg();
+            crc = (crc << 1) ^ (POLYNOMIAL_16 & mask);
+        }
+    }
+    crc
+}
+
+// CRC-16/MODBUS, the reflected 0x8005 the RTU frames end with, low byte
+// first
+const POLYNOMIAL_MODBUS: u16 = 0xa001;
+
+pub fn crc16_modbus(bytes: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for byte in bytes {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLYNOMIAL_MODBUS & mask);
+        }
+    }
+    crc
+}
+
+// CRC-8/MAXIM, the one 1-Wire ROM codes and scratchpads end with. A block
+// followed by its own CRC checks to 0.
+const POLYNOMIAL_8: u8 = 0x8c;
+
+pub fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in bytes {
+        crc ^= *byte;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLYNOMIAL_8 & mask);
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32_of(&[b"1234", b"", b"56789"]), 0xcbf4_3926);
+        assert_eq!(crc16(b""), 0xffff);
+        assert_eq!(crc16(b"123456789"), 0x29b1);
+        assert_eq!(crc16_xmodem(b"123456789"), 0x31c3);
+        assert_eq!(crc16_modbus(b"123456789"), 0x4b37);
+        // The spec's example request, sent as 76 87
+        assert_eq!(crc16_modbus(&[0x11, 0x03, 0x00, 0x6b, 0x00, 0x03]), 0x8776);
+        assert_eq!(crc8(b"123456789"), 0xa1);
+    }
+}
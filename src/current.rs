@@ -0,0 +1,159 @@
+use crate::math;
+
+// The ACS712 runs on 5 V, with 0 A at 2.5 V and up to ±2 V around it, past
+// the ADC's range: its output goes through a 10k/20k divider
+pub const DIVIDER_RATIO: f32 = 2.0 / 3.0;
+
+// Sensitivity of the 20 A part, the 5 A one has 185 mV/A and the 30 A one 66
+pub const ACS712_MV_PER_A: f32 = 100.0;
+pub const ACS712_ZERO_MV: f32 = 2_500.0;
+
+// At 11 dB, close enough to linear between 150 mV and 2.45 V, which the
+// divided output stays within
+pub const ADC_MV_PER_COUNT: f32 = 3_100.0 / 4_095.0;
+
+// A mains cycle in 100 conversions, 200 µs apart, once a second
+pub const MAINS_HZ: u32 = 50;
+pub const CURRENT_SAMPLES: u32 = 100;
+pub const CURRENT_SAMPLE_US: u32 = 1_000_000 / MAINS_HZ / CURRENT_SAMPLES;
+pub const CURRENT_PERIOD_MS: u32 = 1_000;
+
+// A window that lost more conversions than this to an interrupt says nothing
+pub const CURRENT_MIN_SAMPLES: u32 = CURRENT_SAMPLES * 3 / 4;
+
+// Windows averaged for the zero offset
+pub const ZERO_WINDOWS: u32 = 10;
+
+// Counts to amps, from the sensitivity and the counts the output reads at
+// 0 A
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurrentScale {
+    pub zero_counts: f32,
+    pub counts_per_amp: f32,
+}
+
+impl CurrentScale {
+    pub fn new(mv_per_amp: f32, zero_counts: f32) -> Self {
+        Self {
+            zero_counts,
+            counts_per_amp: mv_per_amp * DIVIDER_RATIO / ADC_MV_PER_COUNT,
+        }
+    }
+
+    pub fn with_zero(self, zero_counts: f32) -> Self {
+        Self {
+            zero_counts,
+            ..self
+        }
+    }
+
+    pub fn amps(&self, counts: f32) -> f32 {
+        (counts - self.zero_counts) / self.counts_per_amp
+    }
+}
+
+// The datasheet's zero until one is measured
+impl Default for CurrentScale {
+    fn default() -> Self {
+        let zero_counts = ACS712_ZERO_MV * DIVIDER_RATIO / ADC_MV_PER_COUNT;
+        Self::new(ACS712_MV_PER_A, zero_counts)
+    }
+}
+
+// The conversions of one window, for the RMS current and the mean counts
+#[derive(Clone, Copy, Debug)]
+pub struct CurrentWindow {
+    scale: CurrentScale,
+    sum_squares: f32,
+    sum_counts: u32,
+    samples: u32,
+}
+
+impl CurrentWindow {
+    pub fn new(scale: CurrentScale) -> Self {
+        Self {
+            scale,
+            sum_squares: 0.0,
+            sum_counts: 0,
+            samples: 0,
+        }
+    }
+
+    pub fn push(&mut self, counts: u16) {
+        let amps = self.scale.amps(counts as f32);
+        self.sum_squares += amps * amps;
+        self.sum_counts += counts as u32;
+        self.samples += 1;
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // In A, None below CURRENT_MIN_SAMPLES
+    pub fn rms(&self) -> Option<f32> {
+        (self.samples >= CURRENT_MIN_SAMPLES)
+            .then(|| math::sqrt(self.sum_squares / self.samples as f32))
+    }
+
+    // What a window with the motor off reads, the zero offset. An AC
+    // current averages out over whole cycles, so it works with the motor
+    // running too.
+    pub fn mean_counts(&self) -> Option<f32> {
+        (self.samples >= CURRENT_MIN_SAMPLES).then(|| self.sum_counts as f32 / self.samples as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts the ADC reads for `amps`, at the default scale
+    fn counts(amps: f32) -> u16 {
+        let scale = CurrentScale::default();
+        math::round(scale.zero_counts + amps * scale.counts_per_amp) as u16
+    }
+
+    #[test]
+    fn counts_convert_to_amps() {
+        let scale = CurrentScale::default();
+        // 2.5 V divided to 1.67 V, and 66.7 mV a A after the divider
+        assert!((scale.zero_counts - 2_201.6).abs() < 0.1);
+        assert!((scale.counts_per_amp - 88.06).abs() < 0.01);
+        assert!((scale.amps(2_201.6 + 88.06 * 5.0) - 5.0).abs() < 0.001);
+        assert!((scale.amps(2_201.6 - 88.06 * 2.0) + 2.0).abs() < 0.001);
+
+        let shifted = scale.with_zero(2_150.0);
+        assert_eq!(shifted.amps(2_150.0), 0.0);
+        let small = CurrentScale::new(185.0, 2_150.0);
+        assert!((small.amps(2_150.0 + 162.9) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rms_over_a_mains_cycle() {
+        // 10 A peak, 7.07 A RMS
+        let mut window = CurrentWindow::new(CurrentScale::default());
+        for i in 0..CURRENT_SAMPLES {
+            let phase = 2.0 * core::f32::consts::PI * i as f32 / CURRENT_SAMPLES as f32;
+            window.push(counts(10.0 * math::sin(phase)));
+        }
+        assert_eq!(window.samples(), CURRENT_SAMPLES);
+        let rms = window.rms().unwrap();
+        assert!((rms - 7.071).abs() < 0.02, "{}", rms);
+        // Whole cycles average out to the zero
+        let zero = CurrentScale::default().zero_counts;
+        assert!((window.mean_counts().unwrap() - zero).abs() < 0.5);
+    }
+
+    #[test]
+    fn a_short_window_gives_nothing() {
+        let mut window = CurrentWindow::new(CurrentScale::default());
+        for _ in 1..CURRENT_MIN_SAMPLES {
+            window.push(counts(3.0));
+        }
+        assert_eq!(window.rms(), None);
+        assert_eq!(window.mean_counts(), None);
+        window.push(counts(3.0));
+        assert!((window.rms().unwrap() - 3.0).abs() < 0.01);
+    }
+}
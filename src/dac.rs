@@ -0,0 +1,153 @@
+use crate::math;
+
+// The vibration RMS as a voltage for the `dac` feature, for an analog chart
+// recorder: 0 V at the mapping's zero, the DAC's top at its full scale, in
+// m/s^2. The 8-bit DAC tops out a step short of its 3.3 V supply.
+pub const DAC_MAX: u8 = u8::MAX;
+
+pub const DAC_ZERO: f32 = 0.0;
+// Past the mechanical critical level, so an alarm is still on the chart
+pub const DAC_FULL_SCALE: f32 = 2.0;
+// What `dac zero` and `dac full` take
+pub const DAC_RANGE: (f32, f32) = (0.0, 20.0);
+// Less than this between the two and the pen would only jump
+pub const DAC_MIN_SPAN: f32 = 0.1;
+
+// The test pattern, long enough a step for a slow chart to draw it flat
+pub const STAIRCASE_PERCENT: [u8; 5] = [0, 25, 50, 75, 100];
+pub const STAIRCASE_STEP_MS: u32 = 5_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DacMapping {
+    pub zero: f32,
+    pub full_scale: f32,
+}
+
+impl DacMapping {
+    // None if either is out of `DAC_RANGE` or they're too close
+    pub fn new(zero: f32, full_scale: f32) -> Option<Self> {
+        let mapping = Self { zero, full_scale };
+        mapping.is_valid().then_some(mapping)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let (min, max) = DAC_RANGE;
+        // NaN fails the comparisons too
+        let in_range = |value: f32| value >= min && value <= max;
+        in_range(self.zero)
+            && in_range(self.full_scale)
+            && self.full_scale - self.zero >= DAC_MIN_SPAN
+    }
+
+    // Saturates at both rails. Without a reading, 0 V.
+    pub fn code(&self, value: Option<f32>) -> u8 {
+        let Some(value) = value.filter(|value| !value.is_nan()) else {
+            return 0;
+        };
+        let fraction = ((value - self.zero) / (self.full_scale - self.zero)).clamp(0.0, 1.0);
+        math::round(fraction * DAC_MAX as f32) as u8
+    }
+}
+
+impl Default for DacMapping {
+    fn default() -> Self {
+        Self {
+            zero: DAC_ZERO,
+            full_scale: DAC_FULL_SCALE,
+        }
+    }
+}
+
+pub fn percent_code(percent: u8) -> u8 {
+    (u32::from(percent.min(100)) * u32::from(DAC_MAX) / 100) as u8
+}
+
+// 0, 25, 50, 75 and 100 % in turn, for setting the recorder's zero and span
+// against what's known to be coming out
+pub struct Staircase {
+    started_ms: Option<u32>,
+}
+
+impl Staircase {
+    pub const fn new() -> Self {
+        Self { started_ms: None }
+    }
+
+    pub fn start(&mut self, now_ms: u32) {
+        self.started_ms = Some(now_ms);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started_ms.is_some()
+    }
+
+    // The step's percentage, None once the last one is over
+    pub fn percent(&mut self, now_ms: u32) -> Option<u8> {
+        let started_ms = self.started_ms?;
+        let step = now_ms.wrapping_sub(started_ms) / STAIRCASE_STEP_MS;
+        let percent = STAIRCASE_PERCENT.get(step as usize).copied();
+        if percent.is_none() {
+            self.started_ms = None;
+        }
+        percent
+    }
+}
+
+impl Default for Staircase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_the_range_onto_the_codes() {
+        let mapping = DacMapping::new(0.5, 2.5).unwrap();
+        assert_eq!(mapping.code(Some(0.5)), 0);
+        assert_eq!(mapping.code(Some(1.5)), 128);
+        assert_eq!(mapping.code(Some(2.5)), DAC_MAX);
+        assert_eq!(DacMapping::default().code(Some(1.0)), 128);
+    }
+
+    #[test]
+    fn saturates_at_the_rails() {
+        let mapping = DacMapping::default();
+        assert_eq!(mapping.code(Some(-1.0)), 0);
+        assert_eq!(mapping.code(Some(1_000.0)), DAC_MAX);
+        assert_eq!(mapping.code(Some(f32::INFINITY)), DAC_MAX);
+        assert_eq!(mapping.code(Some(f32::NAN)), 0);
+        assert_eq!(mapping.code(None), 0);
+    }
+
+    #[test]
+    fn rejects_a_bad_mapping() {
+        assert_eq!(DacMapping::new(1.0, 1.05), None);
+        assert_eq!(DacMapping::new(2.0, 1.0), None);
+        assert_eq!(DacMapping::new(0.0, 25.0), None);
+        assert_eq!(DacMapping::new(f32::NAN, 1.0), None);
+        assert!(DacMapping::default().is_valid());
+    }
+
+    #[test]
+    fn the_staircase_steps_up_then_ends() {
+        let mut staircase = Staircase::new();
+        assert_eq!(staircase.percent(0), None);
+
+        staircase.start(u32::MAX - 1_000);
+        let steps: [Option<u8>; 6] = core::array::from_fn(|i| {
+            staircase.percent((u32::MAX - 1_000).wrapping_add(i as u32 * STAIRCASE_STEP_MS + 10))
+        });
+        assert_eq!(
+            steps,
+            [Some(0), Some(25), Some(50), Some(75), Some(100), None]
+        );
+        assert!(!staircase.is_running());
+        assert_eq!(
+            STAIRCASE_PERCENT.map(percent_code),
+            [0, 63, 127, 191, DAC_MAX]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+// Total acceleration below this is free fall, in g
+pub const FREE_FALL_G: f32 = 0.3;
+
+// A sensor hanging by its wires settles far from the calibrated orientation,
+// in degrees, for this many consecutive samples
+pub const DETACHED_TILT: f32 = 45.0;
+pub const DETACHED_SETTLE_SAMPLES: u8 = 4;
+
+// Tells when the sensor came off its mount.
+// Once detached it stays so until `reset()`, every reading after that is
+// meaningless and only a new calibration on the remounted sensor fixes it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DetachDetector {
+    far_samples: u8,
+    detached: bool,
+}
+
+impl DetachDetector {
+    pub const fn new() -> Self {
+        Self {
+            far_samples: 0,
+            detached: false,
+        }
+    }
+
+    // Smallest total acceleration since the last update, in g, and the
+    // deviation from the calibrated orientation, in degrees
+    pub fn update(&mut self, min_acc_g: f32, tilt: f32) -> bool {
+        if tilt > DETACHED_TILT {
+            self.far_samples = self.far_samples.saturating_add(1);
+        } else {
+            self.far_samples = 0;
+        }
+
+        if min_acc_g < FREE_FALL_G || self.far_samples >= DETACHED_SETTLE_SAMPLES {
+            self.detached = true;
+        }
+        self.detached
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_fall_detaches_right_away() {
+        let mut detector = DetachDetector::new();
+
+        assert!(!detector.update(1.0, 0.0));
+        assert!(detector.update(0.05, 0.0));
+        // Back on something solid, still not where it was calibrated
+        assert!(detector.update(1.0, 0.0));
+
+        detector.reset();
+        assert!(!detector.is_detached());
+    }
+
+    #[test]
+    fn swinging_past_the_tilt_is_not_enough() {
+        let mut detector = DetachDetector::new();
+
+        for tilt in [60.0, 70.0, 50.0, 10.0, 60.0, 80.0, 60.0] {
+            assert!(!detector.update(1.0, tilt));
+        }
+        assert!(detector.update(1.0, 90.0));
+    }
+}
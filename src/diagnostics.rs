@@ -0,0 +1,216 @@
+use core::fmt;
+
+// Why the board restarted, from the ROM reset code of the PRO CPU and
+// whether a panic handler left its mark before resetting
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetCause {
+    PowerOn,
+    Software,
+    DeepSleep,
+    Watchdog,
+    Brownout,
+    Panic,
+    External,
+    Unknown,
+}
+
+impl ResetCause {
+    // The flash event log indexes this
+    pub const ALL: [ResetCause; 8] = [
+        ResetCause::PowerOn,
+        ResetCause::Software,
+        ResetCause::DeepSleep,
+        ResetCause::Watchdog,
+        ResetCause::Brownout,
+        ResetCause::Panic,
+        ResetCause::External,
+        ResetCause::Unknown,
+    ];
+
+    // A panic resets through software or a watchdog, the mark it leaves
+    // behind is what tells it apart
+    pub fn classify(code: Option<u32>, panicked: bool) -> Self {
+        if panicked {
+            return ResetCause::Panic;
+        }
+
+        match code {
+            Some(0x01) => ResetCause::PowerOn,
+            Some(0x03 | 0x0c) => ResetCause::Software,
+            Some(0x05) => ResetCause::DeepSleep,
+            Some(0x04 | 0x07 | 0x08 | 0x09 | 0x0b | 0x0d | 0x10) => ResetCause::Watchdog,
+            Some(0x0f) => ResetCause::Brownout,
+            Some(0x0e) => ResetCause::External,
+            _ => ResetCause::Unknown,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ResetCause::PowerOn => "power on",
+            ResetCause::Software => "software reset",
+            ResetCause::DeepSleep => "deep sleep wake-up",
+            ResetCause::Watchdog => "watchdog, the firmware had stopped responding",
+            ResetCause::Brownout => "brownout, the supply dipped",
+            ResetCause::Panic => "panic",
+            ResetCause::External => "reset by the other CPU or the debugger",
+            ResetCause::Unknown => "unknown",
+        }
+    }
+
+    // Something went wrong, worth a chirp for whoever is on site
+    pub fn is_abnormal(&self) -> bool {
+        matches!(self, ResetCause::Watchdog | ResetCause::Panic)
+    }
+}
+
+// Finer detail of a reset code, printed along with the cause
+pub fn code_name(code: u32) -> &'static str {
+    match code {
+        0x01 => "POWERON_RESET",
+        0x03 => "SW_RESET",
+        0x04 => "OWDT_RESET",
+        0x05 => "DEEPSLEEP_RESET",
+        0x06 => "SDIO_RESET",
+        0x07 => "TG0WDT_SYS_RESET",
+        0x08 => "TG1WDT_SYS_RESET",
+        0x09 => "RTCWDT_SYS_RESET",
+        0x0a => "INTRUSION_RESET",
+        0x0b => "TGWDT_CPU_RESET",
+        0x0c => "SW_CPU_RESET",
+        0x0d => "RTCWDT_CPU_RESET",
+        0x0e => "EXT_CPU_RESET",
+        0x0f => "RTCWDT_BROWN_OUT_RESET",
+        0x10 => "RTCWDT_RTC_RESET",
+        _ => "unknown code",
+    }
+}
+
+// First bytes of a panic message, kept in RTC memory for the next boot
+pub const PANIC_MESSAGE_LEN: usize = 64;
+
+#[derive(Clone, Copy)]
+pub struct PanicMessage {
+    bytes: [u8; PANIC_MESSAGE_LEN],
+    len: usize,
+}
+
+impl PanicMessage {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; PANIC_MESSAGE_LEN],
+            len: 0,
+        }
+    }
+
+    // From what was kept in RTC memory, which may be garbage
+    pub fn from_raw(bytes: [u8; PANIC_MESSAGE_LEN], len: usize) -> Self {
+        Self {
+            bytes,
+            len: len.min(PANIC_MESSAGE_LEN),
+        }
+    }
+
+    pub fn raw(&self) -> ([u8; PANIC_MESSAGE_LEN], usize) {
+        (self.bytes, self.len)
+    }
+
+    // Up to the first byte that isn't valid UTF-8
+    pub fn as_str(&self) -> &str {
+        let bytes = &self.bytes[..self.len];
+        match core::str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(error) => core::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap_or(""),
+        }
+    }
+}
+
+impl Default for PanicMessage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Keeps what fits and drops the rest, cut at a character boundary.
+// Never fails, so a long message still gets its start written.
+impl fmt::Write for PanicMessage {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let mut end = text.len().min(PANIC_MESSAGE_LEN - self.len);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.bytes[self.len..self.len + end].copy_from_slice(&text.as_bytes()[..end]);
+        self.len += end;
+        Ok(())
+    }
+}
+
+// SOS in Morse on the LED and buzzer after a panic, as (on, off) times in
+// units: dots are one unit, dashes three, with the standard gaps
+pub const SOS_UNIT_MS: u32 = 150;
+pub const SOS: [(u32, u32); 9] = [
+    (1, 1),
+    (1, 1),
+    (1, 3),
+    (3, 1),
+    (3, 1),
+    (3, 3),
+    (1, 1),
+    (1, 1),
+    (1, 7),
+];
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+
+    #[test]
+    fn classifies_the_reset_codes() {
+        assert_eq!(ResetCause::classify(Some(0x01), false), ResetCause::PowerOn);
+        assert_eq!(
+            ResetCause::classify(Some(0x07), false),
+            ResetCause::Watchdog
+        );
+        assert_eq!(
+            ResetCause::classify(Some(0x0f), false),
+            ResetCause::Brownout
+        );
+        assert_eq!(ResetCause::classify(Some(0x42), false), ResetCause::Unknown);
+        assert_eq!(ResetCause::classify(None, false), ResetCause::Unknown);
+    }
+
+    #[test]
+    fn the_panic_mark_wins() {
+        let cause = ResetCause::classify(Some(0x0c), true);
+        assert_eq!(cause, ResetCause::Panic);
+        assert!(cause.is_abnormal());
+        assert!(!ResetCause::classify(Some(0x0c), false).is_abnormal());
+    }
+
+    #[test]
+    fn panic_message_keeps_the_start() {
+        let mut message = PanicMessage::new();
+        write!(message, "panicked at {}", "x".repeat(100)).unwrap();
+        assert_eq!(message.as_str().len(), PANIC_MESSAGE_LEN);
+        assert!(message.as_str().starts_with("panicked at xxx"));
+
+        // Never cut inside a character
+        let mut message = PanicMessage::new();
+        write!(message, "{}ºC", "x".repeat(PANIC_MESSAGE_LEN - 1)).unwrap();
+        assert_eq!(message.as_str().len(), PANIC_MESSAGE_LEN - 1);
+
+        // Garbage in RTC memory
+        let mut bytes = [b'x'; PANIC_MESSAGE_LEN];
+        bytes[10] = 0xff;
+        assert_eq!(PanicMessage::from_raw(bytes, 1_000).as_str(), "xxxxxxxxxx");
+    }
+
+    #[test]
+    fn sos_is_a_word_long() {
+        let units: u32 = SOS.iter().map(|(on, off)| on + off).sum();
+        assert_eq!(units, 34);
+    }
+}
@@ -0,0 +1,129 @@
+use core::fmt;
+
+use crate::Reading;
+
+// The frame sensor, an MPU6050 with AD0 tied high. The machine's stays on
+// 0x68.
+pub const FRAME_ADDRESS: u8 = 0x69;
+
+// Two sensors, one on the machine and one on the frame or floor it stands
+// on. What the building does shows up on both, so the machine's reading
+// minus the frame's motion is the machine's own. The frame's motion is its
+// deviation from its rest vector, taken at the boot calibration: gravity
+// stays in the result, for the tilt and detach checks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Differential {
+    // m/s^2 and rad/s, as the frame sensor read them at rest
+    rest_acc: [f32; 3],
+    rest_gyro: [f32; 3],
+}
+
+impl Differential {
+    pub fn new(rest_acc: [f32; 3], rest_gyro: [f32; 3]) -> Self {
+        Self {
+            rest_acc,
+            rest_gyro,
+        }
+    }
+
+    // Both in m/s^2. The machine's reading keeps its timestamp and
+    // temperature.
+    pub fn apply(&self, machine: &Reading, frame: &Reading) -> Reading {
+        let minus_motion = |machine: [f32; 3], frame: [f32; 3], rest: [f32; 3]| {
+            [0, 1, 2].map(|i| machine[i] - (frame[i] - rest[i]))
+        };
+        Reading {
+            acc: minus_motion(machine.acc, frame.acc, self.rest_acc),
+            gyro: minus_motion(machine.gyro, frame.gyro, self.rest_gyro),
+            ..*machine
+        }
+    }
+}
+
+// How far apart the two sensors were read, in µs. The reads share the bus
+// one after the other, so the frame's reading is always a little later: a
+// machine shock shorter than that can land on one sensor only.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Skew {
+    last_us: u32,
+    max_us: u32,
+    sum_us: u64,
+    count: u32,
+}
+
+impl Skew {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, skew_us: u32) {
+        self.last_us = skew_us;
+        self.max_us = self.max_us.max(skew_us);
+        self.sum_us += skew_us as u64;
+        self.count += 1;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    // None before the first pair
+    pub fn mean_us(&self) -> Option<u32> {
+        (self.count > 0).then(|| (self.sum_us / self.count as u64) as u32)
+    }
+}
+
+impl fmt::Display for Skew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mean_us() {
+            Some(mean) => write!(
+                f,
+                "{} µs, mean {} µs, max {} µs over {} pairs",
+                self.last_us, mean, self.max_us, self.count
+            ),
+            None => write!(f, "no pairs"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn building_motion_cancels_out() {
+        let differential = Differential::new([0.0, 0.0, 9.75], [0.01, 0.0, -0.02]);
+        let machine = Reading::new([0.5, -0.25, 10.75], [0.11, 0.0, 0.0], 31.0, 2_000);
+        // The floor moving by 0.25 m/s^2 on X and 1 m/s^2 on Z
+        let frame = Reading::new([0.25, 0.0, 10.75], [0.01, 0.0, -0.02], 24.0, 2_001);
+
+        let reading = differential.apply(&machine, &frame);
+        assert_eq!(reading.acc, [0.25, -0.25, 9.75]);
+        assert_eq!(reading.gyro, [0.11, 0.0, 0.0]);
+        assert_eq!((reading.temp, reading.t_ms), (31.0, 2_000));
+    }
+
+    #[test]
+    fn skew_stats() {
+        let mut skew = Skew::new();
+        assert_eq!(skew.mean_us(), None);
+        assert_eq!(skew.to_string(), "no pairs");
+
+        for us in [1_200, 1_400, 1_000] {
+            skew.push(us);
+        }
+        assert_eq!(skew.max_us(), 1_400);
+        assert_eq!(skew.mean_us(), Some(1_200));
+        assert_eq!(
+            skew.to_string(),
+            "1000 µs, mean 1200 µs, max 1400 µs over 3 pairs"
+        );
+
+        skew.clear();
+        assert_eq!(skew.max_us(), 0);
+    }
+}
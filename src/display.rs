@@ -0,0 +1,487 @@
+use core::fmt::Write as _;
+
+use embedded_hal::blocking::i2c::Write;
+use heapless::String;
+
+use crate::{fixed::Fixed, math, Levels, Limit, MaintenanceMonitor, Reading};
+
+// SSD1306 128x64 OLED, on the MPU6050's bus
+pub const DISPLAY_ADDRESS: u8 = 0x3c;
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+// Rows of 8 pixels, one byte per column
+pub const PAGES: usize = HEIGHT / 8;
+
+// A new frame twice a second. Sent one page per tick, so no tick waits for
+// the whole 1 KB to go out.
+pub const DISPLAY_PERIOD_MS: u32 = 500;
+
+// Failed pages in a row before the display counts as gone
+pub const DISPLAY_ATTEMPTS: u8 = 3;
+
+// Control bytes ahead of a command list and of display RAM data
+const COMMANDS: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+// Internal charge pump, horizontal addressing, flipped to have the header
+// pins on top like most of the 0.96" boards, then display on
+const INIT: [u8; 26] = [
+    COMMANDS, 0xae, 0xd5, 0x80, 0xa8, 0x3f, 0xd3, 0x00, 0x40, 0x8d, 0x14, 0x20, 0x00, 0xa1, 0xc8,
+    0xda, 0x12, 0x81, 0xcf, 0xd9, 0xf1, 0xdb, 0x40, 0xa4, 0xa6, 0xaf,
+];
+
+// 5x7 glyphs, a byte per column with the top pixel in bit 0. Lowercase
+// prints as uppercase, anything else missing as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00],
+        '%' => [0x23, 0x13, 0x08, 0x64, 0x62],
+        '+' => [0x08, 0x08, 0x3e, 0x08, 0x08],
+        '-' => [0x08, 0x08, 0x08, 0x08, 0x08],
+        '.' => [0x00, 0x60, 0x60, 0x00, 0x00],
+        '/' => [0x20, 0x10, 0x08, 0x04, 0x02],
+        '0' => [0x3e, 0x51, 0x49, 0x45, 0x3e],
+        '1' => [0x00, 0x42, 0x7f, 0x40, 0x00],
+        '2' => [0x42, 0x61, 0x51, 0x49, 0x46],
+        '3' => [0x21, 0x41, 0x45, 0x4b, 0x31],
+        '4' => [0x18, 0x14, 0x12, 0x7f, 0x10],
+        '5' => [0x27, 0x45, 0x45, 0x45, 0x39],
+        '6' => [0x3c, 0x4a, 0x49, 0x49, 0x30],
+        '7' => [0x01, 0x71, 0x09, 0x05, 0x03],
+        '8' => [0x36, 0x49, 0x49, 0x49, 0x36],
+        '9' => [0x06, 0x49, 0x49, 0x29, 0x1e],
+        ':' => [0x00, 0x36, 0x36, 0x00, 0x00],
+        'A' => [0x7e, 0x11, 0x11, 0x11, 0x7e],
+        'B' => [0x7f, 0x49, 0x49, 0x49, 0x36],
+        'C' => [0x3e, 0x41, 0x41, 0x41, 0x22],
+        'D' => [0x7f, 0x41, 0x41, 0x22, 0x1c],
+        'E' => [0x7f, 0x49, 0x49, 0x49, 0x41],
+        'F' => [0x7f, 0x09, 0x09, 0x09, 0x01],
+        'G' => [0x3e, 0x41, 0x49, 0x49, 0x7a],
+        'H' => [0x7f, 0x08, 0x08, 0x08, 0x7f],
+        'I' => [0x00, 0x41, 0x7f, 0x41, 0x00],
+        'J' => [0x20, 0x40, 0x41, 0x3f, 0x01],
+        'K' => [0x7f, 0x08, 0x14, 0x22, 0x41],
+        'L' => [0x7f, 0x40, 0x40, 0x40, 0x40],
+        'M' => [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+        'N' => [0x7f, 0x04, 0x08, 0x10, 0x7f],
+        'O' => [0x3e, 0x41, 0x41, 0x41, 0x3e],
+        'P' => [0x7f, 0x09, 0x09, 0x09, 0x06],
+        'Q' => [0x3e, 0x41, 0x51, 0x21, 0x5e],
+        'R' => [0x7f, 0x09, 0x19, 0x29, 0x46],
+        'S' => [0x46, 0x49, 0x49, 0x49, 0x31],
+        'T' => [0x01, 0x01, 0x7f, 0x01, 0x01],
+        'U' => [0x3f, 0x40, 0x40, 0x40, 0x3f],
+        'V' => [0x1f, 0x20, 0x40, 0x20, 0x1f],
+        'W' => [0x3f, 0x40, 0x38, 0x40, 0x3f],
+        'X' => [0x63, 0x14, 0x08, 0x14, 0x63],
+        'Y' => [0x07, 0x08, 0x70, 0x08, 0x07],
+        'Z' => [0x61, 0x51, 0x49, 0x45, 0x43],
+        _ => [0x02, 0x01, 0x51, 0x09, 0x06],
+    }
+}
+
+// Pixels per character at scale 1, with the gap after it
+const ADVANCE: usize = 6;
+
+// Width of a line of text, without the gap after the last character
+pub fn text_width(text: &str, scale: usize) -> usize {
+    (text.chars().count() * ADVANCE * scale).saturating_sub(scale)
+}
+
+// The display RAM, laid out like the SSD1306's: page by page, a byte per
+// column, the top pixel of the page in bit 0. Pixels off the edges are
+// dropped.
+pub struct Frame {
+    pixels: [u8; WIDTH * PAGES],
+}
+
+impl Frame {
+    pub const fn new() -> Self {
+        Self {
+            pixels: [0; WIDTH * PAGES],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels = [0; WIDTH * PAGES];
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < WIDTH && y < HEIGHT && self.pixels[y / 8 * WIDTH + x] & (1 << (y % 8)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+        let byte = &mut self.pixels[y / 8 * WIDTH + x];
+        if on {
+            *byte |= 1 << (y % 8);
+        } else {
+            *byte &= !(1 << (y % 8));
+        }
+    }
+
+    pub fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, on: bool) {
+        for y in y..y + height {
+            for x in x..x + width {
+                self.set(x, y, on);
+            }
+        }
+    }
+
+    pub fn outline(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        self.fill(x, y, width, 1, true);
+        self.fill(x, y + height - 1, width, 1, true);
+        self.fill(x, y, 1, height, true);
+        self.fill(x + width - 1, y, 1, height, true);
+    }
+
+    // Each glyph pixel as a `scale` x `scale` block, `on` or off for text
+    // over a lit background
+    pub fn text(&mut self, x: usize, y: usize, text: &str, scale: usize, on: bool) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * ADVANCE * scale;
+            for (column, bits) in glyph(c).into_iter().enumerate() {
+                for row in 0..7 {
+                    if bits & (1 << row) != 0 {
+                        let (px, py) = (left + column * scale, y + row * scale);
+                        self.fill(px, py, scale, scale, on);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn page(&self, page: usize) -> &[u8] {
+        &self.pixels[page * WIDTH..(page + 1) * WIDTH]
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One limit as a bar, filled up to the critical level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bar {
+    pub label: &'static str,
+    // Both as a share of the critical level, 0 to 1
+    pub fill: f32,
+    pub warning: f32,
+}
+
+impl Bar {
+    pub fn new(label: &'static str, value: f32, levels: &Levels) -> Self {
+        let share = |value: f32| {
+            let share = value / levels.critical;
+            if share.is_finite() {
+                share.clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        Self {
+            label,
+            fill: share(value),
+            warning: share(levels.warning),
+        }
+    }
+}
+
+// What the display shows of a status sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Screen {
+    // |a| in m/s^2, gravity included
+    pub magnitude: f32,
+    pub temp: f32,
+    pub bars: [Bar; 3],
+    // The latched limit with the highest priority
+    pub alarm: Option<Limit>,
+}
+
+impl Screen {
+    pub fn new(reading: &Reading, monitor: &MaintenanceMonitor) -> Self {
+        let thresholds = monitor.thresholds();
+        let [x, y, z] = reading.acc;
+        Self {
+            magnitude: math::sqrt(x * x + y * y + z * z),
+            temp: reading.temp,
+            bars: [
+                Bar::new(
+                    "MECH",
+                    monitor.reading(Limit::Mechanical),
+                    &thresholds.mechanical,
+                ),
+                Bar::new(
+                    "GYRO",
+                    monitor.reading(Limit::Rotational),
+                    &thresholds.rotational,
+                ),
+                Bar::new("TEMP", reading.temp, &thresholds.temperature_ceiling),
+            ],
+            alarm: Limit::ALL
+                .into_iter()
+                .find(|limit| monitor.latched(*limit).is_some()),
+        }
+    }
+
+    fn acc_text(&self) -> String<16> {
+        value_text(self.magnitude, 2, " M/S2")
+    }
+
+    fn temp_text(&self) -> String<16> {
+        value_text(self.temp, 1, " C")
+    }
+}
+
+// `--` for a NaN, no room for more on a line
+fn value_text(value: f32, decimals: u8, unit: &str) -> String<16> {
+    let mut text = String::new();
+    let _ = match Fixed::new(value, decimals) {
+        Some(value) => write!(text, "{}{}", value, unit),
+        None => write!(text, "--{}", unit),
+    };
+    text
+}
+
+const BAR_LEFT: usize = 30;
+const BAR_WIDTH: usize = WIDTH - BAR_LEFT;
+const BAR_HEIGHT: usize = 8;
+
+// The readings on top, the bars below, or a lit ALARM banner with the limit
+// name while one is latched
+pub fn render(frame: &mut Frame, screen: &Screen) {
+    frame.clear();
+
+    let Some(limit) = screen.alarm else {
+        frame.text(0, 0, &screen.acc_text(), 2, true);
+        frame.text(0, 18, &screen.temp_text(), 2, true);
+        for (i, bar) in screen.bars.iter().enumerate() {
+            draw_bar(frame, 36 + i * (BAR_HEIGHT + 1), bar);
+        }
+        return;
+    };
+
+    frame.fill(0, 0, WIDTH, 30, true);
+    let banner = "ALARM";
+    frame.text((WIDTH - text_width(banner, 3)) / 2, 4, banner, 3, false);
+
+    let name = limit.name();
+    let scale = if text_width(name, 2) <= WIDTH { 2 } else { 1 };
+    frame.text((WIDTH - text_width(name, scale)) / 2, 34, name, scale, true);
+
+    let mut values: String<32> = String::new();
+    let _ = write!(values, "{}  {}", screen.acc_text(), screen.temp_text());
+    frame.text((WIDTH - text_width(&values, 1)) / 2, 56, &values, 1, true);
+}
+
+// The fill up to the value, and a notch at the warning level, dark over
+// the fill
+fn draw_bar(frame: &mut Frame, y: usize, bar: &Bar) {
+    frame.text(0, y + 1, bar.label, 1, true);
+    frame.outline(BAR_LEFT, y, BAR_WIDTH, BAR_HEIGHT);
+
+    let inner = BAR_WIDTH - 2;
+    let filled = (bar.fill * inner as f32) as usize;
+    frame.fill(BAR_LEFT + 1, y + 1, filled, BAR_HEIGHT - 2, true);
+
+    let notch = BAR_LEFT + 1 + ((bar.warning * inner as f32) as usize).min(inner - 1);
+    let lit = frame.get(notch, y + 1);
+    frame.fill(notch, y, 1, BAR_HEIGHT, !lit);
+}
+
+// The SSD1306 over I2C, with a frame sent one page per `flush_page()`
+pub struct Oled<I> {
+    i2c: I,
+    address: u8,
+    frame: Frame,
+    // Next page to send, None once the frame is out
+    next_page: Option<usize>,
+    failures: u8,
+}
+
+impl<I, E> Oled<I>
+where
+    I: Write<Error = E>,
+{
+    pub fn new(i2c: I, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            frame: Frame::new(),
+            next_page: None,
+            failures: 0,
+        }
+    }
+
+    // Fails with a NACK when there's no display at the address. The RAM
+    // keeps whatever it powered up with until the first frame is out.
+    pub fn init(&mut self) -> Result<(), E> {
+        self.i2c.write(self.address, &INIT)
+    }
+
+    // Starts over any frame still going out
+    pub fn show(&mut self, screen: &Screen) {
+        render(&mut self.frame, screen);
+        self.next_page = Some(0);
+    }
+
+    pub fn is_flushing(&self) -> bool {
+        self.next_page.is_some()
+    }
+
+    // 128 bytes, about 3 ms at 400 kHz. A failed page is sent again next time.
+    pub fn flush_page(&mut self) -> Result<(), E> {
+        let Some(page) = self.next_page else {
+            return Ok(());
+        };
+        let sent = self.send_page(page);
+        self.failures = match sent {
+            Ok(()) => 0,
+            Err(_) => self.failures.saturating_add(1),
+        };
+        sent?;
+
+        self.next_page = Some(page + 1).filter(|page| *page < PAGES);
+        Ok(())
+    }
+
+    // Unplugged, or its power or wiring went bad
+    pub fn is_lost(&self) -> bool {
+        self.failures >= DISPLAY_ATTEMPTS
+    }
+
+    fn send_page(&mut self, page: usize) -> Result<(), E> {
+        self.i2c.write(
+            self.address,
+            &[
+                COMMANDS,
+                0x21,
+                0,
+                WIDTH as u8 - 1,
+                0x22,
+                page as u8,
+                page as u8,
+            ],
+        )?;
+        let mut data = [DATA; WIDTH + 1];
+        data[1..].copy_from_slice(self.frame.page(page));
+        self.i2c.write(self.address, &data)
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Thresholds, SAMPLE_PERIOD_MS};
+
+    // Keeps what went to the display, NACKs any other address
+    struct Bus {
+        writes: std::vec::Vec<(u8, std::vec::Vec<u8>)>,
+    }
+
+    impl Write for Bus {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            if address != DISPLAY_ADDRESS {
+                return Err(());
+            }
+            self.writes.push((address, bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    fn screen(alarm: Option<Limit>) -> Screen {
+        let levels = Levels::new(1.0, 2.0);
+        Screen {
+            magnitude: 9.81,
+            temp: 25.5,
+            bars: [
+                Bar::new("MECH", 1.5, &levels),
+                Bar::new("GYRO", 0.0, &levels),
+                Bar::new("TEMP", f32::NAN, &levels),
+            ],
+            alarm,
+        }
+    }
+
+    #[test]
+    fn missing_display_fails_init() {
+        let mut oled = Oled::new(Bus { writes: vec![] }, 0x3d);
+        assert_eq!(oled.init(), Err(()));
+
+        let mut oled = Oled::new(Bus { writes: vec![] }, DISPLAY_ADDRESS);
+        assert_eq!(oled.init(), Ok(()));
+        assert_eq!(oled.i2c.writes[0].1, INIT);
+
+        // Unplugged later on
+        oled.address = 0x3d;
+        oled.show(&screen(None));
+        for _ in 0..DISPLAY_ATTEMPTS {
+            assert!(!oled.is_lost());
+            assert_eq!(oled.flush_page(), Err(()));
+        }
+        assert!(oled.is_lost());
+        assert!(oled.is_flushing());
+    }
+
+    #[test]
+    fn frame_goes_out_a_page_at_a_time() {
+        let mut oled = Oled::new(Bus { writes: vec![] }, DISPLAY_ADDRESS);
+        oled.show(&screen(None));
+
+        for page in 0..PAGES {
+            assert!(oled.is_flushing());
+            oled.flush_page().unwrap();
+            let (command, data) = (&oled.i2c.writes[2 * page], &oled.i2c.writes[2 * page + 1]);
+            assert_eq!(
+                command.1,
+                [COMMANDS, 0x21, 0, 127, 0x22, page as u8, page as u8]
+            );
+            assert_eq!(data.1[0], DATA);
+            assert_eq!(&data.1[1..], oled.frame().page(page));
+        }
+        assert!(!oled.is_flushing());
+        oled.flush_page().unwrap();
+        assert_eq!(oled.i2c.writes.len(), 2 * PAGES);
+    }
+
+    #[test]
+    fn bars_and_alarm_banner() {
+        let mut frame = Frame::new();
+        render(&mut frame, &screen(None));
+        // Three quarters full, the notch dark in the fill
+        let (y, inner) = (36 + 4, BAR_WIDTH - 2);
+        assert!(frame.get(BAR_LEFT + 1 + inner / 4, y));
+        assert!(!frame.get(BAR_LEFT + 1 + inner / 2, y));
+        assert!(frame.get(BAR_LEFT + 1 + inner * 5 / 8, y));
+        assert!(!frame.get(BAR_LEFT + 1 + inner * 7 / 8, y));
+        assert!(!frame.get(0, 0));
+        assert_eq!(screen(None).bars[2].fill, 0.0);
+
+        render(&mut frame, &screen(Some(Limit::SensorDetached)));
+        assert!(frame.get(0, 0));
+        assert!(frame.get(WIDTH - 1, 29));
+        assert!(!frame.get(0, 30));
+        // Lettering in the banner
+        assert!((0..WIDTH).any(|x| !frame.get(x, 10)));
+
+        let mut monitor = MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let reading = Reading::new([0.0, 3.0, 4.0], [0.0; 3], 30.0, 0);
+        monitor.update(&reading);
+        let screen = Screen::new(&reading, &monitor);
+        assert_eq!(screen.magnitude, 5.0);
+        assert_eq!(screen.alarm, None);
+        assert_eq!(screen.acc_text(), "5.00 M/S2");
+        assert_eq!(screen.temp_text(), "30.0 C");
+    }
+}
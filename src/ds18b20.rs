@@ -0,0 +1,208 @@
+use crate::crc::crc8;
+use crate::onewire::{OneWire, Rom};
+
+pub const FAMILY: u8 = 0x28;
+
+// Function commands
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xbe;
+
+const SCRATCHPAD_LEN: usize = 9;
+
+// At the power-on 12-bit resolution a conversion takes up to 750 ms, so the
+// probe is read every second, a conversion behind
+pub const CONVERSION_MS: u32 = 750;
+pub const PROBE_PERIOD_MS: u32 = 1_000;
+
+// Failed cycles in a row before the channel is given up on
+pub const PROBE_ATTEMPTS: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeError {
+    // No presence pulse
+    Missing,
+    BadCrc,
+}
+
+impl ProbeError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ProbeError::Missing => "not answering",
+            ProbeError::BadCrc => "scratchpad CRC mismatch",
+        }
+    }
+}
+
+// ºC from the first two scratchpad bytes, 1/16 ºC per count
+pub fn temperature(scratchpad: &[u8; SCRATCHPAD_LEN]) -> f32 {
+    i16::from_le_bytes([scratchpad[0], scratchpad[1]]) as f32 / 16.0
+}
+
+// A DS18B20 on external power, without blocking for the conversions: every
+// `cycle()` reads the temperature the previous one started converting and
+// starts the next, so they have to be CONVERSION_MS apart.
+pub struct Ds18b20 {
+    rom: Rom,
+    converting: bool,
+    failures: u8,
+}
+
+impl Ds18b20 {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            rom,
+            converting: false,
+            failures: 0,
+        }
+    }
+
+    pub fn rom(&self) -> Rom {
+        self.rom
+    }
+
+    // The last conversion's temperature, None on the first call. A failed
+    // read still starts the next conversion.
+    pub fn cycle<W: OneWire>(&mut self, bus: &mut W) -> Result<Option<f32>, ProbeError> {
+        let temp = match self.converting {
+            true => self.read(bus).map(Some),
+            false => Ok(None),
+        };
+        let started = self.start(bus);
+        self.converting = started.is_ok();
+
+        let result = started.and(temp);
+        match result {
+            Ok(_) => self.failures = 0,
+            Err(_) => self.failures = self.failures.saturating_add(1),
+        }
+        result
+    }
+
+    // PROBE_ATTEMPTS failed cycles in a row
+    pub fn is_lost(&self) -> bool {
+        self.failures >= PROBE_ATTEMPTS
+    }
+
+    fn start<W: OneWire>(&self, bus: &mut W) -> Result<(), ProbeError> {
+        if !self.rom.select(bus) {
+            return Err(ProbeError::Missing);
+        }
+        bus.write_byte(CONVERT_T);
+        Ok(())
+    }
+
+    fn read<W: OneWire>(&self, bus: &mut W) -> Result<f32, ProbeError> {
+        if !self.rom.select(bus) {
+            return Err(ProbeError::Missing);
+        }
+        bus.write_byte(READ_SCRATCHPAD);
+        let mut scratchpad = [0; SCRATCHPAD_LEN];
+        for byte in &mut scratchpad {
+            *byte = bus.read_byte();
+        }
+        // A missing device reads as all ones, which fails the CRC too
+        if crc8(&scratchpad) != 0 {
+            return Err(ProbeError::BadCrc);
+        }
+        Ok(temperature(&scratchpad))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One probe, answering with `scratchpad` whatever it's asked, and the
+    // bytes written to it since the last reset
+    struct Probe {
+        present: bool,
+        scratchpad: [u8; SCRATCHPAD_LEN],
+        written: Vec<bool>,
+        read: usize,
+        commands: Vec<u8>,
+    }
+
+    impl Probe {
+        fn new(scratchpad: [u8; SCRATCHPAD_LEN]) -> Self {
+            Self {
+                present: true,
+                scratchpad,
+                written: Vec::new(),
+                read: 0,
+                commands: Vec::new(),
+            }
+        }
+    }
+
+    impl OneWire for Probe {
+        fn reset(&mut self) -> bool {
+            self.written.clear();
+            self.read = 0;
+            self.present
+        }
+
+        fn write_bit(&mut self, bit: bool) {
+            self.written.push(bit);
+            // The function command comes after MATCH_ROM and the 8 ROM bytes
+            if self.written.len() == 80 {
+                let byte = (0..8).fold(0, |byte, i| byte | (self.written[72 + i] as u8) << i);
+                self.commands.push(byte);
+            }
+        }
+
+        fn read_bit(&mut self) -> bool {
+            let bit = self.scratchpad[self.read / 8] >> (self.read % 8) & 1 == 1;
+            self.read += 1;
+            bit
+        }
+    }
+
+    fn scratchpad(temp_counts: i16) -> [u8; SCRATCHPAD_LEN] {
+        let [lsb, msb] = temp_counts.to_le_bytes();
+        let mut scratchpad = [lsb, msb, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0];
+        scratchpad[8] = crc8(&scratchpad[..8]);
+        scratchpad
+    }
+
+    const ROM: Rom = Rom([0x28, 0xff, 0x4c, 0x60, 0x91, 0x16, 0x04, 0x00]);
+
+    #[test]
+    fn reads_a_conversion_behind() {
+        let mut probe = Probe::new(scratchpad(0x0191));
+        let mut ds18b20 = Ds18b20::new(ROM);
+
+        // Only starts the first conversion
+        assert_eq!(ds18b20.cycle(&mut probe), Ok(None));
+        assert_eq!(probe.commands, [CONVERT_T]);
+
+        // 0x0191 is 25.0625 ºC in the datasheet's table
+        assert_eq!(ds18b20.cycle(&mut probe), Ok(Some(25.0625)));
+        assert_eq!(probe.commands, [CONVERT_T, READ_SCRATCHPAD, CONVERT_T]);
+        assert_eq!(temperature(&scratchpad(-0x0191)), -25.0625);
+    }
+
+    #[test]
+    fn bad_reads_count_towards_losing_the_probe() {
+        let mut probe = Probe::new(scratchpad(0x0191));
+        let mut ds18b20 = Ds18b20::new(ROM);
+        ds18b20.cycle(&mut probe).unwrap();
+
+        probe.scratchpad[0] ^= 0x01;
+        assert_eq!(ds18b20.cycle(&mut probe), Err(ProbeError::BadCrc));
+        probe.present = false;
+        assert_eq!(ds18b20.cycle(&mut probe), Err(ProbeError::Missing));
+        assert!(!ds18b20.is_lost());
+        assert_eq!(ds18b20.cycle(&mut probe), Err(ProbeError::Missing));
+        assert!(ds18b20.is_lost());
+
+        // A good cycle in between starts the count over
+        let mut ds18b20 = Ds18b20::new(ROM);
+        let mut probe = Probe::new(scratchpad(0x0191));
+        ds18b20.cycle(&mut probe).unwrap();
+        probe.scratchpad[0] ^= 0x01;
+        ds18b20.cycle(&mut probe).unwrap_err();
+        probe.scratchpad[0] ^= 0x01;
+        assert_eq!(ds18b20.cycle(&mut probe), Ok(Some(25.0625)));
+        assert_eq!(ds18b20.failures, 0);
+    }
+}
@@ -0,0 +1,152 @@
+use heapless::HistoryBuffer;
+
+use crate::biquad::{Biquad, Coefficients};
+use crate::math;
+
+// A bearing defect rings the structure at its resonance, a few hundred Hz
+// and up, once per ball pass. The band is picked around that ringing, the
+// envelope low-pass keeps the repetition rate.
+pub const ENVELOPE_BAND_HZ: f32 = 300.0;
+pub const ENVELOPE_BAND_Q: f32 = 2.0;
+pub const ENVELOPE_CUTOFF_HZ: f32 = 100.0;
+// Samples of each burst left out while the filters settle
+pub const ENVELOPE_SETTLE_SAMPLES: usize = 32;
+pub const ENVELOPE_WINDOW: usize = 192;
+
+// Envelope of the acceleration magnitude in one frequency band.
+// Steady vibration has a flat envelope, a crest factor close to 1. Short
+// repetitive bursts have a spiky one, and a crest factor well above.
+// Meant for the ~1 kHz spectrum bursts, `start()` before each of them.
+pub struct Envelope<const N: usize = ENVELOPE_WINDOW> {
+    band_pass: Biquad,
+    low_pass: Biquad,
+    settling: usize,
+    envelope: HistoryBuffer<f32, N>,
+}
+
+impl<const N: usize> Envelope<N> {
+    pub fn new(rate_hz: f32) -> Self {
+        Self::with_filters(
+            Coefficients::band_pass(ENVELOPE_BAND_HZ, ENVELOPE_BAND_Q, rate_hz),
+            Coefficients::low_pass(
+                ENVELOPE_CUTOFF_HZ,
+                core::f32::consts::FRAC_1_SQRT_2,
+                rate_hz,
+            ),
+        )
+    }
+
+    pub fn with_filters(band_pass: Coefficients, low_pass: Coefficients) -> Self {
+        Self {
+            band_pass: Biquad::new(band_pass),
+            low_pass: Biquad::new(low_pass),
+            settling: ENVELOPE_SETTLE_SAMPLES,
+            envelope: HistoryBuffer::new(),
+        }
+    }
+
+    // Starts a new burst, the filters settle again
+    pub fn start(&mut self) {
+        self.band_pass.reset();
+        self.low_pass.reset();
+        self.settling = ENVELOPE_SETTLE_SAMPLES;
+        self.envelope.clear();
+    }
+
+    // Acceleration in m/s^2
+    pub fn push(&mut self, acc: [f32; 3]) {
+        let [x, y, z] = acc;
+        let band = self.band_pass.push(math::sqrt(x * x + y * y + z * z));
+        // Rectified, then smoothed
+        let envelope = self.low_pass.push(math::abs(band));
+
+        if self.settling > 0 {
+            self.settling -= 1;
+        } else {
+            self.envelope.write(envelope);
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.envelope.len() == self.envelope.capacity()
+    }
+
+    // RMS of the envelope in m/s^2, None until the window is full
+    pub fn rms(&self) -> Option<f32> {
+        if !self.is_full() || N == 0 {
+            return None;
+        }
+
+        let squares: f32 = self.envelope.as_slice().iter().map(|e| e * e).sum();
+        Some(math::sqrt(squares / N as f32))
+    }
+
+    // Peak of the envelope over its RMS
+    pub fn crest_factor(&self) -> Option<f32> {
+        let rms = self.rms()?;
+        if rms <= 0.0 {
+            return None;
+        }
+
+        let peak = self
+            .envelope
+            .as_slice()
+            .iter()
+            .fold(0.0, |peak: f32, e| peak.max(math::abs(*e)));
+        Some(peak / rms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE_HZ: f32 = 1_000.0;
+    const BURST_SAMPLES: usize = ENVELOPE_SETTLE_SAMPLES + ENVELOPE_WINDOW;
+
+    fn carrier(n: usize) -> f32 {
+        math::sin(2.0 * core::f32::consts::PI * ENVELOPE_BAND_HZ * n as f32 / RATE_HZ)
+    }
+
+    fn burst(envelope: &mut Envelope, wave: impl Fn(usize) -> f32) {
+        envelope.start();
+        for n in 0..BURST_SAMPLES {
+            envelope.push([0.0, 0.0, 9.81 + wave(n)]);
+        }
+    }
+
+    #[test]
+    fn steady_vibration_has_a_low_crest_factor() {
+        let mut envelope: Envelope = Envelope::new(RATE_HZ);
+
+        burst(&mut envelope, |n| 0.5 * carrier(n));
+        assert!(envelope.crest_factor().unwrap() < 1.5);
+        assert!(envelope.rms().unwrap() > 0.2);
+    }
+
+    #[test]
+    fn repetitive_bursts_have_a_high_crest_factor() {
+        let mut envelope: Envelope = Envelope::new(RATE_HZ);
+
+        // 5 ms of ringing every 40 ms, a 25 Hz defect frequency
+        burst(&mut envelope, |n| {
+            if n % 40 < 5 {
+                2.0 * carrier(n)
+            } else {
+                0.0
+            }
+        });
+        let crest = envelope.crest_factor().unwrap();
+        assert!(crest > 2.0, "crest factor {}", crest);
+    }
+
+    #[test]
+    fn gravity_and_slow_motion_stay_out_of_the_band() {
+        let mut envelope: Envelope = Envelope::new(RATE_HZ);
+
+        burst(&mut envelope, |n| {
+            math::sin(2.0 * core::f32::consts::PI * 10.0 * n as f32 / RATE_HZ)
+        });
+        assert!(envelope.rms().unwrap() < 0.02);
+    }
+}
@@ -0,0 +1,311 @@
+use core::fmt;
+
+use heapless::Deque;
+
+use crate::console::Setting;
+use crate::time::Timestamp;
+use crate::{Alert, Fault, Limit, MaintenanceMonitor, ResetCause, Severity};
+
+// 16 bytes an entry, 4 KB of RAM
+pub const EVENT_LOG_CAPACITY: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Reset(ResetCause),
+    Alarm(Limit, Severity),
+    Fault(Fault),
+    // The MPU's temperature out of line with the chip's, see `CrossCheck`
+    Implausible,
+    // From the console
+    Setting(Setting),
+    FactoryReset,
+    Calibrated,
+    // Running hours since the last service and the interval
+    MaintenanceDue,
+    ServiceDone,
+}
+
+// `value` and `threshold` are NaN for the kinds that have none. An alarm's
+// value is the peak of its reading while latched, the threshold the level
+// it tripped at. A setting's value is the new warning level, the
+// threshold the old one. A service's value is the running hours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    // Uptime, in s
+    pub t_s: u32,
+    pub kind: EventKind,
+    pub value: f32,
+    pub threshold: f32,
+}
+
+impl Event {
+    pub fn new(t_s: u32, kind: EventKind) -> Self {
+        Self {
+            t_s,
+            kind,
+            value: f32::NAN,
+            threshold: f32::NAN,
+        }
+    }
+
+    pub fn with_values(self, value: f32, threshold: f32) -> Self {
+        Self {
+            value,
+            threshold,
+            ..self
+        }
+    }
+}
+
+// One line, after the uptime
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", Timestamp(self.t_s as u64 * 1_000))?;
+        match self.kind {
+            EventKind::Reset(cause) => write!(f, "reset: {}", cause.description()),
+            EventKind::Alarm(limit, severity) => {
+                write!(
+                    f,
+                    "{} {}: peak {}",
+                    limit.name(),
+                    severity.label(),
+                    self.value
+                )?;
+                if !self.threshold.is_nan() {
+                    write!(f, ", level {}", self.threshold)?;
+                }
+                Ok(())
+            }
+            EventKind::Fault(fault) => write!(f, "fault: {}", fault.description()),
+            EventKind::Implausible => write!(
+                f,
+                "MPU temperature implausible: {} ºC off the chip's",
+                self.value
+            ),
+            EventKind::Setting(setting) => write!(
+                f,
+                "set {}: {} {}, was {}",
+                setting.name(),
+                self.value,
+                setting.unit(),
+                self.threshold
+            ),
+            EventKind::FactoryReset => write!(f, "factory reset"),
+            EventKind::Calibrated => write!(f, "recalibrated"),
+            EventKind::MaintenanceDue => write!(
+                f,
+                "maintenance due: {} running hours since the last service, every {} h",
+                self.value, self.threshold
+            ),
+            EventKind::ServiceDone => write!(f, "service done at {} running hours", self.value),
+        }
+    }
+}
+
+// The flight recorder: everything worth knowing about later, up to N
+// events. A full log drops its oldest to make room.
+pub struct EventLog<const N: usize = EVENT_LOG_CAPACITY> {
+    events: Deque<Event, N>,
+    // Limits still latched since their alarm, whose entry follows the peak
+    open: [bool; Limit::COUNT],
+    overwritten: u32,
+    // The newest events not yet written to flash
+    unsaved: usize,
+}
+
+impl<const N: usize> EventLog<N> {
+    pub const fn new() -> Self {
+        Self {
+            events: Deque::new(),
+            open: [false; Limit::COUNT],
+            overwritten: 0,
+            unsaved: 0,
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.events.is_full() {
+            self.events.pop_front();
+            self.overwritten = self.overwritten.saturating_add(1);
+        }
+        // There's room now
+        let _ = self.events.push_back(event);
+        self.unsaved = (self.unsaved + 1).min(self.events.len());
+    }
+
+    // The oldest event not yet written to flash, once. An alarm's peak is
+    // as far as it got by then.
+    pub fn take_unsaved(&mut self) -> Option<Event> {
+        if self.unsaved == 0 {
+            return None;
+        }
+        let event = self.events.iter().nth(self.events.len() - self.unsaved)?;
+        self.unsaved -= 1;
+        Some(*event)
+    }
+
+    // An alert as it fires, with the reading that raised it
+    pub fn alarm(&mut self, alert: &Alert, value: f32, threshold: Option<f32>, t_s: u32) {
+        let kind = EventKind::Alarm(alert.limit, alert.severity);
+        self.push(Event::new(t_s, kind).with_values(value, threshold.unwrap_or(f32::NAN)));
+        self.open[alert.limit as usize] = true;
+    }
+
+    // After every update: the latest alarm entry of each limit still
+    // latched keeps the highest reading since
+    pub fn track<const M: usize>(&mut self, monitor: &MaintenanceMonitor<M>) {
+        for limit in Limit::ALL {
+            if !self.open[limit as usize] {
+                continue;
+            }
+            if monitor.latched(limit).is_none() {
+                self.open[limit as usize] = false;
+                continue;
+            }
+
+            let reading = monitor.reading(limit);
+            let entry = self.events.iter_mut().rev().find(
+                |event| matches!(event.kind, EventKind::Alarm(alarmed, _) if alarmed == limit),
+            );
+            match entry {
+                Some(entry) if reading > entry.value => entry.value = reading,
+                Some(_) => {}
+                // Overwritten while it lasted
+                None => self.open[limit as usize] = false,
+            }
+        }
+    }
+
+    // Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    // Events dropped to make room since the last `clear()`
+    pub fn overwritten(&self) -> u32 {
+        self.overwritten
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl<const N: usize> Default for EventLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::{PROBE_RISE_CRITICAL, PROBE_RISE_WARNING};
+    use crate::{Reading, Thresholds, SAMPLE_PERIOD_MS};
+
+    const GRAVITY: [f32; 3] = [0.0, 0.0, 1.0];
+    const STILL: [f32; 3] = [0.0; 3];
+
+    #[test]
+    fn a_full_log_drops_the_oldest() {
+        assert!(core::mem::size_of::<Event>() <= 16);
+
+        let mut log: EventLog<4> = EventLog::new();
+        assert!(log.is_empty());
+        log.push(Event::new(0, EventKind::Reset(ResetCause::PowerOn)));
+        for t_s in 1..=4 {
+            log.push(Event::new(t_s, EventKind::Calibrated));
+        }
+        assert_eq!(log.len(), 4);
+        assert_eq!(log.overwritten(), 1);
+        let times: Vec<u32> = log.iter().map(|event| event.t_s).collect();
+        assert_eq!(times, [1, 2, 3, 4]);
+
+        let unsaved: Vec<u32> = core::iter::from_fn(|| log.take_unsaved())
+            .map(|event| event.t_s)
+            .collect();
+        assert_eq!(unsaved, [1, 2, 3, 4]);
+        assert_eq!(log.take_unsaved(), None);
+        log.push(Event::new(5, EventKind::FactoryReset));
+        assert_eq!(log.take_unsaved().map(|event| event.t_s), Some(5));
+
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(log.overwritten(), 0);
+    }
+
+    #[test]
+    fn alarm_entries_keep_the_peak_of_the_episode() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut log: EventLog = EventLog::new();
+        let mut t_s = 0;
+        let mut feed = |monitor: &mut MaintenanceMonitor, log: &mut EventLog, probe: f32| {
+            monitor.set_probe(Some(probe));
+            if let Some(alert) = monitor.update(&Reading::new(GRAVITY, STILL, 25.0, 0)) {
+                let threshold = monitor.threshold(alert.limit, alert.severity);
+                log.alarm(&alert, monitor.reading(alert.limit), threshold, t_s);
+            }
+            log.track(monitor);
+            t_s += 1;
+        };
+
+        // The probe's reference, then rising past both levels and back
+        for probe in [20.0, 36.0, 37.0, 40.0, 48.0, 52.0, 41.0] {
+            feed(&mut monitor, &mut log, probe);
+        }
+        for _ in 0..5 {
+            feed(&mut monitor, &mut log, 20.0);
+        }
+        assert_eq!(monitor.latched(Limit::Probe), None);
+        // A new episode, the old entries stay as they were
+        for probe in [45.0, 60.0] {
+            feed(&mut monitor, &mut log, probe);
+        }
+
+        let entries: Vec<Event> = log.iter().copied().collect();
+        let kinds: Vec<EventKind> = entries.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Alarm(Limit::Probe, Severity::Warning),
+                EventKind::Alarm(Limit::Probe, Severity::Critical),
+                EventKind::Alarm(Limit::Probe, Severity::Critical),
+            ]
+        );
+        // The warning's peak stops where the escalation takes over
+        assert_eq!(entries[0].threshold, PROBE_RISE_WARNING);
+        assert!(entries[0].value < entries[1].value);
+        assert_eq!(
+            (entries[1].value, entries[1].threshold),
+            (32.0, PROBE_RISE_CRITICAL)
+        );
+        assert_eq!(entries[2].value, 40.0);
+    }
+
+    #[test]
+    fn entries_print_on_one_line() {
+        let alarm = Event::new(
+            3_723,
+            EventKind::Alarm(Limit::Mechanical, Severity::Critical),
+        )
+        .with_values(1.5, 0.8);
+        assert_eq!(
+            alarm.to_string(),
+            "[01:02:03.000] Mechanical CRITICAL: peak 1.5, level 0.8"
+        );
+        let setting = Event::new(0, EventKind::Setting(Setting::Mechanical)).with_values(0.6, 0.5);
+        assert_eq!(
+            setting.to_string(),
+            "[00:00:00.000] set mech: 0.6 m/s^2, was 0.5"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// Weights of each new sample in the two motion averages. At 500 ms per
+// sample the fast one settles in well under a second, the slow one takes
+// about a minute.
+pub const FAST_ALPHA: f32 = 0.8;
+pub const SLOW_ALPHA: f32 = 0.01;
+
+// Fast and slow exponential averages of the same [x, y, z] signal.
+// The slow average is the reference, the fast one the measurement, and
+// their divergence is what gets checked against the limits: a machine
+// slowly shifting drags both along, an event building up over a few
+// seconds only moves the fast one, and a single-sample spike is mostly
+// smoothed out of both.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DualEwma {
+    fast_alpha: f32,
+    slow_alpha: f32,
+    fast: [f32; 3],
+    slow: [f32; 3],
+    seeded: bool,
+}
+
+impl DualEwma {
+    pub const fn new(fast_alpha: f32, slow_alpha: f32) -> Self {
+        Self {
+            fast_alpha,
+            slow_alpha,
+            fast: [0.0; 3],
+            slow: [0.0; 3],
+            seeded: false,
+        }
+    }
+
+    // Starts both averages at a known value, e.g. a calibration
+    pub fn seed(&mut self, value: [f32; 3]) {
+        self.fast = value;
+        self.slow = value;
+        self.seeded = true;
+    }
+
+    // Returns fast minus slow.
+    // Until seeded, i.e. on cold start, the sample seeds both averages and
+    // there is no divergence.
+    pub fn update(&mut self, value: [f32; 3]) -> [f32; 3] {
+        if !self.seeded {
+            self.seed(value);
+            return [0.0; 3];
+        }
+
+        let mut divergence = [0.0; 3];
+        for i in 0..3 {
+            self.fast[i] += self.fast_alpha * (value[i] - self.fast[i]);
+            // Against the reference from before this sample
+            divergence[i] = self.fast[i] - self.slow[i];
+            self.slow[i] += self.slow_alpha * (value[i] - self.slow[i]);
+        }
+        divergence
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        self.seeded
+    }
+
+    pub fn fast(&self) -> [f32; 3] {
+        self.fast
+    }
+
+    pub fn slow(&self) -> [f32; 3] {
+        self.slow
+    }
+}
+
+impl Default for DualEwma {
+    fn default() -> Self {
+        Self::new(FAST_ALPHA, SLOW_ALPHA)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: f32 = 0.5;
+
+    // Largest divergence on X over a sequence of samples
+    fn worst(input: impl Iterator<Item = f32>) -> f32 {
+        let mut ewma = DualEwma::default();
+        ewma.update([0.0; 3]);
+
+        input.fold(0.0, |worst, x| {
+            let [dx, ..] = ewma.update([x, 0.0, 0.0]);
+            worst.max(libm::fabsf(dx))
+        })
+    }
+
+    #[test]
+    fn cold_start_seeds_both_averages() {
+        let mut ewma = DualEwma::default();
+        assert!(!ewma.is_seeded());
+
+        assert_eq!(ewma.update([1.0, 2.0, 3.0]), [0.0; 3]);
+        assert_eq!(ewma.fast(), [1.0, 2.0, 3.0]);
+        assert_eq!(ewma.slow(), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn step_diverges() {
+        assert!(worst([1.0; 10].into_iter()) > 0.9);
+    }
+
+    #[test]
+    fn event_building_up_over_20_s_diverges() {
+        assert!(worst((1..=40).map(|n| n as f32 / 40.0)) > LIMIT);
+    }
+
+    #[test]
+    fn slow_ramp_does_not_diverge() {
+        // 1 m/s^2 over 5 minutes
+        assert!(worst((1..=600).map(|n| n as f32 / 600.0)) < LIMIT / 2.0);
+    }
+
+    #[test]
+    fn single_sample_impulse_fades() {
+        let mut ewma = DualEwma::default();
+        ewma.update([0.0; 3]);
+
+        assert!(ewma.update([1.0, 0.0, 0.0])[0] > LIMIT);
+        assert!(ewma.update([0.0; 3])[0] < LIMIT / 2.0);
+    }
+}
@@ -0,0 +1,55 @@
+// Conditions that keep the monitor from running at all.
+// Each one has its own blink count on the LED so it can be told apart
+// without a serial console.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    SelfTest,
+    UnknownSensor,
+    NoDevice,
+    Bus,
+    StuckSensor,
+}
+
+// Length of each half of a fault blink, and how often the pattern repeats, in ms
+pub const FAULT_BLINK_MS: u32 = 150;
+pub const FAULT_REPEAT_MS: u32 = 2_000;
+
+impl Fault {
+    // The flash event log indexes this
+    pub const ALL: [Fault; 5] = [
+        Fault::SelfTest,
+        Fault::UnknownSensor,
+        Fault::NoDevice,
+        Fault::Bus,
+        Fault::StuckSensor,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Fault::SelfTest => "MPU6050 self-test failed",
+            Fault::UnknownSensor => "unknown device at the MPU6050 address",
+            Fault::NoDevice => "no MPU6050 on the I2C bus",
+            Fault::Bus => "sensor not responding after bus recovery",
+            Fault::StuckSensor => "sensor output frozen after re-init",
+        }
+    }
+
+    pub fn blinks(&self) -> u8 {
+        match self {
+            Fault::SelfTest => 2,
+            Fault::UnknownSensor => 3,
+            Fault::NoDevice => 4,
+            Fault::Bus => 5,
+            Fault::StuckSensor => 6,
+        }
+    }
+
+    // A sensor that is damaged or stopped answering also sounds the buzzer,
+    // wiring problems found at boot only blink
+    pub fn beeps(&self) -> bool {
+        match self {
+            Fault::SelfTest | Fault::Bus | Fault::StuckSensor => true,
+            Fault::UnknownSensor | Fault::NoDevice => false,
+        }
+    }
+}
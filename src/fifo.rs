@@ -0,0 +1,173 @@
+use crate::sensor::{Dlpf, SensorConfig};
+
+// The MPU6050 FIFO holds 1024 bytes: 170 accelerometer frames, or 85 with
+// the gyroscope, i.e. 170 ms at 1 kHz before it overflows
+pub const FIFO_SIZE: usize = 1024;
+// Largest read in one go, a whole number of frames with or without the
+// gyroscope. At 400 kHz it takes ~6 ms.
+pub const FIFO_BURST_BYTES: usize = 240;
+
+// Sample rate divider for the spectrum bursts, 1 kHz / (1 + 0)
+pub const FIFO_RATE_DIVIDER: u8 = 0;
+
+const ACC_FRAME_BYTES: usize = 6;
+const GYRO_FRAME_BYTES: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FifoFrame {
+    // In g, like the driver's readings
+    pub acc: [f32; 3],
+    // In rad/s, only when the gyroscope goes into the FIFO too
+    pub gyro: Option<[f32; 3]>,
+}
+
+// Layout and scaling of the frames the FIFO is set up for.
+// The accelerometer comes first, then the gyroscope, each as big-endian
+// X/Y/Z counts at the configured range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FifoFormat {
+    pub gyro: bool,
+    acc_lsb_per_g: f32,
+    gyro_lsb_per_rad: f32,
+}
+
+impl FifoFormat {
+    pub fn new(config: &SensorConfig, gyro: bool) -> Self {
+        Self {
+            gyro,
+            acc_lsb_per_g: 32_768.0 / config.accel_full_scale_g(),
+            gyro_lsb_per_rad: 32_768.0 / config.gyro_full_scale_dps().to_radians(),
+        }
+    }
+
+    pub fn frame_len(&self) -> usize {
+        if self.gyro {
+            ACC_FRAME_BYTES + GYRO_FRAME_BYTES
+        } else {
+            ACC_FRAME_BYTES
+        }
+    }
+
+    // Bytes to read for `count` in the FIFO, whole frames only: a partial
+    // frame at the end stays in the FIFO until the rest of it is written
+    pub fn burst_len(&self, count: usize, capacity: usize) -> usize {
+        let frame_len = self.frame_len();
+        count.min(capacity) / frame_len * frame_len
+    }
+
+    // Frames in a burst read, a trailing partial frame is ignored
+    pub fn frames<'a>(&self, bytes: &'a [u8]) -> impl Iterator<Item = FifoFrame> + 'a {
+        let format = *self;
+        bytes
+            .chunks_exact(self.frame_len())
+            .map(move |frame| format.parse(frame))
+    }
+
+    fn parse(&self, frame: &[u8]) -> FifoFrame {
+        let axes = |bytes: &[u8], lsb: f32| {
+            [0, 1, 2].map(|i| i16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]]) as f32 / lsb)
+        };
+
+        FifoFrame {
+            acc: axes(&frame[..ACC_FRAME_BYTES], self.acc_lsb_per_g),
+            gyro: self
+                .gyro
+                .then(|| axes(&frame[ACC_FRAME_BYTES..], self.gyro_lsb_per_rad)),
+        }
+    }
+}
+
+// Sample rate the FIFO is filled at. The gyroscope output rate is 8 kHz
+// with the low-pass filter off and 1 kHz with it on, the accelerometer's is
+// always 1 kHz: above that the same accelerometer sample is repeated.
+pub fn sample_rate_hz(config: &SensorConfig, divider: u8) -> f32 {
+    let output_rate_hz = match config.dlpf {
+        Dlpf::Hz260 => 8_000.0,
+        _ => 1_000.0,
+    };
+    output_rate_hz / (1.0 + divider as f32)
+}
+
+// Given the FIFO count at the start of a drain, whether it overflowed.
+// The overflow interrupt bit is what says so for sure, a full FIFO on its
+// own already means samples are being dropped.
+pub fn overflowed(count: usize, overflow_bit: bool) -> bool {
+    overflow_bit || count >= FIFO_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensor::SENSOR_CONFIG;
+
+    fn counts(values: [i16; 3]) -> [u8; 6] {
+        let mut bytes = [0; 6];
+        for (chunk, value) in bytes.chunks_mut(2).zip(values) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn frames_are_scaled_to_the_range() {
+        // ±8 g is 4096 counts per g
+        let format = FifoFormat::new(&SENSOR_CONFIG, false);
+        let mut bytes = [0; 12];
+        bytes[..6].copy_from_slice(&counts([4096, -2048, 0]));
+        bytes[6..].copy_from_slice(&counts([0, 0, 4096]));
+
+        let frames: heapless::Vec<FifoFrame, 2> = format.frames(&bytes).collect();
+        assert_eq!(frames[0].acc, [1.0, -0.5, 0.0]);
+        assert_eq!(frames[1].acc, [0.0, 0.0, 1.0]);
+        assert_eq!(frames[1].gyro, None);
+    }
+
+    #[test]
+    fn gyro_follows_the_accelerometer() {
+        let format = FifoFormat::new(&SENSOR_CONFIG, true);
+        let mut bytes = [0; 12];
+        bytes[..6].copy_from_slice(&counts([0, 0, 4096]));
+        // ±500 °/s is ~65.5 counts per °/s
+        bytes[6..].copy_from_slice(&counts([6554, 0, 0]));
+
+        let frame = format.frames(&bytes).next().unwrap();
+        assert_eq!(frame.acc, [0.0, 0.0, 1.0]);
+        let gyro = frame.gyro.unwrap();
+        assert!((gyro[0] - 100f32.to_radians()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn partial_frames_stay_in_the_fifo() {
+        let format = FifoFormat::new(&SENSOR_CONFIG, false);
+
+        assert_eq!(format.burst_len(100, FIFO_BURST_BYTES), 96);
+        assert_eq!(format.burst_len(5, FIFO_BURST_BYTES), 0);
+        assert_eq!(format.burst_len(1000, FIFO_BURST_BYTES), FIFO_BURST_BYTES);
+        assert_eq!(
+            FifoFormat::new(&SENSOR_CONFIG, true).burst_len(1000, FIFO_BURST_BYTES),
+            FIFO_BURST_BYTES
+        );
+
+        // A read cut short still only yields whole frames
+        assert_eq!(format.frames(&[0; 16]).count(), 2);
+    }
+
+    #[test]
+    fn full_fifo_is_an_overflow() {
+        assert!(!overflowed(600, false));
+        assert!(overflowed(600, true));
+        assert!(overflowed(FIFO_SIZE, false));
+    }
+
+    #[test]
+    fn divider_and_filter_set_the_rate() {
+        assert_eq!(sample_rate_hz(&SENSOR_CONFIG, FIFO_RATE_DIVIDER), 1_000.0);
+        assert_eq!(sample_rate_hz(&SENSOR_CONFIG, 4), 200.0);
+
+        let unfiltered = SensorConfig {
+            dlpf: Dlpf::Hz260,
+            ..SENSOR_CONFIG
+        };
+        assert_eq!(sample_rate_hz(&unfiltered, 7), 1_000.0);
+    }
+}
@@ -0,0 +1,167 @@
+use core::fmt;
+
+// More digits after the point than any of the outputs needs
+pub const MAX_DECIMALS: u8 = 6;
+
+const POW10: [u64; MAX_DECIMALS as usize + 1] = [1, 10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+// Sign, 10 digits of a u32, the point and a leading zero
+const BUF_LEN: usize = 13;
+
+// A value as a scaled integer, e.g. milli-m/s^2 at 3 decimals or centi-ºC at
+// 2, printed as `12.345` with integer math only. Skips core's float
+// formatting, which is big and slow on a chip without a double FPU.
+// Rounds like `{:.N}` does, to the nearest with ties to even on the exact
+// binary value, and keeps the sign of values that round to zero, so the
+// output matches `format!("{:.N}", value)` digit for digit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixed {
+    negative: bool,
+    magnitude: u32,
+    decimals: u8,
+}
+
+impl Fixed {
+    // None for NaN, infinities and values beyond ±u32::MAX units, ±4294 at
+    // 6 decimals and ±4294967 at 3
+    pub fn new(value: f32, decimals: u8) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        let decimals = decimals.min(MAX_DECIMALS);
+
+        // value = mantissa * 2^exponent, exactly
+        let bits = value.to_bits();
+        let biased = ((bits >> 23) & 0xff) as i32;
+        let fraction = (bits & 0x7f_ffff) as u64;
+        let (mantissa, exponent) = match biased {
+            0 => (fraction, -149),
+            _ => (fraction | 0x80_0000, biased - 150),
+        };
+        // Below 2^44, the shift is the only rounding step
+        let product = mantissa * POW10[decimals as usize];
+
+        let magnitude = if exponent >= 0 {
+            if exponent > 20 {
+                return None;
+            }
+            product << exponent
+        } else {
+            // Past 2^-63 everything left rounds to zero anyway
+            let shift = (-exponent).min(63) as u32;
+            let quotient = product >> shift;
+            let remainder = product & ((1 << shift) - 1);
+            let half = 1 << (shift - 1);
+            if remainder > half || (remainder == half && quotient & 1 == 1) {
+                quotient + 1
+            } else {
+                quotient
+            }
+        };
+
+        Some(Self {
+            negative: bits >> 31 == 1,
+            magnitude: u32::try_from(magnitude).ok()?,
+            decimals,
+        })
+    }
+
+    // From a value that's already scaled, e.g. the centi-ºC of a ReadingFrame
+    pub fn from_scaled(scaled: i32, decimals: u8) -> Self {
+        Self {
+            negative: scaled < 0,
+            magnitude: scaled.unsigned_abs(),
+            decimals: decimals.min(MAX_DECIMALS),
+        }
+    }
+
+    // In units of the last decimal, None past i32
+    pub fn scaled(&self) -> Option<i32> {
+        let magnitude = i32::try_from(self.magnitude).ok()?;
+        Some(if self.negative { -magnitude } else { magnitude })
+    }
+}
+
+// Pads to the width of the format like a string, the precision is the
+// decimals given to `new()`
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; BUF_LEN];
+        let mut start = BUF_LEN;
+        let mut push = |digit: u8| {
+            start -= 1;
+            buf[start] = digit;
+        };
+
+        let mut rest = self.magnitude;
+        for _ in 0..self.decimals {
+            push(b'0' + (rest % 10) as u8);
+            rest /= 10;
+        }
+        if self.decimals > 0 {
+            push(b'.');
+        }
+        loop {
+            push(b'0' + (rest % 10) as u8);
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        if self.negative {
+            push(b'-');
+        }
+
+        // Only ASCII went in
+        f.pad(core::str::from_utf8(&buf[start..]).map_err(|_| fmt::Error)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: f32, decimals: u8) -> String {
+        Fixed::new(value, decimals).unwrap().to_string()
+    }
+
+    #[test]
+    fn prints_like_core() {
+        assert_eq!(fixed(12.345, 3), "12.345");
+        assert_eq!(fixed(-0.25, 0), "-0");
+        assert_eq!(fixed(-0.001, 2), "-0.00");
+        assert_eq!(fixed(0.0, 3), "0.000");
+        assert_eq!(fixed(-199.9996, 3), "-200.000");
+        assert_eq!(fixed(200.0, 6), "200.000000");
+        assert_eq!(fixed(9.8125, 2), "9.81");
+        assert_eq!(format!("{:>8}|", Fixed::new(-1.5, 3).unwrap()), "  -1.500|");
+
+        // Ties to even on the exact binary values, across the ±200 the
+        // monitor's values stay within
+        for decimals in 0..=MAX_DECIMALS {
+            for step in -8_000..=8_000 {
+                let value = step as f32 * 0.025 + step as f32 * 1e-5;
+                assert_eq!(
+                    fixed(value, decimals),
+                    format!("{:.*}", decimals as usize, value),
+                    "{} at {} decimals",
+                    value,
+                    decimals
+                );
+            }
+        }
+        assert_eq!(fixed(f32::MIN_POSITIVE / 4.0, 6), "0.000000");
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_hold() {
+        assert_eq!(Fixed::new(f32::NAN, 3), None);
+        assert_eq!(Fixed::new(f32::NEG_INFINITY, 3), None);
+        assert_eq!(Fixed::new(5_000.0, 6), None);
+        assert_eq!(Fixed::new(1e30, 0), None);
+        assert_eq!(Fixed::new(4_000.0, 6).unwrap().scaled(), None);
+        assert_eq!(Fixed::new(-25.5, 2).unwrap().scaled(), Some(-2_550));
+        assert_eq!(Fixed::from_scaled(-2_550, 2).to_string(), "-25.50");
+        assert_eq!(Fixed::from_scaled(i32::MIN, 0).to_string(), "-2147483648");
+    }
+}
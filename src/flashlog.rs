@@ -0,0 +1,397 @@
+use core::marker::PhantomData;
+
+use crate::crc::crc32;
+use crate::{Event, EventKind, Fault, Limit, ResetCause, Setting, Severity};
+
+// Append-only copy of the event log in flash, so it outlives a power cut.
+// The region is a ring of erase sectors filled one fixed-size record at a
+// time. The sector after the one being written holds the oldest records,
+// and is erased when the writing reaches it.
+// A record, little-endian: sequence number, uptime in s, kind and its two
+// arguments, a spare byte, value, threshold, CRC-32 of everything before it.
+// Erased flash reads as 0xff, so a blank slot has no valid CRC.
+pub const FLASH_RECORD_LEN: usize = 24;
+pub const FLASH_SECTOR_LEN: usize = 4_096;
+pub const FLASH_LOG_SECTORS: usize = 3;
+
+pub const RECORDS_PER_SECTOR: usize = FLASH_SECTOR_LEN / FLASH_RECORD_LEN;
+pub const FLASH_LOG_SLOTS: usize = FlashLog::SLOTS;
+
+// Newest events printed at boot
+pub const FLASH_LOG_REPLAY: usize = 5;
+
+// How often the events logged since are written out
+pub const FLASH_LOG_PERIOD_MS: u32 = 1_000;
+
+const CRC_AT: usize = FLASH_RECORD_LEN - 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlashRecord {
+    pub seq: u32,
+    pub event: Event,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Slot<R> {
+    Blank,
+    // A torn write, or whatever the region held before
+    Corrupt,
+    Record(R),
+}
+
+impl FlashRecord {
+    pub fn encode(&self) -> [u8; FLASH_RECORD_LEN] {
+        let (kind, a, b) = kind_code(self.event.kind);
+        let mut bytes = [0; FLASH_RECORD_LEN];
+        bytes[0..4].copy_from_slice(&self.seq.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.event.t_s.to_le_bytes());
+        bytes[8..11].copy_from_slice(&[kind, a, b]);
+        bytes[12..16].copy_from_slice(&self.event.value.to_bits().to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.event.threshold.to_bits().to_le_bytes());
+        let crc = crc32(&bytes[..CRC_AT]);
+        bytes[CRC_AT..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; FLASH_RECORD_LEN]) -> Slot<Self> {
+        let word = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+
+        if is_blank(bytes) {
+            return Slot::Blank;
+        }
+        if word(CRC_AT) != crc32(&bytes[..CRC_AT]) {
+            return Slot::Corrupt;
+        }
+        let Some(kind) = kind_from_code(bytes[8], bytes[9], bytes[10]) else {
+            return Slot::Corrupt;
+        };
+        let event = Event::new(word(4), kind)
+            .with_values(f32::from_bits(word(12)), f32::from_bits(word(16)));
+        Slot::Record(FlashRecord {
+            seq: word(0),
+            event,
+        })
+    }
+}
+
+pub fn is_blank(bytes: &[u8; FLASH_RECORD_LEN]) -> bool {
+    bytes.iter().all(|byte| *byte == 0xff)
+}
+
+fn kind_code(kind: EventKind) -> (u8, u8, u8) {
+    match kind {
+        EventKind::Reset(cause) => (1, cause as u8, 0),
+        EventKind::Alarm(limit, severity) => (2, limit as u8, severity as u8),
+        EventKind::Fault(fault) => (3, fault as u8, 0),
+        EventKind::Implausible => (4, 0, 0),
+        EventKind::Setting(setting) => (5, setting as u8, 0),
+        EventKind::FactoryReset => (6, 0, 0),
+        EventKind::Calibrated => (7, 0, 0),
+        EventKind::MaintenanceDue => (8, 0, 0),
+        EventKind::ServiceDone => (9, 0, 0),
+    }
+}
+
+fn kind_from_code(kind: u8, a: u8, b: u8) -> Option<EventKind> {
+    let a = a as usize;
+    Some(match kind {
+        1 => EventKind::Reset(*ResetCause::ALL.get(a)?),
+        2 => EventKind::Alarm(*Limit::ALL.get(a)?, *Severity::ALL.get(b as usize)?),
+        3 => EventKind::Fault(*Fault::ALL.get(a)?),
+        4 => EventKind::Implausible,
+        5 => EventKind::Setting(*Setting::ALL.get(a)?),
+        6 => EventKind::FactoryReset,
+        7 => EventKind::Calibrated,
+        8 => EventKind::MaintenanceDue,
+        9 => EventKind::ServiceDone,
+        _ => return None,
+    })
+}
+
+// One record to write, at an offset into the region. The sector is erased
+// first if there is one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlashWrite {
+    pub erase: Option<u32>,
+    pub offset: u32,
+    pub bytes: [u8; FLASH_RECORD_LEN],
+}
+
+// What a ring keeps, one FLASH_RECORD_LEN slot each
+pub trait RingRecord: Sized {
+    fn seq(&self) -> u32;
+    fn encode(&self) -> [u8; FLASH_RECORD_LEN];
+    fn decode(bytes: &[u8; FLASH_RECORD_LEN]) -> Slot<Self>;
+}
+
+impl RingRecord for FlashRecord {
+    fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    fn encode(&self) -> [u8; FLASH_RECORD_LEN] {
+        FlashRecord::encode(self)
+    }
+
+    fn decode(bytes: &[u8; FLASH_RECORD_LEN]) -> Slot<Self> {
+        FlashRecord::decode(bytes)
+    }
+}
+
+// Where the next record goes in a ring of SECTORS. Kept across deep sleep
+// instead of scanning the region again on every wake-up.
+#[derive(Debug)]
+pub struct FlashRing<R, const SECTORS: usize> {
+    next_slot: usize,
+    next_seq: u32,
+    // As of the scan at boot
+    records: usize,
+    corrupt: usize,
+    record: PhantomData<R>,
+}
+
+pub type FlashLog = FlashRing<FlashRecord, FLASH_LOG_SECTORS>;
+
+impl<R: RingRecord, const SECTORS: usize> FlashRing<R, SECTORS> {
+    pub const SLOTS: usize = RECORDS_PER_SECTOR * SECTORS;
+
+    // An empty region
+    pub const fn new() -> Self {
+        Self {
+            next_slot: 0,
+            next_seq: 0,
+            records: 0,
+            corrupt: 0,
+            record: PhantomData,
+        }
+    }
+
+    // Finds the newest record from every slot's contents, and the slot
+    // after it. A record torn by a power cut can't be written over without
+    // an erase, the next one goes past it.
+    // Returns the newest record too.
+    pub fn scan(mut read: impl FnMut(usize) -> [u8; FLASH_RECORD_LEN]) -> (Self, Option<R>) {
+        let mut ring = Self::new();
+        let mut newest: Option<(usize, R)> = None;
+        for slot in 0..Self::SLOTS {
+            match R::decode(&read(slot)) {
+                Slot::Blank => {}
+                Slot::Corrupt => ring.corrupt += 1,
+                Slot::Record(record) => {
+                    ring.records += 1;
+                    if !matches!(&newest, Some((_, newer)) if newer.seq() >= record.seq()) {
+                        newest = Some((slot, record));
+                    }
+                }
+            }
+        }
+
+        if let Some((slot, record)) = &newest {
+            ring.next_slot = (slot + 1) % Self::SLOTS;
+            ring.next_seq = record.seq().wrapping_add(1);
+        }
+        while !starts_sector(ring.next_slot) && !is_blank(&read(ring.next_slot)) {
+            ring.next_slot += 1;
+        }
+        (ring, newest.map(|(_, record)| record))
+    }
+
+    // The record from its sequence number
+    pub fn append(&mut self, record: impl FnOnce(u32) -> R) -> FlashWrite {
+        let slot = self.next_slot;
+        let record = record(self.next_seq);
+        self.next_slot = (slot + 1) % Self::SLOTS;
+        self.next_seq = self.next_seq.wrapping_add(1);
+
+        let erase =
+            starts_sector(slot).then_some((slot / RECORDS_PER_SECTOR * FLASH_SECTOR_LEN) as u32);
+        FlashWrite {
+            erase,
+            offset: slot_offset(slot),
+            bytes: record.encode(),
+        }
+    }
+
+    // Every slot, oldest first: from the next sector to be erased around
+    // to the one being written
+    pub fn slots(&self) -> impl DoubleEndedIterator<Item = usize> {
+        let sector = self.next_slot / RECORDS_PER_SECTOR;
+        let start = if starts_sector(self.next_slot) {
+            self.next_slot
+        } else {
+            (sector + 1) % SECTORS * RECORDS_PER_SECTOR
+        };
+        (start..Self::SLOTS).chain(0..start)
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn corrupt(&self) -> usize {
+        self.corrupt
+    }
+}
+
+// Not derived, that would want R: Copy
+impl<R, const SECTORS: usize> Clone for FlashRing<R, SECTORS> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R, const SECTORS: usize> Copy for FlashRing<R, SECTORS> {}
+
+impl<R: RingRecord, const SECTORS: usize> Default for FlashRing<R, SECTORS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn starts_sector(slot: usize) -> bool {
+    slot.checked_rem(RECORDS_PER_SECTOR) == Some(0)
+}
+
+// Where a slot is in the region
+pub fn slot_offset(slot: usize) -> u32 {
+    (slot / RECORDS_PER_SECTOR * FLASH_SECTOR_LEN + slot % RECORDS_PER_SECTOR * FLASH_RECORD_LEN)
+        as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The region in RAM, with NOR flash's rules: an erase sets a sector's
+    // bits, a write can only clear them
+    struct Region(Vec<u8>);
+
+    impl Region {
+        fn new() -> Self {
+            Self(vec![0xff; FLASH_SECTOR_LEN * FLASH_LOG_SECTORS])
+        }
+
+        fn read(&self, slot: usize) -> [u8; FLASH_RECORD_LEN] {
+            let at = slot_offset(slot) as usize;
+            self.0[at..at + FLASH_RECORD_LEN].try_into().unwrap()
+        }
+
+        fn apply(&mut self, write: &FlashWrite) {
+            if let Some(sector) = write.erase {
+                let at = sector as usize;
+                self.0[at..at + FLASH_SECTOR_LEN].fill(0xff);
+            }
+            let at = write.offset as usize;
+            for (byte, new) in self.0[at..].iter_mut().zip(write.bytes) {
+                *byte &= new;
+            }
+        }
+
+        fn scan(&self) -> FlashLog {
+            FlashLog::scan(|slot| self.read(slot)).0
+        }
+
+        // Sequence numbers, oldest first
+        fn seqs(&self, log: &FlashLog) -> Vec<u32> {
+            log.slots()
+                .filter_map(|slot| match FlashRecord::decode(&self.read(slot)) {
+                    Slot::Record(record) => Some(record.seq),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    fn event(t_s: u32) -> Event {
+        Event::new(t_s, EventKind::Alarm(Limit::Mechanical, Severity::Critical))
+            .with_values(1.5, 0.8)
+    }
+
+    fn record(t_s: u32) -> impl FnOnce(u32) -> FlashRecord {
+        move |seq| FlashRecord {
+            seq,
+            event: event(t_s),
+        }
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let record = FlashRecord {
+            seq: 7,
+            event: event(3_723),
+        };
+        assert_eq!(FlashRecord::decode(&record.encode()), Slot::Record(record));
+        let reset = FlashRecord {
+            seq: 8,
+            event: Event::new(0, EventKind::Reset(ResetCause::Brownout)),
+        };
+        let Slot::Record(decoded) = FlashRecord::decode(&reset.encode()) else {
+            panic!("not a record");
+        };
+        assert_eq!(decoded.event.kind, reset.event.kind);
+        assert!(decoded.event.value.is_nan());
+
+        assert_eq!(FlashRecord::decode(&[0xff; FLASH_RECORD_LEN]), Slot::Blank);
+        let mut flipped = record.encode();
+        flipped[5] ^= 0x10;
+        assert_eq!(FlashRecord::decode(&flipped), Slot::Corrupt);
+    }
+
+    #[test]
+    fn appends_continue_after_a_rescan() {
+        let mut region = Region::new();
+        let mut log = region.scan();
+        assert_eq!((log.records(), log.corrupt()), (0, 0));
+
+        for t_s in 0..3 {
+            let write = log.append(record(t_s));
+            assert_eq!(write.erase.is_some(), t_s == 0);
+            region.apply(&write);
+        }
+        let (mut log, newest) = FlashLog::scan(|slot| region.read(slot));
+        assert_eq!(log.records(), 3);
+        assert_eq!(newest.map(|record| record.event.t_s), Some(2));
+        region.apply(&log.append(record(3)));
+        assert_eq!(region.seqs(&log), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn a_full_region_wraps_over_its_oldest_sector() {
+        let mut region = Region::new();
+        let mut log = region.scan();
+        let total = FLASH_LOG_SLOTS + 10;
+        for t_s in 0..total as u32 {
+            region.apply(&log.append(record(t_s)));
+        }
+
+        // The first sector went for the newest 10
+        let log = region.scan();
+        let seqs = region.seqs(&log);
+        assert_eq!(seqs.len(), FLASH_LOG_SLOTS - RECORDS_PER_SECTOR + 10);
+        assert_eq!(seqs[0], RECORDS_PER_SECTOR as u32);
+        assert_eq!(*seqs.last().unwrap(), total as u32 - 1);
+        assert!(seqs.windows(2).all(|pair| pair[1] == pair[0] + 1));
+    }
+
+    #[test]
+    fn a_torn_record_is_skipped() {
+        let mut region = Region::new();
+        let mut log = region.scan();
+        for t_s in 0..5 {
+            region.apply(&log.append(record(t_s)));
+        }
+        // Power lost halfway through the sixth
+        let mut torn = log.append(record(5));
+        torn.bytes[12..].fill(0xff);
+        region.apply(&torn);
+
+        let mut log = region.scan();
+        assert_eq!((log.records(), log.corrupt()), (5, 1));
+        let write = log.append(record(6));
+        assert_eq!(write.offset, slot_offset(6));
+        region.apply(&write);
+        assert_eq!(region.seqs(&region.scan()), [0, 1, 2, 3, 4, 5]);
+    }
+}
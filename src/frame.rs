@@ -0,0 +1,335 @@
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+
+use crate::{
+    crc::crc16,
+    math,
+    sensor::{SensorConfig, STANDARD_GRAVITY},
+    Reading, Severity,
+};
+
+// Binary telemetry, for sample rates the text modes can't keep up with.
+// Each frame is a type byte, its payload and a CRC-16/CCITT-FALSE of both,
+// COBS-encoded so the only 0x00 on the wire is the delimiter ending it.
+// Multi-byte fields are little-endian.
+pub const DELIMITER: u8 = 0x00;
+
+pub const FRAME_READING: u8 = 0x01;
+// Payload is ASCII text, readable on a terminal between the frames
+pub const FRAME_BANNER: u8 = b'#';
+
+// Reading frame, 22 bytes before encoding:
+// type (u8), t_ms (u32, wraps after ~49 days), ax ay az and gx gy gz
+// (i16 raw counts, see the banner for the scale), temperature (i16, in
+// centidegrees), flags (u8, FLAG_*), CRC (u16) of the 20 bytes before it
+pub const OFFSET_TYPE: usize = 0;
+pub const OFFSET_T_MS: usize = 1;
+pub const OFFSET_ACC: usize = 5;
+pub const OFFSET_GYRO: usize = 11;
+pub const OFFSET_TEMP: usize = 17;
+pub const OFFSET_FLAGS: usize = 19;
+pub const OFFSET_CRC: usize = 20;
+pub const READING_LEN: usize = 22;
+
+// A limit is latched
+pub const FLAG_ALARM: u8 = 1 << 0;
+// ... at the critical level
+pub const FLAG_CRITICAL: u8 = 1 << 1;
+pub const FLAG_RELAY_TRIPPED: u8 = 1 << 2;
+// An axis read at the rail of its range
+pub const FLAG_CLIPPED: u8 = 1 << 3;
+
+// Banner frame: type, up to BANNER_TEXT_LEN bytes of text, CRC
+pub const BANNER_TEXT_LEN: usize = 96;
+pub const BANNER_LEN: usize = BANNER_TEXT_LEN + 3;
+// Sent this often, and whenever the binary mode is entered
+pub const BANNER_PERIOD_MS: u32 = 5_000;
+
+// Longest encoding of `len` bytes, the delimiter included: COBS adds one
+// byte, plus one for every run of 254 bytes without a zero
+pub const fn encoded_len(len: usize) -> usize {
+    len + len / 254 + 2
+}
+
+pub const READING_ENCODED_LEN: usize = encoded_len(READING_LEN);
+pub const BANNER_ENCODED_LEN: usize = encoded_len(BANNER_LEN);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadingFrame {
+    pub t_ms: u32,
+    pub acc: [i16; 3],
+    pub gyro: [i16; 3],
+    pub temp_centi: i16,
+    pub flags: u8,
+}
+
+impl ReadingFrame {
+    // From an uncalibrated reading, in m/s^2 and rad/s, back to the counts
+    // of the configured ranges. Counts out of the i16 range saturate.
+    pub fn new(raw: &Reading, config: &SensorConfig, flags: u8) -> Self {
+        let acc_lsb = config.acc_lsb_per_g();
+        let gyro_lsb = config.gyro_lsb_per_dps();
+        Self {
+            t_ms: raw.t_ms as u32,
+            acc: raw
+                .acc
+                .map(|acc| math::round(acc / STANDARD_GRAVITY * acc_lsb) as i16),
+            gyro: raw
+                .gyro
+                .map(|rate| math::round(rate.to_degrees() * gyro_lsb) as i16),
+            temp_centi: math::round(raw.temp * 100.0) as i16,
+            flags,
+        }
+    }
+
+    // `alarm` is the severity of the most severe latched limit
+    pub fn flags(alarm: Option<Severity>, relay_tripped: bool, clipped: bool) -> u8 {
+        let mut flags = 0;
+        if alarm.is_some() {
+            flags |= FLAG_ALARM;
+        }
+        if alarm == Some(Severity::Critical) {
+            flags |= FLAG_CRITICAL;
+        }
+        if relay_tripped {
+            flags |= FLAG_RELAY_TRIPPED;
+        }
+        if clipped {
+            flags |= FLAG_CLIPPED;
+        }
+        flags
+    }
+
+    pub fn pack(&self) -> [u8; READING_LEN] {
+        let mut bytes = [0; READING_LEN];
+        bytes[OFFSET_TYPE] = FRAME_READING;
+        bytes[OFFSET_T_MS..OFFSET_ACC].copy_from_slice(&self.t_ms.to_le_bytes());
+        for (i, value) in self.acc.iter().chain(&self.gyro).enumerate() {
+            let offset = OFFSET_ACC + 2 * i;
+            bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[OFFSET_TEMP..OFFSET_FLAGS].copy_from_slice(&self.temp_centi.to_le_bytes());
+        bytes[OFFSET_FLAGS] = self.flags;
+        let crc = crc16(&bytes[..OFFSET_CRC]);
+        bytes[OFFSET_CRC..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    // What a host decoder does once a frame is COBS-decoded. None for
+    // another frame type, the wrong length or a CRC that doesn't match.
+    pub fn unpack(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != READING_LEN || bytes[OFFSET_TYPE] != FRAME_READING || !crc_ok(bytes) {
+            return None;
+        }
+
+        let word = |offset: usize| i16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let mut t_ms = [0; 4];
+        t_ms.copy_from_slice(&bytes[OFFSET_T_MS..OFFSET_ACC]);
+        Some(Self {
+            t_ms: u32::from_le_bytes(t_ms),
+            acc: [0, 1, 2].map(|i| word(OFFSET_ACC + 2 * i)),
+            gyro: [0, 1, 2].map(|i| word(OFFSET_GYRO + 2 * i)),
+            temp_centi: word(OFFSET_TEMP),
+            flags: bytes[OFFSET_FLAGS],
+        })
+    }
+}
+
+fn crc_ok(bytes: &[u8]) -> bool {
+    let (data, crc) = bytes.split_at(bytes.len() - 2);
+    crc16(data).to_le_bytes() == crc
+}
+
+// Tells whoever attaches a terminal what the noise is, and a decoder the
+// scale of the counts
+pub fn banner(config: &SensorConfig) -> Vec<u8, BANNER_LEN> {
+    let mut text: String<BANNER_TEXT_LEN> = String::new();
+    // Fits, and a cut banner would still do its job
+    let _ = write!(
+        text,
+        "\r\n# binary telemetry v1, {}-byte COBS frames, acc {} LSB/g, gyro {} LSB/dps\r\n",
+        READING_LEN,
+        config.acc_lsb_per_g(),
+        config.gyro_lsb_per_dps()
+    );
+
+    let mut bytes = Vec::new();
+    // BANNER_LEN leaves room for the type and the CRC
+    let _ = bytes.push(FRAME_BANNER);
+    let _ = bytes.extend_from_slice(text.as_bytes());
+    let crc = crc16(&bytes);
+    let _ = bytes.extend_from_slice(&crc.to_le_bytes());
+    bytes
+}
+
+// COBS encoding of `frame` into `output`, delimiter included. Returns the
+// length written, None if `output` is shorter than `encoded_len()`.
+pub fn encode(frame: &[u8], output: &mut [u8]) -> Option<usize> {
+    if output.len() < encoded_len(frame.len()) {
+        return None;
+    }
+
+    // Each code byte holds the distance to the next zero, or 0xff for a run
+    // of 254 bytes without one
+    let mut code_at = 0;
+    let mut code = 1u8;
+    let mut len = 1;
+    for byte in frame {
+        if *byte == 0 {
+            output[code_at] = code;
+            code_at = len;
+            code = 1;
+        } else {
+            output[len] = *byte;
+            code += 1;
+        }
+        len += 1;
+        if code == 0xff {
+            output[code_at] = code;
+            code_at = len;
+            code = 1;
+            len += 1;
+        }
+    }
+    output[code_at] = code;
+    output[len] = DELIMITER;
+    Some(len + 1)
+}
+
+// The reverse of `encode()`, for one frame without its delimiter. None on
+// a malformed one, or if it doesn't fit in `output`.
+pub fn decode(encoded: &[u8], output: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+    let mut at = 0;
+    while at < encoded.len() {
+        let code = encoded[at] as usize;
+        if code == 0 || at + code > encoded.len() {
+            return None;
+        }
+        for byte in &encoded[at + 1..at + code] {
+            *output.get_mut(len)? = *byte;
+            len += 1;
+        }
+        at += code;
+        // A zero was there, unless the run was a full one or the frame ends
+        if code < 0xff && at < encoded.len() {
+            *output.get_mut(len)? = 0;
+            len += 1;
+        }
+    }
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(frame: &[u8]) -> std::vec::Vec<u8> {
+        let mut output = [0; 300];
+        let len = encode(frame, &mut output).unwrap();
+        output[..len].to_vec()
+    }
+
+    #[test]
+    fn cobs_known_sequences() {
+        assert_eq!(encoded(&[0x00]), [0x01, 0x01, 0x00]);
+        assert_eq!(encoded(&[0x00, 0x00]), [0x01, 0x01, 0x01, 0x00]);
+        assert_eq!(
+            encoded(&[0x11, 0x22, 0x00, 0x33]),
+            [0x03, 0x11, 0x22, 0x02, 0x33, 0x00]
+        );
+        assert_eq!(
+            encoded(&[0x11, 0x00, 0x00, 0x00]),
+            [0x02, 0x11, 0x01, 0x01, 0x01, 0x00]
+        );
+
+        // A full run of 254 bytes without a zero
+        let run: std::vec::Vec<u8> = (1..=254).collect();
+        let bytes = encoded(&run);
+        assert_eq!(bytes.len(), encoded_len(run.len()));
+        assert_eq!(bytes[0], 0xff);
+        assert_eq!(&bytes[1..255], &run[..]);
+        assert_eq!(&bytes[255..], [0x01, 0x00]);
+
+        for frame in [&[0x00][..], &[0x11, 0x22, 0x00, 0x33], &run] {
+            let bytes = encoded(frame);
+            assert_eq!(bytes.iter().filter(|byte| **byte == 0).count(), 1);
+            let mut decoded = [0; 300];
+            let len = decode(&bytes[..bytes.len() - 1], &mut decoded).unwrap();
+            assert_eq!(&decoded[..len], frame);
+        }
+        assert_eq!(encode(&[1, 2, 3], &mut [0; 4]), None);
+    }
+
+    #[test]
+    fn reading_frame_layout() {
+        let frame = ReadingFrame {
+            t_ms: 0x0102_0304,
+            acc: [1, -1, 4_096],
+            gyro: [0, 0x0100, -32_768],
+            temp_centi: 2_550,
+            flags: FLAG_ALARM | FLAG_RELAY_TRIPPED,
+        };
+        let bytes = frame.pack();
+        assert_eq!(
+            bytes,
+            [
+                0x01, 0x04, 0x03, 0x02, 0x01, 0x01, 0x00, 0xff, 0xff, 0x00, 0x10, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x80, 0xf6, 0x09, 0x05, 0xb7, 0x33
+            ]
+        );
+        assert_eq!(
+            encoded(&bytes),
+            [
+                0x07, 0x01, 0x04, 0x03, 0x02, 0x01, 0x01, 0x03, 0xff, 0xff, 0x02, 0x10, 0x01, 0x01,
+                0x02, 0x01, 0x07, 0x80, 0xf6, 0x09, 0x05, 0xb7, 0x33, 0x00
+            ]
+        );
+        assert_eq!(encoded(&bytes).len(), READING_ENCODED_LEN);
+        assert_eq!(ReadingFrame::unpack(&bytes), Some(frame));
+
+        let mut corrupted = bytes;
+        corrupted[OFFSET_TEMP] ^= 0x01;
+        assert_eq!(ReadingFrame::unpack(&corrupted), None);
+    }
+
+    #[test]
+    fn counts_from_a_driver_reading() {
+        let config = SensorConfig::default();
+        let raw = Reading::new(
+            crate::sensor::to_ms2([0.0, -0.5, 1.0]),
+            [0.0, 2.0_f32.to_radians(), 0.0],
+            25.456,
+            7,
+        );
+        let frame = ReadingFrame::new(&raw, &config, 0);
+        assert_eq!(frame.acc, [0, -2_048, 4_096]);
+        assert_eq!(frame.gyro, [0, 131, 0]);
+        assert_eq!(frame.temp_centi, 2_546);
+
+        // Past the rail
+        let raw = Reading::new([1_000.0, -1_000.0, 0.0], [0.0; 3], 0.0, 7);
+        let frame = ReadingFrame::new(&raw, &config, 0);
+        assert_eq!(frame.acc[..2], [i16::MAX, i16::MIN]);
+
+        assert_eq!(
+            ReadingFrame::flags(Some(Severity::Critical), false, true),
+            FLAG_ALARM | FLAG_CRITICAL | FLAG_CLIPPED
+        );
+        assert_eq!(ReadingFrame::flags(None, false, false), 0);
+    }
+
+    #[test]
+    fn banner_is_readable_text() {
+        let banner = banner(&SensorConfig::default());
+        assert_eq!(banner[0], FRAME_BANNER);
+        let text = core::str::from_utf8(&banner[1..banner.len() - 2]).unwrap();
+        assert_eq!(
+            text,
+            "\r\n# binary telemetry v1, 22-byte COBS frames, acc 4096 LSB/g, gyro 65.5 LSB/dps\r\n"
+        );
+        assert!(crc_ok(&banner));
+        assert!(encoded(&banner).len() <= BANNER_ENCODED_LEN);
+    }
+}
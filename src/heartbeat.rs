@@ -0,0 +1,110 @@
+// The LED when no alarm pattern runs and nothing is latched, so a hung
+// firmware (LED frozen) doesn't look like a healthy quiet one.
+// Healthy: one 50 ms blink every 2 s. Sensor fault: three fast blinks
+// every 2 s, easy to tell from the slower alarm and fault patterns.
+// Maintenance due: a slow blink, 1 s on and 1 s off.
+pub const HEARTBEAT_PERIOD_MS: u32 = 2_000;
+pub const HEARTBEAT_BLINK_MS: u32 = 50;
+pub const FAULT_BLINKS: u32 = 3;
+// One fast blink and the gap after it
+pub const FAULT_BLINK_PERIOD_MS: u32 = 2 * HEARTBEAT_BLINK_MS;
+
+// Without a good status sample for this long, the sensor counts as faulty.
+// A couple of missed samples, a single one is only skipped.
+pub const HEARTBEAT_STALE_MS: u32 = 2_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    Healthy,
+    SensorFault,
+    MaintenanceDue,
+}
+
+impl Health {
+    // From how long ago the last good sample was read, and whether the sensor
+    // is failing after a bus recovery or frozen. A sensor fault hides a due
+    // service, there's no maintenance schedule without the sensor.
+    pub fn of(read_age_ms: u32, bus_escalated: bool, stuck: bool, service_due: bool) -> Self {
+        if read_age_ms >= HEARTBEAT_STALE_MS || bus_escalated || stuck {
+            Health::SensorFault
+        } else if service_due {
+            Health::MaintenanceDue
+        } else {
+            Health::Healthy
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Health::Healthy => "one short blink every 2 s",
+            Health::SensorFault => "three fast blinks every 2 s",
+            Health::MaintenanceDue => "slow blink, 1 s on and 1 s off",
+        }
+    }
+}
+
+// Whether the LED is on at `now_ms`. Only depends on the time, so it can be
+// polled at any rate finer than the blinks.
+pub fn led(health: Health, now_ms: u32) -> bool {
+    let phase = now_ms % HEARTBEAT_PERIOD_MS;
+
+    match health {
+        Health::Healthy => phase < HEARTBEAT_BLINK_MS,
+        Health::SensorFault => {
+            phase < FAULT_BLINKS * FAULT_BLINK_PERIOD_MS
+                && phase % FAULT_BLINK_PERIOD_MS < HEARTBEAT_BLINK_MS
+        }
+        Health::MaintenanceDue => phase < HEARTBEAT_PERIOD_MS / 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blinks(health: Health) -> u32 {
+        let mut count = 0;
+        let mut was_on = false;
+        for now_ms in (0..HEARTBEAT_PERIOD_MS).step_by(10) {
+            let on = led(health, now_ms);
+            if on && !was_on {
+                count += 1;
+            }
+            was_on = on;
+        }
+        count
+    }
+
+    #[test]
+    fn healthy_blinks_once_per_period() {
+        assert_eq!(blinks(Health::Healthy), 1);
+        assert!(led(Health::Healthy, 0));
+        assert!(!led(Health::Healthy, HEARTBEAT_BLINK_MS));
+        assert!(led(Health::Healthy, 3 * HEARTBEAT_PERIOD_MS + 10));
+    }
+
+    #[test]
+    fn sensor_fault_blinks_three_times() {
+        assert_eq!(blinks(Health::SensorFault), 3);
+        assert!(!led(Health::SensorFault, 1_000));
+    }
+
+    #[test]
+    fn maintenance_due_blinks_slowly() {
+        assert_eq!(blinks(Health::MaintenanceDue), 1);
+        assert!(led(Health::MaintenanceDue, 990));
+        assert!(!led(Health::MaintenanceDue, 1_000));
+    }
+
+    #[test]
+    fn stale_or_failing_sensor_is_a_fault() {
+        assert_eq!(Health::of(500, false, false, false), Health::Healthy);
+        assert_eq!(
+            Health::of(HEARTBEAT_STALE_MS, false, false, false),
+            Health::SensorFault
+        );
+        assert_eq!(Health::of(0, true, false, false), Health::SensorFault);
+        assert_eq!(Health::of(0, false, true, true), Health::SensorFault);
+        assert_eq!(Health::of(0, false, false, true), Health::MaintenanceDue);
+    }
+}
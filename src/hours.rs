@@ -0,0 +1,285 @@
+use crate::crc::crc32;
+use crate::flashlog::{is_blank, FlashRing, RingRecord, Slot, FLASH_RECORD_LEN};
+
+// Running hours between services, the machine's maintenance schedule
+pub const SERVICE_INTERVAL_H: u32 = 2_000;
+pub const SERVICE_INTERVAL_RANGE_H: (u32, u32) = (1, 100_000);
+
+// Running seconds between saves to flash, what a power cut may lose.
+// One record every 5 min of running erases a sector of the ring once
+// every 14 h of it.
+pub const HOURS_SAVE_S: u32 = 300;
+
+// How often a due service is brought up again, in uptime
+pub const SERVICE_REMINDER_MS: u64 = 24 * 3_600_000;
+
+// A gap between samples counts for this much at most: a missed sample, or
+// a deep sleep, doesn't mean the machine ran through all of it
+pub const HOURS_MAX_STEP_MS: u64 = 10_000;
+
+pub const HOURS_SECTORS: usize = 2;
+
+pub type HoursRing = FlashRing<HoursRecord, HOURS_SECTORS>;
+
+// A save, little-endian: magic, sequence number, running seconds, running
+// seconds at the last service, service interval in hours, CRC-32 of
+// everything before it. The running seconds are the total, not what was
+// added since, so a record written twice counts once.
+const HOURS_MAGIC: u32 = 0x5352_4852;
+const CRC_AT: usize = FLASH_RECORD_LEN - 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HoursRecord {
+    pub seq: u32,
+    pub running_s: u32,
+    pub service_s: u32,
+    pub interval_h: u32,
+}
+
+impl RingRecord for HoursRecord {
+    fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    fn encode(&self) -> [u8; FLASH_RECORD_LEN] {
+        let mut bytes = [0; FLASH_RECORD_LEN];
+        let words = [
+            HOURS_MAGIC,
+            self.seq,
+            self.running_s,
+            self.service_s,
+            self.interval_h,
+        ];
+        for (chunk, word) in bytes[..CRC_AT].chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let crc = crc32(&bytes[..CRC_AT]);
+        bytes[CRC_AT..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8; FLASH_RECORD_LEN]) -> Slot<Self> {
+        let word = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+
+        if is_blank(bytes) {
+            return Slot::Blank;
+        }
+        if word(0) != HOURS_MAGIC || word(CRC_AT) != crc32(&bytes[..CRC_AT]) {
+            return Slot::Corrupt;
+        }
+        let record = Self {
+            seq: word(4),
+            running_s: word(8),
+            service_s: word(12),
+            interval_h: word(16),
+        };
+        let (min_h, max_h) = SERVICE_INTERVAL_RANGE_H;
+        if record.service_s > record.running_s || !(min_h..=max_h).contains(&record.interval_h) {
+            return Slot::Corrupt;
+        }
+        Slot::Record(record)
+    }
+}
+
+// Adds up the time the machine runs, and says when it's due for service
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunHours {
+    running_ms: u64,
+    // Running seconds in the last save
+    saved_s: u32,
+    // A service or a new interval, saved right away
+    changed: bool,
+    service_s: u32,
+    interval_h: u32,
+    last_ms: Option<u64>,
+    reminded_ms: Option<u64>,
+}
+
+impl RunHours {
+    pub const fn new() -> Self {
+        Self {
+            running_ms: 0,
+            saved_s: 0,
+            changed: false,
+            service_s: 0,
+            interval_h: SERVICE_INTERVAL_H,
+            last_ms: None,
+            reminded_ms: None,
+        }
+    }
+
+    // From the newest save
+    pub fn restore(record: &HoursRecord) -> Self {
+        Self {
+            running_ms: record.running_s as u64 * 1_000,
+            saved_s: record.running_s,
+            service_s: record.service_s,
+            interval_h: record.interval_h,
+            ..Self::new()
+        }
+    }
+
+    pub fn record(&self, seq: u32) -> HoursRecord {
+        HoursRecord {
+            seq,
+            running_s: self.running_s(),
+            service_s: self.service_s,
+            interval_h: self.interval_h,
+        }
+    }
+
+    // Every status sample. The time since the last one counts if the
+    // machine is running now.
+    pub fn update(&mut self, running: bool, t_ms: u64) {
+        if let (true, Some(last_ms)) = (running, self.last_ms) {
+            self.running_ms += t_ms.saturating_sub(last_ms).min(HOURS_MAX_STEP_MS);
+        }
+        self.last_ms = Some(t_ms);
+    }
+
+    pub fn save_due(&self) -> bool {
+        self.changed || self.running_s() - self.saved_s >= HOURS_SAVE_S
+    }
+
+    // Once the record is written
+    pub fn saved(&mut self) {
+        self.saved_s = self.running_s();
+        self.changed = false;
+    }
+
+    pub fn running_h(&self) -> f32 {
+        self.running_ms as f32 / 3_600_000.0
+    }
+
+    pub fn since_service_h(&self) -> f32 {
+        (self.running_s() - self.service_s) as f32 / 3_600.0
+    }
+
+    pub fn interval_h(&self) -> u32 {
+        self.interval_h
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.running_s() - self.service_s >= self.interval_h.saturating_mul(3_600)
+    }
+
+    // True when a due service should be brought up: the first time, then
+    // once every SERVICE_REMINDER_MS
+    pub fn reminder(&mut self, t_ms: u64) -> bool {
+        if !self.is_due()
+            || matches!(self.reminded_ms, Some(at) if t_ms.saturating_sub(at) < SERVICE_REMINDER_MS)
+        {
+            return false;
+        }
+        self.reminded_ms = Some(t_ms);
+        true
+    }
+
+    // The next interval starts from here
+    pub fn service_done(&mut self) {
+        self.service_s = self.running_s();
+        self.reminded_ms = None;
+        self.changed = true;
+    }
+
+    pub fn set_interval(&mut self, interval_h: u32) {
+        self.interval_h = interval_h;
+        self.reminded_ms = None;
+        self.changed = true;
+    }
+
+    fn running_s(&self) -> u32 {
+        (self.running_ms / 1_000) as u32
+    }
+}
+
+impl Default for RunHours {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    // Samples every second for `ms`
+    fn run(hours: &mut RunHours, t_ms: &mut u64, ms: u64, running: bool) {
+        for _ in 0..ms / 1_000 {
+            *t_ms += 1_000;
+            hours.update(running, *t_ms);
+        }
+    }
+
+    #[test]
+    fn only_running_time_counts() {
+        let mut hours = RunHours::new();
+        let mut t_ms = 0;
+        hours.update(true, t_ms);
+        run(&mut hours, &mut t_ms, HOUR_MS, true);
+        run(&mut hours, &mut t_ms, HOUR_MS, false);
+        assert!((hours.running_h() - 1.0).abs() < 1e-6);
+
+        // A long gap only counts for the longest step
+        t_ms += HOUR_MS;
+        hours.update(true, t_ms);
+        assert_eq!(hours.running_ms, HOUR_MS + HOURS_MAX_STEP_MS);
+    }
+
+    #[test]
+    fn saves_are_coarse_and_count_once() {
+        let mut hours = RunHours::new();
+        let mut t_ms = 0;
+        hours.update(true, t_ms);
+        run(
+            &mut hours,
+            &mut t_ms,
+            (HOURS_SAVE_S as u64 - 1) * 1_000,
+            true,
+        );
+        assert!(!hours.save_due());
+        run(&mut hours, &mut t_ms, 1_000, true);
+        assert!(hours.save_due());
+        let record = hours.record(4);
+        hours.saved();
+        assert!(!hours.save_due());
+
+        // Picked up after a reset from the save, the time since is gone
+        let bytes = record.encode();
+        assert_eq!(HoursRecord::decode(&bytes), Slot::Record(record));
+        let restored = RunHours::restore(&record);
+        assert_eq!(restored.running_s(), HOURS_SAVE_S);
+        assert!(!restored.save_due());
+
+        let mut flipped = bytes;
+        flipped[9] ^= 0x01;
+        assert_eq!(HoursRecord::decode(&flipped), Slot::Corrupt);
+    }
+
+    #[test]
+    fn a_due_service_is_brought_up_daily_until_done() {
+        let mut hours = RunHours::new();
+        hours.set_interval(2);
+        hours.saved();
+        let mut t_ms = 0;
+        hours.update(true, t_ms);
+        run(&mut hours, &mut t_ms, 2 * HOUR_MS - 1_000, true);
+        assert!(!hours.reminder(t_ms));
+
+        run(&mut hours, &mut t_ms, 1_000, true);
+        assert!(hours.is_due());
+        assert!(hours.reminder(t_ms));
+        assert!(!hours.reminder(t_ms + SERVICE_REMINDER_MS - 1));
+        assert!(hours.reminder(t_ms + SERVICE_REMINDER_MS));
+
+        hours.service_done();
+        assert!(!hours.is_due());
+        assert!(hours.save_due());
+        assert_eq!(hours.since_service_h(), 0.0);
+        assert_eq!(hours.record(0).service_s, 2 * 3_600);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::math;
+
+// Rate of change of acceleration between consecutive samples, in m/s^3.
+// The interval comes from the readings' timestamps rather than the nominal
+// sample period, so a late or retried read doesn't inflate it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Jerk {
+    last: Option<([f32; 3], u64)>,
+    value: Option<f32>,
+}
+
+impl Jerk {
+    pub const fn new() -> Self {
+        Self {
+            last: None,
+            value: None,
+        }
+    }
+
+    // Acceleration in m/s^2, timestamp in ms.
+    // Returns the length of the jerk vector, None for a sample without a
+    // predecessor.
+    pub fn update(&mut self, acc: [f32; 3], t_ms: u64) -> Option<f32> {
+        self.value = match self.last {
+            Some((last, last_ms)) if t_ms > last_ms => {
+                let dt = (t_ms - last_ms) as f32 / 1000.0;
+                let [dx, dy, dz] = core::array::from_fn::<f32, 3, _>(|i| acc[i] - last[i]);
+                Some(math::sqrt(dx * dx + dy * dy + dz * dz) / dt)
+            }
+            _ => None,
+        };
+
+        self.last = Some((acc, t_ms));
+        self.value
+    }
+
+    // Last jerk computed, None right after a reset
+    pub fn value(&self) -> Option<f32> {
+        self.value
+    }
+
+    // Forgets the previous sample, e.g. after a failed read
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAVITY: [f32; 3] = [0.0, 0.0, 9.81];
+
+    #[test]
+    fn first_sample_has_no_jerk() {
+        let mut jerk = Jerk::new();
+
+        assert_eq!(jerk.update([5.0, 0.0, 9.81], 1_000), None);
+        assert_eq!(jerk.update([5.0, 0.0, 9.81], 1_500), Some(0.0));
+    }
+
+    #[test]
+    fn divides_by_the_measured_interval() {
+        let mut jerk = Jerk::new();
+        jerk.update(GRAVITY, 0);
+
+        assert_eq!(jerk.update([0.0, 1.0, 9.81], 500), Some(2.0));
+        // A late sample, same change over twice the time
+        assert_eq!(jerk.update([0.0, 2.0, 9.81], 1_500), Some(1.0));
+    }
+
+    #[test]
+    fn reset_drops_the_predecessor() {
+        let mut jerk = Jerk::new();
+        jerk.update(GRAVITY, 0);
+
+        jerk.reset();
+        assert_eq!(jerk.update([0.0, 10.0, 9.81], 500), None);
+        assert_eq!(jerk.value(), None);
+    }
+}
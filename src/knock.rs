@@ -0,0 +1,148 @@
+use crate::math;
+
+// A burst every 100 ms, of up to 128 one-shot conversions or 2 ms,
+// whichever ends first. A conversion takes ~10 µs, so the time limit only
+// cuts in when something else keeps the ADC busy.
+pub const KNOCK_PERIOD_MS: u32 = 100;
+pub const KNOCK_BURST_SAMPLES: u32 = 128;
+pub const KNOCK_BURST_BUDGET_US: u32 = 2_000;
+
+// Bursts averaged for the bias at boot, with the machine still
+pub const KNOCK_BIAS_BURSTS: u32 = 8;
+
+// A burst with fewer conversions than this says nothing
+pub const KNOCK_MIN_SAMPLES: u32 = 16;
+
+// Full scale of the 12-bit ADC
+pub const ADC_MAX_COUNTS: u16 = 4_095;
+
+// Peak and RMS of the sensor's swing around its bias, in ADC counts: at
+// 11 dB a count is roughly 0.8 mV, but the ESP32's ADC isn't linear enough
+// for the limits to be worth more than counts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KnockLevel {
+    pub peak: f32,
+    pub rms: f32,
+    pub samples: u32,
+}
+
+// One burst of conversions, relative to the bias
+#[derive(Clone, Copy, Debug)]
+pub struct KnockBurst {
+    bias: f32,
+    peak: f32,
+    sum_squares: f32,
+    samples: u32,
+}
+
+impl KnockBurst {
+    pub fn new(bias: f32) -> Self {
+        Self {
+            bias,
+            peak: 0.0,
+            sum_squares: 0.0,
+            samples: 0,
+        }
+    }
+
+    pub fn push(&mut self, counts: u16) {
+        let swing = counts as f32 - self.bias;
+        self.peak = self.peak.max(math::abs(swing));
+        self.sum_squares += swing * swing;
+        self.samples += 1;
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // True once the burst has all its conversions
+    pub fn is_full(&self) -> bool {
+        self.samples >= KNOCK_BURST_SAMPLES
+    }
+
+    // None if the time ran out before KNOCK_MIN_SAMPLES
+    pub fn level(&self) -> Option<KnockLevel> {
+        (self.samples >= KNOCK_MIN_SAMPLES).then(|| KnockLevel {
+            peak: self.peak,
+            rms: math::sqrt(self.sum_squares / self.samples as f32),
+            samples: self.samples,
+        })
+    }
+}
+
+// The DC level the sensor sits at, from bursts at rest
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KnockBias {
+    sum: u64,
+    samples: u32,
+}
+
+impl KnockBias {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, counts: u16) {
+        self.sum += counts as u64;
+        self.samples += 1;
+    }
+
+    // In counts. None without any conversion, or at either end of the
+    // range: an open input reads 0 or full scale, with no swing left to see.
+    pub fn mean(&self) -> Option<f32> {
+        if self.samples == 0 {
+            return None;
+        }
+        let mean = self.sum as f32 / self.samples as f32;
+        (mean > 0.0 && mean < ADC_MAX_COUNTS as f32).then_some(mean)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_peak_and_rms_leave_the_bias_out() {
+        let mut bias = KnockBias::new();
+        for counts in [2_040, 2_060, 2_050, 2_050] {
+            bias.push(counts);
+        }
+        let bias = bias.mean().unwrap();
+        assert_eq!(bias, 2_050.0);
+
+        // A square wave of ±100 counts, with a single 400-count knock
+        let mut burst = KnockBurst::new(bias);
+        for i in 0..KNOCK_BURST_SAMPLES - 1 {
+            burst.push(if i & 1 == 0 { 2_150 } else { 1_950 });
+        }
+        assert!(!burst.is_full());
+        burst.push(1_650);
+        assert!(burst.is_full());
+
+        let level = burst.level().unwrap();
+        assert_eq!(level.peak, 400.0);
+        assert_eq!(level.samples, KNOCK_BURST_SAMPLES);
+        let rms = math::sqrt((127.0 * 100.0 * 100.0 + 400.0 * 400.0) / 128.0);
+        assert!((level.rms - rms).abs() < 0.01);
+    }
+
+    #[test]
+    fn short_bursts_and_open_inputs_give_nothing() {
+        let mut burst = KnockBurst::new(2_048.0);
+        for _ in 0..KNOCK_MIN_SAMPLES - 1 {
+            burst.push(2_048);
+        }
+        assert_eq!(burst.level(), None);
+
+        assert_eq!(KnockBias::new().mean(), None);
+        let mut open = KnockBias::new();
+        open.push(0);
+        open.push(0);
+        assert_eq!(open.mean(), None);
+        let mut railed = KnockBias::new();
+        railed.push(ADC_MAX_COUNTS);
+        assert_eq!(railed.mean(), None);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{Alert, Limit, MaintenanceMonitor, Severity};
+
+// Interval between reminder chirps while an alarm is latched, in ms
+pub const REMINDER_PERIOD_MS: u32 = 30_000;
+
+// What happened to one limit since it latched
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatchedEvent {
+    pub limit: Limit,
+    pub severity: Severity,
+    pub since_ms: u32,
+    // Largest reading seen while latched, see `MaintenanceMonitor::reading()`
+    pub peak: f32,
+    // Samples over the warning level while latched
+    pub samples: u32,
+}
+
+// Alarm state for unattended machines.
+// Once a limit raises an alert it stays latched until an operator
+// acknowledges it, even if the readings are back to normal.
+pub struct AlarmLatch {
+    events: [Option<LatchedEvent>; Limit::COUNT],
+    last_reminder_ms: u32,
+}
+
+impl AlarmLatch {
+    pub const fn new() -> Self {
+        Self {
+            events: [None; Limit::COUNT],
+            last_reminder_ms: 0,
+        }
+    }
+
+    // Called after every `MaintenanceMonitor::update()` with its result
+    pub fn update<const N: usize>(
+        &mut self,
+        monitor: &MaintenanceMonitor<N>,
+        alert: Option<Alert>,
+        now_ms: u32,
+    ) {
+        if let Some(alert) = alert {
+            let was_active = self.is_active();
+
+            let event = self.events[alert.limit as usize].get_or_insert(LatchedEvent {
+                limit: alert.limit,
+                severity: alert.severity,
+                since_ms: now_ms,
+                peak: monitor.reading(alert.limit),
+                samples: 0,
+            });
+            event.severity = event.severity.max(alert.severity);
+
+            if !was_active {
+                self.last_reminder_ms = now_ms;
+            }
+        }
+
+        for event in self.events.iter_mut().flatten() {
+            let reading = monitor.reading(event.limit);
+            if reading > event.peak {
+                event.peak = reading;
+            }
+            if monitor.exceeded(event.limit).is_some() {
+                event.samples = event.samples.saturating_add(1);
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.events.iter().any(|event| event.is_some())
+    }
+
+    pub fn event(&self, limit: Limit) -> Option<&LatchedEvent> {
+        self.events[limit as usize].as_ref()
+    }
+
+    // True once every REMINDER_PERIOD_MS while latched
+    pub fn reminder_due(&mut self, now_ms: u32) -> bool {
+        if !self.is_active() || now_ms.wrapping_sub(self.last_reminder_ms) < REMINDER_PERIOD_MS {
+            return false;
+        }
+
+        self.last_reminder_ms = now_ms;
+        true
+    }
+
+    // Clears the latch, returning what was latched for the summary
+    pub fn acknowledge(&mut self) -> [Option<LatchedEvent>; Limit::COUNT] {
+        core::mem::take(&mut self.events)
+    }
+}
+
+impl Default for AlarmLatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reading, Thresholds, SAMPLE_PERIOD_MS};
+
+    const GRAVITY: [f32; 3] = [0.0, 0.0, 1.0];
+    const STILL: [f32; 3] = [0.0; 3];
+
+    fn feed(
+        monitor: &mut MaintenanceMonitor,
+        latch: &mut AlarmLatch,
+        temp: f32,
+        now_ms: u32,
+    ) -> Option<Alert> {
+        let alert = monitor.update(&Reading::new(GRAVITY, STILL, temp, 0));
+        latch.update(monitor, alert, now_ms);
+        alert
+    }
+
+    #[test]
+    fn stays_latched_after_readings_recover() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut latch = AlarmLatch::new();
+
+        let mut now_ms = 0;
+        for temp in [71.0, 74.0, 72.0, 25.0, 25.0, 25.0, 25.0] {
+            feed(&mut monitor, &mut latch, temp, now_ms);
+            now_ms += SAMPLE_PERIOD_MS;
+        }
+
+        assert!(!monitor.any_latched());
+        assert!(latch.is_active());
+
+        let event = latch.event(Limit::Temperature).unwrap();
+        assert_eq!(event.severity, Severity::Warning);
+        assert_eq!(event.peak, 74.0);
+        // Latched on the second sample, then one more over the limit
+        assert_eq!(event.samples, 2);
+        assert_eq!(event.since_ms, SAMPLE_PERIOD_MS);
+
+        let events = latch.acknowledge();
+        assert!(events[Limit::Temperature as usize].is_some());
+        assert!(!latch.is_active());
+    }
+
+    #[test]
+    fn reminder_every_period_while_latched() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut latch = AlarmLatch::new();
+
+        assert!(!latch.reminder_due(REMINDER_PERIOD_MS));
+
+        feed(&mut monitor, &mut latch, 71.0, 1_000);
+        feed(&mut monitor, &mut latch, 71.0, 1_500);
+
+        assert!(!latch.reminder_due(1_500 + REMINDER_PERIOD_MS - 1));
+        assert!(latch.reminder_due(1_500 + REMINDER_PERIOD_MS));
+        assert!(!latch.reminder_due(1_500 + REMINDER_PERIOD_MS + 1));
+
+        latch.acknowledge();
+        assert!(!latch.reminder_due(1_500 + 2 * REMINDER_PERIOD_MS));
+    }
+}
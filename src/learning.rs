@@ -0,0 +1,408 @@
+use crate::console::Setting;
+use crate::crc::crc32;
+use crate::zscore::{RunningStats, MECHANICAL_SIGMA_FLOOR, THERMAL_SIGMA_FLOOR};
+use crate::{Limit, MaintenanceMonitor, Thresholds};
+
+// How long a commissioning run watches the machine, and how far past the
+// mean of what it saw each proposed warning level goes
+pub const LEARNING_MS: u64 = 10 * 60_000;
+pub const LEARNING_SIGMAS: f32 = 6.0;
+// Fewer samples than this, a minute at 500 ms, propose nothing
+pub const LEARNING_MIN_SAMPLES: u32 = 120;
+
+// The samples drift is measured over, about an hour at 500 ms
+pub const DRIFT_WINDOW: u32 = 7_200;
+
+// What a commissioning run records the distribution of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantity {
+    // Acceleration delta, m/s^2
+    Mechanical,
+    // Rotation rate delta, rad/s
+    Rotational,
+    // Temperature rise rate, ºC/min
+    TemperatureRate,
+    // Vibration RMS, m/s^2
+    Vibration,
+    // ºC
+    Temperature,
+}
+
+impl Quantity {
+    pub const ALL: [Quantity; 5] = [
+        Quantity::Mechanical,
+        Quantity::Rotational,
+        Quantity::TemperatureRate,
+        Quantity::Vibration,
+        Quantity::Temperature,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quantity::Mechanical => "mech",
+            Quantity::Rotational => "gyro",
+            Quantity::TemperatureRate => "dT/min",
+            Quantity::Vibration => "vib",
+            Quantity::Temperature => "temp",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Quantity::Mechanical | Quantity::Vibration => "m/s^2",
+            Quantity::Rotational => "rad/s",
+            Quantity::TemperatureRate => "ºC/min",
+            Quantity::Temperature => "ºC",
+        }
+    }
+
+    // The tunable limit it proposes a warning level for
+    pub fn setting(&self) -> Option<Setting> {
+        match self {
+            Quantity::Mechanical => Some(Setting::Mechanical),
+            Quantity::Rotational => Some(Setting::Rotational),
+            Quantity::TemperatureRate => Some(Setting::Temperature),
+            Quantity::Vibration | Quantity::Temperature => None,
+        }
+    }
+
+    // Drift is in standard deviations of the commissioning run, which for
+    // a quiet machine can be next to nothing
+    fn sigma_floor(&self) -> f32 {
+        match self {
+            Quantity::TemperatureRate | Quantity::Temperature => THERMAL_SIGMA_FLOOR,
+            _ => MECHANICAL_SIGMA_FLOOR,
+        }
+    }
+
+    fn of(monitor: &MaintenanceMonitor) -> [Option<f32>; 5] {
+        [
+            Some(monitor.reading(Limit::Mechanical)),
+            Some(monitor.reading(Limit::Rotational)),
+            monitor.temp_rate(),
+            monitor.vibration_rms(),
+            Some(monitor.filtered().temp),
+        ]
+    }
+}
+
+// While learning, a learned limit only trips past its compiled-in
+// critical level: gross events still alarm, the machine's normal doesn't
+pub fn safety_cap(setting: Setting) -> f32 {
+    let (min, max) = setting.range();
+    setting
+        .levels(&Thresholds::default())
+        .critical
+        .clamp(min, max)
+}
+
+// The running distribution of every quantity, indexed like Quantity::ALL
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Profile {
+    stats: [RunningStats; 5],
+}
+
+impl Profile {
+    pub const fn new(window: u32) -> Self {
+        Self {
+            stats: [RunningStats::new(window, 0.0); 5],
+        }
+    }
+
+    // After every update
+    pub fn push(&mut self, monitor: &MaintenanceMonitor) {
+        self.push_values(Quantity::of(monitor));
+    }
+
+    pub fn push_values(&mut self, values: [Option<f32>; 5]) {
+        for (stats, value) in self.stats.iter_mut().zip(values) {
+            if let Some(value) = value.filter(|value| value.is_finite()) {
+                stats.push(value);
+            }
+        }
+    }
+
+    pub fn distribution(&self, quantity: Quantity) -> Distribution {
+        let stats = &self.stats[quantity as usize];
+        Distribution {
+            mean: stats.mean(),
+            std_dev: stats.std_dev(),
+            samples: stats.count(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Distribution {
+    pub mean: f32,
+    pub std_dev: f32,
+    pub samples: u32,
+}
+
+// A commissioning run under way. The learned limits sit at their safety
+// caps until it's over, and go back to what they were.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Learning {
+    profile: Profile,
+    started_ms: Option<u64>,
+    last_ms: u64,
+    // Indexed like Setting::ALL
+    warnings: [f32; Setting::ALL.len()],
+    // Started by itself on a board without saved settings
+    pub automatic: bool,
+}
+
+impl Learning {
+    pub fn start(monitor: &mut MaintenanceMonitor, automatic: bool) -> Self {
+        let warnings = Setting::ALL.map(|setting| setting.levels(monitor.thresholds()).warning);
+        for setting in Setting::ALL {
+            monitor.set_warning(setting.limit(), safety_cap(setting));
+        }
+        Self {
+            profile: Profile::new(u32::MAX),
+            started_ms: None,
+            last_ms: 0,
+            warnings,
+            automatic,
+        }
+    }
+
+    // After every update, with the time of its sample
+    pub fn push(&mut self, monitor: &MaintenanceMonitor, t_ms: u64) {
+        self.push_values(Quantity::of(monitor), t_ms);
+    }
+
+    pub fn push_values(&mut self, values: [Option<f32>; 5], t_ms: u64) {
+        self.started_ms.get_or_insert(t_ms);
+        self.last_ms = t_ms;
+        self.profile.push_values(values);
+    }
+
+    pub fn remaining_ms(&self) -> u64 {
+        let learned_ms = self
+            .started_ms
+            .map_or(0, |started| self.last_ms.saturating_sub(started));
+        LEARNING_MS.saturating_sub(learned_ms)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining_ms() == 0
+    }
+
+    // The limits go back to where they were before the run, the
+    // commissioning baseline is what it saw
+    pub fn finish(self, monitor: &mut MaintenanceMonitor) -> Commissioning {
+        self.abort(monitor);
+        Commissioning {
+            stats: Quantity::ALL.map(|quantity| self.profile.distribution(quantity)),
+        }
+    }
+
+    pub fn abort(&self, monitor: &mut MaintenanceMonitor) {
+        for (setting, warning) in Setting::ALL.into_iter().zip(self.warnings) {
+            monitor.set_warning(setting.limit(), warning);
+        }
+    }
+}
+
+// Saved after the settings block, little-endian: magic, version, payload
+// length, then mean, standard deviation and sample count of every
+// quantity, CRC-32 of everything before it
+pub const BASELINE_MAGIC: u32 = 0x4c52_4e42;
+pub const BASELINE_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 8;
+const PAYLOAD_LEN: usize = 12 * Quantity::ALL.len();
+pub const BASELINE_LEN: usize = HEADER_LEN + PAYLOAD_LEN + 4;
+
+// What the machine looked like when it was commissioned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Commissioning {
+    // Indexed like Quantity::ALL
+    pub stats: [Distribution; 5],
+}
+
+impl Commissioning {
+    pub fn distribution(&self, quantity: Quantity) -> Distribution {
+        self.stats[quantity as usize]
+    }
+
+    // The mean plus LEARNING_SIGMAS standard deviations, within what the
+    // console accepts. None without enough samples.
+    pub fn proposal(&self, setting: Setting) -> Option<f32> {
+        let quantity = Quantity::ALL
+            .into_iter()
+            .find(|quantity| quantity.setting() == Some(setting))?;
+        let stats = self.distribution(quantity);
+        if stats.samples < LEARNING_MIN_SAMPLES {
+            return None;
+        }
+        let (min, max) = setting.range();
+        Some((stats.mean + LEARNING_SIGMAS * stats.std_dev).clamp(min, max))
+    }
+
+    // Sets the proposed warning levels. The ones without a proposal stay.
+    pub fn apply(&self, monitor: &mut MaintenanceMonitor) {
+        for setting in Setting::ALL {
+            if let Some(warning) = self.proposal(setting) {
+                monitor.set_warning(setting.limit(), warning);
+            }
+        }
+    }
+
+    // How far the recent mean has moved from the commissioning one, in its
+    // standard deviations. None without enough samples on either side.
+    pub fn drift(&self, recent: &Profile, quantity: Quantity) -> Option<f32> {
+        let base = self.distribution(quantity);
+        let now = recent.distribution(quantity);
+        if base.samples < LEARNING_MIN_SAMPLES || now.samples < LEARNING_MIN_SAMPLES {
+            return None;
+        }
+        Some((now.mean - base.mean) / base.std_dev.max(quantity.sigma_floor()))
+    }
+
+    pub fn encode(&self) -> [u8; BASELINE_LEN] {
+        let mut bytes = [0; BASELINE_LEN];
+        bytes[0..4].copy_from_slice(&BASELINE_MAGIC.to_le_bytes());
+        bytes[4..6].copy_from_slice(&BASELINE_VERSION.to_le_bytes());
+        bytes[6..8].copy_from_slice(&(PAYLOAD_LEN as u16).to_le_bytes());
+
+        let words = self
+            .stats
+            .iter()
+            .flat_map(|stats| [stats.mean.to_bits(), stats.std_dev.to_bits(), stats.samples]);
+        for (chunk, word) in bytes[HEADER_LEN..HEADER_LEN + PAYLOAD_LEN]
+            .chunks_exact_mut(4)
+            .zip(words)
+        {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        let crc = crc32(&bytes[..HEADER_LEN + PAYLOAD_LEN]);
+        bytes[HEADER_LEN + PAYLOAD_LEN..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    // None for an erased, damaged or foreign block
+    pub fn decode(bytes: &[u8; BASELINE_LEN]) -> Option<Self> {
+        let word = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let len = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        if word(0) != BASELINE_MAGIC
+            || version != BASELINE_VERSION
+            || len != PAYLOAD_LEN
+            || word(HEADER_LEN + PAYLOAD_LEN) != crc32(&bytes[..HEADER_LEN + PAYLOAD_LEN])
+        {
+            return None;
+        }
+
+        let payload = |i: usize| word(HEADER_LEN + 4 * i);
+        let stats = core::array::from_fn(|i| Distribution {
+            mean: f32::from_bits(payload(3 * i)),
+            std_dev: f32::from_bits(payload(3 * i + 1)),
+            samples: payload(3 * i + 2),
+        });
+        Some(Self { stats })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SAMPLE_PERIOD_MS;
+
+    // Mechanical deltas alternating 0.1 and 0.3 m/s^2, everything else
+    // steady
+    fn learn(learning: &mut Learning, samples: core::ops::Range<u32>) {
+        for i in samples {
+            let mech = if i % 2 == 0 { 0.1 } else { 0.3 };
+            let values = [Some(mech), Some(0.02), None, Some(0.5), Some(30.0)];
+            learning.push_values(values, i as u64 * SAMPLE_PERIOD_MS as u64);
+        }
+    }
+
+    #[test]
+    fn learned_levels_wait_at_the_caps_then_come_from_the_run() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let defaults = *monitor.thresholds();
+        let mut learning = Learning::start(&mut monitor, false);
+        assert_eq!(
+            monitor.thresholds().mechanical.warning,
+            safety_cap(Setting::Mechanical)
+        );
+        assert_eq!(
+            safety_cap(Setting::Mechanical),
+            defaults.mechanical.critical
+        );
+
+        let samples = (LEARNING_MS / SAMPLE_PERIOD_MS as u64) as u32;
+        learn(&mut learning, 0..samples);
+        assert_eq!(learning.remaining_ms(), SAMPLE_PERIOD_MS as u64);
+        learn(&mut learning, samples..samples + 2);
+        assert!(learning.is_done());
+
+        let baseline = learning.finish(&mut monitor);
+        assert_eq!(
+            monitor.thresholds().mechanical.warning,
+            defaults.mechanical.warning
+        );
+        // 0.2 ± 0.1, six sigmas out
+        let mech = baseline.proposal(Setting::Mechanical).unwrap();
+        assert!((mech - 0.8).abs() < 1e-3, "mech {}", mech);
+        // A steady gyroscope gets the lowest level the console takes, a
+        // rate that was never measured gets none
+        assert_eq!(
+            baseline.proposal(Setting::Rotational),
+            Some(Setting::Rotational.range().0)
+        );
+        assert_eq!(baseline.proposal(Setting::Temperature), None);
+
+        baseline.apply(&mut monitor);
+        assert_eq!(monitor.thresholds().mechanical.warning, mech);
+        assert_eq!(
+            monitor.thresholds().temperature_rate.warning,
+            defaults.temperature_rate.warning
+        );
+    }
+
+    #[test]
+    fn drift_is_in_commissioning_sigmas() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut learning = Learning::start(&mut monitor, true);
+        learn(&mut learning, 0..1_000);
+        let baseline = learning.finish(&mut monitor);
+
+        let mut recent = Profile::new(DRIFT_WINDOW);
+        assert_eq!(baseline.drift(&recent, Quantity::Mechanical), None);
+        for _ in 0..LEARNING_MIN_SAMPLES {
+            recent.push_values([Some(0.5), Some(0.02), None, Some(0.5), Some(31.0)]);
+        }
+        let mech = baseline.drift(&recent, Quantity::Mechanical).unwrap();
+        assert!((mech - 3.0).abs() < 1e-3, "mech {}", mech);
+        // Steady during commissioning, so against the floor
+        let temp = baseline.drift(&recent, Quantity::Temperature).unwrap();
+        assert!((temp - 1.0 / THERMAL_SIGMA_FLOOR).abs() < 1e-3);
+        assert_eq!(baseline.drift(&recent, Quantity::Vibration), Some(0.0));
+    }
+
+    #[test]
+    fn baseline_round_trips_through_flash() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut learning = Learning::start(&mut monitor, true);
+        learn(&mut learning, 0..200);
+        let baseline = learning.finish(&mut monitor);
+
+        let bytes = baseline.encode();
+        assert_eq!(Commissioning::decode(&bytes), Some(baseline));
+        assert_eq!(Commissioning::decode(&[0xff; BASELINE_LEN]), None);
+        let mut flipped = bytes;
+        flipped[HEADER_LEN + 1] ^= 0x01;
+        assert_eq!(Commissioning::decode(&flipped), None);
+    }
+}
@@ -0,0 +1,146 @@
+#![cfg_attr(not(test), no_std)]
+
+// Detection logic shared by the firmware.
+// Nothing in here touches the ESP HAL, so it also builds for the host target.
+
+pub mod aggregate;
+pub mod alarm;
+pub mod ansi;
+pub mod biquad;
+pub mod bus;
+pub mod button;
+pub mod calibration;
+pub mod can;
+pub mod capture;
+pub mod chiptemp;
+pub mod condition;
+pub mod config;
+pub mod console;
+pub mod crc;
+pub mod current;
+pub mod dac;
+pub mod detach;
+pub mod diagnostics;
+pub mod differential;
+pub mod display;
+pub mod ds18b20;
+pub mod envelope;
+pub mod eventlog;
+pub mod ewma;
+pub mod fault;
+pub mod fifo;
+pub mod fixed;
+pub mod flashlog;
+pub mod frame;
+pub mod heartbeat;
+pub mod hours;
+pub mod jerk;
+pub mod knock;
+pub mod latch;
+pub mod learning;
+pub mod lsm6ds3;
+pub mod math;
+pub mod median;
+pub mod modbus;
+pub mod monitor;
+pub mod motion;
+pub mod onewire;
+pub mod orientation;
+pub mod peak;
+pub mod rate;
+pub mod rawlog;
+pub mod reading;
+pub mod record;
+pub mod relay;
+pub mod replay;
+pub mod runstate;
+pub mod scheduler;
+pub mod score;
+pub mod sdlog;
+pub mod sensor;
+pub mod settings;
+pub mod sim;
+pub mod sleep;
+pub mod spectrum;
+pub mod stuck;
+pub mod summary;
+pub mod table;
+pub mod tachometer;
+pub mod telemetry;
+pub mod time;
+pub mod trend;
+pub mod trigger;
+pub mod velocity;
+pub mod vibration;
+pub mod xmodem;
+pub mod zscore;
+
+pub use aggregate::{Aggregator, Bucket, TrendHeader, TrendRow};
+pub use alarm::{Alarm, AlarmError, Buzzer, PinBuzzer};
+pub use ansi::{Paint, Style};
+pub use biquad::{Biquad, Coefficients};
+pub use button::Debouncer;
+pub use calibration::{Baseline, Calibration, GyroBias, Stats};
+pub use capture::{Capture, CsvLine, PostTrigger};
+pub use chiptemp::{CrossCheck, Plausibility};
+pub use condition::Condition;
+pub use config::{OutputConfig, OutputMode};
+pub use console::{
+    Command, CommandError, Dac, Learn, LineBuffer, LogFile, Raw, Service, Setting, Sim,
+};
+pub use current::{CurrentScale, CurrentWindow};
+pub use dac::{DacMapping, Staircase};
+pub use detach::DetachDetector;
+pub use diagnostics::ResetCause;
+pub use differential::{Differential, Skew};
+pub use display::{Frame, Oled, Screen};
+pub use ds18b20::{Ds18b20, ProbeError};
+pub use envelope::Envelope;
+pub use eventlog::{Event, EventKind, EventLog};
+pub use ewma::DualEwma;
+pub use fault::Fault;
+pub use fifo::{FifoFormat, FifoFrame};
+pub use fixed::Fixed;
+pub use flashlog::{FlashLog, FlashRecord, FlashRing, FlashWrite, RingRecord, Slot};
+pub use frame::ReadingFrame;
+pub use heartbeat::Health;
+pub use hours::{HoursRecord, HoursRing, RunHours};
+pub use jerk::Jerk;
+pub use knock::{KnockBias, KnockBurst, KnockLevel};
+pub use latch::{AlarmLatch, LatchedEvent};
+pub use learning::{Commissioning, Learning, Profile, Quantity};
+pub use lsm6ds3::Lsm6ds3;
+pub use median::{AxisMedian, MovingMedian};
+pub use monitor::{
+    Alert, Axis, ElectricalTrip, Levels, Limit, MaintenanceMonitor, MechanicalMode, Severity,
+    SpeedBand, TemperatureTrip, Thresholds, SAMPLE_PERIOD_MS,
+};
+pub use motion::MotionTrigger;
+pub use onewire::{OneWire, Rom};
+pub use orientation::{ComplementaryFilter, Orientation};
+pub use peak::PeakHold;
+pub use rate::{RateMeter, RateReport};
+pub use rawlog::{RawEncoding, RawLog, RawRing};
+pub use reading::Reading;
+pub use record::{EventRecord, LastAlarm};
+pub use relay::{Relay, RELAY_TRIP_SAMPLES};
+pub use replay::{Replay, ReplayLine};
+pub use runstate::{Activity, RunState, RunStateDetector, RunTransition};
+pub use scheduler::{Overrun, Scheduler, Task};
+pub use score::{ScoreBand, ScoreTracker, ScoreWeights};
+pub use sdlog::{RowContext, SdLog};
+pub use settings::{Settings, SettingsError};
+pub use sim::{Scenario, Simulator};
+pub use sleep::{DutyCycle, Wake, WakeGuard};
+pub use spectrum::{Peak, Spectrum};
+pub use stuck::{StuckAction, StuckDetector};
+pub use summary::{Spread, Summary};
+pub use table::{StatusHeader, StatusRow};
+pub use tachometer::Tachometer;
+pub use telemetry::{CsvRow, JsonEvent, JsonLine};
+pub use time::Timestamp;
+pub use trend::TemperatureTrend;
+pub use trigger::TriggerGate;
+pub use velocity::{VelocityRms, Zone, Zones};
+pub use vibration::VibrationRms;
+pub use zscore::RunningStats;
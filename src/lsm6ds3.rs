@@ -0,0 +1,286 @@
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use mpu6050::device::{AccelRange, GyroRange};
+
+use crate::sensor::{ImuSensor, SensorConfig, SENSOR_CONFIG, STANDARD_GRAVITY};
+
+// SA0 low, and high
+pub const ADDRESSES: [u8; 2] = [0x6a, 0x6b];
+
+// LSM6DS3, and the LSM6DS3TR-C/LSM6DSL with the same register layout
+const IDS: [u8; 2] = [0x69, 0x6a];
+
+// Registers (datasheet DocID026899 rev 10)
+const WHO_AM_I: u8 = 0x0f;
+const CTRL1_XL: u8 = 0x10;
+const CTRL2_G: u8 = 0x11;
+const CTRL3_C: u8 = 0x12;
+const OUT_TEMP_L: u8 = 0x20;
+const OUTX_L_G: u8 = 0x22;
+const OUTX_L_XL: u8 = 0x28;
+
+// CTRL3_C: block data update, so the two bytes of a value come from the
+// same sample, and register auto-increment for the 6-byte reads
+const CTRL3_C_BDU_IF_INC: u8 = 0b0100_0100;
+const CTRL3_C_SW_RESET: u8 = 0b0000_0001;
+// The reset takes ~50 µs, the boot of the trimming values up to 15 ms
+const RESET_MS: u8 = 20;
+
+// 416 Hz for both, the ODR bits of CTRL1_XL and CTRL2_G. There is no
+// separate low-pass filter setting, the output follows the rate.
+const ODR_416_HZ: u8 = 0b0110_0000;
+
+// 16 counts per ºC, 0 at 25 ºC
+const TEMP_LSB_PER_C: f32 = 16.0;
+const TEMP_OFFSET_C: f32 = 25.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lsm6ds3Error<E> {
+    I2c(E),
+    // WHO_AM_I of another chip
+    UnknownId(u8),
+}
+
+// First LSM6DS3 address among the ones found
+pub fn address(found: &[u8]) -> Option<u8> {
+    ADDRESSES
+        .into_iter()
+        .find(|address| found.contains(address))
+}
+
+// An LSM6DS3 on I2C, at the ranges of a SensorConfig. It has no ±250 º/s
+// range, D250 is its ±245 º/s. The filter and power settings are MPU6050
+// only and left out.
+pub struct Lsm6ds3<I> {
+    i2c: I,
+    address: u8,
+    accel_range: AccelRange,
+    gyro_range: GyroRange,
+}
+
+impl<I, E> Lsm6ds3<I>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    // At the ranges of SENSOR_CONFIG until `configure()`
+    pub fn new(i2c: I, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            accel_range: SENSOR_CONFIG.accel_range,
+            gyro_range: SENSOR_CONFIG.gyro_range,
+        }
+    }
+
+    pub fn who_am_i(&mut self) -> Result<u8, Lsm6ds3Error<E>> {
+        let mut id = [0];
+        self.read_registers(WHO_AM_I, &mut id)?;
+        Ok(id[0])
+    }
+
+    pub fn configure(&mut self, config: &SensorConfig) -> Result<(), Lsm6ds3Error<E>> {
+        self.accel_range = config.accel_range;
+        self.gyro_range = config.gyro_range;
+        self.write_register(CTRL1_XL, ODR_416_HZ | accel_fs(self.accel_range) << 2)?;
+        self.write_register(CTRL2_G, ODR_416_HZ | gyro_fs(self.gyro_range) << 2)
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Lsm6ds3Error<E>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .map_err(Lsm6ds3Error::I2c)
+    }
+
+    fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Lsm6ds3Error<E>> {
+        self.i2c
+            .write_read(self.address, &[register], buffer)
+            .map_err(Lsm6ds3Error::I2c)
+    }
+
+    // X, Y and Z from `register` on, little endian
+    fn read_axes(&mut self, register: u8) -> Result<[f32; 3], Lsm6ds3Error<E>> {
+        let mut buffer = [0; 6];
+        self.read_registers(register, &mut buffer)?;
+        Ok([0, 1, 2].map(|i| i16::from_le_bytes([buffer[2 * i], buffer[2 * i + 1]]) as f32))
+    }
+}
+
+impl<I, E> ImuSensor for Lsm6ds3<I>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Error = Lsm6ds3Error<E>;
+
+    // Checks the identity, resets the chip and sets the stored ranges
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Self::Error> {
+        let id = self.who_am_i()?;
+        if !IDS.contains(&id) {
+            return Err(Lsm6ds3Error::UnknownId(id));
+        }
+        self.write_register(CTRL3_C, CTRL3_C_BDU_IF_INC | CTRL3_C_SW_RESET)?;
+        delay.delay_ms(RESET_MS);
+        self.write_register(CTRL3_C, CTRL3_C_BDU_IF_INC)?;
+        let config = SensorConfig {
+            accel_range: self.accel_range,
+            gyro_range: self.gyro_range,
+            ..SENSOR_CONFIG
+        };
+        self.configure(&config)
+    }
+
+    fn read_accel(&mut self) -> Result<[f32; 3], Self::Error> {
+        let g_per_lsb = accel_mg_per_lsb(self.accel_range) / 1_000.0;
+        Ok(self
+            .read_axes(OUTX_L_XL)?
+            .map(|counts| counts * g_per_lsb * STANDARD_GRAVITY))
+    }
+
+    fn read_gyro(&mut self) -> Result<[f32; 3], Self::Error> {
+        let dps_per_lsb = gyro_mdps_per_lsb(self.gyro_range) / 1_000.0;
+        Ok(self
+            .read_axes(OUTX_L_G)?
+            .map(|counts| (counts * dps_per_lsb).to_radians()))
+    }
+
+    fn read_temp(&mut self) -> Result<f32, Self::Error> {
+        let mut buffer = [0; 2];
+        self.read_registers(OUT_TEMP_L, &mut buffer)?;
+        Ok(TEMP_OFFSET_C + i16::from_le_bytes(buffer) as f32 / TEMP_LSB_PER_C)
+    }
+}
+
+// FS_XL bits, not in range order
+fn accel_fs(range: AccelRange) -> u8 {
+    match range {
+        AccelRange::G2 => 0b00,
+        AccelRange::G4 => 0b10,
+        AccelRange::G8 => 0b11,
+        AccelRange::G16 => 0b01,
+    }
+}
+
+fn gyro_fs(range: GyroRange) -> u8 {
+    match range {
+        GyroRange::D250 => 0b00,
+        GyroRange::D500 => 0b01,
+        GyroRange::D1000 => 0b10,
+        GyroRange::D2000 => 0b11,
+    }
+}
+
+// Sensitivities from the datasheet, table 3
+fn accel_mg_per_lsb(range: AccelRange) -> f32 {
+    match range {
+        AccelRange::G2 => 0.061,
+        AccelRange::G4 => 0.122,
+        AccelRange::G8 => 0.244,
+        AccelRange::G16 => 0.488,
+    }
+}
+
+fn gyro_mdps_per_lsb(range: GyroRange) -> f32 {
+    match range {
+        GyroRange::D250 => 8.75,
+        GyroRange::D500 => 17.5,
+        GyroRange::D1000 => 35.0,
+        GyroRange::D2000 => 70.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    // LSM6DS3 register file behind a fake bus
+    struct Bus {
+        registers: [u8; 128],
+    }
+
+    impl Write for Bus {
+        type Error = Infallible;
+
+        fn write(&mut self, _address: u8, bytes: &[u8]) -> Result<(), Infallible> {
+            if let [register, data @ ..] = bytes {
+                let start = *register as usize;
+                self.registers[start..start + data.len()].copy_from_slice(data);
+            }
+            Ok(())
+        }
+    }
+
+    impl WriteRead for Bus {
+        type Error = Infallible;
+
+        fn write_read(
+            &mut self,
+            _address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), Infallible> {
+            let start = bytes[0] as usize;
+            buffer.copy_from_slice(&self.registers[start..start + buffer.len()]);
+            Ok(())
+        }
+    }
+
+    struct NoDelay;
+
+    impl DelayMs<u8> for NoDelay {
+        fn delay_ms(&mut self, _ms: u8) {}
+    }
+
+    fn lsm(id: u8) -> Lsm6ds3<Bus> {
+        let mut registers = [0; 128];
+        registers[WHO_AM_I as usize] = id;
+        Lsm6ds3::new(Bus { registers }, ADDRESSES[0])
+    }
+
+    #[test]
+    fn init_checks_the_id_and_sets_the_ranges() {
+        assert_eq!(
+            lsm(0x68).init(&mut NoDelay),
+            Err(Lsm6ds3Error::UnknownId(0x68))
+        );
+
+        let mut lsm = lsm(0x69);
+        lsm.init(&mut NoDelay).unwrap();
+        let registers = lsm.release().registers;
+        assert_eq!(registers[CTRL3_C as usize], 0b0100_0100);
+        // ±8 g and ±500 º/s, like the MPU6050
+        assert_eq!(registers[CTRL1_XL as usize], 0b0110_1100);
+        assert_eq!(registers[CTRL2_G as usize], 0b0110_0100);
+    }
+
+    #[test]
+    fn readings_come_out_in_the_monitor_units() {
+        let mut lsm = lsm(0x6a);
+        lsm.init(&mut NoDelay).unwrap();
+        let counts: [i16; 3] = [4_098, -4_098, 0];
+        for (i, value) in counts.iter().enumerate() {
+            let bytes = value.to_le_bytes();
+            lsm.i2c.registers[OUTX_L_XL as usize + 2 * i..][..2].copy_from_slice(&bytes);
+        }
+        lsm.i2c.registers[OUTX_L_G as usize..][..2].copy_from_slice(&5_714i16.to_le_bytes());
+        lsm.i2c.registers[OUT_TEMP_L as usize..][..2].copy_from_slice(&(-80i16).to_le_bytes());
+
+        // 4098 counts at 0.244 mg are 1 g
+        let acc = lsm.read_accel().unwrap();
+        assert!((acc[0] - STANDARD_GRAVITY).abs() < 0.01);
+        assert!((acc[1] + STANDARD_GRAVITY).abs() < 0.01);
+        // 5714 counts at 17.5 mdps are 100 º/s
+        let gyro = lsm.read_gyro().unwrap();
+        assert!((gyro[0] - 100f32.to_radians()).abs() < 0.001);
+        assert_eq!(lsm.read_temp().unwrap(), 20.0);
+
+        let reading = lsm.read(1_500).unwrap();
+        assert_eq!((reading.temp, reading.t_ms), (20.0, 1_500));
+
+        assert_eq!(address(&[0x3c, 0x6b]), Some(0x6b));
+        assert_eq!(address(&[0x68]), None);
+    }
+}
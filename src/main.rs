@@ -1,33 +1,272 @@
 #![no_std]
 #![no_main]
+#![cfg_attr(feature = "embassy", feature(type_alias_impl_trait))]
 
+use core::fmt::Debug;
+
+#[cfg(not(feature = "sim"))]
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use embedded_hal::digital::v2::OutputPin;
+#[cfg(not(feature = "embassy"))]
+use embedded_hal::serial::Read;
 use esp_backtrace as _;
-use esp_println::println;
+#[cfg(any(feature = "knock", feature = "current"))]
+use hal::adc::{AdcConfig, Attenuation, ADC, ADC1};
+#[cfg(not(feature = "embassy"))]
 use hal::{
-    clock::ClockControl, i2c, peripherals::Peripherals, prelude::*, timer::TimerGroup, Delay, Rtc,
-    IO,
+    clock::ClockControl,
+    gpio::{Gpio2, Gpio26, Output, PushPull},
+    i2c,
+    peripherals::{Peripherals, I2C0, TIMG0, UART0},
+    timer::{TimerGroup, Wdt},
+    Rtc, Uart, IO,
 };
+use hal::{i2c::I2C, prelude::*, Delay};
+#[cfg(not(any(feature = "lsm6ds3", feature = "sim")))]
 use mpu6050::*;
+#[cfg(feature = "can")]
+use rs_esp32_simple_preventive_maintenance_example::can::CanMessage;
+#[cfg(not(feature = "sim"))]
+use rs_esp32_simple_preventive_maintenance_example::fault::FAULT_REPEAT_MS;
+#[cfg(feature = "knock")]
+use rs_esp32_simple_preventive_maintenance_example::knock::KNOCK_PERIOD_MS;
+#[cfg(feature = "modbus")]
+use rs_esp32_simple_preventive_maintenance_example::modbus::{self, Registers};
+#[cfg(feature = "tachometer")]
+use rs_esp32_simple_preventive_maintenance_example::tachometer::RPM_PERIOD_MS;
+#[cfg(not(any(feature = "embassy", feature = "ledc-buzzer")))]
+use rs_esp32_simple_preventive_maintenance_example::PinBuzzer;
+#[cfg(not(feature = "embassy"))]
+use rs_esp32_simple_preventive_maintenance_example::{
+    aggregate::trend_limits,
+    bus::BusAction,
+    capture::{POST_TRIGGER_MS, POST_TRIGGER_PERIOD_MS},
+    chiptemp::{CHIP_OFFSET_READS, CROSS_CHECK_MARGIN},
+    console::{self, Command, Learn, LineBuffer, LogFile, Service, Setting},
+    display::{DISPLAY_ADDRESS, DISPLAY_PERIOD_MS},
+    flashlog::{FLASH_LOG_PERIOD_MS, FLASH_LOG_REPLAY},
+    frame::{self, BANNER_PERIOD_MS},
+    heartbeat,
+    learning::{Quantity, DRIFT_WINDOW, LEARNING_MS},
+    motion::MOTION_BURST_SAMPLES,
+    peak::PEAK_PERIOD_MS,
+    scheduler::{Scheduler, Task, MAX_TASKS},
+    score::SCORE_WEIGHTS,
+    summary::SUMMARY_SAMPLES,
+    vibration::VIBRATION_PERIOD_MS,
+    xmodem::{Protocol, START_TIMEOUT_MS},
+    Aggregator, AlarmLatch, Commissioning, DacMapping, Debouncer, Event, EventKind, EventLog,
+    EventRecord, FlashLog, FlashRecord, Health, HoursRing, Learning, MotionTrigger, Oled,
+    Plausibility, PostTrigger, Profile, RateMeter, ReadingFrame, Relay, Replay, ReplayLine,
+    RunHours, RunState, ScoreBand, ScoreTracker, Screen, Settings, SettingsError, Slot,
+    StuckAction, StuckDetector, Summary, Thresholds, TrendHeader, TrendRow, RELAY_TRIP_SAMPLES,
+    SAMPLE_PERIOD_MS,
+};
+use rs_esp32_simple_preventive_maintenance_example::{
+    bus::{self, BusHealth, BusProxy, SharedBus},
+    calibration::{CALIBRATION_PERIOD_MS, CALIBRATION_RETRIES},
+    capture::CSV_HEADER,
+    config::{self, Level},
+    diagnostics, motion,
+    sensor::{self, ImuSensor, Model, SensorConfig},
+    telemetry::CSV_COLUMNS,
+    Alarm, Alert, Baseline, Buzzer, Calibration, Capture, CsvLine, CsvRow, ElectricalTrip, Fault,
+    GyroBias, JsonEvent, JsonLine, LatchedEvent, Limit, MaintenanceMonitor, OutputMode, Reading,
+    ResetCause, RunningStats, Severity, StatusHeader, StatusRow, TemperatureTrip, Timestamp, Wake,
+};
+#[cfg(feature = "daq-trigger")]
+use rs_esp32_simple_preventive_maintenance_example::{config::TRIGGER_WIDTH_MS, TriggerGate};
+#[cfg(feature = "dac")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    console::Dac,
+    dac::{DAC_MAX, DAC_MIN_SPAN, STAIRCASE_STEP_MS},
+};
+#[cfg(feature = "raw-log")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    console::Raw,
+    rawlog::{RAW_DUMP_PERIOD_MS, RAW_LOG_SECTORS, RAW_PERIOD_MS},
+};
+#[cfg(feature = "sim")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    console::Sim,
+    sim::{SIM_SCENARIO, SIM_SEED},
+    Simulator,
+};
+#[cfg(feature = "current")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    current::{ACS712_MV_PER_A, CURRENT_PERIOD_MS},
+    CurrentScale,
+};
+#[cfg(feature = "differential")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    differential::FRAME_ADDRESS, Differential, Skew,
+};
+#[cfg(feature = "ds18b20")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    ds18b20::{self, PROBE_PERIOD_MS},
+    onewire, Ds18b20,
+};
+#[cfg(feature = "spectrum")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    fifo::{self, FIFO_BURST_BYTES, FIFO_RATE_DIVIDER},
+    spectrum::{SPECTRUM_PERIOD_MS, SPECTRUM_SAMPLES},
+    FifoFormat, Spectrum,
+};
+#[cfg(feature = "lsm6ds3")]
+use rs_esp32_simple_preventive_maintenance_example::{lsm6ds3, Lsm6ds3};
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+use rs_esp32_simple_preventive_maintenance_example::{
+    motion::MOTION_DURATION_MS, sensor::PowerProfile,
+};
+#[cfg(feature = "sd-card")]
+use rs_esp32_simple_preventive_maintenance_example::{
+    sdlog::{self, SD_WRITE_MS},
+    RowContext, SdLog,
+};
+#[cfg(feature = "deep-sleep")]
+use rs_esp32_simple_preventive_maintenance_example::{sleep, DutyCycle, WakeGuard};
+
+// Declared first for its logging macros, see `board::log`
+#[macro_use]
+mod board;
+#[cfg(feature = "embassy")]
+mod tasks;
+
+#[cfg(all(
+    feature = "embassy",
+    any(
+        feature = "spectrum",
+        feature = "ledc-buzzer",
+        feature = "deep-sleep",
+        feature = "binary-telemetry",
+        feature = "differential",
+        feature = "lsm6ds3",
+        feature = "sim",
+        feature = "ds18b20",
+        feature = "knock",
+        feature = "current",
+        feature = "tachometer",
+        feature = "modbus",
+        feature = "can",
+        feature = "dac",
+        feature = "daq-trigger",
+        feature = "sd-card",
+        feature = "raw-log"
+    )
+))]
+compile_error!(
+    "the embassy firmware doesn't support the spectrum, ledc-buzzer, deep-sleep, binary-telemetry, differential, lsm6ds3, sim, ds18b20, knock, current, tachometer, modbus, can, dac, daq-trigger, sd-card and raw-log features yet"
+);
+// The frame sensor's rest vector isn't kept across a deep sleep
+#[cfg(all(feature = "differential", feature = "deep-sleep"))]
+compile_error!("pick one of the differential and deep-sleep features");
+// Nor is the probe's reference, and a conversion doesn't fit a wake-up
+#[cfg(all(feature = "ds18b20", feature = "deep-sleep"))]
+compile_error!("pick one of the ds18b20 and deep-sleep features");
+// The bursts need the board awake between the samples
+#[cfg(all(any(feature = "knock", feature = "current"), feature = "deep-sleep"))]
+compile_error!("the knock and current features don't go with deep-sleep");
+// Asleep, the pulses would go uncounted
+#[cfg(all(feature = "tachometer", feature = "deep-sleep"))]
+compile_error!("pick one of the tachometer and deep-sleep features");
+// Nor would the master's requests, the UART sleeps too
+#[cfg(all(feature = "modbus", feature = "deep-sleep"))]
+compile_error!("pick one of the modbus and deep-sleep features");
+// A node that drops off the bus between samples would look like a dead one
+#[cfg(all(feature = "can", feature = "deep-sleep"))]
+compile_error!("pick one of the can and deep-sleep features");
+// Asleep the output drops to 0 V, which reads as a quiet machine
+#[cfg(all(feature = "dac", feature = "deep-sleep"))]
+compile_error!("pick one of the dac and deep-sleep features");
+// The rows waiting in RAM would go with it
+#[cfg(all(feature = "sd-card", feature = "deep-sleep"))]
+compile_error!("pick one of the sd-card and deep-sleep features");
+// and the block filling in RAM, or the sampling between two wake-ups
+#[cfg(all(feature = "raw-log", feature = "deep-sleep"))]
+compile_error!("pick one of the raw-log and deep-sleep features");
+#[cfg(all(feature = "dac", feature = "tachometer"))]
+compile_error!("pick one of the dac and tachometer features, both are on GPIO25");
+// These drive MPU6050 registers directly: the FIFO, the cycle mode and the
+// second sensor
+#[cfg(all(
+    feature = "lsm6ds3",
+    any(feature = "spectrum", feature = "deep-sleep", feature = "differential")
+))]
+compile_error!(
+    "the lsm6ds3 build doesn't support the spectrum, deep-sleep and differential features"
+);
+// Nor does the simulator have any of it, it stands in for the MPU6050
+#[cfg(all(
+    feature = "sim",
+    any(
+        feature = "lsm6ds3",
+        feature = "spectrum",
+        feature = "deep-sleep",
+        feature = "differential"
+    )
+))]
+compile_error!(
+    "the sim build doesn't support the lsm6ds3, spectrum, deep-sleep and differential features"
+);
 
 // Compile, flash and run:
 // source ~/export-esp.sh
 // cargo espflash --release --monitor
+// Add `--features ledc-buzzer` when using a passive piezo,
+// `--features spectrum` for the vibration frequency report,
+// `--features deep-sleep` on battery power,
+// `--features differential` with a second MPU6050 on the frame,
+// `--features lsm6ds3` with an LSM6DS3 instead of the MPU6050,
+// `--features sim` with no sensor at all, on synthetic data,
+// `--features ds18b20` with a DS18B20 probe on the bearing housing,
+// `--features knock` with a piezo knock sensor on GPIO34,
+// `--features current` with an ACS712 on the motor's supply, on GPIO35,
+// `--features tachometer` with a once-per-rev speed sensor on GPIO25,
+// `--features modbus` for a SCADA master on UART1, over RS-485,
+// `--features can` to broadcast on the machine's CAN bus,
+// `--features dac` for a chart recorder on GPIO25,
+// `--features daq-trigger` for an external DAQ's trigger input on GPIO23,
+// `--features sd-card` to log CSV rows on a microSD card,
+// `--features raw-log` to keep the last hour of 100 Hz acceleration in
+// flash, on a 4 MB module,
+// `--features json-telemetry`, `csv-telemetry` or `binary-telemetry` to
+// start with JSON lines, CSV rows or binary frames
+// `--no-default-features --features backtrace-panic,log-defmt` for defmt
+// logging over RTT
 
-enum Limit {
-    Mechanical,
-    Temperature,
-}
+// Fine enough for the 100 Hz peak-hold polling, paced by a hardware timer
+const TICK_MS: u32 = 10;
+
+// The spectrum burst needs fast mode: at 1 kHz the FIFO fills with 60 bytes
+// every tick, which take ~5.5 ms to read at 100 kHz
+#[cfg(not(feature = "spectrum"))]
+const I2C_FREQUENCY_KHZ: u32 = 100;
+#[cfg(feature = "spectrum")]
+const I2C_FREQUENCY_KHZ: u32 = 400;
+// A burst takes SPECTRUM_SAMPLES ms, one that isn't complete after this
+// long is given up
+#[cfg(feature = "spectrum")]
+const SPECTRUM_TIMEOUT_MS: u32 = 1_000;
 
-const MECHANICAL_LIMIT: f32 = 0.8;
-const TEMPERATURE_LIMIT: f32 = 2.5;
+// Played at boot after a watchdog or panic reset
+const RESET_CHIRPS: u8 = 3;
 
+// The loop feeds the watchdog every tick, so this only trips if it wedges
+const WATCHDOG_TIMEOUT_S: u64 = 3;
+// Without a good sensor read for this long, the watchdog is left to
+// reset the chip and the boot checks run again
+const SENSOR_TIMEOUT_MS: u32 = 60_000;
+
+#[cfg(not(feature = "embassy"))]
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take();
     let mut system = peripherals.DPORT.split();
     let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 
-    // Disable the RTC and TIMG watchdog timers
+    // Only the TIMG0 watchdog is used, started right before the main loop.
+    // The boot checks can block for a while, so the others stay disabled.
+    // TIMG1 timer 0 ticks the main loop, timer 1 keeps the uptime.
     let mut rtc = Rtc::new(peripherals.RTC_CNTL);
     let timer_group0 = TimerGroup::new(
         peripherals.TIMG0,
@@ -41,192 +280,3655 @@ fn main() -> ! {
         &mut system.peripheral_clock_control,
     );
     let mut wdt1 = timer_group1.wdt;
+    let tick_timer = timer_group1.timer0;
+    board::time::init(timer_group1.timer1, clocks.apb_clock.to_Hz());
     rtc.rwdt.disable();
     wdt0.disable();
     wdt1.disable();
 
+    let boot = board::diagnostics::boot();
+
+    // Picking up after a deep sleep: the boot checks and the calibration
+    // are skipped, the monitor carries on where it was
+    let resumed = board::sleep::restore(&boot);
+    let mut resume = resumed.as_ref().map(|&(_, resume)| resume);
+    let wake = resume.map(|_| board::sleep::wake_source(&boot));
+    if let Some(resume) = &mut resume {
+        board::time::resume(resume.uptime_ms);
+        match wake {
+            Some(Wake::Motion) => {
+                info!("Motion wake-up, sampling right away");
+                resume.wake_guard.motion_woke(resume.uptime_ms);
+            }
+            _ => info!("Timer wake-up"),
+        }
+    }
+
+    // Alarm counters from before the reset, in RTC memory.
+    // A wake-up isn't a boot, and only a cold boot gets the banner.
+    let (mut record, kept) = board::record::load();
+    if resume.is_none() {
+        if !kept {
+            println!("Event record: none from before, counters start at zero");
+        }
+        record.booted();
+        record.reset(boot.cause);
+        board::record::store(&record);
+        print_boot(&boot, Some(record.boot_count));
+        print_record(&record);
+        print_led_patterns();
+    }
+
+    // The event log in flash, from before the reset
+    let flash_log = match &resume {
+        Some(resume) => resume.flash_log,
+        None => {
+            let flash_log = board::flash::scan_log();
+            print_flash_log(&flash_log);
+            flash_log
+        }
+    };
+    // and the running hours, from the last save
+    let (hours, hours_ring) = match &resume {
+        Some(resume) => (resume.hours, resume.hours_ring),
+        None => {
+            let (ring, newest) = board::flash::scan_hours();
+            let hours = newest.as_ref().map_or(RunHours::new(), RunHours::restore);
+            print_hours(&hours);
+            (hours, ring)
+        }
+    };
+
     // Initialize Delay
     let mut delay = Delay::new(&clocks);
 
     // Initialize IO && Pin definitions
     let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
-    let (mut internal_led, mut buzzer, sda, scl) = (
+    let (internal_led, buzzer_pin, relay_pin, button, int_pin, sda, scl) = (
         io.pins.gpio2.into_push_pull_output(),
         io.pins.gpio33.into_push_pull_output(),
+        io.pins.gpio26.into_push_pull_output(),
+        io.pins.gpio0.into_pull_up_input(),
+        io.pins.gpio4.into_pull_down_input(),
         io.pins.gpio21,
         io.pins.gpio22,
     );
 
+    // Machine power relay, let the machine run until a critical condition
+    // persists. Set up before the MPU so a sensor failure doesn't stop it.
+    let relay = Relay::new(relay_pin, RELAY_TRIP_SAMPLES).unwrap();
+
+    // The DAQ trigger, driven low as early as the relay
+    #[cfg(feature = "daq-trigger")]
+    {
+        board::trigger::init(io.pins.gpio23, timer_group0.timer1);
+        println!(
+            "DAQ trigger: GPIO23, a {} ms pulse as a limit trips",
+            TRIGGER_WIDTH_MS
+        );
+    }
+
+    // Buzzer: active buzzer on a GPIO, or passive piezo on LEDC tones
+    #[cfg(not(feature = "ledc-buzzer"))]
+    let buzzer = PinBuzzer::new(buzzer_pin);
+
+    #[cfg(feature = "ledc-buzzer")]
+    let ledc = hal::ledc::LEDC::new(
+        peripherals.LEDC,
+        &clocks,
+        &mut system.peripheral_clock_control,
+    );
+    #[cfg(feature = "ledc-buzzer")]
+    let tone_timers = board::tone::timers(&ledc);
+    #[cfg(feature = "ledc-buzzer")]
+    let buzzer = board::tone::ToneBuzzer::new(&ledc, &tone_timers, buzzer_pin);
+
+    let mut alarm = Alarm::new(buzzer, internal_led);
+    if boot.cause.is_abnormal() {
+        warn_reset(&mut alarm, &mut delay);
+    }
+
+    // Acknowledge/mute button
+    board::button::init(button);
+    let ack_button = Debouncer::default();
+
+    // Command console, on the UART the log goes out on.
+    // Only read from here, esp-println keeps writing to it directly.
+    let uart = Uart::new(peripherals.UART0, &mut system.peripheral_clock_control);
+
+    // The SCADA's, its requests answered in the `modbus` task
+    #[cfg(feature = "modbus")]
+    let modbus = board::modbus::Port::new(
+        peripherals.UART1,
+        io.pins.gpio17,
+        io.pins.gpio16,
+        io.pins.gpio18,
+        &clocks,
+        &mut system.peripheral_clock_control,
+    );
+    #[cfg(feature = "modbus")]
+    println!(
+        "Modbus: slave {} at {} baud 8E1, UART1 TX GPIO17 RX GPIO16, DE GPIO18",
+        config::MODBUS.address,
+        config::MODBUS.baud
+    );
+
+    // The machine's CAN bus, through a transceiver on GPIO5 and GPIO19
+    #[cfg(feature = "can")]
+    let can = board::can::Bus::new(
+        peripherals.TWAI0,
+        io.pins.gpio5,
+        io.pins.gpio19,
+        &clocks,
+        &mut system.peripheral_clock_control,
+    );
+    #[cfg(feature = "can")]
+    println!(
+        "CAN: {} kbit/s, TX GPIO5 RX GPIO19, readings on {:#05x} every {} ms, alarms on {:#05x}",
+        config::CAN.kbps,
+        config::CAN.readings_id,
+        config::CAN.period_ms,
+        config::CAN.alarm_id
+    );
+
+    // The microSD card, mounted by the first `sd-card` task
+    #[cfg(feature = "sd-card")]
+    let sd = board::sdcard::Card::new(
+        peripherals.SPI2,
+        io.pins.gpio14,
+        io.pins.gpio13,
+        io.pins.gpio32,
+        io.pins.gpio15,
+        &clocks,
+        &mut system.peripheral_clock_control,
+    );
+    #[cfg(feature = "sd-card")]
+    println!(
+        "SD: SCK GPIO14 MOSI GPIO13 MISO GPIO32 CS GPIO15, rows written every {} s",
+        SD_WRITE_MS / 1_000
+    );
+
     // Configure I2C
     let i2c = i2c::I2C::new(
         peripherals.I2C0,
         sda,
         scl,
-        100u32.kHz(),
+        I2C_FREQUENCY_KHZ.kHz(),
         &mut system.peripheral_clock_control,
         &clocks,
     );
-    delay.delay_ms(255u8);
 
-    // Initialize MPU6050 module
-    let mut mpu = Mpu6050::new(i2c);
-    mpu.init(&mut delay)
-        .expect("Error while initializing MPU6050");
+    // Shared by the MPU6050 and the display
+    let bus = SharedBus::new(i2c);
+
+    // The address is only kept for the next wake-up
+    #[cfg_attr(not(feature = "deep-sleep"), allow(unused_variables))]
+    let (mut mpu, address, model, sensor_config) = match &resume {
+        #[cfg(not(any(feature = "lsm6ds3", feature = "sim")))]
+        Some(resume) => (
+            wake_up(&bus, resume, &mut delay),
+            resume.address,
+            resume.model,
+            resume.sensor_config,
+        ),
+        _ => {
+            delay.delay_ms(255u8);
+            bring_up(&bus, &mut alarm, &mut delay)
+        }
+    };
+
+    #[cfg(feature = "differential")]
+    let frame_mpu = bring_up_frame(&bus, address, &sensor_config, &mut delay);
+
+    // Optional, without one on the bus the monitor runs headless
+    let mut display = Oled::new(bus.proxy(), DISPLAY_ADDRESS);
+    let display = match display.init() {
+        Ok(()) => {
+            if resume.is_none() {
+                println!("Display: SSD1306 at 0x{:02x}", DISPLAY_ADDRESS);
+            }
+            Some(display)
+        }
+        Err(_) => {
+            if resume.is_none() {
+                println!(
+                    "Display: none at 0x{:02x}, running headless",
+                    DISPLAY_ADDRESS
+                );
+            }
+            None
+        }
+    };
+
+    #[cfg(feature = "ds18b20")]
+    let probe = bring_up_probe(board::onewire::OneWirePin::new(
+        io.pins.gpio27.into_open_drain_output(),
+        Delay::new(&clocks),
+    ));
+
+    #[cfg(feature = "tachometer")]
+    {
+        board::tachometer::init(io.pins.gpio25.into_pull_up_input());
+        println!(
+            "Tachometer: GPIO25, {} pulse(s) per rev",
+            rs_esp32_simple_preventive_maintenance_example::tachometer::PULSES_PER_REV
+        );
+    }
+
+    // The analog sensors, on ADC1 at 11 dB
+    #[cfg(any(feature = "knock", feature = "current", feature = "dac"))]
+    let analog = peripherals.SENS.split();
+    #[cfg(any(feature = "knock", feature = "current"))]
+    let mut adc1_config = AdcConfig::new();
+    #[cfg(feature = "knock")]
+    let knock_pin =
+        adc1_config.enable_pin(io.pins.gpio34.into_analog(), Attenuation::Attenuation11dB);
+    #[cfg(feature = "current")]
+    let current_pin =
+        adc1_config.enable_pin(io.pins.gpio35.into_analog(), Attenuation::Attenuation11dB);
+    #[cfg(any(feature = "knock", feature = "current"))]
+    let mut adc = ADC::<ADC1>::adc(analog.adc1, adc1_config).unwrap();
+    #[cfg(feature = "knock")]
+    let knock = bring_up_knock(&mut adc, board::knock::KnockSensor::new(knock_pin));
+    #[cfg(feature = "current")]
+    let current = bring_up_current(
+        &mut adc,
+        board::current::CurrentSensor::new(current_pin, CurrentScale::default()),
+    );
+
+    let (mut monitor, mut gyro_bias, sample_period_ms, dac_mapping, saved) = match resumed {
+        Some((monitor, resume)) => (
+            monitor,
+            resume.gyro_bias,
+            resume.sample_period_ms,
+            resume.dac,
+            true,
+        ),
+        None => load_settings(),
+    };
+
+    // The chart recorder, starting with its test pattern
+    #[cfg(feature = "dac")]
+    let dac = board::dac::Output::new(analog.dac1, io.pins.gpio25.into_analog());
+    #[cfg(feature = "dac")]
+    println!(
+        "DAC: vibration RMS on GPIO25, 0 V at {} m/s^2 to full scale at {} m/s^2, test pattern first",
+        dac_mapping.zero, dac_mapping.full_scale
+    );
+    // and what the machine looked like when it was commissioned
+    let (baseline, recent) = match &resume {
+        Some(resume) => (resume.baseline, resume.recent),
+        None => {
+            let baseline = board::flash::load_baseline();
+            match &baseline {
+                Some(baseline) => println!(
+                    "Baseline: commissioned over {} samples, `learn` for the drift since",
+                    baseline.distribution(Quantity::Mechanical).samples
+                ),
+                None => println!("Baseline: none, `learn start` to commission the machine"),
+            }
+            (baseline, Profile::new(DRIFT_WINDOW))
+        }
+    };
+
+    // The 100 Hz acceleration in flash, where it left off
+    #[cfg(feature = "raw-log")]
+    let raw = {
+        let ring = board::flash::scan_raw();
+        println!(
+            "Raw log: {} of {} blocks in flash, {} corrupt, {} ms samples",
+            ring.stored(),
+            RAW_LOG_SECTORS,
+            ring.corrupt(),
+            RAW_PERIOD_MS
+        );
+        board::rawlog::Recorder::new(ring, sensor_config.acc_lsb_per_g() as u16)
+    };
+
+    // Optional MPU6050 INT wire, motion above the mechanical limit takes a
+    // sample right away instead of waiting for the next one
+    let motion_threshold = motion::motion_threshold(monitor.thresholds().mechanical.warning);
+    #[cfg(not(any(feature = "lsm6ds3", feature = "sim")))]
+    {
+        sensor::enable_motion_interrupt(&mut mpu, motion_threshold, MOTION_DURATION_MS)
+            .expect("Error while configuring the motion interrupt");
+        board::motion::init(int_pin);
+        if resume.is_none() {
+            println!(
+                "Motion interrupt on GPIO4 above {} mg",
+                motion_threshold as f32 * motion::MOT_THR_LSB_MG
+            );
+        }
+    }
+    #[cfg(any(feature = "lsm6ds3", feature = "sim"))]
+    {
+        let _ = int_pin;
+        println!(
+            "Motion interrupt: not set up on the {}, polling only",
+            model.name()
+        );
+    }
+
+    // Boot calibration: the references are the average of a couple of seconds
+    // of samples instead of a single, possibly noisy, reading.
+    // The LED blinks fast meanwhile, the rig must not be touched.
+    // A saved bias is kept if the machine is running meanwhile.
+    let mut bus_health = BusHealth::new();
+    if resume.is_none() {
+        let baseline = calibrate(
+            &mut mpu,
+            &mut alarm,
+            &mut delay,
+            &mut bus_health,
+            &mut || {},
+        );
+        adopt(&baseline, &mut monitor, &mut gyro_bias);
+
+        // With everything still cold, what the chip reads against the MPU
+        let chip_temp = board::chiptemp::mean(&mut delay, CHIP_OFFSET_READS);
+        monitor.set_chip_offset(chip_temp);
+        println!(
+            "Chip temperature: {} ºC, the MPU's {} ºC off it",
+            chip_temp,
+            monitor.temp_reference() - chip_temp
+        );
+    }
+    // Calibrated on a machine at rest, the scenario starts from here
+    #[cfg(feature = "sim")]
+    {
+        mpu.play(SIM_SCENARIO);
+        print_sim(&mpu);
+    }
+    #[cfg(feature = "differential")]
+    let frame = frame_mpu.map(|mut mpu| {
+        let differential = calibrate_frame(
+            &mut mpu,
+            &mut alarm,
+            &mut delay,
+            &mut bus_health,
+            &mut || {},
+        );
+        FrameSensor {
+            mpu,
+            differential,
+            skew: Skew::new(),
+        }
+    });
+
+    // Woken by motion: the burst for the peak deltas, the first status
+    // sample right after compares it against the references from before
+    // the sleep
+    if wake == Some(Wake::Motion) {
+        motion_burst(
+            &mut mpu,
+            &mut delay,
+            &mut bus_health,
+            &gyro_bias,
+            &mut monitor,
+        );
+    }
+
+    #[cfg(feature = "spectrum")]
+    let spectrum = SpectrumBurst::new(&sensor_config);
+    #[cfg(feature = "spectrum")]
+    println!(
+        "Spectrum: {} samples every {} ms, above {} Hz attenuated by the low-pass filter",
+        SPECTRUM_SAMPLES,
+        SPECTRUM_PERIOD_MS,
+        sensor_config.dlpf.bandwidth_hz()
+    );
+
+    let mut context = Context {
+        sample_period_ms,
+        delay,
+        wdt: wdt0,
+        mpu,
+        alarm,
+        relay,
+        model,
+        sensor_config,
+        motion_threshold,
+        monitor,
+        bus_health,
+        gyro_bias,
+        dac_mapping,
+        stuck: StuckDetector::default(),
+        events: EventLog::new(),
+        flash_log,
+        hours,
+        hours_ring,
+        pre_trigger: Capture::new(),
+        post_trigger: PostTrigger::new(),
+        latch: AlarmLatch::new(),
+        record,
+        ack_button,
+        uart,
+        console: LineBuffer::new(),
+        motion_trigger: MotionTrigger::default(),
+        // Alarm patterns don't block, so a tick never gets near the timeout
+        last_read_ms: 0,
+        fast: None,
+        sensor_cycling: false,
+        output: config::OUTPUT.mode,
+        summary: Summary::new(),
+        trend: Aggregator::new(),
+        score: ScoreTracker::new(),
+        learning: None,
+        proposal: None,
+        baseline,
+        recent,
+        replay: None,
+        download: None,
+        display,
+        #[cfg(feature = "differential")]
+        frame,
+        #[cfg(feature = "ds18b20")]
+        probe,
+        #[cfg(any(feature = "knock", feature = "current"))]
+        adc,
+        #[cfg(feature = "knock")]
+        knock,
+        #[cfg(feature = "current")]
+        current,
+        #[cfg(feature = "spectrum")]
+        spectrum,
+        #[cfg(feature = "modbus")]
+        modbus,
+        #[cfg(feature = "can")]
+        can,
+        #[cfg(feature = "dac")]
+        dac,
+        #[cfg(feature = "daq-trigger")]
+        trigger: TriggerGate::new(),
+        #[cfg(feature = "sd-card")]
+        sd,
+        #[cfg(feature = "sd-card")]
+        sd_log: SdLog::new(),
+        #[cfg(feature = "raw-log")]
+        raw,
+        #[cfg(feature = "deep-sleep")]
+        rtc,
+        #[cfg(feature = "deep-sleep")]
+        address,
+        #[cfg(feature = "deep-sleep")]
+        stay_awake_ms: match resume {
+            Some(_) => 0,
+            None => sleep::STAY_AWAKE_MS,
+        },
+        #[cfg(feature = "deep-sleep")]
+        duty: resume.map_or(DutyCycle::new(), |resume| resume.duty),
+        #[cfg(feature = "deep-sleep")]
+        wake_guard: resume.map_or(WakeGuard::new(), |resume| resume.wake_guard),
+    };
+    // A wake-up isn't a reset, and would fill the flash log
+    if resume.is_none() {
+        context.events.push(Event::new(
+            board::time::uptime_s(),
+            EventKind::Reset(boot.cause),
+        ));
+    }
+    // Nothing saved, a new machine: it's learned without asking
+    if !saved {
+        start_learning(&mut context, true);
+    }
+
+    // The sensor is checked once every sample period, or early on a
+    // motion interrupt. In between it's polled every PEAK_PERIOD_MS for the
+    // peak deltas, every VIBRATION_PERIOD_MS for the vibration RMS, and every
+    // POST_TRIGGER_PERIOD_MS while a post-trigger capture runs. The rest
+    // runs every tick, to advance the alarm pattern.
+    // Tasks due on the same tick run in this order.
+    let mut scheduler = Scheduler::<_, MAX_TASKS>::with_tasks(
+        TICK_MS,
+        [
+            Task::new("status", sample_period_ms, status_task),
+            Task::new("motion", TICK_MS, motion_task),
+            Task::new("peak", PEAK_PERIOD_MS, peak_task),
+            Task::new("vibration", VIBRATION_PERIOD_MS, vibration_task),
+            #[cfg(feature = "raw-log")]
+            Task::new("raw-log", RAW_PERIOD_MS, raw_task),
+            #[cfg(feature = "raw-log")]
+            Task::new("raw-dump", RAW_DUMP_PERIOD_MS, raw_dump_task),
+            Task::new("post-trigger", POST_TRIGGER_PERIOD_MS, post_trigger_task),
+            // The spectrum burst lands halfway between two status samples, and is
+            // read from the FIFO once per tick until it's complete
+            #[cfg(feature = "spectrum")]
+            Task::new("spectrum", SPECTRUM_PERIOD_MS, spectrum_task)
+                .with_offset(sample_period_ms / 2),
+            #[cfg(feature = "spectrum")]
+            Task::new("fifo", TICK_MS, fifo_task),
+            #[cfg(feature = "ds18b20")]
+            Task::new("probe", PROBE_PERIOD_MS, probe_task),
+            #[cfg(feature = "knock")]
+            Task::new("knock", KNOCK_PERIOD_MS, knock_task),
+            #[cfg(feature = "current")]
+            Task::new("current", CURRENT_PERIOD_MS, current_task),
+            #[cfg(feature = "tachometer")]
+            Task::new("rpm", RPM_PERIOD_MS, rpm_task),
+            #[cfg(feature = "modbus")]
+            Task::new("modbus", TICK_MS, modbus_task),
+            #[cfg(feature = "can")]
+            Task::new("can", TICK_MS, can_task),
+            #[cfg(feature = "can")]
+            Task::new("can-readings", config::CAN.period_ms, can_readings_task),
+            #[cfg(feature = "dac")]
+            Task::new("dac", TICK_MS, dac_task),
+            #[cfg(feature = "sd-card")]
+            Task::new("sd-card", SD_WRITE_MS, sd_task),
+            Task::new("button", TICK_MS, button_task),
+            Task::new("console", TICK_MS, console_task),
+            Task::new("download", TICK_MS, download_task),
+            Task::new("alarm", TICK_MS, alarm_task),
+            Task::new("heartbeat", TICK_MS, heartbeat_task),
+            Task::new("flash-log", FLASH_LOG_PERIOD_MS, flash_log_task),
+            Task::new("learning", sample_period_ms, learning_task),
+            Task::new("watchdog", TICK_MS, watchdog_task),
+            Task::new("power", TICK_MS, power_task),
+            Task::new("banner", BANNER_PERIOD_MS, banner_task),
+            Task::new("display", DISPLAY_PERIOD_MS, display_task),
+            Task::new("display-flush", TICK_MS, display_flush_task),
+            // Last, once the sample's alarm has started and the button and console
+            // had their turn
+            #[cfg(feature = "deep-sleep")]
+            Task::new("sleep", sample_period_ms, sleep_task),
+        ],
+    );
 
-    // Define reference values
-    let mut acc_ref = mpu.get_acc();
-    let temp_ref = mpu.get_temp();
+    context.wdt.start(WATCHDOG_TIMEOUT_S.secs());
 
-    // Only sudden moves should activate the buzzer.
-    // For that, each loop cycle should reset the accelerometer's reference.
-    // Otherwise, changing the MPU's position would also sound the alarm.
-    let mut reset_reference = true;
+    // Iterations that run long are caught up back to back, up to half a
+    // sample period. Past that (a recalibration blocks for seconds) the
+    // missed ticks are skipped instead.
+    let max_lag = sample_period_ms / TICK_MS / 2;
+    let mut rate_meter = RateMeter::default();
+    let mut tick: u32 = 0;
+    board::ticker::init(tick_timer, TICK_MS);
 
     println!("---");
+    // A wake-up carries on under the header printed at boot
+    if resume.is_none() {
+        print_header(context.output);
+        if context.output == OutputMode::Binary {
+            send_banner(&mut context.uart, &context.sensor_config);
+        }
+    }
     loop {
-        if reset_reference {
-            acc_ref = mpu.get_acc();
+        let lag = board::ticker::ticks().wrapping_sub(tick);
+        if lag > max_lag {
+            warn!("WARNING: main loop {} ticks behind, skipping them", lag);
+            tick = board::ticker::ticks();
+        }
+        if let Some(report) = rate_meter.tick(board::time::uptime_ms(), lag) {
+            info!(
+                "Tick rate: {} Hz (target {} Hz), up to {} ticks behind",
+                report.rate_hz,
+                1000 / TICK_MS,
+                report.max_lag
+            );
+            print_tasks(&scheduler);
+        }
 
-            reset_reference = false;
-            delay.delay_ms(100u8);
-        } else {
-            // Update values
-            let acc = mpu.get_acc();
-            let gyro = mpu.get_gyro();
-            let temp = mpu.get_temp();
-            // All of those "get" methods return a Result<T,E>.
-            // "acc" and "gyro"'s 'T' is equivalent to an array of 3 f32, [x, y, z];
-            // "temp"'s T is an f32
-
-            // Accelerometer data
-            match acc {
-                Ok(data) => {
-                    println!("Accelerometer:");
-                    println!("Ax: {} m/s^2", data[0]);
-                    println!("Ay: {} m/s^2", data[1]);
-                    println!("Az: {} m/s^2", data[2]);
-
-                    let acc_ref_x = acc_ref.as_ref().unwrap()[0];
-
-                    let mut delta = data[0] - acc_ref_x;
-
-                    if delta.abs() >= MECHANICAL_LIMIT {
-                        println!("MECHANICAL STRESS DETECTED!");
-                        println!("Current: {}", data[0]);
-                        println!("Reference: {}", acc_ref_x);
-                        println!("Delta: {}", delta);
-
-                        alarm(
-                            &mut buzzer,
-                            &mut internal_led,
-                            &Limit::Mechanical,
-                            &mut delay,
-                        );
-                    }
-                }
-                Err(_) => panic!("Error reading data from the accelerometer"),
-            };
+        let now_ms = tick.wrapping_mul(TICK_MS);
+        if let Some(overrun) = scheduler.run(&mut context, now_ms, clock) {
+            warn!(
+                "WARNING: {} task took {} ms, longer than its {} ms period",
+                overrun.task, overrun.took_ms, overrun.period_ms
+            );
+        }
 
-            // Gyroscope data
-            match gyro {
-                Ok(data) => {
-                    println!("Gyroscope:");
-                    println!("Gx: {} rad/s", data[0]);
-                    println!("Gy: {} rad/s", data[1]);
-                    println!("Gz: {} rad/s", data[2]);
-                }
-                Err(_) => panic!("Error reading data from the gyroscope"),
-            };
+        board::ticker::wait(tick);
+        tick = tick.wrapping_add(1);
+    }
+}
 
-            // Temperature data
-            match temp {
-                Ok(data) => {
-                    println!("Temperature:\n{} ºC", data);
-
-                    let temp_ref = temp_ref.as_ref().unwrap();
-                    let mut delta = data - temp_ref;
-
-                    if delta.abs() >= TEMPERATURE_LIMIT {
-                        println!("OVERHEATING DETECTED");
-                        println!("Current: {}", data);
-                        println!("Reference: {}", temp_ref);
-                        println!("Delta: {}", delta);
-
-                        alarm(
-                            &mut buzzer,
-                            &mut internal_led,
-                            &Limit::Temperature,
-                            &mut delay,
-                        );
-                    }
-                }
-                Err(_) => panic!("Error reading data from the temperature sensor"),
-            }
+// A driver's handle on I2C0
+#[cfg(not(feature = "embassy"))]
+type SharedI2c<'a> = BusProxy<'a, I2C<'a, I2C0>>;
+
+// The machine sensor. Sampling and calibration only go through
+// `ImuSensor`, the rest is MPU6050 setup.
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+type Imu<I> = Mpu6050<I>;
+#[cfg(feature = "lsm6ds3")]
+type Imu<I> = Lsm6ds3<I>;
+
+// Everything the scheduled tasks share
+#[cfg(not(feature = "embassy"))]
+struct Context<'a, B> {
+    sample_period_ms: u32,
+    delay: Delay,
+    wdt: Wdt<TIMG0>,
+    #[cfg(not(feature = "sim"))]
+    mpu: Imu<SharedI2c<'a>>,
+    #[cfg(feature = "sim")]
+    mpu: Simulator,
+    alarm: Alarm<B, Gpio2<Output<PushPull>>>,
+    relay: Relay<Gpio26<Output<PushPull>>>,
+    model: Model,
+    sensor_config: SensorConfig,
+    motion_threshold: u8,
+    monitor: MaintenanceMonitor,
+    bus_health: BusHealth,
+    gyro_bias: GyroBias,
+    // What the DAC puts out, kept with the settings whether it's built or not
+    dac_mapping: DacMapping,
+    stuck: StuckDetector,
+    // What happened since boot, for the `log` command
+    events: EventLog,
+    // and where it's kept through a power cut
+    flash_log: FlashLog,
+    // The machine's, for its service schedule
+    hours: RunHours,
+    hours_ring: HoursRing,
+    // Readings leading up to an alarm, dumped when it fires
+    pre_trigger: Capture,
+    // and the fast readings right after it
+    post_trigger: PostTrigger,
+    // Alarms stay latched until acknowledged with the button
+    latch: AlarmLatch,
+    // Survives resets, stored after every change
+    record: EventRecord,
+    ack_button: Debouncer,
+    uart: Uart<'a, UART0>,
+    console: LineBuffer,
+    motion_trigger: MotionTrigger,
+    last_read_ms: u32,
+    // This tick's fast reading, shared by the tasks that need one
+    fast: Option<FastReading>,
+    // In the low-power cycle mode, see `PowerProfile`
+    sensor_cycling: bool,
+    output: OutputMode,
+    summary: Summary,
+    // Per minute, the last hour for the `trend` command
+    trend: Aggregator,
+    // The health score, from the closed minutes
+    score: ScoreTracker,
+    // A commissioning run, then its proposed levels until they're accepted
+    // or discarded
+    learning: Option<Learning>,
+    proposal: Option<Commissioning>,
+    // The saved one, and the recent samples drift is measured with
+    baseline: Option<Commissioning>,
+    recent: Profile,
+    // Recorded rows coming in on the console in place of the sensor
+    replay: Option<Replay>,
+    // A file going out on the console in place of everything else
+    download: Option<board::download::Download>,
+    // None when there's no display, or it stopped answering
+    display: Option<Oled<SharedI2c<'a>>>,
+    // None in single-sensor mode
+    #[cfg(feature = "differential")]
+    frame: Option<FrameSensor<SharedI2c<'a>>>,
+    // None without a probe, or once it stopped answering
+    #[cfg(feature = "ds18b20")]
+    probe: Option<(board::onewire::OneWirePin, Ds18b20)>,
+    // None if its input sat at a rail at boot
+    #[cfg(any(feature = "knock", feature = "current"))]
+    adc: board::adc::Adc1<'a>,
+    #[cfg(feature = "knock")]
+    knock: Option<board::knock::KnockSensor>,
+    #[cfg(feature = "current")]
+    current: board::current::CurrentSensor,
+    #[cfg(feature = "spectrum")]
+    spectrum: SpectrumBurst,
+    #[cfg(feature = "modbus")]
+    modbus: board::modbus::Port,
+    #[cfg(feature = "can")]
+    can: board::can::Bus<'a>,
+    #[cfg(feature = "dac")]
+    dac: board::dac::Output,
+    #[cfg(feature = "daq-trigger")]
+    trigger: TriggerGate,
+    #[cfg(feature = "sd-card")]
+    sd: board::sdcard::Card<'a>,
+    // The rows waiting for the card
+    #[cfg(feature = "sd-card")]
+    sd_log: SdLog,
+    #[cfg(feature = "raw-log")]
+    raw: board::rawlog::Recorder,
+    #[cfg(feature = "deep-sleep")]
+    rtc: Rtc<'a>,
+    // MPU6050 I2C address, for the next wake-up
+    #[cfg(feature = "deep-sleep")]
+    address: u8,
+    // No deep sleep before this, since the loop started
+    #[cfg(feature = "deep-sleep")]
+    stay_awake_ms: u32,
+    #[cfg(feature = "deep-sleep")]
+    duty: DutyCycle,
+    #[cfg(feature = "deep-sleep")]
+    wake_guard: WakeGuard,
+}
+
+#[cfg(not(feature = "embassy"))]
+#[derive(Clone, Copy)]
+struct FastReading {
+    now_ms: u32,
+    // Taken by the status sample, which already ran it through `update()`
+    sampled: bool,
+    // Calibrated and median-filtered, None if the read failed
+    readings: Option<(Reading, Reading)>,
+}
+
+// Times the tasks
+#[cfg(not(feature = "embassy"))]
+fn clock<B>(_: &Context<'_, B>) -> u32 {
+    board::time::uptime_ms() as u32
+}
+
+#[cfg(not(feature = "embassy"))]
+fn status_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    context.motion_trigger.sampled(now_ms);
+    wake_sensor(context);
+    sample(context, now_ms);
+}
+
+// A motion interrupt brings the next sample forward, after a burst of reads
+// for the peak deltas. It counts like a regular sample.
+#[cfg(not(feature = "embassy"))]
+fn motion_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    if sampled_at(context, now_ms) {
+        return;
+    }
+    let edge = board::motion::take_motion();
+    if context.replay.is_some() || !context.motion_trigger.take(edge, now_ms) {
+        return;
+    }
 
+    info!("Motion interrupt, sampling early");
+    wake_sensor(context);
+    motion_burst(
+        &mut context.mpu,
+        &mut context.delay,
+        &mut context.bus_health,
+        &context.gyro_bias,
+        &mut context.monitor,
+    );
+    sample(context, now_ms);
+}
+
+// Fast samples only feed the peak deltas, the orientation filter, the
+// vibration window and the post-trigger capture, the limits are checked at
+// the normal cadence
+#[cfg(not(feature = "embassy"))]
+fn peak_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    if sampled_at(context, now_ms) {
+        return;
+    }
+    if let Some((_, filtered)) = fast_reading(context, now_ms) {
+        context.monitor.hold_peak(filtered.acc);
+        context.monitor.fuse_orientation(&filtered);
+    }
+}
+
+#[cfg(not(feature = "embassy"))]
+fn vibration_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    if let Some((_, filtered)) = fast_reading(context, now_ms) {
+        context.monitor.push_vibration(filtered.acc);
+    }
+}
+
+// The capture gets the unfiltered reading
+#[cfg(not(feature = "embassy"))]
+fn post_trigger_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    if !context.post_trigger.is_open() {
+        return;
+    }
+    let Some((reading, _)) = fast_reading(context, now_ms) else {
+        return;
+    };
+
+    if context.post_trigger.push(reading) {
+        let origin_ms = context.post_trigger.origin_ms().unwrap_or(reading.t_ms);
+        if matches!(context.output, OutputMode::Human | OutputMode::Json) {
+            print_capture("Post-trigger", context.post_trigger.readings(), origin_ms);
             println!("---");
+        }
+        context.post_trigger.close();
+    }
+}
 
-            reset_reference = true;
-            delay.delay_ms(500u16);
-        }
-    }
-}
-
-// Functions to make the alarm sound
-fn alarm(
-    buzzer: &mut hal::gpio::GpioPin<
-        hal::gpio::Output<hal::gpio::PushPull>,
-        hal::gpio::Bank1GpioRegisterAccess,
-        hal::gpio::DualCoreInteruptStatusRegisterAccessBank1,
-        hal::gpio::InputOutputAnalogPinType,
-        hal::gpio::Gpio33Signals,
-        33,
-    >,
-    led: &mut hal::gpio::GpioPin<
-        hal::gpio::Output<hal::gpio::PushPull>,
-        hal::gpio::Bank0GpioRegisterAccess,
-        hal::gpio::DualCoreInteruptStatusRegisterAccessBank0,
-        hal::gpio::InputOutputAnalogPinType,
-        hal::gpio::Gpio2Signals,
-        2,
-    >,
-    limit: &Limit,
-    delay: &mut Delay,
-) {
-    let buzzes: u8 = match limit {
-        Limit::Mechanical => 3,
-        Limit::Temperature => 9,
+#[cfg(feature = "spectrum")]
+fn spectrum_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    if context.spectrum.started_ms.is_some() {
+        return;
+    }
+    wake_sensor(context);
+    let burst = &mut context.spectrum;
+    match start_burst(&mut context.mpu, &mut burst.spectrum, &mut context.monitor) {
+        Ok(()) => burst.started_ms = Some(now_ms),
+        Err(_) => warn!("WARNING: sensor FIFO setup failed, spectrum skipped"),
+    }
+}
+
+#[cfg(feature = "spectrum")]
+fn fifo_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    let (mpu, monitor, burst) = (
+        &mut context.mpu,
+        &mut context.monitor,
+        &mut context.spectrum,
+    );
+    let Some(started_ms) = burst.started_ms else {
+        return;
+    };
+
+    let drained = drain_fifo(
+        mpu,
+        &burst.format,
+        &mut burst.buffer,
+        &mut burst.spectrum,
+        monitor,
+    );
+    let done = match drained {
+        Ok(FifoDrain::Full) => {
+            print_spectrum(&burst.spectrum, burst.rate_hz);
+            true
+        }
+        Ok(FifoDrain::Overflowed) => {
+            warn!("WARNING: sensor FIFO overflowed, spectrum burst restarted");
+            burst.started_ms = Some(now_ms);
+            start_burst(mpu, &mut burst.spectrum, monitor).is_err()
+        }
+        Ok(FifoDrain::Filling) if now_ms.wrapping_sub(started_ms) > SPECTRUM_TIMEOUT_MS => {
+            warn!("WARNING: sensor FIFO not filling, spectrum skipped");
+            true
+        }
+        Ok(FifoDrain::Filling) => false,
+        Err(_) => {
+            warn!("WARNING: sensor FIFO read failed, spectrum skipped");
+            true
+        }
+    };
+    if done {
+        sensor::stop_fifo(mpu).ok();
+        burst.spectrum.clear();
+        burst.started_ms = None;
+    }
+}
+
+// The button acknowledges the latched alarm, resets the relay and mutes
+// the conditions that are still active, the LED keeps showing those.
+// A detached sensor is calibrated again, once it's back on its mount.
+#[cfg(not(feature = "embassy"))]
+fn button_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    if !(board::button::take_press() && context.ack_button.press(now_ms)) {
+        return;
+    }
+
+    if context.monitor.is_detached() {
+        recalibrate(context);
+    }
+
+    if context.latch.is_active() {
+        info!("ALARM ACKNOWLEDGED");
+        for event in context.latch.acknowledge().iter().flatten() {
+            print_latched_event(event, now_ms);
+        }
+    }
+    if context.relay.tripped().is_some() {
+        context.relay.acknowledge().unwrap();
+        info!("Relay reset, machine allowed to run");
+    }
+    if context.monitor.any_latched() {
+        context.monitor.mute();
+        info!("Alarm muted");
+    }
+    context.alarm.silence().unwrap();
+}
+
+// One request a tick. A write is applied like `set` and `save` on the
+// console before it's answered.
+#[cfg(feature = "modbus")]
+fn modbus_task<B>(context: &mut Context<'_, B>, _now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    let Some(adu) = context.modbus.take() else {
+        return;
+    };
+    let monitor = &context.monitor;
+    let registers = Registers {
+        reading: *monitor.filtered(),
+        acc_magnitude: monitor.acc_magnitude(),
+        health: context.score.last(),
+        uptime_s: board::time::uptime_s(),
+        warnings: Setting::ALL.map(|setting| setting.levels(monitor.thresholds()).warning),
+        latched: Limit::ALL.map(|limit| monitor.latched(limit)),
+        learning: context.learning.is_some(),
+    };
+    let Some(handled) = modbus::handle(&adu, config::MODBUS.address, &registers) else {
+        return;
+    };
+    if let Some(warnings) = handled.warnings {
+        for (setting, warning) in Setting::ALL.into_iter().zip(warnings) {
+            if let Some(warning) = warning {
+                info!("Modbus: {} written", setting.name());
+                run_command(context, Command::Set(setting, warning));
+            }
+        }
+        run_command(context, Command::Save);
+    }
+    if let Some(reply) = handled.reply {
+        context.modbus.send(&reply);
+    }
+}
+
+#[cfg(feature = "can")]
+fn can_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    context.can.poll(now_ms);
+}
+
+#[cfg(feature = "can")]
+fn can_readings_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let monitor = &context.monitor;
+    context.can.push(CanMessage::Readings {
+        vibration_rms: monitor.vibration_rms(),
+        acc_peak: Some(monitor.acc_peak().magnitude()),
+        temp: Some(monitor.filtered().temp),
+        health: context.score.last(),
+    });
+}
+
+#[cfg(feature = "dac")]
+fn dac_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    let vibration_rms = context.monitor.vibration_rms();
+    context
+        .dac
+        .update(now_ms, &context.dac_mapping, vibration_rms);
+}
+
+#[cfg(feature = "sd-card")]
+fn sd_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    context.sd.write(now_ms, &mut context.sd_log);
+}
+
+// Every tick's reading, the unfiltered one in the sensor's counts
+#[cfg(feature = "raw-log")]
+fn raw_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    let acc = if context.raw.wants_sample() {
+        fast_reading(context, now_ms)
+            .map(|(reading, _)| ReadingFrame::new(&reading, &context.sensor_config, 0).acc)
+    } else {
+        None
     };
+    context.raw.record(board::time::uptime_ms(), acc);
+}
+
+#[cfg(feature = "raw-log")]
+fn raw_dump_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    context.raw.dump_line();
+}
 
-    for _ in 0..buzzes {
-        buzzer.set_high().unwrap();
-        led.set_high().unwrap();
+// Line-based commands on the serial monitor, see `console::HELP`.
+// The RX FIFO holds 128 bytes, plenty for a typed line between two polls.
+#[cfg(not(feature = "embassy"))]
+fn console_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    while let Ok(byte) = context.uart.read() {
+        if let Some(download) = &mut context.download {
+            download.receive(byte, board::time::uptime_ms() as u32);
+            continue;
+        }
+        if context.replay.is_some() {
+            replay_byte(context, byte, now_ms);
+            continue;
+        }
+        match context.console.push(byte) {
+            Some(Ok(command)) => run_command(context, command),
+            Some(Err(error)) => println!("ERROR: {}", error),
+            None => {}
+        }
+    }
 
-        alarm_time(limit, delay);
+    let uptime_ms = board::time::uptime_ms();
+    if matches!(&context.replay, Some(replay) if replay.timed_out(uptime_ms)) {
+        end_replay(context, "timed out");
+    }
+}
+
+// While replaying, every line is a row, or `end`
+#[cfg(not(feature = "embassy"))]
+fn replay_byte<B>(context: &mut Context<'_, B>, byte: u8, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    let uptime_ms = board::time::uptime_ms();
+    let Context {
+        console, replay, ..
+    } = context;
+    let Some(replay) = replay else {
+        return;
+    };
+    let line = match console.push_with(byte, |line| replay.line(line, uptime_ms)) {
+        Some(Ok(line)) => line,
+        // Too long for a row
+        Some(Err(_)) => {
+            replay.skip(uptime_ms);
+            return;
+        }
+        None => return,
+    };
 
-        buzzer.set_low().unwrap();
-        led.set_low().unwrap();
+    match line {
+        ReplayLine::Sample(reading) => sample_from(context, Some(reading), now_ms),
+        ReplayLine::End => end_replay(context, "done"),
+        ReplayLine::Header | ReplayLine::Malformed => {}
+    }
+}
 
-        alarm_time(limit, delay);
+// The file `download` started, a piece of a frame a tick at ~10 KB/s.
+// The monitoring's output is back as soon as it's over, however it ended.
+#[cfg(not(feature = "embassy"))]
+fn download_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let Some(download) = &mut context.download else {
+        return;
+    };
+    let Some(outcome) = download.send(&mut context.uart, board::time::uptime_ms() as u32) else {
+        return;
+    };
+    board::log::pause(false);
+    println!(
+        "Download {}: {} of {} bytes of {}",
+        outcome.description(),
+        download.sent(),
+        download.size(),
+        download.name()
+    );
+    context.download = None;
+    print_header(context.output);
+    if context.output == OutputMode::Binary {
+        send_banner(&mut context.uart, &context.sensor_config);
     }
 }
 
-fn alarm_time(limit: &Limit, delay: &mut Delay) {
-    match limit {
-        Limit::Mechanical => delay.delay_ms(100u8),
-        Limit::Temperature => delay.delay_ms(50u8),
+// The OK line goes out before the console is handed over
+#[cfg(not(feature = "embassy"))]
+fn start_download<B>(context: &mut Context<'_, B>, file: LogFile, protocol: Protocol) {
+    let now_ms = board::time::uptime_ms() as u32;
+    let download = match file {
+        LogFile::Events => {
+            // The events waiting go in the file, the later ones after it
+            save_events(context);
+            board::download::Download::events(context.flash_log, protocol, now_ms)
+        }
+        #[cfg(feature = "raw-log")]
+        LogFile::Raw if context.raw.is_dumping() => {
+            println!("ERROR: dumping the raw log, `raw stop` first");
+            return;
+        }
+        #[cfg(feature = "raw-log")]
+        LogFile::Raw => board::download::Download::raw(context.raw.log().ring(), protocol, now_ms),
+        #[cfg(not(feature = "raw-log"))]
+        LogFile::Raw => {
+            println!("ERROR: no raw log, build with `raw-log`");
+            return;
+        }
+    };
+    println!(
+        "OK: {} bytes of {} over {}, start the receive within {} s, Ctrl-X twice cancels it",
+        download.size(),
+        download.name(),
+        protocol.name(),
+        START_TIMEOUT_MS / 1_000
+    );
+    board::log::pause(true);
+    context.download = Some(download);
+}
+
+#[cfg(not(feature = "embassy"))]
+fn end_replay<B>(context: &mut Context<'_, B>, how: &str) {
+    let Some(replay) = context.replay.take() else {
+        return;
+    };
+    // Nothing to compute the jerk against across the switch
+    context.monitor.sample_missed();
+    println!(
+        "Replay {}: {} samples over {} ms, {} lines skipped, back to live sampling",
+        how,
+        replay.samples(),
+        replay.span_ms(),
+        replay.skipped()
+    );
+}
+
+#[cfg(not(feature = "embassy"))]
+fn run_command<B>(context: &mut Context<'_, B>, command: Command)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    match command {
+        Command::Set(setting, warning) => {
+            let was = setting.levels(context.monitor.thresholds()).warning;
+            if context
+                .monitor
+                .set_warning(setting.limit(), warning)
+                .is_none()
+            {
+                return;
+            }
+            println!("OK: {} updated", setting.name());
+            context.events.push(
+                Event::new(board::time::uptime_s(), EventKind::Setting(setting))
+                    .with_values(warning, was),
+            );
+            print_setting(setting, context.monitor.thresholds());
+            if setting == Setting::Mechanical {
+                follow_mechanical_limit(context);
+            }
+        }
+        Command::Get => {
+            for setting in Setting::ALL {
+                print_setting(setting, context.monitor.thresholds());
+            }
+        }
+        Command::Calibrate => recalibrate(context),
+        #[cfg(feature = "current")]
+        Command::ZeroCurrent => zero_current(&mut context.adc, &mut context.current),
+        #[cfg(not(feature = "current"))]
+        Command::ZeroCurrent => println!("ERROR: no current sensor, build with `current`"),
+        Command::Mute => {
+            context.monitor.mute();
+            context.alarm.silence().unwrap();
+            println!("OK: alarm muted");
+        }
+        Command::Status => {
+            print_filtered(&context.monitor);
+            print_status(&context.monitor);
+            print_hours(&context.hours);
+            #[cfg(feature = "can")]
+            print_can(&context.can);
+            #[cfg(feature = "sd-card")]
+            print_sd(&context.sd, &context.sd_log);
+            #[cfg(feature = "raw-log")]
+            print_raw(&context.raw);
+        }
+        // It would save the safety caps
+        Command::Save if context.learning.is_some() => {
+            println!("ERROR: learning, `learn discard` first or wait for it to finish")
+        }
+        Command::Save => {
+            let settings = Settings::new(
+                context.monitor.thresholds(),
+                context.sample_period_ms,
+                &context.gyro_bias,
+                context.dac_mapping,
+            );
+            match board::flash::save(&settings) {
+                Ok(()) => println!("OK: settings saved"),
+                Err(error) => println!("ERROR: flash write failed: {:?}", error),
+            }
+        }
+        // The gyroscope bias is measured, not configured, it stays
+        Command::FactoryReset => {
+            let defaults = Settings::new(
+                &Thresholds::default(),
+                SAMPLE_PERIOD_MS,
+                &context.gyro_bias,
+                DacMapping::default(),
+            );
+            defaults.apply(&mut context.monitor, &mut context.gyro_bias);
+            context.dac_mapping = defaults.dac;
+            follow_mechanical_limit(context);
+            context
+                .events
+                .push(Event::new(board::time::uptime_s(), EventKind::FactoryReset));
+            match board::flash::erase() {
+                Ok(()) => println!("OK: compiled-in levels restored, saved settings erased"),
+                Err(error) => println!("ERROR: flash erase failed: {:?}", error),
+            }
+        }
+        Command::Counters => print_record(&context.record),
+        // The boot count starts over with this boot
+        Command::ClearCounters => {
+            context.record = EventRecord::new();
+            context.record.booted();
+            board::record::store(&context.record);
+            println!("OK: counters cleared");
+        }
+        Command::Log => print_events(&context.events),
+        Command::ClearLog => {
+            context.events.clear();
+            println!("OK: event log cleared");
+        }
+        Command::Trend => print_trend(&context.trend),
+        Command::Replay if context.learning.is_some() => {
+            println!("ERROR: learning, `learn discard` first or wait for it to finish");
+        }
+        Command::Replay => {
+            context.replay = Some(Replay::new(board::time::uptime_ms()));
+            context.monitor.sample_missed();
+            println!(
+                "Replay: send t,ax,ay,az,gx,gy,gz,temp rows, `end` when done; the sensor isn't read meanwhile"
+            );
+        }
+        Command::DumpFlash => {
+            save_events(context);
+            dump_flash_log(context);
+        }
+        Command::Service(service) => {
+            match service {
+                Service::Status => {}
+                Service::Done => {
+                    context.hours.service_done();
+                    context.events.push(
+                        Event::new(board::time::uptime_s(), EventKind::ServiceDone)
+                            .with_values(context.hours.running_h(), f32::NAN),
+                    );
+                    println!("OK: service done");
+                }
+                Service::Interval(interval_h) => {
+                    context.hours.set_interval(interval_h);
+                    println!("OK: service every {} running hours", interval_h);
+                }
+            }
+            save_hours(context);
+            print_hours(&context.hours);
+        }
+        Command::Learn(Learn::Status) => print_learning(context),
+        Command::Learn(Learn::Start) => start_learning(context, false),
+        Command::Learn(Learn::Accept) => match context.proposal.take() {
+            Some(proposal) => accept_proposal(context, proposal),
+            None => println!("ERROR: no proposed levels, `learn start` first"),
+        },
+        Command::Learn(Learn::Discard) => {
+            if let Some(run) = context.learning.take() {
+                run.abort(&mut context.monitor);
+                follow_mechanical_limit(context);
+                println!("OK: learning stopped, levels back to before it");
+            } else if context.proposal.take().is_some() {
+                println!("OK: proposed levels discarded");
+            } else {
+                println!("ERROR: not learning, nothing proposed");
+            }
+        }
+        #[cfg(feature = "sim")]
+        Command::Sim(sim) => {
+            match sim {
+                Sim::Status => {}
+                Sim::Play(scenario) => context.mpu.play(scenario),
+                Sim::Seed(seed) => context.mpu.reseed(seed),
+            }
+            print_sim(&context.mpu);
+        }
+        #[cfg(not(feature = "sim"))]
+        Command::Sim(_) => println!("ERROR: no simulator, build with `sim`"),
+        #[cfg(feature = "sd-card")]
+        Command::Ls => context.sd.list(),
+        #[cfg(feature = "sd-card")]
+        Command::Tail => context.sd.tail(),
+        #[cfg(not(feature = "sd-card"))]
+        Command::Ls | Command::Tail => println!("ERROR: no SD card, build with `sd-card`"),
+        #[cfg(feature = "dac")]
+        Command::Dac(dac) => run_dac(context, dac),
+        #[cfg(not(feature = "dac"))]
+        Command::Dac(_) => println!("ERROR: no analog output, build with `dac`"),
+        #[cfg(feature = "raw-log")]
+        Command::Raw(Raw::Status) => print_raw(&context.raw),
+        #[cfg(feature = "raw-log")]
+        Command::Raw(Raw::Dump(encoding, blocks)) => context.raw.dump(encoding, blocks),
+        #[cfg(feature = "raw-log")]
+        Command::Raw(Raw::Stop) => context.raw.stop(),
+        #[cfg(not(feature = "raw-log"))]
+        Command::Raw(_) => println!("ERROR: no raw log, build with `raw-log`"),
+        Command::Download(file, protocol) => start_download(context, file, protocol),
+        Command::Output(mode) => {
+            context.output = mode;
+            println!("OK: {} output", mode.name());
+            print_header(mode);
+            if mode == OutputMode::Binary {
+                send_banner(&mut context.uart, &context.sensor_config);
+            }
+        }
+        Command::Level(level) => {
+            board::log::set_level(level);
+            println!("OK: {} level", level.name());
+        }
+        Command::Color(on) => {
+            board::log::set_color(on);
+            println!("OK: color {}", if on { "on" } else { "off" });
+        }
+        Command::Help => {
+            for line in console::HELP {
+                println!("{}", line);
+            }
+        }
     }
 }
 
-// abs() method for f32 is not defined outside std
-pub trait Absolute {
-    fn abs(&mut self) -> Self;
+// A new mapping takes effect at once, `save` keeps it
+#[cfg(feature = "dac")]
+fn run_dac<B>(context: &mut Context<'_, B>, dac: Dac) {
+    let mapping = context.dac_mapping;
+    let mapped = match dac {
+        Dac::Status => {
+            print_dac(&context.dac, &mapping);
+            return;
+        }
+        Dac::Test => {
+            context.dac.test();
+            println!(
+                "OK: test pattern, 0, 25, 50, 75 and 100% for {} s each",
+                STAIRCASE_STEP_MS / 1_000
+            );
+            return;
+        }
+        Dac::Zero(zero) => DacMapping::new(zero, mapping.full_scale),
+        Dac::FullScale(full_scale) => DacMapping::new(mapping.zero, full_scale),
+    };
+    match mapped {
+        Some(mapped) => {
+            context.dac_mapping = mapped;
+            println!("OK: DAC mapping updated");
+            print_dac(&context.dac, &mapped);
+        }
+        None => println!(
+            "ERROR: the full scale has to be at least {} m/s^2 over the zero",
+            DAC_MIN_SPAN
+        ),
+    }
 }
 
-impl Absolute for f32 {
-    fn abs(&mut self) -> Self {
-        if self.is_sign_negative() {
-            *self *= -1.0;
+// The motion interrupt threshold tracks the mechanical warning level
+#[cfg(not(feature = "embassy"))]
+fn follow_mechanical_limit<B>(context: &mut Context<'_, B>) {
+    let warning = context.monitor.thresholds().mechanical.warning;
+    context.motion_threshold = motion::motion_threshold(warning);
+    #[cfg(not(any(feature = "lsm6ds3", feature = "sim")))]
+    {
+        let enabled = sensor::enable_motion_interrupt(
+            &mut context.mpu,
+            context.motion_threshold,
+            MOTION_DURATION_MS,
+        );
+        if enabled.is_err() {
+            warn!("WARNING: motion interrupt not updated");
         }
-        *self
     }
 }
+
+// New references, blocking for a couple of seconds with the watchdog fed
+#[cfg(not(feature = "embassy"))]
+fn recalibrate<B>(context: &mut Context<'_, B>)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    wake_sensor(context);
+    let wdt = &mut context.wdt;
+    let baseline = calibrate(
+        &mut context.mpu,
+        &mut context.alarm,
+        &mut context.delay,
+        &mut context.bus_health,
+        &mut || wdt.feed(),
+    );
+    adopt(&baseline, &mut context.monitor, &mut context.gyro_bias);
+    #[cfg(feature = "differential")]
+    if let Some(frame) = &mut context.frame {
+        frame.differential = calibrate_frame(
+            &mut frame.mpu,
+            &mut context.alarm,
+            &mut context.delay,
+            &mut context.bus_health,
+            &mut || wdt.feed(),
+        );
+    }
+    context.monitor.reset_probe_reference();
+    context
+        .events
+        .push(Event::new(board::time::uptime_s(), EventKind::Calibrated));
+    info!("Sensor recalibrated");
+}
+
+#[cfg(not(feature = "embassy"))]
+fn alarm_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    let (alarm, latch, monitor) = (&mut context.alarm, &context.latch, &context.monitor);
+
+    if latch.reminder_due(now_ms) {
+        if let Some(event) = Limit::ALL.iter().find_map(|limit| latch.event(*limit)) {
+            alarm.chirp(event.limit, now_ms).unwrap();
+        }
+    }
+
+    alarm
+        .set_indicator(monitor.any_latched() || latch.is_active())
+        .unwrap();
+
+    alarm.tick(now_ms).unwrap();
+}
+
+// The LED shows the heartbeat whenever the alarm leaves it alone
+#[cfg(not(feature = "embassy"))]
+fn heartbeat_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    let health = Health::of(
+        now_ms.wrapping_sub(context.last_read_ms),
+        context.bus_health.is_escalated(),
+        context.stuck.is_stuck(),
+        context.hours.is_due(),
+    );
+    context
+        .alarm
+        .set_heartbeat(heartbeat::led(health, now_ms))
+        .unwrap();
+}
+
+#[cfg(not(feature = "embassy"))]
+fn flash_log_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    // The file's length is the log's as it started
+    if !matches!(&context.download, Some(download) if download.holds_event_log()) {
+        save_events(context);
+    }
+    save_hours(context);
+}
+
+// Every HOURS_SAVE_S of running, or right after a change. A failed write
+// is only retried with the next one.
+#[cfg(not(feature = "embassy"))]
+fn save_hours<B>(context: &mut Context<'_, B>) {
+    if !context.hours.save_due() {
+        return;
+    }
+    if let Err(error) = board::flash::save_hours(&mut context.hours_ring, &context.hours) {
+        warn!("WARNING: running hours flash write failed: {:?}", error);
+    }
+    context.hours.saved();
+}
+
+// What the event log got since the last time, to flash. A failed write
+// drops the event, the RAM log still has it.
+#[cfg(not(feature = "embassy"))]
+fn save_events<B>(context: &mut Context<'_, B>) {
+    while let Some(event) = context.events.take_unsaved() {
+        if let Err(error) = board::flash::append(&mut context.flash_log, &event) {
+            warn!("WARNING: event log flash write failed: {:?}", error);
+        }
+    }
+}
+
+// Once a commissioning run is over: its levels are proposed, or taken
+// right away on a new machine
+#[cfg(not(feature = "embassy"))]
+fn learning_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let Some(run) = context.learning else {
+        return;
+    };
+    if !run.is_done() {
+        return;
+    }
+    context.learning = None;
+    let proposal = run.finish(&mut context.monitor);
+    follow_mechanical_limit(context);
+    print_proposal(&proposal, context.monitor.thresholds());
+    if run.automatic {
+        accept_proposal(context, proposal);
+    } else {
+        context.proposal = Some(proposal);
+        println!("Learning: done, `learn accept` to take these levels, `learn discard` to keep the current ones");
+    }
+}
+
+// A run already going starts over
+#[cfg(not(feature = "embassy"))]
+fn start_learning<B>(context: &mut Context<'_, B>, automatic: bool) {
+    if let Some(run) = context.learning.take() {
+        run.abort(&mut context.monitor);
+    }
+    context.proposal = None;
+    context.learning = Some(Learning::start(&mut context.monitor, automatic));
+    follow_mechanical_limit(context);
+    println!(
+        "Learning: {} min of the machine's normal running, only gross events alarm meanwhile",
+        LEARNING_MS / 60_000
+    );
+    for setting in Setting::ALL {
+        print_setting(setting, context.monitor.thresholds());
+    }
+}
+
+// The proposed levels become the active ones, saved with the statistics
+// they came from
+#[cfg(not(feature = "embassy"))]
+fn accept_proposal<B>(context: &mut Context<'_, B>, proposal: Commissioning) {
+    for setting in Setting::ALL {
+        let was = setting.levels(context.monitor.thresholds()).warning;
+        let Some(warning) = proposal.proposal(setting) else {
+            continue;
+        };
+        context.events.push(
+            Event::new(board::time::uptime_s(), EventKind::Setting(setting))
+                .with_values(warning, was),
+        );
+    }
+    proposal.apply(&mut context.monitor);
+    follow_mechanical_limit(context);
+    context.baseline = Some(proposal);
+    context.recent = Profile::new(DRIFT_WINDOW);
+
+    let settings = Settings::new(
+        context.monitor.thresholds(),
+        context.sample_period_ms,
+        &context.gyro_bias,
+        context.dac_mapping,
+    );
+    match board::flash::save(&settings).and_then(|()| board::flash::save_baseline(&proposal)) {
+        Ok(()) => println!("OK: learned levels active and saved, with the baseline"),
+        Err(error) => println!("ERROR: flash write failed: {:?}", error),
+    }
+    for setting in Setting::ALL {
+        print_setting(setting, context.monitor.thresholds());
+    }
+}
+
+// Without a good sensor read for a while, the watchdog is left to reset the
+// chip and the boot checks run again
+#[cfg(not(feature = "embassy"))]
+fn watchdog_task<B>(context: &mut Context<'_, B>, now_ms: u32) {
+    if now_ms.wrapping_sub(context.last_read_ms) < SENSOR_TIMEOUT_MS {
+        context.wdt.feed();
+    }
+}
+
+// Sleeps from right after the status sample until the next one. Nothing
+// that needs the board awake may be going on: an alarm pattern, a latched
+// alarm waiting for the button, a tripped relay (undriven while asleep) or
+// a post-trigger capture.
+#[cfg(feature = "deep-sleep")]
+fn sleep_task<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    if now_ms < context.stay_awake_ms
+        || context.alarm.is_playing()
+        || context.latch.is_active()
+        || context.relay.tripped().is_some()
+        || context.post_trigger.is_open()
+        || context.learning.is_some()
+        || context.replay.is_some()
+        || context.download.is_some()
+    {
+        return;
+    }
+
+    let awake_ms = board::time::awake_ms() as u32;
+    let sleep_ms = sleep::sleep_ms(context.sample_period_ms, awake_ms);
+    context.duty.cycle(awake_ms, sleep_ms);
+    info!(
+        "Awake {} ms, sleeping {} ms, duty cycle {}% over {} cycles",
+        awake_ms,
+        sleep_ms,
+        context.duty.percent().unwrap_or(100.0),
+        context.duty.cycles
+    );
+
+    // Motion wakes the board up early, unless it just did
+    let uptime_ms = board::time::uptime_ms();
+    let motion = context.wake_guard.arm_motion(uptime_ms);
+    let powered_down = if motion {
+        let threshold = motion::motion_threshold(
+            context.monitor.thresholds().mechanical.warning * sleep::WAKE_THRESHOLD_RATIO,
+        );
+        sensor::watch_motion(&mut context.mpu, threshold)
+    } else {
+        info!("Motion wake-up off for this sleep, the last one was too recent");
+        sensor::set_sleep(&mut context.mpu, true)
+    };
+    if powered_down.is_err() {
+        warn!("WARNING: MPU6050 didn't take the low-power mode");
+    }
+    context.alarm.set_heartbeat(false).unwrap();
+    save_events(context);
+    save_hours(context);
+
+    let resume = board::sleep::Resume {
+        sample_period_ms: context.sample_period_ms,
+        gyro_bias: context.gyro_bias,
+        dac: context.dac_mapping,
+        address: context.address,
+        model: context.model,
+        sensor_config: context.sensor_config,
+        motion_threshold: context.motion_threshold,
+        duty: context.duty,
+        wake_guard: context.wake_guard,
+        flash_log: context.flash_log,
+        hours: context.hours,
+        hours_ring: context.hours_ring,
+        baseline: context.baseline,
+        recent: context.recent,
+        uptime_ms: uptime_ms + sleep_ms as u64,
+    };
+    board::sleep::sleep(
+        &mut context.rtc,
+        &mut context.delay,
+        &context.monitor,
+        resume,
+        sleep_ms,
+        motion,
+    );
+}
+
+// Lets a human on a terminal, or a decoder that joins late, know what the
+// binary frames are
+#[cfg(not(feature = "embassy"))]
+fn banner_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    if context.output == OutputMode::Binary {
+        send_banner(&mut context.uart, &context.sensor_config);
+    }
+}
+
+// Redraws the last status sample, display_flush_task sends it out
+#[cfg(not(feature = "embassy"))]
+fn display_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let Context {
+        display: Some(display),
+        pre_trigger,
+        monitor,
+        ..
+    } = context
+    else {
+        return;
+    };
+    if let Some(reading) = pre_trigger.iter().last() {
+        display.show(&Screen::new(reading, monitor));
+    }
+}
+
+// A page per tick while a frame is going out. A page that fails is sent
+// again, a display that keeps failing is dropped.
+#[cfg(not(feature = "embassy"))]
+fn display_flush_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let Some(display) = &mut context.display else {
+        return;
+    };
+    if display.flush_page().is_err() && display.is_lost() {
+        warn!("WARNING: display not answering, running headless");
+        context.display = None;
+    }
+}
+
+// Reads the conversion started a period ago and starts the next, the
+// status sample picks the temperature up. A probe that keeps failing is
+// dropped.
+#[cfg(feature = "ds18b20")]
+fn probe_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let Some((bus, probe)) = &mut context.probe else {
+        return;
+    };
+    match probe.cycle(bus) {
+        Ok(Some(temp)) => context.monitor.set_probe(Some(temp)),
+        Ok(None) => {}
+        Err(error) => warn!("WARNING: probe {}: {}", probe.rom(), error.description()),
+    }
+    if probe.is_lost() {
+        warn!("WARNING: probe lost, temperature channel disabled");
+        context.monitor.set_probe(None);
+        context.probe = None;
+    }
+}
+
+// One time-bounded burst of the knock sensor, the next status sample
+// checks the loudest
+#[cfg(feature = "knock")]
+fn knock_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let Some(knock) = &mut context.knock else {
+        return;
+    };
+    let burst = knock.burst(&mut context.adc);
+    match burst.level() {
+        Some(level) => context.monitor.push_knock(level),
+        None => debug!("Knock burst cut short at {} conversions", burst.samples()),
+    }
+}
+
+// The motor's RMS current over a mains cycle, for the next status samples
+#[cfg(feature = "current")]
+fn current_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let window = context.current.window(&mut context.adc);
+    match window.rms() {
+        Some(amps) => context.monitor.set_current(Some(amps)),
+        None => debug!(
+            "Current window cut short at {} conversions",
+            window.samples()
+        ),
+    }
+}
+
+// The shaft speed for the vibration limits' speed band
+#[cfg(feature = "tachometer")]
+fn rpm_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let (rpm, bounces) = board::tachometer::take();
+    context.monitor.set_speed(Some(rpm));
+    if bounces > 0 {
+        debug!("Tachometer: {} pulses dropped as bounce", bounces);
+    }
+}
+
+// Out of the low-power cycle mode, for a sample or a burst
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+fn wake_sensor<B>(context: &mut Context<'_, B>) {
+    if !context.sensor_cycling {
+        return;
+    }
+    match sensor::full_power(&mut context.mpu) {
+        Ok(()) => {
+            context.delay.delay_ms(sensor::WAKE_SETTLE_MS);
+            context.sensor_cycling = false;
+        }
+        Err(_) => warn!("WARNING: MPU6050 didn't leave the cycle mode"),
+    }
+}
+
+// The LSM6DS3 and the simulator run continuously, `sensor_cycling` stays
+// false
+#[cfg(any(feature = "lsm6ds3", feature = "sim"))]
+fn wake_sensor<B>(_context: &mut Context<'_, B>) {}
+
+// With the low-power profile, the sensor goes back to cycling as soon as
+// nothing needs it at full power
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+fn power_task<B>(context: &mut Context<'_, B>, _now_ms: u32) {
+    let PowerProfile::LowPower(rate) = context.sensor_config.power else {
+        return;
+    };
+    if context.sensor_cycling || context.post_trigger.is_open() {
+        return;
+    }
+    #[cfg(feature = "spectrum")]
+    if context.spectrum.started_ms.is_some() {
+        return;
+    }
+
+    if sensor::enter_cycle(&mut context.mpu, rate).is_ok() {
+        context.sensor_cycling = true;
+    }
+}
+
+#[cfg(any(feature = "lsm6ds3", feature = "sim"))]
+fn power_task<B>(_context: &mut Context<'_, B>, _now_ms: u32) {}
+
+#[cfg(not(feature = "embassy"))]
+fn sampled_at<B>(context: &Context<'_, B>, now_ms: u32) -> bool {
+    matches!(context.fast, Some(fast) if fast.now_ms == now_ms && fast.sampled)
+}
+
+// Reads the sensor once per tick, whichever task asks first.
+// The sample tick's reading already went through the median filter.
+#[cfg(not(feature = "embassy"))]
+fn fast_reading<B>(context: &mut Context<'_, B>, now_ms: u32) -> Option<(Reading, Reading)> {
+    // Only the accelerometer runs, and only now and then. A replay stands
+    // in for every read.
+    if context.sensor_cycling || context.replay.is_some() {
+        return None;
+    }
+    if let Some(fast) = context.fast.filter(|fast| fast.now_ms == now_ms) {
+        return fast.readings;
+    }
+
+    #[cfg(feature = "differential")]
+    let machine_us = board::time::awake_us();
+    let readings = read_sample(
+        &mut context.mpu,
+        &mut context.delay,
+        &mut context.bus_health,
+    )
+    .map(|raw| {
+        let reading = calibrated(&raw, &context.gyro_bias);
+        #[cfg(feature = "differential")]
+        let reading = match &mut context.frame {
+            Some(frame) => frame.subtract(
+                reading,
+                machine_us,
+                &mut context.delay,
+                &mut context.bus_health,
+            ),
+            None => reading,
+        };
+        (reading, context.monitor.filter(&reading))
+    });
+    context.fast = Some(FastReading {
+        now_ms,
+        sampled: false,
+        readings,
+    });
+    readings
+}
+
+// The status sample: checks the limits and reports everything
+#[cfg(not(feature = "embassy"))]
+fn sample<B>(context: &mut Context<'_, B>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    if context.replay.is_none() {
+        sample_from(context, None, now_ms);
+    }
+}
+
+// A replayed row takes the sensor read's place, calibrated already. It's
+// all the fast reads there are too.
+#[cfg(not(feature = "embassy"))]
+fn sample_from<B>(context: &mut Context<'_, B>, replayed: Option<Reading>, now_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+{
+    let Context {
+        delay,
+        mpu,
+        alarm,
+        relay,
+        model,
+        sensor_config,
+        motion_threshold,
+        monitor,
+        bus_health,
+        gyro_bias,
+        stuck,
+        events,
+        hours,
+        pre_trigger,
+        post_trigger,
+        latch,
+        record,
+        motion_trigger,
+        last_read_ms,
+        fast,
+        output,
+        uart,
+        summary,
+        trend,
+        score,
+        learning,
+        recent,
+        #[cfg(feature = "differential")]
+        frame,
+        #[cfg(feature = "can")]
+        can,
+        #[cfg(feature = "daq-trigger")]
+        trigger,
+        #[cfg(feature = "sd-card")]
+        sd_log,
+        ..
+    } = context;
+    // The full dump of every sample, the summary line covers them otherwise
+    let dump = *output == OutputMode::Human && board::log::enabled(Level::Debug);
+    let mut sampled = None;
+
+    // Update values. Transient I2C errors are retried, a read that
+    // keeps failing only costs this sample.
+    #[cfg(feature = "differential")]
+    let machine_us = board::time::awake_us();
+    let read = match replayed {
+        Some(row) => {
+            monitor.fuse_orientation(&row);
+            monitor.push_vibration(row.acc);
+            Some(row)
+        }
+        None => read_sample(mpu, delay, bus_health),
+    };
+    match read {
+        Some(raw) => {
+            *last_read_ms = now_ms;
+            if bus_health.success() {
+                info!("Sensor responding again");
+            }
+
+            let was_stuck = stuck.is_stuck();
+            match stuck.update(&raw) {
+                Some(StuckAction::Recover) => {
+                    warn!(
+                        "WARNING: sensor output frozen for {} samples, re-initializing it",
+                        stuck.repeats()
+                    );
+                    reinit(mpu, *model, sensor_config, *motion_threshold, delay);
+                    monitor.sample_missed();
+                }
+                Some(StuckAction::Fault) => {
+                    error!("FAULT: {}", Fault::StuckSensor.description());
+                    score.fault();
+                    events.push(Event::new(
+                        board::time::uptime_s(),
+                        EventKind::Fault(Fault::StuckSensor),
+                    ));
+                    if !alarm.is_playing() {
+                        alarm.fault(Fault::StuckSensor, now_ms).unwrap();
+                    }
+                }
+                None if was_stuck && !stuck.is_stuck() => {
+                    info!("Sensor output changing again");
+                }
+                None => {}
+            }
+
+            for axis in sensor_config.clipped_acc(raw.acc) {
+                warn!(
+                    "WARNING: accelerometer {} axis clipped at ±{} g, increase the range",
+                    axis.name(),
+                    sensor_config.accel_full_scale_g()
+                );
+            }
+            for axis in sensor_config.clipped_gyro(raw.gyro) {
+                warn!(
+                    "WARNING: gyroscope {} axis clipped at ±{} º/s, increase the range",
+                    axis.name(),
+                    sensor_config.gyro_full_scale_dps()
+                );
+            }
+
+            let reading = match replayed {
+                Some(_) => raw,
+                None => calibrated(&raw, gyro_bias),
+            };
+            #[cfg(feature = "differential")]
+            let reading = match frame {
+                Some(frame) if replayed.is_none() => {
+                    frame.subtract(reading, machine_us, delay, bus_health)
+                }
+                _ => reading,
+            };
+            pre_trigger.push(reading);
+
+            // The chip's temperature has nothing to do with a recording
+            let chip_temp = match replayed {
+                Some(_) => None,
+                None => Some(board::chiptemp::read(delay)),
+            };
+            monitor.set_chip_temp(chip_temp);
+            let alert = monitor.update(&reading);
+            // The DAQ's edge before anything else, and not for a recording
+            #[cfg(feature = "daq-trigger")]
+            let triggered = {
+                let decided_us = board::time::awake_us();
+                let tripped = alert
+                    .as_ref()
+                    .map(|alert| alert.limit)
+                    .filter(|_| replayed.is_none());
+                trigger
+                    .update(tripped, |limit| monitor.latched(limit).is_some())
+                    .then(|| {
+                        board::trigger::fire().map(|edge_us| edge_us.saturating_sub(decided_us))
+                    })
+            };
+            events.track(monitor);
+            match learning {
+                Some(run) => run.push(monitor, reading.t_ms),
+                None => recent.push(monitor),
+            }
+            hours.update(monitor.run_state() != RunState::Stopped, reading.t_ms);
+            if hours.reminder(reading.t_ms) {
+                warn!(
+                    "MAINTENANCE DUE: {} running hours since the last service, every {} h, `service done` once it's done",
+                    hours.since_service_h(),
+                    hours.interval_h()
+                );
+                events.push(
+                    Event::new(board::time::uptime_s(), EventKind::MaintenanceDue)
+                        .with_values(hours.since_service_h(), hours.interval_h() as f32),
+                );
+            }
+            print_run_transition(monitor);
+            print_plausibility(monitor);
+            if monitor.plausibility() == Some(Plausibility::Diverged) {
+                let divergence = monitor.cross_check().divergence().unwrap_or(f32::NAN);
+                events.push(
+                    Event::new(board::time::uptime_s(), EventKind::Implausible)
+                        .with_values(divergence, CROSS_CHECK_MARGIN),
+                );
+            }
+            sampled = Some((reading, *monitor.filtered()));
+            summary.push(&reading);
+            let minute = trend.push(&reading, &trend_limits(monitor.thresholds()));
+            if let Some(bucket) = minute {
+                let ceiling = monitor.thresholds().temperature_ceiling.warning;
+                let health = score.close(ceiling - bucket.channels[4].max, &SCORE_WEIGHTS);
+                if *output == OutputMode::Human {
+                    info!(
+                        "Minute: {}, health {} ({})",
+                        bucket,
+                        health,
+                        ScoreBand::of(health).label()
+                    );
+                }
+            }
+            let running = monitor.run_state() != RunState::Stopped;
+            score.push(monitor.vibration_rms().filter(|_| running));
+            print_sample(*output, &reading, monitor, score.last());
+            // Only what the machine did, not a recording
+            #[cfg(feature = "sd-card")]
+            let sd_row = RowContext {
+                vibration_rms: monitor.vibration_rms(),
+                health: score.last(),
+            };
+            #[cfg(feature = "sd-card")]
+            if replayed.is_none() {
+                sd_log.sample(&reading, &sd_row);
+            }
+            latch.update(monitor, alert, now_ms);
+            if let Some(limit) = relay.update(monitor).unwrap() {
+                error!("RELAY TRIPPED: {} CRITICAL, MACHINE STOPPED", limit.name());
+            }
+            if *output == OutputMode::Binary {
+                send_reading(
+                    uart,
+                    &raw,
+                    sensor_config,
+                    monitor,
+                    relay.tripped().is_some(),
+                );
+            }
+
+            for limit in Limit::ALL {
+                match monitor.pending(limit) {
+                    Some((count, required)) if dump => {
+                        println!("{} violation {}/{}", limit.name(), count, required)
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(alert) = alert {
+                print_alarm(*output, &alert, monitor, &reading, &*pre_trigger);
+                #[cfg(feature = "daq-trigger")]
+                match triggered {
+                    Some(Some(latency_us)) => info!(
+                        "DAQ trigger: {} ms pulse, {} µs after the alarm was decided",
+                        TRIGGER_WIDTH_MS, latency_us
+                    ),
+                    Some(None) => warn!("WARNING: DAQ trigger still high from the last alarm"),
+                    None => {}
+                }
+                #[cfg(feature = "can")]
+                {
+                    let latched = Limit::ALL
+                        .into_iter()
+                        .filter(|limit| monitor.latched(*limit).is_some())
+                        .count();
+                    can.push(CanMessage::alarm(
+                        &alert,
+                        monitor.reading(alert.limit),
+                        latched as u8,
+                    ));
+                }
+                #[cfg(feature = "sd-card")]
+                if replayed.is_none() {
+                    sd_log.alarm(
+                        &alert,
+                        &reading,
+                        monitor.reading(alert.limit),
+                        monitor.threshold(alert.limit, alert.severity),
+                        &sd_row,
+                    );
+                }
+                score.alarm();
+                alarm.start(alert, now_ms).unwrap();
+                record.alarm(&alert, monitor.reading(alert.limit), reading.t_ms);
+                events.alarm(
+                    &alert,
+                    monitor.reading(alert.limit),
+                    monitor.threshold(alert.limit, alert.severity),
+                    board::time::uptime_s(),
+                );
+                board::record::store(record);
+
+                if post_trigger.open(reading.t_ms) {
+                    info!("Recording {} ms post-trigger", POST_TRIGGER_MS);
+                }
+            }
+
+            if dump {
+                match relay.tripped() {
+                    Some(limit) => println!("Relay: TRIPPED ({})", limit.name()),
+                    None => match relay.pending() {
+                        Some((count, required)) => {
+                            println!("Relay: RUN, trip {}/{}", count, required)
+                        }
+                        None => println!("Relay: RUN"),
+                    },
+                }
+
+                print_status(monitor);
+            }
+        }
+        None => {
+            // The next sample has nothing to compute the jerk against
+            monitor.sample_missed();
+
+            match bus_health.failure(now_ms) {
+                BusAction::Skip => warn!("WARNING: sensor read failed, sample skipped"),
+                // The I2C driver owns SCL, so a stuck slave can't be clocked
+                // out by hand. Re-initializing the MPU is what's left.
+                BusAction::Recover => {
+                    warn!("WARNING: sensor keeps failing, re-initializing it");
+                    reinit(mpu, *model, sensor_config, *motion_threshold, delay);
+                }
+                BusAction::Escalate => {
+                    error!("FAULT: {}", Fault::Bus.description());
+                    score.fault();
+                    events.push(Event::new(
+                        board::time::uptime_s(),
+                        EventKind::Fault(Fault::Bus),
+                    ));
+                    if !alarm.is_playing() {
+                        alarm.fault(Fault::Bus, now_ms).unwrap();
+                    }
+                }
+            }
+        }
+    }
+    *fast = Some(FastReading {
+        now_ms,
+        sampled: true,
+        readings: sampled,
+    });
+
+    // In the machine-readable modes every sample is there already
+    if *output == OutputMode::Human && summary.count() >= SUMMARY_SAMPLES {
+        info!("Summary: {}", summary);
+        summary.clear();
+        #[cfg(feature = "differential")]
+        if let Some(frame) = frame {
+            info!("Sensor skew: {}", frame.skew);
+            frame.skew.clear();
+        }
+        print_header(*output);
+    }
+    if dump {
+        println!(
+            "I2C errors: {} skipped, {} retries, {} recoveries",
+            bus_health.skipped, bus_health.retries, bus_health.recoveries
+        );
+        println!("Motion-triggered samples: {}", motion_trigger.triggered());
+        println!("---");
+    }
+}
+
+// Finds the sensor on the bus, checks and configures it.
+// Halts on a sensor that can't be trusted.
+#[cfg(not(any(feature = "lsm6ds3", feature = "sim")))]
+fn bring_up<'b, I, E, B, L>(
+    bus: &'b SharedBus<I>,
+    alarm: &mut Alarm<B, L>,
+    delay: &mut Delay,
+) -> (Mpu6050<BusProxy<'b, I>>, u8, Model, SensorConfig)
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    E: Debug,
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    // Look for the MPU6050 before talking to it. Swapped or loose wires can be
+    // fixed while this retries, without reflashing.
+    let address = loop {
+        let found = bus::scan(&mut bus.proxy());
+        for address in &found {
+            match bus::device_name(*address) {
+                Some(name) => println!("I2C device at 0x{:02x}: {}", address, name),
+                None => println!("I2C device at 0x{:02x}", address),
+            }
+        }
+        if let Some(address) = bus::mpu_address(&found) {
+            break address;
+        }
+
+        if found.is_empty() {
+            println!("No I2C devices found, check the wiring:");
+            println!("SDA on GPIO21, SCL on GPIO22, VCC on 3.3V, GND");
+        }
+        error!("FAULT: {}, retrying", Fault::NoDevice.description());
+        signal(alarm, delay, Fault::NoDevice, FAULT_REPEAT_MS);
+    };
+
+    // Initialize MPU6050 module, after checking what is on the bus
+    let mut mpu = Mpu6050::new_with_addr(bus.proxy(), address);
+    let id = sensor::who_am_i(&mut mpu).expect("Error while reading WHO_AM_I");
+    let Some(model) = Model::from_who_am_i(id) else {
+        println!(
+            "WHO_AM_I: 0x{:02x}, expected 0x{:02x}",
+            id,
+            sensor::EXPECTED_WHO_AM_I
+        );
+        halt(alarm, delay, Fault::UnknownSensor);
+    };
+    println!("Sensor: {} (WHO_AM_I 0x{:02x})", model.name(), id);
+    if model != Model::Mpu6050 {
+        println!("Note: not an MPU6050, temperature readings may be off");
+    }
+    sensor::init(&mut mpu, model, delay).expect("Error while initializing MPU6050");
+
+    // Make sure the MEMS structure wasn't damaged before trusting it
+    if model == Model::Mpu6050 {
+        let self_test =
+            sensor::self_test(&mut mpu, delay).expect("Error while running the self-test");
+        let (accel, gyro) = (self_test.accel, self_test.gyro);
+        println!("Self-test accel: {}% {}% {}%", accel[0], accel[1], accel[2]);
+        println!("Self-test gyro: {}% {}% {}%", gyro[0], gyro[1], gyro[2]);
+        if !self_test.passed() {
+            halt(alarm, delay, Fault::SelfTest);
+        }
+        println!("self-test OK");
+    } else {
+        println!("Self-test skipped, only supported on the MPU6050");
+    }
+
+    let sensor_config = SensorConfig::default();
+    sensor_config
+        .apply(&mut mpu)
+        .expect("Error while configuring MPU6050");
+    println!(
+        "Accelerometer range: ±{} g",
+        sensor_config.accel_full_scale_g()
+    );
+    println!(
+        "Gyroscope range: ±{} º/s",
+        sensor_config.gyro_full_scale_dps()
+    );
+    println!("Low-pass filter: {} Hz", sensor_config.dlpf.bandwidth_hz());
+    match sensor_config.power {
+        PowerProfile::Continuous => println!("Sensor power: continuous"),
+        PowerProfile::LowPower(rate) => println!(
+            "Sensor power: accelerometer cycling at {} Hz between samples, no fast reads",
+            rate.hz()
+        ),
+    }
+
+    (mpu, address, model, sensor_config)
+}
+
+// The same for an LSM6DS3, which has no self-test procedure here
+#[cfg(feature = "lsm6ds3")]
+fn bring_up<'b, I, E, B, L>(
+    bus: &'b SharedBus<I>,
+    alarm: &mut Alarm<B, L>,
+    delay: &mut Delay,
+) -> (Lsm6ds3<BusProxy<'b, I>>, u8, Model, SensorConfig)
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    E: Debug,
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    let address = loop {
+        let found = bus::scan(&mut bus.proxy());
+        for address in &found {
+            match bus::device_name(*address) {
+                Some(name) => println!("I2C device at 0x{:02x}: {}", address, name),
+                None => println!("I2C device at 0x{:02x}", address),
+            }
+        }
+        if let Some(address) = lsm6ds3::address(&found) {
+            break address;
+        }
+
+        if found.is_empty() {
+            println!("No I2C devices found, check the wiring:");
+            println!("SDA on GPIO21, SCL on GPIO22, VCC on 3.3V, GND");
+        }
+        error!("FAULT: {}, retrying", Fault::NoDevice.description());
+        signal(alarm, delay, Fault::NoDevice, FAULT_REPEAT_MS);
+    };
+
+    let mut imu = Lsm6ds3::new(bus.proxy(), address);
+    let sensor_config = SensorConfig::default();
+    match imu.init(delay).and_then(|_| imu.configure(&sensor_config)) {
+        Ok(()) => println!("Sensor: LSM6DS3 at 0x{:02x}", address),
+        Err(lsm6ds3::Lsm6ds3Error::UnknownId(id)) => {
+            println!("WHO_AM_I: 0x{:02x}, not an LSM6DS3", id);
+            halt(alarm, delay, Fault::UnknownSensor);
+        }
+        Err(error) => panic!("Error while initializing the LSM6DS3: {:?}", error),
+    }
+    println!("Self-test skipped, only supported on the MPU6050");
+    println!(
+        "Accelerometer range: ±{} g",
+        sensor_config.accel_full_scale_g()
+    );
+    println!(
+        "Gyroscope range: ±{} º/s",
+        sensor_config.gyro_full_scale_dps()
+    );
+    println!("Sensor power: continuous");
+
+    (imu, address, Model::Lsm6ds3, sensor_config)
+}
+
+// No sensor at all, the simulator plays the quiet machine until the boot
+// calibration is over. The display can still go on the bus.
+#[cfg(feature = "sim")]
+fn bring_up<I, B, L>(
+    _bus: &SharedBus<I>,
+    _alarm: &mut Alarm<B, L>,
+    _delay: &mut Delay,
+) -> (Simulator, u8, Model, SensorConfig) {
+    let simulator = Simulator::new(0, SIM_SEED, board::time::uptime_ms);
+    println!("Sensor: simulator, synthetic data, `sim` to pick a scenario");
+    (simulator, 0, Model::Simulator, SensorConfig::default())
+}
+
+#[cfg(feature = "sim")]
+fn print_sim(simulator: &Simulator) {
+    let scenario = simulator.scenario();
+    println!(
+        "Simulator: `{}`, step {} of {}, seed {}",
+        scenario.name,
+        simulator.step() + 1,
+        scenario.steps.len(),
+        simulator.seed()
+    );
+}
+
+// After a deep sleep: the MPU6050 kept its configuration through its own
+// low-power mode, only the driver's scaling needs setting again. The
+// motion threshold is set back with the interrupt.
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+fn wake_up<'b, I, E>(
+    bus: &'b SharedBus<I>,
+    resume: &board::sleep::Resume,
+    delay: &mut Delay,
+) -> Mpu6050<BusProxy<'b, I>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    E: Debug,
+{
+    let mut mpu = Mpu6050::new_with_addr(bus.proxy(), resume.address);
+    sensor::full_power(&mut mpu).expect("Error while waking up the MPU6050");
+    resume
+        .sensor_config
+        .apply(&mut mpu)
+        .expect("Error while configuring MPU6050");
+    delay.delay_ms(sensor::WAKE_SETTLE_MS);
+    mpu
+}
+
+// The second MPU6050, on the frame, configured like the machine's. None,
+// with a log line, for single-sensor mode.
+#[cfg(feature = "differential")]
+fn bring_up_frame<'b, I, E>(
+    bus: &'b SharedBus<I>,
+    machine_address: u8,
+    sensor_config: &SensorConfig,
+    delay: &mut Delay,
+) -> Option<Mpu6050<BusProxy<'b, I>>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    if machine_address == FRAME_ADDRESS {
+        println!(
+            "Differential: the machine sensor is on 0x{:02x}, single-sensor mode",
+            FRAME_ADDRESS
+        );
+        return None;
+    }
+    let mut mpu = Mpu6050::new_with_addr(bus.proxy(), FRAME_ADDRESS);
+    let Some(model) = sensor::who_am_i(&mut mpu)
+        .ok()
+        .and_then(Model::from_who_am_i)
+    else {
+        println!(
+            "Differential: no sensor at 0x{:02x}, single-sensor mode",
+            FRAME_ADDRESS
+        );
+        return None;
+    };
+    let configured =
+        sensor::init(&mut mpu, model, delay).and_then(|_| sensor_config.apply(&mut mpu));
+    if configured.is_err() {
+        println!(
+            "Differential: {} at 0x{:02x} failed to initialize, single-sensor mode",
+            model.name(),
+            FRAME_ADDRESS
+        );
+        return None;
+    }
+    println!(
+        "Differential: {} at 0x{:02x} on the frame",
+        model.name(),
+        FRAME_ADDRESS
+    );
+    Some(mpu)
+}
+
+// The frame sensor's rest vector, with the machine still too
+#[cfg(feature = "differential")]
+fn calibrate_frame<I, E, B, L>(
+    mpu: &mut Mpu6050<I>,
+    alarm: &mut Alarm<B, L>,
+    delay: &mut Delay,
+    health: &mut BusHealth,
+    feed: &mut impl FnMut(),
+) -> Differential
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    println!("Frame sensor:");
+    let baseline = calibrate(mpu, alarm, delay, health, feed);
+    if !baseline.is_steady() {
+        warn!("WARNING: frame not steady, the differential may be off");
+    }
+    Differential::new(baseline.acc_mean(), baseline.gyro_mean())
+}
+
+// The devices on the 1-Wire bus, and the first DS18B20 among them. None,
+// with a log line, without one: the channel stays off.
+#[cfg(feature = "ds18b20")]
+fn bring_up_probe(
+    mut bus: board::onewire::OneWirePin,
+) -> Option<(board::onewire::OneWirePin, Ds18b20)> {
+    let roms = onewire::enumerate(&mut bus);
+    for rom in &roms {
+        println!("1-Wire: {} (family 0x{:02x})", rom, rom.family());
+    }
+    match roms.iter().find(|rom| rom.family() == ds18b20::FAMILY) {
+        Some(rom) => {
+            println!("Probe: DS18B20 {} on GPIO27", rom);
+            Some((bus, Ds18b20::new(*rom)))
+        }
+        None => {
+            println!("Probe: no DS18B20 on GPIO27, temperature channel disabled");
+            None
+        }
+    }
+}
+
+// The knock sensor with its bias calibrated out. None, with a log line, if
+// the input reads a rail: nothing connected, or the divider is off.
+#[cfg(feature = "knock")]
+fn bring_up_knock(
+    adc: &mut board::adc::Adc1<'_>,
+    mut knock: board::knock::KnockSensor,
+) -> Option<board::knock::KnockSensor> {
+    match knock.calibrate(adc) {
+        Some(bias) => {
+            println!("Knock: GPIO34, bias {} counts", bias);
+            Some(knock)
+        }
+        None => {
+            println!("Knock: GPIO34 at a rail, channel disabled");
+            None
+        }
+    }
+}
+
+// The current sensor with its zero taken, the motor must be off. A zero
+// that fails leaves the datasheet's.
+#[cfg(feature = "current")]
+fn bring_up_current(
+    adc: &mut board::adc::Adc1<'_>,
+    mut current: board::current::CurrentSensor,
+) -> board::current::CurrentSensor {
+    println!("Current: ACS712 on GPIO35, {} mV/A", ACS712_MV_PER_A);
+    zero_current(adc, &mut current);
+    current
+}
+
+#[cfg(feature = "current")]
+fn zero_current(adc: &mut board::adc::Adc1<'_>, current: &mut board::current::CurrentSensor) {
+    match current.zero(adc) {
+        Some(zero) => println!("Current: zero at {} counts", zero),
+        None => warn!("WARNING: current zero failed, windows cut short"),
+    }
+}
+
+// The sensor on the frame and what the machine's readings lose of its motion
+#[cfg(feature = "differential")]
+struct FrameSensor<I> {
+    mpu: Mpu6050<I>,
+    differential: Differential,
+    skew: Skew,
+}
+
+#[cfg(feature = "differential")]
+impl<I, E> FrameSensor<I>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    // The machine's calibrated reading minus the frame's motion, read right
+    // after it. `machine_us` is when the machine's read started. A frame
+    // read that fails leaves the machine's reading as it is.
+    fn subtract(
+        &mut self,
+        machine: Reading,
+        machine_us: u64,
+        delay: &mut Delay,
+        health: &mut BusHealth,
+    ) -> Reading {
+        let frame_us = board::time::awake_us();
+        let Some(raw) = read_sample(&mut self.mpu, delay, health) else {
+            warn!("WARNING: frame sensor read failed, sample not differential");
+            return machine;
+        };
+        self.skew
+            .push(frame_us.saturating_sub(machine_us).min(u32::MAX as u64) as u32);
+        self.differential.apply(&machine, &raw)
+    }
+}
+
+// Levels, gyroscope bias and DAC mapping saved from the console, if any,
+// and the monitor built with them. False when nothing was ever saved.
+#[cfg(not(feature = "embassy"))]
+fn load_settings() -> (MaintenanceMonitor, GyroBias, u32, DacMapping, bool) {
+    let loaded = board::flash::load();
+    let saved = !matches!(loaded, Err(SettingsError::Blank));
+    let settings = match loaded {
+        Ok(settings) => {
+            println!("Settings: loaded from flash");
+            settings
+        }
+        Err(error) => {
+            println!("Settings: compiled-in defaults ({})", error.description());
+            Settings::defaults()
+        }
+    };
+    let mut monitor = MaintenanceMonitor::new(Thresholds::default(), settings.sample_period_ms);
+    let mut gyro_bias = GyroBias::default();
+    settings.apply(&mut monitor, &mut gyro_bias);
+    for setting in Setting::ALL {
+        print_setting(setting, monitor.thresholds());
+    }
+    (
+        monitor,
+        gyro_bias,
+        settings.sample_period_ms,
+        settings.dac,
+        saved,
+    )
+}
+
+// Reads every channel in one go, retrying a few times.
+// Still with the gyroscope bias, as read. None if the sensor doesn't answer.
+fn read_sample<S: ImuSensor>(
+    imu: &mut S,
+    delay: &mut Delay,
+    health: &mut BusHealth,
+) -> Option<Reading> {
+    for attempt in 0..bus::READ_ATTEMPTS {
+        if attempt > 0 {
+            delay.delay_ms(bus::RETRY_DELAY_MS);
+        }
+
+        if let Ok(sample) = imu.read(board::time::uptime_ms()) {
+            if attempt > 0 {
+                health.retried(attempt);
+            }
+            return Some(sample);
+        }
+    }
+
+    None
+}
+
+// Averages a couple of seconds of samples, extended while the machine
+// vibrates. `feed` is called on every sample so a running watchdog doesn't
+// bite meanwhile.
+fn calibrate<S, B, L>(
+    imu: &mut S,
+    alarm: &mut Alarm<B, L>,
+    delay: &mut Delay,
+    health: &mut BusHealth,
+    feed: &mut impl FnMut(),
+) -> Baseline
+where
+    S: ImuSensor,
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    println!("Calibrating, keep the machine still...");
+    alarm.set_heartbeat(false).unwrap();
+    let mut calibration: Calibration = Calibration::new();
+    let mut retries = 0;
+    let baseline = loop {
+        let mut led_on = false;
+        calibration.clear();
+        while !calibration.is_full() {
+            // A failed read only costs its sample
+            if let Some(raw) = read_sample(imu, delay, health) {
+                calibration.push(raw.acc, raw.gyro, raw.temp);
+            }
+
+            led_on = !led_on;
+            alarm.set_indicator(led_on).unwrap();
+            feed();
+            delay.delay_ms(CALIBRATION_PERIOD_MS);
+        }
+
+        let baseline = calibration.baseline();
+        if baseline.is_steady() || retries == CALIBRATION_RETRIES {
+            break baseline;
+        }
+        retries += 1;
+        warn!("WARNING: vibration during calibration, extending it");
+    };
+    alarm.set_indicator(false).unwrap();
+
+    baseline
+}
+
+// Takes a calibration as the new references and gyroscope bias
+fn adopt(baseline: &Baseline, monitor: &mut MaintenanceMonitor, gyro_bias: &mut GyroBias) {
+    print_baseline(baseline);
+    if !baseline.is_steady() {
+        warn!("WARNING: machine not steady, the references may be off");
+    }
+
+    // Subtracted from every reading from here on
+    if !gyro_bias.update(baseline) {
+        warn!("WARNING: gyroscope moving during calibration, bias not updated");
+    }
+    let bias = gyro_bias.offsets();
+    println!("Gyro bias: {} {} {} rad/s", bias[0], bias[1], bias[2]);
+
+    monitor.set_reference(
+        baseline.acc_mean(),
+        gyro_bias.apply(baseline.gyro_mean()),
+        baseline.temp.mean,
+    );
+}
+
+// Back-to-back reads after a motion interrupt, into the peak deltas.
+// A failed read ends the burst, the sample that follows retries anyway.
+#[cfg(not(feature = "embassy"))]
+fn motion_burst<S: ImuSensor>(
+    imu: &mut S,
+    delay: &mut Delay,
+    health: &mut BusHealth,
+    gyro_bias: &GyroBias,
+    monitor: &mut MaintenanceMonitor,
+) {
+    for _ in 0..MOTION_BURST_SAMPLES {
+        let Some(raw) = read_sample(imu, delay, health) else {
+            break;
+        };
+        let filtered = monitor.filter(&calibrated(&raw, gyro_bias));
+        monitor.hold_peak(filtered.acc);
+    }
+}
+
+// Rotation without the bias
+fn calibrated(raw: &Reading, gyro_bias: &GyroBias) -> Reading {
+    Reading {
+        gyro: gyro_bias.apply(raw.gyro),
+        ..*raw
+    }
+}
+
+// Progress of the spectrum burst through the sensor FIFO
+#[cfg(feature = "spectrum")]
+struct SpectrumBurst {
+    spectrum: Spectrum,
+    // Filled by the sensor FIFO at a fixed rate, so the loop's own timing
+    // doesn't matter as long as it drains the FIFO before it overflows
+    format: FifoFormat,
+    rate_hz: f32,
+    buffer: [u8; FIFO_BURST_BYTES],
+    started_ms: Option<u32>,
+}
+
+#[cfg(feature = "spectrum")]
+impl SpectrumBurst {
+    fn new(config: &SensorConfig) -> Self {
+        Self {
+            spectrum: Spectrum::new(),
+            format: FifoFormat::new(config, false),
+            rate_hz: fifo::sample_rate_hz(config, FIFO_RATE_DIVIDER),
+            buffer: [0; FIFO_BURST_BYTES],
+            started_ms: None,
+        }
+    }
+}
+
+#[cfg(feature = "spectrum")]
+enum FifoDrain {
+    Filling,
+    Full,
+    // The buffered samples are lost, the burst has to start over
+    Overflowed,
+}
+
+// Empties the FIFO and the spectrum and bearing envelope buffers
+#[cfg(feature = "spectrum")]
+fn start_burst<I, E>(
+    mpu: &mut Mpu6050<I>,
+    spectrum: &mut Spectrum,
+    monitor: &mut MaintenanceMonitor,
+) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    spectrum.clear();
+    monitor.start_envelope();
+    sensor::start_fifo(mpu, false, FIFO_RATE_DIVIDER)
+}
+
+// Reads up to one buffer of whole frames from the FIFO into the spectrum
+// and the bearing envelope
+#[cfg(feature = "spectrum")]
+fn drain_fifo<I, E>(
+    mpu: &mut Mpu6050<I>,
+    format: &FifoFormat,
+    buffer: &mut [u8],
+    spectrum: &mut Spectrum,
+    monitor: &mut MaintenanceMonitor,
+) -> Result<FifoDrain, Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    let count = sensor::fifo_count(mpu)?;
+    if fifo::overflowed(count, sensor::fifo_overflowed(mpu)?) {
+        sensor::reset_fifo(mpu)?;
+        return Ok(FifoDrain::Overflowed);
+    }
+
+    let len = format.burst_len(count, buffer.len());
+    sensor::read_fifo(mpu, &mut buffer[..len])?;
+    for frame in format.frames(&buffer[..len]) {
+        let acc = sensor::to_ms2(frame.acc);
+        monitor.push_envelope(acc);
+        if spectrum.push(acc) {
+            return Ok(FifoDrain::Full);
+        }
+    }
+    Ok(FifoDrain::Filling)
+}
+
+#[cfg(feature = "spectrum")]
+fn print_spectrum(spectrum: &Spectrum, rate_hz: f32) {
+    info!("Spectrum at {} Hz:", rate_hz);
+    for peak in spectrum.peaks(rate_hz).iter().flatten().flatten() {
+        info!("{} Hz: {} m/s^2", peak.frequency_hz, peak.amplitude);
+    }
+}
+
+#[cfg(not(any(feature = "embassy", feature = "lsm6ds3", feature = "sim")))]
+fn reinit<I, E>(
+    mpu: &mut Mpu6050<I>,
+    model: Model,
+    config: &SensorConfig,
+    motion_threshold: u8,
+    delay: &mut Delay,
+) where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    let recovered = sensor::init(mpu, model, delay)
+        .and_then(|_| config.apply(mpu))
+        .and_then(|_| sensor::enable_motion_interrupt(mpu, motion_threshold, MOTION_DURATION_MS));
+    if recovered.is_err() {
+        warn!("WARNING: sensor re-initialization failed");
+    }
+}
+
+// Same without the motion interrupt, which isn't set up on the LSM6DS3
+#[cfg(feature = "lsm6ds3")]
+fn reinit<I, E>(
+    imu: &mut Lsm6ds3<I>,
+    _model: Model,
+    config: &SensorConfig,
+    _motion_threshold: u8,
+    delay: &mut Delay,
+) where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    if imu.init(delay).and_then(|_| imu.configure(config)).is_err() {
+        warn!("WARNING: sensor re-initialization failed");
+    }
+}
+
+// Nothing to recover, the simulator never gets stuck
+#[cfg(feature = "sim")]
+fn reinit(
+    _imu: &mut Simulator,
+    _model: Model,
+    _config: &SensorConfig,
+    _motion_threshold: u8,
+    _delay: &mut Delay,
+) {
+}
+
+// Never returns: reports the fault and repeats its pattern until reset.
+// The relay is left alone, the machine is still allowed to run.
+#[cfg(not(feature = "sim"))]
+fn halt<B, L>(alarm: &mut Alarm<B, L>, delay: &mut Delay, fault: Fault) -> !
+where
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    error!("FAULT: {}, monitoring stopped", fault.description());
+
+    loop {
+        signal(alarm, delay, fault, FAULT_REPEAT_MS);
+    }
+}
+
+// Plays a fault pattern, blocking for duration_ms
+#[cfg(not(feature = "sim"))]
+fn signal<B, L>(alarm: &mut Alarm<B, L>, delay: &mut Delay, fault: Fault, duration_ms: u32)
+where
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    alarm.fault(fault, 0).unwrap();
+
+    let mut now_ms = 0;
+    while now_ms < duration_ms {
+        alarm.tick(now_ms).unwrap();
+
+        delay.delay_ms(TICK_MS);
+        now_ms += TICK_MS;
+    }
+    alarm.silence().unwrap();
+}
+
+// Triple chirp after a watchdog or panic reset, for whoever is on site.
+// Blocks until it's played.
+fn warn_reset<B, L>(alarm: &mut Alarm<B, L>, delay: &mut Delay)
+where
+    B: Buzzer,
+    B::Error: Debug,
+    L: OutputPin,
+    L::Error: Debug,
+{
+    alarm.chirps(Limit::Mechanical, RESET_CHIRPS, 0).unwrap();
+
+    let mut now_ms = 0;
+    while alarm.is_playing() {
+        delay.delay_ms(TICK_MS);
+        now_ms += TICK_MS;
+        alarm.tick(now_ms).unwrap();
+    }
+}
+
+// Boot banner line, with the boot count when the counters are kept
+fn print_boot(boot: &board::diagnostics::Boot, boot_count: Option<u32>) {
+    let code = boot.code.map_or("unknown code", diagnostics::code_name);
+    match boot_count {
+        Some(count) => println!(
+            "Boot {}, reset reason: {} ({})",
+            count,
+            boot.cause.description(),
+            code
+        ),
+        None => println!("Reset reason: {} ({})", boot.cause.description(), code),
+    }
+    if let Some(message) = &boot.panic {
+        println!("Panic: {}", message.as_str());
+    }
+    if boot.cause == ResetCause::DeepSleep {
+        println!("Wake-up cause: {:?}", boot.wakeup);
+    }
+    if boot.cause.is_abnormal() {
+        warn!("WARNING: abnormal reset, the monitor had stopped working");
+    }
+}
+
+#[cfg(not(feature = "embassy"))]
+fn print_record(record: &EventRecord) {
+    println!(
+        "Boot {}, alarms: {} mechanical, {} temperature",
+        record.boot_count, record.mechanical_alarms, record.temperature_alarms
+    );
+    println!(
+        "Resets: {} watchdog, {} panic",
+        record.watchdog_resets, record.panics
+    );
+    match record.last_alarm {
+        Some(last) => println!(
+            "Last alarm: {} {} on boot {} at {}, peak {}",
+            last.limit.name(),
+            last.severity.label(),
+            last.boot,
+            Timestamp(last.uptime_ms),
+            last.peak
+        ),
+        None => println!("Last alarm: none"),
+    }
+}
+
+// Boot banner for reading the LED without a serial console
+#[cfg(not(feature = "embassy"))]
+fn print_led_patterns() {
+    println!("LED patterns:");
+    println!("Healthy: {}", Health::Healthy.description());
+    println!("Sensor fault: {}", Health::SensorFault.description());
+    println!("Maintenance due: {}", Health::MaintenanceDue.description());
+    println!("Alarm: blinks per limit, steady until acknowledged");
+}
+
+// What the detectors saw of the last sample
+fn print_filtered(monitor: &MaintenanceMonitor) {
+    let filtered = monitor.filtered().acc;
+    println!(
+        "Filtered: {} {} {} m/s^2",
+        filtered[0], filtered[1], filtered[2]
+    );
+    let peak = monitor.acc_peak().axes();
+    println!(
+        "Peak delta: {} {} {} m/s^2 over {} samples",
+        peak[0],
+        peak[1],
+        peak[2],
+        monitor.acc_peak().samples()
+    );
+}
+
+// Everything the monitor derives from the readings
+fn print_status(monitor: &MaintenanceMonitor) {
+    let orientation = monitor.orientation();
+    println!(
+        "Pitch: {} º, roll: {} º",
+        orientation.pitch, orientation.roll
+    );
+    match monitor.jerk() {
+        Some(jerk) => println!("Jerk: {} m/s^3", jerk),
+        None => println!("Jerk: n/a"),
+    }
+    let cross_check = monitor.cross_check();
+    if let (Some(chip_temp), Some(divergence)) = (monitor.chip_temp(), cross_check.divergence()) {
+        let verdict = if cross_check.is_diverged() {
+            ", implausible"
+        } else {
+            ""
+        };
+        println!(
+            "Chip temperature: {} ºC, the MPU {} ºC off it since boot{}",
+            chip_temp, divergence, verdict
+        );
+    }
+    if let Some(minutes) = monitor.minutes_to_ceiling() {
+        println!("Temperature ceiling in ~{} min at this rate", minutes);
+    }
+    if let (Some(temp), Some(rise)) = (monitor.probe(), monitor.probe_rise()) {
+        println!("Probe: {} ºC, {} ºC over its reference", temp, rise);
+    }
+    if let Some(knock) = monitor.knock() {
+        println!("Knock: peak {}, RMS {} counts", knock.peak, knock.rms);
+    }
+    if let Some(amps) = monitor.current() {
+        println!("Current: {} A RMS", amps);
+    }
+    match (monitor.is_transient(), monitor.thresholds().transient_scale) {
+        (true, Some(scale)) => println!(
+            "Run state: {}, motion levels x{}",
+            monitor.run_state().name(),
+            scale
+        ),
+        (true, None) => println!(
+            "Run state: {}, motion limits off",
+            monitor.run_state().name()
+        ),
+        (false, _) => println!("Run state: {}", monitor.run_state().name()),
+    }
+    if let Some(rpm) = monitor.speed() {
+        match monitor.speed_scale() {
+            Some(scale) => println!("Speed: {} RPM, vibration levels x{}", rpm, scale),
+            None => println!("Speed: {} RPM, vibration limits off", rpm),
+        }
+    }
+    match monitor.vibration_rms() {
+        Some(rms) => println!("Vibration RMS: {} m/s^2", rms),
+        None => println!("Vibration RMS: n/a"),
+    }
+    match (monitor.velocity_rms(), monitor.velocity_zone()) {
+        (Some(rms), Some(zone)) => {
+            println!("Velocity RMS: {} mm/s, zone {}", rms, zone.name())
+        }
+        _ => println!("Velocity RMS: n/a"),
+    }
+    if let (Some(rms), Some(crest)) = (monitor.envelope_rms(), monitor.crest_factor()) {
+        println!("Envelope RMS: {} m/s^2, crest factor {}", rms, crest);
+    }
+    print_stats("|a|", "m/s^2", monitor.acc_stats(), monitor.acc_z());
+    print_stats("Temperature", "ºC", monitor.temp_stats(), monitor.temp_z());
+}
+
+#[cfg(not(feature = "embassy"))]
+fn print_setting(setting: Setting, thresholds: &Thresholds) {
+    let levels = setting.levels(thresholds);
+    println!(
+        "{}: warning {} {}, critical {} {}",
+        setting.name(),
+        levels.warning,
+        setting.unit(),
+        levels.critical,
+        setting.unit()
+    );
+}
+
+// What a commissioning run saw, and the levels it comes up with
+#[cfg(not(feature = "embassy"))]
+fn print_proposal(proposal: &Commissioning, thresholds: &Thresholds) {
+    for quantity in Quantity::ALL {
+        let stats = proposal.distribution(quantity);
+        println!(
+            "Learned {}: mean {} {}, std dev {}, {} samples",
+            quantity.name(),
+            stats.mean,
+            quantity.unit(),
+            stats.std_dev,
+            stats.samples
+        );
+    }
+    for setting in Setting::ALL {
+        let now = setting.levels(thresholds).warning;
+        match proposal.proposal(setting) {
+            Some(warning) => println!(
+                "Proposed {} warning: {} {}, now {}",
+                setting.name(),
+                warning,
+                setting.unit(),
+                now
+            ),
+            None => println!(
+                "Proposed {} warning: none, too few samples, stays at {}",
+                setting.name(),
+                now
+            ),
+        }
+    }
+}
+
+// The run under way or waiting for an answer, then how far the machine has
+// moved from its commissioning baseline
+#[cfg(not(feature = "embassy"))]
+fn print_learning<B>(context: &Context<'_, B>) {
+    if let Some(run) = &context.learning {
+        println!("Learning: {} s left", run.remaining_ms() / 1_000);
+    }
+    if let Some(proposal) = &context.proposal {
+        print_proposal(proposal, context.monitor.thresholds());
+        println!("Learning: `learn accept` or `learn discard`");
+    }
+    let Some(baseline) = &context.baseline else {
+        println!("Baseline: none, `learn start` to commission the machine");
+        return;
+    };
+    for quantity in Quantity::ALL {
+        let stats = baseline.distribution(quantity);
+        match baseline.drift(&context.recent, quantity) {
+            Some(drift) => println!(
+                "Drift {}: {} sigma, now {} against {} ± {} {}",
+                quantity.name(),
+                drift,
+                context.recent.distribution(quantity).mean,
+                stats.mean,
+                stats.std_dev,
+                quantity.unit()
+            ),
+            None => println!("Drift {}: n/a, too few samples", quantity.name()),
+        }
+    }
+}
+
+// Worst case per task since boot, reported with the tick rate
+#[cfg(not(feature = "embassy"))]
+fn print_tasks<C>(scheduler: &Scheduler<C>) {
+    for task in scheduler.tasks() {
+        println!(
+            "Task {}: longest {} ms of {} ms, {} overruns",
+            task.name(),
+            task.longest_ms(),
+            task.period_ms(),
+            task.overruns()
+        );
+    }
+    println!("Ticks over {} ms: {}", TICK_MS, scheduler.tick_overruns());
+}
+
+fn print_reading(reading: &Reading) {
+    let Reading {
+        acc, gyro, temp, ..
+    } = reading;
+
+    println!("Accelerometer:");
+    println!("Ax: {} m/s^2", acc[0]);
+    println!("Ay: {} m/s^2", acc[1]);
+    println!("Az: {} m/s^2", acc[2]);
+
+    println!("Gyroscope:");
+    println!("Gx: {} rad/s", gyro[0]);
+    println!("Gy: {} rad/s", gyro[1]);
+    println!("Gz: {} rad/s", gyro[2]);
+
+    println!("Temperature:");
+    println!("{} ºC", temp);
+}
+
+// The JSON and CSV lines go out without the timestamp prefix, they carry
+// the reading's own
+fn print_sample(
+    mode: OutputMode,
+    reading: &Reading,
+    monitor: &MaintenanceMonitor,
+    health: Option<u8>,
+) {
+    let alarm = Limit::ALL
+        .into_iter()
+        .find(|limit| monitor.latched(*limit).is_some());
+    match mode {
+        OutputMode::Human => {
+            let row = StatusRow {
+                reading,
+                monitor,
+                color: board::log::color(),
+            };
+            info!("{}", row);
+            if board::log::enabled(Level::Debug) {
+                print_reading(reading);
+                print_filtered(monitor);
+            }
+        }
+        OutputMode::Json => data_println!(
+            "{}",
+            JsonLine {
+                reading,
+                chip_temp: monitor.chip_temp(),
+                health,
+                alarm,
+            }
+        ),
+        OutputMode::Csv => data_println!(
+            "{}",
+            CsvRow {
+                reading,
+                chip_temp: monitor.chip_temp(),
+                health,
+                alarm,
+                decimals: config::OUTPUT.decimals,
+            }
+        ),
+        // Framed from the raw counts, see `send_reading()`
+        OutputMode::Binary => {}
+    }
+}
+
+// In CSV and binary the alarm column or flags are all there is, anything
+// else would break the rows
+fn print_alarm<const N: usize>(
+    mode: OutputMode,
+    alert: &Alert,
+    monitor: &MaintenanceMonitor,
+    reading: &Reading,
+    pre_trigger: &Capture<N>,
+) {
+    match mode {
+        OutputMode::Human => {
+            let level = match alert.severity {
+                Severity::Warning => Level::Warn,
+                Severity::Critical => Level::Error,
+            };
+            if board::log::enabled(level) {
+                print_capture("Pre-trigger", pre_trigger, reading.t_ms);
+                print_alert(alert, monitor, reading);
+            }
+        }
+        OutputMode::Json => {
+            print_capture("Pre-trigger", pre_trigger, reading.t_ms);
+            let event = JsonEvent {
+                alert,
+                t_ms: reading.t_ms,
+                value: monitor.reading(alert.limit),
+            };
+            data_println!("{}", event);
+        }
+        OutputMode::Csv | OutputMode::Binary => {}
+    }
+}
+
+fn print_header(mode: OutputMode) {
+    match mode {
+        OutputMode::Human => info!("{}", StatusHeader),
+        OutputMode::Csv => data_println!("{}", CSV_COLUMNS),
+        OutputMode::Json | OutputMode::Binary => {}
+    }
+}
+
+// Frames go to the UART as they are, without esp-println's line handling
+#[cfg(not(feature = "embassy"))]
+fn send_reading(
+    uart: &mut Uart<'_, UART0>,
+    raw: &Reading,
+    sensor_config: &SensorConfig,
+    monitor: &MaintenanceMonitor,
+    relay_tripped: bool,
+) {
+    if board::log::paused() {
+        return;
+    }
+    let alarm = Limit::ALL
+        .into_iter()
+        .filter_map(|limit| monitor.latched(limit))
+        .max();
+    let clipped = sensor_config.clipped_acc(raw.acc).next().is_some()
+        || sensor_config.clipped_gyro(raw.gyro).next().is_some();
+    let frame = ReadingFrame::new(
+        raw,
+        sensor_config,
+        ReadingFrame::flags(alarm, relay_tripped, clipped),
+    );
+
+    let mut bytes = [0; frame::READING_ENCODED_LEN];
+    if let Some(len) = frame::encode(&frame.pack(), &mut bytes) {
+        uart.write_bytes(&bytes[..len]).ok();
+    }
+}
+
+#[cfg(not(feature = "embassy"))]
+fn send_banner(uart: &mut Uart<'_, UART0>, sensor_config: &SensorConfig) {
+    if board::log::paused() {
+        return;
+    }
+    let mut bytes = [0; frame::BANNER_ENCODED_LEN];
+    if let Some(len) = frame::encode(&frame::banner(sensor_config), &mut bytes) {
+        uart.write_bytes(&bytes[..len]).ok();
+    }
+}
+
+fn print_capture<const N: usize>(label: &str, capture: &Capture<N>, origin_ms: u64) {
+    println!("{} capture, {} samples:", label, capture.len());
+    println!("{}", CSV_HEADER);
+    for reading in capture.iter() {
+        println!("{}", CsvLine { reading, origin_ms });
+    }
+}
+
+fn print_alert(alert: &Alert, monitor: &MaintenanceMonitor, reading: &Reading) {
+    let label = alert.severity.label();
+    let Reading {
+        acc, gyro, temp, ..
+    } = reading;
+
+    match alert.limit {
+        Limit::SensorDetached => {
+            banner!("{}: SENSOR DETACHED FROM ITS MOUNT", label);
+            println!("Current: {} {} {}", acc[0], acc[1], acc[2]);
+            println!("Deviation: {} º", monitor.reading(Limit::SensorDetached));
+            println!("Motion alarms suppressed, remount it and press the button");
+        }
+        Limit::Mechanical => {
+            let (reference, delta) = (monitor.acc_reference(), monitor.acc_delta());
+
+            banner!("{}: MECHANICAL STRESS DETECTED!", label);
+            for axis in monitor.tripped_axes() {
+                println!("Axis: {}", axis.name());
+            }
+            let measured = monitor.acc_measurement();
+            println!("Current: {} {} {}", acc[0], acc[1], acc[2]);
+            println!("Measured: {} {} {}", measured[0], measured[1], measured[2]);
+            println!(
+                "Reference: {} {} {}",
+                reference[0], reference[1], reference[2]
+            );
+            println!("Delta: {} {} {}", delta[0], delta[1], delta[2]);
+            println!("Magnitude: {}", monitor.acc_magnitude());
+        }
+        Limit::Rotational => {
+            let (reference, delta) = (monitor.gyro_reference(), monitor.gyro_delta());
+
+            banner!("{}: ROTATIONAL JERK DETECTED!", label);
+            for axis in monitor.tripped_gyro_axes() {
+                println!("Axis: {}", axis.name());
+            }
+            println!("Current: {} {} {}", gyro[0], gyro[1], gyro[2]);
+            println!(
+                "Reference: {} {} {}",
+                reference[0], reference[1], reference[2]
+            );
+            println!("Delta: {} {} {}", delta[0], delta[1], delta[2]);
+        }
+        Limit::Jerk => {
+            banner!("{}: MECHANICAL IMPACT DETECTED!", label);
+            println!("Jerk: {} m/s^3", monitor.reading(Limit::Jerk));
+            println!("Current: {} {} {}", acc[0], acc[1], acc[2]);
+            println!("Limit: {} m/s^3", monitor.thresholds().jerk.warning);
+        }
+        Limit::Temperature => {
+            match monitor.temperature_trip() {
+                Some(TemperatureTrip::Floor) => banner!("{}: TEMPERATURE SENSOR FAULT", label),
+                _ => banner!("{}: OVERHEATING DETECTED", label),
+            }
+            if let Some(trip) = monitor.temperature_trip() {
+                println!("Cause: {}", trip.description());
+            }
+            println!("Current: {}", temp);
+            println!("At boot: {}", monitor.temp_reference());
+            println!(
+                "Ceiling: {}",
+                monitor.thresholds().temperature_ceiling.critical
+            );
+            match monitor.temp_rate() {
+                Some(rate) => println!("Rate: {} ºC/min", rate),
+                None => println!("Rate: n/a"),
+            }
+            if let Some(minutes) = monitor.minutes_to_ceiling() {
+                println!("Time to ceiling: {} min", minutes);
+            }
+        }
+        Limit::Orientation => {
+            let (current, reference) = (monitor.orientation(), monitor.orientation_reference());
+
+            banner!("{}: MOUNTING SHIFTED", label);
+            println!("Pitch: {} º (boot {} º)", current.pitch, reference.pitch);
+            println!("Roll: {} º (boot {} º)", current.roll, reference.roll);
+            println!("Deviation: {} º", monitor.reading(Limit::Orientation));
+        }
+        Limit::Vibration => {
+            banner!("{}: SUSTAINED VIBRATION DETECTED", label);
+            println!("RMS: {} m/s^2", monitor.reading(Limit::Vibration));
+            println!("Limit: {} m/s^2", monitor.thresholds().vibration.warning);
+        }
+        Limit::Velocity => {
+            let zones = monitor.thresholds().velocity_zones;
+
+            banner!("{}: VIBRATION SEVERITY TOO HIGH", label);
+            if let Some(zone) = monitor.velocity_zone() {
+                println!("Zone: {}", zone.name());
+            }
+            println!("Velocity RMS: {} mm/s", monitor.reading(Limit::Velocity));
+            println!(
+                "Zones: B from {}, C from {}, D from {} mm/s",
+                zones.ab, zones.bc, zones.cd
+            );
+        }
+        Limit::Bearing => {
+            banner!("{}: POSSIBLE BEARING DEFECT", label);
+            println!("Crest factor: {}", monitor.reading(Limit::Bearing));
+            if let Some(rms) = monitor.envelope_rms() {
+                println!("Envelope RMS: {} m/s^2", rms);
+            }
+            println!("Limit: {}", monitor.thresholds().crest_factor);
+        }
+        Limit::Probe => {
+            banner!("{}: BEARING TEMPERATURE RISING", label);
+            if let Some(temp) = monitor.probe() {
+                println!("Current: {} ºC", temp);
+            }
+            if let Some(reference) = monitor.probe_reference() {
+                println!("Reference: {} ºC", reference);
+            }
+            println!("Rise: {} ºC", monitor.reading(Limit::Probe));
+            println!("Limit: {} ºC", monitor.thresholds().probe_rise.warning);
+        }
+        Limit::Electrical => {
+            let thresholds = monitor.thresholds();
+
+            match monitor.electrical_trip() {
+                Some(ElectricalTrip::Under) => banner!("{}: MOTOR LOST ITS LOAD", label),
+                _ => banner!("{}: MOTOR OVERLOADED", label),
+            }
+            if let Some(trip) = monitor.electrical_trip() {
+                println!("Cause: {}", trip.description());
+            }
+            println!("Current: {} A RMS", monitor.reading(Limit::Electrical));
+            println!(
+                "Band: {} to {} A, stopped under {} A",
+                thresholds.under_current,
+                thresholds.over_current.warning,
+                thresholds.motor_off_current
+            );
+        }
+        Limit::Knock => {
+            banner!("{}: KNOCKING DETECTED", label);
+            println!("Peak: {} counts", monitor.reading(Limit::Knock));
+            if let Some(knock) = monitor.knock() {
+                println!(
+                    "RMS: {} counts over {} conversions",
+                    knock.rms, knock.samples
+                );
+            }
+            println!("Limit: {} counts", monitor.thresholds().knock.warning);
+        }
+        Limit::Anomaly => {
+            let thresholds = monitor.thresholds();
+
+            banner!("{}: UNUSUAL READING", label);
+            print_stats("|a|", "m/s^2", monitor.acc_stats(), monitor.acc_z());
+            print_stats("Temperature", "ºC", monitor.temp_stats(), monitor.temp_z());
+            println!(
+                "Limits: {} sigma mechanical, {} sigma thermal",
+                thresholds.mechanical_z_limit, thresholds.thermal_z_limit
+            );
+        }
+    }
+}
+
+fn print_stats(name: &str, unit: &str, stats: &RunningStats, z: Option<f32>) {
+    let (mean, sigma) = (stats.mean(), stats.std_dev());
+
+    match z {
+        Some(z) => println!("{}: mean {} {}, sigma {}, z {}", name, mean, unit, sigma, z),
+        None => println!("{}: mean {} {}, sigma {}, z n/a", name, mean, unit, sigma),
+    }
+}
+
+// Only a warning, a lying MPU temperature isn't an overheating machine
+#[cfg(not(feature = "embassy"))]
+fn print_plausibility(monitor: &MaintenanceMonitor) {
+    let divergence = monitor.cross_check().divergence().unwrap_or(0.0);
+    match monitor.plausibility() {
+        Some(Plausibility::Diverged) => warn!(
+            "WARNING: MPU temperature implausible, {} ºC off the chip's since boot",
+            divergence
+        ),
+        Some(Plausibility::Agreed) => info!("MPU temperature back in line with the chip's"),
+        None => {}
+    }
+}
+
+// Every start and stop, whatever the log level
+fn print_run_transition(monitor: &MaintenanceMonitor) {
+    let Some(transition) = monitor.run_transition() else {
+        return;
+    };
+    let failed = if transition.is_failed_start() {
+        ", failed start"
+    } else {
+        ""
+    };
+    println!(
+        "Run state: {} -> {}{}",
+        transition.from.name(),
+        transition.to.name(),
+        failed
+    );
+}
+
+#[cfg(not(feature = "embassy"))]
+fn print_events(events: &EventLog) {
+    println!(
+        "Event log: {} events, {} overwritten",
+        events.len(),
+        events.overwritten()
+    );
+    for event in events.iter() {
+        println!("{}", event);
+    }
+}
+
+// Oldest first, the minute still going last
+#[cfg(not(feature = "embassy"))]
+fn print_trend(trend: &Aggregator) {
+    println!("{}", TrendHeader);
+    for bucket in trend.history().chain(trend.current()) {
+        println!("{}", TrendRow(bucket));
+    }
+}
+
+#[cfg(feature = "dac")]
+fn print_dac(dac: &board::dac::Output, mapping: &DacMapping) {
+    println!(
+        "DAC: 0 V at {} m/s^2, full scale at {} m/s^2, now {} of {} (~{:.2} V){}",
+        mapping.zero,
+        mapping.full_scale,
+        dac.code(),
+        DAC_MAX,
+        dac.code() as f32 * 3.3 / 256.0,
+        if dac.is_testing() {
+            ", test pattern"
+        } else {
+            ""
+        }
+    );
+}
+
+#[cfg(feature = "sd-card")]
+fn print_sd(sd: &board::sdcard::Card<'_>, log: &SdLog) {
+    match sd.current() {
+        Some((number, length)) => println!(
+            "SD: {}, {} bytes, {} bytes waiting, {} rows dropped",
+            sdlog::file_name(number),
+            length,
+            log.pending().len(),
+            log.dropped()
+        ),
+        None => println!(
+            "SD: no card, {} bytes waiting, {} rows dropped",
+            log.pending().len(),
+            log.dropped()
+        ),
+    }
+}
+
+#[cfg(feature = "raw-log")]
+fn print_raw(raw: &board::rawlog::Recorder) {
+    let log = raw.log();
+    let ring = log.ring();
+    println!(
+        "Raw log: {} blocks at boot, {} written since, next #{}, {} bytes in RAM, {} slots dropped, {} flash failures{}",
+        ring.stored(),
+        log.written(),
+        ring.next_seq(),
+        log.filling(),
+        log.dropped(),
+        raw.failed(),
+        if raw.is_dumping() { ", dumping" } else { "" }
+    );
+}
+
+#[cfg(feature = "can")]
+fn print_can(can: &board::can::Bus<'_>) {
+    let (counters, tec) = can.counters();
+    println!(
+        "CAN: {} frames sent, {} TX errors, {} bus-offs, transmit error counter {}",
+        counters.sent, counters.tx_errors, counters.bus_offs, tec
+    );
+}
+
+#[cfg(not(feature = "embassy"))]
+fn print_hours(hours: &RunHours) {
+    println!(
+        "Running hours: {} h, {} h since the last service, due every {} h{}",
+        hours.running_h(),
+        hours.since_service_h(),
+        hours.interval_h(),
+        if hours.is_due() {
+            ", MAINTENANCE DUE"
+        } else {
+            ""
+        }
+    );
+}
+
+// The newest few, oldest first
+#[cfg(not(feature = "embassy"))]
+fn print_flash_log(log: &FlashLog) {
+    println!(
+        "Flash event log: {} events, {} corrupt records skipped",
+        log.records(),
+        log.corrupt()
+    );
+    let mut newest: heapless::Vec<FlashRecord, FLASH_LOG_REPLAY> = heapless::Vec::new();
+    for slot in log.slots().rev() {
+        if let Slot::Record(record) = FlashRecord::decode(&board::flash::read_record(slot)) {
+            if newest.push(record).is_err() {
+                break;
+            }
+        }
+    }
+    for record in newest.iter().rev() {
+        println!("#{} {}", record.seq, record.event);
+    }
+}
+
+// The whole of it takes seconds at 115200 baud, the watchdog is fed along
+#[cfg(not(feature = "embassy"))]
+fn dump_flash_log<B>(context: &mut Context<'_, B>) {
+    println!("Flash event log:");
+    let (mut records, mut corrupt) = (0, 0);
+    for slot in context.flash_log.slots() {
+        match FlashRecord::decode(&board::flash::read_record(slot)) {
+            Slot::Record(record) => {
+                records += 1;
+                println!("#{} {}", record.seq, record.event);
+            }
+            Slot::Corrupt => corrupt += 1,
+            Slot::Blank => {}
+        }
+        context.wdt.feed();
+    }
+    println!(
+        "Flash event log: {} events, {} corrupt records skipped",
+        records, corrupt
+    );
+}
+
+fn print_latched_event(event: &LatchedEvent, now_ms: u32) {
+    println!("Limit: {} ({})", event.limit.name(), event.severity.label());
+    println!(
+        "Latched for: {} s",
+        now_ms.wrapping_sub(event.since_ms) / 1000
+    );
+    println!("Peak: {}", event.peak);
+    println!("Samples over the limit: {}", event.samples);
+}
+
+fn print_baseline(baseline: &Baseline) {
+    println!("Calibration:");
+    for (name, stats) in ["Ax", "Ay", "Az"].iter().zip(baseline.acc) {
+        println!(
+            "{}: {} m/s^2 (std dev {}, {} outliers)",
+            name, stats.mean, stats.std_dev, stats.rejected
+        );
+    }
+    println!(
+        "Temperature: {} ºC (std dev {}, {} outliers)",
+        baseline.temp.mean, baseline.temp.std_dev, baseline.temp.rejected
+    );
+}
@@ -0,0 +1,83 @@
+// f32 helpers for no_std, backed by libm.
+// Unlike the old Absolute trait, none of these touch their arguments.
+
+pub fn abs(x: f32) -> f32 {
+    libm::fabsf(x)
+}
+
+pub fn sqrt(x: f32) -> f32 {
+    libm::sqrtf(x)
+}
+
+pub fn atan2(y: f32, x: f32) -> f32 {
+    libm::atan2f(y, x)
+}
+
+pub fn pow(x: f32, y: f32) -> f32 {
+    libm::powf(x, y)
+}
+
+pub fn sin(x: f32) -> f32 {
+    libm::sinf(x)
+}
+
+pub fn cos(x: f32) -> f32 {
+    libm::cosf(x)
+}
+
+// Halfway cases away from zero
+pub fn round(x: f32) -> f32 {
+    libm::roundf(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-6;
+
+    #[test]
+    fn abs_does_not_change_sign_of_positive_values() {
+        assert_eq!(abs(2.5), 2.5);
+        assert_eq!(abs(0.0), 0.0);
+    }
+
+    #[test]
+    fn abs_flips_negative_values() {
+        assert_eq!(abs(-2.5), 2.5);
+        assert_eq!(abs(-0.0), 0.0);
+        assert!(abs(-0.0).is_sign_positive());
+    }
+
+    #[test]
+    fn abs_leaves_its_argument_untouched() {
+        let delta = -0.9;
+        assert_eq!(abs(delta), 0.9);
+        assert_eq!(delta, -0.9);
+    }
+
+    #[test]
+    fn sqrt_of_squares() {
+        assert!((sqrt(9.0) - 3.0).abs() < EPSILON);
+        assert!((sqrt(2.0) - core::f32::consts::SQRT_2).abs() < EPSILON);
+        assert_eq!(sqrt(0.0), 0.0);
+        assert!(sqrt(-1.0).is_nan());
+    }
+
+    #[test]
+    fn atan2_quadrants() {
+        use core::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+        assert!((atan2(1.0, 1.0) - FRAC_PI_4).abs() < EPSILON);
+        assert!((atan2(1.0, 0.0) - FRAC_PI_2).abs() < EPSILON);
+        assert!((atan2(0.0, -1.0) - PI).abs() < EPSILON);
+        assert!((atan2(-1.0, -1.0) + 3.0 * FRAC_PI_4).abs() < EPSILON);
+    }
+
+    #[test]
+    fn pow_integer_and_fractional_exponents() {
+        assert!((pow(2.0, 10.0) - 1024.0).abs() < EPSILON);
+        assert!((pow(16.0, 0.5) - 4.0).abs() < EPSILON);
+        assert_eq!(pow(5.0, 0.0), 1.0);
+    }
+}
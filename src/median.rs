@@ -0,0 +1,122 @@
+// Samples in the moving median. A glitch shorter than half the window is
+// dropped, anything at least MEDIAN_LENGTH / 2 + 1 samples long survives:
+// with 5, an impact has to last 3 fast samples (30 ms). Use 3 to keep
+// 2-sample impacts, at the cost of only rejecting single-sample glitches.
+pub const MEDIAN_LENGTH: usize = 5;
+
+// Median of the last N samples of one signal.
+// Small enough that sorting a copy on every sample is cheaper than keeping
+// a sorted structure around.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovingMedian<const N: usize = MEDIAN_LENGTH> {
+    window: [f32; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> MovingMedian<N> {
+    pub const fn new() -> Self {
+        Self {
+            window: [0.0; N],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    // Until the window fills up, the median of the samples so far
+    pub fn push(&mut self, value: f32) -> f32 {
+        if N == 0 {
+            return value;
+        }
+
+        self.window[self.next] = value;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..self.len];
+        // Insertion sort
+        for i in 1..sorted.len() {
+            let mut j = i;
+            while j > 0 && sorted[j - 1] > sorted[j] {
+                sorted.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        sorted[sorted.len() / 2]
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl<const N: usize> Default for MovingMedian<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One moving median per axis of an [x, y, z] signal
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AxisMedian<const N: usize = MEDIAN_LENGTH> {
+    axes: [MovingMedian<N>; 3],
+}
+
+impl<const N: usize> AxisMedian<N> {
+    pub const fn new() -> Self {
+        Self {
+            axes: [MovingMedian::new(); 3],
+        }
+    }
+
+    pub fn push(&mut self, value: [f32; 3]) -> [f32; 3] {
+        core::array::from_fn(|i| self.axes[i].push(value[i]))
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(input: &[f32]) -> [f32; 12] {
+        let mut median: MovingMedian = MovingMedian::new();
+        let mut output = [0.0; 12];
+        for (out, value) in output.iter_mut().zip(input) {
+            *out = median.push(*value);
+        }
+        output
+    }
+
+    #[test]
+    fn single_sample_glitch_is_rejected() {
+        let output = filtered(&[
+            1.0, 1.0, 1.0, 40.0, 1.0, 1.0, -40.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+        ]);
+
+        assert!(output.iter().all(|value| *value == 1.0), "{:?}", output);
+    }
+
+    #[test]
+    fn short_impact_survives() {
+        let output = filtered(&[1.0, 1.0, 1.0, 1.0, 1.0, 9.0, 9.0, 9.0, 1.0, 1.0, 1.0, 1.0]);
+
+        // Just as long, two samples late
+        assert_eq!(output.iter().filter(|value| **value == 9.0).count(), 3);
+        assert_eq!(output[7], 9.0);
+    }
+
+    #[test]
+    fn axes_are_filtered_independently() {
+        let mut median: AxisMedian = AxisMedian::new();
+
+        for _ in 0..MEDIAN_LENGTH {
+            median.push([0.0, 0.0, 1.0]);
+        }
+        assert_eq!(median.push([40.0, 0.0, -40.0]), [0.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,540 @@
+use heapless::Vec;
+
+use crate::console::Setting;
+use crate::crc::crc16_modbus;
+use crate::{math, Limit, Reading, Severity};
+
+// A Modbus RTU slave (Modbus over serial line, V1.02) for the plant's
+// SCADA: frames told apart by the line's silence, big-endian registers,
+// the CRC at the end low byte first. The register map, addresses from 0 as
+// they go out on the wire:
+//
+// Input registers, 0x04, signed unless said otherwise
+//   0-2    acceleration x, y, z      0.01 m/s^2
+//   3-5    rotation rate x, y, z     0.001 rad/s
+//   6      temperature               0.01 ºC
+//   7      acceleration delta        0.01 m/s^2, unsigned
+//   8      health score              0-100
+//   9      limits latched
+//   10-11  uptime                    s, u32, high word first
+// Holding registers, 0x03, 0x06 and 0x10: the warning levels of `set`, in
+// its ranges, saved like `save` does when one changes
+//   0      mech                      0.01 m/s^2
+//   1      temp                      0.01 ºC/min
+//   2      gyro                      0.01 rad/s
+// Discrete inputs, 0x02, a limit each in `Limit::ALL` order
+//   0-12   latched
+//   13-25  latched as critical
+//
+// A register with nothing to give, the health score before the first
+// minute or a NaN, reads NO_VALUE.
+pub const IR_ACCELERATION: u16 = 0;
+pub const IR_ROTATION: u16 = 3;
+pub const IR_TEMPERATURE: u16 = 6;
+pub const IR_ACCELERATION_DELTA: u16 = 7;
+pub const IR_HEALTH: u16 = 8;
+pub const IR_LATCHED: u16 = 9;
+pub const IR_UPTIME: u16 = 10;
+pub const INPUT_REGISTERS: u16 = 12;
+
+// Indexed like `Setting::ALL`
+pub const HR_WARNINGS: u16 = 0;
+pub const HOLDING_REGISTERS: u16 = Setting::ALL.len() as u16;
+
+pub const DI_LATCHED: u16 = 0;
+pub const DI_CRITICAL: u16 = Limit::COUNT as u16;
+pub const DISCRETE_INPUTS: u16 = 2 * Limit::COUNT as u16;
+
+pub const ACCELERATION_SCALE: f32 = 100.0;
+pub const ROTATION_SCALE: f32 = 1_000.0;
+pub const TEMPERATURE_SCALE: f32 = 100.0;
+pub const WARNING_SCALE: f32 = 100.0;
+pub const NO_VALUE: u16 = 0x8000;
+
+pub const READ_DISCRETE_INPUTS: u8 = 0x02;
+pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+pub const READ_INPUT_REGISTERS: u8 = 0x04;
+pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+// Writes to it are carried out, nothing is answered
+pub const BROADCAST: u8 = 0;
+// The address byte, the PDU and the CRC
+pub const ADU_LEN: usize = 256;
+pub type Adu = Vec<u8, ADU_LEN>;
+
+// The most one request may ask for, the spec's
+const MAX_READ_BITS: u16 = 2_000;
+const MAX_READ_REGISTERS: u16 = 125;
+const MAX_WRITE_REGISTERS: u16 = 123;
+
+// Start, 8 data bits, parity and stop
+pub const CHAR_BITS: u32 = 11;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exception {
+    IllegalFunction = 1,
+    IllegalDataAddress = 2,
+    IllegalDataValue = 3,
+    // A commissioning run would save its safety caps
+    DeviceBusy = 6,
+}
+
+// The silences of the spec: more than 1.5 characters inside a frame breaks
+// it, 3.5 end it. Above 19200 baud they're fixed, the UART's interrupt
+// couldn't keep up with shorter ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameTiming {
+    pub t1_5_us: u32,
+    pub t3_5_us: u32,
+}
+
+impl FrameTiming {
+    pub const fn new(baud: u32) -> Self {
+        if baud > 19_200 {
+            return Self {
+                t1_5_us: 750,
+                t3_5_us: 1_750,
+            };
+        }
+        let char_us = CHAR_BITS * 1_000_000 / baud;
+        Self {
+            t1_5_us: char_us * 3 / 2,
+            t3_5_us: char_us * 7 / 2,
+        }
+    }
+}
+
+// Cuts the timestamped bytes off the line into frames. One with a gap
+// inside it, or too long for ADU_LEN, is dropped without a word, as the
+// spec wants a malformed frame to be.
+#[derive(Clone, Debug)]
+pub struct Receiver {
+    timing: FrameTiming,
+    adu: Adu,
+    last_us: Option<u64>,
+    broken: bool,
+}
+
+impl Receiver {
+    pub const fn new(baud: u32) -> Self {
+        Self {
+            timing: FrameTiming::new(baud),
+            adu: Vec::new(),
+            last_us: None,
+            broken: false,
+        }
+    }
+
+    // A byte as it came in, and the frame the silence ahead of it ended
+    pub fn push(&mut self, byte: u8, t_us: u64) -> Option<Adu> {
+        let gap_us = self.last_us.map(|last_us| t_us.saturating_sub(last_us));
+        let ended = match gap_us {
+            Some(gap_us) if gap_us >= self.timing.t3_5_us as u64 => self.end(),
+            Some(gap_us) if gap_us > self.timing.t1_5_us as u64 => {
+                self.broken = true;
+                None
+            }
+            _ => None,
+        };
+        if self.adu.push(byte).is_err() {
+            self.broken = true;
+        }
+        self.last_us = Some(t_us);
+        ended
+    }
+
+    // The frame, once the line has been quiet for 3.5 characters after it
+    pub fn idle(&mut self, now_us: u64) -> Option<Adu> {
+        match self.last_us {
+            Some(last_us) if now_us.saturating_sub(last_us) >= self.timing.t3_5_us as u64 => {
+                self.end()
+            }
+            _ => None,
+        }
+    }
+
+    // Anything half in, the echo of a reply
+    pub fn clear(&mut self) {
+        self.end();
+    }
+
+    fn end(&mut self) -> Option<Adu> {
+        let adu = core::mem::take(&mut self.adu);
+        let broken = self.broken;
+        self.broken = false;
+        self.last_us = None;
+        (!broken).then_some(adu)
+    }
+}
+
+// What the registers hold as of a request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub reading: Reading,
+    pub acc_magnitude: f32,
+    pub health: Option<u8>,
+    pub uptime_s: u32,
+    // Indexed like `Setting::ALL`
+    pub warnings: [f32; Setting::ALL.len()],
+    pub latched: [Option<Severity>; Limit::COUNT],
+    // Writes are refused meanwhile, like a config update from the broker
+    pub learning: bool,
+}
+
+impl Registers {
+    pub fn input(&self, address: u16) -> Option<u16> {
+        let reading = &self.reading;
+        let index = |first: u16| (address - first) as usize;
+        Some(match address {
+            IR_ACCELERATION..=2 => scaled(reading.acc[index(IR_ACCELERATION)], ACCELERATION_SCALE),
+            IR_ROTATION..=5 => scaled(reading.gyro[index(IR_ROTATION)], ROTATION_SCALE),
+            IR_TEMPERATURE => scaled(reading.temp, TEMPERATURE_SCALE),
+            IR_ACCELERATION_DELTA => scaled(self.acc_magnitude, ACCELERATION_SCALE),
+            IR_HEALTH => self.health.map_or(NO_VALUE, u16::from),
+            IR_LATCHED => self.latched.iter().flatten().count() as u16,
+            IR_UPTIME => (self.uptime_s >> 16) as u16,
+            _ if address == IR_UPTIME + 1 => self.uptime_s as u16,
+            _ => return None,
+        })
+    }
+
+    pub fn holding(&self, address: u16) -> Option<u16> {
+        let warning = self
+            .warnings
+            .get(address.checked_sub(HR_WARNINGS)? as usize)?;
+        Some(scaled(*warning, WARNING_SCALE))
+    }
+
+    pub fn discrete(&self, address: u16) -> Option<bool> {
+        let severity = |index: u16| self.latched.get(index as usize).copied();
+        match address {
+            address if address < DI_CRITICAL => Some(severity(address - DI_LATCHED)?.is_some()),
+            address if address < DISCRETE_INPUTS => {
+                Some(severity(address - DI_CRITICAL)? == Some(Severity::Critical))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Rounded to the register's unit, saturated, NO_VALUE for a NaN
+fn scaled(value: f32, scale: f32) -> u16 {
+    if value.is_nan() {
+        return NO_VALUE;
+    }
+    let scaled = math::round(value * scale).clamp(-(i16::MAX as f32), i16::MAX as f32);
+    scaled as i16 as u16
+}
+
+// The warning level a holding register is written to, None outside its
+// range
+fn warning_level(setting: Setting, value: u16) -> Option<f32> {
+    let warning = value as f32 / WARNING_SCALE;
+    let (min, max) = setting.range();
+    (min..=max).contains(&warning).then_some(warning)
+}
+
+// Indexed like `Setting::ALL`, the ones a write sets
+pub type Warnings = [Option<f32>; Setting::ALL.len()];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handled {
+    // None for a broadcast
+    pub reply: Option<Adu>,
+    // All of them checked, to be applied and saved
+    pub warnings: Option<Warnings>,
+}
+
+// None for a frame that's cut short, fails its CRC or is for another
+// slave. Anything else is answered, with an exception if need be, and
+// nothing of a refused write is applied.
+pub fn handle(adu: &[u8], address: u8, registers: &Registers) -> Option<Handled> {
+    let [unit, pdu @ .., crc_low, crc_high] = adu else {
+        return None;
+    };
+    if pdu.is_empty()
+        || crc16_modbus(&adu[..adu.len() - 2]) != u16::from_le_bytes([*crc_low, *crc_high])
+    {
+        return None;
+    }
+    if *unit != address && *unit != BROADCAST {
+        return None;
+    }
+
+    let function = pdu[0];
+    let mut reply = Adu::new();
+    reply.extend_from_slice(&[address, function]).ok();
+    let mut warnings = Warnings::default();
+    let warnings = match execute(function, &pdu[1..], registers, &mut warnings, &mut reply) {
+        Ok(written) => written.then_some(warnings),
+        Err(exception) => {
+            reply.truncate(1);
+            reply
+                .extend_from_slice(&[function | 0x80, exception as u8])
+                .ok();
+            None
+        }
+    };
+    let crc = crc16_modbus(&reply);
+    reply.extend_from_slice(&crc.to_le_bytes()).ok();
+    Some(Handled {
+        reply: (*unit != BROADCAST).then_some(reply),
+        warnings,
+    })
+}
+
+// The reply's data after the function code, and whether a level changed. The
+// checks go in the spec's order: function, quantity, address, then the
+// values.
+fn execute(
+    function: u8,
+    data: &[u8],
+    registers: &Registers,
+    warnings: &mut Warnings,
+    reply: &mut Adu,
+) -> Result<bool, Exception> {
+    let word = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+    match function {
+        READ_DISCRETE_INPUTS | READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+            if data.len() != 4 {
+                return Err(Exception::IllegalDataValue);
+            }
+            let (start, count) = (word(0), word(2));
+            let (max, len) = match function {
+                READ_DISCRETE_INPUTS => (MAX_READ_BITS, DISCRETE_INPUTS),
+                READ_HOLDING_REGISTERS => (MAX_READ_REGISTERS, HOLDING_REGISTERS),
+                _ => (MAX_READ_REGISTERS, INPUT_REGISTERS),
+            };
+            if !(1..=max).contains(&count) {
+                return Err(Exception::IllegalDataValue);
+            }
+            if start as u32 + count as u32 > len as u32 {
+                return Err(Exception::IllegalDataAddress);
+            }
+            let addresses = start..start + count;
+            if function == READ_DISCRETE_INPUTS {
+                // The byte count, once the bits are in
+                reply.push(0).ok();
+                for (i, address) in addresses.enumerate() {
+                    if i % 8 == 0 {
+                        reply.push(0).ok();
+                    }
+                    if let (Some(true), Some(byte)) =
+                        (registers.discrete(address), reply.last_mut())
+                    {
+                        *byte |= 1 << (i % 8);
+                    }
+                }
+                reply[2] = (reply.len() - 3) as u8;
+            } else {
+                reply.push(2 * count as u8).ok();
+                for address in addresses {
+                    let value = match function {
+                        READ_HOLDING_REGISTERS => registers.holding(address),
+                        _ => registers.input(address),
+                    };
+                    reply
+                        .extend_from_slice(&value.unwrap_or(0).to_be_bytes())
+                        .ok();
+                }
+            }
+            Ok(false)
+        }
+        WRITE_SINGLE_REGISTER => {
+            if data.len() != 4 {
+                return Err(Exception::IllegalDataValue);
+            }
+            let changed = write(word(0), &[word(2)], registers, warnings)?;
+            reply.extend_from_slice(data).ok();
+            Ok(changed)
+        }
+        WRITE_MULTIPLE_REGISTERS => {
+            let (Some(&byte_count), Some(values)) = (data.get(4), data.get(5..)) else {
+                return Err(Exception::IllegalDataValue);
+            };
+            let (start, count) = (word(0), word(2));
+            if !(1..=MAX_WRITE_REGISTERS).contains(&count)
+                || byte_count as u16 != 2 * count
+                || values.len() != byte_count as usize
+            {
+                return Err(Exception::IllegalDataValue);
+            }
+            let mut words: Vec<u16, { MAX_WRITE_REGISTERS as usize }> = Vec::new();
+            for pair in values.chunks(2) {
+                words.push(u16::from_be_bytes([pair[0], pair[1]])).ok();
+            }
+            let changed = write(start, &words, registers, warnings)?;
+            reply.extend_from_slice(&data[..4]).ok();
+            Ok(changed)
+        }
+        _ => Err(Exception::IllegalFunction),
+    }
+}
+
+fn write(
+    start: u16,
+    values: &[u16],
+    registers: &Registers,
+    warnings: &mut Warnings,
+) -> Result<bool, Exception> {
+    if start as usize + values.len() > HOLDING_REGISTERS as usize {
+        return Err(Exception::IllegalDataAddress);
+    }
+    for (address, value) in (start..).zip(values) {
+        // Written back as it was read, nothing to set or save
+        if registers.holding(address) == Some(*value) {
+            continue;
+        }
+        let index = (address - HR_WARNINGS) as usize;
+        let level = warning_level(Setting::ALL[index], *value);
+        warnings[index] = Some(level.ok_or(Exception::IllegalDataValue)?);
+    }
+    let changed = warnings.iter().any(Option::is_some);
+    if changed && registers.learning {
+        return Err(Exception::DeviceBusy);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> Registers {
+        let mut latched = [None; Limit::COUNT];
+        latched[Limit::Mechanical as usize] = Some(Severity::Warning);
+        latched[Limit::Temperature as usize] = Some(Severity::Critical);
+        Registers {
+            reading: Reading::new([0.125, -9.81, 0.0], [0.0, 0.5, -0.002], f32::NAN, 0),
+            acc_magnitude: 1.5,
+            health: None,
+            uptime_s: 0x0001_2345,
+            warnings: [0.5, 2.0, 0.3],
+            latched,
+            learning: false,
+        }
+    }
+
+    // `pdu` to slave 1, with its CRC
+    fn request(pdu: &[u8]) -> std::vec::Vec<u8> {
+        let mut adu = vec![1];
+        adu.extend_from_slice(pdu);
+        let crc = crc16_modbus(&adu);
+        adu.extend_from_slice(&crc.to_le_bytes());
+        adu
+    }
+
+    fn reply(pdu: &[u8], registers: &Registers) -> std::vec::Vec<u8> {
+        let handled = handle(&request(pdu), 1, registers).unwrap();
+        let reply = handled.reply.unwrap();
+        let (body, crc) = reply.split_at(reply.len() - 2);
+        assert_eq!(crc, crc16_modbus(body).to_le_bytes());
+        body[1..].to_vec()
+    }
+
+    #[test]
+    fn frames_end_on_the_silence_after_them() {
+        // 19200 baud: a character is 572 µs, 1.5 of them 858 µs, 3.5 2002 µs
+        let mut receiver = Receiver::new(19_200);
+        assert_eq!(FrameTiming::new(19_200).t3_5_us, 2_002);
+        assert_eq!(FrameTiming::new(115_200).t3_5_us, 1_750);
+        for (i, byte) in [1, 3, 0, 0].into_iter().enumerate() {
+            assert_eq!(receiver.push(byte, i as u64 * 600), None);
+        }
+        assert_eq!(receiver.idle(1_800 + 2_001), None);
+        assert_eq!(receiver.idle(1_800 + 2_002).unwrap(), [1, 3, 0, 0]);
+        assert_eq!(receiver.idle(10_000), None);
+
+        // A pause of 2 characters inside breaks it, the next after 3.5 is fine
+        receiver.push(1, 20_000);
+        receiver.push(3, 21_200);
+        assert_eq!(receiver.push(1, 24_000), None);
+        assert_eq!(receiver.idle(30_000).unwrap(), [1]);
+        // and too long a frame only ends
+        for i in 0..=ADU_LEN as u64 {
+            receiver.push(0, 40_000 + i * 500);
+        }
+        assert_eq!(receiver.idle(1_000_000), None);
+    }
+
+    #[test]
+    fn reads_the_register_map() {
+        let registers = registers();
+        assert_eq!(
+            reply(&[0x04, 0, 0, 0, 12], &registers),
+            [
+                0x04, 24, 0, 13, 0xfc, 0x2b, 0, 0, 0, 0, 0x01, 0xf4, 0xff, 0xfe, 0x80, 0, 0, 150,
+                0x80, 0, 0, 2, 0, 1, 0x23, 0x45
+            ]
+        );
+        assert_eq!(
+            reply(&[0x03, 0, 1, 0, 2], &registers),
+            [0x03, 4, 0, 200, 0, 30]
+        );
+        // Mechanical and Temperature latched, 13 bits in, Temperature critical
+        assert_eq!(
+            reply(&[0x02, 0, 0, 0, 26], &registers),
+            [0x02, 4, 0x12, 0, 0x02, 0]
+        );
+
+        // Past the map, too much at once, a function it doesn't have
+        assert_eq!(reply(&[0x04, 0, 11, 0, 2], &registers), [0x84, 2]);
+        assert_eq!(reply(&[0x03, 0, 0, 0, 126], &registers), [0x83, 3]);
+        assert_eq!(reply(&[0x03, 0, 0, 0], &registers), [0x83, 3]);
+        assert_eq!(reply(&[0x01, 0, 0, 0, 1], &registers), [0x81, 1]);
+
+        // Someone else's, or a bad CRC, aren't answered at all
+        let mut adu = request(&[0x03, 0, 0, 0, 1]);
+        assert!(handle(&adu, 2, &registers).is_none());
+        adu[3] ^= 1;
+        assert!(handle(&adu, 1, &registers).is_none());
+        assert!(handle(&[1, 0x03], 1, &registers).is_none());
+    }
+
+    #[test]
+    fn writes_all_of_it_or_nothing() {
+        let mut registers = registers();
+        let handled = handle(&request(&[0x06, 0, 1, 0x01, 0x2c]), 1, &registers).unwrap();
+        assert_eq!(handled.warnings, Some([None, Some(3.0), None]));
+        assert_eq!(handled.reply.unwrap()[1..6], [0x06, 0, 1, 0x01, 0x2c]);
+
+        let pdu = [0x10, 0, 0, 0, 3, 6, 0, 80, 0, 250, 0, 40];
+        let handled = handle(&request(&pdu), 1, &registers).unwrap();
+        assert_eq!(handled.warnings, Some([Some(0.8), Some(2.5), Some(0.4)]));
+        assert_eq!(reply(&pdu, &registers), [0x10, 0, 0, 0, 3]);
+
+        // gyro at 10 rad/s is past its range, so temp isn't set either
+        let pdu = [0x10, 0, 1, 0, 2, 4, 0, 250, 0x03, 0xe8];
+        let handled = handle(&request(&pdu), 1, &registers).unwrap();
+        assert_eq!(handled.warnings, None);
+        assert_eq!(reply(&pdu, &registers), [0x90, 3]);
+        assert_eq!(
+            reply(&[0x10, 0, 2, 0, 2, 4, 0, 1, 0, 1], &registers),
+            [0x90, 2]
+        );
+        assert_eq!(reply(&[0x10, 0, 0, 0, 1, 4, 0, 80], &registers), [0x90, 3]);
+
+        // A broadcast is carried out without a reply
+        let mut adu = request(&[0x06, 0, 0, 0, 80]);
+        adu[0] = BROADCAST;
+        let crc = crc16_modbus(&adu[..adu.len() - 2]);
+        let len = adu.len();
+        adu[len - 2..].copy_from_slice(&crc.to_le_bytes());
+        let handled = handle(&adu, 1, &registers).unwrap();
+        assert_eq!(handled.reply, None);
+        assert_eq!(handled.warnings, Some([Some(0.8), None, None]));
+
+        // mech as it is, gyro to 0.4: only gyro is set
+        let pdu = [0x10, 0, 0, 0, 3, 6, 0, 50, 0, 200, 0, 40];
+        let handled = handle(&request(&pdu), 1, &registers).unwrap();
+        assert_eq!(handled.warnings, Some([None, None, Some(0.4)]));
+
+        // Nothing is saved while learning, a write of the same values passes
+        registers.learning = true;
+        assert_eq!(reply(&[0x06, 0, 0, 0, 80], &registers), [0x86, 6]);
+        let handled = handle(&request(&[0x06, 0, 0, 0, 50]), 1, &registers).unwrap();
+        assert_eq!(handled.warnings, None);
+        assert!(handled.reply.is_some());
+    }
+}
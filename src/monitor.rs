@@ -0,0 +1,2154 @@
+use crate::chiptemp::{CrossCheck, Plausibility};
+use crate::condition::Condition;
+use crate::detach::DetachDetector;
+use crate::envelope::Envelope;
+use crate::ewma::{DualEwma, FAST_ALPHA, SLOW_ALPHA};
+use crate::jerk::Jerk;
+use crate::knock::KnockLevel;
+use crate::math;
+use crate::median::AxisMedian;
+use crate::orientation::{ComplementaryFilter, Orientation};
+use crate::peak::PeakHold;
+use crate::reading::Reading;
+use crate::runstate::{
+    Activity, RunState, RunStateDetector, RunTransition, RUN_GRACE_MS, TRANSIENT_SCALE,
+};
+use crate::spectrum::SPECTRUM_RATE_HZ;
+use crate::trend::TemperatureTrend;
+use crate::velocity::{VelocityRms, Zone, Zones};
+use crate::vibration::{VibrationRms, VIBRATION_PERIOD_MS};
+use crate::zscore::{
+    RunningStats, MECHANICAL_SIGMA_FLOOR, MECHANICAL_Z_LIMIT, THERMAL_SIGMA_FLOOR, THERMAL_Z_LIMIT,
+    Z_WINDOW,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    SensorDetached,
+    Mechanical,
+    Rotational,
+    Jerk,
+    Temperature,
+    Vibration,
+    Velocity,
+    Bearing,
+    Orientation,
+    Anomaly,
+    Probe,
+    Knock,
+    Electrical,
+}
+
+impl Limit {
+    // In priority order. The external sensors came last and stay there, the
+    // stored event records index this.
+    pub const ALL: [Limit; 13] = [
+        Limit::SensorDetached,
+        Limit::Mechanical,
+        Limit::Rotational,
+        Limit::Jerk,
+        Limit::Temperature,
+        Limit::Vibration,
+        Limit::Velocity,
+        Limit::Bearing,
+        Limit::Orientation,
+        Limit::Anomaly,
+        Limit::Probe,
+        Limit::Knock,
+        Limit::Electrical,
+    ];
+    pub const COUNT: usize = Self::ALL.len();
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Limit::SensorDetached => "Sensor detached",
+            Limit::Mechanical => "Mechanical",
+            Limit::Rotational => "Rotational",
+            Limit::Jerk => "Jerk",
+            Limit::Temperature => "Temperature",
+            Limit::Vibration => "Vibration",
+            Limit::Velocity => "Velocity",
+            Limit::Bearing => "Bearing",
+            Limit::Orientation => "Orientation",
+            Limit::Anomaly => "Anomaly",
+            Limit::Probe => "Probe temperature",
+            Limit::Knock => "Knock",
+            Limit::Electrical => "Electrical",
+        }
+    }
+}
+
+// What tripped the temperature limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TemperatureTrip {
+    // Rising faster than the rate limit
+    Rate,
+    // Over the absolute ceiling, whatever the reference
+    Ceiling,
+    // Below the floor, the sensor is most likely broken
+    Floor,
+    // Heading for the ceiling within the forecast horizon
+    Forecast,
+}
+
+impl TemperatureTrip {
+    pub fn description(&self) -> &'static str {
+        match self {
+            TemperatureTrip::Rate => "rising too fast",
+            TemperatureTrip::Ceiling => "over the absolute ceiling",
+            TemperatureTrip::Floor => "implausibly low, check the sensor",
+            TemperatureTrip::Forecast => "on track to reach the ceiling",
+        }
+    }
+}
+
+// Which check raised the electrical limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElectricalTrip {
+    // Over the over-current levels, an overload or a seizing bearing
+    Over,
+    // Running, but under the under-current level: a snapped belt, a pump
+    // running dry
+    Under,
+}
+
+impl ElectricalTrip {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ElectricalTrip::Over => "over-current, overload or seizure",
+            ElectricalTrip::Under => "under-current, lost load",
+        }
+    }
+}
+
+// Warnings only blink the LED, critical alarms also sound the buzzer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 2] = [Severity::Warning, Severity::Critical];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alert {
+    pub limit: Limit,
+    pub severity: Severity,
+}
+
+impl Alert {
+    // The most severe alert wins. On equal severity a detached sensor comes
+    // first, since it makes every other reading meaningless, then motion:
+    // an overheating machine keeps tripping on the following cycles, a sudden
+    // shock or rotational jerk does not.
+    pub fn outranks(&self, other: &Alert) -> bool {
+        self.severity > other.severity
+            || (self.severity == other.severity && (self.limit as u8) < (other.limit as u8))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+        }
+    }
+}
+
+// How the accelerometer delta is compared against the mechanical limit.
+// Per-axis checks depend on how the sensor is mounted: a shock at 45° is split
+// across two axes. The magnitude of the delta vector does not have that problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MechanicalMode {
+    PerAxis,
+    Magnitude,
+}
+
+// Motion deltas, in m/s^2 and rad/s
+pub const MECHANICAL_WARNING: f32 = 0.5;
+pub const MECHANICAL_CRITICAL: f32 = 0.8;
+pub const GYRO_WARNING: f32 = 0.6;
+pub const GYRO_CRITICAL: f32 = 1.0;
+pub const MECHANICAL_MODE: MechanicalMode = MechanicalMode::PerAxis;
+
+// Length of the jerk vector between consecutive samples, in m/s^3
+pub const JERK_LIMIT_WARNING: f32 = 2.0;
+pub const JERK_LIMIT_CRITICAL: f32 = 4.0;
+
+// Change of pitch or roll against the boot orientation, in degrees.
+// A loose mount or a shifted skid is a slow change, so the deviation has to
+// persist for TILT_HOLD_MS before it trips.
+pub const TILT_LIMIT_WARNING: f32 = 5.0;
+pub const TILT_LIMIT_CRITICAL: f32 = 10.0;
+pub const TILT_HOLD_MS: u32 = 5_000;
+
+// Temperature rise in ºC/minute, and an absolute ceiling in ºC
+pub const TEMPERATURE_RATE_WARNING: f32 = 1.5;
+pub const TEMPERATURE_RATE_CRITICAL: f32 = 2.5;
+pub const TEMPERATURE_CEILING_WARNING: f32 = 70.0;
+pub const TEMPERATURE_CEILING_CRITICAL: f32 = 80.0;
+
+// Rise of the external probe over its own reference, in ºC. Unlike the die,
+// it sits on the bearing housing, which runs this much above its cold
+// temperature only when something rubs.
+pub const PROBE_RISE_WARNING: f32 = 15.0;
+pub const PROBE_RISE_CRITICAL: f32 = 25.0;
+
+// Peak swing of the knock sensor about its bias, in ADC counts, see
+// `knock::KnockLevel`
+pub const KNOCK_PEAK_WARNING: f32 = 600.0;
+pub const KNOCK_PEAK_CRITICAL: f32 = 1_200.0;
+
+// Motor RMS current, in A. Below the off level the motor is stopped, which
+// is no fault; between it and the under-current level it runs without its
+// load.
+pub const OVER_CURRENT_WARNING: f32 = 8.0;
+pub const OVER_CURRENT_CRITICAL: f32 = 12.0;
+pub const UNDER_CURRENT: f32 = 1.0;
+pub const MOTOR_OFF_CURRENT: f32 = 0.3;
+
+// The vibration and velocity limits over a range of shaft speeds, from
+// `from_rpm` up to the next band's: scaled by `scale`, or off with None
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedBand {
+    pub from_rpm: f32,
+    pub scale: Option<f32>,
+}
+
+// Below 200 RPM the machine is ramping up or coasting down, its shake says
+// nothing about its condition. A fast machine in good condition shakes more
+// than a slow one.
+pub const SPEED_BANDS: [SpeedBand; 3] = [
+    SpeedBand {
+        from_rpm: 0.0,
+        scale: None,
+    },
+    SpeedBand {
+        from_rpm: 200.0,
+        scale: Some(1.0),
+    },
+    SpeedBand {
+        from_rpm: 3_000.0,
+        scale: Some(1.5),
+    },
+];
+
+// The die can't be this cold on a running machine, in ºC.
+// It's only a warning: a broken sensor is no reason to stop the machine.
+pub const TEMPERATURE_FLOOR: f32 = -20.0;
+pub const TEMPERATURE_FLOOR_RELEASE: f32 = -15.0;
+
+// RMS of the high-pass-filtered acceleration magnitude, in m/s^2
+pub const RMS_LIMIT_WARNING: f32 = 0.3;
+pub const RMS_LIMIT_CRITICAL: f32 = 0.6;
+
+// Peak over RMS of the bearing-band envelope. Only a warning, a spiky
+// envelope is an early sign, not a reason to stop the machine.
+// Sensor noise alone has a spiky envelope too, so it's only checked once
+// the envelope RMS is over ENVELOPE_MIN_RMS, in m/s^2.
+pub const CREST_FACTOR_LIMIT: f32 = 2.0;
+pub const ENVELOPE_MIN_RMS: f32 = 0.1;
+
+// A tripped limit clears once its reading stays below the release level
+// for RELEASE_SAMPLES consecutive samples. Delta-based levels release at
+// RELEASE_RATIO of the warning level, the absolute ceiling at a fixed value.
+pub const RELEASE_RATIO: f32 = 0.6;
+pub const RELEASE_SAMPLES: u8 = 3;
+pub const TEMPERATURE_CEILING_RELEASE: f32 = 65.0;
+
+// Consecutive samples over the limit before an alert is raised
+pub const MECHANICAL_CONFIRMATIONS: u8 = 2;
+pub const ROTATIONAL_CONFIRMATIONS: u8 = 2;
+// An impact is over by the next sample, a single one is enough
+pub const JERK_CONFIRMATIONS: u8 = 1;
+pub const TEMPERATURE_CONFIRMATIONS: u8 = 2;
+pub const VIBRATION_CONFIRMATIONS: u8 = 2;
+pub const VELOCITY_CONFIRMATIONS: u8 = 2;
+pub const BEARING_CONFIRMATIONS: u8 = 2;
+pub const ORIENTATION_CONFIRMATIONS: u8 = (TILT_HOLD_MS / SAMPLE_PERIOD_MS) as u8;
+pub const ANOMALY_CONFIRMATIONS: u8 = 2;
+pub const PROBE_CONFIRMATIONS: u8 = 2;
+pub const KNOCK_CONFIRMATIONS: u8 = 2;
+// The current is measured once a second, every other sample
+pub const ELECTRICAL_CONFIRMATIONS: u8 = 4;
+
+// The firmware samples every ~500 ms, so 120 samples cover about a minute
+pub const SAMPLE_PERIOD_MS: u32 = 500;
+pub const TEMPERATURE_WINDOW: usize = 120;
+
+// The trend over the last 3 minutes is extrapolated to the warning ceiling,
+// a warning is raised if it would get there within the horizon.
+// Nothing is forecast until there is a minute of data.
+pub const FORECAST_WINDOW: usize = 360;
+pub const FORECAST_HORIZON_MIN: f32 = 10.0;
+pub const FORECAST_MIN_SPAN_MS: u32 = 60_000;
+
+// Warning and critical trip points for one measurement,
+// and the level it has to fall below before the alarm clears
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Levels {
+    pub warning: f32,
+    pub critical: f32,
+    pub release: f32,
+}
+
+impl Levels {
+    pub fn new(warning: f32, critical: f32) -> Self {
+        Self {
+            warning,
+            critical,
+            release: warning * RELEASE_RATIO,
+        }
+    }
+
+    pub fn with_release(self, release: f32) -> Self {
+        Self { release, ..self }
+    }
+
+    // The same levels relative to each other, moved so the warning is at
+    // `warning`
+    pub fn scaled_to(self, warning: f32) -> Self {
+        let factor = warning / self.warning;
+        Self {
+            warning,
+            critical: self.critical * factor,
+            release: self.release * factor,
+        }
+    }
+
+    pub fn is_released(&self, value: f32) -> bool {
+        value < self.release
+    }
+
+    // Only the highest level reached is reported
+    pub fn severity(&self, value: f32) -> Option<Severity> {
+        if value >= self.critical {
+            Some(Severity::Critical)
+        } else if value >= self.warning {
+            Some(Severity::Warning)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thresholds {
+    pub mechanical: Levels,
+    pub rotational: Levels,
+    pub jerk: Levels,
+    pub temperature_rate: Levels,
+    pub temperature_ceiling: Levels,
+    pub vibration: Levels,
+    // Zone C is a warning, zone D critical
+    pub velocity_zones: Zones,
+    pub crest_factor: f32,
+    pub orientation: Levels,
+    pub probe_rise: Levels,
+    pub knock: Levels,
+    pub over_current: Levels,
+    pub under_current: f32,
+    pub motor_off_current: f32,
+    // In increasing order of speed, the first from 0
+    pub speed_bands: [SpeedBand; SPEED_BANDS.len()],
+    // Through a start or a stop, see `RunStateDetector`
+    pub run_grace_ms: u32,
+    pub transient_scale: Option<f32>,
+    pub mechanical_mode: MechanicalMode,
+    pub release_samples: u8,
+    pub mechanical_confirmations: u8,
+    pub rotational_confirmations: u8,
+    pub jerk_confirmations: u8,
+    pub temperature_confirmations: u8,
+    pub vibration_confirmations: u8,
+    pub velocity_confirmations: u8,
+    pub bearing_confirmations: u8,
+    pub orientation_confirmations: u8,
+    pub anomaly_confirmations: u8,
+    pub probe_confirmations: u8,
+    pub knock_confirmations: u8,
+    pub electrical_confirmations: u8,
+    // Standard deviations from the running mean of the acceleration
+    // magnitude and of the temperature before a sample is an anomaly
+    pub mechanical_z_limit: f32,
+    pub thermal_z_limit: f32,
+    // Weights of the fast measurement and slow reference averages, see `DualEwma`
+    pub fast_alpha: f32,
+    pub slow_alpha: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            mechanical: Levels::new(MECHANICAL_WARNING, MECHANICAL_CRITICAL),
+            rotational: Levels::new(GYRO_WARNING, GYRO_CRITICAL),
+            jerk: Levels::new(JERK_LIMIT_WARNING, JERK_LIMIT_CRITICAL),
+            temperature_rate: Levels::new(TEMPERATURE_RATE_WARNING, TEMPERATURE_RATE_CRITICAL),
+            temperature_ceiling: Levels::new(
+                TEMPERATURE_CEILING_WARNING,
+                TEMPERATURE_CEILING_CRITICAL,
+            )
+            .with_release(TEMPERATURE_CEILING_RELEASE),
+            vibration: Levels::new(RMS_LIMIT_WARNING, RMS_LIMIT_CRITICAL),
+            velocity_zones: Zones::default(),
+            crest_factor: CREST_FACTOR_LIMIT,
+            orientation: Levels::new(TILT_LIMIT_WARNING, TILT_LIMIT_CRITICAL),
+            probe_rise: Levels::new(PROBE_RISE_WARNING, PROBE_RISE_CRITICAL),
+            knock: Levels::new(KNOCK_PEAK_WARNING, KNOCK_PEAK_CRITICAL),
+            over_current: Levels::new(OVER_CURRENT_WARNING, OVER_CURRENT_CRITICAL),
+            under_current: UNDER_CURRENT,
+            motor_off_current: MOTOR_OFF_CURRENT,
+            speed_bands: SPEED_BANDS,
+            run_grace_ms: RUN_GRACE_MS,
+            transient_scale: TRANSIENT_SCALE,
+            mechanical_mode: MECHANICAL_MODE,
+            release_samples: RELEASE_SAMPLES,
+            mechanical_confirmations: MECHANICAL_CONFIRMATIONS,
+            rotational_confirmations: ROTATIONAL_CONFIRMATIONS,
+            jerk_confirmations: JERK_CONFIRMATIONS,
+            temperature_confirmations: TEMPERATURE_CONFIRMATIONS,
+            vibration_confirmations: VIBRATION_CONFIRMATIONS,
+            velocity_confirmations: VELOCITY_CONFIRMATIONS,
+            bearing_confirmations: BEARING_CONFIRMATIONS,
+            orientation_confirmations: ORIENTATION_CONFIRMATIONS,
+            anomaly_confirmations: ANOMALY_CONFIRMATIONS,
+            probe_confirmations: PROBE_CONFIRMATIONS,
+            knock_confirmations: KNOCK_CONFIRMATIONS,
+            electrical_confirmations: ELECTRICAL_CONFIRMATIONS,
+            mechanical_z_limit: MECHANICAL_Z_LIMIT,
+            thermal_z_limit: THERMAL_Z_LIMIT,
+            fast_alpha: FAST_ALPHA,
+            slow_alpha: SLOW_ALPHA,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn confirmations(&self, limit: Limit) -> u8 {
+        match limit {
+            // DetachDetector already waits for the sensor to settle
+            Limit::SensorDetached => 1,
+            Limit::Mechanical => self.mechanical_confirmations,
+            Limit::Rotational => self.rotational_confirmations,
+            Limit::Jerk => self.jerk_confirmations,
+            Limit::Temperature => self.temperature_confirmations,
+            Limit::Vibration => self.vibration_confirmations,
+            Limit::Velocity => self.velocity_confirmations,
+            Limit::Bearing => self.bearing_confirmations,
+            Limit::Orientation => self.orientation_confirmations,
+            Limit::Anomaly => self.anomaly_confirmations,
+            Limit::Probe => self.probe_confirmations,
+            Limit::Knock => self.knock_confirmations,
+            Limit::Electrical => self.electrical_confirmations,
+        }
+    }
+}
+
+// Owns the reference values and decides when the alarm should sound.
+// Readings are plain `Reading`s of [x, y, z] arrays, so any sensor driver
+// can feed it.
+// N is the number of samples in the temperature trend window.
+// Vibration is fed separately at VIBRATION_PERIOD_MS, see `push_vibration()`,
+// and so are the fast samples behind the peak deltas and the fused
+// orientation, see `hold_peak()` and `fuse_orientation()`.
+// Those go through `filter()` first, `update()` filters its own sample.
+pub struct MaintenanceMonitor<const N: usize = TEMPERATURE_WINDOW> {
+    thresholds: Thresholds,
+    acc_median: AxisMedian,
+    filtered: Reading,
+    acc_ref: DualEwma,
+    gyro_ref: DualEwma,
+    temp_ref: f32,
+    probe: Option<f32>,
+    probe_ref: Option<f32>,
+    chip_temp: Option<f32>,
+    cross_check: CrossCheck,
+    plausibility: Option<Plausibility>,
+    // The loudest burst since the last `update()`, and the one it checked
+    knock_pending: Option<KnockLevel>,
+    knock: Option<KnockLevel>,
+    current: Option<f32>,
+    speed: Option<f32>,
+    run_state: RunStateDetector,
+    run_transition: Option<RunTransition>,
+    electrical_trip: Option<ElectricalTrip>,
+    acc_delta: [f32; 3],
+    acc_tripped: [Option<Severity>; 3],
+    acc_magnitude: f32,
+    peak_hold: PeakHold,
+    acc_peak: PeakHold,
+    gyro_delta: [f32; 3],
+    gyro_tripped: [Option<Severity>; 3],
+    jerk: Jerk,
+    temp_trend: TemperatureTrend<N>,
+    temp_rate: Option<f32>,
+    temp_trip: Option<TemperatureTrip>,
+    temp_forecast: TemperatureTrend<FORECAST_WINDOW>,
+    minutes_to_ceiling: Option<f32>,
+    vibration: VibrationRms,
+    vibration_rms: Option<f32>,
+    velocity: VelocityRms,
+    velocity_rms: Option<f32>,
+    envelope: Envelope,
+    envelope_rms: Option<f32>,
+    crest_factor: Option<f32>,
+    orientation_ref: Orientation,
+    orientation_filter: ComplementaryFilter,
+    orientation: Orientation,
+    gravity_ref: f32,
+    min_acc: f32,
+    detach: DetachDetector,
+    acc_stats: RunningStats,
+    temp_stats: RunningStats,
+    acc_z: Option<f32>,
+    temp_z: Option<f32>,
+    conditions: [Condition; Limit::COUNT],
+    exceeded: [Option<Severity>; Limit::COUNT],
+    readings: [f32; Limit::COUNT],
+}
+
+impl<const N: usize> MaintenanceMonitor<N> {
+    pub fn new(thresholds: Thresholds, sample_period_ms: u32) -> Self {
+        Self {
+            thresholds,
+            acc_median: AxisMedian::new(),
+            filtered: Reading::new([0.0; 3], [0.0; 3], 0.0, 0),
+            acc_ref: DualEwma::new(thresholds.fast_alpha, thresholds.slow_alpha),
+            gyro_ref: DualEwma::new(thresholds.fast_alpha, thresholds.slow_alpha),
+            temp_ref: 0.0,
+            probe: None,
+            probe_ref: None,
+            chip_temp: None,
+            cross_check: CrossCheck::new(),
+            plausibility: None,
+            knock_pending: None,
+            knock: None,
+            current: None,
+            speed: None,
+            run_state: RunStateDetector::new(thresholds.run_grace_ms),
+            run_transition: None,
+            electrical_trip: None,
+            acc_delta: [0.0; 3],
+            acc_tripped: [None; 3],
+            acc_magnitude: 0.0,
+            peak_hold: PeakHold::new(),
+            acc_peak: PeakHold::new(),
+            gyro_delta: [0.0; 3],
+            gyro_tripped: [None; 3],
+            jerk: Jerk::new(),
+            temp_trend: TemperatureTrend::new(sample_period_ms),
+            temp_rate: None,
+            temp_trip: None,
+            temp_forecast: TemperatureTrend::new(sample_period_ms),
+            minutes_to_ceiling: None,
+            vibration: VibrationRms::new(VIBRATION_PERIOD_MS),
+            vibration_rms: None,
+            velocity: VelocityRms::new(VIBRATION_PERIOD_MS),
+            velocity_rms: None,
+            envelope: Envelope::new(SPECTRUM_RATE_HZ as f32),
+            envelope_rms: None,
+            crest_factor: None,
+            orientation_ref: Orientation::default(),
+            orientation_filter: ComplementaryFilter::default(),
+            orientation: Orientation::default(),
+            gravity_ref: 0.0,
+            min_acc: f32::MAX,
+            detach: DetachDetector::new(),
+            acc_stats: RunningStats::new(Z_WINDOW, MECHANICAL_SIGMA_FLOOR),
+            temp_stats: RunningStats::new(Z_WINDOW, THERMAL_SIGMA_FLOOR),
+            acc_z: None,
+            temp_z: None,
+            conditions: Limit::ALL.map(|limit| {
+                Condition::new(thresholds.confirmations(limit), thresholds.release_samples)
+            }),
+            exceeded: [None; Limit::COUNT],
+            // The references are seeded by the first sample, unless
+            // `set_reference()` is called first
+            readings: [0.0; Limit::COUNT],
+        }
+    }
+
+    // Sets the references from a boot calibration instead of the first sample.
+    // This is also the only way out of a detached sensor.
+    pub fn set_reference(&mut self, acc: [f32; 3], gyro: [f32; 3], temp: f32) {
+        self.seed_gravity(acc);
+        self.detach.reset();
+        self.conditions[Limit::SensorDetached as usize].clear();
+        self.acc_ref.seed(acc);
+        self.gyro_ref.seed(gyro);
+        self.temp_ref = temp;
+    }
+
+    // The external probe's temperature for the next `update()`, None while
+    // it has none or once it's given up on. The first one is its reference.
+    pub fn set_probe(&mut self, temp: Option<f32>) {
+        self.probe = temp;
+        if self.probe_ref.is_none() {
+            self.probe_ref = temp;
+        }
+    }
+
+    // The next probe temperature becomes the reference, on a recalibration
+    pub fn reset_probe_reference(&mut self) {
+        self.probe_ref = None;
+    }
+
+    // The ESP32's own temperature for the next `update()`, to cross-check
+    // the MPU's against. None without a reading.
+    pub fn set_chip_temp(&mut self, temp: Option<f32>) {
+        self.chip_temp = temp;
+    }
+
+    // The offset between the two, from the calibrated MPU temperature and
+    // the chip's at the same time. Only taken at boot, with both cold.
+    pub fn set_chip_offset(&mut self, chip_temp: f32) {
+        self.cross_check.set_offset(self.temp_ref, chip_temp);
+    }
+
+    // The motor's RMS current for the next `update()`s, in A. None while
+    // there's no sensor.
+    pub fn set_current(&mut self, amps: Option<f32>) {
+        self.current = amps;
+    }
+
+    // Shaft speed in RPM for the next `update()`s, None without a speed
+    // sensor: then the vibration limits apply at every speed as they are
+    pub fn set_speed(&mut self, rpm: Option<f32>) {
+        self.speed = rpm;
+    }
+
+    // A knock sensor burst. The bursts come faster than the samples, the
+    // next `update()` checks the loudest since the last one. Without any
+    // the limit releases.
+    pub fn push_knock(&mut self, level: KnockLevel) {
+        match self.knock_pending {
+            Some(pending) if pending.peak >= level.peak => {}
+            _ => self.knock_pending = Some(level),
+        }
+    }
+
+    // Every fast sample goes through the accelerometer median before any of
+    // the detectors, so a single-sample electrical glitch never reaches them.
+    // Returns the filtered reading, the gyro is left alone.
+    pub fn filter(&mut self, reading: &Reading) -> Reading {
+        Reading {
+            acc: self.acc_median.push(reading.acc),
+            ..*reading
+        }
+    }
+
+    // Fast accelerometer samples in m/s^2, one every VIBRATION_PERIOD_MS.
+    // The acceleration and velocity RMS are only checked against their
+    // limits on `update()`.
+    pub fn push_vibration(&mut self, acc: [f32; 3]) {
+        self.vibration.push(acc);
+        self.velocity.push(acc);
+    }
+
+    // Samples of a ~1 kHz burst, see `Envelope`, after `start_envelope()`.
+    // The envelope of the last complete burst is checked on every `update()`.
+    pub fn start_envelope(&mut self) {
+        self.envelope.start();
+    }
+
+    pub fn push_envelope(&mut self, acc: [f32; 3]) {
+        self.envelope.push(acc);
+    }
+
+    // The calibrated orientation and length of the gravity vector
+    fn seed_gravity(&mut self, acc: [f32; 3]) {
+        let [x, y, z] = acc;
+        self.gravity_ref = math::sqrt(x * x + y * y + z * z);
+        self.orientation_ref = Orientation::from_acc(acc);
+    }
+
+    // Fast accelerometer samples in m/s^2, one every PEAK_PERIOD_MS.
+    // The peak delta since the last `update()` is what the mechanical limit
+    // is checked against, so short impacts between two updates still count.
+    pub fn hold_peak(&mut self, acc: [f32; 3]) {
+        let [x, y, z] = acc;
+        self.min_acc = self.min_acc.min(math::sqrt(x * x + y * y + z * z));
+
+        if !self.acc_ref.is_seeded() {
+            return;
+        }
+
+        let acc_ref = self.acc_ref.slow();
+        self.peak_hold
+            .push(core::array::from_fn(|i| acc[i] - acc_ref[i]));
+    }
+
+    // The next sample has no predecessor to compute the jerk from,
+    // called when a read fails or the sensor is re-initialized
+    pub fn sample_missed(&mut self) {
+        self.jerk.reset();
+    }
+
+    // Fast samples, one every PEAK_PERIOD_MS, for the complementary filter.
+    // `update()` feeds it as well, so a sample goes to either one.
+    pub fn fuse_orientation(&mut self, reading: &Reading) {
+        self.orientation = self
+            .orientation_filter
+            .update(reading.acc, reading.gyro, reading.t_ms);
+    }
+
+    // Compares the readings against the references.
+    // Only sudden moves should activate the buzzer. For that, the motion
+    // deltas are the divergence of a fast average of the samples from a slow
+    // one, so changing the MPU's position doesn't sound the alarm but every
+    // sample is still checked.
+    // In per-axis mode every accelerometer axis is checked, but a sample that
+    // trips more than one axis still yields a single mechanical alert.
+    // The gyroscope is always checked per axis.
+    // Temperature trips on a fast rise or on the absolute ceiling, so slow
+    // ambient warming is ignored but a hot machine is still caught.
+    // Only newly raised (or escalated) conditions produce an alert, a limit
+    // that is still latched stays quiet until it has been released.
+    // When several are raised at once, the one with the highest priority wins.
+    // Detection runs on the median-filtered sample, see `filtered()`.
+    pub fn update(&mut self, reading: &Reading) -> Option<Alert> {
+        // Temperature doesn't glitch like the accelerometer, and its limits
+        // already wait for confirmation or look at the trend
+        self.filtered = self.filter(reading);
+        let reading = self.filtered;
+        let Reading {
+            acc,
+            gyro,
+            temp,
+            t_ms,
+        } = reading;
+
+        // The very first sample only seeds the references
+        if !self.acc_ref.is_seeded() {
+            self.temp_ref = temp;
+            self.seed_gravity(acc);
+        }
+
+        self.acc_delta = self.acc_ref.update(acc);
+        self.gyro_delta = self.gyro_ref.update(gyro);
+        // The peak ends with this sample, and a new one starts
+        self.peak_hold.push(self.acc_delta);
+        self.acc_peak = self.peak_hold.take();
+        let peak = self.acc_peak.axes();
+
+        let [dx, dy, dz] = self.acc_delta;
+        self.acc_magnitude = math::sqrt(dx * dx + dy * dy + dz * dz);
+        let jerk = self.jerk.update(acc, t_ms);
+        // Unlike the motion references, the boot orientation never adapts
+        self.fuse_orientation(&reading);
+        let tilt = self.orientation.deviation(&self.orientation_ref);
+
+        let [x, y, z] = acc;
+        let magnitude = math::sqrt(x * x + y * y + z * z);
+        let min_acc = self.min_acc.min(magnitude);
+        self.min_acc = f32::MAX;
+        let detached = if self.gravity_ref > 0.0 {
+            self.detach.update(min_acc / self.gravity_ref, tilt)
+        } else {
+            self.detach.is_detached()
+        };
+        // A dangling sensor would only teach the statistics nonsense
+        self.acc_z = if detached {
+            None
+        } else {
+            self.acc_stats.push(magnitude)
+        };
+        self.temp_z = self.temp_stats.push(temp);
+        self.plausibility = match self.chip_temp {
+            Some(chip_temp) => self.cross_check.update(temp, chip_temp),
+            None => None,
+        };
+
+        self.temp_trend.push(temp);
+        self.temp_rate = self.temp_trend.rate_per_minute();
+        self.temp_forecast.push(temp);
+        self.minutes_to_ceiling = self.temp_forecast.minutes_to(
+            self.thresholds.temperature_ceiling.warning,
+            FORECAST_MIN_SPAN_MS,
+        );
+        self.vibration_rms = self.vibration.rms();
+        self.velocity_rms = self.velocity.rms();
+        self.envelope_rms = self.envelope.rms();
+        self.crest_factor = self.envelope.crest_factor();
+
+        // The speed tells a start from a stop best, the vibration of an
+        // attached sensor does without a tachometer
+        let activity = match self.speed {
+            Some(rpm) => Some(Activity::Speed(rpm)),
+            None if detached => None,
+            None => self.vibration_rms.map(Activity::Energy),
+        };
+        self.run_transition = self.run_state.update(activity, t_ms);
+        // Through a start or a stop, the motion limits are raised or off
+        let transient = self.run_state.is_transient(t_ms);
+        let suppressed = transient && self.thresholds.transient_scale.is_none();
+        let motion_levels = |levels: Levels| match self.thresholds.transient_scale {
+            Some(scale) if transient => levels.scaled_to(levels.warning * scale),
+            _ => levels,
+        };
+        let mechanical_levels = motion_levels(self.thresholds.mechanical);
+        let rotational_levels = motion_levels(self.thresholds.rotational);
+        let jerk_levels = motion_levels(self.thresholds.jerk);
+
+        let per_axis = self.thresholds.mechanical_mode == MechanicalMode::PerAxis;
+        for axis in Axis::ALL {
+            let i = axis as usize;
+            self.acc_tripped[i] = if per_axis && !suppressed {
+                mechanical_levels.severity(peak[i])
+            } else {
+                None
+            };
+            self.gyro_tripped[i] = if suppressed {
+                None
+            } else {
+                rotational_levels.severity(math::abs(self.gyro_delta[i]))
+            };
+        }
+
+        let mechanical = match self.thresholds.mechanical_mode {
+            MechanicalMode::PerAxis => self.acc_tripped.iter().copied().max().flatten(),
+            MechanicalMode::Magnitude if suppressed => None,
+            MechanicalMode::Magnitude => mechanical_levels.severity(self.acc_peak.magnitude()),
+        };
+        let rotational = self.gyro_tripped.iter().copied().max().flatten();
+        let jerk_tripped = jerk
+            .filter(|_| !suppressed)
+            .and_then(|jerk| jerk_levels.severity(jerk));
+        // The ceiling and the rate don't depend on the boot reference, so a
+        // restart on a hot machine doesn't hide a runaway
+        let ceiling = self.thresholds.temperature_ceiling.severity(temp);
+        let rate = self
+            .temp_rate
+            .and_then(|rate| self.thresholds.temperature_rate.severity(rate));
+        let floor = (temp <= TEMPERATURE_FLOOR).then_some(Severity::Warning);
+        let forecast_released = match self.minutes_to_ceiling {
+            Some(minutes) => minutes > FORECAST_HORIZON_MIN,
+            None => true,
+        };
+        let forecast = (!forecast_released).then_some(Severity::Warning);
+        self.temp_trip = [
+            (ceiling, TemperatureTrip::Ceiling),
+            (rate, TemperatureTrip::Rate),
+            (floor, TemperatureTrip::Floor),
+            (forecast, TemperatureTrip::Forecast),
+        ]
+        .into_iter()
+        .filter(|(severity, _)| severity.is_some())
+        // The first one wins a tie
+        .rev()
+        .max_by_key(|(severity, _)| *severity)
+        .map(|(_, trip)| trip);
+        let temperature = ceiling.max(rate).max(floor).max(forecast);
+        // Against the speed band's levels, or not at all
+        let speed_scale = self.speed_scale();
+        let scaled = |rms: Option<f32>| Some(rms? / speed_scale?);
+        let (vibration_rms, velocity_rms) = (scaled(self.vibration_rms), scaled(self.velocity_rms));
+        let vibration = vibration_rms.and_then(|rms| self.thresholds.vibration.severity(rms));
+        let velocity_zone = velocity_rms.map(|rms| self.thresholds.velocity_zones.zone(rms));
+        let velocity = velocity_zone.and_then(|zone| match zone {
+            Zone::A | Zone::B => None,
+            Zone::C => Some(Severity::Warning),
+            Zone::D => Some(Severity::Critical),
+        });
+        let crest_factor = match self.envelope_rms {
+            Some(rms) if rms >= ENVELOPE_MIN_RMS => self.crest_factor,
+            _ => None,
+        };
+        let bearing = crest_factor
+            .filter(|crest| *crest >= self.thresholds.crest_factor)
+            .map(|_| Severity::Warning);
+        let orientation = self.thresholds.orientation.severity(tilt);
+        let probe_rise = self.probe_rise();
+        let probe = probe_rise.and_then(|rise| self.thresholds.probe_rise.severity(rise));
+        self.knock = self.knock_pending.take();
+        let knock_peak = self.knock.map(|level| level.peak);
+        let knock = knock_peak.and_then(|peak| self.thresholds.knock.severity(peak));
+        let over_current = self
+            .current
+            .and_then(|amps| self.thresholds.over_current.severity(amps));
+        let under_current = self
+            .current
+            .filter(|amps| {
+                *amps > self.thresholds.motor_off_current && *amps < self.thresholds.under_current
+            })
+            .map(|_| Severity::Warning);
+        self.electrical_trip = match (over_current, under_current) {
+            (Some(_), _) => Some(ElectricalTrip::Over),
+            (None, Some(_)) => Some(ElectricalTrip::Under),
+            (None, None) => None,
+        };
+        let electrical = over_current.or(under_current);
+        // Statistical outliers are only a hint, never critical
+        let z_of = |z: Option<f32>| z.map_or(0.0, math::abs);
+        let (acc_z, temp_z) = (z_of(self.acc_z), z_of(self.temp_z));
+        let anomaly = (acc_z > self.thresholds.mechanical_z_limit
+            || temp_z > self.thresholds.thermal_z_limit)
+            .then_some(Severity::Warning);
+
+        // The motion readings of a detached sensor mean nothing, only the
+        // detachment itself is reported and the motion limits release.
+        // The die temperature is still good.
+        let (mechanical, rotational, jerk_tripped, vibration, velocity, bearing, orientation) =
+            if detached {
+                (None, None, None, None, None, None, None)
+            } else {
+                (
+                    mechanical,
+                    rotational,
+                    jerk_tripped,
+                    vibration,
+                    velocity,
+                    bearing,
+                    orientation,
+                )
+            };
+        let detached_tripped = detached.then_some(Severity::Critical);
+
+        let thresholds = &self.thresholds;
+        let mechanical_released = match thresholds.mechanical_mode {
+            MechanicalMode::PerAxis => peak.iter().all(|peak| mechanical_levels.is_released(*peak)),
+            MechanicalMode::Magnitude => mechanical_levels.is_released(self.acc_peak.magnitude()),
+        } || suppressed;
+        let rotational_released = self
+            .gyro_delta
+            .iter()
+            .all(|delta| rotational_levels.is_released(math::abs(*delta)))
+            || suppressed;
+        let jerk_released = match jerk {
+            Some(jerk) => jerk_levels.is_released(jerk),
+            None => true,
+        } || suppressed;
+        let rate_released = match self.temp_rate {
+            Some(rate) => thresholds.temperature_rate.is_released(rate),
+            None => true,
+        };
+        let temperature_released = thresholds.temperature_ceiling.is_released(temp)
+            && rate_released
+            && temp > TEMPERATURE_FLOOR_RELEASE
+            && forecast_released;
+        let vibration_released = match vibration_rms {
+            Some(rms) => thresholds.vibration.is_released(rms),
+            None => true,
+        };
+        let velocity_released = match velocity_rms {
+            Some(rms) => rms < thresholds.velocity_zones.bc * RELEASE_RATIO,
+            None => true,
+        };
+        let bearing_released = match crest_factor {
+            Some(crest) => crest < thresholds.crest_factor * RELEASE_RATIO,
+            None => true,
+        };
+        let orientation_released = thresholds.orientation.is_released(tilt);
+        let anomaly_released = acc_z < thresholds.mechanical_z_limit * RELEASE_RATIO
+            && temp_z < thresholds.thermal_z_limit * RELEASE_RATIO;
+        // A probe that went missing releases, its loss is reported by itself
+        let probe_released = match probe_rise {
+            Some(rise) => thresholds.probe_rise.is_released(rise),
+            None => true,
+        };
+        let knock_released = match knock_peak {
+            Some(peak) => thresholds.knock.is_released(peak),
+            None => true,
+        };
+        // Back at its load, or stopped
+        let electrical_released = match self.current {
+            Some(amps) => {
+                thresholds.over_current.is_released(amps)
+                    && (amps >= thresholds.under_current || amps <= thresholds.motor_off_current)
+            }
+            None => true,
+        };
+
+        let max_abs = |deltas: &[f32; 3]| deltas.iter().fold(0.0, |max, d| math::abs(*d).max(max));
+        self.exceeded = [
+            detached_tripped,
+            mechanical,
+            rotational,
+            jerk_tripped,
+            temperature,
+            vibration,
+            velocity,
+            bearing,
+            orientation,
+            anomaly,
+            probe,
+            knock,
+            electrical,
+        ];
+        self.readings = [
+            tilt,
+            match thresholds.mechanical_mode {
+                MechanicalMode::PerAxis => max_abs(&peak),
+                MechanicalMode::Magnitude => self.acc_peak.magnitude(),
+            },
+            max_abs(&self.gyro_delta),
+            jerk.unwrap_or(0.0),
+            temp,
+            self.vibration_rms.unwrap_or(0.0),
+            self.velocity_rms.unwrap_or(0.0),
+            self.crest_factor.unwrap_or(0.0),
+            tilt,
+            acc_z.max(temp_z),
+            probe_rise.unwrap_or(0.0),
+            knock_peak.unwrap_or(0.0),
+            self.current.unwrap_or(0.0),
+        ];
+
+        let mut alert: Option<Alert> = None;
+        for (limit, tripped, released) in [
+            (Limit::SensorDetached, detached_tripped, !detached),
+            (
+                Limit::Mechanical,
+                mechanical,
+                mechanical_released || detached,
+            ),
+            (
+                Limit::Rotational,
+                rotational,
+                rotational_released || detached,
+            ),
+            (Limit::Jerk, jerk_tripped, jerk_released || detached),
+            (Limit::Temperature, temperature, temperature_released),
+            (Limit::Vibration, vibration, vibration_released || detached),
+            (Limit::Velocity, velocity, velocity_released || detached),
+            (Limit::Bearing, bearing, bearing_released || detached),
+            (
+                Limit::Orientation,
+                orientation,
+                orientation_released || detached,
+            ),
+            (Limit::Anomaly, anomaly, anomaly_released),
+            (Limit::Probe, probe, probe_released),
+            (Limit::Knock, knock, knock_released),
+            (Limit::Electrical, electrical, electrical_released),
+        ] {
+            let raised = self.conditions[limit as usize].update(tripped, released);
+            let Some(severity) = raised else {
+                continue;
+            };
+
+            let raised = Alert { limit, severity };
+            let replaces = match alert {
+                Some(current) => raised.outranks(&current),
+                None => true,
+            };
+            if replaces {
+                alert = Some(raised);
+            }
+        }
+
+        alert
+    }
+
+    // Severity a limit is currently latched at, None once released
+    pub fn latched(&self, limit: Limit) -> Option<Severity> {
+        self.conditions[limit as usize].latched()
+    }
+
+    // Consecutive violations of a limit still waiting for confirmation,
+    // as (count, required)
+    pub fn pending(&self, limit: Limit) -> Option<(u8, u8)> {
+        self.conditions[limit as usize].pending()
+    }
+
+    // Severity reached by a limit on the last sample, before debouncing
+    pub fn exceeded(&self, limit: Limit) -> Option<Severity> {
+        self.exceeded[limit as usize]
+    }
+
+    // Value each limit was checked against on the last sample: the largest
+    // motion delta (or the magnitude) for motion, the peak one for the
+    // accelerometer, the reading for temperature, the largest z-score for
+    // anomalies
+    pub fn reading(&self, limit: Limit) -> f32 {
+        self.readings[limit as usize]
+    }
+
+    // Operator acknowledgement: no more alerts for the latched conditions
+    // until they clear and trip again
+    pub fn mute(&mut self) {
+        for condition in &mut self.conditions {
+            condition.mute();
+        }
+    }
+
+    pub fn is_muted(&self, limit: Limit) -> bool {
+        self.conditions[limit as usize].is_muted()
+    }
+
+    pub fn any_latched(&self) -> bool {
+        Limit::ALL
+            .iter()
+            .any(|limit| self.latched(*limit).is_some())
+    }
+
+    // Axes that reached at least the warning level on the last update
+    pub fn tripped_axes(&self) -> impl Iterator<Item = Axis> + '_ {
+        Axis::ALL
+            .into_iter()
+            .filter(move |axis| self.acc_tripped[*axis as usize].is_some())
+    }
+
+    // Axes that reached at least the rotational warning level on the last update
+    pub fn tripped_gyro_axes(&self) -> impl Iterator<Item = Axis> + '_ {
+        Axis::ALL
+            .into_iter()
+            .filter(move |axis| self.gyro_tripped[*axis as usize].is_some())
+    }
+
+    pub fn thresholds(&self) -> &Thresholds {
+        &self.thresholds
+    }
+
+    // Runtime tuning: moves a limit's warning level, the critical and
+    // release levels keep their ratio to it. Takes effect on the next update.
+    // Returns the new levels, or None for the limits without plain levels.
+    // The temperature limit is its rise rate.
+    pub fn set_warning(&mut self, limit: Limit, warning: f32) -> Option<Levels> {
+        let thresholds = &mut self.thresholds;
+        let levels = match limit {
+            Limit::Mechanical => &mut thresholds.mechanical,
+            Limit::Rotational => &mut thresholds.rotational,
+            Limit::Jerk => &mut thresholds.jerk,
+            Limit::Temperature => &mut thresholds.temperature_rate,
+            Limit::Vibration => &mut thresholds.vibration,
+            Limit::Orientation => &mut thresholds.orientation,
+            Limit::Probe => &mut thresholds.probe_rise,
+            Limit::Knock => &mut thresholds.knock,
+            Limit::Electrical => &mut thresholds.over_current,
+            Limit::SensorDetached | Limit::Velocity | Limit::Bearing | Limit::Anomaly => {
+                return None
+            }
+        };
+
+        *levels = levels.scaled_to(warning);
+        Some(*levels)
+    }
+
+    // The last sample `update()` checked, after the median filter
+    // The level `reading()` was checked against for `severity`, for the
+    // temperature and current that of the way they tripped. None for the
+    // limits without one.
+    pub fn threshold(&self, limit: Limit, severity: Severity) -> Option<f32> {
+        let thresholds = &self.thresholds;
+        let level = |levels: Levels| match severity {
+            Severity::Warning => levels.warning,
+            Severity::Critical => levels.critical,
+        };
+        match limit {
+            Limit::SensorDetached => None,
+            Limit::Mechanical => Some(level(thresholds.mechanical)),
+            Limit::Rotational => Some(level(thresholds.rotational)),
+            Limit::Jerk => Some(level(thresholds.jerk)),
+            Limit::Temperature => match self.temp_trip? {
+                TemperatureTrip::Ceiling => Some(level(thresholds.temperature_ceiling)),
+                TemperatureTrip::Rate => Some(level(thresholds.temperature_rate)),
+                TemperatureTrip::Floor => Some(TEMPERATURE_FLOOR),
+                TemperatureTrip::Forecast => None,
+            },
+            Limit::Vibration => Some(level(thresholds.vibration)),
+            Limit::Velocity => Some(match severity {
+                Severity::Warning => thresholds.velocity_zones.bc,
+                Severity::Critical => thresholds.velocity_zones.cd,
+            }),
+            Limit::Bearing => Some(thresholds.crest_factor),
+            Limit::Orientation => Some(level(thresholds.orientation)),
+            Limit::Anomaly => Some(thresholds.mechanical_z_limit),
+            Limit::Probe => Some(level(thresholds.probe_rise)),
+            Limit::Knock => Some(level(thresholds.knock)),
+            Limit::Electrical => match self.electrical_trip? {
+                ElectricalTrip::Over => Some(level(thresholds.over_current)),
+                ElectricalTrip::Under => Some(thresholds.under_current),
+            },
+        }
+    }
+
+    pub fn filtered(&self) -> &Reading {
+        &self.filtered
+    }
+
+    // Slow averages the motion deltas are taken against
+    pub fn acc_reference(&self) -> [f32; 3] {
+        self.acc_ref.slow()
+    }
+
+    pub fn gyro_reference(&self) -> [f32; 3] {
+        self.gyro_ref.slow()
+    }
+
+    // Fast averages, the measurement side of the motion deltas
+    pub fn acc_measurement(&self) -> [f32; 3] {
+        self.acc_ref.fast()
+    }
+
+    pub fn gyro_measurement(&self) -> [f32; 3] {
+        self.gyro_ref.fast()
+    }
+
+    // Temperature at boot, the limits don't depend on it
+    pub fn temp_reference(&self) -> f32 {
+        self.temp_ref
+    }
+
+    // The external probe's last temperature and its reference, in ºC
+    pub fn probe(&self) -> Option<f32> {
+        self.probe
+    }
+
+    pub fn probe_reference(&self) -> Option<f32> {
+        self.probe_ref
+    }
+
+    // How far the probe is over its reference, what its limit checks
+    pub fn probe_rise(&self) -> Option<f32> {
+        Some(self.probe? - self.probe_ref?)
+    }
+
+    // The knock burst the last `update()` checked, None if there was none
+    pub fn chip_temp(&self) -> Option<f32> {
+        self.chip_temp
+    }
+
+    pub fn cross_check(&self) -> &CrossCheck {
+        &self.cross_check
+    }
+
+    // Whether the last update found the MPU's temperature newly out of
+    // line with the chip's, or back in line
+    pub fn plausibility(&self) -> Option<Plausibility> {
+        self.plausibility
+    }
+
+    pub fn knock(&self) -> Option<KnockLevel> {
+        self.knock
+    }
+
+    pub fn acc_delta(&self) -> [f32; 3] {
+        self.acc_delta
+    }
+
+    // Length of the accelerometer delta vector on the last update
+    pub fn acc_magnitude(&self) -> f32 {
+        self.acc_magnitude
+    }
+
+    // Largest accelerometer deltas between the last two updates
+    pub fn acc_peak(&self) -> &PeakHold {
+        &self.acc_peak
+    }
+
+    pub fn gyro_delta(&self) -> [f32; 3] {
+        self.gyro_delta
+    }
+
+    // Jerk in m/s^3 on the last update, None without a previous sample
+    pub fn jerk(&self) -> Option<f32> {
+        self.jerk.value()
+    }
+
+    // Temperature rate of change in ºC/minute, None while the window fills up
+    pub fn temp_rate(&self) -> Option<f32> {
+        self.temp_rate
+    }
+
+    // Projected minutes until the warning ceiling, None while cooling, steady
+    // or with under a minute of data
+    pub fn minutes_to_ceiling(&self) -> Option<f32> {
+        self.minutes_to_ceiling
+    }
+
+    // What exceeded the temperature limit on the last update, if anything
+    pub fn temperature_trip(&self) -> Option<TemperatureTrip> {
+        self.temp_trip
+    }
+
+    pub fn current(&self) -> Option<f32> {
+        self.current
+    }
+
+    pub fn speed(&self) -> Option<f32> {
+        self.speed
+    }
+
+    // What the vibration levels are scaled by at this speed, None while
+    // its band has them off. 1 without a speed sensor.
+    pub fn speed_scale(&self) -> Option<f32> {
+        let Some(rpm) = self.speed else {
+            return Some(1.0);
+        };
+        self.thresholds
+            .speed_bands
+            .iter()
+            .rev()
+            .find(|band| rpm >= band.from_rpm)
+            .and_then(|band| band.scale)
+    }
+
+    // Where the machine is between its starts and stops, and the transition
+    // the last update made, if any
+    pub fn run_state(&self) -> RunState {
+        self.run_state.state()
+    }
+
+    pub fn run_transition(&self) -> Option<RunTransition> {
+        self.run_transition
+    }
+
+    // True while a start or a stop has the motion limits raised or off
+    pub fn is_transient(&self) -> bool {
+        self.run_state.is_transient(self.filtered.t_ms)
+    }
+
+    pub fn electrical_trip(&self) -> Option<ElectricalTrip> {
+        self.electrical_trip
+    }
+
+    // True from the moment the sensor came off until the next calibration
+    pub fn is_detached(&self) -> bool {
+        self.detach.is_detached()
+    }
+
+    // Fused pitch and roll as of the last sample
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn orientation_reference(&self) -> Orientation {
+        self.orientation_ref
+    }
+
+    // Running mean and standard deviation of the acceleration magnitude,
+    // in m/s^2, and of the temperature
+    pub fn acc_stats(&self) -> &RunningStats {
+        &self.acc_stats
+    }
+
+    pub fn temp_stats(&self) -> &RunningStats {
+        &self.temp_stats
+    }
+
+    // z-scores of the last sample, None while the statistics warm up
+    pub fn acc_z(&self) -> Option<f32> {
+        self.acc_z
+    }
+
+    pub fn temp_z(&self) -> Option<f32> {
+        self.temp_z
+    }
+
+    // Vibration RMS in m/s^2 as of the last update, None while the window fills up
+    pub fn vibration_rms(&self) -> Option<f32> {
+        self.vibration_rms
+    }
+
+    // Vibration velocity RMS in mm/s as of the last update, and its zone
+    pub fn velocity_rms(&self) -> Option<f32> {
+        self.velocity_rms
+    }
+
+    // RMS in m/s^2 and crest factor of the last complete envelope burst
+    pub fn envelope_rms(&self) -> Option<f32> {
+        self.envelope_rms
+    }
+
+    pub fn crest_factor(&self) -> Option<f32> {
+        self.crest_factor
+    }
+
+    pub fn velocity_zone(&self) -> Option<Zone> {
+        self.velocity_rms
+            .map(|rms| self.thresholds.velocity_zones.zone(rms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{ENVELOPE_SETTLE_SAMPLES, ENVELOPE_WINDOW};
+    use crate::median::MEDIAN_LENGTH;
+    use crate::velocity::VELOCITY_WINDOW;
+    use crate::vibration::RMS_WINDOW;
+
+    const GRAVITY: [f32; 3] = [0.0, 0.0, 1.0];
+    const STILL: [f32; 3] = [0.0; 3];
+    const ROOM_TEMP: f32 = 25.0;
+
+    fn monitor() -> MaintenanceMonitor {
+        MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS)
+    }
+
+    fn sample(acc: [f32; 3], gyro: [f32; 3], temp: f32) -> Reading {
+        Reading::new(acc, gyro, temp, 0)
+    }
+
+    // Like the firmware, feeds the reading through the median as fast
+    // samples before the update, so the filter already holds it
+    fn held(monitor: &mut MaintenanceMonitor, reading: &Reading) -> Option<Alert> {
+        for _ in 1..MEDIAN_LENGTH {
+            monitor.filter(reading);
+        }
+        monitor.update(reading)
+    }
+
+    // Feeds the same sample until the limit is confirmed,
+    // returning the outcome of the last one
+    fn confirmed(
+        monitor: &mut MaintenanceMonitor,
+        limit: Limit,
+        acc: [f32; 3],
+        temp: f32,
+    ) -> Option<Alert> {
+        for _ in 1..monitor.thresholds().confirmations(limit) {
+            assert_eq!(monitor.update(&sample(acc, STILL, temp)), None);
+        }
+        monitor.update(&sample(acc, STILL, temp))
+    }
+
+    #[test]
+    fn first_sample_only_seeds_the_reference() {
+        let mut monitor = monitor();
+
+        assert_eq!(
+            monitor.update(&sample([5.0, 0.0, 1.0], STILL, ROOM_TEMP)),
+            None
+        );
+        assert_eq!(monitor.acc_reference(), [5.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn calibrated_reference_replaces_the_first_sample() {
+        let mut monitor = monitor();
+        monitor.set_reference([0.0, 0.0, 1.0], STILL, 24.0);
+
+        // Far from the reference but not a seed anymore
+        assert_eq!(
+            monitor.update(&sample([1.0, 0.0, 1.0], STILL, ROOM_TEMP)),
+            None
+        );
+        assert_eq!(
+            monitor.exceeded(Limit::Mechanical),
+            Some(Severity::Critical)
+        );
+        assert_eq!(monitor.temp_reference(), 24.0);
+    }
+
+    #[test]
+    fn warning_level_can_be_moved_at_runtime() {
+        let mut monitor = monitor();
+        monitor.set_reference([0.0, 0.0, 1.0], STILL, 24.0);
+
+        let levels = monitor.set_warning(Limit::Mechanical, 1.0).unwrap();
+        assert_eq!(levels.critical, 1.6);
+        assert_eq!(levels.release, RELEASE_RATIO);
+        assert_eq!(monitor.thresholds().mechanical, levels);
+
+        // ~1.2 m/s^2 after the fast average, critical before
+        monitor.update(&sample([1.5, 0.0, 1.0], STILL, ROOM_TEMP));
+        assert_eq!(monitor.exceeded(Limit::Mechanical), Some(Severity::Warning));
+
+        assert_eq!(monitor.set_warning(Limit::Anomaly, 1.0), None);
+    }
+
+    #[test]
+    fn slow_tilt_only_trips_the_orientation_limit() {
+        let mut monitor = monitor();
+
+        // Rotate gravity 30º from Z towards X over 200 samples (~100 s)
+        for step in 0..=200 {
+            let angle = step as f32 / 200.0 * core::f32::consts::FRAC_PI_6;
+            let acc = [libm::sinf(angle), 0.0, libm::cosf(angle)];
+
+            let alert = monitor.update(&sample(acc, STILL, ROOM_TEMP));
+            assert!(
+                matches!(
+                    alert,
+                    None | Some(Alert {
+                        limit: Limit::Orientation,
+                        ..
+                    })
+                ),
+                "step {}",
+                step
+            );
+        }
+        assert_eq!(
+            monitor.latched(Limit::Orientation),
+            Some(Severity::Critical)
+        );
+    }
+
+    #[test]
+    fn sharp_tap_alarms_on_every_loop_phase() {
+        for phase in 0..4 {
+            let mut monitor = monitor();
+
+            // The seeding sample is the only one not checked
+            for _ in 0..=phase {
+                assert_eq!(monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP)), None);
+            }
+
+            let tap = sample([1.2, 0.0, 1.0], STILL, ROOM_TEMP);
+            for _ in 1..monitor.thresholds().confirmations(Limit::Mechanical) {
+                assert_eq!(held(&mut monitor, &tap), None);
+            }
+            assert_eq!(
+                held(&mut monitor, &tap).map(|alert| alert.limit),
+                Some(Limit::Mechanical),
+                "phase {}",
+                phase
+            );
+        }
+    }
+
+    #[test]
+    fn single_sample_spike_is_ignored() {
+        let mut monitor = monitor();
+        monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+
+        assert_eq!(
+            monitor.update(&sample([2.0, 0.0, 1.0], STILL, ROOM_TEMP)),
+            None
+        );
+        assert_eq!(
+            monitor.pending(Limit::Mechanical),
+            Some((1, MECHANICAL_CONFIRMATIONS))
+        );
+
+        assert_eq!(monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP)), None);
+        assert_eq!(monitor.pending(Limit::Mechanical), None);
+        assert!(!monitor.any_latched());
+    }
+
+    #[test]
+    fn delta_between_levels_is_a_warning() {
+        let mut monitor = monitor();
+        monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+
+        // The fast average reaches 80% of the step on the first sample
+        assert_eq!(
+            confirmed(&mut monitor, Limit::Mechanical, [0.7, 0.0, 1.0], ROOM_TEMP),
+            Some(Alert {
+                limit: Limit::Mechanical,
+                severity: Severity::Warning,
+            })
+        );
+    }
+
+    #[test]
+    fn jump_past_both_levels_is_only_critical() {
+        let mut monitor = monitor();
+        monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+
+        assert_eq!(
+            confirmed(&mut monitor, Limit::Mechanical, [2.0, 0.0, 1.0], ROOM_TEMP),
+            Some(Alert {
+                limit: Limit::Mechanical,
+                severity: Severity::Critical,
+            })
+        );
+    }
+
+    #[test]
+    fn latched_condition_does_not_retrigger() {
+        let mut monitor = monitor();
+
+        assert!(confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0).is_some());
+        // Hovering around the trip point
+        assert_eq!(monitor.update(&sample(GRAVITY, STILL, 69.5)), None);
+        for _ in 0..TEMPERATURE_CONFIRMATIONS {
+            assert_eq!(monitor.update(&sample(GRAVITY, STILL, 71.0)), None);
+        }
+        assert_eq!(monitor.latched(Limit::Temperature), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn latched_condition_escalates_to_critical() {
+        let mut monitor = monitor();
+
+        assert!(confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0).is_some());
+        assert_eq!(
+            monitor.update(&sample(GRAVITY, STILL, 81.0)),
+            Some(Alert {
+                limit: Limit::Temperature,
+                severity: Severity::Critical,
+            })
+        );
+    }
+
+    #[test]
+    fn condition_clears_after_consecutive_released_samples() {
+        let mut monitor = monitor();
+        confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0);
+
+        for _ in 0..RELEASE_SAMPLES - 1 {
+            monitor.update(&sample(GRAVITY, STILL, 60.0));
+        }
+        // A sample between the release and trip levels restarts the count
+        monitor.update(&sample(GRAVITY, STILL, 68.0));
+        for _ in 0..RELEASE_SAMPLES - 1 {
+            monitor.update(&sample(GRAVITY, STILL, 60.0));
+        }
+        assert!(monitor.any_latched());
+
+        monitor.update(&sample(GRAVITY, STILL, 60.0));
+        assert!(!monitor.any_latched());
+        assert!(confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0).is_some());
+    }
+
+    #[test]
+    fn muted_condition_stays_quiet_until_it_clears() {
+        let mut monitor = monitor();
+        assert!(confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0).is_some());
+
+        monitor.mute();
+        assert!(monitor.is_muted(Limit::Temperature));
+        assert_eq!(monitor.latched(Limit::Temperature), Some(Severity::Warning));
+
+        for _ in 0..RELEASE_SAMPLES {
+            monitor.update(&sample(GRAVITY, STILL, 60.0));
+        }
+        assert!(!monitor.is_muted(Limit::Temperature));
+        assert!(confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0).is_some());
+    }
+
+    #[test]
+    fn muted_condition_still_alerts_on_escalation() {
+        let mut monitor = monitor();
+        confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0);
+        monitor.mute();
+
+        assert_eq!(monitor.update(&sample(GRAVITY, STILL, 72.0)), None);
+        assert_eq!(
+            monitor
+                .update(&sample(GRAVITY, STILL, 81.0))
+                .map(|alert| alert.severity),
+            Some(Severity::Critical)
+        );
+    }
+
+    #[test]
+    fn sustained_vibration_alarms_below_the_mechanical_limit() {
+        let mut monitor = monitor();
+        monitor.set_reference([0.0, 0.0, 9.81], STILL, ROOM_TEMP);
+
+        // 0.5 m/s^2 peak at 10 Hz, under the mechanical warning level
+        let mut alerts = 0;
+        for n in 0..RMS_WINDOW * 2 {
+            let t = n as f32 * VIBRATION_PERIOD_MS as f32 / 1000.0;
+            let wave = 0.5 * libm::sinf(2.0 * core::f32::consts::PI * 10.0 * t);
+            let acc = [0.0, 0.0, 9.81 + wave];
+            monitor.push_vibration(acc);
+
+            if n % 25 == 0 {
+                let alert = monitor.update(&sample(acc, STILL, ROOM_TEMP));
+                assert_eq!(monitor.exceeded(Limit::Mechanical), None);
+                // At 10 Hz this is also zone C velocity, see below
+                if let Some(alert) = alert.filter(|alert| alert.limit != Limit::Velocity) {
+                    assert_eq!(alert.limit, Limit::Vibration);
+                    assert_eq!(alert.severity, Severity::Warning);
+                    alerts += 1;
+                }
+            }
+        }
+        assert_eq!(alerts, 1);
+        assert!(monitor.vibration_rms().unwrap() > RMS_LIMIT_WARNING);
+    }
+
+    #[test]
+    fn vibration_limits_follow_the_speed_band() {
+        let mut monitor = monitor();
+        monitor.set_reference([0.0, 0.0, 9.81], STILL, ROOM_TEMP);
+        assert_eq!(monitor.speed_scale(), Some(1.0));
+
+        // The vibration of the test above, on the startup ramp
+        monitor.set_speed(Some(150.0));
+        assert_eq!(monitor.speed_scale(), None);
+        for n in 0..RMS_WINDOW * 2 {
+            let t = n as f32 * VIBRATION_PERIOD_MS as f32 / 1000.0;
+            let wave = 0.5 * libm::sinf(2.0 * core::f32::consts::PI * 10.0 * t);
+            let acc = [0.0, 0.0, 9.81 + wave];
+            monitor.push_vibration(acc);
+            if n % 25 == 0 {
+                assert_eq!(monitor.update(&sample(acc, STILL, ROOM_TEMP)), None);
+            }
+        }
+        assert!(monitor.vibration_rms().unwrap() > RMS_LIMIT_WARNING);
+
+        // Up to speed, it counts
+        monitor.set_speed(Some(1_450.0));
+        assert_eq!(monitor.speed_scale(), Some(1.0));
+        let alerts: Vec<Limit> = (0..VIBRATION_CONFIRMATIONS.max(VELOCITY_CONFIRMATIONS))
+            .filter_map(|_| monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP)))
+            .map(|alert| alert.limit)
+            .collect();
+        assert!(alerts.contains(&Limit::Vibration), "{:?}", alerts);
+        monitor.set_speed(Some(3_600.0));
+        assert_eq!(monitor.speed_scale(), Some(1.5));
+    }
+
+    #[test]
+    fn motion_limits_are_raised_through_a_start() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+        let shake = |t_ms| Reading::new([0.9, 0.0, 1.0], STILL, ROOM_TEMP, t_ms);
+        let rest = |t_ms| Reading::new(GRAVITY, STILL, ROOM_TEMP, t_ms);
+
+        monitor.set_speed(Some(0.0));
+        held(&mut monitor, &rest(0));
+        assert_eq!(monitor.run_state(), RunState::Stopped);
+
+        // The inrush shake on the way up, well past the warning level
+        monitor.set_speed(Some(120.0));
+        assert_eq!(held(&mut monitor, &shake(500)), None);
+        assert_eq!(
+            monitor.run_transition(),
+            Some(RunTransition {
+                from: RunState::Stopped,
+                to: RunState::Starting
+            })
+        );
+        assert!(monitor.is_transient());
+        for t_ms in [1_000, 1_500, 2_000] {
+            assert_eq!(held(&mut monitor, &shake(t_ms)), None);
+        }
+        assert_eq!(monitor.latched(Limit::Mechanical), None);
+
+        // Up to speed, the same shake counts
+        monitor.set_speed(Some(1_450.0));
+        held(&mut monitor, &rest(2_500));
+        assert_eq!(monitor.run_state(), RunState::Running);
+        assert!(!monitor.is_transient());
+        let alerts: Vec<Limit> = (0..MECHANICAL_CONFIRMATIONS as u64)
+            .filter_map(|i| held(&mut monitor, &shake(3_000 + i * 500)))
+            .map(|alert| alert.limit)
+            .collect();
+        assert!(alerts.contains(&Limit::Mechanical), "{:?}", alerts);
+    }
+
+    #[test]
+    fn vibration_velocity_across_gravity_is_zone_d() {
+        let mut monitor = monitor();
+        monitor.set_reference([0.0, 0.0, 9.81], STILL, ROOM_TEMP);
+
+        // 0.4 m/s^2 at 5 Hz sideways, ~8 mm/s. It hardly changes the
+        // magnitude of the acceleration, the vibration RMS misses it.
+        let mut alerts = 0;
+        for n in 0..VELOCITY_WINDOW * 4 {
+            let t = n as f32 * VIBRATION_PERIOD_MS as f32 / 1000.0;
+            let wave = 0.4 * libm::sinf(2.0 * core::f32::consts::PI * 5.0 * t);
+            let acc = [wave, 0.0, 9.81];
+            monitor.push_vibration(acc);
+
+            if n % 25 == 0 {
+                if let Some(alert) = monitor.update(&sample(acc, STILL, ROOM_TEMP)) {
+                    assert_eq!(
+                        alert,
+                        Alert {
+                            limit: Limit::Velocity,
+                            severity: Severity::Critical,
+                        }
+                    );
+                    alerts += 1;
+                }
+            }
+        }
+        assert_eq!(alerts, 1);
+        assert_eq!(monitor.velocity_zone(), Some(Zone::D));
+        assert_eq!(monitor.latched(Limit::Vibration), None);
+    }
+
+    #[test]
+    fn ringing_bursts_are_a_bearing_warning() {
+        let mut monitor = monitor();
+        monitor.set_reference([0.0, 0.0, 9.81], STILL, ROOM_TEMP);
+
+        // 5 ms of ringing at 300 Hz every 40 ms, at 1 kHz
+        monitor.start_envelope();
+        for n in 0..ENVELOPE_SETTLE_SAMPLES + ENVELOPE_WINDOW {
+            let t = n as f32 / SPECTRUM_RATE_HZ as f32;
+            let ringing = 2.0 * libm::sinf(2.0 * core::f32::consts::PI * 300.0 * t);
+            let wave = if n % 40 < 5 { ringing } else { 0.0 };
+            monitor.push_envelope([0.0, 0.0, 9.81 + wave]);
+        }
+
+        let still = sample([0.0, 0.0, 9.81], STILL, ROOM_TEMP);
+        assert_eq!(monitor.update(&still), None);
+        assert_eq!(
+            monitor.update(&still),
+            Some(Alert {
+                limit: Limit::Bearing,
+                severity: Severity::Warning,
+            })
+        );
+        assert!(monitor.reading(Limit::Bearing) > CREST_FACTOR_LIMIT);
+    }
+
+    #[test]
+    fn impact_between_updates_is_checked_at_its_peak() {
+        let mut monitor = monitor();
+        monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+
+        for _ in 0..MECHANICAL_CONFIRMATIONS {
+            monitor.hold_peak(GRAVITY);
+            monitor.hold_peak([0.0, -1.5, 1.0]);
+            monitor.hold_peak(GRAVITY);
+            monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+
+            assert_eq!(monitor.acc_delta(), [0.0; 3]);
+            assert_eq!(monitor.acc_peak().axes(), [0.0, 1.5, 0.0]);
+        }
+        assert_eq!(monitor.latched(Limit::Mechanical), Some(Severity::Critical));
+
+        // Reset after each update
+        monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        assert_eq!(monitor.acc_peak().axes(), [0.0; 3]);
+    }
+
+    #[test]
+    fn electrical_glitch_never_reaches_the_detectors() {
+        let mut monitor = monitor();
+        let still = sample(GRAVITY, STILL, ROOM_TEMP);
+        held(&mut monitor, &still);
+
+        for acc in [GRAVITY, [40.0, 0.0, 1.0], GRAVITY, GRAVITY] {
+            let fast = monitor.filter(&sample(acc, STILL, ROOM_TEMP));
+            monitor.hold_peak(fast.acc);
+        }
+        let glitch = sample([0.0, -40.0, 1.0], STILL, ROOM_TEMP);
+        assert_eq!(monitor.update(&glitch), None);
+
+        assert_eq!(monitor.filtered().acc, GRAVITY);
+        assert_eq!(monitor.acc_peak().axes(), [0.0; 3]);
+    }
+
+    #[test]
+    fn sample_after_a_missed_one_has_no_jerk() {
+        let mut monitor = monitor();
+        monitor.update(&Reading::new(GRAVITY, STILL, ROOM_TEMP, 0));
+
+        monitor.sample_missed();
+        // Would be 4 m/s^3 against the sample before the gap
+        assert_eq!(
+            held(
+                &mut monitor,
+                &Reading::new([0.0, 0.4, 1.0], STILL, ROOM_TEMP, 100)
+            ),
+            None
+        );
+        assert_eq!(monitor.jerk(), None);
+
+        assert_eq!(
+            held(
+                &mut monitor,
+                &Reading::new([0.0, -0.6, 1.0], STILL, ROOM_TEMP, 600)
+            ),
+            Some(Alert {
+                limit: Limit::Jerk,
+                severity: Severity::Warning,
+            })
+        );
+        assert_eq!(monitor.jerk(), Some(2.0));
+    }
+
+    #[test]
+    fn lasting_tilt_alarms_but_momentary_tilt_does_not() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+
+        let angle = 7f32.to_radians();
+        let tilted = [libm::sinf(angle), 0.0, libm::cosf(angle)];
+
+        // One status update with the fast samples in between, at 100 Hz
+        let mut t_ms = 0;
+        let mut status = |monitor: &mut MaintenanceMonitor, acc| {
+            for _ in 1..SAMPLE_PERIOD_MS / 10 {
+                t_ms += 10;
+                monitor.fuse_orientation(&Reading::new(acc, STILL, ROOM_TEMP, t_ms));
+            }
+            t_ms += 10;
+            monitor.update(&Reading::new(acc, STILL, ROOM_TEMP, t_ms))
+        };
+
+        // Shaken for a couple of seconds, then back
+        for _ in 0..ORIENTATION_CONFIRMATIONS / 2 {
+            status(&mut monitor, tilted);
+        }
+        status(&mut monitor, GRAVITY);
+        assert_eq!(monitor.pending(Limit::Orientation), None);
+
+        let mut alert = None;
+        for _ in 0..ORIENTATION_CONFIRMATIONS {
+            alert = alert.or(status(&mut monitor, tilted));
+        }
+        assert_eq!(monitor.latched(Limit::Orientation), Some(Severity::Warning));
+        assert!(monitor.orientation().pitch < -6.9);
+        assert_eq!(monitor.orientation_reference(), Orientation::default());
+        assert!(alert.is_some());
+    }
+
+    #[test]
+    fn free_fall_reports_detachment_instead_of_shock() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+
+        monitor.hold_peak([0.0, 0.0, 0.05]);
+        assert_eq!(
+            monitor.update(&sample([0.0, 0.9, 0.4], STILL, ROOM_TEMP)),
+            Some(Alert {
+                limit: Limit::SensorDetached,
+                severity: Severity::Critical,
+            })
+        );
+        assert_eq!(monitor.exceeded(Limit::Mechanical), None);
+
+        // Remounted, it stays detached until calibrated again
+        for _ in 0..RELEASE_SAMPLES * 2 {
+            monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        }
+        assert!(monitor.is_detached());
+        assert_eq!(
+            monitor.latched(Limit::SensorDetached),
+            Some(Severity::Critical)
+        );
+
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+        assert!(!monitor.is_detached());
+        assert_eq!(monitor.latched(Limit::SensorDetached), None);
+    }
+
+    #[test]
+    fn sensor_hanging_upside_down_is_detached() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+
+        // The fall itself may trip the mechanical limit before it settles
+        let mut detached = false;
+        for n in 1..=10 {
+            let hanging = Reading::new([0.0, 0.0, -1.0], STILL, ROOM_TEMP, n * 500);
+            if let Some(alert) = monitor.update(&hanging) {
+                detached |= alert.limit == Limit::SensorDetached;
+            }
+        }
+        assert!(detached);
+        assert_eq!(monitor.latched(Limit::Mechanical), None);
+    }
+
+    #[test]
+    fn ceiling_trips_regardless_of_the_boot_temperature() {
+        let mut monitor = monitor();
+        // Restarted on a machine that was already hot
+        monitor.set_reference(GRAVITY, STILL, 68.0);
+
+        assert!(confirmed(&mut monitor, Limit::Temperature, GRAVITY, 71.0).is_some());
+        assert_eq!(monitor.temperature_trip(), Some(TemperatureTrip::Ceiling));
+    }
+
+    #[test]
+    fn probe_rise_trips_its_own_limit() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+        monitor.set_probe(Some(30.0));
+        assert_eq!(monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP)), None);
+        assert_eq!(monitor.probe_reference(), Some(30.0));
+
+        // The die stays at room temperature, the bearing heats up
+        monitor.set_probe(Some(46.0));
+        let alert = confirmed(&mut monitor, Limit::Probe, GRAVITY, ROOM_TEMP);
+        assert_eq!(
+            alert,
+            Some(Alert {
+                limit: Limit::Probe,
+                severity: Severity::Warning,
+            })
+        );
+        assert_eq!(monitor.reading(Limit::Probe), 16.0);
+        assert_eq!(monitor.latched(Limit::Temperature), None);
+
+        // A lost probe releases the limit
+        monitor.set_probe(None);
+        for _ in 0..RELEASE_SAMPLES {
+            monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        }
+        assert_eq!(monitor.latched(Limit::Probe), None);
+        assert_eq!(monitor.probe_rise(), None);
+    }
+
+    #[test]
+    fn loudest_knock_between_samples_is_checked() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+        let knock = |peak| KnockLevel {
+            peak,
+            rms: peak / 4.0,
+            samples: 128,
+        };
+
+        let mut alert = None;
+        for _ in 0..KNOCK_CONFIRMATIONS {
+            // A single loud burst among quiet ones is enough
+            for peak in [80.0, 1_300.0, 90.0] {
+                monitor.push_knock(knock(peak));
+            }
+            alert = monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        }
+        assert_eq!(
+            alert,
+            Some(Alert {
+                limit: Limit::Knock,
+                severity: Severity::Critical,
+            })
+        );
+        assert_eq!(monitor.reading(Limit::Knock), 1_300.0);
+        assert_eq!(monitor.knock().map(|level| level.rms), Some(325.0));
+
+        // No bursts, as with the channel off, releases it
+        for _ in 0..RELEASE_SAMPLES {
+            monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        }
+        assert_eq!(monitor.latched(Limit::Knock), None);
+        assert_eq!(monitor.knock(), None);
+    }
+
+    #[test]
+    fn current_out_of_band_trips_the_electrical_limit() {
+        let mut monitor = monitor();
+        monitor.set_reference(GRAVITY, STILL, ROOM_TEMP);
+
+        // Stopped, and running at its load
+        for amps in [0.0, 4.0] {
+            monitor.set_current(Some(amps));
+            assert_eq!(
+                confirmed(&mut monitor, Limit::Electrical, GRAVITY, ROOM_TEMP),
+                None
+            );
+        }
+
+        // The belt snaps, the motor runs on at its no-load current
+        monitor.set_current(Some(0.6));
+        let alert = confirmed(&mut monitor, Limit::Electrical, GRAVITY, ROOM_TEMP);
+        assert_eq!(
+            alert,
+            Some(Alert {
+                limit: Limit::Electrical,
+                severity: Severity::Warning,
+            })
+        );
+        assert_eq!(monitor.electrical_trip(), Some(ElectricalTrip::Under));
+        // Switched off, it releases
+        monitor.set_current(Some(0.1));
+        for _ in 0..RELEASE_SAMPLES {
+            monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        }
+        assert_eq!(monitor.latched(Limit::Electrical), None);
+
+        // Seizing
+        monitor.set_current(Some(13.0));
+        let alert = confirmed(&mut monitor, Limit::Electrical, GRAVITY, ROOM_TEMP);
+        assert_eq!(alert.map(|alert| alert.severity), Some(Severity::Critical));
+        assert_eq!(monitor.electrical_trip(), Some(ElectricalTrip::Over));
+        assert_eq!(monitor.reading(Limit::Electrical), 13.0);
+    }
+
+    #[test]
+    fn steady_rise_towards_the_ceiling_is_forecast() {
+        let mut monitor = monitor();
+
+        // 1 ºC/minute from 58 ºC, under the rate limit
+        let mut alert = None;
+        for i in 0..=FORECAST_WINDOW {
+            let temp = 58.0 + i as f32 * SAMPLE_PERIOD_MS as f32 / 60_000.0;
+            alert = alert.or(monitor.update(&sample(GRAVITY, STILL, temp)));
+        }
+
+        assert_eq!(
+            alert,
+            Some(Alert {
+                limit: Limit::Temperature,
+                severity: Severity::Warning,
+            })
+        );
+        assert_eq!(monitor.temperature_trip(), Some(TemperatureTrip::Forecast));
+        // 61 ºC after 3 minutes
+        let minutes = monitor.minutes_to_ceiling().unwrap();
+        assert!((minutes - 9.0).abs() < 0.1, "minutes {}", minutes);
+    }
+
+    #[test]
+    fn implausibly_cold_sensor_is_a_warning() {
+        let mut monitor = monitor();
+
+        assert_eq!(
+            confirmed(&mut monitor, Limit::Temperature, GRAVITY, -40.0),
+            Some(Alert {
+                limit: Limit::Temperature,
+                severity: Severity::Warning,
+            })
+        );
+        assert_eq!(monitor.temperature_trip(), Some(TemperatureTrip::Floor));
+
+        for _ in 0..RELEASE_SAMPLES {
+            monitor.update(&sample(GRAVITY, STILL, -18.0));
+        }
+        assert!(monitor.any_latched());
+    }
+
+    #[test]
+    fn outlier_against_the_running_statistics_is_an_anomaly() {
+        let mut monitor = monitor();
+
+        for _ in 0..=crate::zscore::Z_MIN_SAMPLES {
+            assert_eq!(monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP)), None);
+        }
+        assert_eq!(monitor.temp_z(), Some(0.0));
+
+        // Nowhere near any temperature limit, just unlike the last minute
+        assert_eq!(
+            confirmed(&mut monitor, Limit::Anomaly, GRAVITY, ROOM_TEMP + 2.0),
+            Some(Alert {
+                limit: Limit::Anomaly,
+                severity: Severity::Warning,
+            })
+        );
+        assert!(monitor.reading(Limit::Anomaly) > THERMAL_Z_LIMIT);
+        assert_eq!(monitor.exceeded(Limit::Temperature), None);
+
+        for _ in 0..RELEASE_SAMPLES {
+            monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+        }
+        assert!(!monitor.any_latched());
+    }
+
+    #[test]
+    fn critical_temperature_beats_motion_warning() {
+        let mut monitor = monitor();
+        monitor.update(&sample(GRAVITY, STILL, ROOM_TEMP));
+
+        assert_eq!(
+            confirmed(&mut monitor, Limit::Temperature, [0.6, 0.0, 1.0], 85.0),
+            Some(Alert {
+                limit: Limit::Temperature,
+                severity: Severity::Critical,
+            })
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::sensor::STANDARD_GRAVITY;
+
+// The MPU6050 motion detector compares every accelerometer sample, after
+// its high-pass filter, against MOT_THR and raises INT once it's been
+// above for MOT_DUR samples. It's a much blunter check than the monitor's,
+// it only decides when to take a sample early.
+// MOT_THR counts 2 mg, MOT_DUR 1 ms at the 1 kHz accelerometer rate.
+pub const MOT_THR_LSB_MG: f32 = 2.0;
+pub const MOTION_DURATION_MS: u8 = 1;
+
+// Back-to-back reads after a trigger, enough to get through the median filter
+pub const MOTION_BURST_SAMPLES: usize = 8;
+
+// A machine vibrating right at the threshold would trigger all the time,
+// the triggered samples are at least this far apart from any other
+pub const MOTION_HOLDOFF_MS: u32 = 250;
+
+// MOT_THR for a limit in m/s^2, saturating at the largest threshold
+// (about 5 m/s^2). Never 0, that would trigger on every sample.
+pub fn motion_threshold(limit_ms2: f32) -> u8 {
+    let counts = limit_ms2 / STANDARD_GRAVITY * 1000.0 / MOT_THR_LSB_MG;
+    (counts + 0.5).clamp(1.0, u8::MAX as f32) as u8
+}
+
+// Decides whether an INT edge gets a sample of its own
+pub struct MotionTrigger {
+    holdoff_ms: u32,
+    last_sample_ms: Option<u32>,
+    triggered: u32,
+}
+
+impl MotionTrigger {
+    pub const fn new(holdoff_ms: u32) -> Self {
+        Self {
+            holdoff_ms,
+            last_sample_ms: None,
+            triggered: 0,
+        }
+    }
+
+    // A regular sample was taken
+    pub fn sampled(&mut self, now_ms: u32) {
+        self.last_sample_ms = Some(now_ms);
+    }
+
+    // Returns true if the edge should be sampled right away. An edge within
+    // the holdoff is dropped, the peak hold still catches that motion.
+    pub fn take(&mut self, edge: bool, now_ms: u32) -> bool {
+        if !edge {
+            return false;
+        }
+        if let Some(last) = self.last_sample_ms {
+            if now_ms.wrapping_sub(last) < self.holdoff_ms {
+                return false;
+            }
+        }
+
+        self.last_sample_ms = Some(now_ms);
+        self.triggered = self.triggered.wrapping_add(1);
+        true
+    }
+
+    // Triggered samples since boot
+    pub fn triggered(&self) -> u32 {
+        self.triggered
+    }
+}
+
+impl Default for MotionTrigger {
+    fn default() -> Self {
+        Self::new(MOTION_HOLDOFF_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::MECHANICAL_WARNING;
+
+    #[test]
+    fn threshold_follows_the_mechanical_limit() {
+        // 0.5 m/s^2 is ~51 mg
+        assert_eq!(motion_threshold(MECHANICAL_WARNING), 25);
+        assert_eq!(motion_threshold(0.0), 1);
+        assert_eq!(motion_threshold(100.0), u8::MAX);
+    }
+
+    #[test]
+    fn edges_close_to_a_sample_are_dropped() {
+        let mut trigger = MotionTrigger::new(250);
+
+        assert!(!trigger.take(false, 0));
+        assert!(trigger.take(true, 0));
+        assert!(!trigger.take(true, 100));
+
+        trigger.sampled(500);
+        assert!(!trigger.take(true, 600));
+        assert!(trigger.take(true, 750));
+        assert_eq!(trigger.triggered(), 2);
+    }
+}
@@ -0,0 +1,234 @@
+use core::fmt;
+
+use heapless::Vec;
+
+use crate::crc::crc8;
+
+// ROM commands
+pub const SEARCH_ROM: u8 = 0xf0;
+pub const MATCH_ROM: u8 = 0x55;
+
+// Devices remembered by `enumerate()`, more are left out
+pub const MAX_DEVICES: usize = 4;
+
+// The time slots of a 1-Wire bus, see Maxim AN126. The ESP32 has no 1-Wire
+// peripheral, the firmware bit-bangs a GPIO, see `board::onewire`. Bytes
+// go out least significant bit first.
+pub trait OneWire {
+    // Reset pulse, true if a device answered with a presence pulse
+    fn reset(&mut self) -> bool;
+
+    fn write_bit(&mut self, bit: bool);
+
+    fn read_bit(&mut self) -> bool;
+
+    fn write_byte(&mut self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte >> i & 1 == 1);
+        }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        (0..8).fold(0, |byte, i| byte | (self.read_bit() as u8) << i)
+    }
+}
+
+// A device's 64-bit ROM code: family, 48-bit serial, CRC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rom(pub [u8; 8]);
+
+impl Rom {
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn is_valid(&self) -> bool {
+        crc8(&self.0) == 0
+    }
+
+    // Reset and MATCH_ROM, the next command goes to this device only.
+    // False if nothing answered the reset.
+    pub fn select<W: OneWire>(&self, bus: &mut W) -> bool {
+        if !bus.reset() {
+            return false;
+        }
+        bus.write_byte(MATCH_ROM);
+        for byte in self.0 {
+            bus.write_byte(byte);
+        }
+        true
+    }
+}
+
+// As the datasheets print them, family first
+impl fmt::Display for Rom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// The ROM search of Maxim AN187: every pass walks the 64 bits, each device
+// answering with its bit and its complement, and takes the 1 branch at the
+// last discrepancy left from the pass before.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Search {
+    rom: [u8; 8],
+    last_discrepancy: u8,
+    done: bool,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The next device, None once all were found, with nothing on the bus
+    // or on a pass with a bad CRC
+    pub fn next<W: OneWire>(&mut self, bus: &mut W) -> Option<Rom> {
+        if self.done || !bus.reset() {
+            return None;
+        }
+        bus.write_byte(SEARCH_ROM);
+
+        let mut last_zero = 0;
+        for bit in 1..=64u8 {
+            let (byte, mask) = (((bit - 1) / 8) as usize, 1 << ((bit - 1) % 8));
+            let direction = match (bus.read_bit(), bus.read_bit()) {
+                // Nobody answered, the device went away mid-search
+                (true, true) => return None,
+                (id, complement) if id != complement => id,
+                // Devices on both branches
+                _ => {
+                    let direction = match bit.cmp(&self.last_discrepancy) {
+                        core::cmp::Ordering::Less => self.rom[byte] & mask != 0,
+                        core::cmp::Ordering::Equal => true,
+                        core::cmp::Ordering::Greater => false,
+                    };
+                    if !direction {
+                        last_zero = bit;
+                    }
+                    direction
+                }
+            };
+            if direction {
+                self.rom[byte] |= mask;
+            } else {
+                self.rom[byte] &= !mask;
+            }
+            bus.write_bit(direction);
+        }
+
+        self.last_discrepancy = last_zero;
+        self.done = last_zero == 0;
+        let rom = Rom(self.rom);
+        rom.is_valid().then_some(rom)
+    }
+}
+
+// Every device on the bus, up to MAX_DEVICES
+pub fn enumerate<W: OneWire>(bus: &mut W) -> Vec<Rom, MAX_DEVICES> {
+    let mut search = Search::new();
+    let mut found = Vec::new();
+    while let Some(rom) = search.next(bus) {
+        if found.push(rom).is_err() {
+            break;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Devices answering a ROM search, wired-AND like the real bus
+    struct SearchBus {
+        roms: std::vec::Vec<[u8; 8]>,
+        active: std::vec::Vec<bool>,
+        command: std::vec::Vec<bool>,
+        bit: usize,
+        complement: bool,
+    }
+
+    impl SearchBus {
+        fn new(roms: &[[u8; 8]]) -> Self {
+            Self {
+                roms: roms.to_vec(),
+                active: std::vec::Vec::new(),
+                command: std::vec::Vec::new(),
+                bit: 0,
+                complement: false,
+            }
+        }
+
+        fn rom_bit(rom: &[u8; 8], bit: usize) -> bool {
+            rom[bit / 8] >> (bit % 8) & 1 == 1
+        }
+    }
+
+    impl OneWire for SearchBus {
+        fn reset(&mut self) -> bool {
+            self.active = vec![true; self.roms.len()];
+            self.command.clear();
+            self.bit = 0;
+            self.complement = false;
+            !self.roms.is_empty()
+        }
+
+        fn write_bit(&mut self, bit: bool) {
+            if self.command.len() < 8 {
+                self.command.push(bit);
+                return;
+            }
+            for (active, rom) in self.active.iter_mut().zip(&self.roms) {
+                *active &= Self::rom_bit(rom, self.bit) == bit;
+            }
+            self.bit += 1;
+        }
+
+        fn read_bit(&mut self) -> bool {
+            let complement = self.complement;
+            self.complement = !complement;
+            self.roms
+                .iter()
+                .zip(&self.active)
+                .filter(|(_, active)| **active)
+                .all(|(rom, _)| Self::rom_bit(rom, self.bit) != complement)
+        }
+    }
+
+    // With the CRC in the last byte
+    fn rom(family: u8, serial: [u8; 6]) -> [u8; 8] {
+        let mut rom = [family, 0, 0, 0, 0, 0, 0, 0];
+        rom[1..7].copy_from_slice(&serial);
+        rom[7] = crc8(&rom[..7]);
+        rom
+    }
+
+    #[test]
+    fn search_finds_every_device() {
+        let roms = [
+            rom(0x28, [0xff, 0x4c, 0x60, 0x91, 0x16, 0x04]),
+            rom(0x28, [0xff, 0x4c, 0x60, 0x91, 0x16, 0x05]),
+            rom(0x10, [0x01, 0x02, 0x03, 0x04, 0x05, 0x06]),
+        ];
+        let mut bus = SearchBus::new(&roms);
+
+        let found = enumerate(&mut bus);
+        assert_eq!(found.len(), 3);
+        for rom in roms {
+            assert!(found.contains(&Rom(rom)));
+        }
+        assert!(found.iter().all(Rom::is_valid));
+
+        assert_eq!(enumerate(&mut SearchBus::new(&[])).len(), 0);
+        assert_eq!(
+            Rom(roms[0]).to_string(),
+            format!("28ff4c60911604{:02x}", roms[0][7])
+        );
+        assert!(!Rom([0x28, 0, 0, 0, 0, 0, 0, 0x01]).is_valid());
+    }
+}
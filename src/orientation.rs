@@ -0,0 +1,195 @@
+use crate::math;
+
+// Weight of the gyro-integrated angle in the complementary filter, the rest
+// comes from the accelerometer. At 100 Hz this trusts the gyro for about
+// half a second, long enough to ride out vibration, short enough that its
+// drift never builds up.
+pub const COMPLEMENTARY_ALPHA: f32 = 0.98;
+
+// Static orientation of the sensor from the gravity vector, in degrees.
+// Only meaningful while the machine isn't accelerating, which is why the
+// orientation limit needs the deviation to persist before it trips.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    pub pitch: f32,
+    pub roll: f32,
+}
+
+impl Orientation {
+    // Any unit works for the acceleration, only its direction matters
+    pub fn from_acc(acc: [f32; 3]) -> Self {
+        let [x, y, z] = acc;
+
+        Self {
+            pitch: math::atan2(-x, math::sqrt(y * y + z * z)).to_degrees(),
+            roll: math::atan2(y, z).to_degrees(),
+        }
+    }
+
+    // Largest change of pitch or roll against another orientation
+    pub fn deviation(&self, from: &Orientation) -> f32 {
+        let pitch = math::abs(self.pitch - from.pitch);
+        // Roll wraps around at ±180º
+        let roll = math::abs(self.roll - from.roll);
+        let roll = if roll > 180.0 { 360.0 - roll } else { roll };
+
+        pitch.max(roll)
+    }
+}
+
+// Wraps an angle difference into ±180º
+fn wrap(degrees: f32) -> f32 {
+    if degrees > 180.0 {
+        degrees - 360.0
+    } else if degrees < -180.0 {
+        degrees + 360.0
+    } else {
+        degrees
+    }
+}
+
+// Pitch and roll fused from both sensors.
+// The accelerometer angle is right on average but noisy while the machine
+// vibrates, the integrated gyro is smooth but drifts. Each sample moves the
+// gyro prediction a little towards the accelerometer angle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComplementaryFilter {
+    alpha: f32,
+    state: Option<(Orientation, u64)>,
+}
+
+impl ComplementaryFilter {
+    pub const fn new(alpha: f32) -> Self {
+        Self { alpha, state: None }
+    }
+
+    // Acceleration in any unit, rotation in rad/s, timestamp in ms.
+    // The first sample only seeds the estimate.
+    pub fn update(&mut self, acc: [f32; 3], gyro: [f32; 3], t_ms: u64) -> Orientation {
+        let measured = Orientation::from_acc(acc);
+
+        let fused = match self.state {
+            None => measured,
+            Some((last, last_ms)) => {
+                let dt = t_ms.saturating_sub(last_ms) as f32 / 1000.0;
+                let predicted = Orientation {
+                    pitch: last.pitch + gyro[1].to_degrees() * dt,
+                    roll: last.roll + gyro[0].to_degrees() * dt,
+                };
+
+                Orientation {
+                    pitch: measured.pitch + self.alpha * (predicted.pitch - measured.pitch),
+                    roll: wrap(measured.roll + self.alpha * wrap(predicted.roll - measured.roll)),
+                }
+            }
+        };
+
+        self.state = Some((fused, t_ms));
+        fused
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.state.map(|(orientation, _)| orientation)
+    }
+
+    // Starts over from the next accelerometer angle, e.g. after a gap
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+impl Default for ComplementaryFilter {
+    fn default() -> Self {
+        Self::new(COMPLEMENTARY_ALPHA)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    #[test]
+    fn flat_sensor_is_level() {
+        let level = Orientation::from_acc([0.0, 0.0, 9.81]);
+
+        assert!(math::abs(level.pitch) < EPSILON);
+        assert!(math::abs(level.roll) < EPSILON);
+    }
+
+    #[test]
+    fn tilt_shows_up_on_the_right_angle() {
+        let angle = 10f32.to_radians();
+        let pitched = Orientation::from_acc([-libm::sinf(angle), 0.0, libm::cosf(angle)]);
+        let rolled = Orientation::from_acc([0.0, libm::sinf(angle), libm::cosf(angle)]);
+
+        assert!(math::abs(pitched.pitch - 10.0) < EPSILON);
+        assert!(math::abs(pitched.roll) < EPSILON);
+        assert!(math::abs(rolled.roll - 10.0) < EPSILON);
+        assert!(math::abs(rolled.deviation(&Orientation::default()) - 10.0) < EPSILON);
+    }
+
+    // Rolls at 20º/s for 3 s, at 100 Hz, while the machine shakes the
+    // accelerometer by ±1.5 m/s^2
+    #[test]
+    fn fused_angle_follows_a_rotation_through_vibration() {
+        let mut filter = ComplementaryFilter::default();
+        let rate = 20f32.to_radians();
+
+        let mut worst_raw: f32 = 0.0;
+        let mut worst_fused: f32 = 0.0;
+        for n in 0..=300u64 {
+            let roll = rate * n as f32 / 100.0;
+            // Seeded from a clean sample
+            let shake = match n {
+                0 => 0.0,
+                _ if n % 2 == 0 => 1.5,
+                _ => -1.5,
+            };
+            let acc = [
+                0.0,
+                9.81 * libm::sinf(roll) + shake,
+                9.81 * libm::cosf(roll),
+            ];
+
+            let fused = filter.update(acc, [rate, 0.0, 0.0], n * 10);
+            let raw = Orientation::from_acc(acc);
+            worst_raw = worst_raw.max(math::abs(raw.roll - roll.to_degrees()));
+            worst_fused = worst_fused.max(math::abs(fused.roll - roll.to_degrees()));
+        }
+
+        assert!(worst_raw > 5.0);
+        assert!(worst_fused < 1.0, "fused error {}", worst_fused);
+    }
+
+    #[test]
+    fn fused_angle_converges_on_the_accelerometer() {
+        let mut filter = ComplementaryFilter::default();
+        filter.update([0.0, 0.0, 9.81], [0.0; 3], 0);
+
+        let angle = 15f32.to_radians();
+        let tilted = [-9.81 * libm::sinf(angle), 0.0, 9.81 * libm::cosf(angle)];
+        for n in 1..=300 {
+            filter.update(tilted, [0.0; 3], n * 10);
+        }
+
+        let fused = filter.orientation().unwrap();
+        assert!(math::abs(fused.pitch - 15.0) < 0.1);
+        assert!(math::abs(fused.roll) < EPSILON);
+    }
+
+    #[test]
+    fn roll_deviation_wraps_around() {
+        let a = Orientation {
+            pitch: 0.0,
+            roll: 178.0,
+        };
+        let b = Orientation {
+            pitch: 0.0,
+            roll: -178.0,
+        };
+
+        assert!(math::abs(a.deviation(&b) - 4.0) < EPSILON);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::math;
+
+// The accelerometer is polled at ~100 Hz between status updates
+pub const PEAK_PERIOD_MS: u32 = 10;
+
+// Largest absolute accelerometer delta on each axis since the last report.
+// Impacts shorter than the status cadence would fall between two samples,
+// the peak keeps them until the next `MaintenanceMonitor::update()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PeakHold {
+    axes: [f32; 3],
+    magnitude: f32,
+    samples: u32,
+}
+
+impl PeakHold {
+    pub const fn new() -> Self {
+        Self {
+            axes: [0.0; 3],
+            magnitude: 0.0,
+            samples: 0,
+        }
+    }
+
+    // Delta against the reference, in m/s^2
+    pub fn push(&mut self, delta: [f32; 3]) {
+        for (peak, delta) in self.axes.iter_mut().zip(delta) {
+            *peak = peak.max(math::abs(delta));
+        }
+
+        let [dx, dy, dz] = delta;
+        self.magnitude = self.magnitude.max(math::sqrt(dx * dx + dy * dy + dz * dz));
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    pub fn axes(&self) -> [f32; 3] {
+        self.axes
+    }
+
+    // Largest length of the delta vector
+    pub fn magnitude(&self) -> f32 {
+        self.magnitude
+    }
+
+    // Samples folded into the peak
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // Returns the peak and starts a new one
+    pub fn take(&mut self) -> Self {
+        core::mem::take(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_largest_absolute_delta_per_axis() {
+        let mut peak = PeakHold::new();
+
+        peak.push([0.1, -0.2, 0.0]);
+        peak.push([-0.9, 0.1, 0.3]);
+        peak.push([0.2, 0.0, -0.4]);
+        assert_eq!(peak.axes(), [0.9, 0.2, 0.4]);
+        assert_eq!(peak.samples(), 3);
+
+        let taken = peak.take();
+        assert_eq!(taken.axes(), [0.9, 0.2, 0.4]);
+        assert_eq!(peak, PeakHold::new());
+    }
+}
@@ -0,0 +1,98 @@
+// Measures the rate the loop actually runs at, against the RTC.
+// The hardware timer keeps the average on target, but a tick that runs
+// long delays the ones after it, which then run back to back to catch up.
+
+pub const RATE_REPORT_MS: u64 = 60_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateReport {
+    pub rate_hz: f32,
+    // Most timer ticks the loop was behind at the start of an iteration
+    pub max_lag: u32,
+}
+
+pub struct RateMeter {
+    window_ms: u64,
+    start_ms: Option<u64>,
+    ticks: u32,
+    max_lag: u32,
+}
+
+impl RateMeter {
+    pub const fn new(window_ms: u64) -> Self {
+        Self {
+            window_ms,
+            start_ms: None,
+            ticks: 0,
+            max_lag: 0,
+        }
+    }
+
+    // One loop iteration at `now_ms`, `lag` timer ticks behind.
+    // Returns the rate once per window, and starts the next one.
+    pub fn tick(&mut self, now_ms: u64, lag: u32) -> Option<RateReport> {
+        let Some(start_ms) = self.start_ms else {
+            self.start_ms = Some(now_ms);
+            return None;
+        };
+
+        self.ticks += 1;
+        self.max_lag = self.max_lag.max(lag);
+        let elapsed_ms = now_ms.saturating_sub(start_ms);
+        if elapsed_ms < self.window_ms {
+            return None;
+        }
+
+        let report = RateReport {
+            rate_hz: self.ticks as f32 * 1000.0 / elapsed_ms as f32,
+            max_lag: self.max_lag,
+        };
+        self.start_ms = Some(now_ms);
+        self.ticks = 0;
+        self.max_lag = 0;
+        Some(report)
+    }
+}
+
+impl Default for RateMeter {
+    fn default() -> Self {
+        Self::new(RATE_REPORT_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_once_per_window() {
+        let mut meter = RateMeter::new(1_000);
+
+        assert_eq!(meter.tick(0, 0), None);
+        for n in 1..100 {
+            assert_eq!(meter.tick(n * 10, 0), None);
+        }
+        let report = meter.tick(1_000, 0).unwrap();
+        assert!((report.rate_hz - 100.0).abs() < 1e-3);
+
+        // The next window starts at the report
+        assert_eq!(meter.tick(1_010, 0), None);
+    }
+
+    #[test]
+    fn slow_ticks_show_in_the_rate_and_the_lag() {
+        let mut meter = RateMeter::new(1_000);
+
+        meter.tick(0, 0);
+        // 90 ticks in a second, one of them 3 ticks late
+        for n in 1..=90 {
+            let lag = if n == 45 { 3 } else { 0 };
+            if let Some(report) = meter.tick(n * 1_000 / 90, lag) {
+                assert!((report.rate_hz - 90.0).abs() < 0.1);
+                assert_eq!(report.max_lag, 3);
+                return;
+            }
+        }
+        panic!("no report");
+    }
+}
@@ -0,0 +1,646 @@
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+
+use crate::crc::crc32_of;
+use crate::flashlog::{Slot, FLASH_SECTOR_LEN};
+
+// The accelerometer at 100 Hz on the internal flash for the `raw-log`
+// feature, for a post-mortem without an SD card. The region is a ring of
+// erase sectors, one block each, filled in RAM and programmed once full.
+// A block, little-endian: magic, sequence number, time of its first slot
+// in ms, a spare byte, slot period in ms, LSB per g, slots, payload length, CRC-32
+// of the header so far and the payload; then the payload.
+// The header is programmed last, a block cut short by a power cut has none
+// and reads as blank. Only the block in RAM is lost.
+pub const RAW_BLOCK_LEN: usize = FLASH_SECTOR_LEN;
+pub const RAW_HEADER_LEN: usize = 32;
+pub const RAW_PAYLOAD_LEN: usize = RAW_BLOCK_LEN - RAW_HEADER_LEN;
+// 2 MiB, about an hour of a quiet machine
+pub const RAW_LOG_SECTORS: usize = 512;
+
+pub const RAW_PERIOD_MS: u32 = 10;
+// What's programmed in one go, a tick's worth, in whole words
+pub const RAW_PAGE_LEN: usize = 256;
+const WORD_LEN: usize = 4;
+
+// A dump line holds this many bytes, 64 characters of base64 or 96 of
+// hex, and goes out every RAW_DUMP_PERIOD_MS: under the UART's 128-byte
+// FIFO at 115200 baud, so printing it never blocks a tick
+pub const RAW_DUMP_LINE: usize = 48;
+pub const RAW_DUMP_PERIOD_MS: u32 = 20;
+
+const MAGIC: u32 = 0x3157_4152;
+const CRC_AT: usize = 24;
+// Three 3-byte varints
+const MAX_ENTRY_LEN: usize = 9;
+
+// The payload is a run of entries, each slot's sample as the difference
+// from the one before it, zigzag varints. The first varint's low bit is 0
+// for a sample, and the rest of it is the x delta; 1 for a gap, and the
+// rest is how many slots had no sample. A block starts from zero, so it
+// decodes on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Entry {
+    Sample([i16; 3]),
+    Gap(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub seq: u32,
+    // The uptime
+    pub t_ms: u64,
+    pub period_ms: u8,
+    pub acc_lsb_per_g: u16,
+    pub slots: u16,
+    pub len: u16,
+    crc: u32,
+}
+
+impl BlockHeader {
+    pub fn decode(bytes: &[u8; RAW_HEADER_LEN]) -> Slot<Self> {
+        let half = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let word = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+
+        if bytes.iter().all(|byte| *byte == 0xff) {
+            return Slot::Blank;
+        }
+        let len = half(22);
+        if word(0) != MAGIC || len as usize > RAW_PAYLOAD_LEN {
+            return Slot::Corrupt;
+        }
+        Slot::Record(Self {
+            seq: word(4),
+            t_ms: word(8) as u64 | (word(12) as u64) << 32,
+            period_ms: bytes[17],
+            acc_lsb_per_g: half(18),
+            slots: half(20),
+            len,
+            crc: word(CRC_AT),
+        })
+    }
+
+    // Against the block it heads, a whole RAW_BLOCK_LEN
+    pub fn verify(&self, block: &[u8]) -> bool {
+        let end = RAW_HEADER_LEN + self.len as usize;
+        block.len() >= end && block_crc(block, end) == self.crc
+    }
+}
+
+fn block_crc(block: &[u8], end: usize) -> u32 {
+    crc32_of(&[&block[..CRC_AT], &block[RAW_HEADER_LEN..end]])
+}
+
+// The entries of a block's payload, oldest first. Stops at a truncated one.
+pub struct Entries<'a> {
+    payload: &'a [u8],
+    last: [i16; 3],
+}
+
+impl<'a> Entries<'a> {
+    pub fn new(payload: &'a [u8]) -> Self {
+        Self {
+            payload,
+            last: [0; 3],
+        }
+    }
+
+    fn varint(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for shift in [0, 7, 14, 21] {
+            let (byte, rest) = self.payload.split_first()?;
+            self.payload = rest;
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        let first = self.varint()?;
+        if first & 1 == 1 {
+            return Some(Entry::Gap((first >> 1) as u16));
+        }
+        let deltas = [first >> 1, self.varint()?, self.varint()?];
+        for (last, delta) in self.last.iter_mut().zip(deltas) {
+            *last = last.wrapping_add(unzigzag(delta) as i16);
+        }
+        Some(Entry::Sample(self.last))
+    }
+}
+
+fn zigzag(delta: i32) -> u32 {
+    ((delta << 1) ^ (delta >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+// One flash operation, at an offset into the region
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RawOp<'a> {
+    Erase(u32),
+    Program(u32, &'a [u8]),
+}
+
+// Where the next block goes
+#[derive(Clone, Copy, Debug)]
+pub struct RawRing {
+    next: usize,
+    next_seq: u32,
+    // Whether the next block's sector is known to be erased
+    erased: bool,
+    // As of the scan at boot
+    blocks: usize,
+    corrupt: usize,
+}
+
+impl RawRing {
+    pub const fn new() -> Self {
+        Self {
+            next: 0,
+            next_seq: 0,
+            erased: false,
+            blocks: 0,
+            corrupt: 0,
+        }
+    }
+
+    // Finds the newest block from every sector's header. The one after it
+    // may hold a block torn by a power cut, it's erased before it's used.
+    pub fn scan(mut read: impl FnMut(usize) -> [u8; RAW_HEADER_LEN]) -> Self {
+        let mut ring = Self::new();
+        let mut newest: Option<(usize, u32)> = None;
+        for block in 0..RAW_LOG_SECTORS {
+            match BlockHeader::decode(&read(block)) {
+                Slot::Blank => {}
+                Slot::Corrupt => ring.corrupt += 1,
+                Slot::Record(header) => {
+                    ring.blocks += 1;
+                    if !matches!(newest, Some((_, seq)) if seq >= header.seq) {
+                        newest = Some((block, header.seq));
+                    }
+                }
+            }
+        }
+        if let Some((block, seq)) = newest {
+            ring.next = (block + 1) % RAW_LOG_SECTORS;
+            ring.next_seq = seq.wrapping_add(1);
+        }
+        ring
+    }
+
+    // Every block, oldest first: from the one to be written next around
+    // to the newest
+    pub fn blocks(&self) -> impl Iterator<Item = usize> {
+        (self.next..RAW_LOG_SECTORS).chain(0..self.next)
+    }
+
+    // The next sequence number, what a dump stops short of
+    pub fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    pub fn stored(&self) -> usize {
+        self.blocks
+    }
+
+    pub fn corrupt(&self) -> usize {
+        self.corrupt
+    }
+}
+
+impl Default for RawRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Where a block is in the region
+pub fn block_offset(block: usize) -> u32 {
+    (block * RAW_BLOCK_LEN) as u32
+}
+
+// The block being filled, and the one before it while it's programmed.
+// `next_op()` hands out one flash operation a tick at most, the erase for
+// the next block right after a block is programmed: ~45 ms of a blocked
+// CPU, once a block, well before the block in RAM fills.
+pub struct RawLog {
+    ring: RawRing,
+    acc_lsb_per_g: u16,
+    // Time of the first slot
+    start: Option<u64>,
+    payload: Vec<u8, RAW_PAYLOAD_LEN>,
+    last: [i16; 3],
+    slots: u16,
+    // Slots without a sample since the last entry
+    gap: u16,
+    sealed: Vec<u8, RAW_BLOCK_LEN>,
+    // How much of the sealed block is programmed, the header last
+    programmed: usize,
+    written: u32,
+    dropped: u32,
+}
+
+impl RawLog {
+    pub fn new(ring: RawRing, acc_lsb_per_g: u16) -> Self {
+        Self {
+            ring,
+            acc_lsb_per_g,
+            start: None,
+            payload: Vec::new(),
+            last: [0; 3],
+            slots: 0,
+            gap: 0,
+            sealed: Vec::new(),
+            programmed: 0,
+            written: 0,
+            dropped: 0,
+        }
+    }
+
+    // A slot's sample, None if there wasn't one, at its uptime `t_ms`
+    pub fn push(&mut self, t_ms: u64, acc: Option<[i16; 3]>) {
+        if self.is_full() {
+            self.seal();
+        }
+        if self.is_full() {
+            // Still programming the last one
+            self.dropped += 1;
+            return;
+        }
+        if self.start.is_none() {
+            self.start = Some(t_ms);
+        }
+        self.slots += 1;
+        let Some(acc) = acc else {
+            self.gap += 1;
+            return;
+        };
+
+        self.flush_gap();
+        for (axis, (last, value)) in self.last.iter_mut().zip(acc).enumerate() {
+            let delta = zigzag(value as i32 - *last as i32);
+            let delta = if axis == 0 { delta << 1 } else { delta };
+            push_varint(&mut self.payload, delta);
+            *last = value;
+        }
+    }
+
+    // The next flash operation, if any, then `done()` once it's applied
+    pub fn next_op(&self) -> Option<RawOp<'_>> {
+        let base = block_offset(self.ring.next);
+        if !self.ring.erased {
+            return Some(RawOp::Erase(base));
+        }
+        if self.sealed.is_empty() {
+            return None;
+        }
+        // The payload page by page, the header on its own once it's all there
+        let op = if self.programmed < RAW_HEADER_LEN {
+            RawOp::Program(
+                base + RAW_HEADER_LEN as u32,
+                &self.sealed[RAW_HEADER_LEN..self.page_end(RAW_HEADER_LEN)],
+            )
+        } else if self.programmed < self.sealed.len() {
+            RawOp::Program(
+                base + self.programmed as u32,
+                &self.sealed[self.programmed..self.page_end(self.programmed)],
+            )
+        } else {
+            RawOp::Program(base, &self.sealed[..RAW_HEADER_LEN])
+        };
+        Some(op)
+    }
+
+    // A failed operation counts as done, the block is given up rather than
+    // holding up the ones after it
+    pub fn done(&mut self) {
+        if !self.ring.erased {
+            self.ring.erased = true;
+            return;
+        }
+        if self.programmed < RAW_HEADER_LEN {
+            self.programmed = self.page_end(RAW_HEADER_LEN);
+        } else if self.programmed < self.sealed.len() {
+            self.programmed = self.page_end(self.programmed);
+        } else {
+            self.sealed.clear();
+            self.programmed = 0;
+            self.ring.next = (self.ring.next + 1) % RAW_LOG_SECTORS;
+            self.ring.next_seq = self.ring.next_seq.wrapping_add(1);
+            self.ring.erased = false;
+            self.written += 1;
+        }
+    }
+
+    pub fn ring(&self) -> &RawRing {
+        &self.ring
+    }
+
+    // Bytes in RAM so far, what a power cut would lose
+    pub fn filling(&self) -> usize {
+        self.payload.len()
+    }
+
+    // Blocks programmed since boot
+    pub fn written(&self) -> u32 {
+        self.written
+    }
+
+    // Slots that came while a full block waited to be programmed
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    // Room for a gap and a sample, the most a push adds
+    fn is_full(&self) -> bool {
+        self.payload.len() + 2 * MAX_ENTRY_LEN > RAW_PAYLOAD_LEN || self.slots == u16::MAX
+    }
+
+    fn page_end(&self, from: usize) -> usize {
+        ((from / RAW_PAGE_LEN + 1) * RAW_PAGE_LEN).min(self.sealed.len())
+    }
+
+    fn flush_gap(&mut self) {
+        if self.gap > 0 {
+            push_varint(&mut self.payload, (self.gap as u32) << 1 | 1);
+            self.gap = 0;
+        }
+    }
+
+    // Into the sealed block, unless the one before it is still there
+    fn seal(&mut self) {
+        if !self.sealed.is_empty() {
+            return;
+        }
+        let Some(t_ms) = self.start.take() else {
+            return;
+        };
+        self.flush_gap();
+        let len = self.payload.len();
+        let mut header = [0xff; RAW_HEADER_LEN];
+        header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&self.ring.next_seq.to_le_bytes());
+        header[8..16].copy_from_slice(&t_ms.to_le_bytes());
+        header[16] = 0;
+        header[17] = RAW_PERIOD_MS as u8;
+        header[18..20].copy_from_slice(&self.acc_lsb_per_g.to_le_bytes());
+        header[20..22].copy_from_slice(&self.slots.to_le_bytes());
+        header[22..24].copy_from_slice(&(len as u16).to_le_bytes());
+        // Both fit, they're the same length as the block
+        self.sealed.extend_from_slice(&header).ok();
+        self.sealed.extend_from_slice(&self.payload).ok();
+        let crc = block_crc(&self.sealed, RAW_HEADER_LEN + len);
+        self.sealed[CRC_AT..CRC_AT + 4].copy_from_slice(&crc.to_le_bytes());
+        let padded = (self.sealed.len() + WORD_LEN - 1) & !(WORD_LEN - 1);
+        self.sealed.resize(padded, 0xff).ok();
+
+        self.payload.clear();
+        self.last = [0; 3];
+        self.slots = 0;
+    }
+}
+
+fn push_varint(payload: &mut Vec<u8, RAW_PAYLOAD_LEN>, mut value: u32) {
+    while value >= 0x80 {
+        // Room was made for a whole entry
+        payload.push(value as u8 | 0x80).ok();
+        value >>= 7;
+    }
+    payload.push(value as u8).ok();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawEncoding {
+    Hex,
+    Base64,
+}
+
+impl RawEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RawEncoding::Hex => "hex",
+            RawEncoding::Base64 => "base64",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [RawEncoding::Hex, RawEncoding::Base64]
+            .into_iter()
+            .find(|encoding| encoding.name().eq_ignore_ascii_case(name))
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Up to RAW_DUMP_LINE bytes. Base64 is padded, every line decodes on its own.
+pub fn encode_line(encoding: RawEncoding, bytes: &[u8]) -> String<{ RAW_DUMP_LINE * 2 }> {
+    let mut line = String::new();
+    let bytes = &bytes[..bytes.len().min(RAW_DUMP_LINE)];
+    match encoding {
+        RawEncoding::Hex => {
+            for byte in bytes {
+                write!(line, "{:02x}", byte).ok();
+            }
+        }
+        RawEncoding::Base64 => {
+            for chunk in bytes.chunks(3) {
+                let word = chunk.iter().enumerate().fold(0u32, |word, (i, byte)| {
+                    word | (*byte as u32) << (16 - 8 * i)
+                });
+                for i in 0..4 {
+                    let char = if i <= chunk.len() {
+                        BASE64[(word >> (18 - 6 * i) & 0x3f) as usize] as char
+                    } else {
+                        '='
+                    };
+                    line.push(char).ok();
+                }
+            }
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The region in RAM, with NOR flash's rules
+    struct Region(std::vec::Vec<u8>);
+
+    impl Region {
+        fn new() -> Self {
+            Self(vec![0xff; RAW_BLOCK_LEN * RAW_LOG_SECTORS])
+        }
+
+        fn header(&self, block: usize) -> [u8; RAW_HEADER_LEN] {
+            let at = block_offset(block) as usize;
+            self.0[at..at + RAW_HEADER_LEN].try_into().unwrap()
+        }
+
+        fn block(&self, block: usize) -> &[u8] {
+            let at = block_offset(block) as usize;
+            &self.0[at..at + RAW_BLOCK_LEN]
+        }
+
+        // Every operation there is, `limit` of them at most
+        fn apply(&mut self, log: &mut RawLog, limit: usize) {
+            for _ in 0..limit {
+                match log.next_op() {
+                    None => return,
+                    Some(RawOp::Erase(at)) => {
+                        let at = at as usize;
+                        self.0[at..at + RAW_BLOCK_LEN].fill(0xff);
+                    }
+                    Some(RawOp::Program(at, bytes)) => {
+                        assert!(bytes.len() <= RAW_PAGE_LEN);
+                        assert_eq!((at as usize % WORD_LEN, bytes.len() % WORD_LEN), (0, 0));
+                        assert_eq!(
+                            at as usize / RAW_PAGE_LEN,
+                            (at as usize + bytes.len() - 1) / RAW_PAGE_LEN
+                        );
+                        for (byte, new) in self.0[at as usize..].iter_mut().zip(bytes) {
+                            *byte &= new;
+                        }
+                    }
+                }
+                log.done();
+            }
+        }
+
+        fn headers(&self, ring: &RawRing) -> std::vec::Vec<BlockHeader> {
+            ring.blocks()
+                .filter_map(|block| match BlockHeader::decode(&self.header(block)) {
+                    Slot::Record(header) if header.verify(self.block(block)) => Some(header),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    fn wobble(i: usize) -> [i16; 3] {
+        let i = i as i16;
+        [i % 7 - 3, 16_384 + i % 40 * 9, -(i % 300)]
+    }
+
+    #[test]
+    fn samples_and_gaps_round_trip() {
+        let mut region = Region::new();
+        let mut log = RawLog::new(RawRing::new(), 16_384);
+        let extremes = [[i16::MIN, i16::MAX, 0], [i16::MAX, i16::MIN, -1]];
+        let mut pushed = std::vec::Vec::new();
+        for i in 0..2_000 {
+            let acc = match extremes.get(i) {
+                Some(acc) => Some(*acc),
+                None if i % 100 < 3 => None,
+                None => Some(wobble(i)),
+            };
+            log.push(1_000 + i as u64 * 10, acc);
+            pushed.push(acc);
+            region.apply(&mut log, 1);
+        }
+        region.apply(&mut log, 100);
+
+        let ring = RawRing::scan(|block| region.header(block));
+        let Slot::Record(header) = BlockHeader::decode(&region.header(0)) else {
+            panic!("no block");
+        };
+        assert_eq!((header.seq, header.t_ms), (0, 1_000));
+        assert_eq!((header.period_ms, header.acc_lsb_per_g), (10, 16_384));
+        assert!(header.verify(region.block(0)));
+
+        let payload = &region.block(0)[RAW_HEADER_LEN..RAW_HEADER_LEN + header.len as usize];
+        let mut decoded = std::vec::Vec::new();
+        for entry in Entries::new(payload) {
+            match entry {
+                Entry::Sample(acc) => decoded.push(Some(acc)),
+                Entry::Gap(slots) => decoded.extend((0..slots).map(|_| None)),
+            }
+        }
+        assert_eq!(decoded.len(), header.slots as usize);
+        assert_eq!(decoded[..], pushed[..decoded.len()]);
+        assert_eq!(ring.next_seq(), 1);
+        assert_eq!(log.dropped(), 0);
+    }
+
+    #[test]
+    fn a_full_region_wraps_over_its_oldest_block() {
+        let mut region = Region::new();
+        let mut log = RawLog::new(RawRing::new(), 4_096);
+        let mut i = 0;
+        while log.written() < RAW_LOG_SECTORS as u32 + 3 {
+            log.push(i as u64 * 10, Some(wobble(i)));
+            region.apply(&mut log, 1);
+            i += 1;
+        }
+        // The erase ahead of the next block goes for the oldest one
+        region.apply(&mut log, 1);
+
+        let ring = RawRing::scan(|block| region.header(block));
+        let seqs: std::vec::Vec<u32> = region.headers(&ring).iter().map(|h| h.seq).collect();
+        assert_eq!(seqs.len(), RAW_LOG_SECTORS - 1);
+        assert_eq!(seqs[0], 4);
+        assert!(seqs.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert_eq!(ring.next_seq(), RAW_LOG_SECTORS as u32 + 3);
+    }
+
+    #[test]
+    fn a_block_cut_short_reads_as_blank() {
+        let mut region = Region::new();
+        let mut log = RawLog::new(RawRing::new(), 16_384);
+        let mut i = 0;
+        while log.written() < 1 {
+            log.push(i as u64 * 10, Some(wobble(i)));
+            region.apply(&mut log, 1);
+            i += 1;
+        }
+        // Power cut with the second block's payload half programmed
+        while log.next_op().is_some() {
+            region.apply(&mut log, 1);
+        }
+        while log.sealed.is_empty() {
+            log.push(i as u64 * 10, Some(wobble(i)));
+            i += 1;
+        }
+        region.apply(&mut log, 4);
+
+        let ring = RawRing::scan(|block| region.header(block));
+        assert_eq!((ring.stored(), ring.corrupt()), (1, 0));
+        assert_eq!(BlockHeader::decode(&region.header(1)), Slot::Blank);
+        // and the next boot erases it before it's used
+        let mut log = RawLog::new(ring, 16_384);
+        assert_eq!(log.next_op(), Some(RawOp::Erase(block_offset(1))));
+        log.done();
+        assert_eq!(log.next_op(), None);
+
+        let mut block = region.block(0).to_vec();
+        block[100] ^= 0x01;
+        let Slot::Record(header) = BlockHeader::decode(&region.header(0)) else {
+            panic!("no block");
+        };
+        assert!(!header.verify(&block));
+    }
+
+    #[test]
+    fn encodes_dump_lines() {
+        assert_eq!(encode_line(RawEncoding::Hex, &[0x00, 0xab, 0x7f]), "00ab7f");
+        assert_eq!(encode_line(RawEncoding::Base64, b"Man"), "TWFu");
+        assert_eq!(encode_line(RawEncoding::Base64, b"Ma"), "TWE=");
+        assert_eq!(encode_line(RawEncoding::Base64, b"M"), "TQ==");
+        let line = encode_line(RawEncoding::Hex, &[0xff; 100]);
+        assert_eq!(line.len(), RAW_DUMP_LINE * 2);
+        assert_eq!(RawEncoding::parse("BASE64"), Some(RawEncoding::Base64));
+    }
+}
@@ -0,0 +1,21 @@
+// One sample of every sensor channel, taken together once per cycle.
+// Acceleration in m/s^2, rotation in rad/s, temperature in ºC.
+// t_ms is the uptime the sample was read at.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Reading {
+    pub acc: [f32; 3],
+    pub gyro: [f32; 3],
+    pub temp: f32,
+    pub t_ms: u64,
+}
+
+impl Reading {
+    pub const fn new(acc: [f32; 3], gyro: [f32; 3], temp: f32, t_ms: u64) -> Self {
+        Self {
+            acc,
+            gyro,
+            temp,
+            t_ms,
+        }
+    }
+}
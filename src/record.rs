@@ -0,0 +1,188 @@
+use crate::crc::crc32;
+use crate::{Alert, Limit, ResetCause, Severity};
+
+// Alarm history kept in RTC slow memory across resets. RTC memory holds
+// garbage after a power-on, a record only counts with the magic number and
+// a checksum that matches, anything else starts over from zero. The
+// checksum also catches a write cut short by the reset it should survive.
+pub const RECORD_MAGIC: u32 = 0x5245_4302;
+pub const RECORD_WORDS: usize = 12;
+
+// Last word is the checksum
+const DATA_WORDS: usize = RECORD_WORDS - 1;
+const NO_ALARM: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LastAlarm {
+    pub limit: Limit,
+    pub severity: Severity,
+    // Boot it happened on, and its uptime then
+    pub boot: u32,
+    pub uptime_ms: u64,
+    // The limit's reading when it fired
+    pub peak: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EventRecord {
+    // Boots since the record was cleared, this one included
+    pub boot_count: u32,
+    pub mechanical_alarms: u32,
+    pub temperature_alarms: u32,
+    pub last_alarm: Option<LastAlarm>,
+    pub watchdog_resets: u32,
+    pub panics: u32,
+}
+
+impl EventRecord {
+    pub const fn new() -> Self {
+        Self {
+            boot_count: 0,
+            mechanical_alarms: 0,
+            temperature_alarms: 0,
+            last_alarm: None,
+            watchdog_resets: 0,
+            panics: 0,
+        }
+    }
+
+    pub fn booted(&mut self) {
+        self.boot_count = self.boot_count.wrapping_add(1);
+    }
+
+    // Counts the resets that shouldn't have happened
+    pub fn reset(&mut self, cause: ResetCause) {
+        match cause {
+            ResetCause::Watchdog => self.watchdog_resets = self.watchdog_resets.wrapping_add(1),
+            ResetCause::Panic => self.panics = self.panics.wrapping_add(1),
+            _ => {}
+        }
+    }
+
+    pub fn alarm(&mut self, alert: &Alert, peak: f32, uptime_ms: u64) {
+        match alert.limit {
+            Limit::Mechanical => self.mechanical_alarms = self.mechanical_alarms.wrapping_add(1),
+            Limit::Temperature => self.temperature_alarms = self.temperature_alarms.wrapping_add(1),
+            _ => {}
+        }
+        self.last_alarm = Some(LastAlarm {
+            limit: alert.limit,
+            severity: alert.severity,
+            boot: self.boot_count,
+            uptime_ms,
+            peak,
+        });
+    }
+
+    pub fn encode(&self) -> [u32; RECORD_WORDS] {
+        let mut words = [0; RECORD_WORDS];
+        words[0] = RECORD_MAGIC;
+        words[1] = self.boot_count;
+        words[2] = self.mechanical_alarms;
+        words[3] = self.temperature_alarms;
+        match self.last_alarm {
+            Some(last) => {
+                words[4] = last.limit as u32 | (last.severity as u32) << 8;
+                words[5] = last.boot;
+                words[6] = last.uptime_ms as u32;
+                words[7] = (last.uptime_ms >> 32) as u32;
+                words[8] = last.peak.to_bits();
+            }
+            None => words[4] = NO_ALARM,
+        }
+        words[9] = self.watchdog_resets;
+        words[10] = self.panics;
+        words[DATA_WORDS] = checksum(&words[..DATA_WORDS]);
+        words
+    }
+
+    // None for a record that was never written, or only partly
+    pub fn decode(words: &[u32; RECORD_WORDS]) -> Option<Self> {
+        if words[0] != RECORD_MAGIC || words[DATA_WORDS] != checksum(&words[..DATA_WORDS]) {
+            return None;
+        }
+
+        let last_alarm = match words[4] {
+            NO_ALARM => None,
+            tag => Some(LastAlarm {
+                limit: *Limit::ALL.get((tag & 0xff) as usize)?,
+                severity: match tag >> 8 {
+                    0 => Severity::Warning,
+                    1 => Severity::Critical,
+                    _ => return None,
+                },
+                boot: words[5],
+                uptime_ms: words[6] as u64 | (words[7] as u64) << 32,
+                peak: f32::from_bits(words[8]),
+            }),
+        };
+        Some(Self {
+            boot_count: words[1],
+            mechanical_alarms: words[2],
+            temperature_alarms: words[3],
+            last_alarm,
+            watchdog_resets: words[9],
+            panics: words[10],
+        })
+    }
+}
+
+fn checksum(words: &[u32]) -> u32 {
+    let mut bytes = [0; DATA_WORDS * 4];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    crc32(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(limit: Limit) -> Alert {
+        Alert {
+            limit,
+            severity: Severity::Critical,
+        }
+    }
+
+    #[test]
+    fn counts_alarms_and_keeps_the_last_one() {
+        let mut record = EventRecord::new();
+        record.booted();
+        record.alarm(&alert(Limit::Mechanical), 0.9, 1_000);
+        record.booted();
+        record.reset(ResetCause::Watchdog);
+        record.reset(ResetCause::Software);
+        record.alarm(&alert(Limit::Temperature), 2.7, 5_000_000_000);
+        record.alarm(&alert(Limit::Jerk), 4.5, 5_000_000_100);
+
+        assert_eq!(record.boot_count, 2);
+        assert_eq!(record.mechanical_alarms, 1);
+        assert_eq!(record.temperature_alarms, 1);
+        let last = record.last_alarm.unwrap();
+        assert_eq!(last.limit, Limit::Jerk);
+        assert_eq!(last.boot, 2);
+        assert_eq!(record.watchdog_resets, 1);
+        assert_eq!(record.panics, 0);
+
+        assert_eq!(EventRecord::decode(&record.encode()), Some(record));
+    }
+
+    #[test]
+    fn garbage_and_torn_writes_are_rejected() {
+        assert_eq!(EventRecord::decode(&[0; RECORD_WORDS]), None);
+        assert_eq!(EventRecord::decode(&[0xdead_beef; RECORD_WORDS]), None);
+
+        let mut record = EventRecord::new();
+        record.booted();
+        let blank = record.encode();
+        assert_eq!(EventRecord::decode(&blank), Some(record));
+
+        record.alarm(&alert(Limit::Mechanical), 0.9, 1_000);
+        let mut torn = record.encode();
+        // Reset halfway through the write: new counters, old checksum
+        torn[DATA_WORDS] = blank[DATA_WORDS];
+        assert_eq!(EventRecord::decode(&torn), None);
+    }
+}
@@ -0,0 +1,161 @@
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::{Limit, MaintenanceMonitor, Severity};
+
+// Consecutive critical samples before the relay cuts power
+pub const RELAY_TRIP_SAMPLES: u8 = 3;
+
+// Limits allowed to stop the machine
+pub const RELAY_LIMITS: [Limit; 2] = [Limit::Mechanical, Limit::Temperature];
+
+// Relay or contactor that cuts power to the monitored machine.
+// It only trips after a critical condition persists for a number of samples,
+// then stays tripped until acknowledged.
+// The output is low while the machine is allowed to run, so `new()` puts the
+// pin in that state before anything else can fail.
+pub struct Relay<P: OutputPin> {
+    pin: P,
+    trip_samples: u8,
+    critical_samples: u8,
+    tripped: Option<Limit>,
+}
+
+impl<P: OutputPin> Relay<P> {
+    pub fn new(mut pin: P, trip_samples: u8) -> Result<Self, P::Error> {
+        pin.set_low()?;
+
+        Ok(Self {
+            pin,
+            trip_samples,
+            critical_samples: 0,
+            tripped: None,
+        })
+    }
+
+    // Called after every `MaintenanceMonitor::update()`.
+    // Returns the limit that tripped the relay on this sample, if any.
+    pub fn update<const N: usize>(
+        &mut self,
+        monitor: &MaintenanceMonitor<N>,
+    ) -> Result<Option<Limit>, P::Error> {
+        let critical = RELAY_LIMITS
+            .into_iter()
+            .find(|limit| monitor.exceeded(*limit) == Some(Severity::Critical));
+
+        let Some(limit) = critical else {
+            self.critical_samples = 0;
+            return Ok(None);
+        };
+
+        self.critical_samples = self.critical_samples.saturating_add(1);
+        if self.tripped.is_some() || self.critical_samples < self.trip_samples {
+            return Ok(None);
+        }
+
+        self.pin.set_high()?;
+        self.tripped = Some(limit);
+        Ok(Some(limit))
+    }
+
+    // Limit that tripped the relay, None while the machine is allowed to run
+    pub fn tripped(&self) -> Option<Limit> {
+        self.tripped
+    }
+
+    // Critical samples counted towards the trip delay, as (count, required)
+    pub fn pending(&self) -> Option<(u8, u8)> {
+        if self.tripped.is_none() && self.critical_samples > 0 {
+            Some((self.critical_samples, self.trip_samples))
+        } else {
+            None
+        }
+    }
+
+    // Lets the machine run again.
+    // If the condition is still critical the relay trips again after the
+    // trip delay.
+    pub fn acknowledge(&mut self) -> Result<(), P::Error> {
+        self.pin.set_low()?;
+        self.tripped = None;
+        self.critical_samples = 0;
+        Ok(())
+    }
+
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reading, Thresholds, SAMPLE_PERIOD_MS};
+    use core::convert::Infallible;
+
+    const GRAVITY: [f32; 3] = [0.0, 0.0, 1.0];
+    const STILL: [f32; 3] = [0.0; 3];
+
+    #[derive(Default)]
+    struct Pin {
+        high: bool,
+    }
+
+    impl OutputPin for Pin {
+        type Error = Infallible;
+
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.high = false;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.high = true;
+            Ok(())
+        }
+    }
+
+    fn feed(monitor: &mut MaintenanceMonitor, relay: &mut Relay<Pin>, temp: f32) -> Option<Limit> {
+        monitor.update(&Reading::new(GRAVITY, STILL, temp, 0));
+        relay.update(monitor).unwrap()
+    }
+
+    #[test]
+    fn trips_after_delay_and_holds_until_acknowledged() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut relay = Relay::new(Pin { high: true }, RELAY_TRIP_SAMPLES).unwrap();
+        assert!(!relay.pin.high);
+
+        for _ in 1..RELAY_TRIP_SAMPLES {
+            assert_eq!(feed(&mut monitor, &mut relay, 85.0), None);
+        }
+        assert_eq!(relay.pending(), Some((2, 3)));
+        assert_eq!(
+            feed(&mut monitor, &mut relay, 85.0),
+            Some(Limit::Temperature)
+        );
+        assert!(relay.pin.high);
+
+        for _ in 0..5 {
+            feed(&mut monitor, &mut relay, 25.0);
+        }
+        assert_eq!(relay.tripped(), Some(Limit::Temperature));
+        assert!(relay.pin.high);
+
+        relay.acknowledge().unwrap();
+        assert_eq!(relay.tripped(), None);
+        assert!(!relay.pin.high);
+    }
+
+    #[test]
+    fn ignores_warnings_and_short_spikes() {
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut relay = Relay::new(Pin::default(), RELAY_TRIP_SAMPLES).unwrap();
+
+        for temp in [75.0, 75.0, 75.0, 85.0, 85.0, 75.0, 85.0, 85.0, 75.0] {
+            assert_eq!(feed(&mut monitor, &mut relay, temp), None);
+        }
+        assert!(!relay.pin.high);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::Reading;
+
+// A replayed row is `t,ax,ay,az,gx,gy,gz,temp`, in ms, m/s^2, rad/s and ºC,
+// the first columns of the `csv` output. Any columns after them are
+// ignored, so a captured `csv` log replays as it is.
+pub const REPLAY_FIELDS: usize = 8;
+// Back to live sampling after this long without a line
+pub const REPLAY_TIMEOUT_MS: u64 = 10_000;
+pub const REPLAY_END: &str = "end";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayLine {
+    // Calibrated already, like the `csv` output, and stamped on the uptime
+    Sample(Reading),
+    // A header row, skipped without counting
+    Header,
+    End,
+    // Skipped and counted
+    Malformed,
+}
+
+// The fields of a row, None unless every one of the first REPLAY_FIELDS
+// is a number
+pub fn parse_row(line: &str) -> Option<(u64, [f32; 7])> {
+    let mut fields = line.split(',').map(str::trim);
+    let t_ms = fields.next()?.parse().ok()?;
+    let mut values = [0.0; REPLAY_FIELDS - 1];
+    for value in &mut values {
+        *value = fields
+            .next()?
+            .parse()
+            .ok()
+            .filter(|value: &f32| value.is_finite())?;
+    }
+    Some((t_ms, values))
+}
+
+// What's left of a `replay` session, fed one line at a time. The rows'
+// times move to the uptime the session started at, their spacing kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Replay {
+    start_ms: u64,
+    // The first row's time and the last one's
+    first_t_ms: Option<u64>,
+    last_t_ms: u64,
+    last_line_ms: u64,
+    samples: u32,
+    skipped: u32,
+}
+
+impl Replay {
+    pub fn new(uptime_ms: u64) -> Self {
+        Self {
+            start_ms: uptime_ms,
+            first_t_ms: None,
+            last_t_ms: 0,
+            last_line_ms: uptime_ms,
+            samples: 0,
+            skipped: 0,
+        }
+    }
+
+    // A row going back in time is malformed too
+    pub fn line(&mut self, line: &str, uptime_ms: u64) -> ReplayLine {
+        self.last_line_ms = uptime_ms;
+        let line = line.trim();
+        if line.eq_ignore_ascii_case(REPLAY_END) {
+            return ReplayLine::End;
+        }
+
+        let row = parse_row(line)
+            .filter(|(t_ms, _)| self.first_t_ms.is_none() || *t_ms >= self.last_t_ms);
+        let Some((t_ms, values)) = row else {
+            if self.samples == 0 && line.starts_with(|c: char| c.eq_ignore_ascii_case(&'t')) {
+                return ReplayLine::Header;
+            }
+            self.skipped += 1;
+            return ReplayLine::Malformed;
+        };
+
+        let first_t_ms = *self.first_t_ms.get_or_insert(t_ms);
+        self.last_t_ms = t_ms;
+        self.samples += 1;
+        let [ax, ay, az, gx, gy, gz, temp] = values;
+        ReplayLine::Sample(Reading::new(
+            [ax, ay, az],
+            [gx, gy, gz],
+            temp,
+            self.start_ms + (t_ms - first_t_ms),
+        ))
+    }
+
+    // A line that never ends counts as one too: a row too long for the
+    // console
+    pub fn skip(&mut self, uptime_ms: u64) {
+        self.last_line_ms = uptime_ms;
+        self.skipped += 1;
+    }
+
+    pub fn timed_out(&self, uptime_ms: u64) -> bool {
+        uptime_ms.saturating_sub(self.last_line_ms) >= REPLAY_TIMEOUT_MS
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+
+    // How long the replayed rows span, ms
+    pub fn span_ms(&self) -> u64 {
+        self.first_t_ms
+            .map_or(0, |first_t_ms| self.last_t_ms - first_t_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rows_and_the_csv_output() {
+        assert_eq!(
+            parse_row("500,0.1,-0.2,9.8,0,0.01,0,25.5"),
+            Some((500, [0.1, -0.2, 9.8, 0.0, 0.01, 0.0, 25.5]))
+        );
+        // A `csv` output row, chip temperature, health and alarm after it
+        assert_eq!(
+            parse_row(" 1000, 0.5,0,9.81,0,0,0,26 ,41.2,87,MECH"),
+            Some((1000, [0.5, 0.0, 9.81, 0.0, 0.0, 0.0, 26.0]))
+        );
+        for line in [
+            "500,0.1,-0.2,9.8,0,0.01,0",
+            "500,0.1,-0.2,9.8,0,x,0,25.5",
+            "-5,0.1,-0.2,9.8,0,0.01,0,25.5",
+            "500,0.1,-0.2,NaN,0,0.01,0,25.5",
+            "500;0.1;-0.2;9.8;0;0.01;0;25.5",
+            "",
+        ] {
+            assert_eq!(parse_row(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn session_rebases_counts_and_ends() {
+        let mut replay = Replay::new(60_000);
+        assert_eq!(
+            replay.line("t_ms,ax,ay,az,gx,gy,gz,temp,chip_temp,health,alarm", 60_010),
+            ReplayLine::Header
+        );
+        let lines = [
+            "12000,0,0,9.8,0,0,0,25",
+            "12500,0,0,9.8,0,0,0,25",
+            "garbage",
+            // Back in time
+            "12400,0,0,9.8,0,0,0,25",
+            "13000,1.5,0,9.8,0,0,0,25",
+        ];
+        let stamps: Vec<Option<u64>> = lines
+            .iter()
+            .map(|line| match replay.line(line, 60_020) {
+                ReplayLine::Sample(reading) => Some(reading.t_ms),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            stamps,
+            [Some(60_000), Some(60_500), None, None, Some(61_000)]
+        );
+        replay.skip(60_030);
+        assert_eq!((replay.samples(), replay.skipped()), (3, 3));
+        assert_eq!(replay.span_ms(), 1_000);
+
+        assert!(!replay.timed_out(60_030 + REPLAY_TIMEOUT_MS - 1));
+        assert!(replay.timed_out(60_030 + REPLAY_TIMEOUT_MS));
+        assert_eq!(replay.line(" END ", 60_040), ReplayLine::End);
+    }
+}
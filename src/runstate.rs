@@ -0,0 +1,277 @@
+// The shaft speed, in RPM, a stopped machine stays under and a running one
+// reaches. The running speed is where the vibration limits' speed bands
+// start applying.
+pub const STOPPED_RPM: f32 = 30.0;
+pub const RUNNING_RPM: f32 = 200.0;
+
+// Without a speed sensor, the vibration RMS in m/s^2 takes its place: a
+// stopped machine is quieter than this, and a running one louder
+pub const STOPPED_RMS: f32 = 0.05;
+pub const RUNNING_RMS: f32 = 0.1;
+
+// A level is left going down only below this much of it, so a machine
+// sitting right at one doesn't flap between two states
+pub const RUN_HYSTERESIS: f32 = 0.8;
+
+// How long after a start or a stop the motion limits are raised, or
+// suppressed, for the shake of the inrush or of the coasting down
+pub const RUN_GRACE_MS: u32 = 10_000;
+
+// Motion levels during the grace period, multiplied by this. None
+// suppresses them altogether.
+pub const TRANSIENT_SCALE: Option<f32> = Some(2.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunState {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+}
+
+impl RunState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RunState::Stopped => "Stopped",
+            RunState::Starting => "Starting",
+            RunState::Running => "Running",
+            RunState::Stopping => "Stopping",
+        }
+    }
+}
+
+// What tells a running machine from a stopped one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activity {
+    // RPM from the tachometer
+    Speed(f32),
+    // Vibration RMS in m/s^2, when there's no tachometer
+    Energy(f32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunTransition {
+    pub from: RunState,
+    pub to: RunState,
+}
+
+impl RunTransition {
+    // Back to a standstill without ever getting up to speed
+    pub fn is_failed_start(&self) -> bool {
+        self.from == RunState::Starting && self.to == RunState::Stopped
+    }
+}
+
+// Follows the machine through its starts and stops, from the speed or the
+// vibration energy of every sample.
+// The speed says when the machine is up to speed, the energy doesn't: an
+// inrush shakes as much as running does. With the energy alone, a start is
+// only over once its grace period is.
+// A start that never gets there stays Starting, but the limits are back
+// once its grace period is over.
+#[derive(Clone, Copy, Debug)]
+pub struct RunStateDetector {
+    grace_ms: u32,
+    state: RunState,
+    since_ms: u64,
+}
+
+impl RunStateDetector {
+    pub const fn new(grace_ms: u32) -> Self {
+        Self {
+            grace_ms,
+            state: RunState::Stopped,
+            since_ms: 0,
+        }
+    }
+
+    // None keeps the state, for a sample that says nothing about it
+    pub fn update(&mut self, activity: Option<Activity>, t_ms: u64) -> Option<RunTransition> {
+        let (value, stopped, running, settled) = match activity? {
+            Activity::Speed(rpm) => (rpm, STOPPED_RPM, RUNNING_RPM, true),
+            Activity::Energy(rms) => (rms, STOPPED_RMS, RUNNING_RMS, !self.in_grace(t_ms)),
+        };
+        let at_rest = value < stopped * RUN_HYSTERESIS;
+        let up_to_speed = value >= running;
+
+        let to = match self.state {
+            RunState::Stopped if value > stopped => RunState::Starting,
+            RunState::Starting if at_rest => RunState::Stopped,
+            RunState::Starting if up_to_speed && settled => RunState::Running,
+            RunState::Running if value < running * RUN_HYSTERESIS => RunState::Stopping,
+            RunState::Stopping if at_rest => RunState::Stopped,
+            RunState::Stopping if up_to_speed => RunState::Running,
+            state => state,
+        };
+        if to == self.state {
+            return None;
+        }
+
+        let transition = RunTransition {
+            from: self.state,
+            to,
+        };
+        self.state = to;
+        self.since_ms = t_ms;
+        Some(transition)
+    }
+
+    pub fn state(&self) -> RunState {
+        self.state
+    }
+
+    // True while the motion limits are raised
+    pub fn is_transient(&self, t_ms: u64) -> bool {
+        matches!(self.state, RunState::Starting | RunState::Stopping) && self.in_grace(t_ms)
+    }
+
+    fn in_grace(&self, t_ms: u64) -> bool {
+        t_ms.saturating_sub(self.since_ms) < self.grace_ms as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feeds a profile one sample every 500 ms from `t_ms`, and returns the
+    // transitions with the time they happened at
+    fn run(
+        detector: &mut RunStateDetector,
+        t_ms: &mut u64,
+        profile: impl IntoIterator<Item = Activity>,
+    ) -> heapless::Vec<(u64, RunTransition), 8> {
+        let mut transitions = heapless::Vec::new();
+        for activity in profile {
+            if let Some(transition) = detector.update(Some(activity), *t_ms) {
+                transitions.push((*t_ms, transition)).unwrap();
+            }
+            *t_ms += 500;
+        }
+        transitions
+    }
+
+    fn transition(from: RunState, to: RunState) -> RunTransition {
+        RunTransition { from, to }
+    }
+
+    #[test]
+    fn speed_ramps_through_a_start_and_a_stop() {
+        let mut detector = RunStateDetector::new(RUN_GRACE_MS);
+        let mut t_ms = 0;
+
+        // Up to 1500 RPM in 3 s, a while there, then coasting down
+        let ramp_up = (0..=6).map(|i| Activity::Speed(i as f32 * 250.0));
+        let transitions = run(&mut detector, &mut t_ms, ramp_up);
+        assert_eq!(
+            transitions.as_slice(),
+            [
+                (500, transition(RunState::Stopped, RunState::Starting)),
+                (1_000, transition(RunState::Starting, RunState::Running)),
+            ]
+        );
+        assert!(!detector.is_transient(t_ms));
+
+        let steady = [Activity::Speed(1_500.0); 10];
+        assert!(run(&mut detector, &mut t_ms, steady).is_empty());
+
+        let ramp_down = (0..=6).rev().map(|i| Activity::Speed(i as f32 * 25.0));
+        let stop_ms = t_ms;
+        let transitions = run(&mut detector, &mut t_ms, ramp_down);
+        assert_eq!(
+            transitions.as_slice(),
+            [
+                (stop_ms, transition(RunState::Running, RunState::Stopping)),
+                (
+                    stop_ms + 3_000,
+                    transition(RunState::Stopping, RunState::Stopped)
+                ),
+            ]
+        );
+        assert_eq!(detector.state(), RunState::Stopped);
+        assert!(!detector.is_transient(t_ms));
+    }
+
+    #[test]
+    fn a_start_that_never_gets_up_to_speed_fails() {
+        let mut detector = RunStateDetector::new(RUN_GRACE_MS);
+        let mut t_ms = 0;
+
+        // Stalled at 120 RPM for 15 s
+        let stalled = [Activity::Speed(120.0); 30];
+        let transitions = run(&mut detector, &mut t_ms, stalled);
+        assert_eq!(
+            transitions.as_slice(),
+            [(0, transition(RunState::Stopped, RunState::Starting))]
+        );
+        assert_eq!(detector.state(), RunState::Starting);
+        assert!(detector.is_transient(RUN_GRACE_MS as u64 - 1));
+        // Still starting, but the limits are back
+        assert!(!detector.is_transient(t_ms));
+
+        let transitions = run(&mut detector, &mut t_ms, [Activity::Speed(0.0)]);
+        let (_, failed) = transitions[0];
+        assert_eq!(failed, transition(RunState::Starting, RunState::Stopped));
+        assert!(failed.is_failed_start());
+    }
+
+    #[test]
+    fn energy_starts_run_once_the_grace_is_over() {
+        let mut detector = RunStateDetector::new(RUN_GRACE_MS);
+        let mut t_ms = 0;
+
+        // An inrush shake, then running
+        let start = [0.02, 0.8, 0.6]
+            .into_iter()
+            .chain([0.3; 24])
+            .map(Activity::Energy);
+        let transitions = run(&mut detector, &mut t_ms, start);
+        assert_eq!(
+            transitions.as_slice(),
+            [
+                (500, transition(RunState::Stopped, RunState::Starting)),
+                (
+                    500 + RUN_GRACE_MS as u64,
+                    transition(RunState::Starting, RunState::Running)
+                ),
+            ]
+        );
+
+        // Dies down between the levels for a moment, then stops
+        let stop = [0.07, 0.09, 0.12, 0.06, 0.03].map(Activity::Energy);
+        let stop_ms = t_ms;
+        let transitions = run(&mut detector, &mut t_ms, stop);
+        assert_eq!(
+            transitions.as_slice(),
+            [
+                (stop_ms, transition(RunState::Running, RunState::Stopping)),
+                (
+                    stop_ms + 1_000,
+                    transition(RunState::Stopping, RunState::Running)
+                ),
+                (
+                    stop_ms + 1_500,
+                    transition(RunState::Running, RunState::Stopping)
+                ),
+                (
+                    stop_ms + 2_000,
+                    transition(RunState::Stopping, RunState::Stopped)
+                ),
+            ]
+        );
+        // A sample that says nothing keeps the state
+        assert_eq!(detector.update(None, t_ms), None);
+    }
+
+    #[test]
+    fn a_shake_that_dies_down_at_once_is_a_failed_start() {
+        let mut detector = RunStateDetector::new(RUN_GRACE_MS);
+        let mut t_ms = 0;
+
+        let bump = [0.5, 0.2, 0.01].map(Activity::Energy);
+        let transitions = run(&mut detector, &mut t_ms, bump);
+        assert_eq!(transitions.len(), 2);
+        assert!(transitions[1].1.is_failed_start());
+        assert!(!detector.is_transient(t_ms));
+    }
+}
@@ -0,0 +1,266 @@
+use heapless::Vec;
+
+// Room for every task the firmware has, with all its features on
+pub const MAX_TASKS: usize = 32;
+
+// A task gets the shared context and the tick time in ms
+pub type TaskFn<C> = fn(&mut C, u32);
+
+// A periodic activity. It runs on the ticks where
+// `(now_ms - offset_ms) % period_ms == 0`, so the period and offset should
+// be multiples of the tick.
+pub struct Task<C> {
+    name: &'static str,
+    period_ms: u32,
+    offset_ms: u32,
+    run: TaskFn<C>,
+    overruns: u32,
+    longest_ms: u32,
+}
+
+impl<C> Task<C> {
+    pub const fn new(name: &'static str, period_ms: u32, run: TaskFn<C>) -> Self {
+        Self {
+            name,
+            period_ms,
+            offset_ms: 0,
+            run,
+            overruns: 0,
+            longest_ms: 0,
+        }
+    }
+
+    // Spreads tasks of the same period over different ticks
+    pub const fn with_offset(self, offset_ms: u32) -> Self {
+        Self { offset_ms, ..self }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn period_ms(&self) -> u32 {
+        self.period_ms
+    }
+
+    // Runs that took longer than the period
+    pub fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    pub fn longest_ms(&self) -> u32 {
+        self.longest_ms
+    }
+
+    fn is_due(&self, now_ms: u32) -> bool {
+        // A period of 0 never runs
+        now_ms
+            .wrapping_sub(self.offset_ms)
+            .checked_rem(self.period_ms)
+            == Some(0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overrun {
+    pub task: &'static str,
+    pub took_ms: u32,
+    pub period_ms: u32,
+}
+
+// Cooperative multi-rate scheduler, without an RTOS.
+// Every tick the due tasks run to completion in the order they were added.
+// A task that runs longer than its period is an overrun, and so is a tick
+// whose tasks together take longer than the tick: both delay whatever
+// is due next.
+pub struct Scheduler<C, const N: usize = MAX_TASKS> {
+    tick_ms: u32,
+    tasks: Vec<Task<C>, N>,
+    tick_overruns: u32,
+}
+
+impl<C, const N: usize> Scheduler<C, N> {
+    pub const fn new(tick_ms: u32) -> Self {
+        Self {
+            tick_ms,
+            tasks: Vec::new(),
+            tick_overruns: 0,
+        }
+    }
+
+    // The whole list at once, one too long for the scheduler doesn't build
+    pub fn with_tasks<const M: usize>(tick_ms: u32, tasks: [Task<C>; M]) -> Self {
+        let () = Fits::<M, N>::OK;
+        let mut scheduler = Self::new(tick_ms);
+        for task in tasks {
+            scheduler.add(task);
+        }
+        scheduler
+    }
+
+    pub fn add(&mut self, task: Task<C>) {
+        if self.tasks.push(task).is_err() {
+            panic!("scheduler full, raise MAX_TASKS");
+        }
+    }
+
+    // Runs the tasks due at `now_ms`. `clock` reads the actual time in ms
+    // from the context, to time them.
+    // Returns the worst task overrun of the tick, if any.
+    pub fn run(&mut self, context: &mut C, now_ms: u32, clock: fn(&C) -> u32) -> Option<Overrun> {
+        let tick_start = clock(context);
+        let mut worst: Option<Overrun> = None;
+
+        for task in self.tasks.iter_mut().filter(|task| task.is_due(now_ms)) {
+            let start = clock(context);
+            (task.run)(context, now_ms);
+            let took_ms = clock(context).wrapping_sub(start);
+
+            task.longest_ms = task.longest_ms.max(took_ms);
+            if took_ms > task.period_ms {
+                task.overruns = task.overruns.wrapping_add(1);
+                let overrun = Overrun {
+                    task: task.name,
+                    took_ms,
+                    period_ms: task.period_ms,
+                };
+                worst = match worst {
+                    Some(worst) if worst.took_ms >= took_ms => Some(worst),
+                    _ => Some(overrun),
+                };
+            }
+        }
+
+        if clock(context).wrapping_sub(tick_start) > self.tick_ms {
+            self.tick_overruns = self.tick_overruns.wrapping_add(1);
+        }
+        worst
+    }
+
+    pub fn tasks(&self) -> &[Task<C>] {
+        &self.tasks
+    }
+
+    // Ticks whose tasks took longer than the tick
+    pub fn tick_overruns(&self) -> u32 {
+        self.tick_overruns
+    }
+}
+
+// Evaluated for each `with_tasks()` that's called, at compile time
+struct Fits<const M: usize, const N: usize>;
+
+impl<const M: usize, const N: usize> Fits<M, N> {
+    const OK: () = assert!(M <= N, "more tasks than the scheduler has room for");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The clock only moves when a task says it took time
+    #[derive(Default)]
+    struct Context {
+        clock_ms: u32,
+        runs: Vec<(&'static str, u32), 32>,
+    }
+
+    fn clock(context: &Context) -> u32 {
+        context.clock_ms
+    }
+
+    fn fast(context: &mut Context, now_ms: u32) {
+        context.runs.push(("fast", now_ms)).unwrap();
+    }
+
+    fn slow(context: &mut Context, now_ms: u32) {
+        context.runs.push(("slow", now_ms)).unwrap();
+        context.clock_ms += 30;
+    }
+
+    fn scheduler() -> Scheduler<Context, 4> {
+        let mut scheduler = Scheduler::new(10);
+        scheduler.add(Task::new("slow", 50, slow).with_offset(20));
+        scheduler.add(Task::new("fast", 10, fast));
+        scheduler
+    }
+
+    #[test]
+    fn tasks_run_at_their_period_and_in_order() {
+        let mut scheduler = scheduler();
+        let mut context = Context::default();
+
+        for now_ms in (0..100).step_by(10) {
+            scheduler.run(&mut context, now_ms, clock);
+        }
+
+        let slow: Vec<u32, 4> = context
+            .runs
+            .iter()
+            .filter(|(name, _)| *name == "slow")
+            .map(|(_, now_ms)| *now_ms)
+            .collect();
+        assert_eq!(slow, [20, 70]);
+        assert_eq!(
+            context
+                .runs
+                .iter()
+                .filter(|(name, _)| *name == "fast")
+                .count(),
+            10
+        );
+        // Same tick, the slow task was added first
+        let at_20: Vec<&str, 2> = context
+            .runs
+            .iter()
+            .filter(|(_, now_ms)| *now_ms == 20)
+            .map(|(name, _)| *name)
+            .collect();
+        assert_eq!(at_20, ["slow", "fast"]);
+    }
+
+    #[test]
+    fn long_runs_are_overruns() {
+        let mut scheduler = scheduler();
+        let mut context = Context::default();
+
+        assert_eq!(scheduler.run(&mut context, 0, clock), None);
+        // 30 ms is within the slow task's period, but not within the tick
+        assert_eq!(scheduler.run(&mut context, 20, clock), None);
+        assert_eq!(scheduler.tick_overruns(), 1);
+        assert_eq!(scheduler.tasks()[0].longest_ms(), 30);
+
+        let mut scheduler: Scheduler<Context, 1> = Scheduler::new(10);
+        scheduler.add(Task::new("slow", 20, slow));
+        assert_eq!(
+            scheduler.run(&mut context, 0, clock),
+            Some(Overrun {
+                task: "slow",
+                took_ms: 30,
+                period_ms: 20
+            })
+        );
+        assert_eq!(scheduler.tasks()[0].overruns(), 1);
+    }
+
+    #[test]
+    fn tasks_can_be_given_at_once() {
+        let mut scheduler: Scheduler<Context, 2> = Scheduler::with_tasks(
+            10,
+            [Task::new("fast", 10, fast), Task::new("slow", 20, slow)],
+        );
+        let mut context = Context::default();
+
+        scheduler.run(&mut context, 0, clock);
+        assert_eq!(context.runs, [("fast", 0), ("slow", 0)]);
+        assert_eq!(scheduler.tasks().len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_tasks_panic() {
+        let mut scheduler: Scheduler<Context, 1> = Scheduler::new(10);
+        scheduler.add(Task::new("fast", 10, fast));
+        scheduler.add(Task::new("slow", 50, slow));
+    }
+}
@@ -0,0 +1,331 @@
+use heapless::HistoryBuffer;
+
+use crate::zscore::RunningStats;
+
+// Alarms and faults count over the last hour of minutes
+pub const SCORE_WINDOW_MINUTES: usize = 60;
+
+// Where each part of the score bottoms out: the vibration at this many
+// times its baseline, the temperature at its ceiling's warning level, this
+// many alarms or sensor faults in the hour. Each part is full at the
+// baseline, TEMPERATURE_MARGIN_FULL ºC under the ceiling, and none.
+pub const VIBRATION_RATIO_ZERO: f32 = 4.0;
+pub const TEMPERATURE_MARGIN_FULL: f32 = 20.0;
+pub const ALARMS_ZERO: u32 = 10;
+pub const FAULTS_ZERO: u32 = 3;
+
+// The vibration baseline is the mean of the minutes the machine ran, over
+// about a day of them, and only scores once it has BASELINE_MIN_MINUTES.
+// A machine barely vibrating is held to VIBRATION_FLOOR m/s^2.
+pub const BASELINE_MINUTES: u32 = 1_440;
+pub const BASELINE_MIN_MINUTES: u32 = 10;
+pub const VIBRATION_FLOOR: f32 = 0.05;
+
+// How much each part weighs in the score, relative to the others
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreWeights {
+    pub vibration: f32,
+    pub temperature: f32,
+    pub alarms: f32,
+    pub faults: f32,
+}
+
+pub const SCORE_WEIGHTS: ScoreWeights = ScoreWeights {
+    vibration: 0.4,
+    temperature: 0.2,
+    alarms: 0.3,
+    faults: 0.1,
+};
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        SCORE_WEIGHTS
+    }
+}
+
+// A minute's worth of machine state. The vibration ratio and the
+// temperature margin are None when there's nothing to go by, and their
+// weight goes to the other parts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreInputs {
+    // Vibration RMS over its baseline
+    pub vibration_ratio: Option<f32>,
+    // ºC under the temperature ceiling's warning level
+    pub temperature_margin: Option<f32>,
+    pub alarms: u32,
+    pub faults: u32,
+}
+
+// 0 to 1 from `full` down to `zero`, either way round
+fn ramp(value: f32, full: f32, zero: f32) -> f32 {
+    ((value - zero) / (full - zero)).clamp(0.0, 1.0)
+}
+
+// 100 is a machine running like its baseline, cool, with no alarms or
+// faults in the last hour
+pub fn health_score(inputs: &ScoreInputs, weights: &ScoreWeights) -> u8 {
+    let parts = [
+        (
+            weights.vibration,
+            inputs
+                .vibration_ratio
+                .map(|ratio| ramp(ratio, 1.0, VIBRATION_RATIO_ZERO)),
+        ),
+        (
+            weights.temperature,
+            inputs
+                .temperature_margin
+                .map(|margin| ramp(margin, TEMPERATURE_MARGIN_FULL, 0.0)),
+        ),
+        (
+            weights.alarms,
+            Some(ramp(inputs.alarms as f32, 0.0, ALARMS_ZERO as f32)),
+        ),
+        (
+            weights.faults,
+            Some(ramp(inputs.faults as f32, 0.0, FAULTS_ZERO as f32)),
+        ),
+    ];
+
+    let (mut total, mut weighted) = (0.0, 0.0);
+    for (weight, part) in parts {
+        // NaN drops out along with None
+        if let Some(part) = part.filter(|part| !part.is_nan()) {
+            total += weight;
+            weighted += weight * part;
+        }
+    }
+    if total <= 0.0 {
+        return 100;
+    }
+    (100.0 * weighted / total + 0.5) as u8
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreBand {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl ScoreBand {
+    pub fn of(score: u8) -> Self {
+        match score {
+            80.. => ScoreBand::Good,
+            50.. => ScoreBand::Fair,
+            _ => ScoreBand::Poor,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScoreBand::Good => "good",
+            ScoreBand::Fair => "fair",
+            ScoreBand::Poor => "poor",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MinuteCounts {
+    alarms: u16,
+    faults: u16,
+    vibration_sum: f32,
+    vibration_samples: u32,
+}
+
+// Gathers the score's inputs as the minute goes, scores it when it closes
+pub struct ScoreTracker<const N: usize = SCORE_WINDOW_MINUTES> {
+    minute: MinuteCounts,
+    // Alarms and faults of each closed minute
+    history: HistoryBuffer<(u16, u16), N>,
+    baseline: RunningStats,
+    last: Option<u8>,
+}
+
+impl<const N: usize> ScoreTracker<N> {
+    pub const fn new() -> Self {
+        Self {
+            minute: MinuteCounts {
+                alarms: 0,
+                faults: 0,
+                vibration_sum: 0.0,
+                vibration_samples: 0,
+            },
+            history: HistoryBuffer::new(),
+            baseline: RunningStats::new(BASELINE_MINUTES, 0.0),
+            last: None,
+        }
+    }
+
+    // Every status sample, with the vibration RMS only while the machine
+    // runs
+    pub fn push(&mut self, vibration_rms: Option<f32>) {
+        if let Some(rms) = vibration_rms.filter(|rms| rms.is_finite()) {
+            self.minute.vibration_sum += rms;
+            self.minute.vibration_samples += 1;
+        }
+    }
+
+    pub fn alarm(&mut self) {
+        self.minute.alarms = self.minute.alarms.saturating_add(1);
+    }
+
+    pub fn fault(&mut self) {
+        self.minute.faults = self.minute.faults.saturating_add(1);
+    }
+
+    // At the end of the minute. The minute's vibration is scored against
+    // the baseline before it, then joins it.
+    pub fn close(&mut self, temperature_margin: f32, weights: &ScoreWeights) -> u8 {
+        let minute = core::mem::take(&mut self.minute);
+        self.history.write((minute.alarms, minute.faults));
+
+        let vibration = match minute.vibration_samples {
+            0 => None,
+            samples => Some(minute.vibration_sum / samples as f32),
+        };
+        let vibration_ratio = match vibration {
+            Some(rms) if self.baseline.count() >= BASELINE_MIN_MINUTES => {
+                Some(rms / self.baseline.mean().max(VIBRATION_FLOOR))
+            }
+            _ => None,
+        };
+        if let Some(rms) = vibration {
+            self.baseline.push(rms);
+        }
+
+        let (alarms, faults) = self
+            .history
+            .oldest_ordered()
+            .fold((0, 0), |(alarms, faults), (a, f)| {
+                (alarms + *a as u32, faults + *f as u32)
+            });
+        let inputs = ScoreInputs {
+            vibration_ratio,
+            temperature_margin: Some(temperature_margin),
+            alarms,
+            faults,
+        };
+        let score = health_score(&inputs, weights);
+        self.last = Some(score);
+        score
+    }
+
+    // None until the first minute closes
+    pub fn last(&self) -> Option<u8> {
+        self.last
+    }
+}
+
+impl<const N: usize> Default for ScoreTracker<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEALTHY: ScoreInputs = ScoreInputs {
+        vibration_ratio: Some(1.0),
+        temperature_margin: Some(30.0),
+        alarms: 0,
+        faults: 0,
+    };
+
+    #[test]
+    fn scenarios_score_by_their_weights() {
+        let weights = ScoreWeights::default();
+        assert_eq!(health_score(&HEALTHY, &weights), 100);
+        assert_eq!(ScoreBand::of(100), ScoreBand::Good);
+
+        // Worn bearing: two and a half times the baseline vibration, a few
+        // alarms
+        let worn = ScoreInputs {
+            vibration_ratio: Some(2.5),
+            alarms: 3,
+            ..HEALTHY
+        };
+        // 0.4 * 0.5 + 0.2 + 0.3 * 0.7 + 0.1
+        assert_eq!(health_score(&worn, &weights), 71);
+        assert_eq!(ScoreBand::of(71), ScoreBand::Fair);
+
+        // Overheating at the ceiling, alarming all hour, the sensor failing
+        let failing = ScoreInputs {
+            vibration_ratio: Some(5.0),
+            temperature_margin: Some(-2.0),
+            alarms: 40,
+            faults: 3,
+        };
+        assert_eq!(health_score(&failing, &weights), 0);
+        assert_eq!(ScoreBand::of(49), ScoreBand::Poor);
+
+        // Only the temperature counts here
+        let hot = ScoreInputs {
+            temperature_margin: Some(5.0),
+            ..HEALTHY
+        };
+        let temperature_only = ScoreWeights {
+            vibration: 0.0,
+            temperature: 1.0,
+            alarms: 0.0,
+            faults: 0.0,
+        };
+        assert_eq!(health_score(&hot, &temperature_only), 25);
+    }
+
+    #[test]
+    fn missing_inputs_hand_their_weight_over() {
+        let weights = ScoreWeights::default();
+        let unknown = ScoreInputs {
+            vibration_ratio: None,
+            temperature_margin: Some(f32::NAN),
+            alarms: 4,
+            ..HEALTHY
+        };
+        // Alarms and faults only: (0.3 * 0.6 + 0.1) / 0.4
+        assert_eq!(health_score(&unknown, &weights), 70);
+
+        let nothing = ScoreWeights {
+            vibration: 0.0,
+            temperature: 0.0,
+            alarms: 0.0,
+            faults: 0.0,
+        };
+        assert_eq!(health_score(&unknown, &nothing), 100);
+    }
+
+    #[test]
+    fn tracker_learns_the_baseline_and_forgets_old_alarms() {
+        let weights = ScoreWeights {
+            vibration: 1.0,
+            temperature: 0.0,
+            alarms: 1.0,
+            faults: 0.0,
+        };
+        let mut tracker: ScoreTracker<3> = ScoreTracker::new();
+        assert_eq!(tracker.last(), None);
+
+        // Scored on the alarms alone while the baseline warms up
+        for _ in 0..BASELINE_MIN_MINUTES {
+            tracker.push(Some(1.0));
+            tracker.push(None);
+            assert_eq!(tracker.close(30.0, &weights), 100);
+        }
+
+        // Twice the baseline, one alarm
+        tracker.push(Some(2.0));
+        tracker.alarm();
+        let score = tracker.close(30.0, &weights);
+        assert_eq!(score, (100.0 * (2.0 / 3.0 + 0.9) / 2.0 + 0.5) as u8);
+        assert_eq!(tracker.last(), Some(score));
+
+        // Back to normal, the alarm counts until it's out of the window
+        for expected in [95, 95, 100] {
+            tracker.push(Some(1.0));
+            assert_eq!(tracker.close(30.0, &weights), expected);
+        }
+    }
+}
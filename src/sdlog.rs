@@ -0,0 +1,302 @@
+use core::fmt::{self, Write};
+
+use heapless::{String, Vec};
+
+use crate::summary::SUMMARY_SAMPLES;
+use crate::telemetry::{limit_key, severity_key};
+use crate::{fixed::Fixed, Alert, Reading, Summary};
+
+// The `sd-card` feature's CSV log, on a FAT-formatted microSD card. Rows
+// are kept here until the `sd-card` task writes them out, so a slow card
+// or a missing one never holds up a sample. Files are `LOG_000.CSV` to
+// `LOG_999.CSV`, the next one started at 10 MB.
+pub const ROTATE_BYTES: u32 = 10 * 1024 * 1024;
+pub const MAX_LOG_NUMBER: u16 = 999;
+// ~20 rows, a few minutes of summaries between two writes with room for
+// an alarm burst. Past it new rows are dropped, and counted.
+pub const ROW_BUFFER_BYTES: usize = 4096;
+// The card's directory entry is updated every write, what a power cut loses
+pub const SD_WRITE_MS: u32 = 10_000;
+// Mounting the card again, after it was missing or a write failed
+pub const SD_RETRY_MS: u32 = 30_000;
+pub const SD_DECIMALS: u8 = 3;
+// The longest row, an alarm's, with every column filled
+const ROW_LEN: usize = 256;
+
+pub const SD_COLUMNS: &str = "kind,t_ms,samples,\
+ax_min,ax_mean,ax_max,ay_min,ay_mean,ay_max,az_min,az_mean,az_max,\
+gx_min,gx_mean,gx_max,gy_min,gy_mean,gy_max,gz_min,gz_mean,gz_max,\
+temp_min,temp_mean,temp_max,vib_rms,health,limit,severity,value,threshold";
+
+// `LOG_007.CSV`
+pub fn file_name(number: u16) -> String<12> {
+    let mut name = String::new();
+    write!(name, "LOG_{:03}.CSV", number.min(MAX_LOG_NUMBER)).ok();
+    name
+}
+
+// The number of a log's file name, in any case, None for other files
+pub fn file_number(name: &str) -> Option<u16> {
+    let name = name.as_bytes();
+    if name.len() != 11
+        || !name[..4].eq_ignore_ascii_case(b"LOG_")
+        || !name[7..].eq_ignore_ascii_case(b".CSV")
+    {
+        return None;
+    }
+    name[4..7].iter().try_fold(0u16, |number, digit| {
+        digit
+            .is_ascii_digit()
+            .then(|| number * 10 + u16::from(digit - b'0'))
+    })
+}
+
+// What a row has besides its readings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RowContext {
+    pub vibration_rms: Option<f32>,
+    pub health: Option<u8>,
+}
+
+// Every summary interval, and every alarm as it fires
+pub struct SdLog {
+    summary: Summary,
+    rows: Vec<u8, ROW_BUFFER_BYTES>,
+    dropped: u32,
+}
+
+impl SdLog {
+    pub const fn new() -> Self {
+        Self {
+            summary: Summary::new(),
+            rows: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    // A row once `SUMMARY_SAMPLES` are in, whatever the output mode
+    pub fn sample(&mut self, reading: &Reading, context: &RowContext) {
+        self.summary.push(reading);
+        if self.summary.count() < SUMMARY_SAMPLES {
+            return;
+        }
+        let row = SummaryRow {
+            summary: &self.summary,
+            t_ms: reading.t_ms,
+            context,
+        };
+        let mut line = String::<ROW_LEN>::new();
+        let written = writeln!(line, "{}", row).is_ok();
+        self.summary.clear();
+        self.push(written, &line);
+    }
+
+    pub fn alarm(
+        &mut self,
+        alert: &Alert,
+        reading: &Reading,
+        value: f32,
+        threshold: Option<f32>,
+        context: &RowContext,
+    ) {
+        let row = AlarmRow {
+            alert,
+            reading,
+            value,
+            threshold,
+            context,
+        };
+        let mut line = String::<ROW_LEN>::new();
+        let written = writeln!(line, "{}", row).is_ok();
+        self.push(written, &line);
+    }
+
+    fn push(&mut self, written: bool, line: &str) {
+        if !written || self.rows.extend_from_slice(line.as_bytes()).is_err() {
+            self.dropped += 1;
+        }
+    }
+
+    // Whole rows, oldest first
+    pub fn pending(&self) -> &[u8] {
+        &self.rows
+    }
+
+    // Once `pending()` is on the card
+    pub fn written(&mut self) {
+        self.rows.clear();
+    }
+
+    // Since boot, for lack of room
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl Default for SdLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn value(f: &mut fmt::Formatter<'_>, value: Option<f32>) -> fmt::Result {
+    f.write_str(",")?;
+    match value.and_then(|value| Fixed::new(value, SD_DECIMALS)) {
+        Some(value) => write!(f, "{}", value),
+        None => Ok(()),
+    }
+}
+
+fn tail(f: &mut fmt::Formatter<'_>, context: &RowContext) -> fmt::Result {
+    value(f, context.vibration_rms)?;
+    f.write_str(",")?;
+    match context.health {
+        Some(health) => write!(f, "{}", health),
+        None => Ok(()),
+    }
+}
+
+struct SummaryRow<'a> {
+    summary: &'a Summary,
+    t_ms: u64,
+    context: &'a RowContext,
+}
+
+impl fmt::Display for SummaryRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "summary,{},{}", self.t_ms, self.summary.count())?;
+        let acc = self.summary.acc().into_iter().flatten();
+        let gyro = self.summary.gyro().into_iter().flatten();
+        for spread in acc.chain(gyro).chain(self.summary.temp()) {
+            for channel in [spread.min, spread.mean, spread.max] {
+                value(f, Some(channel))?;
+            }
+        }
+        tail(f, self.context)?;
+        // No alarm
+        f.write_str(",,,,")
+    }
+}
+
+// The reading that tripped it in the mean columns
+struct AlarmRow<'a> {
+    alert: &'a Alert,
+    reading: &'a Reading,
+    value: f32,
+    threshold: Option<f32>,
+    context: &'a RowContext,
+}
+
+impl fmt::Display for AlarmRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "alarm,{},1", self.reading.t_ms)?;
+        let reading = self.reading;
+        for channel in reading
+            .acc
+            .iter()
+            .chain(&reading.gyro)
+            .chain([&reading.temp])
+        {
+            f.write_str(",")?;
+            value(f, Some(*channel))?;
+            f.write_str(",")?;
+        }
+        tail(f, self.context)?;
+        write!(
+            f,
+            ",{},{}",
+            limit_key(self.alert.limit),
+            severity_key(self.alert.severity)
+        )?;
+        value(f, Some(self.value))?;
+        value(f, self.threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limit, Severity};
+
+    fn reading(t_ms: u64, ax: f32) -> Reading {
+        Reading {
+            acc: [ax, 0.0, 9.81],
+            gyro: [0.0, 0.01, 0.0],
+            temp: 25.5,
+            t_ms,
+        }
+    }
+
+    const CONTEXT: RowContext = RowContext {
+        vibration_rms: Some(0.25),
+        health: Some(90),
+    };
+
+    fn columns(row: &str) -> usize {
+        row.trim_end().split(',').count()
+    }
+
+    #[test]
+    fn names_the_files() {
+        assert_eq!(file_name(7).as_str(), "LOG_007.CSV");
+        assert_eq!(file_number("LOG_007.CSV"), Some(7));
+        assert_eq!(file_number("log_123.csv"), Some(123));
+        assert_eq!(file_number("LOG_12.CSV"), None);
+        assert_eq!(file_number("LOG_1X3.CSV"), None);
+        assert_eq!(file_number("DATA.CSV"), None);
+    }
+
+    #[test]
+    fn writes_a_summary_every_interval() {
+        let mut log = SdLog::new();
+        for i in 0..SUMMARY_SAMPLES {
+            assert!(log.pending().is_empty());
+            log.sample(&reading(u64::from(i) * 500, i as f32 * 0.1), &CONTEXT);
+        }
+        let row = core::str::from_utf8(log.pending()).unwrap();
+        assert!(row.starts_with("summary,9500,20,0.000,0.950,1.900,0.000,"));
+        assert!(row.ends_with(",25.500,25.500,25.500,0.250,90,,,,\n"));
+        assert_eq!(columns(row), columns(SD_COLUMNS));
+
+        log.written();
+        assert!(log.pending().is_empty());
+    }
+
+    #[test]
+    fn writes_an_alarm_in_full() {
+        let mut log = SdLog::new();
+        let alert = Alert {
+            limit: Limit::Mechanical,
+            severity: Severity::Critical,
+        };
+        let context = RowContext {
+            health: None,
+            ..CONTEXT
+        };
+        log.alarm(&alert, &reading(1_000, 1.5), 0.9, Some(0.8), &context);
+        let row = core::str::from_utf8(log.pending()).unwrap();
+        assert_eq!(
+            row,
+            "alarm,1000,1,,1.500,,,0.000,,,9.810,,,0.000,,,0.010,,,0.000,,,25.500,,0.250,,mechanical,critical,0.900,0.800\n"
+        );
+        assert_eq!(columns(row), columns(SD_COLUMNS));
+    }
+
+    #[test]
+    fn drops_the_rows_that_dont_fit() {
+        let mut log = SdLog::new();
+        let alert = Alert {
+            limit: Limit::Temperature,
+            severity: Severity::Warning,
+        };
+        for _ in 0..100 {
+            log.alarm(&alert, &reading(0, 0.0), 1.0, None, &CONTEXT);
+        }
+        assert!(log.dropped() > 0);
+        assert!(log.pending().ends_with(b"\n"));
+        assert_eq!(
+            log.pending().iter().filter(|byte| **byte == b'\n').count() as u32,
+            100 - log.dropped()
+        );
+    }
+}
@@ -0,0 +1,829 @@
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use mpu6050::device::{
+    AccelRange, GyroRange, ACCEL_CONFIG, ACCEL_HPF, ACC_REGX_H, CONFIG, GYRO_CONFIG, GYRO_REGX_H,
+    INT_ENABLE, INT_PIN_CFG, INT_STATUS, MOT_DUR, MOT_THR, PWR_MGMT_1, PWR_MGMT_2, WHOAMI,
+};
+use mpu6050::{Mpu6050, Mpu6050Error};
+
+use crate::{math, Axis, Reading};
+
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+
+// What the monitor reads from an IMU, whatever the chip. Every
+// implementation converts to m/s^2, rad/s and ºC itself, so the limits mean
+// the same on all of them.
+pub trait ImuSensor {
+    type Error;
+
+    // Out of reset or sleep and ready to read, at its configured ranges
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Self::Error>;
+
+    fn read_accel(&mut self) -> Result<[f32; 3], Self::Error>;
+
+    fn read_gyro(&mut self) -> Result<[f32; 3], Self::Error>;
+
+    fn read_temp(&mut self) -> Result<f32, Self::Error>;
+
+    // Every channel in one go, stamped with the uptime `t_ms`
+    fn read(&mut self, t_ms: u64) -> Result<Reading, Self::Error> {
+        let acc = self.read_accel()?;
+        let gyro = self.read_gyro()?;
+        let temp = self.read_temp()?;
+        Ok(Reading::new(acc, gyro, temp, t_ms))
+    }
+}
+
+// The driver's `init()` resets the ranges to ±2 g and ±250 º/s,
+// `SensorConfig::apply()` has to follow
+impl<I, E> ImuSensor for Mpu6050<I>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Error = Mpu6050Error<E>;
+
+    // An unknown identity is woken up like the MPU6500/9250
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Self::Error> {
+        let model = Model::from_who_am_i(who_am_i(self)?).unwrap_or(Model::Mpu6500);
+        init(self, model, delay)
+    }
+
+    fn read_accel(&mut self) -> Result<[f32; 3], Self::Error> {
+        let acc = self.get_acc()?;
+        Ok(to_ms2([acc[0], acc[1], acc[2]]))
+    }
+
+    fn read_gyro(&mut self) -> Result<[f32; 3], Self::Error> {
+        let gyro = self.get_gyro()?;
+        Ok([gyro[0], gyro[1], gyro[2]])
+    }
+
+    fn read_temp(&mut self) -> Result<f32, Self::Error> {
+        self.get_temp()
+    }
+}
+
+// Chips answering WHO_AM_I with a known identity.
+// The MPU6500/9250 share the register layout used here, but the temperature
+// scaling and the self-test procedure are MPU6050 specific. The LSM6DS3 has
+// its own driver, see `lsm6ds3`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Model {
+    Mpu6050,
+    Mpu6500,
+    Mpu9250,
+    Lsm6ds3,
+    // The `sim` feature's signal generator
+    Simulator,
+}
+
+impl Model {
+    pub fn from_who_am_i(id: u8) -> Option<Self> {
+        match id {
+            0x68 => Some(Model::Mpu6050),
+            0x70 => Some(Model::Mpu6500),
+            0x71 => Some(Model::Mpu9250),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Mpu6050 => "MPU6050",
+            Model::Mpu6500 => "MPU6500",
+            Model::Mpu9250 => "MPU9250",
+            Model::Lsm6ds3 => "LSM6DS3",
+            Model::Simulator => "simulator",
+        }
+    }
+}
+
+pub const EXPECTED_WHO_AM_I: u8 = 0x68;
+
+// Reads WHO_AM_I, before `init()` so a wrong device is reported clearly
+// instead of failing halfway through the setup
+pub fn who_am_i<I, E>(mpu: &mut Mpu6050<I>) -> Result<u8, Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.read_byte(WHOAMI)
+}
+
+// The driver's `init()` only accepts an MPU6050 identity,
+// the other models are woken up the same way without the check
+pub fn init<I, E, D>(
+    mpu: &mut Mpu6050<I>,
+    model: Model,
+    delay: &mut D,
+) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    D: DelayMs<u8>,
+{
+    if model == Model::Mpu6050 {
+        return mpu.init(delay);
+    }
+
+    // Out of sleep, clocked from the X gyro
+    mpu.write_byte(PWR_MGMT_1::ADDR, 0x01)?;
+    delay.delay_ms(100);
+    Ok(())
+}
+
+// Settle time after `full_power()`: the gyroscope start-up time out of
+// sleep or standby is 30 ms typical (datasheet), which also covers the
+// low-pass filter delay and the temperature sensor's first conversion
+pub const WAKE_SETTLE_MS: u8 = 30;
+
+// Sleep mode keeps the configuration, and draws a few µA instead of ~3.8 mA.
+// Nothing is measured, the motion detector included.
+pub fn set_sleep<I, E>(mpu: &mut Mpu6050<I>, sleep: bool) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_bit(PWR_MGMT_1::ADDR, PWR_MGMT_1::SLEEP, sleep)
+}
+
+// How often the accelerometer wakes for a sample in cycle mode
+// (LP_WAKE_CTRL), and the supply current then (datasheet)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WakeRate {
+    // 10 µA
+    Hz1_25 = 0,
+    // 20 µA
+    Hz5,
+    // 70 µA
+    Hz20,
+    // 140 µA
+    Hz40,
+}
+
+impl WakeRate {
+    pub fn hz(&self) -> f32 {
+        match self {
+            WakeRate::Hz1_25 => 1.25,
+            WakeRate::Hz5 => 5.0,
+            WakeRate::Hz20 => 20.0,
+            WakeRate::Hz40 => 40.0,
+        }
+    }
+}
+
+// What the sensor does between the status samples.
+// Low power cycles the accelerometer alone, the motion interrupt still
+// works but the fast reads (peak deltas, vibration, spectrum) are skipped.
+// The sensor is brought back to full power for every sample, which adds
+// WAKE_SETTLE_MS to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerProfile {
+    // ~3.8 mA
+    Continuous,
+    LowPower(WakeRate),
+}
+
+const STBY_GYROS: u8 = 0b0000_0111;
+
+// Accelerometer-only cycle mode: the gyroscopes are in standby, the
+// temperature sensor off, and the accelerometer wakes at `rate` for one
+// sample, which the motion detector still checks.
+pub fn enter_cycle<I, E>(mpu: &mut Mpu6050<I>, rate: WakeRate) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_byte(PWR_MGMT_2::ADDR, (rate as u8) << 6 | STBY_GYROS)?;
+    // Internal oscillator, the gyro clocks are stopped
+    mpu.write_byte(
+        PWR_MGMT_1::ADDR,
+        1 << PWR_MGMT_1::CYCLE | 1 << PWR_MGMT_1::TEMP_DIS,
+    )
+}
+
+// Cycle mode at 20 Hz for a deep sleep, with the motion detector set to
+// `threshold`. INT stays high once raised, until `full_power()`, so a
+// level-triggered wake-up can't miss it.
+pub fn watch_motion<I, E>(mpu: &mut Mpu6050<I>, threshold: u8) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_byte(MOT_THR, threshold)?;
+    mpu.write_bit(INT_PIN_CFG::ADDR, INT_PIN_CFG::LATCH_INT_EN, true)?;
+    // Reading the status releases an interrupt raised before
+    mpu.read_byte(INT_STATUS::ADDR)?;
+    enter_cycle(mpu, WakeRate::Hz20)
+}
+
+// Back to full operation after `set_sleep()` or cycle mode, clocked
+// from the X gyro again with INT back to pulses. MOT_THR is left to the
+// caller. Wait WAKE_SETTLE_MS before trusting a reading.
+pub fn full_power<I, E>(mpu: &mut Mpu6050<I>) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_byte(PWR_MGMT_1::ADDR, 0x01)?;
+    mpu.write_byte(PWR_MGMT_2::ADDR, 0)?;
+    mpu.write_bit(INT_PIN_CFG::ADDR, INT_PIN_CFG::LATCH_INT_EN, false)?;
+    mpu.read_byte(INT_STATUS::ADDR).map(|_| ())
+}
+
+// Digital low-pass filter bandwidth (DLPF_CFG), for the accelerometer.
+// The gyroscope bandwidth is about the same.
+// The monitor only reads one sample every SAMPLE_PERIOD_MS, so the filter
+// doesn't smooth over that period: it only keeps the single sample read from
+// being a spike of motor-drive noise. Narrower bandwidths delay the output
+// more (5 Hz is ~19 ms), still far below the sample period, but they also
+// soften the peak of a sharp tap.
+// Any setting but 260 Hz also drops the gyroscope output rate to 1 kHz.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dlpf {
+    Hz260 = 0,
+    Hz184,
+    Hz94,
+    Hz44,
+    Hz21,
+    Hz10,
+    Hz5,
+}
+
+impl Dlpf {
+    pub fn bandwidth_hz(&self) -> u16 {
+        match self {
+            Dlpf::Hz260 => 260,
+            Dlpf::Hz184 => 184,
+            Dlpf::Hz94 => 94,
+            Dlpf::Hz44 => 44,
+            Dlpf::Hz21 => 21,
+            Dlpf::Hz10 => 10,
+            Dlpf::Hz5 => 5,
+        }
+    }
+}
+
+// Readings within 1% of full scale are taken as clipped
+const RAIL_RATIO: f32 = 0.99;
+
+// MPU6050 setup written at init
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SensorConfig {
+    pub accel_range: AccelRange,
+    pub gyro_range: GyroRange,
+    pub dlpf: Dlpf,
+    pub power: PowerProfile,
+}
+
+// At the default ±2 g a tap on the machine frame already clips, so the delta
+// underestimates the event. Wider ranges lose resolution: ±8 g is still
+// ~0.0024 m/s^2 per count. The same goes for fast rotational shocks and
+// the default ±250 °/s.
+pub const SENSOR_CONFIG: SensorConfig = SensorConfig {
+    accel_range: AccelRange::G8,
+    gyro_range: GyroRange::D500,
+    dlpf: Dlpf::Hz44,
+    power: PowerProfile::Continuous,
+};
+
+impl SensorConfig {
+    // The driver scales its readings to the ranges set here
+    pub fn apply<I, E>(&self, mpu: &mut Mpu6050<I>) -> Result<(), Mpu6050Error<E>>
+    where
+        I: Write<Error = E> + WriteRead<Error = E>,
+    {
+        mpu.set_accel_range(self.accel_range)?;
+        mpu.set_gyro_range(self.gyro_range)?;
+        set_dlpf(mpu, self.dlpf)
+    }
+
+    pub fn accel_full_scale_g(&self) -> f32 {
+        match self.accel_range {
+            AccelRange::G2 => 2.0,
+            AccelRange::G4 => 4.0,
+            AccelRange::G8 => 8.0,
+            AccelRange::G16 => 16.0,
+        }
+    }
+
+    pub fn gyro_full_scale_dps(&self) -> f32 {
+        match self.gyro_range {
+            GyroRange::D250 => 250.0,
+            GyroRange::D500 => 500.0,
+            GyroRange::D1000 => 1000.0,
+            GyroRange::D2000 => 2000.0,
+        }
+    }
+
+    // Counts per g and per º/s at the configured ranges, what the driver
+    // divides the raw readings by
+    pub fn acc_lsb_per_g(&self) -> f32 {
+        match self.accel_range {
+            AccelRange::G2 => 16_384.0,
+            AccelRange::G4 => 8_192.0,
+            AccelRange::G8 => 4_096.0,
+            AccelRange::G16 => 2_048.0,
+        }
+    }
+
+    pub fn gyro_lsb_per_dps(&self) -> f32 {
+        match self.gyro_range {
+            GyroRange::D250 => 131.0,
+            GyroRange::D500 => 65.5,
+            GyroRange::D1000 => 32.8,
+            GyroRange::D2000 => 16.4,
+        }
+    }
+
+    // Axes of an accelerometer reading (in m/s^2) at the rail of the range
+    pub fn clipped_acc(&self, acc: [f32; 3]) -> impl Iterator<Item = Axis> {
+        clipped(acc, self.accel_full_scale_g() * STANDARD_GRAVITY)
+    }
+
+    // Axes of a gyroscope reading (in rad/s) at the rail of the range
+    pub fn clipped_gyro(&self, gyro: [f32; 3]) -> impl Iterator<Item = Axis> {
+        clipped(gyro, self.gyro_full_scale_dps().to_radians())
+    }
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        SENSOR_CONFIG
+    }
+}
+
+fn clipped(reading: [f32; 3], full_scale: f32) -> impl Iterator<Item = Axis> {
+    let rail = full_scale * RAIL_RATIO;
+    Axis::ALL
+        .into_iter()
+        .filter(move |axis| math::abs(reading[*axis as usize]) >= rail)
+}
+
+// The driver reads the accelerometer in g, the limits are in m/s^2
+// whatever the range
+pub fn to_ms2(acc_g: [f32; 3]) -> [f32; 3] {
+    acc_g.map(|value| value * STANDARD_GRAVITY)
+}
+
+// The driver has no setter for the CONFIG register, it is written directly
+fn set_dlpf<I, E>(mpu: &mut Mpu6050<I>, dlpf: Dlpf) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_bits(
+        CONFIG::ADDR,
+        CONFIG::DLPF_CFG.bit,
+        CONFIG::DLPF_CFG.length,
+        dlpf as u8,
+    )
+}
+
+// FIFO registers, the driver has no constants for them (register map rev 4.2)
+const SMPLRT_DIV: u8 = 0x19;
+const FIFO_EN: u8 = 0x23;
+const USER_CTRL: u8 = 0x6a;
+const FIFO_COUNT_H: u8 = 0x72;
+const FIFO_R_W: u8 = 0x74;
+// FIFO_EN: XG, YG and ZG in bits 6-4, then the accelerometer
+const FIFO_EN_GYRO: u8 = 0b0111_0000;
+const FIFO_EN_ACCEL: u8 = 0b0000_1000;
+// USER_CTRL bits
+const USER_CTRL_FIFO_EN: u8 = 6;
+const USER_CTRL_FIFO_RESET: u8 = 2;
+
+// Empties the FIFO and starts filling it with accelerometer frames, and
+// gyroscope ones with `gyro`, at the output rate / (1 + divider).
+// The overflow is flagged in INT_STATUS, read by `fifo_overflowed()`.
+// With the motion interrupt on, an overflow pulses INT as well.
+pub fn start_fifo<I, E>(
+    mpu: &mut Mpu6050<I>,
+    gyro: bool,
+    divider: u8,
+) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_byte(SMPLRT_DIV, divider)?;
+    mpu.write_byte(
+        FIFO_EN,
+        if gyro {
+            FIFO_EN_ACCEL | FIFO_EN_GYRO
+        } else {
+            FIFO_EN_ACCEL
+        },
+    )?;
+    mpu.write_bit(INT_ENABLE::ADDR, INT_ENABLE::FIFO_OFLOW_END, true)?;
+    reset_fifo(mpu)?;
+    // Clears an overflow left over from before
+    fifo_overflowed(mpu)?;
+    mpu.write_bit(USER_CTRL, USER_CTRL_FIFO_EN, true)
+}
+
+pub fn stop_fifo<I, E>(mpu: &mut Mpu6050<I>) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_bit(USER_CTRL, USER_CTRL_FIFO_EN, false)?;
+    mpu.write_byte(FIFO_EN, 0)
+}
+
+// Drops whatever is in the FIFO, the bit clears itself
+pub fn reset_fifo<I, E>(mpu: &mut Mpu6050<I>) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.write_bit(USER_CTRL, USER_CTRL_FIFO_RESET, true)
+}
+
+// Bytes waiting in the FIFO
+pub fn fifo_count<I, E>(mpu: &mut Mpu6050<I>) -> Result<usize, Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    let mut count = [0; 2];
+    mpu.read_bytes(FIFO_COUNT_H, &mut count)?;
+    Ok(u16::from_be_bytes(count) as usize)
+}
+
+// Reading INT_STATUS clears it, the overflow is only reported once
+pub fn fifo_overflowed<I, E>(mpu: &mut Mpu6050<I>) -> Result<bool, Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    let status = mpu.read_byte(INT_STATUS::ADDR)?;
+    Ok(status & (1 << INT_STATUS::FIFO_OFLOW_INT) != 0)
+}
+
+// Consecutive reads of FIFO_R_W pop the FIFO, the address doesn't advance
+pub fn read_fifo<I, E>(mpu: &mut Mpu6050<I>, buffer: &mut [u8]) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    mpu.read_bytes(FIFO_R_W, buffer)
+}
+
+// Raises INT on motion above `threshold` (MOT_THR counts), see `motion`.
+// The pin is active high, push-pull, with a 50 µs pulse per detection.
+// The high-pass filter only feeds the motion detector, the data registers
+// are unfiltered.
+pub fn enable_motion_interrupt<I, E>(
+    mpu: &mut Mpu6050<I>,
+    threshold: u8,
+    duration_ms: u8,
+) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    // Without the filter the detector sees gravity, or nothing when it's 0
+    mpu.set_accel_hpf(ACCEL_HPF::_5)?;
+    mpu.write_byte(MOT_THR, threshold)?;
+    mpu.write_byte(MOT_DUR, duration_ms)?;
+    // INT_LEVEL, INT_OPEN, LATCH_INT_EN and INT_RD_CLEAR all off
+    mpu.write_bits(INT_PIN_CFG::ADDR, INT_PIN_CFG::INT_LEVEL, 4, 0)?;
+    mpu.write_bit(INT_ENABLE::ADDR, INT_ENABLE::MOT_EN, true)
+}
+
+// Largest deviation of the self-test response from the factory trim, in %
+pub const SELF_TEST_LIMIT_PCT: f32 = 14.0;
+
+// First of the factory trim registers: 0x0D-0x0F hold the X/Y/Z gyro trim
+// and the upper accelerometer bits, 0x10 the lower accelerometer bits
+const SELF_TEST_X: u8 = 0x0d;
+
+// Readings averaged with and without self-test, and the settle time
+// after switching it
+const SELF_TEST_SAMPLES: u8 = 10;
+const SELF_TEST_SETTLE_MS: u8 = 250;
+
+// Self-test deviation of each axis from the factory trim, in %
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SelfTest {
+    pub accel: [f32; 3],
+    pub gyro: [f32; 3],
+}
+
+impl SelfTest {
+    pub fn passed(&self) -> bool {
+        self.accel
+            .iter()
+            .chain(&self.gyro)
+            .all(|deviation| math::abs(*deviation) <= SELF_TEST_LIMIT_PCT)
+    }
+}
+
+// MPU6050 self-test, see the register map (rev 4.2) section 4.1.
+// Enabling the self-test bits moves the MEMS structures electrostatically,
+// the change in output is compared against the factory trim.
+// Leaves the sensor at ±8 g and ±250 °/s, `SensorConfig::apply()` has to be
+// called afterwards.
+pub fn self_test<I, E, D>(mpu: &mut Mpu6050<I>, delay: &mut D) -> Result<SelfTest, Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    D: DelayMs<u8>,
+{
+    // The trim values are only valid at these ranges
+    mpu.set_accel_range(AccelRange::G8)?;
+    mpu.set_gyro_range(GyroRange::D250)?;
+    delay.delay_ms(SELF_TEST_SETTLE_MS);
+    let (accel_off, gyro_off) = (
+        mean_raw(mpu, ACC_REGX_H, delay)?,
+        mean_raw(mpu, GYRO_REGX_H, delay)?,
+    );
+
+    set_self_test(mpu, true)?;
+    delay.delay_ms(SELF_TEST_SETTLE_MS);
+    let (accel_on, gyro_on) = (
+        mean_raw(mpu, ACC_REGX_H, delay)?,
+        mean_raw(mpu, GYRO_REGX_H, delay)?,
+    );
+    set_self_test(mpu, false)?;
+
+    let mut trim = [0; 4];
+    mpu.read_bytes(SELF_TEST_X, &mut trim)?;
+    let (accel_trim, gyro_trim) = factory_trim(trim);
+
+    let deviation = |on: [f32; 3], off: [f32; 3], trim: [f32; 3]| {
+        [0, 1, 2].map(|i| deviation_pct(on[i] - off[i], trim[i]))
+    };
+    Ok(SelfTest {
+        accel: deviation(accel_on, accel_off, accel_trim),
+        gyro: deviation(gyro_on, gyro_off, gyro_trim),
+    })
+}
+
+fn set_self_test<I, E>(mpu: &mut Mpu6050<I>, enable: bool) -> Result<(), Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    for register in [ACCEL_CONFIG::ADDR, GYRO_CONFIG::ADDR] {
+        // X, Y and Z self-test bits
+        mpu.write_bits(register, 7, 3, if enable { 0b111 } else { 0 })?;
+    }
+    Ok(())
+}
+
+// Average of the raw X/Y/Z counts starting at a data register
+fn mean_raw<I, E, D>(
+    mpu: &mut Mpu6050<I>,
+    register: u8,
+    delay: &mut D,
+) -> Result<[f32; 3], Mpu6050Error<E>>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+    D: DelayMs<u8>,
+{
+    let mut sum = [0.0; 3];
+    for _ in 0..SELF_TEST_SAMPLES {
+        let mut buf = [0; 6];
+        mpu.read_bytes(register, &mut buf)?;
+        for (axis, word) in sum.iter_mut().zip(buf.chunks(2)) {
+            *axis += i16::from_be_bytes([word[0], word[1]]) as f32;
+        }
+        delay.delay_ms(2);
+    }
+
+    Ok(sum.map(|axis| axis / SELF_TEST_SAMPLES as f32))
+}
+
+// Expected self-test response in counts, as (accelerometer, gyroscope).
+// A zero means no trim was programmed.
+fn factory_trim(registers: [u8; 4]) -> ([f32; 3], [f32; 3]) {
+    let [x, y, z, a] = registers;
+
+    let accel_test = [
+        (x >> 3) & 0x1c | (a >> 4) & 0x03,
+        (y >> 3) & 0x1c | (a >> 2) & 0x03,
+        (z >> 3) & 0x1c | a & 0x03,
+    ];
+    let accel = accel_test.map(|test| match test {
+        0 => 0.0,
+        test => 4096.0 * 0.34 * math::pow(0.92 / 0.34, (test as f32 - 1.0) / 30.0),
+    });
+
+    let gyro_test = [x & 0x1f, y & 0x1f, z & 0x1f];
+    let mut gyro = gyro_test.map(|test| match test {
+        0 => 0.0,
+        test => 25.0 * 131.0 * math::pow(1.046, test as f32 - 1.0),
+    });
+    // The Y gyro responds the other way
+    gyro[1] = -gyro[1];
+
+    (accel, gyro)
+}
+
+fn deviation_pct(response: f32, trim: f32) -> f32 {
+    if trim == 0.0 {
+        return f32::INFINITY;
+    }
+    (response - trim) / trim * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    // MPU6050 register file behind a fake bus
+    struct Bus {
+        registers: [u8; 128],
+    }
+
+    impl Write for Bus {
+        type Error = Infallible;
+
+        fn write(&mut self, _address: u8, bytes: &[u8]) -> Result<(), Infallible> {
+            if let [register, data @ ..] = bytes {
+                let start = *register as usize;
+                self.registers[start..start + data.len()].copy_from_slice(data);
+            }
+            Ok(())
+        }
+    }
+
+    impl WriteRead for Bus {
+        type Error = Infallible;
+
+        fn write_read(
+            &mut self,
+            _address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), Infallible> {
+            let start = bytes[0] as usize;
+            buffer.copy_from_slice(&self.registers[start..start + buffer.len()]);
+            Ok(())
+        }
+    }
+
+    fn mpu() -> Mpu6050<Bus> {
+        Mpu6050::new(Bus {
+            registers: [0; 128],
+        })
+    }
+
+    #[test]
+    fn identifies_the_mpu_family() {
+        let mut mpu = mpu();
+
+        for (id, model) in [
+            (0x68, Some(Model::Mpu6050)),
+            (0x70, Some(Model::Mpu6500)),
+            (0x71, Some(Model::Mpu9250)),
+            (0x00, None),
+        ] {
+            mpu.write_byte(WHOAMI, id).unwrap();
+            assert_eq!(Model::from_who_am_i(who_am_i(&mut mpu).unwrap()), model);
+        }
+    }
+
+    #[test]
+    fn trait_reads_in_the_monitor_units() {
+        let mut mpu = mpu();
+        SENSOR_CONFIG.apply(&mut mpu).unwrap();
+        // 4096 counts are 1 g at ±8 g, 65.5 are 1 º/s at ±500 º/s
+        mpu.write_byte(ACC_REGX_H + 4, 0x10).unwrap();
+        mpu.write_byte(GYRO_REGX_H, 0x00).unwrap();
+        mpu.write_byte(GYRO_REGX_H + 1, 131).unwrap();
+
+        let reading = ImuSensor::read(&mut mpu, 2_000).unwrap();
+        assert_eq!(reading.acc, [0.0, 0.0, STANDARD_GRAVITY]);
+        assert!(math::abs(reading.gyro[0] - 2f32.to_radians()) < 1e-6);
+        assert_eq!(reading.t_ms, 2_000);
+    }
+
+    #[test]
+    fn dlpf_keeps_the_fsync_bits() {
+        let mut mpu = mpu();
+        mpu.write_byte(CONFIG::ADDR, 0b0010_1000).unwrap();
+
+        set_dlpf(&mut mpu, Dlpf::Hz44).unwrap();
+        assert_eq!(mpu.read_byte(CONFIG::ADDR).unwrap(), 0b0010_1011);
+    }
+
+    #[test]
+    fn fifo_takes_accelerometer_and_optionally_gyro_frames() {
+        let mut mpu = mpu();
+        mpu.write_byte(SMPLRT_DIV, 7).unwrap();
+
+        start_fifo(&mut mpu, false, 0).unwrap();
+        assert_eq!(mpu.read_byte(SMPLRT_DIV).unwrap(), 0);
+        assert_eq!(mpu.read_byte(FIFO_EN).unwrap(), 0b0000_1000);
+        assert_eq!(mpu.read_byte(INT_ENABLE::ADDR).unwrap(), 0b0001_0000);
+        assert_eq!(mpu.read_byte(USER_CTRL).unwrap(), 0b0100_0100);
+
+        start_fifo(&mut mpu, true, 0).unwrap();
+        assert_eq!(mpu.read_byte(FIFO_EN).unwrap(), 0b0111_1000);
+
+        stop_fifo(&mut mpu).unwrap();
+        assert_eq!(mpu.read_byte(FIFO_EN).unwrap(), 0);
+        assert_eq!(mpu.read_byte(USER_CTRL).unwrap() & 0b0100_0000, 0);
+    }
+
+    #[test]
+    fn fifo_count_and_overflow_are_read_back() {
+        let mut mpu = mpu();
+        mpu.write_byte(FIFO_COUNT_H, 0x03).unwrap();
+        mpu.write_byte(FIFO_COUNT_H + 1, 0xfc).unwrap();
+        assert_eq!(fifo_count(&mut mpu).unwrap(), 1020);
+
+        assert!(!fifo_overflowed(&mut mpu).unwrap());
+        mpu.write_byte(INT_STATUS::ADDR, 0b0001_0001).unwrap();
+        assert!(fifo_overflowed(&mut mpu).unwrap());
+    }
+
+    #[test]
+    fn motion_interrupt_keeps_the_range() {
+        let mut mpu = mpu();
+        SENSOR_CONFIG.apply(&mut mpu).unwrap();
+        mpu.write_byte(INT_PIN_CFG::ADDR, 0b1010_0010).unwrap();
+
+        enable_motion_interrupt(&mut mpu, 25, 1).unwrap();
+        assert_eq!(mpu.read_byte(ACCEL_CONFIG::ADDR).unwrap(), 0b0001_0001);
+        assert_eq!(mpu.read_byte(MOT_THR).unwrap(), 25);
+        assert_eq!(mpu.read_byte(MOT_DUR).unwrap(), 1);
+        // The I2C bypass bit is left alone
+        assert_eq!(mpu.read_byte(INT_PIN_CFG::ADDR).unwrap(), 0b0000_0010);
+        assert_eq!(mpu.read_byte(INT_ENABLE::ADDR).unwrap(), 0b0100_0000);
+    }
+
+    #[test]
+    fn motion_watch_keeps_only_the_accelerometer() {
+        let mut mpu = mpu();
+        mpu.write_byte(INT_PIN_CFG::ADDR, 0b0000_0010).unwrap();
+
+        watch_motion(&mut mpu, 12).unwrap();
+        assert_eq!(mpu.read_byte(MOT_THR).unwrap(), 12);
+        assert_eq!(mpu.read_byte(INT_PIN_CFG::ADDR).unwrap(), 0b0010_0010);
+        assert_eq!(mpu.read_byte(PWR_MGMT_1::ADDR).unwrap(), 0b0010_1000);
+        assert_eq!(mpu.read_byte(PWR_MGMT_2::ADDR).unwrap(), 0b1000_0111);
+
+        enter_cycle(&mut mpu, WakeRate::Hz5).unwrap();
+        assert_eq!(mpu.read_byte(PWR_MGMT_2::ADDR).unwrap(), 0b0100_0111);
+
+        full_power(&mut mpu).unwrap();
+        assert_eq!(mpu.read_byte(PWR_MGMT_1::ADDR).unwrap(), 0x01);
+        assert_eq!(mpu.read_byte(PWR_MGMT_2::ADDR).unwrap(), 0);
+        assert_eq!(mpu.read_byte(INT_PIN_CFG::ADDR).unwrap(), 0b0000_0010);
+    }
+
+    #[test]
+    fn self_test_response_matches_factory_trim() {
+        // Accelerometer trim 16 (0b100_00) and gyro trim 1 on every axis
+        let (accel, gyro) = factory_trim([0b1000_0001, 0b1000_0001, 0b1000_0001, 0]);
+        assert!(math::abs(accel[0] - 4096.0 * 0.34 * math::pow(0.92 / 0.34, 0.5)) < 0.01);
+        assert_eq!(gyro, [3275.0, -3275.0, 3275.0]);
+
+        assert_eq!(deviation_pct(3275.0, 3275.0), 0.0);
+        assert!(math::abs(deviation_pct(3275.0 * 1.2, 3275.0) - 20.0) < 0.001);
+        assert!(deviation_pct(3275.0, 0.0).is_infinite());
+
+        let result = SelfTest {
+            accel: [1.0, -13.0, 2.0],
+            gyro: [0.0, 5.0, 14.0],
+        };
+        assert!(result.passed());
+        assert!(!SelfTest {
+            gyro: [0.0, -20.0, 0.0],
+            ..result
+        }
+        .passed());
+    }
+
+    #[test]
+    fn range_does_not_change_the_units() {
+        assert_eq!(to_ms2([0.0, 0.0, 1.0]), [0.0, 0.0, STANDARD_GRAVITY]);
+        assert_eq!(to_ms2([0.5, 0.0, 0.0])[0], STANDARD_GRAVITY / 2.0);
+    }
+
+    #[test]
+    fn config_sets_ranges_and_filter() {
+        let mut mpu = mpu();
+        SENSOR_CONFIG.apply(&mut mpu).unwrap();
+
+        assert_eq!(mpu.read_byte(ACCEL_CONFIG::ADDR).unwrap(), 0b0001_0000);
+        assert_eq!(mpu.read_byte(GYRO_CONFIG::ADDR).unwrap(), 0b0000_1000);
+        assert_eq!(mpu.read_byte(CONFIG::ADDR).unwrap(), Dlpf::Hz44 as u8);
+    }
+
+    #[test]
+    fn reading_at_the_rail_is_clipped() {
+        let config = SensorConfig {
+            accel_range: AccelRange::G2,
+            gyro_range: GyroRange::D250,
+            dlpf: Dlpf::Hz260,
+            power: PowerProfile::Continuous,
+        };
+
+        // Raw -32768 and +32767 counts at ±2 g
+        let acc = to_ms2([-32_768.0 / 16_384.0, 32_767.0 / 16_384.0, 1.0]);
+        let clipped: heapless::Vec<Axis, 3> = config.clipped_acc(acc).collect();
+        assert_eq!(clipped, [Axis::X, Axis::Y]);
+
+        // ±250 °/s is ~4.36 rad/s
+        let clipped: heapless::Vec<Axis, 3> = config.clipped_gyro([0.0, 4.0, -4.34]).collect();
+        assert_eq!(clipped, [Axis::Z]);
+
+        let config = SensorConfig {
+            accel_range: AccelRange::G4,
+            gyro_range: GyroRange::D500,
+            ..config
+        };
+        assert_eq!(config.clipped_acc(acc).count(), 0);
+        assert_eq!(config.clipped_gyro([0.0, 4.0, -4.34]).count(), 0);
+    }
+}
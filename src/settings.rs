@@ -0,0 +1,305 @@
+use crate::console::Setting;
+use crate::crc::crc32;
+use crate::dac::DacMapping;
+use crate::{GyroBias, MaintenanceMonitor, Thresholds, SAMPLE_PERIOD_MS};
+
+// Settings block kept in flash, little-endian:
+// magic, version, payload length, payload, CRC-32 of everything before it.
+// Erased flash reads as 0xff, so a board that never saved has no magic.
+pub const SETTINGS_MAGIC: u32 = 0x4743_4d50;
+// Version 2 added the DAC mapping, a version 1 block loads with the default
+pub const SETTINGS_VERSION: u16 = 2;
+
+const HEADER_LEN: usize = 8;
+const PAYLOAD_LEN: usize = 36;
+const V1_PAYLOAD_LEN: usize = 28;
+pub const SETTINGS_LEN: usize = HEADER_LEN + PAYLOAD_LEN + 4;
+
+// Status samples have to land on the 10 ms loop tick
+const SAMPLE_PERIOD_RANGE_MS: (u32, u32) = (100, 5_000);
+const SAMPLE_PERIOD_STEP_MS: u32 = 10;
+// A bias this large is a moving sensor, not an offset
+const GYRO_BIAS_MAX: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettingsError {
+    // Nothing saved yet, or erased by a factory reset
+    Blank,
+    BadMagic,
+    UnsupportedVersion(u16),
+    BadChecksum,
+    // Intact, but a value outside what the console would accept
+    OutOfRange,
+}
+
+impl SettingsError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            SettingsError::Blank => "nothing saved",
+            SettingsError::BadMagic => "not a settings block",
+            SettingsError::UnsupportedVersion(_) => "saved by another firmware version",
+            SettingsError::BadChecksum => "checksum mismatch",
+            SettingsError::OutOfRange => "value out of range",
+        }
+    }
+}
+
+// What survives a reset: the tunable warning levels, the status sample
+// period, the gyroscope bias of the last calibration and the DAC's mapping
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    // Indexed like `Setting::ALL`
+    pub warnings: [f32; Setting::ALL.len()],
+    pub sample_period_ms: u32,
+    pub gyro_bias: [f32; 3],
+    pub dac: DacMapping,
+}
+
+impl Settings {
+    pub fn new(
+        thresholds: &Thresholds,
+        sample_period_ms: u32,
+        gyro_bias: &GyroBias,
+        dac: DacMapping,
+    ) -> Self {
+        Self {
+            warnings: Setting::ALL.map(|setting| setting.levels(thresholds).warning),
+            sample_period_ms,
+            gyro_bias: gyro_bias.offsets(),
+            dac,
+        }
+    }
+
+    // The compiled-in defaults, without a gyroscope bias
+    pub fn defaults() -> Self {
+        Self::new(
+            &Thresholds::default(),
+            SAMPLE_PERIOD_MS,
+            &GyroBias::default(),
+            DacMapping::default(),
+        )
+    }
+
+    pub fn warning(&self, setting: Setting) -> f32 {
+        self.warnings[setting as usize]
+    }
+
+    // Sets the monitor's warning levels. The sample period is only taken at
+    // boot, when the monitor is built.
+    pub fn apply(&self, monitor: &mut MaintenanceMonitor, gyro_bias: &mut GyroBias) {
+        for setting in Setting::ALL {
+            monitor.set_warning(setting.limit(), self.warning(setting));
+        }
+        *gyro_bias = GyroBias::new(self.gyro_bias);
+    }
+
+    pub fn encode(&self) -> [u8; SETTINGS_LEN] {
+        let mut bytes = [0; SETTINGS_LEN];
+        bytes[0..4].copy_from_slice(&SETTINGS_MAGIC.to_le_bytes());
+        bytes[4..6].copy_from_slice(&SETTINGS_VERSION.to_le_bytes());
+        bytes[6..8].copy_from_slice(&(PAYLOAD_LEN as u16).to_le_bytes());
+
+        let words = self
+            .warnings
+            .iter()
+            .map(|warning| warning.to_bits())
+            .chain([self.sample_period_ms])
+            .chain(self.gyro_bias.iter().map(|offset| offset.to_bits()))
+            .chain([self.dac.zero.to_bits(), self.dac.full_scale.to_bits()]);
+        for (chunk, word) in bytes[HEADER_LEN..HEADER_LEN + PAYLOAD_LEN]
+            .chunks_exact_mut(4)
+            .zip(words)
+        {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        let crc = crc32(&bytes[..HEADER_LEN + PAYLOAD_LEN]);
+        bytes[HEADER_LEN + PAYLOAD_LEN..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; SETTINGS_LEN]) -> Result<Self, SettingsError> {
+        let word = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+
+        if bytes.iter().all(|byte| *byte == 0xff) {
+            return Err(SettingsError::Blank);
+        }
+        if word(0) != SETTINGS_MAGIC {
+            return Err(SettingsError::BadMagic);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let len = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        match (version, len) {
+            (SETTINGS_VERSION, PAYLOAD_LEN) | (1, V1_PAYLOAD_LEN) => {}
+            _ => return Err(SettingsError::UnsupportedVersion(version)),
+        }
+        if word(HEADER_LEN + len) != crc32(&bytes[..HEADER_LEN + len]) {
+            return Err(SettingsError::BadChecksum);
+        }
+
+        let payload = |i: usize| word(HEADER_LEN + 4 * i);
+        let count = Setting::ALL.len();
+        let dac = match version {
+            1 => DacMapping::default(),
+            _ => DacMapping {
+                zero: f32::from_bits(payload(count + 4)),
+                full_scale: f32::from_bits(payload(count + 5)),
+            },
+        };
+        let settings = Self {
+            warnings: core::array::from_fn(|i| f32::from_bits(payload(i))),
+            sample_period_ms: payload(count),
+            gyro_bias: core::array::from_fn(|i| f32::from_bits(payload(count + 1 + i))),
+            dac,
+        };
+        if !settings.is_valid() {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(settings)
+    }
+
+    fn is_valid(&self) -> bool {
+        let warnings = Setting::ALL.into_iter().all(|setting| {
+            let (min, max) = setting.range();
+            let warning = self.warning(setting);
+            warning >= min && warning <= max
+        });
+        let (min_ms, max_ms) = SAMPLE_PERIOD_RANGE_MS;
+        let period = (min_ms..=max_ms).contains(&self.sample_period_ms)
+            && self.sample_period_ms.checked_rem(SAMPLE_PERIOD_STEP_MS) == Some(0);
+        // NaN fails the comparison too
+        let bias = self
+            .gyro_bias
+            .iter()
+            .all(|offset| offset.abs() <= GYRO_BIAS_MAX);
+
+        warnings && period && bias && self.dac.is_valid()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::defaults()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::{GYRO_WARNING, MECHANICAL_WARNING, TEMPERATURE_RATE_WARNING};
+
+    fn tuned() -> Settings {
+        Settings {
+            warnings: [1.2, 3.0, 0.9],
+            sample_period_ms: 250,
+            gyro_bias: [0.01, -0.02, 0.005],
+            dac: DacMapping::new(0.2, 3.0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn defaults_are_the_compiled_in_levels() {
+        let defaults = Settings::defaults();
+        assert_eq!(
+            defaults.warnings,
+            [MECHANICAL_WARNING, TEMPERATURE_RATE_WARNING, GYRO_WARNING]
+        );
+        assert_eq!(defaults.sample_period_ms, SAMPLE_PERIOD_MS);
+        assert_eq!(Settings::decode(&defaults.encode()), Ok(defaults));
+    }
+
+    #[test]
+    fn round_trips_through_the_block() {
+        let settings = tuned();
+        assert_eq!(Settings::decode(&settings.encode()), Ok(settings));
+    }
+
+    #[test]
+    fn damaged_blocks_are_rejected() {
+        assert_eq!(
+            Settings::decode(&[0xff; SETTINGS_LEN]),
+            Err(SettingsError::Blank)
+        );
+
+        let mut bytes = tuned().encode();
+        bytes[HEADER_LEN] ^= 0x01;
+        assert_eq!(Settings::decode(&bytes), Err(SettingsError::BadChecksum));
+
+        let mut bytes = tuned().encode();
+        bytes[0] = 0;
+        assert_eq!(Settings::decode(&bytes), Err(SettingsError::BadMagic));
+
+        let mut bytes = tuned().encode();
+        bytes[4] = 3;
+        assert_eq!(
+            Settings::decode(&bytes),
+            Err(SettingsError::UnsupportedVersion(3))
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let mut settings = tuned();
+        settings.warnings[0] = -1.0;
+        assert_eq!(
+            Settings::decode(&settings.encode()),
+            Err(SettingsError::OutOfRange)
+        );
+
+        let mut settings = tuned();
+        settings.sample_period_ms = 255;
+        assert_eq!(
+            Settings::decode(&settings.encode()),
+            Err(SettingsError::OutOfRange)
+        );
+
+        let mut settings = tuned();
+        settings.gyro_bias[1] = f32::NAN;
+        assert_eq!(
+            Settings::decode(&settings.encode()),
+            Err(SettingsError::OutOfRange)
+        );
+
+        let mut settings = tuned();
+        settings.dac.full_scale = settings.dac.zero;
+        assert_eq!(
+            Settings::decode(&settings.encode()),
+            Err(SettingsError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn a_version_1_block_loads_with_the_default_mapping() {
+        // As the last firmware saved it, with what followed in flash after
+        let mut bytes = tuned().encode();
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        bytes[6..8].copy_from_slice(&(V1_PAYLOAD_LEN as u16).to_le_bytes());
+        let end = HEADER_LEN + V1_PAYLOAD_LEN;
+        let crc = crc32(&bytes[..end]);
+        bytes[end..end + 4].copy_from_slice(&crc.to_le_bytes());
+        bytes[end + 4..].fill(0xff);
+
+        let settings = Settings::decode(&bytes).unwrap();
+        assert_eq!(settings.dac, DacMapping::default());
+        assert_eq!(settings.warnings, tuned().warnings);
+        assert_eq!(settings.gyro_bias, tuned().gyro_bias);
+    }
+
+    #[test]
+    fn applies_to_the_monitor() {
+        let mut monitor = MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let mut gyro_bias = GyroBias::default();
+
+        tuned().apply(&mut monitor, &mut gyro_bias);
+        assert_eq!(monitor.thresholds().mechanical.warning, 1.2);
+        assert_eq!(monitor.thresholds().temperature_rate.warning, 3.0);
+        assert_eq!(monitor.thresholds().rotational.warning, 0.9);
+        assert_eq!(gyro_bias.offsets(), [0.01, -0.02, 0.005]);
+        assert_eq!(
+            Settings::new(monitor.thresholds(), 250, &gyro_bias, tuned().dac),
+            tuned()
+        );
+    }
+}
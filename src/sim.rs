@@ -0,0 +1,323 @@
+use core::convert::Infallible;
+use core::f32::consts::PI;
+
+use embedded_hal::blocking::delay::DelayMs;
+
+use crate::{math, sensor::ImuSensor, Reading};
+
+// The scenario played after the boot calibration, an index in SCENARIOS,
+// and the seed of its noise
+pub const SIM_SCENARIO: usize = 0;
+pub const SIM_SEED: u32 = 0x5eed;
+
+// The machine's temperature when a scenario starts, ºC
+pub const SIM_TEMP: f32 = 25.0;
+// How long a knock lasts, ms
+pub const IMPULSE_MS: u64 = 30;
+
+const GRAVITY: f32 = 9.81;
+
+// What the simulated machine does for a while, on top of gravity along Z.
+// Amplitudes are peak, in m/s^2 and rad/s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Waveform {
+    // White noise on every axis
+    pub noise: f32,
+    pub gyro_noise: f32,
+    // A sine shake along Z
+    pub vibration: f32,
+    pub vibration_hz: f32,
+    // A knock along X every `impulse_period_ms`, none at 0
+    pub impulse: f32,
+    pub impulse_period_ms: u32,
+    // Steady rotation about Z
+    pub spin: f32,
+    // ºC a minute, either way
+    pub temp_rate: f32,
+}
+
+// A machine at rest, just the sensor's noise
+pub const QUIET: Waveform = Waveform {
+    noise: 0.02,
+    gyro_noise: 0.002,
+    vibration: 0.0,
+    vibration_hz: 10.0,
+    impulse: 0.0,
+    impulse_period_ms: 0,
+    spin: 0.0,
+    temp_rate: 0.0,
+};
+
+// Past the vibration warning level, and its critical one
+const IMBALANCE: Waveform = Waveform {
+    vibration: 0.5,
+    ..QUIET
+};
+const WORN: Waveform = Waveform {
+    vibration: 1.0,
+    ..QUIET
+};
+const IMPACTS: Waveform = Waveform {
+    impulse: 3.0,
+    impulse_period_ms: 2_000,
+    ..QUIET
+};
+// Past the temperature rate's critical level
+const OVERHEAT: Waveform = Waveform {
+    temp_rate: 3.0,
+    ..QUIET
+};
+const COOLING: Waveform = Waveform {
+    temp_rate: -3.0,
+    ..QUIET
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub duration_ms: u32,
+    pub waveform: Waveform,
+}
+
+const fn step(duration_ms: u32, waveform: Waveform) -> Step {
+    Step {
+        duration_ms,
+        waveform,
+    }
+}
+
+// Steps played in order, the last one holds for good
+#[derive(Debug, PartialEq)]
+pub struct Scenario {
+    pub name: &'static str,
+    pub steps: &'static [Step],
+}
+
+pub const SCENARIOS: [Scenario; 5] = [
+    Scenario {
+        name: "idle",
+        steps: &[step(0, QUIET)],
+    },
+    Scenario {
+        name: "imbalance",
+        steps: &[step(0, IMBALANCE)],
+    },
+    Scenario {
+        name: "impacts",
+        steps: &[step(0, IMPACTS)],
+    },
+    Scenario {
+        name: "overheat",
+        steps: &[step(0, OVERHEAT)],
+    },
+    // A bit of everything, about eleven minutes of it
+    Scenario {
+        name: "script",
+        steps: &[
+            step(60_000, QUIET),
+            step(60_000, IMBALANCE),
+            step(60_000, WORN),
+            step(60_000, QUIET),
+            step(60_000, IMPACTS),
+            step(180_000, OVERHEAT),
+            step(180_000, COOLING),
+            step(0, QUIET),
+        ],
+    },
+];
+
+// An index in SCENARIOS, by name regardless of case
+pub fn scenario(name: &str) -> Option<usize> {
+    SCENARIOS
+        .iter()
+        .position(|scenario| scenario.name.eq_ignore_ascii_case(name))
+}
+
+// -1 to 1, the same for the same seed, time and channel whatever was read
+// before, so a scenario replays exactly
+fn noise(seed: u32, elapsed_ms: u64, channel: u32) -> f32 {
+    let mut x = seed
+        ^ (elapsed_ms as u32).wrapping_mul(0x9e37_79b9)
+        ^ ((elapsed_ms >> 32) as u32)
+        ^ channel.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+// Stands in for the IMU: every read is the scenario's waveform at the
+// uptime `clock` returns
+pub struct Simulator {
+    scenario: usize,
+    seed: u32,
+    start_ms: u64,
+    clock: fn() -> u64,
+}
+
+impl Simulator {
+    pub fn new(scenario: usize, seed: u32, clock: fn() -> u64) -> Self {
+        Self {
+            scenario: scenario.min(SCENARIOS.len() - 1),
+            seed,
+            start_ms: clock(),
+            clock,
+        }
+    }
+
+    // Starts a scenario over from now, with the temperature back at SIM_TEMP
+    pub fn play(&mut self, scenario: usize) {
+        *self = Self::new(scenario, self.seed, self.clock);
+    }
+
+    // The same, with another noise
+    pub fn reseed(&mut self, seed: u32) {
+        *self = Self::new(self.scenario, seed, self.clock);
+    }
+
+    pub fn scenario(&self) -> &'static Scenario {
+        &SCENARIOS[self.scenario]
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    // The step playing at `t_ms` and how far into it, with the temperature
+    // the steps before it left
+    fn step_at(&self, t_ms: u64) -> (usize, u64, f32) {
+        let steps = self.scenario().steps;
+        let mut into = t_ms.saturating_sub(self.start_ms);
+        let mut temp = SIM_TEMP;
+        for (i, step) in steps.iter().enumerate() {
+            let duration = step.duration_ms as u64;
+            if i + 1 == steps.len() || into < duration {
+                return (i, into, temp);
+            }
+            into -= duration;
+            temp += step.waveform.temp_rate * duration as f32 / 60_000.0;
+        }
+        unreachable!("a scenario has at least one step")
+    }
+
+    // The step playing now, for the `sim` command
+    pub fn step(&self) -> usize {
+        self.step_at((self.clock)()).0
+    }
+
+    pub fn sample(&self, t_ms: u64) -> Reading {
+        let (i, into, temp) = self.step_at(t_ms);
+        let wave = &self.scenario().steps[i].waveform;
+        let elapsed = t_ms.saturating_sub(self.start_ms);
+        let noise = |channel| noise(self.seed, elapsed, channel);
+
+        // The phase from whole cycles in f64, an f32 of the uptime in
+        // seconds runs out of precision within hours
+        let cycles = elapsed as f64 * wave.vibration_hz as f64 / 1_000.0;
+        let phase = (cycles - (cycles as u64) as f64) as f32;
+        let shake = wave.vibration * math::sin(2.0 * PI * phase);
+        let knock = match wave.impulse_period_ms {
+            0 => 0.0,
+            period if into % (period as u64) < IMPULSE_MS => wave.impulse,
+            _ => 0.0,
+        };
+
+        let acc = [
+            knock + wave.noise * noise(0),
+            wave.noise * noise(1),
+            GRAVITY + shake + wave.noise * noise(2),
+        ];
+        let gyro = [
+            wave.gyro_noise * noise(3),
+            wave.gyro_noise * noise(4),
+            wave.spin + wave.gyro_noise * noise(5),
+        ];
+        let temp = temp + wave.temp_rate * into as f32 / 60_000.0;
+        Reading::new(acc, gyro, temp, t_ms)
+    }
+}
+
+impl ImuSensor for Simulator {
+    type Error = Infallible;
+
+    fn init<D: DelayMs<u8>>(&mut self, _delay: &mut D) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn read_accel(&mut self) -> Result<[f32; 3], Infallible> {
+        Ok(self.sample((self.clock)()).acc)
+    }
+
+    fn read_gyro(&mut self) -> Result<[f32; 3], Infallible> {
+        Ok(self.sample((self.clock)()).gyro)
+    }
+
+    fn read_temp(&mut self) -> Result<f32, Infallible> {
+        Ok(self.sample((self.clock)()).temp)
+    }
+
+    fn read(&mut self, t_ms: u64) -> Result<Reading, Infallible> {
+        Ok(self.sample(t_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limit, MaintenanceMonitor, Severity, Thresholds, SAMPLE_PERIOD_MS};
+
+    fn at_zero() -> u64 {
+        0
+    }
+
+    #[test]
+    fn same_seed_same_samples() {
+        let overheat = scenario("Overheat").unwrap();
+        let a = Simulator::new(overheat, 7, at_zero);
+        let b = Simulator::new(overheat, 7, at_zero);
+        let c = Simulator::new(overheat, 8, at_zero);
+        for t_ms in (0..10_000).step_by(490) {
+            assert_eq!(a.sample(t_ms), b.sample(t_ms));
+            assert_ne!(a.sample(t_ms).acc, c.sample(t_ms).acc);
+            // Noise only
+            assert!(a.sample(t_ms).acc[0].abs() <= QUIET.noise);
+        }
+        assert_eq!(scenario("spin"), None);
+    }
+
+    #[test]
+    fn script_steps_through_and_keeps_the_temperature() {
+        let script = Simulator::new(scenario("script").unwrap(), SIM_SEED, at_zero);
+        assert_eq!(script.step_at(59_999).0, 0);
+        assert_eq!(script.step_at(60_000), (1, 0, SIM_TEMP));
+        // Up 9 ºC while overheating, down again while cooling
+        let peak = script.sample(480_000).temp;
+        assert!((peak - (SIM_TEMP + 9.0)).abs() < 1e-3);
+        assert!((script.sample(660_000).temp - SIM_TEMP).abs() < 1e-3);
+        // The last step holds
+        assert_eq!(script.step_at(u64::MAX).0, 7);
+
+        // A knock every 2 s while the impacts play
+        let knocks = (240_000..300_000)
+            .step_by(10)
+            .filter(|t_ms| script.sample(*t_ms).acc[0] > 1.0)
+            .count();
+        assert_eq!(knocks, 30 * IMPULSE_MS as usize / 10);
+    }
+
+    #[test]
+    fn overheat_trips_the_temperature_rate() {
+        let sim = Simulator::new(scenario("overheat").unwrap(), SIM_SEED, at_zero);
+        let mut monitor: MaintenanceMonitor =
+            MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let rest = sim.sample(0);
+        monitor.set_reference(rest.acc, rest.gyro, rest.temp);
+
+        let alerts: Vec<_> = (0..600)
+            .filter_map(|i| monitor.update(&sim.sample(i * SAMPLE_PERIOD_MS as u64)))
+            .map(|alert| (alert.limit, alert.severity))
+            .collect();
+        assert_eq!(alerts, [(Limit::Temperature, Severity::Critical)]);
+    }
+}
@@ -0,0 +1,126 @@
+// Low-power mode, `--features deep-sleep`: after each status sample the
+// ESP32 deep-sleeps until the next one, woken by the RTC timer. RAM is lost
+// meanwhile, what the monitor needs is kept in RTC memory.
+
+// After a cold boot the board stays awake this long, so the boot checks can
+// be watched and the console used
+pub const STAY_AWAKE_MS: u32 = 30_000;
+
+// Shortest sleep, when a cycle ran past the sample period
+pub const MIN_SLEEP_MS: u32 = 20;
+
+// What's left of the period after `awake_ms`
+pub fn sleep_ms(period_ms: u32, awake_ms: u32) -> u32 {
+    period_ms.saturating_sub(awake_ms).max(MIN_SLEEP_MS)
+}
+
+// Motion at this fraction of the mechanical warning level already wakes the
+// board, the sample taken then decides whether it's an alarm
+pub const WAKE_THRESHOLD_RATIO: f32 = 0.5;
+
+// Motion wake-ups less than this apart are a storm, a machine vibrating
+// around the threshold would otherwise keep the board awake
+pub const MIN_MOTION_WAKE_INTERVAL_MS: u64 = 5_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wake {
+    Timer,
+    Motion,
+}
+
+// Decides whether motion may cut the next sleep short. After a motion
+// wake-up the board sleeps on the timer only, for at least
+// MIN_MOTION_WAKE_INTERVAL_MS.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WakeGuard {
+    last_motion_ms: Option<u64>,
+}
+
+impl WakeGuard {
+    pub const fn new() -> Self {
+        Self {
+            last_motion_ms: None,
+        }
+    }
+
+    // A motion wake-up at `uptime_ms`
+    pub fn motion_woke(&mut self, uptime_ms: u64) {
+        self.last_motion_ms = Some(uptime_ms);
+    }
+
+    pub fn arm_motion(&self, uptime_ms: u64) -> bool {
+        match self.last_motion_ms {
+            Some(last) => uptime_ms.saturating_sub(last) >= MIN_MOTION_WAKE_INTERVAL_MS,
+            None => true,
+        }
+    }
+}
+
+// Time spent awake against the whole cycle, over every cycle since the
+// cold boot. The ROM and bootloader run before the timer starts, so it
+// reads a bit low.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DutyCycle {
+    pub cycles: u32,
+    awake_ms: u64,
+    total_ms: u64,
+}
+
+impl DutyCycle {
+    pub const fn new() -> Self {
+        Self {
+            cycles: 0,
+            awake_ms: 0,
+            total_ms: 0,
+        }
+    }
+
+    pub fn cycle(&mut self, awake_ms: u32, asleep_ms: u32) {
+        self.cycles = self.cycles.wrapping_add(1);
+        self.awake_ms += awake_ms as u64;
+        self.total_ms += awake_ms as u64 + asleep_ms as u64;
+    }
+
+    // Average so far, in %
+    pub fn percent(&self) -> Option<f32> {
+        match self.total_ms {
+            0 => None,
+            total_ms => Some(self.awake_ms as f32 * 100.0 / total_ms as f32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleeps_for_the_rest_of_the_period() {
+        assert_eq!(sleep_ms(500, 80), 420);
+        assert_eq!(sleep_ms(500, 495), MIN_SLEEP_MS);
+        assert_eq!(sleep_ms(500, 2_000), MIN_SLEEP_MS);
+    }
+
+    #[test]
+    fn motion_wake_ups_are_spaced_out() {
+        let mut guard = WakeGuard::new();
+        assert!(guard.arm_motion(0));
+
+        guard.motion_woke(10_000);
+        assert!(!guard.arm_motion(10_500));
+        assert!(!guard.arm_motion(14_999));
+        assert!(guard.arm_motion(15_000));
+    }
+
+    #[test]
+    fn duty_cycle_averages_the_cycles() {
+        let mut duty = DutyCycle::new();
+        assert_eq!(duty.percent(), None);
+
+        duty.cycle(100, 400);
+        assert_eq!(duty.percent(), Some(20.0));
+        duty.cycle(50, 450);
+        assert_eq!(duty.percent(), Some(15.0));
+        assert_eq!(duty.cycles, 2);
+    }
+}
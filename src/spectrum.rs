@@ -0,0 +1,251 @@
+use core::f32::consts::PI;
+
+use crate::math;
+
+// Burst of accelerometer samples, at SPECTRUM_RATE_HZ or a bit above, taken
+// every SPECTRUM_PERIOD_MS. 256 samples at 1 kHz are a quarter of a second,
+// with ~4 Hz between bins.
+pub const SPECTRUM_SAMPLES: usize = 256;
+pub const SPECTRUM_RATE_HZ: u32 = 1_000;
+pub const SPECTRUM_PERIOD_MS: u32 = 5_000;
+pub const SPECTRUM_PEAKS: usize = 3;
+
+// In-place radix-2 FFT, the length must be a power of two
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    assert!(n.is_power_of_two() && im.len() == n);
+
+    // Bit-reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        for k in 0..half {
+            // Twiddles straight from sin/cos, a recurrence drifts
+            let angle = -2.0 * PI * k as f32 / len as f32;
+            let (w_re, w_im) = (math::cos(angle), math::sin(angle));
+
+            for start in (0..n).step_by(len) {
+                let (a, b) = (start + k, start + k + half);
+                let x_re = re[b] * w_re - im[b] * w_im;
+                let x_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - x_re;
+                im[b] = im[a] - x_im;
+                re[a] += x_re;
+                im[a] += x_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    pub frequency_hz: f32,
+    // Amplitude of the sine at that frequency, in the input's unit
+    pub amplitude: f32,
+}
+
+// Collects a burst of [x, y, z] samples and finds the strongest frequencies.
+// The power spectra of the three axes are added up, so the result doesn't
+// depend on how the sensor is mounted. Taking the magnitude first would
+// not do: a vibration across gravity shows up in it at twice its frequency.
+pub struct Spectrum<const N: usize = SPECTRUM_SAMPLES> {
+    samples: [[f32; 3]; N],
+    len: usize,
+}
+
+impl<const N: usize> Spectrum<N> {
+    pub const fn new() -> Self {
+        Self {
+            samples: [[0.0; 3]; N],
+            len: 0,
+        }
+    }
+
+    // Returns true once the burst is complete, later samples are dropped
+    pub fn push(&mut self, acc: [f32; 3]) -> bool {
+        if self.len < N {
+            self.samples[self.len] = acc;
+            self.len += 1;
+        }
+        self.is_full()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // Strongest local maxima of the spectrum, strongest first.
+    // `rate_hz` is the rate the burst was actually sampled at.
+    // None until the burst is complete.
+    pub fn peaks(&self, rate_hz: f32) -> Option<[Option<Peak>; SPECTRUM_PEAKS]> {
+        if !self.is_full() || N < 4 {
+            return None;
+        }
+
+        // Hann window, against leakage from frequencies between bins
+        let window = |i: usize| 0.5 - 0.5 * math::cos(2.0 * PI * i as f32 / N as f32);
+        let window_sum = N as f32 / 2.0;
+
+        // Only the first half is used, the rest mirrors it
+        let mut power = [0.0; N];
+        for axis in 0..3 {
+            let mean = self.samples.iter().map(|acc| acc[axis]).sum::<f32>() / N as f32;
+            let mut re: [f32; N] =
+                core::array::from_fn(|i| (self.samples[i][axis] - mean) * window(i));
+            let mut im = [0.0; N];
+            fft(&mut re, &mut im);
+
+            for k in 0..=N / 2 {
+                power[k] += re[k] * re[k] + im[k] * im[k];
+            }
+        }
+        let amplitude = |k: usize| 2.0 * math::sqrt(power[k]) / window_sum;
+
+        let mut peaks: [Option<Peak>; SPECTRUM_PEAKS] = [None; SPECTRUM_PEAKS];
+        for k in 1..N / 2 {
+            if power[k] <= power[k - 1] || power[k] < power[k + 1] {
+                continue;
+            }
+
+            // Parabola through the bin and its neighbours, for where the
+            // peak sits between bins
+            let (a, b, c) = (amplitude(k - 1), amplitude(k), amplitude(k + 1));
+            let curvature = a - 2.0 * b + c;
+            let offset = if curvature < 0.0 {
+                0.5 * (a - c) / curvature
+            } else {
+                0.0
+            };
+            let peak = Peak {
+                frequency_hz: (k as f32 + offset) * rate_hz / N as f32,
+                amplitude: b - 0.25 * (a - c) * offset,
+            };
+
+            // Kept sorted, the weakest one drops out
+            let Some(slot) = peaks.iter().position(|kept| match kept {
+                Some(kept) => peak.amplitude > kept.amplitude,
+                None => true,
+            }) else {
+                continue;
+            };
+            for i in (slot + 1..SPECTRUM_PEAKS).rev() {
+                peaks[i] = peaks[i - 1];
+            }
+            peaks[slot] = Some(peak);
+        }
+
+        Some(peaks)
+    }
+}
+
+impl<const N: usize> Default for Spectrum<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE_HZ: f32 = 1_000.0;
+
+    fn sine(amplitude: f32, frequency_hz: f32, i: usize) -> f32 {
+        amplitude * math::sin(2.0 * PI * frequency_hz * i as f32 / RATE_HZ)
+    }
+
+    #[test]
+    fn bin_centred_sine_lands_in_one_bin() {
+        let mut re: [f32; 64] =
+            core::array::from_fn(|i| math::cos(2.0 * PI * 8.0 * i as f32 / 64.0));
+        let mut im = [0.0; 64];
+        fft(&mut re, &mut im);
+
+        for k in 0..64 {
+            let magnitude = math::sqrt(re[k] * re[k] + im[k] * im[k]);
+            let expected = if k == 8 || k == 56 { 32.0 } else { 0.0 };
+            assert!(
+                (magnitude - expected).abs() < 1e-3,
+                "bin {}: {}",
+                k,
+                magnitude
+            );
+        }
+    }
+
+    #[test]
+    fn impulse_has_a_flat_spectrum() {
+        let mut re = [0.0; 16];
+        re[0] = 1.0;
+        let mut im = [0.0; 16];
+        fft(&mut re, &mut im);
+
+        assert!(re.iter().all(|value| (value - 1.0).abs() < 1e-6));
+        assert!(im.iter().all(|value| value.abs() < 1e-6));
+    }
+
+    #[test]
+    fn finds_the_strongest_sines_off_bin() {
+        let mut spectrum: Spectrum = Spectrum::new();
+
+        // Rotation at 1x and its harmonic, plus a weak high-frequency tone
+        for i in 0..SPECTRUM_SAMPLES {
+            let x = sine(1.0, 25.0, i) + sine(0.3, 310.0, i);
+            let y = sine(0.5, 50.0, i);
+            spectrum.push([x, y, 9.81]);
+        }
+        let peaks = spectrum.peaks(RATE_HZ).unwrap();
+
+        for (peak, (frequency_hz, amplitude)) in
+            peaks.iter().zip([(25.0, 1.0), (50.0, 0.5), (310.0, 0.3)])
+        {
+            let peak = peak.unwrap();
+            // A bin is ~3.9 Hz wide
+            assert!((peak.frequency_hz - frequency_hz).abs() < 0.5, "{:?}", peak);
+            assert!(
+                (peak.amplitude - amplitude).abs() < amplitude * 0.1,
+                "{:?}",
+                peak
+            );
+        }
+    }
+
+    #[test]
+    fn vibration_across_gravity_keeps_its_frequency() {
+        let mut spectrum: Spectrum = Spectrum::new();
+
+        for i in 0..SPECTRUM_SAMPLES {
+            spectrum.push([sine(2.0, 40.0, i), 0.0, 9.81]);
+        }
+        let strongest = spectrum.peaks(RATE_HZ).unwrap()[0].unwrap();
+
+        assert!((strongest.frequency_hz - 40.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn nothing_before_the_burst_is_complete() {
+        let mut spectrum: Spectrum = Spectrum::new();
+
+        assert!(!spectrum.push([0.0; 3]));
+        assert_eq!(spectrum.peaks(RATE_HZ), None);
+    }
+}
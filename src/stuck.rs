@@ -0,0 +1,123 @@
+use crate::Reading;
+
+// Consecutive bit-identical samples before the sensor is taken as frozen.
+// MEMS noise changes at least one axis on virtually every sample.
+pub const STUCK_LIMIT: u16 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StuckAction {
+    // Output just froze, re-initialize the sensor
+    Recover,
+    // Still frozen after the re-init, sound the sensor fault alarm
+    Fault,
+}
+
+// Catches a sensor that still answers on I2C but returns the same values
+// forever (seen after a brown-out). The deltas would then stay at zero and
+// no alarm could ever fire.
+pub struct StuckDetector {
+    limit: u16,
+    last: Option<[u32; 7]>,
+    repeats: u16,
+}
+
+impl StuckDetector {
+    pub const fn new(limit: u16) -> Self {
+        Self {
+            limit,
+            last: None,
+            repeats: 0,
+        }
+    }
+
+    // Compares the channels bit for bit with the previous reading,
+    // the timestamp is left out
+    pub fn update(&mut self, reading: &Reading) -> Option<StuckAction> {
+        let sample = bits(reading);
+        let same = self.last == Some(sample);
+        self.last = Some(sample);
+
+        if !same {
+            self.repeats = 0;
+            return None;
+        }
+
+        self.repeats = self.repeats.saturating_add(1);
+        if self.repeats == self.limit {
+            Some(StuckAction::Recover)
+        } else if self.repeats >= self.limit.saturating_mul(2) {
+            Some(StuckAction::Fault)
+        } else {
+            None
+        }
+    }
+
+    // Samples identical to the one before
+    pub fn repeats(&self) -> u16 {
+        self.repeats
+    }
+
+    pub fn is_stuck(&self) -> bool {
+        self.repeats >= self.limit
+    }
+}
+
+impl Default for StuckDetector {
+    fn default() -> Self {
+        Self::new(STUCK_LIMIT)
+    }
+}
+
+fn bits(reading: &Reading) -> [u32; 7] {
+    let Reading {
+        acc, gyro, temp, ..
+    } = *reading;
+    [
+        acc[0].to_bits(),
+        acc[1].to_bits(),
+        acc[2].to_bits(),
+        gyro[0].to_bits(),
+        gyro[1].to_bits(),
+        gyro[2].to_bits(),
+        temp.to_bits(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: Reading = Reading::new([0.01, -0.02, 1.0], [0.001, 0.0, -0.003], 25.0, 0);
+
+    #[test]
+    fn frozen_output_recovers_then_faults() {
+        let mut detector = StuckDetector::new(3);
+
+        assert_eq!(detector.update(&SAMPLE), None);
+        assert_eq!(detector.update(&SAMPLE), None);
+        assert_eq!(detector.update(&SAMPLE), None);
+        assert_eq!(detector.update(&SAMPLE), Some(StuckAction::Recover));
+        assert!(detector.is_stuck());
+
+        assert_eq!(detector.update(&SAMPLE), None);
+        assert_eq!(detector.update(&SAMPLE), None);
+        assert_eq!(detector.update(&SAMPLE), Some(StuckAction::Fault));
+        assert_eq!(detector.update(&SAMPLE), Some(StuckAction::Fault));
+    }
+
+    #[test]
+    fn one_changed_axis_resets_the_count() {
+        let mut detector = StuckDetector::new(3);
+        for _ in 0..3 {
+            detector.update(&SAMPLE);
+        }
+        assert_eq!(detector.repeats(), 2);
+
+        detector.update(&Reading {
+            gyro: [0.001, 0.0, -0.004],
+            ..SAMPLE
+        });
+        assert_eq!(detector.repeats(), 0);
+        assert!(!detector.is_stuck());
+    }
+}
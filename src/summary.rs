@@ -0,0 +1,185 @@
+use core::fmt;
+
+use crate::Reading;
+
+// Status samples between two summary lines, 10 s at the default 500 ms.
+pub const SUMMARY_SAMPLES: u32 = 20;
+
+// Smallest, mean and largest value of one channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spread {
+    pub min: f32,
+    pub mean: f32,
+    pub max: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Channel {
+    min: f32,
+    max: f32,
+    sum: f32,
+}
+
+impl Channel {
+    const EMPTY: Channel = Channel {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+        sum: 0.0,
+    };
+
+    fn push(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+    }
+
+    fn spread(&self, count: u32) -> Spread {
+        Spread {
+            min: self.min,
+            mean: self.sum / count as f32,
+            max: self.max,
+        }
+    }
+}
+
+// Accelerometer, gyroscope and temperature since the last `clear()`, in the
+// units of the readings pushed. A summary of a few dozen samples at a time:
+// the sums are plain f32, fine for that, not for hours of samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    acc: [Channel; 3],
+    gyro: [Channel; 3],
+    temp: Channel,
+    count: u32,
+    first_ms: u64,
+    last_ms: u64,
+}
+
+impl Summary {
+    pub const fn new() -> Self {
+        Self {
+            acc: [Channel::EMPTY; 3],
+            gyro: [Channel::EMPTY; 3],
+            temp: Channel::EMPTY,
+            count: 0,
+            first_ms: 0,
+            last_ms: 0,
+        }
+    }
+
+    pub fn push(&mut self, reading: &Reading) {
+        if self.count == 0 {
+            self.first_ms = reading.t_ms;
+        }
+        self.last_ms = reading.t_ms;
+        self.count += 1;
+
+        for (channel, value) in self.acc.iter_mut().zip(reading.acc) {
+            channel.push(value);
+        }
+        for (channel, value) in self.gyro.iter_mut().zip(reading.gyro) {
+            channel.push(value);
+        }
+        self.temp.push(reading.temp);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    // From the first sample to the last
+    pub fn span_ms(&self) -> u64 {
+        self.last_ms - self.first_ms
+    }
+
+    // None until a sample is pushed
+    pub fn acc(&self) -> Option<[Spread; 3]> {
+        self.spreads(&self.acc)
+    }
+
+    pub fn gyro(&self) -> Option<[Spread; 3]> {
+        self.spreads(&self.gyro)
+    }
+
+    pub fn temp(&self) -> Option<Spread> {
+        (self.count > 0).then(|| self.temp.spread(self.count))
+    }
+
+    fn spreads(&self, channels: &[Channel; 3]) -> Option<[Spread; 3]> {
+        (self.count > 0).then(|| channels.map(|channel| channel.spread(self.count)))
+    }
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Spread {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.min, self.mean, self.max)
+    }
+}
+
+// One line, each channel as min/mean/max:
+// `20 samples over 9500 ms, ax 0.1/0.2/0.3 ay .. az .. gx .. gy .. gz .. temp ..`
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some(acc), Some(gyro), Some(temp)) = (self.acc(), self.gyro(), self.temp()) else {
+            return write!(f, "no samples");
+        };
+
+        write!(f, "{} samples over {} ms", self.count, self.span_ms())?;
+        for (name, spread) in ["ax", "ay", "az"].iter().zip(acc) {
+            write!(f, ", {} {}", name, spread)?;
+        }
+        for (name, spread) in ["gx", "gy", "gz"].iter().zip(gyro) {
+            write!(f, ", {} {}", name, spread)?;
+        }
+        write!(f, ", temp {}", temp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_mean_max_per_channel() {
+        let mut summary = Summary::new();
+        assert_eq!(summary.acc(), None);
+        assert_eq!(summary.to_string(), "no samples");
+
+        summary.push(&Reading::new([1.0, 0.0, 9.0], [0.5; 3], 25.0, 1_000));
+        summary.push(&Reading::new([-1.0, 0.0, 10.0], [0.0; 3], 26.0, 1_500));
+        summary.push(&Reading::new([3.0, 0.0, 11.0], [-0.5; 3], 27.0, 2_000));
+
+        assert_eq!(summary.count(), 3);
+        assert_eq!(summary.span_ms(), 1_000);
+        let acc = summary.acc().unwrap();
+        assert_eq!(
+            acc[0],
+            Spread {
+                min: -1.0,
+                mean: 1.0,
+                max: 3.0
+            }
+        );
+        assert_eq!(acc[2].mean, 10.0);
+        assert_eq!(summary.gyro().unwrap()[1].min, -0.5);
+        assert_eq!(
+            summary.to_string(),
+            "3 samples over 1000 ms, ax -1/1/3, ay 0/0/0, az 9/10/11, \
+             gx -0.5/0/0.5, gy -0.5/0/0.5, gz -0.5/0/0.5, temp 25/26/27"
+        );
+
+        summary.clear();
+        assert_eq!(summary.count(), 0);
+        assert_eq!(summary.temp(), None);
+    }
+}
@@ -0,0 +1,178 @@
+use core::fmt;
+
+use crate::{
+    ansi::{Paint, Style},
+    fixed::Fixed,
+    telemetry::limit_code,
+    Limit, MaintenanceMonitor, Reading,
+};
+
+// Characters per column, enough for -156.906 m/s^2 at ±16 g
+pub const WIDTH: usize = 8;
+const DECIMALS: u8 = 3;
+
+pub const STATUS_COLUMNS: [&str; 9] = [
+    "ax", "ay", "az", "mech", "gyro", "vib", "dT/min", "temp", "rpm",
+];
+
+// The column names of StatusRow, right-aligned over the values
+pub struct StatusHeader;
+
+impl fmt::Display for StatusHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, name) in STATUS_COLUMNS.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:>width$}", name, width = WIDTH)?;
+        }
+        Ok(())
+    }
+}
+
+// Values that can be missing, n/a until the monitor has enough samples,
+// without the sensor or for NaN. With the decimals to print.
+struct Cell(Option<f32>, u8);
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.and_then(|value| Fixed::new(value, self.1)) {
+            Some(value) => value.fmt(f),
+            None => f.pad("n/a"),
+        }
+    }
+}
+
+// A status sample as one fixed-width line under StatusHeader: the
+// acceleration in m/s^2, then what the mechanical, rotational, vibration,
+// temperature rise and temperature ceiling limits are checked against, each
+// shaded by its levels when `color` is set, and the shaft speed. The
+// latched limit with the highest priority, if any, follows in bold red.
+pub struct StatusRow<'a> {
+    pub reading: &'a Reading,
+    pub monitor: &'a MaintenanceMonitor,
+    pub color: bool,
+}
+
+impl fmt::Display for StatusRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            reading,
+            monitor,
+            color,
+        } = self;
+        let thresholds = monitor.thresholds();
+        let shade = |value: Option<f32>, levels| match value {
+            Some(value) => Style::shade(value, levels),
+            None => Style::Plain,
+        };
+        let [ax, ay, az] = reading.acc.map(Some);
+        let mechanical = Some(monitor.reading(Limit::Mechanical));
+        let rotational = Some(monitor.reading(Limit::Rotational));
+        let vibration = monitor.vibration_rms();
+        let rate = monitor.temp_rate();
+        let temp = Some(reading.temp);
+
+        let cells = [
+            (ax, Style::Plain, DECIMALS),
+            (ay, Style::Plain, DECIMALS),
+            (az, Style::Plain, DECIMALS),
+            (
+                mechanical,
+                shade(mechanical, &thresholds.mechanical),
+                DECIMALS,
+            ),
+            (
+                rotational,
+                shade(rotational, &thresholds.rotational),
+                DECIMALS,
+            ),
+            (vibration, shade(vibration, &thresholds.vibration), DECIMALS),
+            (rate, shade(rate, &thresholds.temperature_rate), DECIMALS),
+            (temp, shade(temp, &thresholds.temperature_ceiling), DECIMALS),
+            (monitor.speed(), Style::Plain, 0),
+        ];
+        for (i, (value, style, decimals)) in cells.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(
+                f,
+                "{:>width$}",
+                Paint::new(Cell(value, decimals), style, *color),
+                width = WIDTH
+            )?;
+        }
+
+        let latched = Limit::ALL
+            .into_iter()
+            .find(|limit| monitor.latched(*limit).is_some());
+        if let Some(limit) = latched {
+            write!(
+                f,
+                " {}",
+                Paint::new(limit_code(limit), Style::Alarm, *color)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Thresholds, SAMPLE_PERIOD_MS};
+
+    // The row as a plain terminal shows it
+    fn strip(line: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| *c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn rows_line_up_under_the_header() {
+        let mut monitor = MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+        let reading = Reading::new([0.5, -0.25, 9.81], [0.0; 3], 25.5, 1_000);
+        monitor.update(&reading);
+
+        let header = StatusHeader.to_string();
+        let plain = StatusRow {
+            reading: &reading,
+            monitor: &monitor,
+            color: false,
+        }
+        .to_string();
+        let colored = StatusRow {
+            reading: &reading,
+            monitor: &monitor,
+            color: true,
+        }
+        .to_string();
+
+        assert_eq!(header.len(), STATUS_COLUMNS.len() * (WIDTH + 1) - 1);
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.contains("\x1b[32m"));
+        assert_eq!(strip(&colored), plain);
+        assert_eq!(plain.len(), header.len());
+        assert!(plain.starts_with("   0.500   -0.250    9.810"));
+        assert!(plain.contains("     n/a"));
+        // Without a speed sensor
+        assert!(plain.ends_with("     n/a"));
+
+        monitor.set_speed(Some(1_449.6));
+        let row = StatusRow {
+            reading: &reading,
+            monitor: &monitor,
+            color: false,
+        };
+        assert!(row.to_string().ends_with("    1450"));
+    }
+}
@@ -0,0 +1,145 @@
+// Pulses of a hall-effect or optical sensor, one per revolution with a
+// single magnet or reflective mark
+pub const PULSES_PER_REV: u32 = 1;
+
+// Faster than this is contact bounce or noise, not the shaft: the pulses
+// closer together than it allows are dropped
+pub const MAX_RPM: u32 = 12_000;
+
+// No pulse for this long is a standstill, 30 RPM at one pulse a rev
+pub const ZERO_SPEED_TIMEOUT_US: u64 = 2_000_000;
+
+// How often the firmware reads the speed
+pub const RPM_PERIOD_MS: u32 = 500;
+
+// Shaft speed from the time between pulses, in µs since boot. The pulses
+// come from an interrupt, the speed is read in between: it's the mean
+// period of the pulses since the last read, or the last one's when the
+// shaft turns slower than the reads.
+#[derive(Clone, Copy, Debug)]
+pub struct Tachometer {
+    pulses_per_rev: u32,
+    min_interval_us: u64,
+    last_us: Option<u64>,
+    interval_sum_us: u64,
+    intervals: u32,
+    last_interval_us: Option<u64>,
+    bounces: u32,
+}
+
+impl Tachometer {
+    pub const fn new(pulses_per_rev: u32) -> Self {
+        Self {
+            pulses_per_rev,
+            min_interval_us: 60_000_000 / (MAX_RPM * pulses_per_rev) as u64,
+            last_us: None,
+            interval_sum_us: 0,
+            intervals: 0,
+            last_interval_us: None,
+            bounces: 0,
+        }
+    }
+
+    // False for a pulse too close to the last one, which is dropped
+    pub fn pulse(&mut self, t_us: u64) -> bool {
+        let Some(last_us) = self.last_us else {
+            self.last_us = Some(t_us);
+            return true;
+        };
+        let interval_us = t_us.saturating_sub(last_us);
+        if interval_us < self.min_interval_us {
+            self.bounces = self.bounces.saturating_add(1);
+            return false;
+        }
+        // The first pulse after a standstill only starts the timing over
+        if interval_us <= ZERO_SPEED_TIMEOUT_US {
+            self.interval_sum_us += interval_us;
+            self.intervals += 1;
+            self.last_interval_us = Some(interval_us);
+        }
+        self.last_us = Some(t_us);
+        true
+    }
+
+    // In RPM, 0 at a standstill or before the second pulse
+    pub fn take_rpm(&mut self, now_us: u64) -> f32 {
+        let interval_us = match self.intervals {
+            0 => self.last_interval_us,
+            n => Some(self.interval_sum_us / n as u64),
+        };
+        self.interval_sum_us = 0;
+        self.intervals = 0;
+
+        let stopped = match self.last_us {
+            Some(last_us) => now_us.saturating_sub(last_us) > ZERO_SPEED_TIMEOUT_US,
+            None => true,
+        };
+        if stopped {
+            self.last_interval_us = None;
+            return 0.0;
+        }
+        match interval_us {
+            Some(interval_us) if interval_us > 0 => {
+                60.0e6 / (interval_us as f32 * self.pulses_per_rev as f32)
+            }
+            _ => 0.0,
+        }
+    }
+
+    // Pulses dropped as bounce since the last call, a flaky sensor or a
+    // reed switch if it keeps counting
+    pub fn take_bounces(&mut self) -> u32 {
+        core::mem::take(&mut self.bounces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rpm(rpm: f32, expected: f32) {
+        assert!((rpm - expected).abs() < 0.5, "{} RPM", rpm);
+    }
+
+    #[test]
+    fn speed_from_the_pulse_period() {
+        let mut tachometer = Tachometer::new(1);
+        assert_eq!(tachometer.take_rpm(0), 0.0);
+
+        // 1500 RPM, a pulse every 40 ms
+        for i in 0..10 {
+            assert!(tachometer.pulse(1_000_000 + i * 40_000));
+        }
+        assert_rpm(tachometer.take_rpm(1_370_000), 1_500.0);
+
+        // Slower than the reads, the last period holds
+        let mut slow = Tachometer::new(2);
+        slow.pulse(0);
+        slow.pulse(1_000_000);
+        assert_rpm(slow.take_rpm(1_100_000), 30.0);
+        assert_rpm(slow.take_rpm(1_600_000), 30.0);
+    }
+
+    #[test]
+    fn standstill_and_bounce() {
+        let mut tachometer = Tachometer::new(1);
+        tachometer.pulse(0);
+        tachometer.pulse(60_000);
+        assert_rpm(tachometer.take_rpm(100_000), 1_000.0);
+        // No pulse for more than the timeout
+        assert_eq!(tachometer.take_rpm(2_100_000), 0.0);
+
+        // Starting again, the gap isn't a period
+        tachometer.pulse(5_000_000);
+        assert_eq!(tachometer.take_rpm(5_010_000), 0.0);
+
+        // A bouncing contact, 0.5 ms apart is 120000 RPM
+        for i in 1..=5 {
+            tachometer.pulse(5_000_000 + i * 500);
+        }
+        assert_eq!(tachometer.take_bounces(), 5);
+        assert_eq!(tachometer.take_bounces(), 0);
+        assert!(tachometer.pulse(5_100_000));
+        assert_rpm(tachometer.take_rpm(5_110_000), 600.0);
+    }
+}
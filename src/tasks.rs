@@ -0,0 +1,429 @@
+// The firmware on the Embassy executor, `--features embassy`.
+// The blocking build schedules everything on one 10 ms tick, here every
+// activity is a task with its own cadence:
+// - sampler: reads the MPU6050 and feeds the watchdog
+// - detector: runs the monitor on the readings, owns the latch and the relay
+//   and takes the button presses
+// - alarm: plays the buzzer and LED patterns
+// - reporter: prints the status
+// Readings, alarm commands and reports are message-passed over channels.
+// The monitor, with its thresholds, is shared with the reporter through
+// a mutex.
+// Not ported yet, still blocking-only: the spectrum burst, the motion
+// interrupt, stuck sensor recovery, the post-trigger capture, recalibrating
+// a detached sensor on the button, the heartbeat LED, the serial console,
+// the settings saved to flash, the alarm counters and the LEDC buzzer.
+
+use embassy_executor::Executor;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, mutex::Mutex};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use hal::{
+    clock::ClockControl,
+    embassy,
+    gpio::{Gpio2, Gpio26, Gpio33, Output, PushPull},
+    i2c::I2C,
+    peripherals::{Peripherals, I2C0, TIMG0},
+    prelude::*,
+    timer::{TimerGroup, Wdt},
+    Delay, Rtc, IO,
+};
+use mpu6050::Mpu6050;
+use rs_esp32_simple_preventive_maintenance_example::{
+    bus::{self, BusAction, BusHealth, BusProxy, SharedBus},
+    config::{Level, OUTPUT},
+    peak::PEAK_PERIOD_MS,
+    sensor::{self, ImuSensor, Model, SensorConfig},
+    summary::SUMMARY_SAMPLES,
+    vibration::VIBRATION_PERIOD_MS,
+    Alarm, AlarmLatch, Alert, Capture, Debouncer, Fault, GyroBias, Limit, MaintenanceMonitor,
+    OutputMode, PinBuzzer, Reading, Relay, Summary, Thresholds, RELAY_TRIP_SAMPLES,
+    SAMPLE_PERIOD_MS,
+};
+use static_cell::StaticCell;
+
+use crate::{
+    adopt, board, bring_up, calibrate, calibrated, print_alarm, print_boot, print_header,
+    print_latched_event, print_run_transition, print_sample, print_status, warn_reset,
+    I2C_FREQUENCY_KHZ, SENSOR_TIMEOUT_MS, TICK_MS, WATCHDOG_TIMEOUT_S,
+};
+
+type Mpu = Mpu6050<BusProxy<'static, I2C<'static, I2C0>>>;
+type SensorAlarm = Alarm<PinBuzzer<Gpio33<Output<PushPull>>>, Gpio2<Output<PushPull>>>;
+type MachineRelay = Relay<Gpio26<Output<PushPull>>>;
+
+// A status sample goes through `update()`, the fast ones in between only
+// feed the peak deltas and the orientation filter. A failed status read
+// still comes through, as None.
+struct Sample {
+    reading: Option<Reading>,
+    status: bool,
+    vibration: bool,
+}
+
+enum AlarmCommand {
+    Start(Alert),
+    Chirp(Limit),
+    Fault(Fault),
+    Indicator(bool),
+    Silence,
+}
+
+struct Report {
+    reading: Reading,
+    alert: Option<Alert>,
+    // Tripped on this sample
+    relay_trip: Option<Limit>,
+    relay_tripped: Option<Limit>,
+    relay_pending: Option<(u8, u8)>,
+}
+
+// Enough for the detector to wait on the reporter for a few fast samples
+static READINGS: Channel<CriticalSectionRawMutex, Sample, 8> = Channel::new();
+static ALARM: Channel<CriticalSectionRawMutex, AlarmCommand, 4> = Channel::new();
+// Reports are dropped while the reporter is still printing the last one
+static REPORTS: Channel<CriticalSectionRawMutex, Report, 1> = Channel::new();
+static MONITOR: Mutex<CriticalSectionRawMutex, Option<MaintenanceMonitor>> = Mutex::new(None);
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+// 'static, for the bus handle the sampler task owns
+static BUS: StaticCell<SharedBus<I2C<'static, I2C0>>> = StaticCell::new();
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take();
+    let mut system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    // Same watchdog setup as the blocking version, TIMG0 timer 0 is the
+    // embassy-time driver, timer 1 keeps the uptime as in the blocking version.
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(
+        peripherals.TIMG0,
+        &clocks,
+        &mut system.peripheral_clock_control,
+    );
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(
+        peripherals.TIMG1,
+        &clocks,
+        &mut system.peripheral_clock_control,
+    );
+    let mut wdt1 = timer_group1.wdt;
+    board::time::init(timer_group1.timer1, clocks.apb_clock.to_Hz());
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+    embassy::init(&clocks, timer_group0.timer0);
+
+    let boot = board::diagnostics::boot();
+    print_boot(&boot, None);
+
+    let mut delay = Delay::new(&clocks);
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let relay = Relay::new(io.pins.gpio26.into_push_pull_output(), RELAY_TRIP_SAMPLES).unwrap();
+    let mut alarm = Alarm::new(
+        PinBuzzer::new(io.pins.gpio33.into_push_pull_output()),
+        io.pins.gpio2.into_push_pull_output(),
+    );
+    if boot.cause.is_abnormal() {
+        warn_reset(&mut alarm, &mut delay);
+    }
+    board::button::init(io.pins.gpio0.into_pull_up_input());
+
+    let i2c = I2C::new(
+        peripherals.I2C0,
+        io.pins.gpio21,
+        io.pins.gpio22,
+        I2C_FREQUENCY_KHZ.kHz(),
+        &mut system.peripheral_clock_control,
+        &clocks,
+    );
+    let bus: &'static SharedBus<_> = BUS.init(SharedBus::new(i2c));
+    delay.delay_ms(255u8);
+    let (mut mpu, _, model, sensor_config) = bring_up(bus, &mut alarm, &mut delay);
+
+    // The boot calibration blocks, nothing else runs yet
+    let mut monitor: MaintenanceMonitor =
+        MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+    let mut bus_health = BusHealth::new();
+    let mut gyro_bias = GyroBias::default();
+    let baseline = calibrate(
+        &mut mpu,
+        &mut alarm,
+        &mut delay,
+        &mut bus_health,
+        &mut || {},
+    );
+    adopt(&baseline, &mut monitor, &mut gyro_bias);
+    MONITOR
+        .try_lock()
+        .expect("Monitor mutex taken before the executor started")
+        .replace(monitor);
+
+    wdt0.start(WATCHDOG_TIMEOUT_S.secs());
+
+    println!("---");
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner
+            .spawn(sampler(mpu, model, sensor_config, gyro_bias, delay, wdt0))
+            .unwrap();
+        spawner.spawn(detector(relay)).unwrap();
+        spawner.spawn(alarm_player(alarm)).unwrap();
+        spawner.spawn(reporter()).unwrap();
+    })
+}
+
+fn now_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+// Reads the sensor every PEAK_PERIOD_MS, every SAMPLE_PERIOD_MS one of them
+// is a status sample
+#[embassy_executor::task]
+async fn sampler(
+    mut mpu: Mpu,
+    model: Model,
+    config: SensorConfig,
+    gyro_bias: GyroBias,
+    mut delay: Delay,
+    mut wdt: Wdt<TIMG0>,
+) {
+    let sample_every = SAMPLE_PERIOD_MS / PEAK_PERIOD_MS;
+    let vibration_every = VIBRATION_PERIOD_MS / PEAK_PERIOD_MS;
+    let mut health = BusHealth::new();
+    let mut last_read_ms = now_ms();
+    let mut ticker = Ticker::every(Duration::from_millis(PEAK_PERIOD_MS as u64));
+
+    let mut count: u32 = 0;
+    loop {
+        let status = count % sample_every == 0;
+        let raw = read(&mut mpu, &mut health).await;
+
+        match raw {
+            Some(_) => {
+                last_read_ms = now_ms();
+                if status && health.success() {
+                    println!("Sensor responding again");
+                }
+            }
+            None if status => match health.failure(now_ms()) {
+                BusAction::Skip => warn!("WARNING: sensor read failed, sample skipped"),
+                BusAction::Recover => {
+                    warn!("WARNING: sensor keeps failing, re-initializing it");
+                    let recovered = sensor::init(&mut mpu, model, &mut delay)
+                        .and_then(|_| config.apply(&mut mpu));
+                    if recovered.is_err() {
+                        warn!("WARNING: sensor re-initialization failed");
+                    }
+                }
+                BusAction::Escalate => {
+                    error!("FAULT: {}", Fault::Bus.description());
+                    ALARM.send(AlarmCommand::Fault(Fault::Bus)).await;
+                }
+            },
+            None => {}
+        }
+
+        if raw.is_some() || status {
+            READINGS
+                .send(Sample {
+                    reading: raw.map(|raw| calibrated(&raw, &gyro_bias)),
+                    status,
+                    vibration: count % vibration_every == 0,
+                })
+                .await;
+        }
+
+        if now_ms().wrapping_sub(last_read_ms) < SENSOR_TIMEOUT_MS {
+            wdt.feed();
+        }
+        count = count.wrapping_add(1);
+        ticker.next().await;
+    }
+}
+
+// `read_sample()` with the retries awaited instead of blocking
+async fn read(mpu: &mut Mpu, health: &mut BusHealth) -> Option<Reading> {
+    for attempt in 0..bus::READ_ATTEMPTS {
+        if attempt > 0 {
+            Timer::after(Duration::from_millis(bus::RETRY_DELAY_MS as u64)).await;
+        }
+
+        if let Ok(sample) = mpu.read(board::time::uptime_ms()) {
+            if attempt > 0 {
+                health.retried(attempt);
+            }
+            return Some(sample);
+        }
+    }
+
+    None
+}
+
+#[embassy_executor::task]
+async fn detector(mut relay: MachineRelay) {
+    let mut latch = AlarmLatch::new();
+    let mut ack_button = Debouncer::default();
+    let mut indicator = false;
+
+    loop {
+        let sample = READINGS.recv().await;
+        let now_ms = now_ms();
+        let mut monitor = MONITOR.lock().await;
+        let Some(monitor) = monitor.as_mut() else {
+            continue;
+        };
+
+        match (sample.reading, sample.status) {
+            (Some(reading), false) => {
+                let filtered = monitor.filter(&reading);
+                monitor.hold_peak(filtered.acc);
+                monitor.fuse_orientation(&filtered);
+                if sample.vibration {
+                    monitor.push_vibration(filtered.acc);
+                }
+            }
+            (Some(reading), true) => {
+                let alert = monitor.update(&reading);
+                print_run_transition(monitor);
+                if sample.vibration {
+                    monitor.push_vibration(monitor.filtered().acc);
+                }
+                latch.update(monitor, alert, now_ms);
+                let relay_trip = relay.update(monitor).unwrap();
+                if let Some(alert) = alert {
+                    ALARM.send(AlarmCommand::Start(alert)).await;
+                }
+
+                REPORTS
+                    .try_send(Report {
+                        reading,
+                        alert,
+                        relay_trip,
+                        relay_tripped: relay.tripped(),
+                        relay_pending: relay.pending(),
+                    })
+                    .ok();
+            }
+            // The next sample has nothing to compute the jerk against
+            (None, _) => monitor.sample_missed(),
+        }
+
+        // The button acknowledges the latched alarm, resets the relay and
+        // mutes the conditions that are still active
+        if board::button::take_press() && ack_button.press(now_ms) {
+            if latch.is_active() {
+                println!("ALARM ACKNOWLEDGED");
+                for event in latch.acknowledge().iter().flatten() {
+                    print_latched_event(event, now_ms);
+                }
+            }
+            if relay.tripped().is_some() {
+                relay.acknowledge().unwrap();
+                println!("Relay reset, machine allowed to run");
+            }
+            if monitor.any_latched() {
+                monitor.mute();
+                println!("Alarm muted");
+            }
+            ALARM.send(AlarmCommand::Silence).await;
+        }
+
+        if latch.reminder_due(now_ms) {
+            if let Some(event) = Limit::ALL.iter().find_map(|limit| latch.event(*limit)) {
+                ALARM.send(AlarmCommand::Chirp(event.limit)).await;
+            }
+        }
+
+        let latched = monitor.any_latched() || latch.is_active();
+        if latched != indicator {
+            indicator = latched;
+            ALARM.send(AlarmCommand::Indicator(latched)).await;
+        }
+    }
+}
+
+// Advances the buzzer and LED patterns every TICK_MS
+#[embassy_executor::task]
+async fn alarm_player(mut alarm: SensorAlarm) {
+    let mut ticker = Ticker::every(Duration::from_millis(TICK_MS as u64));
+
+    loop {
+        let now_ms = now_ms();
+        while let Ok(command) = ALARM.try_recv() {
+            match command {
+                AlarmCommand::Start(alert) => alarm.start(alert, now_ms).unwrap(),
+                AlarmCommand::Chirp(limit) => alarm.chirp(limit, now_ms).unwrap(),
+                AlarmCommand::Fault(fault) if !alarm.is_playing() => {
+                    alarm.fault(fault, now_ms).unwrap()
+                }
+                AlarmCommand::Fault(_) => {}
+                AlarmCommand::Indicator(on) => alarm.set_indicator(on).unwrap(),
+                AlarmCommand::Silence => alarm.silence().unwrap(),
+            }
+        }
+
+        alarm.tick(now_ms).unwrap();
+        ticker.next().await;
+    }
+}
+
+// Prints every status sample. The monitor stays locked meanwhile, the
+// readings wait in their channel.
+#[embassy_executor::task]
+async fn reporter() {
+    // Readings leading up to an alarm, dumped when it fires
+    let mut pre_trigger: Capture = Capture::new();
+    let mut summary = Summary::new();
+    print_header(OUTPUT.mode);
+
+    loop {
+        let report = REPORTS.recv().await;
+        let reading = report.reading;
+        pre_trigger.push(reading);
+
+        let monitor = MONITOR.lock().await;
+        let Some(monitor) = monitor.as_ref() else {
+            continue;
+        };
+
+        print_sample(OUTPUT.mode, &reading, monitor);
+        if let Some(limit) = report.relay_trip {
+            error!("RELAY TRIPPED: {} CRITICAL, MACHINE STOPPED", limit.name());
+        }
+        if OUTPUT.mode == OutputMode::Human {
+            summary.push(&reading);
+            if summary.count() >= SUMMARY_SAMPLES {
+                info!("Summary: {}", summary);
+                summary.clear();
+                print_header(OUTPUT.mode);
+            }
+        }
+        // The full dump, at the debug level only
+        if OUTPUT.mode != OutputMode::Human || !board::log::enabled(Level::Debug) {
+            if let Some(alert) = report.alert {
+                print_alarm(OUTPUT.mode, &alert, monitor, &reading, &pre_trigger);
+            }
+            continue;
+        }
+
+        for limit in Limit::ALL {
+            if let Some((count, required)) = monitor.pending(limit) {
+                println!("{} violation {}/{}", limit.name(), count, required);
+            }
+        }
+        if let Some(alert) = report.alert {
+            print_alarm(OUTPUT.mode, &alert, monitor, &reading, &pre_trigger);
+        }
+        match (report.relay_tripped, report.relay_pending) {
+            (Some(limit), _) => println!("Relay: TRIPPED ({})", limit.name()),
+            (None, Some((count, required))) => {
+                println!("Relay: RUN, trip {}/{}", count, required)
+            }
+            (None, None) => println!("Relay: RUN"),
+        }
+        print_status(monitor);
+        println!("---");
+    }
+}
@@ -0,0 +1,257 @@
+use core::fmt;
+
+use crate::{fixed::Fixed, Alert, Limit, Reading, Severity};
+
+// Columns of a CsvRow
+pub const CSV_COLUMNS: &str = "t_ms,ax,ay,az,gx,gy,gz,temp,chip_temp,health,alarm";
+
+// Limit names as JSON values
+pub fn limit_key(limit: Limit) -> &'static str {
+    match limit {
+        Limit::SensorDetached => "sensor_detached",
+        Limit::Mechanical => "mechanical",
+        Limit::Rotational => "rotational",
+        Limit::Jerk => "jerk",
+        Limit::Temperature => "temperature",
+        Limit::Vibration => "vibration",
+        Limit::Velocity => "velocity",
+        Limit::Bearing => "bearing",
+        Limit::Orientation => "orientation",
+        Limit::Anomaly => "anomaly",
+        Limit::Probe => "probe",
+        Limit::Knock => "knock",
+        Limit::Electrical => "electrical",
+    }
+}
+
+pub fn severity_key(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+// JSON has no NaN or infinity, those go out as null
+struct Number(f32);
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_finite() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "null")
+        }
+    }
+}
+
+// A status sample as one line:
+// {"t":12345,"ax":..,"ay":..,"az":..,"gx":..,"gy":..,"gz":..,"temp":..,"chip_temp":..,"health":..,"alarm":"mechanical"}
+// Time in ms since boot, m/s^2, rad/s and ºC. `chip_temp` is the ESP32's
+// own, null without a reading. `health` is the last minute's score, see
+// `score`, null before the first. `alarm` is the limit latched with the
+// highest priority, null when none is.
+pub struct JsonLine<'a> {
+    pub reading: &'a Reading,
+    pub chip_temp: Option<f32>,
+    pub health: Option<u8>,
+    pub alarm: Option<Limit>,
+}
+
+impl fmt::Display for JsonLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Reading {
+            acc,
+            gyro,
+            temp,
+            t_ms,
+        } = self.reading;
+
+        write!(
+            f,
+            "{{\"t\":{},\"ax\":{},\"ay\":{},\"az\":{},\"gx\":{},\"gy\":{},\"gz\":{},\"temp\":{},\"chip_temp\":{},\"health\":{},\"alarm\":",
+            t_ms,
+            Number(acc[0]),
+            Number(acc[1]),
+            Number(acc[2]),
+            Number(gyro[0]),
+            Number(gyro[1]),
+            Number(gyro[2]),
+            Number(*temp),
+            Number(self.chip_temp.unwrap_or(f32::NAN)),
+            Number(self.health.map_or(f32::NAN, f32::from))
+        )?;
+        match self.alarm {
+            Some(limit) => write!(f, "\"{}\"}}", limit_key(limit)),
+            None => write!(f, "null}}"),
+        }
+    }
+}
+
+// An alarm as it fires, on its own line so it isn't lost with a dropped
+// sample line:
+// {"t":12345,"event":"alarm","limit":"mechanical","severity":"critical","value":..}
+// `value` is what the limit was checked against.
+pub struct JsonEvent<'a> {
+    pub alert: &'a Alert,
+    pub t_ms: u64,
+    pub value: f32,
+}
+
+impl fmt::Display for JsonEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"t\":{},\"event\":\"alarm\",\"limit\":\"{}\",\"severity\":\"{}\",\"value\":{}}}",
+            self.t_ms,
+            limit_key(self.alert.limit),
+            severity_key(self.alert.severity),
+            Number(self.value)
+        )
+    }
+}
+
+// Limit names in the CSV alarm column, short to keep the rows compact
+pub fn limit_code(limit: Limit) -> &'static str {
+    match limit {
+        Limit::SensorDetached => "DETACHED",
+        Limit::Mechanical => "MECH",
+        Limit::Rotational => "GYRO",
+        Limit::Jerk => "JERK",
+        Limit::Temperature => "TEMP",
+        Limit::Vibration => "VIB",
+        Limit::Velocity => "VEL",
+        Limit::Bearing => "BEARING",
+        Limit::Orientation => "TILT",
+        Limit::Anomaly => "ANOMALY",
+        Limit::Probe => "PROBE",
+        Limit::Knock => "KNOCK",
+        Limit::Electrical => "CURRENT",
+    }
+}
+
+// A status sample as one row under CSV_COLUMNS, with the same units as
+// JsonLine and `decimals` digits after the point. The alarm column is
+// empty while nothing is latched, and so are NaN and infinite values, a
+// missing chip temperature and the health score before the first minute.
+pub struct CsvRow<'a> {
+    pub reading: &'a Reading,
+    pub chip_temp: Option<f32>,
+    pub health: Option<u8>,
+    pub alarm: Option<Limit>,
+    pub decimals: u8,
+}
+
+impl fmt::Display for CsvRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Reading {
+            acc,
+            gyro,
+            temp,
+            t_ms,
+        } = self.reading;
+        let decimals = self.decimals;
+
+        write!(f, "{}", t_ms)?;
+        let chip_temp = self.chip_temp.unwrap_or(f32::NAN);
+        for value in acc.iter().chain(gyro).chain([temp, &chip_temp]) {
+            f.write_str(",")?;
+            if let Some(value) = Fixed::new(*value, decimals) {
+                write!(f, "{}", value)?;
+            }
+        }
+        f.write_str(",")?;
+        if let Some(health) = self.health {
+            write!(f, "{}", health)?;
+        }
+        write!(f, ",{}", self.alarm.map_or("", limit_code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_line_is_one_json_object() {
+        let reading = Reading::new([0.5, -0.25, 9.75], [0.0, 0.125, -1.0], 25.5, 12_345);
+        let line = JsonLine {
+            reading: &reading,
+            chip_temp: Some(31.25),
+            health: Some(87),
+            alarm: Some(Limit::Mechanical),
+        };
+        assert_eq!(
+            line.to_string(),
+            "{\"t\":12345,\"ax\":0.5,\"ay\":-0.25,\"az\":9.75,\"gx\":0,\"gy\":0.125,\"gz\":-1,\"temp\":25.5,\"chip_temp\":31.25,\"health\":87,\"alarm\":\"mechanical\"}"
+        );
+
+        let reading = Reading::new([f32::NAN; 3], [0.0; 3], f32::INFINITY, 0);
+        let line = JsonLine {
+            reading: &reading,
+            chip_temp: None,
+            health: None,
+            alarm: None,
+        };
+        assert_eq!(
+            line.to_string(),
+            "{\"t\":0,\"ax\":null,\"ay\":null,\"az\":null,\"gx\":0,\"gy\":0,\"gz\":0,\"temp\":null,\"chip_temp\":null,\"health\":null,\"alarm\":null}"
+        );
+    }
+
+    #[test]
+    fn alarm_event_line() {
+        let alert = Alert {
+            limit: Limit::SensorDetached,
+            severity: Severity::Warning,
+        };
+        let event = JsonEvent {
+            alert: &alert,
+            t_ms: 500,
+            value: 1.5,
+        };
+        assert_eq!(
+            event.to_string(),
+            "{\"t\":500,\"event\":\"alarm\",\"limit\":\"sensor_detached\",\"severity\":\"warning\",\"value\":1.5}"
+        );
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let reading = Reading::new([0.5, -0.25, 9.8125], [0.0, 0.125, -1.0], 25.5, 12_345);
+        let row = CsvRow {
+            reading: &reading,
+            chip_temp: Some(31.0),
+            health: Some(100),
+            alarm: None,
+            decimals: 2,
+        };
+        assert_eq!(
+            row.to_string(),
+            "12345,0.50,-0.25,9.81,0.00,0.12,-1.00,25.50,31.00,100,"
+        );
+
+        let row = CsvRow {
+            reading: &reading,
+            chip_temp: None,
+            health: Some(42),
+            alarm: Some(Limit::Temperature),
+            decimals: 0,
+        };
+        let row = row.to_string();
+        assert_eq!(row, "12345,0,-0,10,0,0,-1,26,,42,TEMP");
+        assert_eq!(row.split(',').count(), CSV_COLUMNS.split(',').count());
+
+        let reading = Reading::new([f32::NAN, -199.9996, 0.0], [0.0; 3], 25.5, 0);
+        let row = CsvRow {
+            reading: &reading,
+            chip_temp: None,
+            health: None,
+            alarm: None,
+            decimals: 3,
+        };
+        assert_eq!(
+            row.to_string(),
+            "0,,-200.000,0.000,0.000,0.000,0.000,25.500,,,"
+        );
+    }
+}
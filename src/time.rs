@@ -0,0 +1,67 @@
+use core::fmt;
+
+const MS_PER_SECOND: u64 = 1_000;
+const MS_PER_MINUTE: u64 = 60 * MS_PER_SECOND;
+const MS_PER_HOUR: u64 = 60 * MS_PER_MINUTE;
+const MS_PER_DAY: u64 = 24 * MS_PER_HOUR;
+
+// Uptime as a log line prefix, `[HH:MM:SS.mmm]`.
+// Past the first day the days go in front, `[3d 04:05:06.007]`, so the
+// hours never grow past 23 and a 64-bit uptime can't overflow them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp(pub u64);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0;
+        let (days, hours, minutes, seconds, millis) = (
+            ms / MS_PER_DAY,
+            ms % MS_PER_DAY / MS_PER_HOUR,
+            ms % MS_PER_HOUR / MS_PER_MINUTE,
+            ms % MS_PER_MINUTE / MS_PER_SECOND,
+            ms % MS_PER_SECOND,
+        );
+
+        if days > 0 {
+            write!(
+                f,
+                "[{}d {:02}:{:02}:{:02}.{:03}]",
+                days, hours, minutes, seconds, millis
+            )
+        } else {
+            write!(
+                f,
+                "[{:02}:{:02}:{:02}.{:03}]",
+                hours, minutes, seconds, millis
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    fn format(ms: u64) -> heapless::String<32> {
+        let mut line = heapless::String::new();
+        write!(line, "{}", Timestamp(ms)).unwrap();
+        line
+    }
+
+    #[test]
+    fn formats_hours_minutes_seconds_and_millis() {
+        assert_eq!(format(0), "[00:00:00.000]");
+        assert_eq!(format(5), "[00:00:00.005]");
+        assert_eq!(format(3_723_004), "[01:02:03.004]");
+        assert_eq!(format(MS_PER_DAY - 1), "[23:59:59.999]");
+    }
+
+    #[test]
+    fn days_go_in_front() {
+        assert_eq!(format(MS_PER_DAY), "[1d 00:00:00.000]");
+        assert_eq!(format(3 * MS_PER_DAY + 14_706_007), "[3d 04:05:06.007]");
+        // Half a billion years, still fits
+        assert_eq!(format(u64::MAX), "[213503982334d 14:25:51.615]");
+    }
+}
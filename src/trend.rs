@@ -0,0 +1,178 @@
+use heapless::HistoryBuffer;
+
+// Sliding window of the last N temperature samples.
+// The rate of change is the least-squares slope over the window, so a single
+// noisy sample doesn't swing it much.
+// The regression sums are updated as samples enter and leave the window.
+// They're kept in f64, the indices times ~50 ºC add up past what an f32
+// can hold exactly, and recomputed once per window anyway.
+pub struct TemperatureTrend<const N: usize> {
+    samples: HistoryBuffer<f32, N>,
+    sample_period_ms: u32,
+    // Sum of the temperatures, and of each one times its index (oldest is 0)
+    sum: f64,
+    sum_indexed: f64,
+    until_resum: usize,
+}
+
+impl<const N: usize> TemperatureTrend<N> {
+    pub fn new(sample_period_ms: u32) -> Self {
+        Self {
+            samples: HistoryBuffer::new(),
+            sample_period_ms,
+            sum: 0.0,
+            sum_indexed: 0.0,
+            until_resum: N,
+        }
+    }
+
+    pub fn push(&mut self, temp: f32) {
+        let temp = temp as f64;
+
+        if self.is_full() {
+            // Every sample left moves one index down
+            let oldest = self.samples.oldest_ordered().next().copied().unwrap_or(0.0) as f64;
+            self.sum -= oldest;
+            self.sum_indexed -= self.sum;
+            self.sum_indexed += (N - 1) as f64 * temp;
+        } else {
+            self.sum_indexed += self.samples.len() as f64 * temp;
+        }
+        self.sum += temp;
+        self.samples.write(temp as f32);
+
+        self.until_resum -= 1;
+        if self.until_resum == 0 {
+            self.resum();
+            self.until_resum = N;
+        }
+    }
+
+    fn resum(&mut self) {
+        self.sum = 0.0;
+        self.sum_indexed = 0.0;
+        for (i, temp) in self.samples.oldest_ordered().enumerate() {
+            self.sum += *temp as f64;
+            self.sum_indexed += i as f64 * *temp as f64;
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.samples.len() == self.samples.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.len() == 0
+    }
+
+    // Time covered by the samples in the window, in ms
+    pub fn span_ms(&self) -> u32 {
+        (self.len().saturating_sub(1) as u32).saturating_mul(self.sample_period_ms)
+    }
+
+    // Least-squares line over the samples in the window, as the fitted
+    // temperature of the latest sample and the slope in ºC/minute.
+    // None with fewer than two samples.
+    pub fn fit(&self) -> Option<(f32, f32)> {
+        let len = self.len();
+        if len < 2 || self.sample_period_ms == 0 {
+            return None;
+        }
+
+        let n = len as f64;
+        let sum_i = n * (n - 1.0) / 2.0;
+        let sum_ii = (n - 1.0) * n * (2.0 * n - 1.0) / 6.0;
+
+        let per_sample = (n * self.sum_indexed - sum_i * self.sum) / (n * sum_ii - sum_i * sum_i);
+        let intercept = (self.sum - per_sample * sum_i) / n;
+
+        let latest = intercept + per_sample * (n - 1.0);
+        let per_minute = per_sample * 60_000.0 / self.sample_period_ms as f64;
+        Some((latest as f32, per_minute as f32))
+    }
+
+    // Rate of change in ºC/minute.
+    // None until the window is full, a partial window right after boot
+    // would give a slope over just a few seconds.
+    pub fn rate_per_minute(&self) -> Option<f32> {
+        if !self.is_full() {
+            return None;
+        }
+
+        self.fit().map(|(_, rate)| rate)
+    }
+
+    // Minutes until the fitted line reaches `limit`, once the window holds
+    // at least `min_span_ms` of samples.
+    // None while cooling or steady, a falling trend never reaches a ceiling.
+    pub fn minutes_to(&self, limit: f32, min_span_ms: u32) -> Option<f32> {
+        if self.span_ms() < min_span_ms {
+            return None;
+        }
+
+        let (latest, rate) = self.fit()?;
+        if rate <= 0.0 {
+            return None;
+        }
+
+        Some(((limit - latest) / rate).max(0.0))
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.sum = 0.0;
+        self.sum_indexed = 0.0;
+        self.until_resum = N;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD_MS: u32 = 500;
+
+    // Rises `per_minute` ºC/minute from 50 ºC
+    fn ramp<const N: usize>(trend: &mut TemperatureTrend<N>, samples: usize, per_minute: f32) {
+        let per_sample = per_minute * PERIOD_MS as f32 / 60_000.0;
+        for i in 0..samples {
+            trend.push(50.0 + per_sample * i as f32);
+        }
+    }
+
+    #[test]
+    fn incremental_slope_matches_the_ramp() {
+        let mut trend: TemperatureTrend<120> = TemperatureTrend::new(PERIOD_MS);
+
+        // Several times around the window, including a resum
+        ramp(&mut trend, 500, 0.6);
+        let rate = trend.rate_per_minute().unwrap();
+        assert!((rate - 0.6).abs() < 1e-3, "rate {}", rate);
+    }
+
+    #[test]
+    fn forecast_waits_for_a_minute_of_data() {
+        let mut trend: TemperatureTrend<360> = TemperatureTrend::new(PERIOD_MS);
+
+        ramp(&mut trend, 100, 3.0);
+        assert_eq!(trend.minutes_to(70.0, 60_000), None);
+
+        let mut trend: TemperatureTrend<360> = TemperatureTrend::new(PERIOD_MS);
+        ramp(&mut trend, 121, 3.0);
+        // At 53 ºC after a minute, 17 ºC to go at 3 ºC/minute
+        let minutes = trend.minutes_to(70.0, 60_000).unwrap();
+        assert!((minutes - 17.0 / 3.0).abs() < 0.01, "minutes {}", minutes);
+    }
+
+    #[test]
+    fn cooling_never_reaches_the_ceiling() {
+        let mut trend: TemperatureTrend<360> = TemperatureTrend::new(PERIOD_MS);
+
+        ramp(&mut trend, 360, -1.0);
+        assert_eq!(trend.minutes_to(70.0, 60_000), None);
+    }
+}
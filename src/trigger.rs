@@ -0,0 +1,59 @@
+use crate::Limit;
+
+// The `daq-trigger` feature's pulse goes out once a limit trips, not again
+// for its escalation or while it stays latched. Only a limit that was clear
+// on the sample before is armed.
+pub struct TriggerGate {
+    armed: [bool; Limit::ALL.len()],
+}
+
+impl TriggerGate {
+    pub const fn new() -> Self {
+        Self {
+            armed: [true; Limit::ALL.len()],
+        }
+    }
+
+    // Right after the sample's checks, with the limit its alert is for and
+    // what's latched now. True if the pulse is due.
+    pub fn update(&mut self, tripped: Option<Limit>, latched: impl Fn(Limit) -> bool) -> bool {
+        let fire = tripped.is_some_and(|limit| self.armed[limit as usize]);
+        for limit in Limit::ALL {
+            self.armed[limit as usize] = !latched(limit);
+        }
+        fire
+    }
+}
+
+impl Default for TriggerGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_once_while_latched() {
+        let mut gate = TriggerGate::new();
+        let latched = |limit| limit == Limit::Mechanical;
+        assert!(gate.update(Some(Limit::Mechanical), latched));
+        // Escalated to critical, then still latched
+        assert!(!gate.update(Some(Limit::Mechanical), latched));
+        assert!(!gate.update(None, latched));
+        // Another limit tripping is another event
+        let both = |limit| matches!(limit, Limit::Mechanical | Limit::Temperature);
+        assert!(gate.update(Some(Limit::Temperature), both));
+    }
+
+    #[test]
+    fn rearms_once_released() {
+        let mut gate = TriggerGate::new();
+        let latched = |limit| limit == Limit::Rotational;
+        assert!(gate.update(Some(Limit::Rotational), latched));
+        assert!(!gate.update(None, |_| false));
+        assert!(gate.update(Some(Limit::Rotational), latched));
+    }
+}
@@ -0,0 +1,228 @@
+use heapless::HistoryBuffer;
+
+use crate::math;
+
+// Fed from the vibration samples, 50 of them at 50 Hz make a 1 s window.
+// ISO 10816 measures from 10 Hz up to 1 kHz, at 50 Hz this only sees the
+// low end of that band, i.e. imbalance and misalignment on slow machines.
+pub const VELOCITY_WINDOW: usize = 50;
+
+// Both high-pass filters, in front of and behind the integrator
+pub const VELOCITY_CUTOFF_HZ: f32 = 0.5;
+
+// ISO 10816-3 zone boundaries for medium machines on flexible foundations,
+// velocity RMS in mm/s
+pub const ZONE_AB: f32 = 2.3;
+pub const ZONE_BC: f32 = 4.5;
+pub const ZONE_CD: f32 = 7.1;
+
+// A: newly commissioned, B: fine for unrestricted long-term operation,
+// C: only for limited periods, D: causing damage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Zone {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Zone {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Zone::A => "A",
+            Zone::B => "B",
+            Zone::C => "C",
+            Zone::D => "D",
+        }
+    }
+}
+
+// Upper boundaries of zones A, B and C, in mm/s
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Zones {
+    pub ab: f32,
+    pub bc: f32,
+    pub cd: f32,
+}
+
+impl Zones {
+    pub fn zone(&self, velocity_rms: f32) -> Zone {
+        if velocity_rms >= self.cd {
+            Zone::D
+        } else if velocity_rms >= self.bc {
+            Zone::C
+        } else if velocity_rms >= self.ab {
+            Zone::B
+        } else {
+            Zone::A
+        }
+    }
+}
+
+impl Default for Zones {
+    fn default() -> Self {
+        Self {
+            ab: ZONE_AB,
+            bc: ZONE_BC,
+            cd: ZONE_CD,
+        }
+    }
+}
+
+// First-order high-pass filter on each axis
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct HighPass {
+    alpha: f32,
+    last_input: Option<[f32; 3]>,
+    output: [f32; 3],
+}
+
+impl HighPass {
+    fn new(alpha: f32) -> Self {
+        Self {
+            alpha,
+            last_input: None,
+            output: [0.0; 3],
+        }
+    }
+
+    // The first sample only seeds the filter
+    fn push(&mut self, input: [f32; 3]) -> [f32; 3] {
+        if let Some(last) = self.last_input.replace(input) {
+            for i in 0..3 {
+                self.output[i] = self.alpha * (self.output[i] + input[i] - last[i]);
+            }
+        }
+        self.output
+    }
+}
+
+// Velocity RMS over a sliding window, in mm/s.
+// The acceleration is high-passed to remove gravity, integrated, and the
+// velocity high-passed again. The second filter is what keeps it from
+// winding up: whatever offset is left in the acceleration integrates into
+// a velocity ramp, which the filter pulls back to zero.
+// Each axis is integrated on its own, the RMS is of the velocity vector.
+pub struct VelocityRms<const N: usize = VELOCITY_WINDOW> {
+    dt: f32,
+    acc_filter: HighPass,
+    last_acc: [f32; 3],
+    velocity: [f32; 3],
+    velocity_filter: HighPass,
+    squares: HistoryBuffer<f32, N>,
+    sum: f32,
+    until_resum: usize,
+}
+
+impl<const N: usize> VelocityRms<N> {
+    pub fn new(sample_period_ms: u32) -> Self {
+        let rc = 1.0 / (2.0 * core::f32::consts::PI * VELOCITY_CUTOFF_HZ);
+        let dt = sample_period_ms as f32 / 1000.0;
+        let alpha = rc / (rc + dt);
+
+        Self {
+            dt,
+            acc_filter: HighPass::new(alpha),
+            last_acc: [0.0; 3],
+            velocity: [0.0; 3],
+            velocity_filter: HighPass::new(alpha),
+            squares: HistoryBuffer::new(),
+            sum: 0.0,
+            until_resum: N,
+        }
+    }
+
+    // Acceleration in m/s^2
+    pub fn push(&mut self, acc: [f32; 3]) {
+        let acc = self.acc_filter.push(acc);
+        // Trapezoidal integration
+        for ((velocity, acc), last) in self.velocity.iter_mut().zip(acc).zip(self.last_acc) {
+            *velocity += (acc + last) * 0.5 * self.dt;
+        }
+        self.last_acc = acc;
+        let [x, y, z] = self.velocity_filter.push(self.velocity);
+
+        // In mm/s
+        let square = (x * x + y * y + z * z) * 1e6;
+        if self.is_full() {
+            if let Some(oldest) = self.squares.oldest_ordered().next() {
+                self.sum -= oldest;
+            }
+        }
+        self.squares.write(square);
+        self.sum += square;
+
+        self.until_resum -= 1;
+        if self.until_resum == 0 {
+            self.sum = self.squares.as_slice().iter().sum();
+            self.until_resum = N;
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.squares.len() == self.squares.capacity()
+    }
+
+    // RMS in mm/s, None until the window is full
+    pub fn rms(&self) -> Option<f32> {
+        if !self.is_full() || N == 0 {
+            return None;
+        }
+
+        Some(math::sqrt(self.sum.max(0.0) / N as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vibration::VIBRATION_PERIOD_MS;
+
+    const TWO_PI: f32 = 2.0 * core::f32::consts::PI;
+
+    fn velocity() -> VelocityRms {
+        VelocityRms::new(VIBRATION_PERIOD_MS)
+    }
+
+    fn seconds(n: usize) -> f32 {
+        n as f32 * VIBRATION_PERIOD_MS as f32 / 1000.0
+    }
+
+    #[test]
+    fn sine_velocity_is_acceleration_over_omega() {
+        let mut rms = velocity();
+
+        // 0.2 m/s^2 at 5 Hz across gravity
+        for n in 0..VELOCITY_WINDOW * 10 {
+            let wave = 0.2 * libm::sinf(TWO_PI * 5.0 * seconds(n));
+            rms.push([wave, 0.0, 9.81]);
+        }
+        let expected = 0.2 / (TWO_PI * 5.0) / core::f32::consts::SQRT_2 * 1000.0;
+        // Both filters and the integration take about 10% off at 5 Hz
+        let rms = rms.rms().unwrap();
+        assert!((rms - expected).abs() < 0.15 * expected, "rms {}", rms);
+    }
+
+    #[test]
+    fn constant_offset_does_not_wind_the_velocity_up() {
+        let mut rms = velocity();
+
+        rms.push([0.0, 0.0, 9.81]);
+        // A sensor offset, or the machine tilting, shifts the acceleration
+        // for good. Integrated as is, it would ramp to 5 m/s within 10 s.
+        for _ in 0..VELOCITY_WINDOW * 10 {
+            rms.push([0.5, 0.0, 9.81]);
+        }
+        assert!(rms.rms().unwrap() < 0.1, "rms {:?}", rms.rms());
+    }
+
+    #[test]
+    fn zones_follow_the_boundaries() {
+        let zones = Zones::default();
+
+        assert_eq!(zones.zone(1.0), Zone::A);
+        assert_eq!(zones.zone(ZONE_AB), Zone::B);
+        assert_eq!(zones.zone(5.0), Zone::C);
+        assert_eq!(zones.zone(20.0), Zone::D);
+    }
+}
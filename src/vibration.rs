@@ -0,0 +1,138 @@
+use heapless::HistoryBuffer;
+
+use crate::math;
+
+// Vibration is sampled faster than the status cadence, 128 samples at
+// 50 Hz cover about 2.5 s
+pub const VIBRATION_PERIOD_MS: u32 = 20;
+pub const RMS_WINDOW: usize = 128;
+
+// The high-pass filter removes gravity and slow changes in position
+pub const VIBRATION_CUTOFF_HZ: f32 = 1.0;
+
+// RMS of the high-pass-filtered acceleration magnitude over a sliding window.
+// A worn bearing shows up as sustained low-amplitude vibration, which a
+// single-sample delta misses.
+// The sum of squares is updated as samples enter and leave the window, and
+// recomputed once per window so float errors don't pile up.
+pub struct VibrationRms<const N: usize = RMS_WINDOW> {
+    squares: HistoryBuffer<f32, N>,
+    sum: f32,
+    until_resum: usize,
+    alpha: f32,
+    last_input: Option<f32>,
+    filtered: f32,
+}
+
+impl<const N: usize> VibrationRms<N> {
+    pub fn new(sample_period_ms: u32) -> Self {
+        let rc = 1.0 / (2.0 * core::f32::consts::PI * VIBRATION_CUTOFF_HZ);
+        let dt = sample_period_ms as f32 / 1000.0;
+
+        Self {
+            squares: HistoryBuffer::new(),
+            sum: 0.0,
+            until_resum: N,
+            alpha: rc / (rc + dt),
+            last_input: None,
+            filtered: 0.0,
+        }
+    }
+
+    // Acceleration in m/s^2
+    pub fn push(&mut self, acc: [f32; 3]) {
+        let [x, y, z] = acc;
+        let magnitude = math::sqrt(x * x + y * y + z * z);
+
+        // The first sample only seeds the filter
+        let Some(last) = self.last_input.replace(magnitude) else {
+            return;
+        };
+        self.filtered = self.alpha * (self.filtered + magnitude - last);
+
+        let square = self.filtered * self.filtered;
+        if self.squares.len() == self.squares.capacity() {
+            if let Some(oldest) = self.squares.oldest_ordered().next() {
+                self.sum -= oldest;
+            }
+        }
+        self.squares.write(square);
+        self.sum += square;
+
+        self.until_resum -= 1;
+        if self.until_resum == 0 {
+            self.sum = self.squares.as_slice().iter().sum();
+            self.until_resum = N;
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.squares.len() == self.squares.capacity()
+    }
+
+    // RMS in m/s^2, None until the window is full
+    pub fn rms(&self) -> Option<f32> {
+        if !self.is_full() || N == 0 {
+            return None;
+        }
+
+        Some(math::sqrt(self.sum.max(0.0) / N as f32))
+    }
+
+    // Starts over, e.g. after the sensor was re-initialized
+    pub fn clear(&mut self) {
+        self.squares.clear();
+        self.sum = 0.0;
+        self.until_resum = N;
+        self.last_input = None;
+        self.filtered = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vibrating(amplitude: f32, hz: f32, n: usize) -> [f32; 3] {
+        let t = n as f32 * VIBRATION_PERIOD_MS as f32 / 1000.0;
+        let wave = amplitude * libm::sinf(2.0 * core::f32::consts::PI * hz * t);
+        [0.0, 0.0, 9.81 + wave]
+    }
+
+    #[test]
+    fn still_sensor_has_no_vibration() {
+        let mut rms: VibrationRms = VibrationRms::new(VIBRATION_PERIOD_MS);
+
+        for _ in 0..RMS_WINDOW {
+            rms.push([0.0, 0.0, 9.81]);
+            assert_eq!(rms.rms(), None);
+        }
+        rms.push([0.0, 0.0, 9.81]);
+        assert!(rms.rms().unwrap() < 1e-6);
+    }
+
+    #[test]
+    fn sine_rms_is_amplitude_over_sqrt_2() {
+        let mut rms: VibrationRms = VibrationRms::new(VIBRATION_PERIOD_MS);
+
+        for n in 0..RMS_WINDOW * 4 {
+            rms.push(vibrating(0.5, 10.0, n));
+        }
+        // The filter takes a little off at 10 Hz
+        let expected = 0.5 / core::f32::consts::SQRT_2;
+        assert!((rms.rms().unwrap() - expected).abs() < 0.1 * expected);
+    }
+
+    #[test]
+    fn window_forgets_old_vibration() {
+        let mut rms: VibrationRms = VibrationRms::new(VIBRATION_PERIOD_MS);
+
+        for n in 0..RMS_WINDOW * 2 {
+            rms.push(vibrating(2.0, 10.0, n));
+        }
+        for _ in 0..RMS_WINDOW * 2 {
+            rms.push([0.0, 0.0, 9.81]);
+        }
+        assert!(rms.rms().unwrap() < 0.01);
+    }
+}
@@ -0,0 +1,449 @@
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+
+use crate::crc::crc16_xmodem;
+
+// Sending a file to a terminal program for the `download` command, on the
+// console's UART: XMODEM-CRC in 128-byte blocks, or YMODEM with the file's
+// name and size in block 0 and 1 KiB blocks after it. The receiver drives
+// it: 'C' to start, ACK or NAK after every block, two CANs to cancel.
+// A block, the frame next: SOH or STX, its number and the number's
+// complement, the data, then the big-endian CRC-16/XMODEM of the data.
+pub const SOH: u8 = 0x01;
+pub const STX: u8 = 0x02;
+pub const EOT: u8 = 0x04;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+pub const CAN: u8 = 0x18;
+// What a receiver asks with for CRC-16 rather than the old checksum
+pub const CRC_MODE: u8 = b'C';
+// Pads the last block. XMODEM has no size, the file keeps the padding.
+const PAD: u8 = 0x1a;
+
+// Long enough to start the terminal's receive after the command
+pub const START_TIMEOUT_MS: u32 = 60_000;
+// For an answer to a frame, from when it's all out
+pub const ANSWER_TIMEOUT_MS: u32 = 10_000;
+pub const MAX_RETRIES: u8 = 10;
+// What goes out a tick, under the UART's 128-byte FIFO at 115200 baud so
+// writing it never blocks
+pub const XMODEM_CHUNK: usize = 96;
+
+pub const NAME_LEN: usize = 32;
+const HEADER_LEN: usize = 128;
+const BLOCK_LEN_1K: usize = 1_024;
+const FRAME_LEN: usize = 3 + BLOCK_LEN_1K + 2;
+// What most senders abort with, more than the two a receiver looks for
+const CANCEL: [u8; 8] = [CAN; 8];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Xmodem,
+    Ymodem,
+}
+
+impl Protocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::Xmodem => "xmodem",
+            Protocol::Ymodem => "ymodem",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [Protocol::Xmodem, Protocol::Ymodem]
+            .into_iter()
+            .find(|protocol| protocol.name().eq_ignore_ascii_case(name))
+    }
+
+    fn block_len(&self) -> usize {
+        match self {
+            Protocol::Xmodem => HEADER_LEN,
+            Protocol::Ymodem => BLOCK_LEN_1K,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Sent,
+    // By the receiver
+    Cancelled,
+    // Never started, or never asked for the next step
+    TimedOut,
+    // MAX_RETRIES NAKs or timeouts in a row for one frame
+    Failed,
+}
+
+impl Outcome {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Outcome::Sent => "sent",
+            Outcome::Cancelled => "cancelled by the receiver",
+            Outcome::TimedOut => "timed out",
+            Outcome::Failed => "given up after too many retries",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    // For the receiver's first 'C'
+    Start,
+    // YMODEM's block 0, then the 'C' that follows its ACK
+    Header,
+    HeaderAcked,
+    Data,
+    Eot,
+    // YMODEM: for the 'C' after the EOT, then the empty block 0 ending
+    // the batch
+    Closing,
+    Trailer,
+    Done(Outcome),
+}
+
+pub struct Sender {
+    protocol: Protocol,
+    name: String<NAME_LEN>,
+    len: u32,
+    state: State,
+    block: u8,
+    // Of the file, up to the end of the frame's data
+    offset: u32,
+    frame: Vec<u8, FRAME_LEN>,
+    // How much of the frame is out
+    sent: usize,
+    retries: u8,
+    // Since the frame was all out, or the transfer started
+    waiting_since_ms: u32,
+    // The last byte was a CAN
+    cancel: bool,
+}
+
+impl Sender {
+    // `name` is cut to NAME_LEN, only YMODEM sends it
+    pub fn new(protocol: Protocol, name: &str, len: u32, now_ms: u32) -> Self {
+        let mut sender = Self {
+            protocol,
+            name: String::new(),
+            len,
+            state: State::Start,
+            block: 0,
+            offset: 0,
+            frame: Vec::new(),
+            sent: 0,
+            retries: 0,
+            waiting_since_ms: now_ms,
+            cancel: false,
+        };
+        for char in name.chars() {
+            if sender.name.push(char).is_err() {
+                break;
+            }
+        }
+        sender
+    }
+
+    // A byte from the receiver. `fill` is handed the file's bytes at an
+    // offset when the next block is due, always in order and only once.
+    pub fn receive(&mut self, byte: u8, now_ms: u32, fill: impl FnOnce(u32, &mut [u8])) {
+        if byte == CAN {
+            if core::mem::replace(&mut self.cancel, true) {
+                self.finish(Outcome::Cancelled);
+            }
+            return;
+        }
+        self.cancel = false;
+        // Anything else that comes while a frame goes out is stale
+        if self.sent < self.frame.len() {
+            return;
+        }
+
+        match (self.state, byte) {
+            (State::Start, CRC_MODE) => match self.protocol {
+                Protocol::Xmodem => self.next_block(fill),
+                Protocol::Ymodem => self.header(State::Header),
+            },
+            (State::Header, ACK) => self.wait(State::HeaderAcked, now_ms),
+            (State::HeaderAcked, CRC_MODE) | (State::Data, ACK) => self.next_block(fill),
+            (State::Eot, ACK) => match self.protocol {
+                Protocol::Xmodem => self.finish(Outcome::Sent),
+                Protocol::Ymodem => self.wait(State::Closing, now_ms),
+            },
+            (State::Closing, CRC_MODE) => self.header(State::Trailer),
+            (State::Trailer, ACK) => self.finish(Outcome::Sent),
+            // Some receivers NAK the first EOT on purpose, and ask for a
+            // header they didn't get with another 'C'
+            (State::Header | State::Data | State::Eot | State::Trailer, NAK)
+            | (State::Header, CRC_MODE) => self.resend(),
+            _ => {}
+        }
+    }
+
+    // The timeouts
+    pub fn poll(&mut self, now_ms: u32) {
+        if self.sent < self.frame.len() {
+            return;
+        }
+        let timeout_ms = match self.state {
+            State::Done(_) => return,
+            State::Start => START_TIMEOUT_MS,
+            _ => ANSWER_TIMEOUT_MS,
+        };
+        if now_ms.wrapping_sub(self.waiting_since_ms) < timeout_ms {
+            return;
+        }
+        match self.state {
+            State::Start | State::HeaderAcked => self.finish(Outcome::TimedOut),
+            // The file is all there, only the batch's end went unasked
+            State::Closing => self.finish(Outcome::Sent),
+            _ => {
+                self.waiting_since_ms = now_ms;
+                self.resend();
+            }
+        }
+    }
+
+    // What's left of the frame, for the UART
+    pub fn pending(&self) -> &[u8] {
+        &self.frame[self.sent..]
+    }
+
+    pub fn sent(&mut self, len: usize, now_ms: u32) {
+        self.sent = (self.sent + len).min(self.frame.len());
+        if self.sent == self.frame.len() {
+            self.waiting_since_ms = now_ms;
+        }
+    }
+
+    // Once the last frame is out
+    pub fn finished(&self) -> Option<Outcome> {
+        match self.state {
+            State::Done(outcome) if self.sent == self.frame.len() => Some(outcome),
+            _ => None,
+        }
+    }
+
+    // Bytes of the file sent so far, and its length
+    pub fn progress(&self) -> (u32, u32) {
+        (self.offset, self.len)
+    }
+
+    fn next_block(&mut self, fill: impl FnOnce(u32, &mut [u8])) {
+        if self.offset >= self.len {
+            self.frame.clear();
+            self.frame.push(EOT).ok();
+            self.start(State::Eot);
+            return;
+        }
+        let block_len = self.protocol.block_len();
+        let mut data = [PAD; BLOCK_LEN_1K];
+        let len = (self.len - self.offset).min(block_len as u32) as usize;
+        fill(self.offset, &mut data[..len]);
+        self.offset += len as u32;
+        self.block = self.block.wrapping_add(1);
+        self.frame_block(self.block, &data[..block_len]);
+        self.start(State::Data);
+    }
+
+    // Block 0: the name and the size in decimal, or all zeros for the end
+    fn header(&mut self, state: State) {
+        let mut data = [0; HEADER_LEN];
+        if state == State::Header {
+            let mut text = String::<HEADER_LEN>::new();
+            write!(text, "{}\0{}", self.name, self.len).ok();
+            data[..text.len()].copy_from_slice(text.as_bytes());
+        }
+        self.frame_block(0, &data);
+        self.start(state);
+    }
+
+    fn frame_block(&mut self, number: u8, data: &[u8]) {
+        let start = if data.len() == HEADER_LEN { SOH } else { STX };
+        self.frame.clear();
+        // Always fits, the frame is sized for a 1 KiB block
+        self.frame.extend_from_slice(&[start, number, !number]).ok();
+        self.frame.extend_from_slice(data).ok();
+        self.frame
+            .extend_from_slice(&crc16_xmodem(data).to_be_bytes())
+            .ok();
+    }
+
+    fn start(&mut self, state: State) {
+        self.state = state;
+        self.sent = 0;
+        self.retries = 0;
+    }
+
+    // For an answer, with nothing to send
+    fn wait(&mut self, state: State, now_ms: u32) {
+        self.frame.clear();
+        self.start(state);
+        self.waiting_since_ms = now_ms;
+    }
+
+    fn resend(&mut self) {
+        self.retries += 1;
+        if self.retries > MAX_RETRIES {
+            self.finish(Outcome::Failed);
+        } else {
+            self.sent = 0;
+        }
+    }
+
+    // The receiver is told, unless it's the one that ended it
+    fn finish(&mut self, outcome: Outcome) {
+        self.frame.clear();
+        if !matches!(outcome, Outcome::Sent | Outcome::Cancelled) {
+            self.frame.extend_from_slice(&CANCEL).ok();
+        }
+        self.state = State::Done(outcome);
+        self.sent = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(len: usize) -> std::vec::Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    // The receiver's side of it
+    struct Link {
+        sender: Sender,
+        file: std::vec::Vec<u8>,
+        now_ms: u32,
+    }
+
+    impl Link {
+        fn new(protocol: Protocol, len: usize) -> Self {
+            Self {
+                sender: Sender::new(protocol, "events.txt", len as u32, 0),
+                file: file(len),
+                now_ms: 0,
+            }
+        }
+
+        fn send(&mut self, byte: u8) -> std::vec::Vec<u8> {
+            let file = &self.file;
+            self.sender.receive(byte, self.now_ms, |offset, bytes| {
+                let offset = offset as usize;
+                bytes.copy_from_slice(&file[offset..offset + bytes.len()]);
+            });
+            self.drain()
+        }
+
+        fn drain(&mut self) -> std::vec::Vec<u8> {
+            let mut out = std::vec::Vec::new();
+            while !self.sender.pending().is_empty() {
+                let chunk = self.sender.pending().len().min(XMODEM_CHUNK);
+                out.extend_from_slice(&self.sender.pending()[..chunk]);
+                self.now_ms += 10;
+                self.sender.sent(chunk, self.now_ms);
+            }
+            out
+        }
+    }
+
+    // The block's number and data, checked
+    fn block(frame: &[u8]) -> (u8, &[u8]) {
+        let len = match frame[0] {
+            SOH => 128,
+            STX => 1_024,
+            other => panic!("not a block: {:#04x}", other),
+        };
+        assert_eq!(frame.len(), 3 + len + 2);
+        assert_eq!(frame[1], !frame[2]);
+        let data = &frame[3..3 + len];
+        assert_eq!(crc16_xmodem(data).to_be_bytes(), frame[3 + len..]);
+        (frame[1], data)
+    }
+
+    #[test]
+    fn sends_a_file_over_xmodem() {
+        let mut link = Link::new(Protocol::Xmodem, 300);
+        assert_eq!(link.send(NAK), []);
+        let mut received = std::vec::Vec::new();
+        let mut frame = link.send(CRC_MODE);
+        for number in 1..=3 {
+            let (got, data) = block(&frame);
+            assert_eq!(got, number);
+            received.extend_from_slice(data);
+            frame = link.send(ACK);
+        }
+        assert_eq!(frame, [EOT]);
+        assert_eq!(link.sender.finished(), None);
+        assert_eq!(link.send(ACK), []);
+        assert_eq!(link.sender.finished(), Some(Outcome::Sent));
+
+        assert_eq!(received[..300], link.file[..]);
+        assert!(received[300..].iter().all(|byte| *byte == PAD));
+        assert_eq!(link.sender.progress(), (300, 300));
+    }
+
+    #[test]
+    fn sends_the_name_and_size_over_ymodem() {
+        let mut link = Link::new(Protocol::Ymodem, 2_000);
+        let header = link.send(CRC_MODE);
+        let (number, data) = block(&header);
+        assert_eq!(number, 0);
+        assert!(data.starts_with(b"events.txt\x002000\x00"));
+        assert_eq!(link.send(ACK), []);
+
+        let first = link.send(CRC_MODE);
+        assert_eq!(block(&first).0, 1);
+        // A NAK gets the same block again
+        assert_eq!(link.send(NAK), first);
+        let second = link.send(ACK);
+        let (number, data) = block(&second);
+        assert_eq!(number, 2);
+        assert_eq!(data[..976], link.file[1_024..]);
+        assert_eq!(link.send(ACK), [EOT]);
+        assert_eq!(link.send(NAK), [EOT]);
+        assert_eq!(link.send(ACK), []);
+
+        let trailer = link.send(CRC_MODE);
+        let (number, data) = block(&trailer);
+        assert_eq!(number, 0);
+        assert!(data.iter().all(|byte| *byte == 0));
+        link.send(ACK);
+        assert_eq!(link.sender.finished(), Some(Outcome::Sent));
+    }
+
+    #[test]
+    fn the_receiver_cancels_with_two_cans() {
+        let mut link = Link::new(Protocol::Xmodem, 300);
+        link.send(CRC_MODE);
+        // Not one on its own, line noise can make that
+        link.send(CAN);
+        assert_eq!(block(&link.send(NAK)).0, 1);
+        link.send(CAN);
+        assert_eq!(link.send(CAN), []);
+        assert_eq!(link.sender.finished(), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn times_out_and_gives_up() {
+        let mut link = Link::new(Protocol::Ymodem, 300);
+        link.sender.poll(START_TIMEOUT_MS - 1);
+        assert_eq!(link.drain(), []);
+        link.sender.poll(START_TIMEOUT_MS);
+        assert_eq!(link.drain(), CANCEL);
+        assert_eq!(link.sender.finished(), Some(Outcome::TimedOut));
+
+        // A block is sent again on a timeout, and on every NAK, up to a point
+        let mut link = Link::new(Protocol::Xmodem, 300);
+        let frame = link.send(CRC_MODE);
+        link.now_ms += ANSWER_TIMEOUT_MS;
+        link.sender.poll(link.now_ms);
+        assert_eq!(link.drain(), frame);
+        for _ in 1..MAX_RETRIES {
+            assert_eq!(link.send(NAK), frame);
+        }
+        assert_eq!(link.send(NAK), CANCEL);
+        assert_eq!(link.sender.finished(), Some(Outcome::Failed));
+    }
+}
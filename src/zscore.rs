@@ -0,0 +1,115 @@
+use crate::math;
+
+// Running statistics adapt the anomaly limits to each machine.
+// The first samples are exact (Welford), past `window` samples the older
+// ones fade out exponentially so the statistics follow the machine's
+// normal state over time.
+pub const Z_WINDOW: u32 = 7_200;
+// No sample is flagged before a minute of statistics, at 500 ms
+pub const Z_MIN_SAMPLES: u32 = 120;
+
+// A sample this many standard deviations away from the mean is an anomaly
+pub const MECHANICAL_Z_LIMIT: f32 = 6.0;
+pub const THERMAL_Z_LIMIT: f32 = 4.0;
+
+// A completely still machine has almost no variance, and every bit of noise
+// would be a huge z-score. The standard deviation never goes below these,
+// in m/s^2 and ºC.
+pub const MECHANICAL_SIGMA_FLOOR: f32 = 0.05;
+pub const THERMAL_SIGMA_FLOOR: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunningStats {
+    window: u32,
+    sigma_floor: f32,
+    count: u32,
+    mean: f32,
+    variance: f32,
+}
+
+impl RunningStats {
+    pub const fn new(window: u32, sigma_floor: f32) -> Self {
+        Self {
+            window,
+            sigma_floor,
+            count: 0,
+            mean: 0.0,
+            variance: 0.0,
+        }
+    }
+
+    // Returns the z-score of the sample against the statistics before it,
+    // None while they're still warming up
+    pub fn push(&mut self, value: f32) -> Option<f32> {
+        let z = self.z_score(value);
+
+        self.count = self.count.saturating_add(1);
+        let n = self.count.min(self.window).max(1) as f32;
+        let delta = value - self.mean;
+        self.mean += delta / n;
+        self.variance += (delta * (value - self.mean) - self.variance) / n;
+
+        z
+    }
+
+    pub fn z_score(&self, value: f32) -> Option<f32> {
+        if self.count < Z_MIN_SAMPLES {
+            return None;
+        }
+
+        Some((value - self.mean) / self.std_dev())
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    // Never below the floor
+    pub fn std_dev(&self) -> f32 {
+        math::sqrt(self.variance.max(0.0)).max(self.sigma_floor)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_population_statistics() {
+        let mut stats = RunningStats::new(Z_WINDOW, 0.0);
+
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            stats.push(value);
+        }
+        assert!((stats.mean() - 5.0).abs() < 1e-6);
+        assert!((stats.std_dev() - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn outlier_is_flagged_after_warm_up() {
+        let mut stats = RunningStats::new(Z_WINDOW, MECHANICAL_SIGMA_FLOOR);
+
+        for i in 0..Z_MIN_SAMPLES {
+            let noise = if i % 2 == 0 { 0.1 } else { -0.1 };
+            assert_eq!(stats.push(9.81 + noise), None);
+        }
+        let z = stats.z_score(9.81 + 1.0).unwrap();
+        assert!((z - 10.0).abs() < 0.01, "z {}", z);
+    }
+
+    #[test]
+    fn still_machine_uses_the_sigma_floor() {
+        let mut stats = RunningStats::new(Z_WINDOW, MECHANICAL_SIGMA_FLOOR);
+
+        for _ in 0..Z_MIN_SAMPLES {
+            stats.push(9.81);
+        }
+        assert_eq!(stats.std_dev(), MECHANICAL_SIGMA_FLOOR);
+        // A few mg of noise is nowhere near an anomaly
+        assert!(stats.z_score(9.83).unwrap() < 1.0);
+    }
+}
@@ -0,0 +1,157 @@
+// Whole sequences through `MaintenanceMonitor::update()`, checked for
+// exactly which samples raise which alert. The samples are the firmware's
+// status samples, SAMPLE_PERIOD_MS apart, after a boot calibration at rest.
+// A change in the detection that moves any of these is a change in what
+// the machine's operator sees, and has to be made on purpose.
+
+use core::f32::consts::PI;
+
+use rs_esp32_simple_preventive_maintenance_example::{
+    vibration::VIBRATION_PERIOD_MS, Limit, MaintenanceMonitor, Reading, Severity, Thresholds,
+    SAMPLE_PERIOD_MS,
+};
+use Limit::*;
+use Severity::*;
+
+const GRAVITY: f32 = 9.81;
+const STILL: [f32; 3] = [0.0; 3];
+const ROOM_TEMP: f32 = 25.0;
+
+// Samples a minute
+const MINUTE: usize = (60_000 / SAMPLE_PERIOD_MS) as usize;
+
+// The X axis of the accelerometer, m/s^2 over the calibrated rest
+const STEP_WARNING: [f32; 20] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, //
+    0.7, 0.7, 0.7, 0.7, 0.7, 0.7, 0.7, 0.7, 0.7, 0.7,
+];
+const STEP_CRITICAL: [f32; 20] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, //
+    1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5,
+];
+const SPIKE: [f32; 20] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, //
+    3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+];
+
+type Expected = &'static [(usize, Limit, Severity)];
+
+// The median filter and the confirmations take the first samples of a
+// step, the harder one also jerks on its way up
+const STEP_WARNING_ALERTS: Expected = &[(13, Mechanical, Warning)];
+const STEP_CRITICAL_ALERTS: Expected = &[(12, Jerk, Warning), (13, Mechanical, Critical)];
+// 0.24 m/s^2 a minute along X tilts the machine instead
+const DRIFT_ALERTS: Expected = &[(490, Orientation, Warning), (916, Orientation, Critical)];
+// The RMS needs its window full, the velocity gets there first
+const VIBRATION_WARNING_ALERTS: Expected = &[(2, Velocity, Warning), (6, Vibration, Warning)];
+const VIBRATION_CRITICAL_ALERTS: Expected = &[
+    (1, Mechanical, Critical),
+    (2, Velocity, Critical),
+    (6, Vibration, Critical),
+];
+// The rate is known after a minute, the ceiling's critical level at 80 ºC
+const RAMP_WARNING_ALERTS: Expected = &[(120, Temperature, Warning), (3300, Temperature, Critical)];
+const RAMP_CRITICAL_ALERTS: Expected = &[(120, Temperature, Critical)];
+
+// Calibrated at rest, like after a cold boot
+fn monitor() -> MaintenanceMonitor {
+    let mut monitor = MaintenanceMonitor::new(Thresholds::default(), SAMPLE_PERIOD_MS);
+    monitor.set_reference([0.0, 0.0, GRAVITY], STILL, ROOM_TEMP);
+    monitor
+}
+
+fn at(i: usize, acc: [f32; 3], temp: f32) -> Reading {
+    Reading::new(acc, STILL, temp, i as u64 * SAMPLE_PERIOD_MS as u64)
+}
+
+fn along_x(x: impl IntoIterator<Item = f32>) -> impl Iterator<Item = Reading> {
+    x.into_iter()
+        .enumerate()
+        .map(|(i, x)| at(i, [x, 0.0, GRAVITY], ROOM_TEMP))
+}
+
+// The machine at rest, warming up by `per_minute` ºC a minute
+fn ramp(per_minute: f32, samples: usize) -> impl Iterator<Item = Reading> {
+    (0..samples).map(move |i| {
+        let temp = ROOM_TEMP + i as f32 * per_minute / MINUTE as f32;
+        at(i, [0.0, 0.0, GRAVITY], temp)
+    })
+}
+
+// Every alert of the sequence, by the index of the sample that raised it
+fn alerts(
+    monitor: &mut MaintenanceMonitor,
+    samples: impl IntoIterator<Item = Reading>,
+) -> Vec<(usize, Limit, Severity)> {
+    samples
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, reading)| {
+            let alert = monitor.update(&reading)?;
+            Some((i, alert.limit, alert.severity))
+        })
+        .collect()
+}
+
+// A 10 Hz shake along Z, `peak` m/s^2, read every VIBRATION_PERIOD_MS
+// between the status samples like the vibration task does
+fn vibration_alerts(peak: f32, samples: usize) -> Vec<(usize, Limit, Severity)> {
+    let mut monitor = monitor();
+    let reads = (SAMPLE_PERIOD_MS / VIBRATION_PERIOD_MS) as usize;
+    let mut alerts = Vec::new();
+    for i in 0..samples {
+        let mut acc = [0.0, 0.0, GRAVITY];
+        for read in 0..reads {
+            let t = (i * reads + read) as f32 * VIBRATION_PERIOD_MS as f32 / 1000.0;
+            acc[2] = GRAVITY + peak * libm::sinf(2.0 * PI * 10.0 * t);
+            monitor.push_vibration(acc);
+        }
+        if let Some(alert) = monitor.update(&at(i, acc, ROOM_TEMP)) {
+            alerts.push((i, alert.limit, alert.severity));
+        }
+    }
+    alerts
+}
+
+#[test]
+fn step_change_alarms_once_confirmed() {
+    assert_eq!(
+        alerts(&mut monitor(), along_x(STEP_WARNING)),
+        STEP_WARNING_ALERTS
+    );
+    assert_eq!(
+        alerts(&mut monitor(), along_x(STEP_CRITICAL)),
+        STEP_CRITICAL_ALERTS
+    );
+}
+
+#[test]
+fn single_spike_raises_nothing() {
+    let mut monitor = monitor();
+    assert_eq!(alerts(&mut monitor, along_x(SPIKE)), []);
+    assert!(!monitor.any_latched());
+}
+
+#[test]
+fn slow_drift_is_only_a_tilt() {
+    let drift = (0..10 * MINUTE).map(|i| i as f32 * 0.002);
+    assert_eq!(alerts(&mut monitor(), along_x(drift)), DRIFT_ALERTS);
+}
+
+#[test]
+fn sustained_vibration_alarms_on_its_rms() {
+    assert_eq!(vibration_alerts(0.5, 40), VIBRATION_WARNING_ALERTS);
+    assert_eq!(vibration_alerts(1.0, 40), VIBRATION_CRITICAL_ALERTS);
+}
+
+#[test]
+fn temperature_ramp_trips_the_rate_then_the_ceiling() {
+    assert_eq!(
+        alerts(&mut monitor(), ramp(2.0, 30 * MINUTE)),
+        RAMP_WARNING_ALERTS
+    );
+    assert_eq!(
+        alerts(&mut monitor(), ramp(3.0, 5 * MINUTE)),
+        RAMP_CRITICAL_ALERTS
+    );
+}